@@ -0,0 +1,528 @@
+//! Browsing archives like directories, using virtual paths such as `/home/me/x.zip!/docs/`.
+
+use super::{
+    open_archive, sanitize_entry_path, ArchiveEntry, ArchiveEntryKind, ArchiveError, ArchiveFormat,
+};
+use crate::error_handling::{Error, ErrorCode};
+use crate::log_error;
+use crate::models::{
+    format_system_time, permission_string_from_mode, Directory, Entries, File,
+};
+use crate::state::archive_listing_data::ArchiveListingState;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Separates the path of the archive file from the path inside the archive.
+pub const ARCHIVE_PATH_SEPARATOR: &str = "!/";
+
+/// A path pointing into an archive, e.g. `/home/me/x.zip!/docs/readme.md`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ArchivePath {
+    /// The archive file as given in the path.
+    pub archive: String,
+    /// Path inside the archive with `/` separators, empty for the root of the archive.
+    pub inner: String,
+}
+
+impl ArchivePath {
+    /// Splits a virtual path at the first `!/` which follows a supported archive file.
+    /// Returns `None` for ordinary paths.
+    pub fn parse(path: &str) -> Option<ArchivePath> {
+        let mut search_from = 0;
+        loop {
+            let (archive, rest) = match path[search_from..].find(ARCHIVE_PATH_SEPARATOR) {
+                Some(index) => {
+                    let index = search_from + index;
+                    (
+                        &path[..index],
+                        Some(&path[index + ARCHIVE_PATH_SEPARATOR.len()..]),
+                    )
+                }
+                // `x.zip!` is the root of the archive as well
+                None if path.ends_with('!') && search_from < path.len() => {
+                    (&path[..path.len() - 1], None)
+                }
+                None => return None,
+            };
+
+            let archive_file = Path::new(archive);
+            if ArchiveFormat::from_path(archive_file).is_some() && archive_file.is_file() {
+                let inner = rest
+                    .unwrap_or("")
+                    .replace('\\', "/")
+                    .split('/')
+                    .filter(|part| !part.is_empty() && *part != ".")
+                    .collect::<Vec<_>>()
+                    .join("/");
+                return Some(ArchivePath {
+                    archive: archive.to_string(),
+                    inner,
+                });
+            }
+            match rest {
+                Some(_) => search_from = archive.len() + ARCHIVE_PATH_SEPARATOR.len(),
+                None => return None,
+            }
+        }
+    }
+
+    /// The virtual path of an entry inside the same archive.
+    pub fn virtual_path_of(&self, inner: &str) -> String {
+        format!("{}{}{}", self.archive, ARCHIVE_PATH_SEPARATOR, inner)
+    }
+
+    pub fn name(&self) -> String {
+        match self.inner.rsplit('/').next() {
+            Some(name) if !name.is_empty() => name.to_string(),
+            _ => Path::new(&self.archive)
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+fn lock_error() -> String {
+    Error::new(
+        ErrorCode::InternalError,
+        "Failed to acquire lock on archive listings".to_string(),
+    )
+    .to_json()
+}
+
+/// Returns the entries of an archive, read from the cache if the archive did not change.
+pub fn cached_entries(
+    archive: &Path,
+    listing_state: &Arc<Mutex<ArchiveListingState>>,
+) -> Result<Vec<ArchiveEntry>, String> {
+    let metadata = fs::metadata(archive).map_err(|e| {
+        log_error!("Archive does not exist: {}: {}", archive.display(), e);
+        Error::new(
+            ErrorCode::ResourceNotFound,
+            format!("Archive does not exist: {}", archive.display()),
+        )
+        .to_json()
+    })?;
+    let modified = metadata.modified().ok();
+
+    if let Some(entries) = listing_state
+        .lock()
+        .map_err(|_| lock_error())?
+        .get(archive, metadata.len(), modified)
+    {
+        return Ok(entries);
+    }
+
+    let entries = open_archive(archive, None)
+        .and_then(|mut reader| reader.entries())
+        .map_err(ArchiveError::into_json)?;
+
+    listing_state.lock().map_err(|_| lock_error())?.insert(
+        archive.to_path_buf(),
+        metadata.len(),
+        modified,
+        entries.clone(),
+    );
+    Ok(entries)
+}
+
+/// A file or directory in the tree of an archive. Directories without an entry of their
+/// own, which is common in zip files, only exist as parents of other entries.
+#[derive(Debug, Clone, Default)]
+pub struct ArchiveNode {
+    pub entry: Option<ArchiveEntry>,
+    pub is_dir: bool,
+}
+
+/// The children of every directory in an archive, keyed by the directory path ("" is the root).
+pub struct ArchiveTree {
+    children: HashMap<String, BTreeMap<String, ArchiveNode>>,
+}
+
+impl ArchiveTree {
+    pub fn new(entries: &[ArchiveEntry]) -> Self {
+        let mut children: HashMap<String, BTreeMap<String, ArchiveNode>> = HashMap::new();
+        children.insert(String::new(), BTreeMap::new());
+
+        for entry in entries {
+            // Entries which would end up outside of the archive are not shown
+            let Some(sanitized) = sanitize_entry_path(&entry.name) else {
+                continue;
+            };
+            let parts: Vec<String> = sanitized
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().to_string())
+                .collect();
+
+            let mut parent = String::new();
+            for (i, part) in parts.iter().enumerate() {
+                let node = children
+                    .entry(parent.clone())
+                    .or_default()
+                    .entry(part.clone())
+                    .or_default();
+                if i == parts.len() - 1 {
+                    node.is_dir = node.is_dir || entry.kind == ArchiveEntryKind::Directory;
+                    node.entry = Some(entry.clone());
+                } else {
+                    node.is_dir = true;
+                }
+                let is_dir = node.is_dir;
+
+                let path = join_inner(&parent, part);
+                if is_dir {
+                    children.entry(path.clone()).or_default();
+                }
+                parent = path;
+            }
+        }
+        Self { children }
+    }
+
+    /// The direct children of a directory, `None` if it is no directory in the archive.
+    pub fn children_of(&self, directory: &str) -> Option<&BTreeMap<String, ArchiveNode>> {
+        self.children.get(directory)
+    }
+
+    /// The node at a path, `None` for the root and for paths which don't exist.
+    pub fn node(&self, path: &str) -> Option<&ArchiveNode> {
+        let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+        self.children.get(parent)?.get(name)
+    }
+}
+
+fn join_inner(parent: &str, name: &str) -> String {
+    if parent.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", parent, name)
+    }
+}
+
+fn format_entry_time(entry: Option<&ArchiveEntry>) -> String {
+    entry
+        .and_then(|entry| entry.modified)
+        .map_or("1970-01-01 00:00:00".to_string(), format_system_time)
+}
+
+/// Lists a directory inside an archive with the same model `open_directory` returns.
+/// Paths of the returned entries are virtual paths again, so they can be opened directly.
+///
+/// # Arguments
+/// * `path` - The parsed virtual path of the directory.
+/// * `listing_state` - The cached archive listings.
+///
+/// # Returns
+/// * `Ok(Entries)` - The directories and files directly inside the directory.
+/// * `Err(String)` - If the archive can't be read or the directory does not exist in it.
+pub fn list_archive_directory(
+    path: &ArchivePath,
+    listing_state: &Arc<Mutex<ArchiveListingState>>,
+) -> Result<Entries, String> {
+    let entries = cached_entries(Path::new(&path.archive), listing_state)?;
+    let tree = ArchiveTree::new(&entries);
+
+    let Some(children) = tree.children_of(&path.inner) else {
+        let is_file = tree.node(&path.inner).is_some_and(|node| !node.is_dir);
+        let (code, message) = if is_file {
+            (ErrorCode::InvalidInput, "Path is not a directory")
+        } else {
+            (ErrorCode::ResourceNotFound, "Directory does not exist")
+        };
+        let full_path = path.virtual_path_of(&path.inner);
+        log_error!("{}: {}", message, full_path);
+        return Err(Error::new(code, format!("{}: {}", message, full_path)).to_json());
+    };
+
+    let mut directories = Vec::new();
+    let mut files = Vec::new();
+    for (name, node) in children {
+        let inner = join_inner(&path.inner, name);
+        let entry = node.entry.as_ref();
+        let time = format_entry_time(entry);
+
+        if node.is_dir {
+            let mode = entry.and_then(|e| e.mode).unwrap_or(0o755);
+            let (sub_file_count, sub_dir_count) = tree
+                .children_of(&inner)
+                .map(|sub| {
+                    let dirs = sub.values().filter(|n| n.is_dir).count();
+                    (sub.len() - dirs, dirs)
+                })
+                .unwrap_or((0, 0));
+            directories.push(Directory {
+                name: name.clone(),
+                path: path.virtual_path_of(&inner),
+                is_symlink: false,
+                access_rights_as_string: permission_string_from_mode(mode),
+                access_rights_as_number: mode,
+                size_in_bytes: 0,
+                sub_file_count,
+                sub_dir_count,
+                created: time.clone(),
+                last_modified: time.clone(),
+                accessed: time,
+                owner_id: None,
+                group_id: None,
+                symlink_target: None,
+            });
+        } else {
+            let mode = entry.and_then(|e| e.mode).unwrap_or(0o644);
+            files.push(File {
+                name: name.clone(),
+                path: path.virtual_path_of(&inner),
+                is_symlink: entry.is_some_and(|e| e.kind == ArchiveEntryKind::Symlink),
+                access_rights_as_string: permission_string_from_mode(mode),
+                access_rights_as_number: mode,
+                size_in_bytes: entry.map(|e| e.size).unwrap_or(0),
+                created: time.clone(),
+                last_modified: time.clone(),
+                accessed: time,
+                owner_id: None,
+                group_id: None,
+                symlink_target: None,
+            });
+        }
+    }
+    Ok(Entries { directories, files })
+}
+
+/// Calls `visit` with the content of a file inside an archive.
+fn visit_archive_file(
+    path: &ArchivePath,
+    visit: &mut dyn FnMut(&ArchiveEntry, &mut dyn Read) -> Result<(), ArchiveError>,
+) -> Result<ArchiveEntry, String> {
+    let mut reader =
+        open_archive(Path::new(&path.archive), None).map_err(ArchiveError::into_json)?;
+    let mut found = None;
+    reader
+        .for_each_entry(&mut |entry, content| {
+            let matches = sanitize_entry_path(&entry.name)
+                .is_some_and(|p| p.to_string_lossy().replace('\\', "/") == path.inner);
+            if !matches || entry.kind == ArchiveEntryKind::Directory {
+                return Ok(true);
+            }
+            visit(entry, content)?;
+            found = Some(entry.clone());
+            Ok(false)
+        })
+        .map_err(ArchiveError::into_json)?;
+
+    found.ok_or_else(|| {
+        let full_path = path.virtual_path_of(&path.inner);
+        log_error!("File does not exist: {}", full_path);
+        Error::new(
+            ErrorCode::ResourceNotFound,
+            format!("File does not exist: {}", full_path),
+        )
+        .to_json()
+    })
+}
+
+/// Reads the start of a file inside an archive.
+///
+/// # Arguments
+/// * `path` - The parsed virtual path of the file.
+/// * `max_bytes` - How many bytes of the content are read at most.
+///
+/// # Returns
+/// * `Ok((ArchiveEntry, Vec<u8>))` - The entry and the first `max_bytes` of its content.
+/// * `Err(String)` - If the archive can't be read or the file does not exist in it.
+pub fn read_archive_file(
+    path: &ArchivePath,
+    max_bytes: u64,
+) -> Result<(ArchiveEntry, Vec<u8>), String> {
+    let mut data = Vec::new();
+    let entry = visit_archive_file(path, &mut |_, content| {
+        content
+            .take(max_bytes)
+            .read_to_end(&mut data)
+            .map(|_| ())
+            .map_err(|e| super::internal_error(format!("Failed to read archive entry: {}", e)))
+    })?;
+    Ok((entry, data))
+}
+
+/// Streams a file inside an archive into a new file at `target`.
+///
+/// # Arguments
+/// * `path` - The parsed virtual path of the file.
+/// * `target` - Path of the file to create, an existing file is overwritten.
+/// * `max_bytes` - The largest file which is extracted. The size stored in the archive is
+///   not trusted, the copy stops as soon as more content follows.
+///
+/// # Returns
+/// * `Ok(ArchiveEntry)` - The extracted entry.
+/// * `Err(String)` - If the archive can't be read, the file does not exist in it or is larger
+///   than `max_bytes`. A partial copy is removed again.
+pub fn copy_archive_file(
+    path: &ArchivePath,
+    target: &Path,
+    max_bytes: u64,
+) -> Result<ArchiveEntry, String> {
+    let result = visit_archive_file(path, &mut |entry, content| {
+        let too_large = || {
+            super::archive_error(
+                ErrorCode::InvalidInput,
+                format!(
+                    "'{}' is larger than {} bytes and can't be extracted",
+                    entry.name, max_bytes
+                ),
+            )
+        };
+        if entry.size > max_bytes {
+            return Err(too_large());
+        }
+        let mut file = fs::File::create(target).map_err(|e| {
+            super::internal_error(format!(
+                "Failed to create file '{}': {}",
+                target.display(),
+                e
+            ))
+        })?;
+        let copied = std::io::copy(&mut content.take(max_bytes + 1), &mut file).map_err(|e| {
+            super::internal_error(format!(
+                "Failed to write file '{}': {}",
+                target.display(),
+                e
+            ))
+        })?;
+        if copied > max_bytes {
+            return Err(too_large());
+        }
+        Ok(())
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(target);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests_archive_browse {
+    use super::*;
+    use std::io::Write;
+    use tempfile::tempdir;
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    /// A zip without directory entries, like many tools create them.
+    fn create_zip(path: &Path) {
+        let mut zip = ZipWriter::new(fs::File::create(path).unwrap());
+        for (name, content) in [
+            ("readme.md", "# Readme"),
+            ("docs/guide.txt", "guide"),
+            ("docs/api/index.html", "<html></html>"),
+            ("../evil.txt", "evil"),
+        ] {
+            zip.start_file::<_, ()>(name, FileOptions::default())
+                .unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn parse_archive_path_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let zip_path = temp_dir.path().join("x.zip");
+        create_zip(&zip_path);
+        let zip = zip_path.to_string_lossy().to_string();
+
+        let parsed = ArchivePath::parse(&format!("{}!/docs/", zip)).unwrap();
+        assert_eq!(parsed.archive, zip);
+        assert_eq!(parsed.inner, "docs");
+        assert_eq!(parsed.name(), "docs");
+        assert_eq!(ArchivePath::parse(&format!("{}!", zip)).unwrap().inner, "");
+        assert_eq!(
+            ArchivePath::parse(&format!("{}!/", zip)).unwrap().name(),
+            "x.zip"
+        );
+
+        // Ordinary paths and paths into files which are no archives
+        assert!(ArchivePath::parse(&zip).is_none());
+        assert!(ArchivePath::parse("/does/not/exist.zip!/docs").is_none());
+        assert!(ArchivePath::parse("/home/me/Hello!/world.txt").is_none());
+    }
+
+    #[test]
+    fn list_archive_directory_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let zip_path = temp_dir.path().join("x.zip");
+        create_zip(&zip_path);
+        let zip = zip_path.to_string_lossy().to_string();
+
+        let listing_state = Arc::new(Mutex::new(ArchiveListingState::new()));
+
+        let root = list_archive_directory(
+            &ArchivePath::parse(&format!("{}!/", zip)).unwrap(),
+            &listing_state,
+        )
+        .expect("Failed to list archive root");
+        assert_eq!(root.files.len(), 1, "../evil.txt must not be listed");
+        assert_eq!(root.files[0].name, "readme.md");
+        assert_eq!(root.files[0].size_in_bytes, 8);
+        assert_eq!(root.directories.len(), 1);
+        let docs = &root.directories[0];
+        assert_eq!(docs.name, "docs");
+        assert_eq!(docs.path, format!("{}!/docs", zip));
+        assert_eq!((docs.sub_file_count, docs.sub_dir_count), (1, 1));
+
+        let api =
+            list_archive_directory(&ArchivePath::parse(&docs.path).unwrap(), &listing_state)
+                .unwrap();
+        assert_eq!(api.directories[0].path, format!("{}!/docs/api", zip));
+        assert_eq!(api.files[0].name, "guide.txt");
+
+        let missing = list_archive_directory(
+            &ArchivePath::parse(&format!("{}!/nope", zip)).unwrap(),
+            &listing_state,
+        );
+        assert!(missing.unwrap_err().contains("ResourceNotFound"));
+        let file = list_archive_directory(
+            &ArchivePath::parse(&format!("{}!/readme.md", zip)).unwrap(),
+            &listing_state,
+        );
+        assert!(file.unwrap_err().contains("InvalidInput"));
+    }
+
+    #[test]
+    fn read_archive_file_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let zip_path = temp_dir.path().join("x.zip");
+        create_zip(&zip_path);
+        let zip = zip_path.to_string_lossy().to_string();
+
+        let path = ArchivePath::parse(&format!("{}!/docs/guide.txt", zip)).unwrap();
+        let (entry, data) = read_archive_file(&path, 3).unwrap();
+        assert_eq!(entry.size, 5);
+        assert_eq!(data, b"gui");
+
+        let path = ArchivePath::parse(&format!("{}!/docs/missing.txt", zip)).unwrap();
+        assert!(read_archive_file(&path, 3)
+            .unwrap_err()
+            .contains("ResourceNotFound"));
+    }
+
+    #[test]
+    fn copy_archive_file_limit_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let zip_path = temp_dir.path().join("x.zip");
+        create_zip(&zip_path);
+        let zip = zip_path.to_string_lossy().to_string();
+        let path = ArchivePath::parse(&format!("{}!/docs/guide.txt", zip)).unwrap();
+        let target = temp_dir.path().join("guide.txt");
+
+        let entry = copy_archive_file(&path, &target, 5).unwrap();
+        assert_eq!(entry.size, 5);
+        assert_eq!(fs::read_to_string(&target).unwrap(), "guide");
+
+        assert!(copy_archive_file(&path, &target, 4)
+            .unwrap_err()
+            .contains("InvalidInput"));
+        assert!(!target.exists());
+    }
+}
@@ -0,0 +1,894 @@
+//! # Archive formats
+//!
+//! Reading and writing of the archive formats supported by the file explorer behind one
+//! interface, so the archive commands don't have to care about the format of a file.
+//!
+//! | Format                                     | Read | Write |
+//! |--------------------------------------------|------|-------|
+//! | zip                                        | yes  | yes   |
+//! | tar, tar.gz, tar.bz2, tar.xz, tar.zst      | yes  | yes   |
+//! | 7z                                         | yes  | no    |
+//!
+//! Readers hand out entries one by one together with a reader for their content, which is
+//! the only access pattern all formats support: tar streams can't seek and solid 7z archives
+//! have to be decompressed in order.
+
+pub mod browse;
+pub mod safety;
+mod sevenz_format;
+mod tar_format;
+mod zip_format;
+
+pub use zip_format::ZipCompressionMethod;
+
+use crate::error_handling::{Error, ErrorCode};
+use crate::log_error;
+use crate::models::archive_extraction_config::ArchiveExtractionConfig;
+use crate::state::jobs_data::JobHandle;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+
+/// All supported archive formats.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    Tar,
+    TarGz,
+    TarBz2,
+    TarXz,
+    TarZst,
+    SevenZ,
+}
+
+/// File name suffixes of each format, the first one is used for new archives.
+const FORMAT_SUFFIXES: &[(ArchiveFormat, &[&str])] = &[
+    (ArchiveFormat::TarGz, &[".tar.gz", ".tgz"]),
+    (ArchiveFormat::TarBz2, &[".tar.bz2", ".tbz2", ".tbz"]),
+    (ArchiveFormat::TarXz, &[".tar.xz", ".txz"]),
+    (ArchiveFormat::TarZst, &[".tar.zst", ".tzst"]),
+    (ArchiveFormat::Tar, &[".tar"]),
+    (ArchiveFormat::Zip, &[".zip"]),
+    (ArchiveFormat::SevenZ, &[".7z"]),
+];
+
+impl ArchiveFormat {
+    /// Detects the format from the file name, e.g. `backup.tar.zst` is `TarZst`.
+    pub fn from_path(path: &Path) -> Option<ArchiveFormat> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        FORMAT_SUFFIXES
+            .iter()
+            .find(|(_, suffixes)| suffixes.iter().any(|suffix| name.ends_with(suffix)))
+            .map(|(format, _)| *format)
+    }
+
+    pub fn extension(self) -> &'static str {
+        FORMAT_SUFFIXES
+            .iter()
+            .find(|(format, _)| *format == self)
+            .map(|(_, suffixes)| suffixes[0])
+            .unwrap_or(".zip")
+    }
+
+    pub fn supports_writing(self) -> bool {
+        self != ArchiveFormat::SevenZ
+    }
+
+    /// Returns the file name without the archive suffix, e.g. `backup` for `backup.tar.gz`.
+    pub fn strip_extension(self, file_name: &str) -> String {
+        let lower = file_name.to_lowercase();
+        FORMAT_SUFFIXES
+            .iter()
+            .filter(|(format, _)| *format == self)
+            .flat_map(|(_, suffixes)| suffixes.iter())
+            .find(|suffix| lower.ends_with(*suffix) && lower.len() > suffix.len())
+            .map(|suffix| file_name[..file_name.len() - suffix.len()].to_string())
+            .unwrap_or_else(|| file_name.to_string())
+    }
+
+    /// Compression levels supported by the format and compression method, `None` if levels
+    /// can't be set.
+    fn level_range(
+        self,
+        zip_method: ZipCompressionMethod,
+    ) -> Option<std::ops::RangeInclusive<i64>> {
+        match self {
+            ArchiveFormat::Zip => zip_method.level_range(),
+            ArchiveFormat::TarGz | ArchiveFormat::TarXz => Some(0..=9),
+            ArchiveFormat::TarBz2 => Some(1..=9),
+            ArchiveFormat::TarZst => Some(-7..=22),
+            ArchiveFormat::Tar | ArchiveFormat::SevenZ => None,
+        }
+    }
+}
+
+/// Options for creating archives. Every field has a default, so the frontend
+/// only has to send the fields it wants to change.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default)]
+pub struct ArchiveCreationOptions {
+    /// Format of the new archive. If not set, it is taken from the destination file name,
+    /// falling back to zip.
+    pub format: Option<ArchiveFormat>,
+    /// Compression method of zip archives. Tar archives use the compression of their format.
+    pub compression_method: ZipCompressionMethod,
+    /// Compression level, `None` uses the default of the method.
+    pub compression_level: Option<i64>,
+    /// If set, all entries are encrypted with AES-256 using this password. Zip only.
+    pub password: Option<String>,
+    /// Store the content of symlink targets instead of the symlinks themselves.
+    pub follow_symlinks: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveEntryKind {
+    File,
+    Directory,
+    Symlink,
+}
+
+/// An entry of an archive, independent of the format.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ArchiveEntry {
+    /// Path inside the archive with `/` separators and without a trailing slash.
+    pub name: String,
+    pub kind: ArchiveEntryKind,
+    /// Uncompressed size of the content.
+    pub size: u64,
+    pub compressed_size: Option<u64>,
+    pub modified: Option<SystemTime>,
+    /// Unix permission bits, if the archive stores them.
+    pub mode: Option<u32>,
+    pub link_target: Option<String>,
+}
+
+pub enum ArchiveError {
+    Cancelled,
+    /// The error as json, see `error_handling::Error`.
+    Failed(String),
+}
+
+impl ArchiveError {
+    pub fn into_json(self) -> String {
+        match self {
+            ArchiveError::Cancelled => Error::new(
+                ErrorCode::InternalError,
+                "Archive operation was cancelled".to_string(),
+            )
+            .to_json(),
+            ArchiveError::Failed(err) => err,
+        }
+    }
+}
+
+pub(crate) fn archive_error(code: ErrorCode, message: String) -> ArchiveError {
+    log_error!("{}", message);
+    ArchiveError::Failed(Error::new(code, message).to_json())
+}
+
+pub(crate) fn internal_error(message: String) -> ArchiveError {
+    archive_error(ErrorCode::InternalError, message)
+}
+
+/// Callback for `ArchiveReader::for_each_entry`. Returning `false` stops the iteration.
+pub type EntryVisitor<'a> =
+    dyn FnMut(&ArchiveEntry, &mut dyn Read) -> Result<bool, ArchiveError> + 'a;
+
+pub trait ArchiveReader {
+    /// Lists all entries without reading their content where the format allows it.
+    fn entries(&mut self) -> Result<Vec<ArchiveEntry>, ArchiveError> {
+        let mut entries = Vec::new();
+        self.for_each_entry(&mut |entry, _| {
+            entries.push(entry.clone());
+            Ok(true)
+        })?;
+        Ok(entries)
+    }
+
+    /// Calls `visit` for every entry in archive order with a reader for its content.
+    fn for_each_entry(&mut self, visit: &mut EntryVisitor) -> Result<(), ArchiveError>;
+}
+
+pub trait ArchiveWriter {
+    fn add_directory(&mut self, name: &str, metadata: &fs::Metadata) -> Result<(), ArchiveError>;
+
+    fn add_file(
+        &mut self,
+        name: &str,
+        metadata: &fs::Metadata,
+        content: &mut dyn Read,
+    ) -> Result<(), ArchiveError>;
+
+    fn add_symlink(
+        &mut self,
+        name: &str,
+        target: &str,
+        metadata: &fs::Metadata,
+    ) -> Result<(), ArchiveError>;
+
+    /// Writes the end of the archive. Nothing may be added afterwards.
+    fn finish(self: Box<Self>) -> Result<(), ArchiveError>;
+}
+
+/// Opens an archive for reading, the format is detected from the file name.
+///
+/// # Arguments
+/// * `path` - The archive file.
+/// * `password` - Password for encrypted zip and 7z archives.
+pub fn open_archive(
+    path: &Path,
+    password: Option<&str>,
+) -> Result<Box<dyn ArchiveReader>, ArchiveError> {
+    let format = ArchiveFormat::from_path(path).ok_or_else(|| {
+        archive_error(
+            ErrorCode::InvalidInput,
+            format!("Unsupported archive format: {}", path.display()),
+        )
+    })?;
+    if !path.is_file() {
+        return Err(archive_error(
+            ErrorCode::ResourceNotFound,
+            format!("Archive does not exist: {}", path.display()),
+        ));
+    }
+
+    match format {
+        ArchiveFormat::Zip => Ok(Box::new(zip_format::ZipArchiveReader::open(
+            path, password,
+        )?)),
+        ArchiveFormat::SevenZ => Ok(Box::new(sevenz_format::SevenZArchiveReader::open(
+            path, password,
+        )?)),
+        tar_format => Ok(Box::new(tar_format::TarArchiveReader::new(
+            path, tar_format,
+        ))),
+    }
+}
+
+/// Checks that the compression level and password fit the format.
+pub fn validate_creation_options(
+    format: ArchiveFormat,
+    options: &ArchiveCreationOptions,
+) -> Result<(), String> {
+    if !format.supports_writing() {
+        log_error!("Creating {:?} archives is not supported", format);
+        return Err(Error::new(
+            ErrorCode::NotImplemented,
+            format!("Creating {:?} archives is not supported", format),
+        )
+        .to_json());
+    }
+    if let Some(level) = options.compression_level {
+        let valid = format
+            .level_range(options.compression_method)
+            .is_some_and(|range| range.contains(&level));
+        if !valid {
+            log_error!(
+                "Compression level {} is not supported for {:?}",
+                level,
+                format
+            );
+            return Err(Error::new(
+                ErrorCode::InvalidInput,
+                format!(
+                    "Compression level {} is not supported for {:?}",
+                    level, format
+                ),
+            )
+            .to_json());
+        }
+    }
+    match options.password.as_deref() {
+        Some("") => {
+            log_error!("Password for archive encryption must not be empty");
+            Err(Error::new(
+                ErrorCode::InvalidInput,
+                "Password for archive encryption must not be empty".to_string(),
+            )
+            .to_json())
+        }
+        Some(_) if format != ArchiveFormat::Zip => {
+            log_error!("Encryption is only supported for zip archives");
+            Err(Error::new(
+                ErrorCode::InvalidInput,
+                "Encryption is only supported for zip archives".to_string(),
+            )
+            .to_json())
+        }
+        _ => Ok(()),
+    }
+}
+
+fn create_archive_writer<'a>(
+    path: &Path,
+    format: ArchiveFormat,
+    options: &'a ArchiveCreationOptions,
+) -> Result<Box<dyn ArchiveWriter + 'a>, ArchiveError> {
+    let file = fs::File::create(path)
+        .map_err(|e| internal_error(format!("Failed to create archive file: {}", e)))?;
+    match format {
+        ArchiveFormat::Zip => Ok(Box::new(zip_format::ZipArchiveWriter::new(file, options))),
+        ArchiveFormat::SevenZ => Err(archive_error(
+            ErrorCode::NotImplemented,
+            "Creating 7z archives is not supported".to_string(),
+        )),
+        tar_format => Ok(Box::new(tar_format::TarArchiveWriter::new(
+            file,
+            tar_format,
+            options.compression_level,
+        )?)),
+    }
+}
+
+/// Wraps the content of a file which is added to an archive, reports the read bytes and
+/// stops the writing once the job is cancelled.
+struct ProgressReader<'a, R: Read> {
+    inner: R,
+    job: Option<&'a JobHandle>,
+}
+
+impl<R: Read> Read for ProgressReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if let Some(job) = self.job {
+            if job.is_cancelled() {
+                // Not `Interrupted`, `io::copy` would retry that forever
+                return Err(std::io::Error::other("Archive operation was cancelled"));
+            }
+        }
+        let read = self.inner.read(buf)?;
+        if let Some(job) = self.job {
+            job.update_progress(|p| p.bytes_processed += read as u64);
+        }
+        Ok(read)
+    }
+}
+
+fn is_cancelled(job: Option<&JobHandle>) -> bool {
+    job.is_some_and(|job| job.is_cancelled())
+}
+
+/// Counts the entries and bytes which will be written, used as totals for the progress.
+fn count_totals(source_paths: &[String], follow_symlinks: bool) -> (u64, u64) {
+    let mut items = 0;
+    let mut bytes = 0;
+    for source_path in source_paths {
+        for entry in walkdir::WalkDir::new(source_path)
+            .follow_links(follow_symlinks)
+            .into_iter()
+            .flatten()
+        {
+            items += 1;
+            if entry.file_type().is_file() {
+                bytes += entry.metadata().map(|m| m.len()).unwrap_or(0);
+            }
+        }
+    }
+    (items, bytes)
+}
+
+/// Writes all sources into a new archive. Directories are added recursively with their
+/// relative paths below the name of the source. Files are streamed, so memory usage does
+/// not depend on their size.
+pub fn write_archive(
+    source_paths: &[String],
+    archive_path: &Path,
+    format: ArchiveFormat,
+    options: &ArchiveCreationOptions,
+    job: Option<&JobHandle>,
+) -> Result<(), ArchiveError> {
+    if let Some(job) = job {
+        let (items_total, bytes_total) = count_totals(source_paths, options.follow_symlinks);
+        job.update_progress(|p| {
+            p.items_total = items_total;
+            p.bytes_total = bytes_total;
+        });
+    }
+
+    let mut writer = create_archive_writer(archive_path, format, options)?;
+
+    for source_path in source_paths {
+        let source = Path::new(source_path);
+        let base_name = source
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        for entry in walkdir::WalkDir::new(source).follow_links(options.follow_symlinks) {
+            if is_cancelled(job) {
+                return Err(ArchiveError::Cancelled);
+            }
+            let entry =
+                entry.map_err(|e| internal_error(format!("Error reading directory: {}", e)))?;
+            let path = entry.path();
+
+            let relative = path
+                .strip_prefix(source)
+                .map_err(|e| internal_error(format!("Error creating relative path: {}", e)))?;
+            let name = if relative.as_os_str().is_empty() {
+                base_name.clone()
+            } else {
+                format!(
+                    "{}/{}",
+                    base_name,
+                    relative.to_string_lossy().replace('\\', "/")
+                )
+            };
+
+            let metadata = if options.follow_symlinks {
+                fs::metadata(path)
+            } else {
+                fs::symlink_metadata(path)
+            }
+            .map_err(|e| internal_error(format!("Failed to get metadata: {}", e)))?;
+
+            if metadata.file_type().is_symlink() {
+                let target = fs::read_link(path)
+                    .map_err(|e| internal_error(format!("Failed to read symlink: {}", e)))?;
+                writer.add_symlink(&name, &target.to_string_lossy(), &metadata)?;
+            } else if metadata.is_dir() {
+                writer.add_directory(&name, &metadata)?;
+            } else if metadata.is_file() {
+                let file = fs::File::open(path)
+                    .map_err(|e| internal_error(format!("Error reading file: {}", e)))?;
+                let mut content = ProgressReader { inner: file, job };
+                let result = writer.add_file(&name, &metadata, &mut content);
+                if is_cancelled(job) {
+                    return Err(ArchiveError::Cancelled);
+                }
+                result?;
+            } else {
+                // Sockets, fifos and devices can't be stored in archives
+                continue;
+            }
+
+            if let Some(job) = job {
+                job.update_progress(|p| {
+                    p.items_processed += 1;
+                    p.current_path = Some(path.to_string_lossy().to_string());
+                });
+            }
+        }
+    }
+
+    writer.finish()
+}
+
+/// Turns the name of an archive entry into a relative path. Returns `None` for names which
+/// would end up outside of the extraction directory, like absolute paths or `../` components.
+pub fn sanitize_entry_path(name: &str) -> Option<PathBuf> {
+    let mut path = PathBuf::new();
+    for component in Path::new(&name.replace('\\', "/")).components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    if path.as_os_str().is_empty() {
+        None
+    } else {
+        Some(path)
+    }
+}
+
+/// Checks that a symlink extracted to `entry_path` (relative to the extraction directory)
+/// points to a path inside the extraction directory.
+pub fn symlink_stays_inside(entry_path: &Path, target: &str) -> bool {
+    let target = Path::new(target);
+    if target.has_root() {
+        return false;
+    }
+    let mut depth = entry_path.components().count() as i64 - 1;
+    for component in target.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            Component::ParentDir => {
+                depth -= 1;
+                if depth < 0 {
+                    return false;
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => return false,
+        }
+    }
+    true
+}
+
+/// Checks that `path` below the extraction directory stays inside of it once the symlinks
+/// which already exist on disk are resolved. `symlink_stays_inside` only looks at one link,
+/// links extracted before can be chained to leave the directory: `a/b -> ..` and
+/// `x -> a/b/..` both stay inside on their own, but `x/evil.txt` would not.
+///
+/// `destination` has to be canonical. The nearest existing ancestor of `path` is resolved,
+/// the missing components below it are created as plain directories.
+fn resolves_inside(destination: &Path, path: &Path) -> bool {
+    for ancestor in path.ancestors() {
+        if fs::symlink_metadata(ancestor).is_ok() {
+            // A dangling symlink can't be resolved, it must not be written through either
+            return fs::canonicalize(ancestor)
+                .is_ok_and(|resolved| resolved.starts_with(destination));
+        }
+    }
+    false
+}
+
+/// Sets the permissions and modification time stored in the archive on an extracted file.
+fn apply_entry_metadata(path: &Path, entry: &ArchiveEntry, file: &fs::File) {
+    if let Some(modified) = entry.modified {
+        let _ = file.set_modified(modified);
+    }
+    #[cfg(unix)]
+    if let Some(mode) = entry.mode {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = fs::set_permissions(path, fs::Permissions::from_mode(mode & 0o777));
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+/// Extracts entries of an archive into `destination`. Files which already exist are
+/// kept and the extracted file gets a unique name instead. Nothing is written through a
+/// symlink to a path outside of `destination`, entries which would are skipped.
+///
+/// # Arguments
+/// * `reader` - The opened archive.
+/// * `destination` - Existing directory the entries are extracted into.
+/// * `target_of` - Maps the sanitized path of an entry to its path below `destination`,
+///   entries for which it returns `None` are skipped.
+/// * `limits` - Limits for the extracted content. The listed entries should be checked with
+///   `safety::check_entries` first, this only enforces them on the actual content.
+/// * `job` - Optional job which receives the progress and can cancel the extraction.
+pub fn extract_entries(
+    reader: &mut dyn ArchiveReader,
+    destination: &Path,
+    target_of: &dyn Fn(&Path) -> Option<PathBuf>,
+    limits: &ArchiveExtractionConfig,
+    job: Option<&JobHandle>,
+) -> Result<(), ArchiveError> {
+    let destination = fs::canonicalize(destination).map_err(|e| {
+        internal_error(format!(
+            "Failed to resolve destination '{}': {}",
+            destination.display(),
+            e
+        ))
+    })?;
+    let mut remaining_total = limits.max_total_size;
+    reader.for_each_entry(&mut |entry, content| {
+        if is_cancelled(job) {
+            return Err(ArchiveError::Cancelled);
+        }
+        let Some(sanitized) = sanitize_entry_path(&entry.name) else {
+            log_error!(
+                "Skipping archive entry outside of the destination: {}",
+                entry.name
+            );
+            return Ok(true);
+        };
+        let Some(relative) = target_of(&sanitized).filter(|p| !p.as_os_str().is_empty()) else {
+            return Ok(true);
+        };
+        let out_path = destination.join(&relative);
+        if !resolves_inside(&destination, &out_path) {
+            log_error!(
+                "Skipping archive entry written through a symlink outside of the destination: {}",
+                entry.name
+            );
+            return Ok(true);
+        }
+
+        match entry.kind {
+            ArchiveEntryKind::Directory => {
+                fs::create_dir_all(&out_path).map_err(|e| {
+                    internal_error(format!(
+                        "Failed to create directory '{}': {}",
+                        out_path.display(),
+                        e
+                    ))
+                })?;
+            }
+            ArchiveEntryKind::File | ArchiveEntryKind::Symlink => {
+                if let Some(parent) = out_path.parent() {
+                    fs::create_dir_all(parent).map_err(|e| {
+                        internal_error(format!(
+                            "Failed to create parent directory '{}': {}",
+                            parent.display(),
+                            e
+                        ))
+                    })?;
+                }
+                let unique_path = PathBuf::from(
+                    crate::commands::file_system_operation_commands::generate_unique_path(
+                        &out_path.to_string_lossy(),
+                    ),
+                );
+                // `generate_unique_path` keeps the path of a dangling symlink
+                if !resolves_inside(&destination, &unique_path) {
+                    log_error!(
+                        "Skipping archive entry written through a symlink outside of the destination: {}",
+                        entry.name
+                    );
+                    return Ok(true);
+                }
+
+                if entry.kind == ArchiveEntryKind::Symlink {
+                    let target = entry.link_target.clone().unwrap_or_default();
+                    if !symlink_stays_inside(&relative, &target) {
+                        log_error!(
+                            "Skipping symlink pointing outside of the destination: {}",
+                            entry.name
+                        );
+                        return Ok(true);
+                    }
+                    #[cfg(unix)]
+                    std::os::unix::fs::symlink(&target, &unique_path).map_err(|e| {
+                        internal_error(format!(
+                            "Failed to create symlink '{}': {}",
+                            unique_path.display(),
+                            e
+                        ))
+                    })?;
+                } else {
+                    // `create_new` never follows a symlink at the path of the new file
+                    let mut out_file = fs::OpenOptions::new()
+                        .write(true)
+                        .create_new(true)
+                        .open(&unique_path)
+                        .map_err(|e| {
+                            internal_error(format!(
+                                "Failed to create file '{}': {}",
+                                unique_path.display(),
+                                e
+                            ))
+                        })?;
+                    let mut limited =
+                        safety::LimitedReader::new(content, entry.size, remaining_total);
+                    let copied = std::io::copy(
+                        &mut ProgressReader {
+                            inner: &mut limited,
+                            job,
+                        },
+                        &mut out_file,
+                    );
+                    if let Some(reason) = limited.violation {
+                        drop(out_file);
+                        let _ = fs::remove_file(&unique_path);
+                        return Err(safety::rejection_error(
+                            &[safety::RejectedEntry {
+                                name: Some(entry.name.clone()),
+                                reason,
+                            }],
+                            limits,
+                        ));
+                    }
+                    remaining_total -= limited.read;
+                    if is_cancelled(job) {
+                        return Err(ArchiveError::Cancelled);
+                    }
+                    copied.map_err(|e| {
+                        internal_error(format!(
+                            "Failed to write file '{}': {}",
+                            unique_path.display(),
+                            e
+                        ))
+                    })?;
+                    apply_entry_metadata(&unique_path, entry, &out_file);
+                }
+            }
+        }
+
+        if let Some(job) = job {
+            job.update_progress(|p| {
+                p.items_processed += 1;
+                p.current_path = Some(out_path.to_string_lossy().to_string());
+            });
+        }
+        Ok(true)
+    })
+}
+
+/// Converts the unix timestamps of tar and 7z entries to a `SystemTime`.
+pub(crate) fn system_time_from_unix_seconds(seconds: i64) -> Option<SystemTime> {
+    if seconds >= 0 {
+        SystemTime::UNIX_EPOCH.checked_add(std::time::Duration::from_secs(seconds as u64))
+    } else {
+        SystemTime::UNIX_EPOCH.checked_sub(std::time::Duration::from_secs(seconds.unsigned_abs()))
+    }
+}
+
+#[cfg(test)]
+mod tests_archive {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn detect_format_test() {
+        let cases = [
+            ("backup.tar.gz", Some(ArchiveFormat::TarGz)),
+            ("backup.TGZ", Some(ArchiveFormat::TarGz)),
+            ("backup.tar.zst", Some(ArchiveFormat::TarZst)),
+            ("backup.tar.bz2", Some(ArchiveFormat::TarBz2)),
+            ("backup.txz", Some(ArchiveFormat::TarXz)),
+            ("backup.tar", Some(ArchiveFormat::Tar)),
+            ("docs.zip", Some(ArchiveFormat::Zip)),
+            ("docs.7z", Some(ArchiveFormat::SevenZ)),
+            ("notes.gz", None),
+            ("notes.txt", None),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(
+                ArchiveFormat::from_path(Path::new(name)),
+                expected,
+                "{}",
+                name
+            );
+        }
+        assert_eq!(
+            ArchiveFormat::TarGz.strip_extension("backup.tar.gz"),
+            "backup"
+        );
+        assert_eq!(ArchiveFormat::Zip.strip_extension("Docs.ZIP"), "Docs");
+    }
+
+    #[test]
+    fn sanitize_entry_path_test() {
+        assert_eq!(
+            sanitize_entry_path("a/b.txt"),
+            Some(PathBuf::from("a/b.txt"))
+        );
+        assert_eq!(
+            sanitize_entry_path("./a/./b.txt"),
+            Some(PathBuf::from("a/b.txt"))
+        );
+        assert_eq!(sanitize_entry_path("../evil.txt"), None);
+        assert_eq!(sanitize_entry_path("a/../../evil.txt"), None);
+        assert_eq!(sanitize_entry_path("/etc/passwd"), None);
+        assert_eq!(sanitize_entry_path("..\\evil.txt"), None);
+        assert_eq!(sanitize_entry_path(""), None);
+    }
+
+    fn create_source(root: &Path) -> PathBuf {
+        let source = root.join("project");
+        fs::create_dir_all(source.join("src").join("empty")).unwrap();
+        fs::write(source.join("README.md"), "# Project").unwrap();
+        fs::write(
+            source.join("src").join("main.rs"),
+            "fn main() {}\n".repeat(100),
+        )
+        .unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("README.md", source.join("link")).unwrap();
+        source
+    }
+
+    #[test]
+    fn write_and_read_all_writable_formats_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let source = create_source(temp_dir.path());
+        let sources = vec![source.to_string_lossy().to_string()];
+
+        for format in [
+            ArchiveFormat::Zip,
+            ArchiveFormat::Tar,
+            ArchiveFormat::TarGz,
+            ArchiveFormat::TarBz2,
+            ArchiveFormat::TarXz,
+            ArchiveFormat::TarZst,
+        ] {
+            let archive_path = temp_dir.path().join(format!("out{}", format.extension()));
+            let options = ArchiveCreationOptions::default();
+            assert!(
+                write_archive(&sources, &archive_path, format, &options, None).is_ok(),
+                "Failed to write {:?}",
+                format
+            );
+
+            let mut reader = open_archive(&archive_path, None)
+                .unwrap_or_else(|_| panic!("Failed to open {:?}", format));
+            let entries = reader.entries().ok().unwrap();
+            let find = |name: &str| entries.iter().find(|e| e.name == name).cloned();
+
+            assert_eq!(find("project").unwrap().kind, ArchiveEntryKind::Directory);
+            assert_eq!(
+                find("project/src/empty").unwrap().kind,
+                ArchiveEntryKind::Directory
+            );
+            let main = find("project/src/main.rs").expect("main.rs missing");
+            assert_eq!(main.kind, ArchiveEntryKind::File);
+            assert_eq!(main.size, 1300);
+            assert!(main.modified.is_some());
+            #[cfg(unix)]
+            {
+                let link = find("project/link").expect("link missing");
+                assert_eq!(link.kind, ArchiveEntryKind::Symlink, "{:?}", format);
+                assert_eq!(link.link_target.as_deref(), Some("README.md"));
+            }
+
+            let mut readme = String::new();
+            reader
+                .for_each_entry(&mut |entry, content| {
+                    if entry.name == "project/README.md" {
+                        content.read_to_string(&mut readme).unwrap();
+                        return Ok(false);
+                    }
+                    Ok(true)
+                })
+                .ok()
+                .unwrap();
+            assert_eq!(readme, "# Project", "{:?}", format);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn chained_symlinks_cannot_escape_destination_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let archive_path = temp_dir.path().join("evil.tar");
+        {
+            let mut builder = tar::Builder::new(fs::File::create(&archive_path).unwrap());
+            let mut directory = tar::Header::new_gnu();
+            directory.set_entry_type(tar::EntryType::Directory);
+            directory.set_mode(0o755);
+            directory.set_size(0);
+            builder
+                .append_data(&mut directory, "a", std::io::empty())
+                .unwrap();
+            // Each link stays inside on its own, together `x` points to the parent directory
+            for (name, target) in [("a/b", ".."), ("x", "a/b/.."), ("y", "a/b/../outside.txt")] {
+                let mut link = tar::Header::new_gnu();
+                link.set_entry_type(tar::EntryType::Symlink);
+                link.set_size(0);
+                builder.append_link(&mut link, name, target).unwrap();
+            }
+            for name in ["x/evil.txt", "y", "inside.txt"] {
+                let mut file = tar::Header::new_gnu();
+                file.set_mode(0o644);
+                file.set_size(4);
+                builder.append_data(&mut file, name, &b"evil"[..]).unwrap();
+            }
+            builder.finish().unwrap();
+        }
+        let destination = temp_dir.path().join("out");
+        fs::create_dir(&destination).unwrap();
+
+        let mut reader = open_archive(&archive_path, None).ok().unwrap();
+        assert!(extract_entries(
+            reader.as_mut(),
+            &destination,
+            &|path| Some(path.to_path_buf()),
+            &ArchiveExtractionConfig::default(),
+            None,
+        )
+        .is_ok());
+
+        assert!(!temp_dir.path().join("evil.txt").exists());
+        assert!(!temp_dir.path().join("outside.txt").exists());
+        assert_eq!(
+            fs::read_to_string(destination.join("inside.txt")).unwrap(),
+            "evil"
+        );
+    }
+
+    #[test]
+    fn validate_creation_options_test() {
+        let options = ArchiveCreationOptions {
+            compression_level: Some(19),
+            ..Default::default()
+        };
+        assert!(validate_creation_options(ArchiveFormat::TarZst, &options).is_ok());
+        assert!(validate_creation_options(ArchiveFormat::TarGz, &options).is_err());
+        assert!(validate_creation_options(ArchiveFormat::Tar, &options).is_err());
+
+        let options = ArchiveCreationOptions {
+            password: Some("secret".to_string()),
+            ..Default::default()
+        };
+        assert!(validate_creation_options(ArchiveFormat::Zip, &options).is_ok());
+        assert!(validate_creation_options(ArchiveFormat::TarGz, &options).is_err());
+
+        let result = validate_creation_options(ArchiveFormat::SevenZ, &Default::default());
+        assert!(result.unwrap_err().contains("NotImplemented"));
+    }
+}
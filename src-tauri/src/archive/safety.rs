@@ -0,0 +1,313 @@
+//! Checks which run before and during the extraction of an archive, so a crafted archive
+//! can neither write outside of the destination (zip slip) nor fill up the disk (archive bomb).
+
+use super::{symlink_stays_inside, ArchiveEntry, ArchiveEntryKind, ArchiveError};
+use crate::error_handling::{Error, ErrorCode};
+use crate::log_error;
+use crate::models::archive_extraction_config::ArchiveExtractionConfig;
+use serde::Serialize;
+use serde_json::json;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum RejectionReason {
+    /// The name is absolute or contains `..` components.
+    PathTraversal,
+    /// A symlink which points outside of the destination.
+    SymlinkOutsideDestination,
+    /// The content is compressed more than `max_compression_ratio` allows.
+    CompressionRatio,
+    /// The archive has more than `max_entry_count` entries.
+    TooManyEntries,
+    /// The extracted files would be larger than `max_total_size`.
+    TotalSizeExceeded,
+    /// The content of an entry is larger than the size stored in the archive.
+    SizeMismatch,
+}
+
+/// An entry which is not extracted. `name` is `None` if the limit applies to the whole archive.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct RejectedEntry {
+    pub name: Option<String>,
+    pub reason: RejectionReason,
+}
+
+impl RejectedEntry {
+    fn new(name: Option<&str>, reason: RejectionReason) -> Self {
+        Self {
+            name: name.map(str::to_string),
+            reason,
+        }
+    }
+}
+
+/// The structured error for rejected entries, the `details` hold the entries and the limits.
+pub(crate) fn rejection_error(
+    rejected: &[RejectedEntry],
+    limits: &ArchiveExtractionConfig,
+) -> ArchiveError {
+    let message = format!(
+        "Archive was not extracted, {} unsafe {} found",
+        rejected.len(),
+        if rejected.len() == 1 {
+            "entry was"
+        } else {
+            "entries were"
+        }
+    );
+    log_error!("{}: {:?}", message, rejected);
+    ArchiveError::Failed(
+        Error::new(ErrorCode::InvalidInput, message)
+            .with_details(json!({
+                "rejected_entries": rejected,
+                "limits": limits,
+            }))
+            .to_json(),
+    )
+}
+
+/// Whether the name of an entry is absolute or leads out of the destination with `..`.
+fn escapes_destination(name: &str) -> bool {
+    Path::new(&name.replace('\\', "/")).components().any(|c| {
+        matches!(
+            c,
+            Component::ParentDir | Component::RootDir | Component::Prefix(_)
+        )
+    })
+}
+
+/// Checks the listed entries of an archive against the limits before anything is extracted.
+///
+/// # Arguments
+/// * `entries` - All entries of the archive.
+/// * `archive_size` - Size of the archive file, used for the ratio of formats which don't
+///   store a compressed size per entry.
+/// * `limits` - The configured extraction limits.
+///
+/// # Returns
+/// * `Ok(())` - If the archive can be extracted.
+/// * `Err(ArchiveError)` - An `InvalidInput` error listing all rejected entries.
+pub fn check_entries(
+    entries: &[ArchiveEntry],
+    archive_size: u64,
+    limits: &ArchiveExtractionConfig,
+) -> Result<(), ArchiveError> {
+    let mut rejected = Vec::new();
+    let mut total_size: u64 = 0;
+
+    for entry in entries {
+        if escapes_destination(&entry.name) {
+            rejected.push(RejectedEntry::new(
+                Some(&entry.name),
+                RejectionReason::PathTraversal,
+            ));
+            continue;
+        }
+        match entry.kind {
+            ArchiveEntryKind::Symlink => {
+                let path = PathBuf::from(entry.name.replace('\\', "/"));
+                let target = entry.link_target.as_deref().unwrap_or_default();
+                if !symlink_stays_inside(&path, target) {
+                    rejected.push(RejectedEntry::new(
+                        Some(&entry.name),
+                        RejectionReason::SymlinkOutsideDestination,
+                    ));
+                }
+            }
+            ArchiveEntryKind::File => {
+                total_size = total_size.saturating_add(entry.size);
+                let too_compressed = entry.compressed_size.is_some_and(|compressed| {
+                    entry.size > limits.compression_ratio_min_size
+                        && entry.size / compressed.max(1) > limits.max_compression_ratio
+                });
+                if too_compressed {
+                    rejected.push(RejectedEntry::new(
+                        Some(&entry.name),
+                        RejectionReason::CompressionRatio,
+                    ));
+                }
+            }
+            ArchiveEntryKind::Directory => {}
+        }
+    }
+
+    if entries.len() > limits.max_entry_count {
+        rejected.push(RejectedEntry::new(None, RejectionReason::TooManyEntries));
+    }
+    if total_size > limits.max_total_size {
+        rejected.push(RejectedEntry::new(None, RejectionReason::TotalSizeExceeded));
+    }
+    // Tar and 7z compress the archive as a whole, so only the overall ratio is known
+    if total_size > limits.compression_ratio_min_size
+        && total_size / archive_size.max(1) > limits.max_compression_ratio
+    {
+        rejected.push(RejectedEntry::new(None, RejectionReason::CompressionRatio));
+    }
+
+    if rejected.is_empty() {
+        Ok(())
+    } else {
+        Err(rejection_error(&rejected, limits))
+    }
+}
+
+/// Reads the content of an entry and stops once it gets larger than the size stored in the
+/// archive or than the remaining size allowed for the whole extraction. The sizes in the
+/// headers can't be trusted, so this catches what `check_entries` could not see.
+pub(crate) struct LimitedReader<'a> {
+    inner: &'a mut dyn Read,
+    declared_size: u64,
+    remaining_total: u64,
+    pub(crate) read: u64,
+    pub(crate) violation: Option<RejectionReason>,
+}
+
+impl<'a> LimitedReader<'a> {
+    pub(crate) fn new(inner: &'a mut dyn Read, declared_size: u64, remaining_total: u64) -> Self {
+        Self {
+            inner,
+            declared_size,
+            remaining_total,
+            read: 0,
+            violation: None,
+        }
+    }
+}
+
+impl Read for LimitedReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        // One byte more than allowed is read to notice content which is too large
+        let allowed = self.declared_size.min(self.remaining_total) - self.read + 1;
+        let len = buf
+            .len()
+            .min(usize::try_from(allowed).unwrap_or(usize::MAX));
+        let read = self.inner.read(&mut buf[..len])?;
+        self.read += read as u64;
+        if self.read > self.declared_size {
+            self.violation = Some(RejectionReason::SizeMismatch);
+        } else if self.read > self.remaining_total {
+            self.violation = Some(RejectionReason::TotalSizeExceeded);
+        }
+        if self.violation.is_some() {
+            return Err(std::io::Error::other("Archive entry exceeds its limits"));
+        }
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests_archive_safety {
+    use super::*;
+
+    fn file(name: &str, size: u64, compressed_size: Option<u64>) -> ArchiveEntry {
+        ArchiveEntry {
+            name: name.to_string(),
+            kind: ArchiveEntryKind::File,
+            size,
+            compressed_size,
+            modified: None,
+            mode: None,
+            link_target: None,
+        }
+    }
+
+    fn rejected_of(result: Result<(), ArchiveError>) -> serde_json::Value {
+        let Err(err) = result else {
+            panic!("Expected the entries to be rejected");
+        };
+        let error: serde_json::Value = serde_json::from_str(&err.into_json()).unwrap();
+        error["details"]["rejected_entries"].clone()
+    }
+
+    #[test]
+    fn check_entries_accepts_normal_archive_test() {
+        let entries = vec![
+            file("docs/readme.md", 1000, Some(400)),
+            file("./src/main.rs", 2000, Some(900)),
+        ];
+        assert!(check_entries(&entries, 2000, &ArchiveExtractionConfig::default()).is_ok());
+    }
+
+    #[test]
+    fn check_entries_rejects_escaping_entries_test() {
+        let mut link = file("docs/link", 0, None);
+        link.kind = ArchiveEntryKind::Symlink;
+        link.link_target = Some("../../etc/passwd".to_string());
+        let entries = vec![
+            file("../evil.txt", 4, None),
+            file("/etc/cron.d/evil", 4, None),
+            file("ok.txt", 4, None),
+            link,
+        ];
+
+        let rejected = rejected_of(check_entries(
+            &entries,
+            100,
+            &ArchiveExtractionConfig::default(),
+        ));
+        assert_eq!(
+            rejected,
+            json!([
+                {"name": "../evil.txt", "reason": "PathTraversal"},
+                {"name": "/etc/cron.d/evil", "reason": "PathTraversal"},
+                {"name": "docs/link", "reason": "SymlinkOutsideDestination"},
+            ])
+        );
+    }
+
+    #[test]
+    fn check_entries_enforces_limits_test() {
+        let limits = ArchiveExtractionConfig {
+            max_total_size: 1000,
+            max_entry_count: 2,
+            max_compression_ratio: 10,
+            compression_ratio_min_size: 100,
+        };
+        let entries = vec![
+            file("a.bin", 500, Some(5)),
+            file("b.bin", 50, Some(1)),
+            file("c.bin", 600, None),
+        ];
+
+        let rejected = rejected_of(check_entries(&entries, 10_000, &limits));
+        assert_eq!(
+            rejected,
+            json!([
+                {"name": "a.bin", "reason": "CompressionRatio"},
+                {"name": null, "reason": "TooManyEntries"},
+                {"name": null, "reason": "TotalSizeExceeded"},
+            ])
+        );
+
+        // The ratio of the whole archive counts for formats without compressed sizes
+        let entries = vec![file("zeros.bin", 900, None)];
+        let rejected = rejected_of(check_entries(&entries, 10, &limits));
+        assert_eq!(
+            rejected,
+            json!([{"name": null, "reason": "CompressionRatio"}])
+        );
+    }
+
+    #[test]
+    fn limited_reader_stops_at_declared_size_test() {
+        let mut content: &[u8] = &[7u8; 100];
+        let mut reader = LimitedReader::new(&mut content, 10, 1000);
+        let result = std::io::copy(&mut reader, &mut std::io::sink());
+        assert!(result.is_err());
+        assert_eq!(reader.violation, Some(RejectionReason::SizeMismatch));
+
+        let mut content: &[u8] = &[7u8; 100];
+        let mut reader = LimitedReader::new(&mut content, 100, 50);
+        assert!(std::io::copy(&mut reader, &mut std::io::sink()).is_err());
+        assert_eq!(reader.violation, Some(RejectionReason::TotalSizeExceeded));
+
+        let mut content: &[u8] = &[7u8; 100];
+        let mut reader = LimitedReader::new(&mut content, 100, 100);
+        assert_eq!(
+            std::io::copy(&mut reader, &mut std::io::sink()).unwrap(),
+            100
+        );
+        assert_eq!(reader.violation, None);
+    }
+}
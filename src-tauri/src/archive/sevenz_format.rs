@@ -0,0 +1,156 @@
+use super::{
+    archive_error, system_time_from_unix_seconds, ArchiveEntry, ArchiveEntryKind, ArchiveError,
+    ArchiveReader, EntryVisitor,
+};
+use crate::error_handling::ErrorCode;
+use sevenz_rust::{Password, SevenZArchiveEntry, SevenZReader};
+use std::fs;
+use std::path::Path;
+
+/// Set in the windows attributes if the upper 16 bits hold unix mode bits.
+const UNIX_EXTENSION_FLAG: u32 = 0x8000;
+const UNIX_FILE_TYPE_MASK: u32 = 0o170000;
+const UNIX_SYMLINK_TYPE: u32 = 0o120000;
+
+fn seven_z_error(error: sevenz_rust::Error) -> ArchiveError {
+    let code = match error {
+        sevenz_rust::Error::PasswordRequired | sevenz_rust::Error::MaybeBadPassword(_) => {
+            ErrorCode::Unauthorized
+        }
+        _ => ErrorCode::InternalError,
+    };
+    archive_error(code, format!("Failed to read 7z archive: {}", error))
+}
+
+pub(super) struct SevenZArchiveReader {
+    reader: SevenZReader<fs::File>,
+}
+
+impl SevenZArchiveReader {
+    pub(super) fn open(path: &Path, password: Option<&str>) -> Result<Self, ArchiveError> {
+        let password = password.map(Password::from).unwrap_or_else(Password::empty);
+        let reader = SevenZReader::open(path, password).map_err(seven_z_error)?;
+        Ok(Self { reader })
+    }
+
+    fn entry_of(file: &SevenZArchiveEntry) -> ArchiveEntry {
+        let unix_mode = (file.has_windows_attributes
+            && file.windows_attributes & UNIX_EXTENSION_FLAG != 0)
+            .then_some(file.windows_attributes >> 16);
+        let kind = if file.is_directory() {
+            ArchiveEntryKind::Directory
+        } else if unix_mode.is_some_and(|mode| mode & UNIX_FILE_TYPE_MASK == UNIX_SYMLINK_TYPE) {
+            ArchiveEntryKind::Symlink
+        } else {
+            ArchiveEntryKind::File
+        };
+        ArchiveEntry {
+            name: file
+                .name()
+                .replace('\\', "/")
+                .trim_end_matches('/')
+                .to_string(),
+            kind,
+            size: file.size(),
+            compressed_size: None,
+            modified: file
+                .has_last_modified_date
+                .then(|| system_time_from_unix_seconds(file.last_modified_date().to_unix_time()))
+                .flatten(),
+            mode: unix_mode.map(|mode| mode & 0o7777),
+            link_target: None,
+        }
+    }
+}
+
+impl ArchiveReader for SevenZArchiveReader {
+    fn entries(&mut self) -> Result<Vec<ArchiveEntry>, ArchiveError> {
+        let entries: Vec<ArchiveEntry> = self
+            .reader
+            .archive()
+            .files
+            .iter()
+            .filter(|file| !file.is_anti_item())
+            .map(Self::entry_of)
+            .collect();
+        if entries.iter().all(|e| e.kind != ArchiveEntryKind::Symlink) {
+            return Ok(entries);
+        }
+        // Symlink targets are stored as content, so they need a decompressing pass
+        let mut with_targets = Vec::with_capacity(entries.len());
+        self.for_each_entry(&mut |entry, _| {
+            with_targets.push(entry.clone());
+            Ok(true)
+        })?;
+        Ok(with_targets)
+    }
+
+    fn for_each_entry(&mut self, visit: &mut EntryVisitor) -> Result<(), ArchiveError> {
+        // The callback of sevenz_rust can only return its own error type, so errors of the
+        // visitor are kept here and the iteration is stopped
+        let mut visit_error = None;
+        let result = self.reader.for_each_entries(|file, content| {
+            if file.is_anti_item() {
+                return Ok(true);
+            }
+            let mut entry = Self::entry_of(file);
+            let visited = if entry.kind == ArchiveEntryKind::Symlink {
+                let mut target = String::new();
+                content.read_to_string(&mut target)?;
+                entry.link_target = Some(target);
+                visit(&entry, &mut std::io::empty())
+            } else {
+                visit(&entry, content)
+            };
+            match visited {
+                Ok(keep_going) => Ok(keep_going),
+                Err(err) => {
+                    visit_error = Some(err);
+                    Ok(false)
+                }
+            }
+        });
+        if let Some(err) = visit_error {
+            return Err(err);
+        }
+        result.map_err(seven_z_error)
+    }
+}
+
+#[cfg(test)]
+mod tests_sevenz_format {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn read_7z_archive_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let source = temp_dir.path().join("notes");
+        fs::create_dir_all(source.join("sub")).unwrap();
+        fs::write(source.join("a.txt"), "first").unwrap();
+        fs::write(source.join("sub").join("b.txt"), "second").unwrap();
+        let archive_path = temp_dir.path().join("notes.7z");
+        sevenz_rust::compress_to_path(&source, &archive_path).expect("Failed to create 7z");
+
+        let mut reader = SevenZArchiveReader::open(&archive_path, None).ok().unwrap();
+        let entries = reader.entries().ok().unwrap();
+        let b = entries
+            .iter()
+            .find(|e| e.name == "sub/b.txt")
+            .expect("sub/b.txt missing");
+        assert_eq!(b.kind, ArchiveEntryKind::File);
+        assert_eq!(b.size, 6);
+
+        let mut content = String::new();
+        reader
+            .for_each_entry(&mut |entry, reader| {
+                if entry.name == "a.txt" {
+                    reader.read_to_string(&mut content).unwrap();
+                }
+                Ok(true)
+            })
+            .ok()
+            .unwrap();
+        assert_eq!(content, "first");
+    }
+}
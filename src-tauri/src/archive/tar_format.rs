@@ -0,0 +1,224 @@
+use super::{
+    internal_error, system_time_from_unix_seconds, ArchiveEntry, ArchiveEntryKind, ArchiveError,
+    ArchiveFormat, ArchiveReader, ArchiveWriter, EntryVisitor,
+};
+use std::fs;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use tar::{Builder, EntryType, Header, HeaderMode};
+
+/// The compression stream around the tar data.
+enum Compressor {
+    Plain(BufWriter<fs::File>),
+    Gz(flate2::write::GzEncoder<BufWriter<fs::File>>),
+    Bz2(bzip2::write::BzEncoder<BufWriter<fs::File>>),
+    Xz(xz2::write::XzEncoder<BufWriter<fs::File>>),
+    Zst(zstd::Encoder<'static, BufWriter<fs::File>>),
+}
+
+impl Compressor {
+    fn new(file: fs::File, format: ArchiveFormat, level: Option<i64>) -> std::io::Result<Self> {
+        let writer = BufWriter::new(file);
+        Ok(match format {
+            ArchiveFormat::TarGz => Compressor::Gz(flate2::write::GzEncoder::new(
+                writer,
+                flate2::Compression::new(level.unwrap_or(6) as u32),
+            )),
+            ArchiveFormat::TarBz2 => Compressor::Bz2(bzip2::write::BzEncoder::new(
+                writer,
+                bzip2::Compression::new(level.unwrap_or(9) as u32),
+            )),
+            ArchiveFormat::TarXz => Compressor::Xz(xz2::write::XzEncoder::new(
+                writer,
+                level.unwrap_or(6) as u32,
+            )),
+            ArchiveFormat::TarZst => Compressor::Zst(zstd::Encoder::new(
+                writer,
+                level.unwrap_or(zstd::DEFAULT_COMPRESSION_LEVEL as i64) as i32,
+            )?),
+            _ => Compressor::Plain(writer),
+        })
+    }
+
+    /// Writes the end of the compression stream and flushes the file.
+    fn finish(self) -> std::io::Result<()> {
+        let mut writer = match self {
+            Compressor::Plain(writer) => writer,
+            Compressor::Gz(encoder) => encoder.finish()?,
+            Compressor::Bz2(encoder) => encoder.finish()?,
+            Compressor::Xz(encoder) => encoder.finish()?,
+            Compressor::Zst(encoder) => encoder.finish()?,
+        };
+        writer.flush()
+    }
+}
+
+impl Write for Compressor {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Compressor::Plain(writer) => writer.write(buf),
+            Compressor::Gz(encoder) => encoder.write(buf),
+            Compressor::Bz2(encoder) => encoder.write(buf),
+            Compressor::Xz(encoder) => encoder.write(buf),
+            Compressor::Zst(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Compressor::Plain(writer) => writer.flush(),
+            Compressor::Gz(encoder) => encoder.flush(),
+            Compressor::Bz2(encoder) => encoder.flush(),
+            Compressor::Xz(encoder) => encoder.flush(),
+            Compressor::Zst(encoder) => encoder.flush(),
+        }
+    }
+}
+
+pub(super) struct TarArchiveWriter {
+    builder: Builder<Compressor>,
+}
+
+impl TarArchiveWriter {
+    pub(super) fn new(
+        file: fs::File,
+        format: ArchiveFormat,
+        level: Option<i64>,
+    ) -> Result<Self, ArchiveError> {
+        let compressor = Compressor::new(file, format, level)
+            .map_err(|e| internal_error(format!("Failed to create archive file: {}", e)))?;
+        let mut builder = Builder::new(compressor);
+        builder.follow_symlinks(false);
+        Ok(Self { builder })
+    }
+
+    /// A GNU header, which supports long names, with the permissions and times of the metadata.
+    fn header_of(metadata: &fs::Metadata) -> Header {
+        let mut header = Header::new_gnu();
+        header.set_metadata_in_mode(metadata, HeaderMode::Complete);
+        header
+    }
+}
+
+impl ArchiveWriter for TarArchiveWriter {
+    fn add_directory(&mut self, name: &str, metadata: &fs::Metadata) -> Result<(), ArchiveError> {
+        let mut header = Self::header_of(metadata);
+        header.set_entry_type(EntryType::Directory);
+        header.set_size(0);
+        self.builder
+            .append_data(&mut header, format!("{}/", name), std::io::empty())
+            .map_err(|e| internal_error(format!("Error adding directory to archive: {}", e)))
+    }
+
+    fn add_file(
+        &mut self,
+        name: &str,
+        metadata: &fs::Metadata,
+        content: &mut dyn Read,
+    ) -> Result<(), ArchiveError> {
+        let mut header = Self::header_of(metadata);
+        header.set_entry_type(EntryType::Regular);
+        header.set_size(metadata.len());
+        // The size in the header is fixed, so a file which grows while it is read is cut off
+        self.builder
+            .append_data(&mut header, name, content.take(metadata.len()))
+            .map_err(|e| internal_error(format!("Error adding file to archive: {}", e)))
+    }
+
+    fn add_symlink(
+        &mut self,
+        name: &str,
+        target: &str,
+        metadata: &fs::Metadata,
+    ) -> Result<(), ArchiveError> {
+        let mut header = Self::header_of(metadata);
+        header.set_entry_type(EntryType::Symlink);
+        header.set_size(0);
+        self.builder
+            .append_link(&mut header, name, target)
+            .map_err(|e| internal_error(format!("Error adding symlink to archive: {}", e)))
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), ArchiveError> {
+        let compressor = self
+            .builder
+            .into_inner()
+            .map_err(|e| internal_error(format!("Error finalizing archive: {}", e)))?;
+        compressor
+            .finish()
+            .map_err(|e| internal_error(format!("Error finalizing archive: {}", e)))
+    }
+}
+
+/// Tar archives can only be read front to back, so every pass opens the file again.
+pub(super) struct TarArchiveReader {
+    path: PathBuf,
+    format: ArchiveFormat,
+}
+
+impl TarArchiveReader {
+    pub(super) fn new(path: &Path, format: ArchiveFormat) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            format,
+        }
+    }
+
+    fn open_stream(&self) -> Result<Box<dyn Read>, ArchiveError> {
+        let file = fs::File::open(&self.path)
+            .map_err(|e| internal_error(format!("Failed to open archive: {}", e)))?;
+        let reader = BufReader::new(file);
+        Ok(match self.format {
+            ArchiveFormat::TarGz => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+            ArchiveFormat::TarBz2 => Box::new(bzip2::read::MultiBzDecoder::new(reader)),
+            ArchiveFormat::TarXz => Box::new(xz2::read::XzDecoder::new_multi_decoder(reader)),
+            ArchiveFormat::TarZst => Box::new(
+                zstd::Decoder::with_buffer(reader)
+                    .map_err(|e| internal_error(format!("Failed to open archive: {}", e)))?,
+            ),
+            _ => Box::new(reader),
+        })
+    }
+}
+
+impl ArchiveReader for TarArchiveReader {
+    fn for_each_entry(&mut self, visit: &mut EntryVisitor) -> Result<(), ArchiveError> {
+        let mut archive = tar::Archive::new(self.open_stream()?);
+        let entries = archive
+            .entries()
+            .map_err(|e| internal_error(format!("Failed to read archive: {}", e)))?;
+
+        for entry in entries {
+            let mut entry = entry
+                .map_err(|e| internal_error(format!("Failed to read archive entry: {}", e)))?;
+            let header = entry.header();
+            let kind = match header.entry_type() {
+                EntryType::Regular | EntryType::Continuous => ArchiveEntryKind::File,
+                EntryType::Directory => ArchiveEntryKind::Directory,
+                EntryType::Symlink => ArchiveEntryKind::Symlink,
+                // Hard links, devices and fifos can't be shown or extracted like files
+                _ => continue,
+            };
+            let archive_entry = ArchiveEntry {
+                name: String::from_utf8_lossy(&entry.path_bytes())
+                    .trim_end_matches('/')
+                    .to_string(),
+                kind,
+                size: header.size().unwrap_or(0),
+                compressed_size: None,
+                modified: header
+                    .mtime()
+                    .ok()
+                    .and_then(|mtime| system_time_from_unix_seconds(mtime as i64)),
+                mode: header.mode().ok().map(|mode| mode & 0o7777),
+                link_target: entry
+                    .link_name_bytes()
+                    .map(|target| String::from_utf8_lossy(&target).to_string()),
+            };
+            if !visit(&archive_entry, &mut entry)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
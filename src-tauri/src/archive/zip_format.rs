@@ -0,0 +1,277 @@
+use super::{
+    internal_error, ArchiveCreationOptions, ArchiveEntry, ArchiveEntryKind, ArchiveError,
+    ArchiveReader, ArchiveWriter, EntryVisitor,
+};
+use crate::error_handling::ErrorCode;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::SystemTime;
+use zip::write::FileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+/// Compression methods which can be used for new zip archives.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ZipCompressionMethod {
+    Stored,
+    #[default]
+    Deflated,
+    Bzip2,
+    Zstd,
+    Xz,
+}
+
+impl ZipCompressionMethod {
+    fn to_zip_method(self) -> zip::CompressionMethod {
+        match self {
+            ZipCompressionMethod::Stored => zip::CompressionMethod::Stored,
+            ZipCompressionMethod::Deflated => zip::CompressionMethod::Deflated,
+            ZipCompressionMethod::Bzip2 => zip::CompressionMethod::Bzip2,
+            ZipCompressionMethod::Zstd => zip::CompressionMethod::Zstd,
+            ZipCompressionMethod::Xz => zip::CompressionMethod::Xz,
+        }
+    }
+
+    /// The range of supported compression levels, `None` if the method has no levels.
+    pub(super) fn level_range(self) -> Option<std::ops::RangeInclusive<i64>> {
+        match self {
+            ZipCompressionMethod::Stored => None,
+            ZipCompressionMethod::Deflated => Some(0..=9),
+            ZipCompressionMethod::Bzip2 => Some(1..=9),
+            ZipCompressionMethod::Zstd => Some(-7..=22),
+            ZipCompressionMethod::Xz => Some(0..=9),
+        }
+    }
+}
+
+/// Files larger than this are written with ZIP64 headers. The margin below 4 GiB covers
+/// incompressible data which grows slightly while compressing.
+const ZIP64_THRESHOLD: u64 = u32::MAX as u64 - 64 * 1024 * 1024;
+
+/// Permissions stored for an entry. Windows has no permission bits, so the usual defaults are used.
+pub(super) fn permissions_of(metadata: &fs::Metadata) -> u32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        metadata.permissions().mode() & 0o777
+    }
+    #[cfg(not(unix))]
+    {
+        match (metadata.is_dir(), metadata.permissions().readonly()) {
+            (true, _) => 0o755,
+            (false, true) => 0o444,
+            (false, false) => 0o644,
+        }
+    }
+}
+
+/// Converts a file time to the local time zip timestamp. Zip can't store times before 1980
+/// and only has a resolution of two seconds.
+fn to_zip_date_time(time: SystemTime) -> Option<zip::DateTime> {
+    use chrono::{Datelike, Timelike};
+    let local: chrono::DateTime<chrono::Local> = time.into();
+    let year = u16::try_from(local.year()).ok()?;
+    zip::DateTime::from_date_and_time(
+        year,
+        local.month() as u8,
+        local.day() as u8,
+        local.hour() as u8,
+        local.minute() as u8,
+        (local.second() as u8).min(58),
+    )
+    .ok()
+}
+
+fn from_zip_date_time(time: zip::DateTime) -> Option<SystemTime> {
+    use chrono::TimeZone;
+    let local = chrono::Local
+        .with_ymd_and_hms(
+            time.year() as i32,
+            time.month() as u32,
+            time.day() as u32,
+            time.hour() as u32,
+            time.minute() as u32,
+            time.second() as u32,
+        )
+        .earliest()?;
+    Some(local.into())
+}
+
+pub(super) struct ZipArchiveWriter<'a> {
+    zip: ZipWriter<BufWriter<fs::File>>,
+    options: &'a ArchiveCreationOptions,
+}
+
+impl<'a> ZipArchiveWriter<'a> {
+    pub(super) fn new(file: fs::File, options: &'a ArchiveCreationOptions) -> Self {
+        Self {
+            zip: ZipWriter::new(BufWriter::new(file)),
+            options,
+        }
+    }
+
+    fn entry_options(&self, metadata: &fs::Metadata) -> FileOptions<'a, ()> {
+        let mut entry_options: FileOptions<()> = FileOptions::default()
+            .compression_method(self.options.compression_method.to_zip_method())
+            .compression_level(self.options.compression_level)
+            .unix_permissions(permissions_of(metadata))
+            .large_file(metadata.is_file() && metadata.len() >= ZIP64_THRESHOLD);
+        if let Some(time) = metadata.modified().ok().and_then(to_zip_date_time) {
+            entry_options = entry_options.last_modified_time(time);
+        }
+        if let Some(password) = &self.options.password {
+            entry_options = entry_options.with_aes_encryption(zip::AesMode::Aes256, password);
+        }
+        entry_options
+    }
+}
+
+impl ArchiveWriter for ZipArchiveWriter<'_> {
+    fn add_directory(&mut self, name: &str, metadata: &fs::Metadata) -> Result<(), ArchiveError> {
+        let entry_options = self.entry_options(metadata);
+        self.zip
+            .add_directory(name, entry_options)
+            .map_err(|e| internal_error(format!("Error adding directory to zip: {}", e)))
+    }
+
+    fn add_file(
+        &mut self,
+        name: &str,
+        metadata: &fs::Metadata,
+        content: &mut dyn Read,
+    ) -> Result<(), ArchiveError> {
+        let entry_options = self.entry_options(metadata);
+        self.zip
+            .start_file(name, entry_options)
+            .map_err(|e| internal_error(format!("Error adding file to zip: {}", e)))?;
+        std::io::copy(content, &mut self.zip)
+            .map_err(|e| internal_error(format!("Error writing to zip: {}", e)))?;
+        Ok(())
+    }
+
+    fn add_symlink(
+        &mut self,
+        name: &str,
+        target: &str,
+        metadata: &fs::Metadata,
+    ) -> Result<(), ArchiveError> {
+        let entry_options = self.entry_options(metadata);
+        self.zip
+            .add_symlink(name, target, entry_options)
+            .map_err(|e| internal_error(format!("Error adding symlink to zip: {}", e)))
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), ArchiveError> {
+        let mut writer = self
+            .zip
+            .finish()
+            .map_err(|e| internal_error(format!("Error finalizing zip file: {}", e)))?;
+        writer
+            .flush()
+            .map_err(|e| internal_error(format!("Error finalizing zip file: {}", e)))
+    }
+}
+
+pub(super) struct ZipArchiveReader {
+    archive: ZipArchive<BufReader<fs::File>>,
+    password: Option<String>,
+}
+
+impl ZipArchiveReader {
+    pub(super) fn open(path: &Path, password: Option<&str>) -> Result<Self, ArchiveError> {
+        let file = fs::File::open(path)
+            .map_err(|e| internal_error(format!("Failed to open zip file: {}", e)))?;
+        let archive = ZipArchive::new(BufReader::new(file))
+            .map_err(|e| internal_error(format!("Failed to read zip archive: {}", e)))?;
+        Ok(Self {
+            archive,
+            password: password.map(str::to_string),
+        })
+    }
+
+    fn entry_of(file: &zip::read::ZipFile<'_, BufReader<fs::File>>) -> ArchiveEntry {
+        let kind = if file.is_dir() {
+            ArchiveEntryKind::Directory
+        } else if file.is_symlink() {
+            ArchiveEntryKind::Symlink
+        } else {
+            ArchiveEntryKind::File
+        };
+        ArchiveEntry {
+            name: file.name().trim_end_matches('/').to_string(),
+            kind,
+            size: file.size(),
+            compressed_size: Some(file.compressed_size()),
+            modified: file.last_modified().and_then(from_zip_date_time),
+            mode: file.unix_mode().map(|mode| mode & 0o7777),
+            link_target: None,
+        }
+    }
+}
+
+impl ArchiveReader for ZipArchiveReader {
+    fn entries(&mut self) -> Result<Vec<ArchiveEntry>, ArchiveError> {
+        // The raw entries only need the central directory, nothing is decompressed
+        let mut entries = Vec::with_capacity(self.archive.len());
+        for i in 0..self.archive.len() {
+            let file = self
+                .archive
+                .by_index_raw(i)
+                .map_err(|e| internal_error(format!("Failed to read zip entry: {}", e)))?;
+            entries.push(Self::entry_of(&file));
+        }
+        // Symlink targets are stored as content and need a second, decompressing pass
+        if entries.iter().any(|e| e.kind == ArchiveEntryKind::Symlink) {
+            let mut targets = Vec::new();
+            self.for_each_entry(&mut |entry, _| {
+                if entry.kind == ArchiveEntryKind::Symlink {
+                    targets.push((entry.name.clone(), entry.link_target.clone()));
+                }
+                Ok(true)
+            })?;
+            for (name, target) in targets {
+                if let Some(entry) = entries.iter_mut().find(|e| e.name == name) {
+                    entry.link_target = target;
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    fn for_each_entry(&mut self, visit: &mut EntryVisitor) -> Result<(), ArchiveError> {
+        for i in 0..self.archive.len() {
+            let mut file = match &self.password {
+                Some(password) => self.archive.by_index_decrypt(i, password.as_bytes()),
+                None => self.archive.by_index(i),
+            }
+            .map_err(|e| {
+                let code = match e {
+                    zip::result::ZipError::InvalidPassword => ErrorCode::Unauthorized,
+                    zip::result::ZipError::UnsupportedArchive(message)
+                        if message == zip::result::ZipError::PASSWORD_REQUIRED =>
+                    {
+                        ErrorCode::Unauthorized
+                    }
+                    _ => ErrorCode::InternalError,
+                };
+                super::archive_error(code, format!("Failed to read zip entry: {}", e))
+            })?;
+
+            let mut entry = Self::entry_of(&file);
+            let keep_going = if entry.kind == ArchiveEntryKind::Symlink {
+                let mut target = String::new();
+                file.read_to_string(&mut target)
+                    .map_err(|e| internal_error(format!("Failed to read zip entry: {}", e)))?;
+                entry.link_target = Some(target);
+                visit(&entry, &mut std::io::empty())?
+            } else {
+                visit(&entry, &mut file)?
+            };
+            if !keep_going {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
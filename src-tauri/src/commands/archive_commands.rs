@@ -0,0 +1,259 @@
+use crate::archive::browse::ArchivePath;
+use crate::archive::{self, safety, sanitize_entry_path, ArchiveError};
+use crate::error_handling::{Error, ErrorCode};
+use crate::log_error;
+use crate::models::archive_extraction_config::ArchiveExtractionConfig;
+use crate::state::SettingsState;
+use std::cell::Cell;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::State;
+
+/// Reads the configured limits for extracting archives from the settings.
+pub(crate) fn extraction_limits(
+    settings_state: &Arc<Mutex<SettingsState>>,
+) -> Result<ArchiveExtractionConfig, String> {
+    let lock_error = || {
+        Error::new(
+            ErrorCode::InternalError,
+            "Failed to acquire lock on settings state".to_string(),
+        )
+        .to_json()
+    };
+    let settings_state = settings_state.lock().map_err(|_| lock_error())?;
+    let settings = settings_state.0.lock().map_err(|_| lock_error())?;
+    Ok(settings.backend_settings.archive_extraction_config.clone())
+}
+
+/// Extracts selected files and directories from an archive, without extracting the rest.
+/// Every selected entry ends up directly in the destination: a selected directory is
+/// extracted with everything below it, a selected file by its file name.
+/// Existing files are kept and the extracted files get unique names instead.
+/// Nothing is extracted if the archive contains unsafe entries or exceeds the limits in
+/// `backend_settings.archive_extraction_config`.
+///
+/// # Arguments
+/// * `entry_paths` - Virtual paths of the entries, like `/home/me/x.zip!/docs`. All entries
+///   have to be inside the same archive. The root `/home/me/x.zip!/` selects everything.
+/// * `destination_path` - Existing directory the entries are extracted into
+/// * `password` - Optional password for encrypted zip and 7z archives
+///
+/// # Returns
+/// * `Ok(())` - If all selected entries were extracted
+/// * `Err(String)` - If the paths are invalid or the extraction failed. Rejected archives
+///   return an `InvalidInput` error whose `details` list the rejected entries.
+///
+/// # Example
+/// ```rust
+/// let result = extract_archive_entries_impl(
+///     vec!["/home/me/x.zip!/docs".to_string(), "/home/me/x.zip!/readme.md".to_string()],
+///     "/home/me/Desktop".to_string(),
+///     None,
+/// ).await;
+/// ```
+#[tauri::command]
+pub async fn extract_archive_entries(
+    entry_paths: Vec<String>,
+    destination_path: String,
+    password: Option<String>,
+    settings_state: State<'_, Arc<Mutex<SettingsState>>>,
+) -> Result<(), String> {
+    extract_archive_entries_impl(
+        entry_paths,
+        destination_path,
+        password,
+        settings_state.inner().clone(),
+    )
+}
+
+pub fn extract_archive_entries_impl(
+    entry_paths: Vec<String>,
+    destination_path: String,
+    password: Option<String>,
+    settings_state: Arc<Mutex<SettingsState>>,
+) -> Result<(), String> {
+    if entry_paths.is_empty() {
+        log_error!("No archive entries provided");
+        return Err(Error::new(
+            ErrorCode::InvalidInput,
+            "No archive entries provided".to_string(),
+        )
+        .to_json());
+    }
+
+    let mut archive_file: Option<String> = None;
+    let mut selections = Vec::new();
+    for entry_path in &entry_paths {
+        let Some(parsed) = ArchivePath::parse(entry_path) else {
+            log_error!("Not a path inside an archive: {}", entry_path);
+            return Err(Error::new(
+                ErrorCode::InvalidInput,
+                format!("Not a path inside an archive: {}", entry_path),
+            )
+            .to_json());
+        };
+        if archive_file
+            .as_ref()
+            .is_some_and(|file| *file != parsed.archive)
+        {
+            log_error!("All entries must be inside the same archive");
+            return Err(Error::new(
+                ErrorCode::InvalidInput,
+                "All entries must be inside the same archive".to_string(),
+            )
+            .to_json());
+        }
+        let selection = if parsed.inner.is_empty() {
+            PathBuf::new()
+        } else {
+            sanitize_entry_path(&parsed.inner).ok_or_else(|| {
+                log_error!("Invalid archive entry: {}", entry_path);
+                Error::new(
+                    ErrorCode::InvalidInput,
+                    format!("Invalid archive entry: {}", entry_path),
+                )
+                .to_json()
+            })?
+        };
+        archive_file = Some(parsed.archive);
+        selections.push(selection);
+    }
+    let archive_file = archive_file.unwrap_or_default();
+
+    let destination = Path::new(&destination_path);
+    if !destination.exists() {
+        log_error!("Destination path does not exist: {}", destination_path);
+        return Err(Error::new(
+            ErrorCode::ResourceNotFound,
+            format!("Destination path does not exist: {}", destination_path),
+        )
+        .to_json());
+    }
+    if !destination.is_dir() {
+        log_error!("Destination path is not a directory: {}", destination_path);
+        return Err(Error::new(
+            ErrorCode::InvalidInput,
+            format!("Destination path is not a directory: {}", destination_path),
+        )
+        .to_json());
+    }
+
+    // An entry below a selection keeps its path relative to the parent of the selection
+    let selected_count = Cell::new(0usize);
+    let target_of = |entry_path: &Path| {
+        let selection = selections.iter().find(|s| entry_path.starts_with(s))?;
+        let base = selection.parent().unwrap_or(Path::new(""));
+        selected_count.set(selected_count.get() + 1);
+        entry_path.strip_prefix(base).ok().map(Path::to_path_buf)
+    };
+
+    let limits = extraction_limits(&settings_state)?;
+    let archive_size = fs::metadata(&archive_file).map(|m| m.len()).unwrap_or(0);
+    let mut reader = archive::open_archive(Path::new(&archive_file), password.as_deref())
+        .map_err(ArchiveError::into_json)?;
+    let entries = reader.entries().map_err(ArchiveError::into_json)?;
+    safety::check_entries(&entries, archive_size, &limits).map_err(ArchiveError::into_json)?;
+    archive::extract_entries(reader.as_mut(), destination, &target_of, &limits, None)
+        .map_err(ArchiveError::into_json)?;
+
+    if selected_count.get() == 0 {
+        log_error!("Selected entries do not exist in {}", archive_file);
+        return Err(Error::new(
+            ErrorCode::ResourceNotFound,
+            format!("Selected entries do not exist in {}", archive_file),
+        )
+        .to_json());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests_archive_commands {
+    use super::*;
+    use crate::state::jobs_data::create_test_settings_state;
+    use std::io::Write;
+    use tempfile::tempdir;
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    fn create_zip(path: &Path) {
+        let mut zip = ZipWriter::new(fs::File::create(path).unwrap());
+        for (name, content) in [
+            ("readme.md", "# Readme"),
+            ("docs/guide.txt", "guide"),
+            ("docs/api/index.html", "<html></html>"),
+            ("src/main.rs", "fn main() {}"),
+        ] {
+            zip.start_file::<_, ()>(name, FileOptions::default())
+                .unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn extract_selected_entries_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let zip_path = temp_dir.path().join("x.zip");
+        create_zip(&zip_path);
+        let out = temp_dir.path().join("out");
+        fs::create_dir(&out).unwrap();
+        let zip = zip_path.to_string_lossy().to_string();
+
+        let result = extract_archive_entries_impl(
+            vec![format!("{}!/docs/", zip), format!("{}!/src/main.rs", zip)],
+            out.to_string_lossy().to_string(),
+            None,
+            create_test_settings_state(),
+        );
+        assert!(result.is_ok(), "Failed to extract entries: {:?}", result);
+
+        assert_eq!(
+            fs::read_to_string(out.join("docs/guide.txt")).unwrap(),
+            "guide"
+        );
+        assert!(out.join("docs/api/index.html").exists());
+        assert_eq!(
+            fs::read_to_string(out.join("main.rs")).unwrap(),
+            "fn main() {}"
+        );
+        assert!(!out.join("readme.md").exists());
+        assert!(!out.join("src").exists());
+        assert!(zip_path.exists(), "The archive must be kept");
+    }
+
+    #[test]
+    fn failed_to_extract_entries_because_entry_does_not_exist_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let zip_path = temp_dir.path().join("x.zip");
+        create_zip(&zip_path);
+
+        let result = extract_archive_entries_impl(
+            vec![format!("{}!/missing", zip_path.display())],
+            temp_dir.path().to_string_lossy().to_string(),
+            None,
+            create_test_settings_state(),
+        );
+        assert!(result.unwrap_err().contains("ResourceNotFound"));
+    }
+
+    #[test]
+    fn failed_to_extract_entries_because_path_is_not_in_archive_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+
+        let result = extract_archive_entries_impl(
+            vec![temp_dir
+                .path()
+                .join("file.txt")
+                .to_string_lossy()
+                .to_string()],
+            temp_dir.path().to_string_lossy().to_string(),
+            None,
+            create_test_settings_state(),
+        );
+        let err = result.unwrap_err();
+        assert!(err.contains("InvalidInput"));
+        assert!(err.contains("Not a path inside an archive"));
+    }
+}
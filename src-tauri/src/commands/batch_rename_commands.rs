@@ -0,0 +1,795 @@
+use crate::error_handling::{Error, ErrorCode};
+use crate::{log_error, log_info};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Where a generated token (counter or date) is inserted into the file stem.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub enum InsertPosition {
+    Prefix,
+    Suffix,
+}
+
+/// Case transformation applied to the file stem.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub enum CaseMode {
+    Lower,
+    Upper,
+    Title,
+}
+
+/// Which timestamp of the file metadata is used for date tokens.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub enum DateSource {
+    Created,
+    Modified,
+    Accessed,
+}
+
+/// A single rename rule. Rules are applied in the given order to every path of the batch.
+///
+/// All rules except `ChangeExtension` operate on the file stem (the name without extension),
+/// so a regex or case change never touches the extension by accident.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(tag = "type")]
+pub enum RenameRule {
+    /// Regex find/replace on the stem. The replacement supports capture groups like `$1`.
+    RegexReplace {
+        pattern: String,
+        replacement: String,
+        #[serde(default)]
+        case_insensitive: bool,
+    },
+    /// Changes the case of the stem.
+    ChangeCase { mode: CaseMode },
+    /// Inserts a counter `start + index * step`, zero padded to `padding` digits.
+    Counter {
+        start: u64,
+        step: u64,
+        padding: usize,
+        position: InsertPosition,
+        #[serde(default)]
+        separator: String,
+    },
+    /// Inserts a date taken from the file metadata, formatted with a chrono format string (e.g. `%Y-%m-%d`).
+    DateToken {
+        format: String,
+        source: DateSource,
+        position: InsertPosition,
+        #[serde(default)]
+        separator: String,
+    },
+    /// Replaces the extension. An empty string removes the extension.
+    ChangeExtension { extension: String },
+}
+
+/// One planned rename of the batch.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct BatchRenameEntry {
+    pub old_path: String,
+    pub new_path: String,
+    pub unchanged: bool,
+}
+
+/// Why a planned target path cannot be used.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub enum CollisionReason {
+    /// Several paths of the batch would be renamed to the same target.
+    DuplicateTarget,
+    /// The target already exists on disk and is not renamed away by the batch.
+    ExistingPath,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct RenameCollision {
+    pub new_path: String,
+    pub sources: Vec<String>,
+    pub reason: CollisionReason,
+}
+
+/// The result of planning (and optionally applying) a batch rename.
+///
+/// `cycles` lists rename chains like `a -> b -> a`. They are not an error, the batch is
+/// applied in two phases over temporary names, but the UI may want to point them out.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct BatchRenamePlan {
+    pub entries: Vec<BatchRenameEntry>,
+    pub collisions: Vec<RenameCollision>,
+    pub cycles: Vec<Vec<String>>,
+    pub applied: bool,
+}
+
+fn split_name(path: &Path) -> (String, Option<String>) {
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    // Hidden files like ".bashrc" have no extension, their whole name is the stem
+    match file_name.rfind('.') {
+        Some(index) if index > 0 => (
+            file_name[..index].to_string(),
+            Some(file_name[index + 1..].to_string()),
+        ),
+        _ => (file_name, None),
+    }
+}
+
+fn to_title_case(input: &str) -> String {
+    let mut result = String::with_capacity(input.len());
+    let mut start_of_word = true;
+    for c in input.chars() {
+        if c.is_whitespace() || c == '_' || c == '-' || c == '.' {
+            start_of_word = true;
+            result.push(c);
+        } else if start_of_word {
+            result.extend(c.to_uppercase());
+            start_of_word = false;
+        } else {
+            result.extend(c.to_lowercase());
+        }
+    }
+    result
+}
+
+fn insert_token(stem: &str, token: &str, position: &InsertPosition, separator: &str) -> String {
+    match position {
+        InsertPosition::Prefix => format!("{}{}{}", token, separator, stem),
+        InsertPosition::Suffix => format!("{}{}{}", stem, separator, token),
+    }
+}
+
+fn metadata_time(path: &Path, source: &DateSource) -> Result<SystemTime, String> {
+    let metadata = fs::symlink_metadata(path)
+        .map_err(|e| format!("Failed to read metadata of '{}': {}", path.display(), e))?;
+    let time = match source {
+        DateSource::Created => metadata.created(),
+        DateSource::Modified => metadata.modified(),
+        DateSource::Accessed => metadata.accessed(),
+    };
+    time.map_err(|e| format!("Timestamp not available for '{}': {}", path.display(), e))
+}
+
+/// Compiles the patterns of the regex rules once for the whole batch.
+///
+/// # Returns
+/// * `Ok(Vec<Option<Regex>>)` - One entry per rule, `Some` for regex rules.
+/// * `Err(String)` - If a pattern is not a valid regex.
+fn compile_rules(rules: &[RenameRule]) -> Result<Vec<Option<Regex>>, String> {
+    rules
+        .iter()
+        .map(|rule| match rule {
+            RenameRule::RegexReplace {
+                pattern,
+                case_insensitive,
+                ..
+            } => RegexBuilder::new(pattern)
+                .case_insensitive(*case_insensitive)
+                .build()
+                .map(Some)
+                .map_err(|e| format!("Invalid regex '{}': {}", pattern, e)),
+            _ => Ok(None),
+        })
+        .collect()
+}
+
+/// Computes the new file name for a single path by applying all rules in order.
+///
+/// # Arguments
+/// * `path` - The path whose file name is transformed.
+/// * `index` - The position of the path inside the batch, used for counters.
+/// * `rules` - The rules to apply.
+/// * `regexes` - The compiled patterns of the rules, see `compile_rules`.
+///
+/// # Returns
+/// * `Ok(String)` - The new file name (not the full path).
+/// * `Err(String)` - If a rule is invalid or the resulting name is not a valid file name.
+fn apply_rules(
+    path: &Path,
+    index: usize,
+    rules: &[RenameRule],
+    regexes: &[Option<Regex>],
+) -> Result<String, String> {
+    let (mut stem, mut extension) = split_name(path);
+
+    for (rule, regex) in rules.iter().zip(regexes) {
+        match rule {
+            RenameRule::RegexReplace { replacement, .. } => {
+                if let Some(regex) = regex {
+                    stem = regex.replace_all(&stem, replacement.as_str()).into_owned();
+                }
+            }
+            RenameRule::ChangeCase { mode } => {
+                stem = match mode {
+                    CaseMode::Lower => stem.to_lowercase(),
+                    CaseMode::Upper => stem.to_uppercase(),
+                    CaseMode::Title => to_title_case(&stem),
+                };
+            }
+            RenameRule::Counter {
+                start,
+                step,
+                padding,
+                position,
+                separator,
+            } => {
+                let value = start.saturating_add(step.saturating_mul(index as u64));
+                let token = format!("{:0width$}", value, width = *padding);
+                stem = insert_token(&stem, &token, position, separator);
+            }
+            RenameRule::DateToken {
+                format,
+                source,
+                position,
+                separator,
+            } => {
+                let time = metadata_time(path, source)?;
+                let datetime: chrono::DateTime<chrono::Local> = time.into();
+                let mut token = String::new();
+                // chrono reports invalid format strings as fmt errors instead of panicking here
+                write!(token, "{}", datetime.format(format))
+                    .map_err(|_| format!("Invalid date format: {}", format))?;
+                stem = insert_token(&stem, &token, position, separator);
+            }
+            RenameRule::ChangeExtension { extension: new_extension } => {
+                let trimmed = new_extension.trim_start_matches('.');
+                extension = if trimmed.is_empty() {
+                    None
+                } else {
+                    Some(trimmed.to_string())
+                };
+            }
+        }
+    }
+
+    let new_name = match extension {
+        Some(ext) => format!("{}.{}", stem, ext),
+        None => stem,
+    };
+
+    if new_name.is_empty() || new_name == "." || new_name == ".." {
+        return Err(format!(
+            "Rules produce an invalid name for '{}'",
+            path.display()
+        ));
+    }
+    if new_name.contains('/') || new_name.contains('\\') || new_name.contains('\0') {
+        return Err(format!(
+            "Rules produce a name containing path separators for '{}': {}",
+            path.display(),
+            new_name
+        ));
+    }
+
+    Ok(new_name)
+}
+
+/// Returns true if both paths point to the same file on disk, e.g. for case-only renames
+/// on case-insensitive file systems.
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+fn find_collisions(entries: &[BatchRenameEntry]) -> Vec<RenameCollision> {
+    let mut collisions = Vec::new();
+
+    // Paths that are renamed away by the batch free their old location
+    let moving_sources: HashSet<&str> = entries
+        .iter()
+        .filter(|e| !e.unchanged)
+        .map(|e| e.old_path.as_str())
+        .collect();
+
+    let mut targets: HashMap<&str, Vec<&str>> = HashMap::new();
+    for entry in entries {
+        targets
+            .entry(entry.new_path.as_str())
+            .or_default()
+            .push(entry.old_path.as_str());
+    }
+
+    for entry in entries.iter().filter(|e| !e.unchanged) {
+        let sources = &targets[entry.new_path.as_str()];
+        if sources.len() > 1 {
+            if !collisions
+                .iter()
+                .any(|c: &RenameCollision| c.new_path == entry.new_path)
+            {
+                collisions.push(RenameCollision {
+                    new_path: entry.new_path.clone(),
+                    sources: sources.iter().map(|s| s.to_string()).collect(),
+                    reason: CollisionReason::DuplicateTarget,
+                });
+            }
+            continue;
+        }
+
+        let new_path = Path::new(&entry.new_path);
+        if new_path.symlink_metadata().is_ok()
+            && !moving_sources.contains(entry.new_path.as_str())
+            && !is_same_file(Path::new(&entry.old_path), new_path)
+        {
+            collisions.push(RenameCollision {
+                new_path: entry.new_path.clone(),
+                sources: vec![entry.old_path.clone()],
+                reason: CollisionReason::ExistingPath,
+            });
+        }
+    }
+
+    collisions
+}
+
+fn find_cycles(entries: &[BatchRenameEntry]) -> Vec<Vec<String>> {
+    let mapping: HashMap<&str, &str> = entries
+        .iter()
+        .filter(|e| !e.unchanged)
+        .map(|e| (e.old_path.as_str(), e.new_path.as_str()))
+        .collect();
+
+    let mut cycles = Vec::new();
+    let mut visited: HashSet<&str> = HashSet::new();
+
+    for entry in entries.iter().filter(|e| !e.unchanged) {
+        let start = entry.old_path.as_str();
+        if visited.contains(start) {
+            continue;
+        }
+
+        let mut chain = vec![start];
+        let mut current = start;
+        while let Some(&next) = mapping.get(current) {
+            if next == start {
+                cycles.push(chain.iter().map(|s| s.to_string()).collect());
+                break;
+            }
+            if chain.contains(&next) || visited.contains(next) {
+                break;
+            }
+            chain.push(next);
+            current = next;
+        }
+        visited.extend(chain);
+    }
+
+    cycles
+}
+
+/// Builds the rename plan for the given paths without touching the file system.
+pub fn plan_batch_rename(paths: &[String], rules: &[RenameRule]) -> Result<BatchRenamePlan, String> {
+    let regexes = compile_rules(rules).map_err(|e| {
+        log_error!("Failed to compile rename rules: {}", e);
+        Error::new(ErrorCode::InvalidInput, e).to_json()
+    })?;
+    let mut entries = Vec::with_capacity(paths.len());
+    let mut seen = HashSet::new();
+
+    for (index, path) in paths.iter().enumerate() {
+        let path_obj = Path::new(path);
+        if path_obj.symlink_metadata().is_err() {
+            log_error!("Path does not exist: {}", path);
+            return Err(Error::new(
+                ErrorCode::ResourceNotFound,
+                format!("Path does not exist: {}", path),
+            )
+            .to_json());
+        }
+        if !seen.insert(path.as_str()) {
+            log_error!("Path is listed more than once: {}", path);
+            return Err(Error::new(
+                ErrorCode::InvalidInput,
+                format!("Path is listed more than once: {}", path),
+            )
+            .to_json());
+        }
+
+        let new_name = apply_rules(path_obj, index, rules, &regexes).map_err(|e| {
+            log_error!("Failed to apply rename rules: {}", e);
+            Error::new(ErrorCode::InvalidInput, e).to_json()
+        })?;
+        let new_path = path_obj
+            .parent()
+            .unwrap_or_else(|| Path::new(""))
+            .join(&new_name)
+            .to_string_lossy()
+            .to_string();
+
+        entries.push(BatchRenameEntry {
+            unchanged: new_path == *path,
+            old_path: path.clone(),
+            new_path,
+        });
+    }
+
+    let collisions = find_collisions(&entries);
+    let cycles = find_cycles(&entries);
+
+    Ok(BatchRenamePlan {
+        entries,
+        collisions,
+        cycles,
+        applied: false,
+    })
+}
+
+fn temporary_path_for(path: &Path, index: usize) -> PathBuf {
+    let parent = path.parent().unwrap_or_else(|| Path::new(""));
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let mut attempt = 0;
+    loop {
+        let candidate = parent.join(format!(
+            ".{}.batch-rename-{}-{}.tmp",
+            file_name, index, attempt
+        ));
+        if candidate.symlink_metadata().is_err() {
+            return candidate;
+        }
+        attempt += 1;
+    }
+}
+
+fn rollback(completed: &[(PathBuf, PathBuf)]) {
+    for (from, to) in completed.iter().rev() {
+        if let Err(e) = fs::rename(to, from) {
+            log_error!(
+                "Failed to roll back rename '{}' -> '{}': {}",
+                to.display(),
+                from.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Applies a plan. Every source is first moved to a temporary name and then to its final
+/// name, which resolves swaps and longer cycles. If any step fails, all completed steps
+/// are undone in reverse order.
+fn apply_plan(plan: &BatchRenamePlan) -> Result<(), String> {
+    let renames: Vec<(PathBuf, PathBuf)> = plan
+        .entries
+        .iter()
+        .filter(|e| !e.unchanged)
+        .map(|e| (PathBuf::from(&e.old_path), PathBuf::from(&e.new_path)))
+        .collect();
+
+    let mut completed: Vec<(PathBuf, PathBuf)> = Vec::with_capacity(renames.len() * 2);
+    let mut staged: Vec<(PathBuf, PathBuf)> = Vec::with_capacity(renames.len());
+
+    // Phase 1: move every source out of the way
+    for (index, (old_path, new_path)) in renames.iter().enumerate() {
+        let temp_path = temporary_path_for(old_path, index);
+        if let Err(e) = fs::rename(old_path, &temp_path) {
+            log_error!("Failed to rename '{}': {}", old_path.display(), e);
+            rollback(&completed);
+            return Err(Error::new(
+                ErrorCode::InternalError,
+                format!(
+                    "Failed to rename '{}': {}. All changes were rolled back",
+                    old_path.display(),
+                    e
+                ),
+            )
+            .to_json());
+        }
+        completed.push((old_path.clone(), temp_path.clone()));
+        staged.push((temp_path, new_path.clone()));
+    }
+
+    // Phase 2: move the temporary files to their final names
+    for (temp_path, new_path) in staged {
+        if new_path.symlink_metadata().is_ok() {
+            log_error!("Target appeared during rename: {}", new_path.display());
+            rollback(&completed);
+            return Err(Error::new(
+                ErrorCode::ResourceAlreadyExists,
+                format!(
+                    "Target already exists: {}. All changes were rolled back",
+                    new_path.display()
+                ),
+            )
+            .to_json());
+        }
+        if let Err(e) = fs::rename(&temp_path, &new_path) {
+            log_error!("Failed to rename to '{}': {}", new_path.display(), e);
+            rollback(&completed);
+            return Err(Error::new(
+                ErrorCode::InternalError,
+                format!(
+                    "Failed to rename to '{}': {}. All changes were rolled back",
+                    new_path.display(),
+                    e
+                ),
+            )
+            .to_json());
+        }
+        completed.push((temp_path, new_path));
+    }
+
+    Ok(())
+}
+
+/// Renames many files at once using a list of rules.
+/// With `dry_run` set, only the plan with the old-to-new mapping, collisions and cycles is returned.
+/// Otherwise the whole batch is applied atomically: either every file is renamed or, on failure,
+/// every already renamed file is restored.
+///
+/// # Arguments
+/// * `paths` - The absolute paths to rename. Their order determines the counter values.
+/// * `rules` - The rules applied to each file name in order.
+/// * `dry_run` - If true, nothing is renamed.
+///
+/// # Returns
+/// * `Ok(String)` - The `BatchRenamePlan` as a JSON string.
+/// * `Err(String)` - If a path does not exist, a rule is invalid, the plan has collisions or renaming failed.
+///
+/// # Example
+/// ```rust
+/// let rules = vec![RenameRule::Counter {
+///     start: 1, step: 1, padding: 3, position: InsertPosition::Prefix, separator: "_".to_string(),
+/// }];
+/// let result = batch_rename(vec!["/photos/a.jpg".to_string()], rules, true).await;
+/// match result {
+///     Ok(plan) => println!("Plan: {}", plan),
+///     Err(err) => println!("Error planning rename: {}", err),
+/// }
+/// ```
+#[tauri::command]
+pub async fn batch_rename(
+    paths: Vec<String>,
+    rules: Vec<RenameRule>,
+    dry_run: bool,
+) -> Result<String, String> {
+    if paths.is_empty() {
+        log_error!("No paths provided for batch rename");
+        return Err(Error::new(
+            ErrorCode::InvalidInput,
+            "No paths provided for batch rename".to_string(),
+        )
+        .to_json());
+    }
+
+    let mut plan = plan_batch_rename(&paths, &rules)?;
+
+    if !dry_run {
+        if !plan.collisions.is_empty() {
+            log_error!("Batch rename has {} collisions", plan.collisions.len());
+            return Err(Error::new(
+                ErrorCode::ResourceAlreadyExists,
+                format!(
+                    "Batch rename has {} collisions, e.g. {}",
+                    plan.collisions.len(),
+                    plan.collisions[0].new_path
+                ),
+            )
+            .to_json());
+        }
+
+        apply_plan(&plan)?;
+        plan.applied = true;
+        log_info!(
+            "Batch rename applied to {} paths",
+            plan.entries.iter().filter(|e| !e.unchanged).count()
+        );
+    }
+
+    serde_json::to_string(&plan).map_err(|err| {
+        log_error!("Failed to serialize rename plan: {}", err);
+        Error::new(
+            ErrorCode::InternalError,
+            format!("Failed to serialize rename plan: {}", err),
+        )
+        .to_json()
+    })
+}
+
+#[cfg(test)]
+mod tests_batch_rename_commands {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn create_files(dir: &Path, names: &[&str]) -> Vec<String> {
+        names
+            .iter()
+            .map(|name| {
+                let path = dir.join(name);
+                fs::write(&path, name.as_bytes()).expect("Failed to create test file");
+                path.to_string_lossy().to_string()
+            })
+            .collect()
+    }
+
+    fn parse_plan(json: &str) -> BatchRenamePlan {
+        serde_json::from_str(json).expect("Failed to parse plan")
+    }
+
+    #[tokio::test]
+    async fn dry_run_does_not_touch_files_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let paths = create_files(temp_dir.path(), &["a.txt", "b.txt"]);
+
+        let rules = vec![RenameRule::ChangeCase {
+            mode: CaseMode::Upper,
+        }];
+        let result = batch_rename(paths.clone(), rules, true).await;
+        assert!(result.is_ok(), "Dry run failed: {:?}", result);
+
+        let plan = parse_plan(&result.unwrap());
+        assert!(!plan.applied);
+        assert_eq!(
+            plan.entries[0].new_path,
+            temp_dir.path().join("A.txt").to_string_lossy()
+        );
+        for path in paths {
+            assert!(Path::new(&path).exists(), "Dry run must not rename files");
+        }
+    }
+
+    #[tokio::test]
+    async fn regex_counter_and_extension_rules_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let paths = create_files(temp_dir.path(), &["IMG_1234.jpeg", "IMG_5678.jpeg"]);
+
+        let rules = vec![
+            RenameRule::RegexReplace {
+                pattern: r"^img_(\d+)$".to_string(),
+                replacement: "holiday-$1".to_string(),
+                case_insensitive: true,
+            },
+            RenameRule::Counter {
+                start: 1,
+                step: 1,
+                padding: 3,
+                position: InsertPosition::Prefix,
+                separator: "_".to_string(),
+            },
+            RenameRule::ChangeExtension {
+                extension: ".jpg".to_string(),
+            },
+        ];
+
+        let result = batch_rename(paths, rules, false).await;
+        assert!(result.is_ok(), "Batch rename failed: {:?}", result);
+        assert!(parse_plan(&result.unwrap()).applied);
+
+        assert!(temp_dir.path().join("001_holiday-1234.jpg").exists());
+        assert!(temp_dir.path().join("002_holiday-5678.jpg").exists());
+        assert!(!temp_dir.path().join("IMG_1234.jpeg").exists());
+    }
+
+    #[test]
+    fn date_token_rule_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let paths = create_files(temp_dir.path(), &["scan.pdf"]);
+
+        let modified = fs::metadata(&paths[0]).unwrap().modified().unwrap();
+        let expected_date = chrono::DateTime::<chrono::Local>::from(modified)
+            .format("%Y%m%d")
+            .to_string();
+
+        let rules = vec![RenameRule::DateToken {
+            format: "%Y%m%d".to_string(),
+            source: DateSource::Modified,
+            position: InsertPosition::Suffix,
+            separator: "-".to_string(),
+        }];
+        let plan = plan_batch_rename(&paths, &rules).expect("Planning failed");
+
+        assert_eq!(
+            plan.entries[0].new_path,
+            temp_dir
+                .path()
+                .join(format!("scan-{}.pdf", expected_date))
+                .to_string_lossy()
+        );
+    }
+
+    #[tokio::test]
+    async fn duplicate_targets_are_reported_as_collisions_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let paths = create_files(temp_dir.path(), &["one.txt", "two.txt"]);
+
+        let rules = vec![RenameRule::RegexReplace {
+            pattern: ".*".to_string(),
+            replacement: "same".to_string(),
+            case_insensitive: false,
+        }];
+        let plan = plan_batch_rename(&paths, &rules).expect("Planning failed");
+        assert_eq!(plan.collisions.len(), 1);
+        assert_eq!(plan.collisions[0].reason, CollisionReason::DuplicateTarget);
+
+        let result = batch_rename(paths.clone(), rules, false).await;
+        assert!(result.is_err(), "Collisions must prevent renaming");
+        assert!(result.unwrap_err().contains("409"));
+        for path in paths {
+            assert!(Path::new(&path).exists(), "Files must stay untouched");
+        }
+    }
+
+    #[test]
+    fn existing_target_is_reported_as_collision_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let paths = create_files(temp_dir.path(), &["report.txt"]);
+        create_files(temp_dir.path(), &["report_final.txt"]);
+
+        let rules = vec![RenameRule::RegexReplace {
+            pattern: "$".to_string(),
+            replacement: "_final".to_string(),
+            case_insensitive: false,
+        }];
+        let plan = plan_batch_rename(&paths, &rules).expect("Planning failed");
+        assert_eq!(plan.collisions.len(), 1);
+        assert_eq!(plan.collisions[0].reason, CollisionReason::ExistingPath);
+    }
+
+    #[tokio::test]
+    async fn swapping_names_is_detected_as_cycle_and_applied_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let paths = create_files(temp_dir.path(), &["a.txt", "b.txt"]);
+
+        let rules = vec![
+            RenameRule::RegexReplace {
+                pattern: "^a$".to_string(),
+                replacement: "tmp".to_string(),
+                case_insensitive: false,
+            },
+            RenameRule::RegexReplace {
+                pattern: "^b$".to_string(),
+                replacement: "a".to_string(),
+                case_insensitive: false,
+            },
+            RenameRule::RegexReplace {
+                pattern: "^tmp$".to_string(),
+                replacement: "b".to_string(),
+                case_insensitive: false,
+            },
+        ];
+
+        let result = batch_rename(paths, rules, false).await;
+        assert!(result.is_ok(), "Swap failed: {:?}", result);
+        let plan = parse_plan(&result.unwrap());
+        assert!(plan.collisions.is_empty());
+        assert_eq!(plan.cycles.len(), 1);
+
+        assert_eq!(fs::read_to_string(temp_dir.path().join("a.txt")).unwrap(), "b.txt");
+        assert_eq!(fs::read_to_string(temp_dir.path().join("b.txt")).unwrap(), "a.txt");
+    }
+
+    #[tokio::test]
+    async fn invalid_regex_is_rejected_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let paths = create_files(temp_dir.path(), &["file.txt"]);
+
+        let rules = vec![RenameRule::RegexReplace {
+            pattern: "(".to_string(),
+            replacement: "x".to_string(),
+            case_insensitive: false,
+        }];
+        let result = batch_rename(paths, rules, true).await;
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("InvalidInput"));
+    }
+
+    #[test]
+    fn title_case_and_hidden_files_test() {
+        assert_eq!(to_title_case("hello big_world"), "Hello Big_World");
+        assert_eq!(
+            split_name(Path::new("/tmp/.bashrc")),
+            (".bashrc".to_string(), None)
+        );
+        assert_eq!(
+            split_name(Path::new("/tmp/archive.tar.gz")),
+            ("archive.tar".to_string(), Some("gz".to_string()))
+        );
+    }
+}
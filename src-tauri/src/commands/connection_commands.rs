@@ -0,0 +1,563 @@
+use crate::commands::remote_file_system_commands::open_remote_session_impl;
+use crate::commands::sftp_file_system_operation_commands::{
+    close_sftp_session_impl, open_sftp_session_impl,
+};
+use crate::error_handling::{Error, ErrorCode};
+use crate::log_error;
+use crate::models::remote_connection::{RemoteConnection, RemoteConnectionInput};
+use crate::remote::{connect, RemoteProtocol, RemoteSessionConfig};
+use crate::state::connections_data::{ConnectionsError, ConnectionsState};
+use crate::state::remote_session_data::RemoteSessionState;
+use crate::state::secret_store_data::SecretStoreStatus;
+use crate::state::sftp_session_data::{SftpConnectionConfig, SftpSessionState};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tauri::State;
+
+fn lock_error() -> String {
+    Error::new(
+        ErrorCode::InternalError,
+        "Failed to acquire lock on connections state".to_string(),
+    )
+    .to_json()
+}
+
+fn to_json(error: ConnectionsError) -> String {
+    let error = error.to_error();
+    log_error!("Connection operation failed: {}", error.to_json());
+    error.to_json()
+}
+
+/// Returns whether the secret store exists and is unlocked. Passwords of saved connections can
+/// only be stored and used while it is unlocked.
+///
+/// # Returns
+/// * `Ok(SecretStoreStatus)` - `initialized` and `unlocked`.
+/// * `Err(String)` - An error if the state can't be locked.
+///
+/// # Example
+/// ```rust
+/// let status = get_secret_store_status(state)?;
+/// ```
+#[tauri::command]
+pub fn get_secret_store_status(
+    state: State<Arc<Mutex<ConnectionsState>>>,
+) -> Result<SecretStoreStatus, String> {
+    get_secret_store_status_impl(state.inner().clone())
+}
+
+pub fn get_secret_store_status_impl(
+    state: Arc<Mutex<ConnectionsState>>,
+) -> Result<SecretStoreStatus, String> {
+    Ok(state
+        .lock()
+        .map_err(|_| lock_error())?
+        .secret_store_status())
+}
+
+/// Unlocks the secret store with the master passphrase. The first call creates the store,
+/// encrypted with this passphrase.
+///
+/// # Arguments
+/// * `passphrase` - The master passphrase.
+///
+/// # Returns
+/// * `Ok(())` - If the store is unlocked.
+/// * `Err(String)` - `Unauthorized` with the details reason `WrongPassphrase` if the passphrase
+///   is wrong.
+///
+/// # Example
+/// ```rust
+/// unlock_secret_store("correct horse battery staple".to_string(), state)?;
+/// ```
+#[tauri::command]
+pub fn unlock_secret_store(
+    passphrase: String,
+    state: State<Arc<Mutex<ConnectionsState>>>,
+) -> Result<(), String> {
+    unlock_secret_store_impl(passphrase, state.inner().clone())
+}
+
+pub fn unlock_secret_store_impl(
+    passphrase: String,
+    state: Arc<Mutex<ConnectionsState>>,
+) -> Result<(), String> {
+    if passphrase.is_empty() {
+        return Err(Error::new(
+            ErrorCode::InvalidInput,
+            "The master passphrase is empty".to_string(),
+        )
+        .to_json());
+    }
+    let mut state = state.lock().map_err(|_| lock_error())?;
+    state.unlock_secrets(&passphrase).map_err(to_json)
+}
+
+/// Locks the secret store, the decrypted secrets are dropped from memory.
+///
+/// # Example
+/// ```rust
+/// lock_secret_store(state)?;
+/// ```
+#[tauri::command]
+pub fn lock_secret_store(state: State<Arc<Mutex<ConnectionsState>>>) -> Result<(), String> {
+    lock_secret_store_impl(state.inner().clone())
+}
+
+pub fn lock_secret_store_impl(state: Arc<Mutex<ConnectionsState>>) -> Result<(), String> {
+    state.lock().map_err(|_| lock_error())?.lock_secrets();
+    Ok(())
+}
+
+/// Encrypts the secret store with a new master passphrase, the store has to be unlocked.
+///
+/// # Arguments
+/// * `passphrase` - The new master passphrase.
+///
+/// # Returns
+/// * `Ok(())` - If the store was written with the new passphrase.
+/// * `Err(String)` - `Unauthorized` with the details reason `SecretStoreLocked` if it is locked.
+///
+/// # Example
+/// ```rust
+/// change_secret_store_passphrase("new passphrase".to_string(), state)?;
+/// ```
+#[tauri::command]
+pub fn change_secret_store_passphrase(
+    passphrase: String,
+    state: State<Arc<Mutex<ConnectionsState>>>,
+) -> Result<(), String> {
+    change_secret_store_passphrase_impl(passphrase, state.inner().clone())
+}
+
+pub fn change_secret_store_passphrase_impl(
+    passphrase: String,
+    state: Arc<Mutex<ConnectionsState>>,
+) -> Result<(), String> {
+    if passphrase.is_empty() {
+        return Err(Error::new(
+            ErrorCode::InvalidInput,
+            "The master passphrase is empty".to_string(),
+        )
+        .to_json());
+    }
+    let mut state = state.lock().map_err(|_| lock_error())?;
+    state
+        .change_secrets_passphrase(&passphrase)
+        .map_err(to_json)
+}
+
+/// Returns the saved connections without their secrets.
+///
+/// # Returns
+/// * `Ok(Vec<RemoteConnection>)` - The connections in the order they were added.
+/// * `Err(String)` - An error if the state can't be locked.
+///
+/// # Example
+/// ```rust
+/// let connections = list_remote_connections(state)?;
+/// ```
+#[tauri::command]
+pub fn list_remote_connections(
+    state: State<Arc<Mutex<ConnectionsState>>>,
+) -> Result<Vec<RemoteConnection>, String> {
+    list_remote_connections_impl(state.inner().clone())
+}
+
+pub fn list_remote_connections_impl(
+    state: Arc<Mutex<ConnectionsState>>,
+) -> Result<Vec<RemoteConnection>, String> {
+    Ok(state.lock().map_err(|_| lock_error())?.list())
+}
+
+/// Saves a new connection. The password or key passphrase goes to the secret store, which has
+/// to be unlocked for it.
+///
+/// # Arguments
+/// * `connection` - The name, host, port, user, auth method, key path, default path and jump hosts.
+/// * `secret` - The password or key passphrase, ignored for the agent and keyboard-interactive.
+///
+/// # Returns
+/// * `Ok(RemoteConnection)` - The saved connection with its new id.
+/// * `Err(String)` - `InvalidInput` for missing fields, `ResourceAlreadyExists` if the name is
+///   taken, `Unauthorized` with the details reason `SecretStoreLocked` if a secret is given
+///   while the store is locked.
+///
+/// # Example
+/// ```rust
+/// let connection = add_remote_connection(input, Some("hunter2".to_string()), state)?;
+/// ```
+#[tauri::command]
+pub fn add_remote_connection(
+    connection: RemoteConnectionInput,
+    secret: Option<String>,
+    state: State<Arc<Mutex<ConnectionsState>>>,
+) -> Result<RemoteConnection, String> {
+    add_remote_connection_impl(connection, secret, state.inner().clone())
+}
+
+pub fn add_remote_connection_impl(
+    connection: RemoteConnectionInput,
+    secret: Option<String>,
+    state: Arc<Mutex<ConnectionsState>>,
+) -> Result<RemoteConnection, String> {
+    let mut state = state.lock().map_err(|_| lock_error())?;
+    state.add(connection, secret).map_err(to_json)
+}
+
+/// Replaces the fields of a saved connection.
+///
+/// # Arguments
+/// * `id` - The id of the connection.
+/// * `connection` - The new fields.
+/// * `secret` - `None` keeps the stored secret, an empty string removes it.
+///
+/// # Returns
+/// * `Ok(RemoteConnection)` - The updated connection.
+/// * `Err(String)` - `ResourceNotFound` if there is no connection with this id, otherwise like
+///   `add_remote_connection`.
+///
+/// # Example
+/// ```rust
+/// let connection = update_remote_connection(id, input, None, state)?;
+/// ```
+#[tauri::command]
+pub fn update_remote_connection(
+    id: String,
+    connection: RemoteConnectionInput,
+    secret: Option<String>,
+    state: State<Arc<Mutex<ConnectionsState>>>,
+) -> Result<RemoteConnection, String> {
+    update_remote_connection_impl(id, connection, secret, state.inner().clone())
+}
+
+pub fn update_remote_connection_impl(
+    id: String,
+    connection: RemoteConnectionInput,
+    secret: Option<String>,
+    state: Arc<Mutex<ConnectionsState>>,
+) -> Result<RemoteConnection, String> {
+    let mut state = state.lock().map_err(|_| lock_error())?;
+    state.update(&id, connection, secret).map_err(to_json)
+}
+
+/// Removes a saved connection and its secret.
+///
+/// # Arguments
+/// * `id` - The id of the connection.
+///
+/// # Returns
+/// * `Ok(())` - If the connection was removed.
+/// * `Err(String)` - `ResourceNotFound` if there is no connection with this id.
+///
+/// # Example
+/// ```rust
+/// remove_remote_connection(id, state)?;
+/// ```
+#[tauri::command]
+pub fn remove_remote_connection(
+    id: String,
+    state: State<Arc<Mutex<ConnectionsState>>>,
+) -> Result<(), String> {
+    remove_remote_connection_impl(id, state.inner().clone())
+}
+
+pub fn remove_remote_connection_impl(
+    id: String,
+    state: Arc<Mutex<ConnectionsState>>,
+) -> Result<(), String> {
+    let mut state = state.lock().map_err(|_| lock_error())?;
+    state.remove(&id).map_err(to_json)
+}
+
+fn connection_config(
+    id: &str,
+    state: &Arc<Mutex<ConnectionsState>>,
+) -> Result<(SftpConnectionConfig, Option<String>), String> {
+    let state = state.lock().map_err(|_| lock_error())?;
+    let default_path = state.get(id).map_err(to_json)?.default_path;
+    let config = state.connection_config(id).map_err(to_json)?;
+    Ok((config, default_path))
+}
+
+fn remote_session_config(
+    id: &str,
+    state: &Arc<Mutex<ConnectionsState>>,
+) -> Result<(RemoteSessionConfig, Option<String>), String> {
+    let state = state.lock().map_err(|_| lock_error())?;
+    let default_path = state.get(id).map_err(to_json)?.default_path;
+    let config = state.remote_session_config(id).map_err(to_json)?;
+    Ok((config, default_path))
+}
+
+fn protocol_of(id: &str, state: &Arc<Mutex<ConnectionsState>>) -> Result<RemoteProtocol, String> {
+    let state = state.lock().map_err(|_| lock_error())?;
+    Ok(state.get(id).map_err(to_json)?.protocol)
+}
+
+/// Tests a saved connection by logging in and listing its default path. Host key and
+/// keyboard-interactive prompts of SFTP connections work like with `open_sftp_session`.
+///
+/// # Arguments
+/// * `id` - The id of the connection.
+///
+/// # Returns
+/// * `Ok(())` - If the login and the listing succeeded.
+/// * `Err(String)` - The error of the login or the listing.
+///
+/// # Example
+/// ```rust
+/// test_remote_connection(id, connections_state, sftp_state).await?;
+/// ```
+#[tauri::command]
+pub async fn test_remote_connection(
+    id: String,
+    state: State<'_, Arc<Mutex<ConnectionsState>>>,
+    sftp_state: State<'_, Arc<Mutex<SftpSessionState>>>,
+) -> Result<(), String> {
+    let state = state.inner().clone();
+    let sftp_state = sftp_state.inner().clone();
+    tokio::task::spawn_blocking(move || test_remote_connection_impl(id, state, sftp_state))
+        .await
+        .map_err(|e| Error::new(ErrorCode::InternalError, e.to_string()).to_json())?
+}
+
+pub fn test_remote_connection_impl(
+    id: String,
+    state: Arc<Mutex<ConnectionsState>>,
+    sftp_state: Arc<Mutex<SftpSessionState>>,
+) -> Result<(), String> {
+    if protocol_of(&id, &state)? != RemoteProtocol::Sftp {
+        // The session is closed again right away, so it is not registered in a state
+        let (config, default_path) = remote_session_config(&id, &state)?;
+        let sftp_auth = sftp_state.lock().map_err(|_| lock_error())?.auth();
+        let session = connect(config, sftp_auth)?;
+        let directory = default_path.unwrap_or_else(|| "/".to_string());
+        let listed = session.list_dir(&directory).map(|_| ());
+        session.disconnect();
+        return listed;
+    }
+    let (config, default_path) = connection_config(&id, &state)?;
+    let session_id = open_sftp_session_impl(config, sftp_state.clone())?;
+    let directory = default_path.unwrap_or_else(|| ".".to_string());
+    let session = sftp_state.lock().map_err(|_| lock_error())?.get(session_id);
+    let listed = session.map(|session| {
+        session.with_sftp(|sftp| {
+            sftp.readdir(Path::new(&directory))
+                .map(|_| ())
+                .map_err(|e| {
+                    Error::new(
+                        ErrorCode::ResourceNotFound,
+                        format!("Failed to list {}: {}", directory, e),
+                    )
+                    .to_json()
+                })
+        })
+    });
+    let _ = close_sftp_session_impl(session_id, sftp_state);
+    listed.unwrap_or(Ok(()))
+}
+
+/// Opens a session to a saved connection with its stored secret, so the frontend never
+/// handles the password or secret access key.
+///
+/// # Arguments
+/// * `id` - The id of the connection.
+///
+/// # Returns
+/// * `Ok(u64)` - The id of the new session, see `open_sftp_session` for SFTP connections and
+///   `open_remote_session` for the other protocols.
+/// * `Err(String)` - `Unauthorized` with the details reason `SecretStoreLocked` if the secret is
+///   needed while the store is locked, otherwise the error of opening the session.
+///
+/// # Example
+/// ```rust
+/// let session_id = open_remote_connection(id, connections_state, sftp_state, remote_state).await?;
+/// let listing = load_dir(session_id, connection.default_path.unwrap_or(".".to_string()), sftp_state)?;
+/// ```
+#[tauri::command]
+pub async fn open_remote_connection(
+    id: String,
+    state: State<'_, Arc<Mutex<ConnectionsState>>>,
+    sftp_state: State<'_, Arc<Mutex<SftpSessionState>>>,
+    remote_state: State<'_, Arc<Mutex<RemoteSessionState>>>,
+) -> Result<u64, String> {
+    let state = state.inner().clone();
+    let sftp_state = sftp_state.inner().clone();
+    let remote_state = remote_state.inner().clone();
+    tokio::task::spawn_blocking(move || {
+        open_remote_connection_impl(id, state, sftp_state, remote_state)
+    })
+    .await
+    .map_err(|e| Error::new(ErrorCode::InternalError, e.to_string()).to_json())?
+}
+
+pub fn open_remote_connection_impl(
+    id: String,
+    state: Arc<Mutex<ConnectionsState>>,
+    sftp_state: Arc<Mutex<SftpSessionState>>,
+    remote_state: Arc<Mutex<RemoteSessionState>>,
+) -> Result<u64, String> {
+    if protocol_of(&id, &state)? != RemoteProtocol::Sftp {
+        let (config, _) = remote_session_config(&id, &state)?;
+        return open_remote_session_impl(config, sftp_state, remote_state);
+    }
+    let (config, _) = connection_config(&id, &state)?;
+    open_sftp_session_impl(config, sftp_state)
+}
+
+#[cfg(test)]
+mod tests_connection_commands {
+    use super::*;
+    use crate::models::remote_connection::RemoteAuthMethod;
+    use crate::remote::test_servers::{FtpTestServer, TEST_PASSWORD, TEST_USER};
+    use crate::state::sftp_auth_data::SftpAuthContext;
+    use std::net::TcpListener;
+    use tempfile::{tempdir, TempDir};
+
+    fn states() -> (
+        TempDir,
+        Arc<Mutex<ConnectionsState>>,
+        Arc<Mutex<SftpSessionState>>,
+    ) {
+        let dir = tempdir().unwrap();
+        let state = ConnectionsState::new_with_paths(
+            dir.path().join("connections.json"),
+            dir.path().join("secrets.enc.json"),
+        );
+        let sftp_state = SftpSessionState::with_auth(SftpAuthContext::new(None));
+        (
+            dir,
+            Arc::new(Mutex::new(state)),
+            Arc::new(Mutex::new(sftp_state)),
+        )
+    }
+
+    fn input(port: u16) -> RemoteConnectionInput {
+        RemoteConnectionInput {
+            name: "local".to_string(),
+            protocol: RemoteProtocol::Sftp,
+            host: "127.0.0.1".to_string(),
+            port,
+            username: "explorer".to_string(),
+            auth_method: RemoteAuthMethod::Password,
+            private_key_path: None,
+            default_path: None,
+            proxy_jump: Vec::new(),
+            base_path: None,
+            region: None,
+            accept_invalid_certificates: false,
+        }
+    }
+
+    #[test]
+    fn test_connection_crud() {
+        let (_dir, state, _) = states();
+        unlock_secret_store_impl("master".to_string(), state.clone()).unwrap();
+        let connection =
+            add_remote_connection_impl(input(22), Some("secret".to_string()), state.clone())
+                .unwrap();
+        assert_eq!(
+            list_remote_connections_impl(state.clone()).unwrap(),
+            vec![connection.clone()]
+        );
+
+        let updated =
+            update_remote_connection_impl(connection.id.clone(), input(2222), None, state.clone())
+                .unwrap();
+        assert_eq!(updated.port, 2222);
+        assert!(updated.has_secret);
+
+        remove_remote_connection_impl(connection.id.clone(), state.clone()).unwrap();
+        let error = remove_remote_connection_impl(connection.id, state.clone()).unwrap_err();
+        assert!(error.contains("405"));
+    }
+
+    #[test]
+    fn test_secret_store_commands() {
+        let (_dir, state, _) = states();
+        let status = get_secret_store_status_impl(state.clone()).unwrap();
+        assert!(!status.initialized && !status.unlocked);
+        assert!(unlock_secret_store_impl(String::new(), state.clone()).is_err());
+
+        unlock_secret_store_impl("master".to_string(), state.clone()).unwrap();
+        change_secret_store_passphrase_impl("changed".to_string(), state.clone()).unwrap();
+        lock_secret_store_impl(state.clone()).unwrap();
+        let status = get_secret_store_status_impl(state.clone()).unwrap();
+        assert!(status.initialized && !status.unlocked);
+
+        let error = unlock_secret_store_impl("master".to_string(), state.clone()).unwrap_err();
+        let error: serde_json::Value = serde_json::from_str(&error).unwrap();
+        assert_eq!(error["details"]["reason"], "WrongPassphrase");
+        unlock_secret_store_impl("changed".to_string(), state).unwrap();
+    }
+
+    #[test]
+    fn test_open_needs_unlocked_store() {
+        let (_dir, state, sftp_state) = states();
+        unlock_secret_store_impl("master".to_string(), state.clone()).unwrap();
+        let connection =
+            add_remote_connection_impl(input(22), Some("secret".to_string()), state.clone())
+                .unwrap();
+        lock_secret_store_impl(state.clone()).unwrap();
+
+        let error =
+            open_remote_connection_impl(connection.id, state, sftp_state.clone(), remote_state())
+                .unwrap_err();
+        let error: serde_json::Value = serde_json::from_str(&error).unwrap();
+        assert_eq!(error["details"]["reason"], "SecretStoreLocked");
+        assert!(list_sessions_is_empty(&sftp_state));
+    }
+
+    #[test]
+    fn test_unreachable_connection_fails() {
+        // Bind and drop a listener to get a port nothing listens on
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let (_dir, state, sftp_state) = states();
+        let mut agent = input(port);
+        agent.auth_method = RemoteAuthMethod::Agent;
+        let connection = add_remote_connection_impl(agent, None, state.clone()).unwrap();
+        assert!(test_remote_connection_impl(connection.id, state, sftp_state.clone()).is_err());
+        assert!(list_sessions_is_empty(&sftp_state));
+    }
+
+    #[test]
+    fn test_open_ftp_connection() {
+        let server = FtpTestServer::start(false);
+        std::fs::create_dir(server.root().join("docs")).unwrap();
+        let (_dir, state, sftp_state) = states();
+        unlock_secret_store_impl("master".to_string(), state.clone()).unwrap();
+        let mut ftp = input(server.port());
+        ftp.protocol = RemoteProtocol::Ftp;
+        ftp.username = TEST_USER.to_string();
+        ftp.default_path = Some("/docs".to_string());
+        let connection =
+            add_remote_connection_impl(ftp, Some(TEST_PASSWORD.to_string()), state.clone())
+                .unwrap();
+
+        test_remote_connection_impl(connection.id.clone(), state.clone(), sftp_state.clone())
+            .unwrap();
+        let remote_state = remote_state();
+        let session_id = open_remote_connection_impl(
+            connection.id,
+            state,
+            sftp_state.clone(),
+            remote_state.clone(),
+        )
+        .unwrap();
+        assert!(remote_state.lock().unwrap().get(session_id).is_some());
+        assert!(list_sessions_is_empty(&sftp_state));
+    }
+
+    fn remote_state() -> Arc<Mutex<RemoteSessionState>> {
+        Arc::new(Mutex::new(RemoteSessionState::new()))
+    }
+
+    fn list_sessions_is_empty(sftp_state: &Arc<Mutex<SftpSessionState>>) -> bool {
+        sftp_state.lock().unwrap().sessions().is_empty()
+    }
+}
@@ -0,0 +1,603 @@
+use crate::error_handling::{Error, ErrorCode};
+use crate::state::jobs_data::{JobHandle, JobsState};
+use crate::state::pending_deletions_data::{PendingDeletionsState, CONFIRMATION_TOKEN_LIFETIME};
+use crate::state::SettingsState;
+use crate::{log_error, log_info};
+use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tauri::State;
+use walkdir::WalkDir;
+
+/// Upper bound for overwrite passes. 35 is the Gutmann method, more never helps.
+const MAX_OVERWRITE_PASSES: u32 = 35;
+
+const OVERWRITE_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Summary of what a deletion will remove, returned together with the confirmation token
+/// so the frontend can show it in the confirmation dialog.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct DeleteConfirmation {
+    pub token: String,
+    pub paths: Vec<String>,
+    pub item_count: u64,
+    pub total_bytes: u64,
+    pub expires_in_seconds: u64,
+}
+
+/// Final result of a delete job.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct DeleteSummary {
+    pub deleted_items: u64,
+    pub deleted_bytes: u64,
+    pub overwritten_files: u64,
+}
+
+enum DeleteError {
+    Cancelled,
+    Failed(String),
+}
+
+fn sorted_paths(paths: &[String]) -> Vec<String> {
+    let mut sorted = paths.to_vec();
+    sorted.sort();
+    sorted.dedup();
+    sorted
+}
+
+/// Counts the entries and bytes below the given paths without following symlinks.
+fn collect_deletion_stats(paths: &[String]) -> (u64, u64) {
+    let mut items = 0;
+    let mut bytes = 0;
+    for path in paths {
+        for entry in WalkDir::new(path).follow_links(false).into_iter().flatten() {
+            items += 1;
+            if entry.file_type().is_file() {
+                bytes += entry.metadata().map(|m| m.len()).unwrap_or(0);
+            }
+        }
+    }
+    (items, bytes)
+}
+
+fn validate_paths(paths: &[String]) -> Result<(), String> {
+    if paths.is_empty() {
+        log_error!("No paths provided for deletion");
+        return Err(Error::new(
+            ErrorCode::InvalidInput,
+            "No paths provided for deletion".to_string(),
+        )
+        .to_json());
+    }
+    for path in paths {
+        if fs::symlink_metadata(path).is_err() {
+            log_error!("Path does not exist: {}", path);
+            return Err(Error::new(
+                ErrorCode::ResourceNotFound,
+                format!("Path does not exist: {}", path),
+            )
+            .to_json());
+        }
+        // Refuse to wipe a file system root, that is never what the user wants
+        if Path::new(path).parent().is_none() {
+            log_error!("Refusing to delete root path: {}", path);
+            return Err(Error::new(
+                ErrorCode::InvalidInput,
+                format!("Refusing to delete root path: {}", path),
+            )
+            .to_json());
+        }
+    }
+    Ok(())
+}
+
+/// Consumes a confirmation token. The token must exist, must not be expired
+/// and must have been issued for exactly the same set of paths.
+fn consume_confirmation_token(
+    token: Option<&str>,
+    paths: &[String],
+    pending_deletions_state: &Arc<Mutex<PendingDeletionsState>>,
+) -> Result<(), String> {
+    let token = token.ok_or_else(|| {
+        log_error!("Permanent deletion requires a confirmation token");
+        Error::new(
+            ErrorCode::Unauthorized,
+            "Permanent deletion requires a confirmation token".to_string(),
+        )
+        .to_json()
+    })?;
+
+    let confirmed_paths = pending_deletions_state
+        .lock()
+        .map_err(|_| {
+            Error::new(
+                ErrorCode::InternalError,
+                "Failed to acquire lock on pending deletions".to_string(),
+            )
+            .to_json()
+        })?
+        .take(token)
+        .ok_or_else(|| {
+            log_error!("Invalid or expired confirmation token");
+            Error::new(
+                ErrorCode::Unauthorized,
+                "Invalid or expired confirmation token".to_string(),
+            )
+            .to_json()
+        })?;
+
+    if confirmed_paths != sorted_paths(paths) {
+        log_error!("Confirmation token was issued for different paths");
+        return Err(Error::new(
+            ErrorCode::Unauthorized,
+            "Confirmation token was issued for different paths".to_string(),
+        )
+        .to_json());
+    }
+    Ok(())
+}
+
+fn confirm_delete_enabled(settings_state: &Arc<Mutex<SettingsState>>) -> Result<bool, String> {
+    let lock_error = || {
+        Error::new(
+            ErrorCode::InternalError,
+            "Failed to acquire lock on settings state".to_string(),
+        )
+        .to_json()
+    };
+    let settings_state = settings_state.lock().map_err(|_| lock_error())?;
+    let settings = settings_state.0.lock().map_err(|_| lock_error())?;
+    Ok(settings.confirm_delete)
+}
+
+/// Overwrites the content of a regular file in place. All passes but the last write random
+/// data, the last pass writes zeros. Every pass is flushed to disk before the next one starts.
+fn overwrite_file(path: &Path, passes: u32, job: &JobHandle) -> Result<(), DeleteError> {
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .open(path)
+        .map_err(|e| DeleteError::Failed(format!("Failed to open '{}': {}", path.display(), e)))?;
+    let len = file
+        .metadata()
+        .map_err(|e| DeleteError::Failed(format!("Failed to read '{}': {}", path.display(), e)))?
+        .len();
+
+    let mut buffer = vec![0u8; OVERWRITE_CHUNK_SIZE];
+    let mut rng = rand::thread_rng();
+
+    for pass in 0..passes {
+        let last_pass = pass + 1 == passes;
+        file.seek(SeekFrom::Start(0)).map_err(|e| {
+            DeleteError::Failed(format!("Failed to seek '{}': {}", path.display(), e))
+        })?;
+
+        let mut remaining = len;
+        while remaining > 0 {
+            if job.is_cancelled() {
+                return Err(DeleteError::Cancelled);
+            }
+            let chunk = remaining.min(OVERWRITE_CHUNK_SIZE as u64) as usize;
+            if last_pass {
+                buffer[..chunk].fill(0);
+            } else {
+                rng.fill_bytes(&mut buffer[..chunk]);
+            }
+            file.write_all(&buffer[..chunk]).map_err(|e| {
+                DeleteError::Failed(format!("Failed to overwrite '{}': {}", path.display(), e))
+            })?;
+            remaining -= chunk as u64;
+        }
+
+        file.sync_all().map_err(|e| {
+            DeleteError::Failed(format!("Failed to flush '{}': {}", path.display(), e))
+        })?;
+    }
+    Ok(())
+}
+
+fn delete_entry(
+    path: &Path,
+    file_type: fs::FileType,
+    overwrite_passes: u32,
+    job: &JobHandle,
+    summary: &mut DeleteSummary,
+) -> Result<(), DeleteError> {
+    let mut size = 0;
+    if file_type.is_dir() {
+        fs::remove_dir(path).map_err(|e| {
+            DeleteError::Failed(format!("Failed to delete directory '{}': {}", path.display(), e))
+        })?;
+    } else {
+        // Symlinks are removed themselves, their targets are never touched
+        if file_type.is_file() {
+            size = fs::symlink_metadata(path).map(|m| m.len()).unwrap_or(0);
+            if overwrite_passes > 0 {
+                overwrite_file(path, overwrite_passes, job)?;
+                summary.overwritten_files += 1;
+            }
+        }
+        fs::remove_file(path).map_err(|e| {
+            DeleteError::Failed(format!("Failed to delete '{}': {}", path.display(), e))
+        })?;
+    }
+
+    summary.deleted_items += 1;
+    summary.deleted_bytes += size;
+    job.update_progress(|p| {
+        p.items_processed += 1;
+        p.bytes_processed += size;
+        p.current_path = Some(path.to_string_lossy().to_string());
+    });
+    Ok(())
+}
+
+/// Deletes the given paths recursively and reports progress on the job.
+/// Directory contents are removed before the directory itself.
+fn run_delete_job(
+    paths: &[String],
+    overwrite_passes: u32,
+    job: &JobHandle,
+) -> Result<DeleteSummary, DeleteError> {
+    let (items_total, bytes_total) = collect_deletion_stats(paths);
+    job.update_progress(|p| {
+        p.items_total = items_total;
+        p.bytes_total = bytes_total;
+    });
+
+    let mut summary = DeleteSummary::default();
+    for path in paths {
+        for entry in WalkDir::new(path).follow_links(false).contents_first(true) {
+            if job.is_cancelled() {
+                return Err(DeleteError::Cancelled);
+            }
+            let entry = entry.map_err(|e| {
+                DeleteError::Failed(format!("Failed to read directory entry: {}", e))
+            })?;
+            delete_entry(
+                entry.path(),
+                entry.file_type(),
+                overwrite_passes,
+                job,
+                &mut summary,
+            )?;
+        }
+    }
+    Ok(summary)
+}
+
+/// Prepares a permanent deletion and returns a one-time confirmation token together with the
+/// number of items and bytes that would be removed. The token is valid for two minutes and only
+/// for exactly the given paths.
+///
+/// # Arguments
+/// * `paths` - The absolute paths which should be deleted.
+/// * `pending_deletions_state` - The state the token is stored in until it is used.
+///
+/// # Returns
+/// * `Ok(DeleteConfirmation)` - The token and a summary for the confirmation dialog.
+/// * `Err(String)` - If a path does not exist.
+///
+/// # Example
+/// ```rust
+/// let confirmation = request_delete_confirmation(vec!["/tmp/build".to_string()], pending_deletions_state).await?;
+/// println!("Delete {} items?", confirmation.item_count);
+/// ```
+#[tauri::command]
+pub async fn request_delete_confirmation(
+    paths: Vec<String>,
+    pending_deletions_state: State<'_, Arc<Mutex<PendingDeletionsState>>>,
+) -> Result<DeleteConfirmation, String> {
+    request_delete_confirmation_impl(paths, pending_deletions_state.inner().clone())
+}
+
+pub fn request_delete_confirmation_impl(
+    paths: Vec<String>,
+    pending_deletions_state: Arc<Mutex<PendingDeletionsState>>,
+) -> Result<DeleteConfirmation, String> {
+    validate_paths(&paths)?;
+
+    let (item_count, total_bytes) = collect_deletion_stats(&paths);
+    let token = format!("{:032x}", rand::thread_rng().gen::<u128>());
+
+    pending_deletions_state
+        .lock()
+        .map_err(|_| {
+            Error::new(
+                ErrorCode::InternalError,
+                "Failed to acquire lock on pending deletions".to_string(),
+            )
+            .to_json()
+        })?
+        .insert(token.clone(), sorted_paths(&paths));
+
+    Ok(DeleteConfirmation {
+        token,
+        paths,
+        item_count,
+        total_bytes,
+        expires_in_seconds: CONFIRMATION_TOKEN_LIFETIME.as_secs(),
+    })
+}
+
+/// Permanently deletes files and directories without moving them to the trash.
+/// If `confirm_delete` is enabled in the settings, a token from `request_delete_confirmation`
+/// for the same paths is required. Optionally regular files are overwritten before deletion.
+/// The deletion runs as a background job, its progress can be polled with `get_job_status`.
+///
+/// # Arguments
+/// * `paths` - The absolute paths to delete.
+/// * `confirmation_token` - The token from `request_delete_confirmation`.
+/// * `overwrite_passes` - Number of overwrite passes for regular files (0 or `None` disables secure delete).
+/// * `settings_state` - The settings state, used for `confirm_delete`.
+/// * `pending_deletions_state` - The state holding the confirmation tokens.
+/// * `jobs_state` - The jobs state the deletion is registered in.
+///
+/// # Returns
+/// * `Ok(u64)` - The id of the started job.
+/// * `Err(String)` - If the token is missing or invalid, a path does not exist or the passes are out of range.
+///
+/// # Example
+/// ```rust
+/// let confirmation = request_delete_confirmation(paths.clone(), pending_deletions_state.clone()).await?;
+/// let job_id = delete_permanently(paths, Some(confirmation.token), Some(3), settings_state, pending_deletions_state, jobs_state).await?;
+/// ```
+#[tauri::command]
+pub async fn delete_permanently(
+    paths: Vec<String>,
+    confirmation_token: Option<String>,
+    overwrite_passes: Option<u32>,
+    settings_state: State<'_, Arc<Mutex<SettingsState>>>,
+    pending_deletions_state: State<'_, Arc<Mutex<PendingDeletionsState>>>,
+    jobs_state: State<'_, Arc<Mutex<JobsState>>>,
+) -> Result<u64, String> {
+    delete_permanently_impl(
+        paths,
+        confirmation_token,
+        overwrite_passes,
+        settings_state.inner().clone(),
+        pending_deletions_state.inner().clone(),
+        jobs_state.inner().clone(),
+    )
+}
+
+pub fn delete_permanently_impl(
+    paths: Vec<String>,
+    confirmation_token: Option<String>,
+    overwrite_passes: Option<u32>,
+    settings_state: Arc<Mutex<SettingsState>>,
+    pending_deletions_state: Arc<Mutex<PendingDeletionsState>>,
+    jobs_state: Arc<Mutex<JobsState>>,
+) -> Result<u64, String> {
+    validate_paths(&paths)?;
+
+    let overwrite_passes = overwrite_passes.unwrap_or(0);
+    if overwrite_passes > MAX_OVERWRITE_PASSES {
+        log_error!("Too many overwrite passes: {}", overwrite_passes);
+        return Err(Error::new(
+            ErrorCode::InvalidInput,
+            format!(
+                "Too many overwrite passes: {} (maximum is {})",
+                overwrite_passes, MAX_OVERWRITE_PASSES
+            ),
+        )
+        .to_json());
+    }
+
+    if confirm_delete_enabled(&settings_state)? {
+        consume_confirmation_token(
+            confirmation_token.as_deref(),
+            &paths,
+            &pending_deletions_state,
+        )?;
+    }
+
+    let job = JobsState::start_job(&jobs_state, "delete");
+    let job_id = job.id();
+
+    std::thread::Builder::new()
+        .name("delete-thread".to_string())
+        .spawn(move || match run_delete_job(&paths, overwrite_passes, &job) {
+            Ok(summary) => {
+                log_info!(
+                    "Permanently deleted {} items ({} bytes)",
+                    summary.deleted_items,
+                    summary.deleted_bytes
+                );
+                job.complete(serde_json::to_value(summary).ok());
+            }
+            Err(DeleteError::Cancelled) => {
+                log_info!("Permanent deletion was cancelled");
+                job.mark_cancelled();
+            }
+            Err(DeleteError::Failed(message)) => {
+                log_error!("Permanent deletion failed: {}", message);
+                job.fail(message);
+            }
+        })
+        .map_err(|e| {
+            log_error!("Failed to spawn delete thread: {}", e);
+            Error::new(
+                ErrorCode::InternalError,
+                format!("Failed to spawn delete thread: {}", e),
+            )
+            .to_json()
+        })?;
+
+    Ok(job_id)
+}
+
+#[cfg(test)]
+mod tests_delete_commands {
+    use super::*;
+    use crate::state::jobs_data::{create_test_settings_state, wait_for_job, JobStatus};
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    fn settings_with_confirm_delete(confirm_delete: bool) -> Arc<Mutex<SettingsState>> {
+        let state = create_test_settings_state();
+        state
+            .lock()
+            .unwrap()
+            .update_setting_field("confirm_delete", json!(confirm_delete))
+            .unwrap();
+        state
+    }
+
+    fn create_tree(root: &Path) -> String {
+        let dir = root.join("build");
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        fs::write(dir.join("a.o"), vec![1u8; 1000]).unwrap();
+        fs::write(dir.join("nested").join("b.o"), vec![2u8; 500]).unwrap();
+        dir.to_string_lossy().to_string()
+    }
+
+    #[tokio::test]
+    async fn delete_without_confirmation_when_disabled_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let dir = create_tree(temp_dir.path());
+        let jobs_state = Arc::new(Mutex::new(JobsState::new()));
+
+        let job_id = delete_permanently_impl(
+            vec![dir.clone()],
+            None,
+            None,
+            settings_with_confirm_delete(false),
+            Arc::new(Mutex::new(PendingDeletionsState::new())),
+            jobs_state.clone(),
+        )
+        .expect("Failed to start delete job");
+
+        let info = wait_for_job(&jobs_state, job_id);
+        assert_eq!(info.status, JobStatus::Completed, "{:?}", info.error);
+        assert_eq!(info.progress.items_total, 4);
+        assert_eq!(info.progress.bytes_processed, 1500);
+        assert!(!Path::new(&dir).exists());
+    }
+
+    #[tokio::test]
+    async fn delete_requires_token_when_confirm_delete_enabled_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let dir = create_tree(temp_dir.path());
+        let jobs_state = Arc::new(Mutex::new(JobsState::new()));
+        let settings_state = settings_with_confirm_delete(true);
+        let pending_deletions_state = Arc::new(Mutex::new(PendingDeletionsState::new()));
+
+        let result = delete_permanently_impl(
+            vec![dir.clone()],
+            None,
+            None,
+            settings_state.clone(),
+            pending_deletions_state.clone(),
+            jobs_state.clone(),
+        );
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("401"));
+        assert!(Path::new(&dir).exists());
+
+        let confirmation =
+            request_delete_confirmation_impl(vec![dir.clone()], pending_deletions_state.clone())
+                .expect("Failed to request confirmation");
+        assert_eq!(confirmation.item_count, 4);
+        assert_eq!(confirmation.total_bytes, 1500);
+
+        let job_id = delete_permanently_impl(
+            vec![dir.clone()],
+            Some(confirmation.token.clone()),
+            None,
+            settings_state.clone(),
+            pending_deletions_state.clone(),
+            jobs_state.clone(),
+        )
+        .expect("Failed to start delete job");
+        assert_eq!(wait_for_job(&jobs_state, job_id).status, JobStatus::Completed);
+        assert!(!Path::new(&dir).exists());
+
+        // Tokens can only be used once
+        let other_dir = create_tree(&temp_dir.path().join("second"));
+        let reused = delete_permanently_impl(
+            vec![other_dir],
+            Some(confirmation.token),
+            None,
+            settings_state,
+            pending_deletions_state,
+            jobs_state,
+        );
+        assert!(reused.is_err());
+    }
+
+    #[tokio::test]
+    async fn token_for_other_paths_is_rejected_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let first = temp_dir.path().join("first.txt");
+        let second = temp_dir.path().join("second.txt");
+        fs::write(&first, "1").unwrap();
+        fs::write(&second, "2").unwrap();
+
+        let pending_deletions_state = Arc::new(Mutex::new(PendingDeletionsState::new()));
+        let confirmation = request_delete_confirmation_impl(
+            vec![first.to_string_lossy().to_string()],
+            pending_deletions_state.clone(),
+        )
+        .unwrap();
+        let result = delete_permanently_impl(
+            vec![second.to_string_lossy().to_string()],
+            Some(confirmation.token),
+            None,
+            settings_with_confirm_delete(true),
+            pending_deletions_state,
+            Arc::new(Mutex::new(JobsState::new())),
+        );
+        assert!(result.is_err());
+        assert!(second.exists());
+    }
+
+    #[test]
+    fn secure_overwrite_zeroes_content_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let file = temp_dir.path().join("secret.txt");
+        fs::write(&file, "top secret content").unwrap();
+
+        let jobs_state = Arc::new(Mutex::new(JobsState::new()));
+        let job = JobsState::start_job(&jobs_state, "test");
+        assert!(overwrite_file(&file, 3, &job).is_ok());
+
+        let content = fs::read(&file).unwrap();
+        assert_eq!(content.len(), "top secret content".len());
+        assert!(content.iter().all(|b| *b == 0));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_targets_are_not_deleted_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let target = temp_dir.path().join("keep.txt");
+        fs::write(&target, "keep me").unwrap();
+        let dir = temp_dir.path().join("dir");
+        fs::create_dir(&dir).unwrap();
+        std::os::unix::fs::symlink(&target, dir.join("link")).unwrap();
+
+        let jobs_state = Arc::new(Mutex::new(JobsState::new()));
+        let job = JobsState::start_job(&jobs_state, "test");
+        let result = run_delete_job(&[dir.to_string_lossy().to_string()], 1, &job);
+        assert!(result.is_ok());
+        assert!(!dir.exists());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "keep me");
+    }
+
+    #[test]
+    fn missing_path_is_rejected_test() {
+        let result = delete_permanently_impl(
+            vec!["/definitely/not/existing/path".to_string()],
+            None,
+            None,
+            settings_with_confirm_delete(false),
+            Arc::new(Mutex::new(PendingDeletionsState::new())),
+            Arc::new(Mutex::new(JobsState::new())),
+        );
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("ResourceNotFound"));
+    }
+}
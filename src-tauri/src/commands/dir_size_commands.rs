@@ -0,0 +1,585 @@
+use crate::error_handling::{Error, ErrorCode};
+use crate::state::dir_size_cache_data::{CachedDirectory, DirSizeCacheState, HardLinkedFile};
+use crate::state::jobs_data::{JobHandle, JobsState};
+use crate::{log_error, log_info};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::State;
+
+/// Default number of levels returned by `get_disk_usage`.
+const DEFAULT_DISK_USAGE_DEPTH: usize = 3;
+
+/// Default number of children kept per directory by `get_disk_usage`.
+const DEFAULT_DISK_USAGE_TOP_N: usize = 20;
+
+/// Recursive size of one directory.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct DirectorySize {
+    pub path: String,
+    /// Sum of the file lengths.
+    pub apparent_size: u64,
+    /// Space allocated on disk. Equal to the apparent size on platforms without block counts.
+    pub disk_usage: u64,
+    pub file_count: u64,
+    pub directory_count: u64,
+    /// Number of directories which could not be read, e.g. because of missing permissions.
+    pub unreadable_count: u64,
+}
+
+/// A node of the disk usage tree returned by `get_disk_usage`.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct DiskUsageNode {
+    pub name: String,
+    #[serde(flatten)]
+    pub size: DirectorySize,
+    /// The largest subdirectories, sorted by disk usage. Empty below the requested depth.
+    pub children: Vec<DiskUsageNode>,
+}
+
+enum ScanError {
+    Cancelled,
+}
+
+#[cfg(unix)]
+fn disk_usage_of(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    // st_blocks is always counted in 512 byte units
+    metadata.blocks() * 512
+}
+
+#[cfg(not(unix))]
+fn disk_usage_of(metadata: &fs::Metadata) -> u64 {
+    metadata.len()
+}
+
+/// Returns the file as a `HardLinkedFile` if it has more than one link.
+#[cfg(unix)]
+fn hard_link_of(metadata: &fs::Metadata) -> Option<HardLinkedFile> {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.nlink() > 1).then(|| HardLinkedFile {
+        device: metadata.dev(),
+        inode: metadata.ino(),
+        apparent_size: metadata.len(),
+        disk_usage: disk_usage_of(metadata),
+    })
+}
+
+#[cfg(not(unix))]
+fn hard_link_of(_metadata: &fs::Metadata) -> Option<HardLinkedFile> {
+    None
+}
+
+/// Reads the direct children of a directory, or takes them from the cache if the mtime matches.
+fn read_directory(
+    path: &Path,
+    cache: &Arc<Mutex<DirSizeCacheState>>,
+) -> std::io::Result<CachedDirectory> {
+    let metadata = fs::symlink_metadata(path)?;
+    let modified = metadata.modified()?;
+
+    if let Some(cached) = cache
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .get(path, modified)
+    {
+        return Ok(cached);
+    }
+
+    let mut directory = CachedDirectory {
+        modified,
+        apparent_size: 0,
+        disk_usage: disk_usage_of(&metadata),
+        file_count: 0,
+        subdirectories: Vec::new(),
+        hard_links: Vec::new(),
+    };
+
+    for entry in fs::read_dir(path)?.flatten() {
+        let Ok(entry_metadata) = entry.path().symlink_metadata() else {
+            continue;
+        };
+        // Symlinks are counted with their own size and never followed
+        if entry_metadata.is_dir() {
+            directory.subdirectories.push(entry.path());
+        } else if let Some(hard_link) = hard_link_of(&entry_metadata) {
+            directory.hard_links.push(hard_link);
+        } else {
+            directory.apparent_size += entry_metadata.len();
+            directory.disk_usage += disk_usage_of(&entry_metadata);
+            directory.file_count += 1;
+        }
+    }
+
+    cache
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .insert(path.to_path_buf(), directory.clone());
+    Ok(directory)
+}
+
+/// A directory of a scan whose subdirectories are not all scanned yet.
+struct PendingDirectory {
+    node: DiskUsageNode,
+    keep_depth: usize,
+    subdirectories: std::vec::IntoIter<PathBuf>,
+}
+
+/// Reads one directory of a scan. Hard linked files are only counted if no other link to
+/// them was counted before, `seen_links` holds the device and inode of the counted ones.
+fn open_directory(
+    path: &Path,
+    keep_depth: usize,
+    cache: &Arc<Mutex<DirSizeCacheState>>,
+    job: &JobHandle,
+    seen_links: &mut HashSet<(u64, u64)>,
+) -> PendingDirectory {
+    let mut node = DiskUsageNode {
+        name: path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.to_string_lossy().to_string()),
+        size: DirectorySize {
+            path: path.to_string_lossy().to_string(),
+            ..Default::default()
+        },
+        children: Vec::new(),
+    };
+
+    let subdirectories = match read_directory(path, cache) {
+        Ok(directory) => {
+            node.size.apparent_size = directory.apparent_size;
+            node.size.disk_usage = directory.disk_usage;
+            node.size.file_count = directory.file_count;
+            for hard_link in &directory.hard_links {
+                if seen_links.insert((hard_link.device, hard_link.inode)) {
+                    node.size.apparent_size += hard_link.apparent_size;
+                    node.size.disk_usage += hard_link.disk_usage;
+                    node.size.file_count += 1;
+                }
+            }
+
+            job.update_progress(|p| {
+                p.items_processed += 1;
+                p.bytes_processed += node.size.apparent_size;
+                p.current_path = Some(node.size.path.clone());
+            });
+            directory.subdirectories
+        }
+        Err(err) => {
+            log_error!("Failed to read directory '{}': {}", path.display(), err);
+            node.size.unreadable_count = 1;
+            Vec::new()
+        }
+    };
+
+    PendingDirectory {
+        node,
+        keep_depth,
+        subdirectories: subdirectories.into_iter(),
+    }
+}
+
+/// Calculates the recursive size of a directory. Children are only kept in the returned
+/// node up to `keep_depth` levels and only the `top_n` largest ones per directory.
+///
+/// The tree is walked with an explicit stack, so deep trees can't overflow the stack of the
+/// job thread.
+fn scan_directory(
+    path: &Path,
+    keep_depth: usize,
+    top_n: usize,
+    cache: &Arc<Mutex<DirSizeCacheState>>,
+    job: &JobHandle,
+) -> Result<DiskUsageNode, ScanError> {
+    if job.is_cancelled() {
+        return Err(ScanError::Cancelled);
+    }
+
+    let mut seen_links = HashSet::new();
+    let mut stack = vec![open_directory(path, keep_depth, cache, job, &mut seen_links)];
+
+    while let Some(mut current) = stack.pop() {
+        if let Some(subdirectory) = current.subdirectories.next() {
+            if job.is_cancelled() {
+                return Err(ScanError::Cancelled);
+            }
+            let child = open_directory(
+                &subdirectory,
+                current.keep_depth.saturating_sub(1),
+                cache,
+                job,
+                &mut seen_links,
+            );
+            stack.push(current);
+            stack.push(child);
+            continue;
+        }
+
+        // All subdirectories are done, the directory is added to its parent
+        let mut node = current.node;
+        node.children
+            .sort_by_key(|child| std::cmp::Reverse(child.size.disk_usage));
+        node.children.truncate(top_n);
+
+        let Some(parent) = stack.last_mut() else {
+            return Ok(node);
+        };
+        parent.node.size.apparent_size += node.size.apparent_size;
+        parent.node.size.disk_usage += node.size.disk_usage;
+        parent.node.size.file_count += node.size.file_count;
+        parent.node.size.directory_count += node.size.directory_count + 1;
+        parent.node.size.unreadable_count += node.size.unreadable_count;
+        if parent.keep_depth > 0 {
+            parent.node.children.push(node);
+        }
+    }
+
+    unreachable!("The scanned directory is always finished last")
+}
+
+fn validate_directory(path: &str) -> Result<(), String> {
+    if !Path::new(path).is_dir() {
+        log_error!("Directory does not exist: {}", path);
+        return Err(Error::new(
+            ErrorCode::ResourceNotFound,
+            format!("Directory does not exist: {}", path),
+        )
+        .to_json());
+    }
+    Ok(())
+}
+
+fn spawn_job<F>(name: &str, job: JobHandle, work: F) -> Result<(), String>
+where
+    F: FnOnce(&JobHandle) -> Result<Option<serde_json::Value>, ScanError> + Send + 'static,
+{
+    std::thread::Builder::new()
+        .name(format!("{}-thread", name))
+        .spawn(move || match work(&job) {
+            Ok(result) => job.complete(result),
+            Err(ScanError::Cancelled) => {
+                log_info!("Directory size calculation was cancelled");
+                job.mark_cancelled();
+            }
+        })
+        .map(|_| ())
+        .map_err(|e| {
+            log_error!("Failed to spawn {} thread: {}", name, e);
+            Error::new(
+                ErrorCode::InternalError,
+                format!("Failed to spawn {} thread: {}", name, e),
+            )
+            .to_json()
+        })
+}
+
+/// Starts a background job which calculates the recursive size of each given directory.
+/// Every finished directory is appended to the job result right away, so the UI can fill in
+/// sizes while the remaining directories are still calculated.
+///
+/// # Arguments
+/// * `paths` - The directories to calculate the size for.
+/// * `jobs_state` - The jobs state the calculation is registered in.
+/// * `cache_state` - The directory size cache.
+///
+/// # Returns
+/// * `Ok(u64)` - The id of the job. Its result is a list of `DirectorySize`.
+/// * `Err(String)` - If one of the paths is not a directory.
+///
+/// # Example
+/// ```rust
+/// let job_id = calculate_directory_sizes(vec!["/home/user/Downloads".to_string()], jobs_state, cache_state).await?;
+/// ```
+#[tauri::command]
+pub async fn calculate_directory_sizes(
+    paths: Vec<String>,
+    jobs_state: State<'_, Arc<Mutex<JobsState>>>,
+    cache_state: State<'_, Arc<Mutex<DirSizeCacheState>>>,
+) -> Result<u64, String> {
+    let jobs_state = jobs_state.inner().clone();
+    let cache_state = cache_state.inner().clone();
+    tokio::task::spawn_blocking(move || {
+        calculate_directory_sizes_impl(paths, jobs_state, cache_state)
+    })
+    .await
+    .map_err(|e| Error::new(ErrorCode::InternalError, e.to_string()).to_json())?
+}
+
+pub fn calculate_directory_sizes_impl(
+    paths: Vec<String>,
+    jobs_state: Arc<Mutex<JobsState>>,
+    cache_state: Arc<Mutex<DirSizeCacheState>>,
+) -> Result<u64, String> {
+    for path in &paths {
+        validate_directory(path)?;
+    }
+
+    let job = JobsState::start_job(&jobs_state, "directory_size");
+    let job_id = job.id();
+
+    spawn_job("directory-size", job, move |job| {
+        let mut sizes: Vec<DirectorySize> = Vec::with_capacity(paths.len());
+        for path in &paths {
+            let node = scan_directory(Path::new(path), 0, 0, &cache_state, job)?;
+            sizes.push(node.size);
+            job.set_partial_result(serde_json::to_value(&sizes).unwrap_or_default());
+        }
+        Ok(serde_json::to_value(&sizes).ok())
+    })?;
+
+    Ok(job_id)
+}
+
+/// Starts a background job which builds a disk usage tree of a directory, similar to `ncdu`.
+/// Each level contains the largest subdirectories sorted by disk usage.
+///
+/// # Arguments
+/// * `path` - The directory to analyse.
+/// * `max_depth` - How many levels of subdirectories are returned (default 3).
+/// * `top_n` - How many subdirectories are kept per directory (default 20).
+/// * `jobs_state` - The jobs state the calculation is registered in.
+/// * `cache_state` - The directory size cache.
+///
+/// # Returns
+/// * `Ok(u64)` - The id of the job. Its result is a `DiskUsageNode`.
+/// * `Err(String)` - If the path is not a directory.
+///
+/// # Example
+/// ```rust
+/// let job_id = get_disk_usage("/home/user".to_string(), Some(2), Some(10), jobs_state, cache_state).await?;
+/// ```
+#[tauri::command]
+pub async fn get_disk_usage(
+    path: String,
+    max_depth: Option<usize>,
+    top_n: Option<usize>,
+    jobs_state: State<'_, Arc<Mutex<JobsState>>>,
+    cache_state: State<'_, Arc<Mutex<DirSizeCacheState>>>,
+) -> Result<u64, String> {
+    let jobs_state = jobs_state.inner().clone();
+    let cache_state = cache_state.inner().clone();
+    tokio::task::spawn_blocking(move || {
+        get_disk_usage_impl(path, max_depth, top_n, jobs_state, cache_state)
+    })
+    .await
+    .map_err(|e| Error::new(ErrorCode::InternalError, e.to_string()).to_json())?
+}
+
+pub fn get_disk_usage_impl(
+    path: String,
+    max_depth: Option<usize>,
+    top_n: Option<usize>,
+    jobs_state: Arc<Mutex<JobsState>>,
+    cache_state: Arc<Mutex<DirSizeCacheState>>,
+) -> Result<u64, String> {
+    validate_directory(&path)?;
+    let max_depth = max_depth.unwrap_or(DEFAULT_DISK_USAGE_DEPTH);
+    let top_n = top_n.unwrap_or(DEFAULT_DISK_USAGE_TOP_N);
+
+    let job = JobsState::start_job(&jobs_state, "disk_usage");
+    let job_id = job.id();
+
+    spawn_job("disk-usage", job, move |job| {
+        let root = PathBuf::from(&path);
+        let node = scan_directory(&root, max_depth, top_n, &cache_state, job)?;
+        Ok(serde_json::to_value(node).ok())
+    })?;
+
+    Ok(job_id)
+}
+
+/// Clears the directory size cache. Needed when files changed their size in place,
+/// because that does not change the mtime of the directory.
+///
+/// # Returns
+/// * `Ok(usize)` - The number of removed cache entries.
+#[tauri::command]
+pub async fn clear_directory_size_cache(
+    cache_state: State<'_, Arc<Mutex<DirSizeCacheState>>>,
+) -> Result<usize, String> {
+    let cache_state = cache_state.inner().clone();
+    tokio::task::spawn_blocking(move || clear_directory_size_cache_impl(cache_state))
+    .await
+    .map_err(|e| Error::new(ErrorCode::InternalError, e.to_string()).to_json())?
+}
+
+pub fn clear_directory_size_cache_impl(
+    cache_state: Arc<Mutex<DirSizeCacheState>>,
+) -> Result<usize, String> {
+    let mut cache = cache_state.lock().map_err(|_| {
+        Error::new(
+            ErrorCode::InternalError,
+            "Failed to acquire lock on directory size cache".to_string(),
+        )
+        .to_json()
+    })?;
+    Ok(cache.clear())
+}
+
+#[cfg(test)]
+mod tests_dir_size_commands {
+    use super::*;
+    use crate::state::jobs_data::{wait_for_job, JobStatus};
+    use tempfile::tempdir;
+
+    fn create_tree(root: &Path) {
+        fs::create_dir_all(root.join("big").join("nested")).unwrap();
+        fs::create_dir_all(root.join("small")).unwrap();
+        fs::write(root.join("top.txt"), vec![0u8; 100]).unwrap();
+        fs::write(root.join("big").join("a.bin"), vec![0u8; 5000]).unwrap();
+        fs::write(root.join("big").join("nested").join("b.bin"), vec![0u8; 3000]).unwrap();
+        fs::write(root.join("small").join("c.txt"), vec![0u8; 10]).unwrap();
+    }
+
+    #[test]
+    fn calculate_directory_sizes_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        create_tree(temp_dir.path());
+        let jobs_state = Arc::new(Mutex::new(JobsState::new()));
+        let cache_state = Arc::new(Mutex::new(DirSizeCacheState::new()));
+
+        let paths = vec![
+            temp_dir.path().to_string_lossy().to_string(),
+            temp_dir.path().join("big").to_string_lossy().to_string(),
+        ];
+        let job_id =
+            calculate_directory_sizes_impl(paths, jobs_state.clone(), cache_state.clone())
+                .expect("Failed to start job");
+
+        let info = wait_for_job(&jobs_state, job_id);
+        assert_eq!(info.status, JobStatus::Completed);
+        let sizes: Vec<DirectorySize> = serde_json::from_value(info.result.unwrap()).unwrap();
+        assert_eq!(sizes.len(), 2);
+        assert_eq!(sizes[0].apparent_size, 8110);
+        assert_eq!(sizes[0].file_count, 4);
+        assert_eq!(sizes[0].directory_count, 3);
+        assert_eq!(sizes[1].apparent_size, 8000);
+        assert!(!cache_state.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn cache_is_refreshed_when_directory_changes_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        create_tree(temp_dir.path());
+        let jobs_state = Arc::new(Mutex::new(JobsState::new()));
+        let cache_state = Arc::new(Mutex::new(DirSizeCacheState::new()));
+        let job = JobsState::start_job(&jobs_state, "test");
+
+        let first = scan_directory(temp_dir.path(), 0, 0, &cache_state, &job).ok().unwrap();
+        assert_eq!(first.size.apparent_size, 8110);
+
+        fs::write(temp_dir.path().join("small").join("d.txt"), vec![0u8; 90]).unwrap();
+        let second = scan_directory(temp_dir.path(), 0, 0, &cache_state, &job).ok().unwrap();
+        assert_eq!(second.size.apparent_size, 8200);
+        assert_eq!(second.size.file_count, 5);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hard_links_are_counted_once_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        create_tree(temp_dir.path());
+        fs::hard_link(
+            temp_dir.path().join("big").join("a.bin"),
+            temp_dir.path().join("small").join("a-link.bin"),
+        )
+        .unwrap();
+        fs::hard_link(
+            temp_dir.path().join("big").join("a.bin"),
+            temp_dir.path().join("a-link.bin"),
+        )
+        .unwrap();
+        let jobs_state = Arc::new(Mutex::new(JobsState::new()));
+        let cache_state = Arc::new(Mutex::new(DirSizeCacheState::new()));
+        let job = JobsState::start_job(&jobs_state, "test");
+
+        let root = scan_directory(temp_dir.path(), 0, 0, &cache_state, &job).ok().unwrap();
+        assert_eq!(root.size.apparent_size, 8110);
+        assert_eq!(root.size.file_count, 4);
+
+        // Each scan counts the file once, also when the directories come from the cache
+        let small = scan_directory(&temp_dir.path().join("small"), 0, 0, &cache_state, &job)
+            .ok()
+            .unwrap();
+        assert_eq!(small.size.apparent_size, 5010);
+        let again = scan_directory(temp_dir.path(), 0, 0, &cache_state, &job).ok().unwrap();
+        assert_eq!(again.size.apparent_size, 8110);
+    }
+
+    #[test]
+    fn deep_tree_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let mut deepest = temp_dir.path().to_path_buf();
+        for _ in 0..500 {
+            deepest.push("d");
+        }
+        fs::create_dir_all(&deepest).unwrap();
+        fs::write(deepest.join("leaf.txt"), vec![0u8; 42]).unwrap();
+        let jobs_state = Arc::new(Mutex::new(JobsState::new()));
+        let cache_state = Arc::new(Mutex::new(DirSizeCacheState::new()));
+        let job = JobsState::start_job(&jobs_state, "test");
+
+        let root = scan_directory(temp_dir.path(), 2, 1, &cache_state, &job).ok().unwrap();
+        assert_eq!(root.size.apparent_size, 42);
+        assert_eq!(root.size.directory_count, 500);
+        assert_eq!(root.children.len(), 1);
+        assert_eq!(root.children[0].children.len(), 1);
+        assert!(root.children[0].children[0].children.is_empty());
+    }
+
+    #[test]
+    fn disk_usage_tree_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        create_tree(temp_dir.path());
+        let jobs_state = Arc::new(Mutex::new(JobsState::new()));
+        let cache_state = Arc::new(Mutex::new(DirSizeCacheState::new()));
+
+        let job_id = get_disk_usage_impl(
+            temp_dir.path().to_string_lossy().to_string(),
+            Some(1),
+            Some(1),
+            jobs_state.clone(),
+            cache_state,
+        )
+        .expect("Failed to start job");
+
+        let info = wait_for_job(&jobs_state, job_id);
+        assert_eq!(info.status, JobStatus::Completed);
+        let root: DiskUsageNode = serde_json::from_value(info.result.unwrap()).unwrap();
+        assert_eq!(root.size.apparent_size, 8110);
+        assert_eq!(root.children.len(), 1, "Only the largest child is kept");
+        assert_eq!(root.children[0].name, "big");
+        assert!(
+            root.children[0].children.is_empty(),
+            "Children below max_depth are not returned"
+        );
+    }
+
+    #[test]
+    fn cancelled_scan_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        create_tree(temp_dir.path());
+        let jobs_state = Arc::new(Mutex::new(JobsState::new()));
+        let cache_state = Arc::new(Mutex::new(DirSizeCacheState::new()));
+        let job = JobsState::start_job(&jobs_state, "test");
+        jobs_state.lock().unwrap().cancel_job(job.id()).unwrap();
+
+        assert!(scan_directory(temp_dir.path(), 0, 0, &cache_state, &job).is_err());
+    }
+
+    #[test]
+    fn missing_directory_test() {
+        let result = get_disk_usage_impl(
+            "/definitely/not/existing/path".to_string(),
+            None,
+            None,
+            Arc::new(Mutex::new(JobsState::new())),
+            Arc::new(Mutex::new(DirSizeCacheState::new())),
+        );
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("ResourceNotFound"));
+    }
+}
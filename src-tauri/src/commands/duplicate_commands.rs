@@ -0,0 +1,937 @@
+use crate::commands::hash_commands::{hash_reader, ChecksumMethod};
+use crate::error_handling::{Error, ErrorCode};
+use crate::filesystem::fs_utils::{is_excluded, modified_millis};
+use crate::state::jobs_data::{JobHandle, JobsState};
+use crate::state::SettingsState;
+use crate::{log_error, log_info};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::State;
+
+/// Number of bytes at the start of a file which are hashed in the fast pass.
+const PARTIAL_HASH_BYTES: u64 = 64 * 1024;
+
+/// Method of the fast pass over the start of the files.
+const PARTIAL_HASH_METHOD: ChecksumMethod = ChecksumMethod::CRC32;
+
+/// Method of the full pass, which decides if files are duplicates.
+const FULL_HASH_METHOD: ChecksumMethod = ChecksumMethod::SHA256;
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct DuplicateSearchOptions {
+    /// Paths containing one of these patterns are skipped, matched like the excluded patterns
+    /// of the search engine. `None` uses the excluded patterns from the search engine settings.
+    pub excluded_patterns: Option<Vec<String>>,
+    /// Files smaller than this are ignored. Empty files are never reported.
+    pub min_size: u64,
+    /// Whether files and directories starting with a dot are searched.
+    pub include_hidden: bool,
+}
+
+impl Default for DuplicateSearchOptions {
+    fn default() -> Self {
+        Self {
+            excluded_patterns: None,
+            min_size: 1,
+            include_hidden: true,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct DuplicateFile {
+    pub path: String,
+    pub modified: u64, // as milliseconds since epoch
+}
+
+/// Files with identical content.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct DuplicateGroup {
+    pub size: u64,
+    /// SHA256 of the content.
+    pub hash: String,
+    /// The files of the group, newest first.
+    pub files: Vec<DuplicateFile>,
+    /// Space which is freed if only one copy is kept.
+    pub wasted_bytes: u64,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct DuplicateSearchResult {
+    /// Groups sorted by wasted bytes, largest first.
+    pub groups: Vec<DuplicateGroup>,
+    pub files_scanned: u64,
+    pub wasted_bytes: u64,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub enum DuplicateAction {
+    /// Moves every copy except the kept one to the trash.
+    Trash,
+    /// Replaces every copy except the kept one by a hard link to it.
+    Hardlink,
+}
+
+/// One group of duplicates the action is applied to.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct DuplicateResolution {
+    pub paths: Vec<String>,
+    /// The copy which is kept. `None` keeps the most recently modified copy.
+    pub keep: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub enum DuplicateActionStatus {
+    Trashed,
+    Hardlinked,
+    /// The file no longer has the content of the kept copy, so it was left untouched.
+    Changed,
+    /// The path is the kept file itself, e.g. spelled differently or a hard link to it, so it
+    /// was left untouched.
+    SameFile,
+    Failed,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct DuplicateActionResult {
+    pub path: String,
+    pub kept_path: String,
+    pub status: DuplicateActionStatus,
+    pub error: Option<String>,
+}
+
+/// The outcome of resolving one group.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct DuplicateGroupResult {
+    /// The copy which was kept, `None` if the group was not resolved.
+    pub kept_path: Option<String>,
+    /// The outcome for every other path of the group.
+    pub results: Vec<DuplicateActionResult>,
+    /// Why the group was not resolved, e.g. a missing file, as error json.
+    pub error: Option<String>,
+}
+
+enum SearchError {
+    Cancelled,
+}
+
+struct Candidate {
+    path: PathBuf,
+    modified: u64,
+}
+
+/// Identifies the file behind a path, so hard links and overlapping folders are only
+/// counted once.
+#[cfg(unix)]
+type FileIdentity = (u64, u64);
+#[cfg(not(unix))]
+type FileIdentity = PathBuf;
+
+#[cfg(unix)]
+fn file_identity(_path: &Path, metadata: &fs::Metadata) -> FileIdentity {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.dev(), metadata.ino())
+}
+
+#[cfg(not(unix))]
+fn file_identity(path: &Path, _metadata: &fs::Metadata) -> FileIdentity {
+    path.to_path_buf()
+}
+
+fn is_hidden_or_excluded(path: &Path, excluded_patterns: &[String], include_hidden: bool) -> bool {
+    let is_hidden = path
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+    (!include_hidden && is_hidden) || is_excluded(&path.to_string_lossy(), excluded_patterns)
+}
+
+/// Walks all folders and groups the regular files by size. Symlinks are never followed.
+fn collect_by_size(
+    paths: &[String],
+    options: &DuplicateSearchOptions,
+    excluded_patterns: &[String],
+    job: &JobHandle,
+) -> Result<(HashMap<u64, Vec<Candidate>>, u64), SearchError> {
+    let mut by_size: HashMap<u64, Vec<Candidate>> = HashMap::new();
+    let mut seen: HashSet<FileIdentity> = HashSet::new();
+    let mut files_scanned = 0;
+
+    for root in paths {
+        let walker = walkdir::WalkDir::new(root)
+            .follow_links(false)
+            .into_iter()
+            .filter_entry(|entry| {
+                entry.depth() == 0
+                    || !is_hidden_or_excluded(entry.path(), excluded_patterns, options.include_hidden)
+            });
+        for entry in walker {
+            if job.is_cancelled() {
+                return Err(SearchError::Cancelled);
+            }
+            let Ok(entry) = entry else {
+                continue;
+            };
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if metadata.len() < options.min_size.max(1)
+                || !seen.insert(file_identity(entry.path(), &metadata))
+            {
+                continue;
+            }
+            files_scanned += 1;
+            by_size.entry(metadata.len()).or_default().push(Candidate {
+                path: entry.into_path(),
+                modified: modified_millis(&metadata),
+            });
+        }
+    }
+
+    by_size.retain(|_, candidates| candidates.len() > 1);
+    Ok((by_size, files_scanned))
+}
+
+fn hash_file(path: &Path, method: &ChecksumMethod, limit: Option<u64>) -> std::io::Result<String> {
+    let file = fs::File::open(path)?;
+    hash_reader(method, &mut file.take(limit.unwrap_or(u64::MAX)))
+}
+
+/// Splits the candidates into groups with the same hash. Files which can't be read are
+/// dropped, groups with a single file are not duplicates.
+fn split_by_hash(
+    candidates: Vec<Candidate>,
+    method: &ChecksumMethod,
+    limit: Option<u64>,
+    job: &JobHandle,
+) -> Result<Vec<(String, Vec<Candidate>)>, SearchError> {
+    let mut by_hash: HashMap<String, Vec<Candidate>> = HashMap::new();
+    for candidate in candidates {
+        if job.is_cancelled() {
+            return Err(SearchError::Cancelled);
+        }
+        match hash_file(&candidate.path, method, limit) {
+            Ok(hash) => by_hash.entry(hash).or_default().push(candidate),
+            Err(err) => log_error!(
+                "Failed to hash '{}' for duplicate search: {}",
+                candidate.path.display(),
+                err
+            ),
+        }
+    }
+    Ok(by_hash
+        .into_iter()
+        .filter(|(_, candidates)| candidates.len() > 1)
+        .collect())
+}
+
+fn search_duplicates(
+    paths: &[String],
+    options: &DuplicateSearchOptions,
+    excluded_patterns: &[String],
+    job: &JobHandle,
+) -> Result<DuplicateSearchResult, SearchError> {
+    let (by_size, files_scanned) = collect_by_size(paths, options, excluded_patterns, job)?;
+
+    job.update_progress(|p| {
+        p.items_total = by_size.values().map(|c| c.len() as u64).sum();
+        p.bytes_total = by_size.iter().map(|(size, c)| size * c.len() as u64).sum();
+    });
+
+    let mut groups = Vec::new();
+    for (size, candidates) in by_size {
+        let count = candidates.len() as u64;
+        // Small files are read completely by the fast pass, the full pass alone is cheaper
+        let partial_groups = if size <= PARTIAL_HASH_BYTES {
+            vec![(String::new(), candidates)]
+        } else {
+            split_by_hash(
+                candidates,
+                &PARTIAL_HASH_METHOD,
+                Some(PARTIAL_HASH_BYTES),
+                job,
+            )?
+        };
+        for (_, candidates) in partial_groups {
+            for (hash, mut candidates) in split_by_hash(candidates, &FULL_HASH_METHOD, None, job)? {
+                candidates.sort_by(|a, b| b.modified.cmp(&a.modified).then(a.path.cmp(&b.path)));
+                groups.push(DuplicateGroup {
+                    size,
+                    hash,
+                    wasted_bytes: size * (candidates.len() as u64 - 1),
+                    files: candidates
+                        .into_iter()
+                        .map(|candidate| DuplicateFile {
+                            path: candidate.path.to_string_lossy().to_string(),
+                            modified: candidate.modified,
+                        })
+                        .collect(),
+                });
+            }
+        }
+        job.update_progress(|p| {
+            p.items_processed += count;
+            p.bytes_processed += size * count;
+        });
+    }
+
+    groups.sort_by(|a, b| {
+        b.wasted_bytes
+            .cmp(&a.wasted_bytes)
+            .then_with(|| a.files[0].path.cmp(&b.files[0].path))
+    });
+    Ok(DuplicateSearchResult {
+        files_scanned,
+        wasted_bytes: groups.iter().map(|group| group.wasted_bytes).sum(),
+        groups,
+    })
+}
+
+fn configured_excluded_patterns(
+    settings_state: &Arc<Mutex<SettingsState>>,
+) -> Result<Vec<String>, String> {
+    let lock_error = || {
+        Error::new(
+            ErrorCode::InternalError,
+            "Failed to acquire lock on settings state".to_string(),
+        )
+        .to_json()
+    };
+    let settings_state = settings_state.lock().map_err(|_| lock_error())?;
+    let settings = settings_state.0.lock().map_err(|_| lock_error())?;
+    Ok(settings
+        .backend_settings
+        .search_engine_config
+        .excluded_patterns
+        .clone()
+        .unwrap_or_default())
+}
+
+/// Starts a background job which finds files with identical content in one or more folders.
+/// Files are grouped by size first, then by a CRC32 of their first 64 KiB and finally by the
+/// SHA256 of the whole content, so only files of the same size are ever read completely.
+/// Hard links to the same file are counted once.
+///
+/// # Arguments
+/// * `paths` - The folders to search.
+/// * `options` - Optional exclusions, minimum size and whether hidden files are searched.
+/// * `jobs_state` - The jobs state the search is registered in.
+/// * `settings_state` - The settings with the default excluded patterns.
+///
+/// # Returns
+/// * `Ok(u64)` - The id of the job. Its result is a `DuplicateSearchResult`.
+/// * `Err(String)` - If no folder is given or one of the paths is not a directory.
+///
+/// # Example
+/// ```rust
+/// let job_id = find_duplicates(vec!["/home/user/Downloads".to_string()], None, jobs_state, settings_state)?;
+/// ```
+#[tauri::command]
+pub fn find_duplicates(
+    paths: Vec<String>,
+    options: Option<DuplicateSearchOptions>,
+    jobs_state: State<Arc<Mutex<JobsState>>>,
+    settings_state: State<Arc<Mutex<SettingsState>>>,
+) -> Result<u64, String> {
+    find_duplicates_impl(
+        paths,
+        options,
+        jobs_state.inner().clone(),
+        settings_state.inner().clone(),
+    )
+}
+
+pub fn find_duplicates_impl(
+    paths: Vec<String>,
+    options: Option<DuplicateSearchOptions>,
+    jobs_state: Arc<Mutex<JobsState>>,
+    settings_state: Arc<Mutex<SettingsState>>,
+) -> Result<u64, String> {
+    if paths.is_empty() {
+        log_error!("No folders provided for the duplicate search");
+        return Err(Error::new(
+            ErrorCode::InvalidInput,
+            "No folders provided for the duplicate search".to_string(),
+        )
+        .to_json());
+    }
+    for path in &paths {
+        if !Path::new(path).is_dir() {
+            log_error!("Directory does not exist: {}", path);
+            return Err(Error::new(
+                ErrorCode::ResourceNotFound,
+                format!("Directory does not exist: {}", path),
+            )
+            .to_json());
+        }
+    }
+
+    let options = options.unwrap_or_default();
+    let excluded_patterns = match &options.excluded_patterns {
+        Some(patterns) => patterns.clone(),
+        None => configured_excluded_patterns(&settings_state)?,
+    };
+
+    let job = JobsState::start_job(&jobs_state, "duplicate_search");
+    let job_id = job.id();
+
+    std::thread::Builder::new()
+        .name("duplicate-search-thread".to_string())
+        .spawn(
+            move || match search_duplicates(&paths, &options, &excluded_patterns, &job) {
+                Ok(result) => job.complete(serde_json::to_value(result).ok()),
+                Err(SearchError::Cancelled) => {
+                    log_info!("Duplicate search was cancelled");
+                    job.mark_cancelled();
+                }
+            },
+        )
+        .map_err(|e| {
+            log_error!("Failed to spawn duplicate search thread: {}", e);
+            Error::new(
+                ErrorCode::InternalError,
+                format!("Failed to spawn duplicate search thread: {}", e),
+            )
+            .to_json()
+        })?;
+
+    Ok(job_id)
+}
+
+/// Replaces `path` by a hard link to `kept`. The link is created next to the file first and
+/// renamed over it, so the file is never missing.
+fn replace_with_hardlink(kept: &Path, path: &Path) -> std::io::Result<()> {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let temp_path = path.with_file_name(format!(".{}.duplicate-link", file_name));
+    fs::hard_link(kept, &temp_path)?;
+    fs::rename(&temp_path, path).inspect_err(|_| {
+        let _ = fs::remove_file(&temp_path);
+    })
+}
+
+/// Resolves one group and returns the kept path with the outcome for the other paths.
+/// Paths listed twice are handled once, paths which turn out to be the kept file itself are
+/// never touched, otherwise no copy of the content would be left.
+fn resolve_group(
+    group: &DuplicateResolution,
+    action: DuplicateAction,
+) -> Result<(String, Vec<DuplicateActionResult>), String> {
+    let invalid = |message: String| {
+        log_error!("{}", message);
+        Error::new(ErrorCode::InvalidInput, message).to_json()
+    };
+    if group.paths.len() < 2 {
+        return Err(invalid(
+            "A duplicate group needs at least two paths".to_string(),
+        ));
+    }
+    for path in &group.paths {
+        if !Path::new(path).is_file() {
+            log_error!("File does not exist: {}", path);
+            return Err(Error::new(
+                ErrorCode::ResourceNotFound,
+                format!("File does not exist: {}", path),
+            )
+            .to_json());
+        }
+    }
+    let mut seen = HashSet::new();
+    let paths: Vec<&String> = group
+        .paths
+        .iter()
+        .filter(|path| seen.insert(path.as_str()))
+        .collect();
+
+    let kept_path = match &group.keep {
+        Some(keep) if paths.contains(&keep) => keep.clone(),
+        Some(keep) => {
+            return Err(invalid(format!(
+                "The kept file is not part of the group: {}",
+                keep
+            )))
+        }
+        None => paths
+            .iter()
+            .max_by_key(|path| {
+                fs::metadata(path)
+                    .map(|metadata| modified_millis(&metadata))
+                    .unwrap_or(0)
+            })
+            .map(|path| path.to_string())
+            .unwrap_or_default(),
+    };
+    if paths.len() < 2 {
+        return Err(invalid(format!(
+            "A duplicate group needs at least two different paths, got only {}",
+            kept_path
+        )));
+    }
+
+    let read_error = |path: &str, e: std::io::Error| {
+        log_error!("Failed to read '{}': {}", path, e);
+        Error::new(
+            ErrorCode::InternalError,
+            format!("Failed to read '{}': {}", path, e),
+        )
+        .to_json()
+    };
+    let kept_identity = fs::metadata(&kept_path)
+        .map(|metadata| file_identity(Path::new(&kept_path), &metadata))
+        .map_err(|e| read_error(&kept_path, e))?;
+    let kept_hash = hash_file(Path::new(&kept_path), &FULL_HASH_METHOD, None)
+        .map_err(|e| read_error(&kept_path, e))?;
+
+    let mut results = Vec::new();
+    for path in paths.into_iter().filter(|path| **path != kept_path) {
+        let mut result = DuplicateActionResult {
+            path: path.clone(),
+            kept_path: kept_path.clone(),
+            status: DuplicateActionStatus::Failed,
+            error: None,
+        };
+        let is_kept_file = fs::metadata(path)
+            .is_ok_and(|metadata| file_identity(Path::new(path), &metadata) == kept_identity);
+        if is_kept_file {
+            result.status = DuplicateActionStatus::SameFile;
+            results.push(result);
+            continue;
+        }
+        // The files may have changed since the search, only exact copies are touched
+        match hash_file(Path::new(path), &FULL_HASH_METHOD, None) {
+            Ok(hash) if hash != kept_hash => result.status = DuplicateActionStatus::Changed,
+            Ok(_) => {
+                let outcome = match action {
+                    DuplicateAction::Trash => trash::delete(path)
+                        .map(|_| DuplicateActionStatus::Trashed)
+                        .map_err(|e| e.to_string()),
+                    DuplicateAction::Hardlink => {
+                        replace_with_hardlink(Path::new(&kept_path), Path::new(path))
+                            .map(|_| DuplicateActionStatus::Hardlinked)
+                            .map_err(|e| e.to_string())
+                    }
+                };
+                match outcome {
+                    Ok(status) => result.status = status,
+                    Err(err) => {
+                        log_error!("Failed to resolve duplicate '{}': {}", path, err);
+                        result.error = Some(err);
+                    }
+                }
+            }
+            Err(err) => result.error = Some(err.to_string()),
+        }
+        results.push(result);
+    }
+    Ok((kept_path, results))
+}
+
+/// Resolves groups of duplicates found by `find_duplicates`: one copy per group is kept, the
+/// others are moved to the trash or replaced by hard links to the kept copy. Every copy is
+/// hashed again first and left untouched if its content changed since the search.
+///
+/// # Arguments
+/// * `groups` - The groups with their paths and optionally the copy to keep. Without an
+///   explicit choice the most recently modified copy is kept.
+/// * `action` - `Trash` or `Hardlink`. Hard links require all copies to be on the same volume.
+///
+/// # Returns
+/// * `Ok(Vec<DuplicateGroupResult>)` - The outcome of every group, in the order of `groups`.
+///   A group which is invalid, e.g. has less than two paths or a missing file, has an `error`
+///   and is left untouched, the other groups are resolved anyway.
+///
+/// # Example
+/// ```rust
+/// let results = resolve_duplicates(
+///     vec![DuplicateResolution { paths: vec!["/a/x.jpg".into(), "/b/x.jpg".into()], keep: None }],
+///     DuplicateAction::Trash,
+/// ).await?;
+/// ```
+#[tauri::command]
+pub async fn resolve_duplicates(
+    groups: Vec<DuplicateResolution>,
+    action: DuplicateAction,
+) -> Result<Vec<DuplicateGroupResult>, String> {
+    Ok(groups
+        .iter()
+        .map(|group| match resolve_group(group, action) {
+            Ok((kept_path, results)) => DuplicateGroupResult {
+                kept_path: Some(kept_path),
+                results,
+                error: None,
+            },
+            Err(error) => DuplicateGroupResult {
+                kept_path: None,
+                results: Vec::new(),
+                error: Some(error),
+            },
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests_duplicate_commands {
+    use super::*;
+    use crate::state::jobs_data::{create_test_settings_state, wait_for_job, JobStatus};
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    fn settings_with_excluded_patterns(excluded_patterns: &[&str]) -> Arc<Mutex<SettingsState>> {
+        let state = create_test_settings_state();
+        state
+            .lock()
+            .unwrap()
+            .update_setting_field(
+                "backend_settings.search_engine_config.excluded_patterns",
+                json!(excluded_patterns),
+            )
+            .unwrap();
+        state
+    }
+
+    fn run_search(
+        paths: Vec<String>,
+        options: Option<DuplicateSearchOptions>,
+        settings_state: Arc<Mutex<SettingsState>>,
+    ) -> DuplicateSearchResult {
+        let jobs_state = Arc::new(Mutex::new(JobsState::new()));
+        let job_id =
+            find_duplicates_impl(paths, options, jobs_state.clone(), settings_state).unwrap();
+        let info = wait_for_job(&jobs_state, job_id);
+        assert_eq!(info.status, JobStatus::Completed, "{:?}", info.error);
+        serde_json::from_value(info.result.unwrap()).unwrap()
+    }
+
+    fn paths_of(group: &DuplicateGroup) -> Vec<String> {
+        let mut paths: Vec<String> = group.files.iter().map(|f| f.path.clone()).collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn find_duplicates_across_folders_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let left = temp_dir.path().join("left");
+        let right = temp_dir.path().join("right");
+        fs::create_dir_all(left.join("nested")).unwrap();
+        fs::create_dir_all(&right).unwrap();
+
+        // Large files with the same start, only the full hash tells them apart
+        let mut photo = vec![7u8; PARTIAL_HASH_BYTES as usize + 100];
+        fs::write(left.join("photo.jpg"), &photo).unwrap();
+        fs::write(right.join("photo (1).jpg"), &photo).unwrap();
+        fs::write(left.join("nested").join("copy.jpg"), &photo).unwrap();
+        *photo.last_mut().unwrap() = 8;
+        fs::write(right.join("edited.jpg"), &photo).unwrap();
+
+        fs::write(left.join("a.txt"), "same text").unwrap();
+        fs::write(right.join("b.txt"), "same text").unwrap();
+        fs::write(right.join("c.txt"), "diff text").unwrap();
+        fs::write(left.join("empty1"), "").unwrap();
+        fs::write(right.join("empty2"), "").unwrap();
+
+        let result = run_search(
+            vec![
+                left.to_string_lossy().to_string(),
+                right.to_string_lossy().to_string(),
+            ],
+            None,
+            settings_with_excluded_patterns(&[]),
+        );
+
+        assert_eq!(result.files_scanned, 7, "Empty files must be ignored");
+        assert_eq!(result.groups.len(), 2);
+        let photos = &result.groups[0];
+        assert_eq!(
+            paths_of(photos),
+            vec![
+                left.join("nested")
+                    .join("copy.jpg")
+                    .to_string_lossy()
+                    .to_string(),
+                left.join("photo.jpg").to_string_lossy().to_string(),
+                right.join("photo (1).jpg").to_string_lossy().to_string(),
+            ]
+        );
+        assert_eq!(photos.wasted_bytes, 2 * photos.size);
+        assert_eq!(photos.hash.len(), 64, "The full hash must be a SHA256");
+
+        let texts = &result.groups[1];
+        assert_eq!(texts.size, 9);
+        assert_eq!(texts.wasted_bytes, 9);
+        assert_eq!(result.wasted_bytes, photos.wasted_bytes + 9);
+    }
+
+    #[test]
+    fn find_duplicates_honours_exclusions_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("node_modules")).unwrap();
+        fs::create_dir_all(root.join(".cache")).unwrap();
+        fs::write(root.join("a.txt"), "content").unwrap();
+        fs::write(root.join("node_modules").join("b.txt"), "content").unwrap();
+        fs::write(root.join(".cache").join("c.txt"), "content").unwrap();
+        let paths = vec![root.to_string_lossy().to_string()];
+
+        // The excluded patterns of the settings are used by default
+        let result = run_search(
+            paths.clone(),
+            Some(DuplicateSearchOptions {
+                include_hidden: false,
+                ..Default::default()
+            }),
+            settings_with_excluded_patterns(&["node_modules"]),
+        );
+        assert!(result.groups.is_empty());
+
+        let result = run_search(
+            paths.clone(),
+            Some(DuplicateSearchOptions {
+                excluded_patterns: Some(vec![".cache".to_string()]),
+                ..Default::default()
+            }),
+            settings_with_excluded_patterns(&["node_modules"]),
+        );
+        assert_eq!(result.groups.len(), 1);
+        assert_eq!(result.groups[0].files.len(), 2);
+        assert!(paths_of(&result.groups[0])[1].contains("node_modules"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn find_duplicates_counts_hardlinks_once_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let root = temp_dir.path();
+        fs::write(root.join("a.txt"), "content").unwrap();
+        fs::hard_link(root.join("a.txt"), root.join("b.txt")).unwrap();
+
+        // The same folder twice must not report files as their own duplicates either
+        let result = run_search(
+            vec![
+                root.to_string_lossy().to_string(),
+                root.to_string_lossy().to_string(),
+            ],
+            None,
+            settings_with_excluded_patterns(&[]),
+        );
+        assert_eq!(result.files_scanned, 1);
+        assert!(result.groups.is_empty());
+    }
+
+    #[test]
+    fn find_duplicates_fails_for_missing_folder_test() {
+        let jobs_state = Arc::new(Mutex::new(JobsState::new()));
+        let result = find_duplicates_impl(
+            vec!["/definitely/not/existing".to_string()],
+            None,
+            jobs_state,
+            settings_with_excluded_patterns(&[]),
+        );
+        assert!(result.unwrap_err().contains("ResourceNotFound"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn resolve_duplicates_with_hardlinks_keeps_newest_test() {
+        use std::os::unix::fs::MetadataExt;
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let old = temp_dir.path().join("old.txt");
+        let new = temp_dir.path().join("new.txt");
+        let changed = temp_dir.path().join("changed.txt");
+        fs::write(&old, "content").unwrap();
+        fs::write(&changed, "changed").unwrap();
+        fs::write(&new, "content").unwrap();
+        let now = std::time::SystemTime::now();
+        for (path, age) in [(&old, 3600), (&changed, 60)] {
+            fs::File::options()
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_modified(now - std::time::Duration::from_secs(age))
+                .unwrap();
+        }
+
+        let to_string = |path: &Path| path.to_string_lossy().to_string();
+        let results = resolve_duplicates(
+            vec![DuplicateResolution {
+                paths: vec![to_string(&old), to_string(&new), to_string(&changed)],
+                keep: None,
+            }],
+            DuplicateAction::Hardlink,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(results[0].kept_path, Some(to_string(&new)));
+        let results = &results[0].results;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].path, to_string(&old));
+        assert_eq!(results[0].kept_path, to_string(&new));
+        assert_eq!(results[0].status, DuplicateActionStatus::Hardlinked);
+        assert_eq!(results[1].status, DuplicateActionStatus::Changed);
+        assert_eq!(
+            fs::metadata(&old).unwrap().ino(),
+            fs::metadata(&new).unwrap().ino()
+        );
+        assert_eq!(fs::read_to_string(&changed).unwrap(), "changed");
+    }
+
+    #[tokio::test]
+    async fn resolve_duplicates_moves_copies_to_trash_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let keep = temp_dir.path().join("keep.txt");
+        let copy = temp_dir.path().join("copy.txt");
+        fs::write(&keep, "duplicate content").unwrap();
+        fs::write(&copy, "duplicate content").unwrap();
+
+        let results = resolve_duplicates(
+            vec![DuplicateResolution {
+                paths: vec![
+                    keep.to_string_lossy().to_string(),
+                    copy.to_string_lossy().to_string(),
+                ],
+                keep: Some(keep.to_string_lossy().to_string()),
+            }],
+            DuplicateAction::Trash,
+        )
+        .await
+        .unwrap();
+
+        let results = &results[0].results;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].status, DuplicateActionStatus::Trashed);
+        assert!(keep.exists());
+        assert!(!copy.exists());
+    }
+
+    #[tokio::test]
+    async fn resolve_duplicates_rejects_invalid_groups_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let a = temp_dir.path().join("a.txt");
+        fs::write(&a, "a").unwrap();
+        let a = a.to_string_lossy().to_string();
+
+        let result = resolve_duplicates(
+            vec![DuplicateResolution {
+                paths: vec![a.clone()],
+                keep: None,
+            }],
+            DuplicateAction::Trash,
+        )
+        .await
+        .unwrap();
+        assert!(result[0].error.as_ref().unwrap().contains("at least two paths"));
+
+        let result = resolve_duplicates(
+            vec![DuplicateResolution {
+                paths: vec![a.clone(), a.clone()],
+                keep: Some("/other.txt".to_string()),
+            }],
+            DuplicateAction::Trash,
+        )
+        .await
+        .unwrap();
+        assert!(result[0].error.as_ref().unwrap().contains("not part of the group"));
+
+        // The same path twice is no duplicate, the only copy is kept
+        let result = resolve_duplicates(
+            vec![DuplicateResolution {
+                paths: vec![a.clone(), a.clone()],
+                keep: None,
+            }],
+            DuplicateAction::Trash,
+        )
+        .await
+        .unwrap();
+        assert!(result[0].error.as_ref().unwrap().contains("two different paths"));
+        assert!(Path::new(&a).exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn resolve_duplicates_never_removes_the_kept_file_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let keep = temp_dir.path().join("keep.txt");
+        let link = temp_dir.path().join("link.txt");
+        let copy = temp_dir.path().join("copy.txt");
+        fs::write(&keep, "duplicate content").unwrap();
+        fs::write(&copy, "duplicate content").unwrap();
+        fs::hard_link(&keep, &link).unwrap();
+        let to_string = |path: &Path| path.to_string_lossy().to_string();
+        let other_spelling = to_string(&temp_dir.path().join(".").join("keep.txt"));
+
+        let results = resolve_duplicates(
+            vec![DuplicateResolution {
+                paths: vec![
+                    to_string(&keep),
+                    other_spelling.clone(),
+                    to_string(&link),
+                    to_string(&keep),
+                    to_string(&copy),
+                ],
+                keep: Some(to_string(&keep)),
+            }],
+            DuplicateAction::Trash,
+        )
+        .await
+        .unwrap();
+
+        let statuses: Vec<(String, DuplicateActionStatus)> = results[0]
+            .results
+            .iter()
+            .map(|result| (result.path.clone(), result.status.clone()))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                (other_spelling, DuplicateActionStatus::SameFile),
+                (to_string(&link), DuplicateActionStatus::SameFile),
+                (to_string(&copy), DuplicateActionStatus::Trashed),
+            ]
+        );
+        assert_eq!(fs::read_to_string(&keep).unwrap(), "duplicate content");
+        assert!(link.exists());
+    }
+
+    #[tokio::test]
+    async fn resolve_duplicates_reports_every_group_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let keep = temp_dir.path().join("keep.txt");
+        let copy = temp_dir.path().join("copy.txt");
+        fs::write(&keep, "duplicate content").unwrap();
+        fs::write(&copy, "duplicate content").unwrap();
+        let to_string = |path: &Path| path.to_string_lossy().to_string();
+
+        let results = resolve_duplicates(
+            vec![
+                DuplicateResolution {
+                    paths: vec![to_string(&keep), to_string(&copy)],
+                    keep: Some(to_string(&keep)),
+                },
+                DuplicateResolution {
+                    paths: vec![to_string(&keep), "/definitely/not/existing.txt".to_string()],
+                    keep: None,
+                },
+            ],
+            DuplicateAction::Trash,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].error, None);
+        assert_eq!(results[0].results[0].status, DuplicateActionStatus::Trashed);
+        assert!(!copy.exists());
+        assert_eq!(results[1].kept_path, None);
+        assert!(results[1].error.as_ref().unwrap().contains("ResourceNotFound"));
+    }
+}
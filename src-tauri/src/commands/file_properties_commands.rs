@@ -0,0 +1,590 @@
+#[cfg(unix)]
+use crate::commands::permission_commands::id_name;
+use crate::error_handling::{Error, ErrorCode};
+use crate::log_error;
+use crate::models::detailed_metadata::{AclEntry, AclTag, DetailedMetadata, ExtendedAttribute};
+use crate::models::{
+    format_system_time, get_access_permission_number, get_access_permission_string,
+};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// The ACLs are stored as extended attributes on Linux, they are shown separately.
+const ACL_ACCESS_ATTRIBUTE: &str = "system.posix_acl_access";
+const ACL_DEFAULT_ATTRIBUTE: &str = "system.posix_acl_default";
+
+/// How the value of an extended attribute is passed to `set_extended_attribute`.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+pub enum AttributeEncoding {
+    #[default]
+    Text,
+    Base64,
+}
+
+fn io_error(path: &Path, action: &str, error: std::io::Error) -> String {
+    let code = match error.kind() {
+        std::io::ErrorKind::PermissionDenied => ErrorCode::Unauthorized,
+        std::io::ErrorKind::NotFound => ErrorCode::ResourceNotFound,
+        std::io::ErrorKind::Unsupported => ErrorCode::NotImplementedForOS,
+        _ => ErrorCode::InternalError,
+    };
+    log_error!("Failed to {} '{}': {}", action, path.display(), error);
+    Error::new(
+        code,
+        format!("Failed to {} '{}': {}", action, path.display(), error),
+    )
+    .to_json()
+}
+
+fn unsupported_platform() -> String {
+    log_error!("Extended attributes are not supported on this OS");
+    Error::new(
+        ErrorCode::NotImplementedForOS,
+        "Extended attributes are not supported on this OS".to_string(),
+    )
+    .to_json()
+}
+
+/// Whether the error of `removexattr` means the attribute does not exist.
+fn is_missing_attribute(error: &std::io::Error) -> bool {
+    #[cfg(target_os = "linux")]
+    let missing = libc::ENODATA;
+    #[cfg(all(unix, not(target_os = "linux")))]
+    let missing = libc::ENOATTR;
+    #[cfg(not(unix))]
+    let missing = -1;
+    error.raw_os_error() == Some(missing)
+}
+
+/// Parses the binary form of a POSIX ACL as Linux stores it in `system.posix_acl_access`:
+/// a little-endian version 2 header followed by entries of tag, permissions and id.
+fn parse_posix_acl(data: &[u8]) -> Option<Vec<AclEntry>> {
+    let (header, entries) = data.split_at_checked(4)?;
+    if u32::from_le_bytes(header.try_into().ok()?) != 2 || entries.len() % 8 != 0 {
+        return None;
+    }
+
+    entries
+        .chunks_exact(8)
+        .map(|entry| {
+            let tag = match u16::from_le_bytes([entry[0], entry[1]]) {
+                0x01 => AclTag::UserObj,
+                0x02 => AclTag::User,
+                0x04 => AclTag::GroupObj,
+                0x08 => AclTag::Group,
+                0x10 => AclTag::Mask,
+                0x20 => AclTag::Other,
+                _ => return None,
+            };
+            let permissions = u16::from_le_bytes([entry[2], entry[3]]);
+            let id = u32::from_le_bytes([entry[4], entry[5], entry[6], entry[7]]);
+            let qualifier = matches!(tag, AclTag::User | AclTag::Group).then_some(id);
+
+            #[cfg(unix)]
+            let qualifier_name = qualifier.and_then(|id| id_name(id, tag == AclTag::Group));
+            #[cfg(not(unix))]
+            let qualifier_name = None;
+
+            Some(AclEntry {
+                tag,
+                qualifier,
+                qualifier_name,
+                permissions: [(4, 'r'), (2, 'w'), (1, 'x')]
+                    .iter()
+                    .map(|&(bit, c)| if permissions & bit != 0 { c } else { '-' })
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+/// Reads the extended attributes and the ACLs stored in them. Symlinks are not followed.
+fn read_extended_attributes(
+    path: &Path,
+) -> (
+    Vec<ExtendedAttribute>,
+    Option<Vec<AclEntry>>,
+    Option<Vec<AclEntry>>,
+) {
+    let mut attributes = Vec::new();
+    let mut acl = None;
+    let mut default_acl = None;
+    if !xattr::SUPPORTED_PLATFORM {
+        return (attributes, acl, default_acl);
+    }
+
+    let Ok(names) = xattr::list(path) else {
+        return (attributes, acl, default_acl);
+    };
+    for name in names {
+        let name = name.to_string_lossy().to_string();
+        let Ok(Some(value)) = xattr::get(path, &name) else {
+            continue;
+        };
+        match name.as_str() {
+            ACL_ACCESS_ATTRIBUTE => acl = parse_posix_acl(&value),
+            ACL_DEFAULT_ATTRIBUTE => default_acl = parse_posix_acl(&value),
+            _ => attributes.push(ExtendedAttribute {
+                name,
+                size: value.len(),
+                value_base64: base64::engine::general_purpose::STANDARD.encode(&value),
+                value_text: String::from_utf8(value).ok(),
+            }),
+        }
+    }
+    attributes.sort_by(|a, b| a.name.cmp(&b.name));
+    (attributes, acl, default_acl)
+}
+
+/// The flags of `lsattr`, read with the `FS_IOC_GETFLAGS` ioctl.
+#[cfg(target_os = "linux")]
+fn read_flags(path: &Path, metadata: &fs::Metadata) -> Vec<String> {
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::io::AsRawFd;
+
+    if !metadata.is_file() && !metadata.is_dir() {
+        return Vec::new();
+    }
+    let Ok(file) = fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(path)
+    else {
+        return Vec::new();
+    };
+    let mut flags: libc::c_int = 0;
+    // SAFETY: the ioctl writes a single int into `flags`, the descriptor is valid while `file` lives.
+    if unsafe { libc::ioctl(file.as_raw_fd(), libc::FS_IOC_GETFLAGS, &mut flags) } != 0 {
+        return Vec::new();
+    }
+    flag_names(
+        flags as u64,
+        &[
+            (0x04, "compressed"),
+            (0x10, "immutable"),
+            (0x20, "append_only"),
+            (0x40, "no_dump"),
+            (0x80, "no_atime"),
+        ],
+    )
+}
+
+/// The flags of `chflags`.
+#[cfg(target_os = "macos")]
+fn read_flags(_path: &Path, metadata: &fs::Metadata) -> Vec<String> {
+    use std::os::macos::fs::MetadataExt;
+
+    flag_names(
+        metadata.st_flags() as u64,
+        &[
+            (0x1, "no_dump"),
+            (0x2, "immutable"),
+            (0x4, "append_only"),
+            (0x20, "compressed"),
+            (0x8000, "hidden"),
+            (0x20000, "system_immutable"),
+            (0x40000, "system_append_only"),
+        ],
+    )
+}
+
+#[cfg(windows)]
+fn read_flags(_path: &Path, metadata: &fs::Metadata) -> Vec<String> {
+    use std::os::windows::fs::MetadataExt;
+
+    flag_names(
+        metadata.file_attributes() as u64,
+        &[
+            (0x1, "read_only"),
+            (0x2, "hidden"),
+            (0x4, "system"),
+            (0x20, "archive"),
+            (0x800, "compressed"),
+            (0x4000, "encrypted"),
+        ],
+    )
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn read_flags(_path: &Path, _metadata: &fs::Metadata) -> Vec<String> {
+    Vec::new()
+}
+
+#[allow(dead_code)]
+fn flag_names(flags: u64, names: &[(u64, &str)]) -> Vec<String> {
+    names
+        .iter()
+        .filter(|(bit, _)| flags & bit != 0)
+        .map(|(_, name)| name.to_string())
+        .collect()
+}
+
+/// Returns everything known about a file or directory: permissions, owner and group, inode,
+/// hard link count, device, flags, extended attributes and POSIX ACLs. This is slower than a
+/// directory listing and meant for the properties of a single entry.
+///
+/// # Arguments
+/// * `path` - The path of the file or directory. A symlink is described itself, not its target.
+///
+/// # Returns
+/// * `Ok(DetailedMetadata)` - The metadata of the path.
+/// * `Err(String)` - An error if the path does not exist or can't be read.
+///
+/// # Example
+/// ```rust
+/// let metadata = get_detailed_metadata("/srv/share/report.pdf".to_string())?;
+/// println!("{:?} {:?}", metadata.owner_name, metadata.extended_attributes);
+/// ```
+#[tauri::command]
+pub fn get_detailed_metadata(path: String) -> Result<DetailedMetadata, String> {
+    let entry_path = Path::new(&path);
+    let metadata = fs::symlink_metadata(entry_path)
+        .map_err(|e| io_error(entry_path, "read metadata of", e))?;
+    let is_symlink = metadata.file_type().is_symlink();
+    let symlink_target = is_symlink
+        .then(|| fs::read_link(entry_path).ok())
+        .flatten()
+        .map(|target| target.to_string_lossy().to_string());
+    let (extended_attributes, acl, default_acl) = read_extended_attributes(entry_path);
+    let time = |time: std::io::Result<std::time::SystemTime>| {
+        time.map_or_else(|_| String::new(), format_system_time)
+    };
+
+    #[allow(unused_mut)]
+    let mut details = DetailedMetadata {
+        name: entry_path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.clone()),
+        path: path.clone(),
+        is_directory: metadata.is_dir(),
+        is_symlink,
+        symlink_target,
+        size_in_bytes: metadata.len(),
+        access_rights_as_string: get_access_permission_string(
+            metadata.permissions(),
+            metadata.is_dir(),
+        ),
+        access_rights_as_number: get_access_permission_number(
+            metadata.permissions(),
+            metadata.is_dir(),
+        ),
+        created: time(metadata.created()),
+        last_modified: time(metadata.modified()),
+        accessed: time(metadata.accessed()),
+        inode: None,
+        hard_link_count: None,
+        device_id: None,
+        owner_id: None,
+        owner_name: None,
+        group_id: None,
+        group_name: None,
+        flags: read_flags(entry_path, &metadata),
+        extended_attributes,
+        acl,
+        default_acl,
+    };
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        details.inode = Some(metadata.ino());
+        details.hard_link_count = Some(metadata.nlink());
+        details.device_id = Some(metadata.dev());
+        details.owner_id = Some(metadata.uid());
+        details.owner_name = id_name(metadata.uid(), false);
+        details.group_id = Some(metadata.gid());
+        details.group_name = id_name(metadata.gid(), true);
+    }
+
+    Ok(details)
+}
+
+/// Sets an extended attribute of a file or directory. Symlinks are not followed.
+///
+/// # Arguments
+/// * `path` - The path of the file or directory.
+/// * `name` - The name of the attribute. On Linux it needs a namespace, e.g. `user.comment`.
+/// * `value` - The new value.
+/// * `encoding` - Whether `value` is text (default) or base64 encoded binary data.
+///
+/// # Returns
+/// * `Ok(())` - If the attribute was set.
+/// * `Err(String)` - `Unauthorized` if the OS refuses the change, `NotImplementedForOS` if the
+///   OS or file system does not support extended attributes.
+///
+/// # Example
+/// ```rust
+/// set_extended_attribute("/srv/share/report.pdf".to_string(), "user.reviewed_by".to_string(), "alice".to_string(), None)?;
+/// ```
+#[tauri::command]
+pub fn set_extended_attribute(
+    path: String,
+    name: String,
+    value: String,
+    encoding: Option<AttributeEncoding>,
+) -> Result<(), String> {
+    if !xattr::SUPPORTED_PLATFORM {
+        return Err(unsupported_platform());
+    }
+    if name.is_empty() {
+        log_error!("No attribute name provided");
+        return Err(Error::new(
+            ErrorCode::InvalidInput,
+            "No attribute name provided".to_string(),
+        )
+        .to_json());
+    }
+    let value = match encoding.unwrap_or_default() {
+        AttributeEncoding::Text => value.into_bytes(),
+        AttributeEncoding::Base64 => base64::engine::general_purpose::STANDARD
+            .decode(value)
+            .map_err(|e| {
+                log_error!("Invalid base64 value: {}", e);
+                Error::new(
+                    ErrorCode::InvalidInput,
+                    format!("Invalid base64 value: {}", e),
+                )
+                .to_json()
+            })?,
+    };
+
+    let entry_path = Path::new(&path);
+    xattr::set(entry_path, &name, &value)
+        .map_err(|e| io_error(entry_path, &format!("set attribute '{}' of", name), e))
+}
+
+/// Removes an extended attribute of a file or directory. Symlinks are not followed.
+///
+/// # Arguments
+/// * `path` - The path of the file or directory.
+/// * `name` - The name of the attribute.
+///
+/// # Returns
+/// * `Ok(())` - If the attribute was removed.
+/// * `Err(String)` - `ResourceNotFound` if the attribute does not exist, `Unauthorized` if the
+///   OS refuses the change.
+///
+/// # Example
+/// ```rust
+/// remove_extended_attribute("/srv/share/report.pdf".to_string(), "user.reviewed_by".to_string())?;
+/// ```
+#[tauri::command]
+pub fn remove_extended_attribute(path: String, name: String) -> Result<(), String> {
+    if !xattr::SUPPORTED_PLATFORM {
+        return Err(unsupported_platform());
+    }
+    let entry_path = Path::new(&path);
+    xattr::remove(entry_path, &name).map_err(|e| {
+        if is_missing_attribute(&e) {
+            log_error!("Attribute '{}' does not exist on '{}'", name, path);
+            Error::new(
+                ErrorCode::ResourceNotFound,
+                format!("Attribute '{}' does not exist on '{}'", name, path),
+            )
+            .to_json()
+        } else {
+            io_error(entry_path, &format!("remove attribute '{}' of", name), e)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests_file_properties_commands {
+    use super::*;
+
+    fn error_code(error: &str) -> u64 {
+        let error: serde_json::Value = serde_json::from_str(error).unwrap();
+        error["code"].as_u64().unwrap()
+    }
+
+    /// Whether the file system of the temp dir supports user attributes.
+    fn supports_user_attributes(path: &Path) -> bool {
+        xattr::SUPPORTED_PLATFORM && xattr::set(path, "user.probe", b"1").is_ok() && {
+            let _ = xattr::remove(path, "user.probe");
+            true
+        }
+    }
+
+    #[test]
+    fn test_get_detailed_metadata() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file = temp_dir.path().join("data.txt");
+        fs::write(&file, b"hello").unwrap();
+
+        let metadata = get_detailed_metadata(file.to_string_lossy().to_string()).unwrap();
+        assert_eq!(metadata.name, "data.txt");
+        assert_eq!(metadata.size_in_bytes, 5);
+        assert!(!metadata.is_directory);
+        assert!(!metadata.is_symlink);
+        assert!(!metadata.last_modified.is_empty());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+
+            let expected = fs::metadata(&file).unwrap();
+            fs::hard_link(&file, temp_dir.path().join("link.txt")).unwrap();
+            let metadata = get_detailed_metadata(file.to_string_lossy().to_string()).unwrap();
+            assert_eq!(metadata.inode, Some(expected.ino()));
+            assert_eq!(metadata.device_id, Some(expected.dev()));
+            assert_eq!(metadata.hard_link_count, Some(2));
+            assert_eq!(metadata.owner_id, Some(expected.uid()));
+            assert_eq!(metadata.group_id, Some(expected.gid()));
+            assert_eq!(metadata.owner_name, id_name(expected.uid(), false));
+
+            std::os::unix::fs::symlink(&file, temp_dir.path().join("symlink")).unwrap();
+            let metadata = get_detailed_metadata(
+                temp_dir
+                    .path()
+                    .join("symlink")
+                    .to_string_lossy()
+                    .to_string(),
+            )
+            .unwrap();
+            assert!(metadata.is_symlink);
+            assert_eq!(
+                metadata.symlink_target.as_deref(),
+                Some(file.to_string_lossy().as_ref())
+            );
+        }
+
+        let missing =
+            get_detailed_metadata(temp_dir.path().join("nope").to_string_lossy().to_string());
+        assert_eq!(error_code(&missing.unwrap_err()), 405);
+    }
+
+    #[test]
+    fn test_set_and_remove_extended_attributes() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file = temp_dir.path().join("tagged.txt");
+        fs::write(&file, b"content").unwrap();
+        if !supports_user_attributes(&file) {
+            return;
+        }
+        let path = file.to_string_lossy().to_string();
+
+        set_extended_attribute(
+            path.clone(),
+            "user.comment".to_string(),
+            "reviewed".to_string(),
+            None,
+        )
+        .unwrap();
+        set_extended_attribute(
+            path.clone(),
+            "user.binary".to_string(),
+            "AP8=".to_string(),
+            Some(AttributeEncoding::Base64),
+        )
+        .unwrap();
+
+        let attributes = get_detailed_metadata(path.clone())
+            .unwrap()
+            .extended_attributes;
+        assert_eq!(
+            attributes,
+            vec![
+                ExtendedAttribute {
+                    name: "user.binary".to_string(),
+                    size: 2,
+                    value_base64: "AP8=".to_string(),
+                    value_text: None,
+                },
+                ExtendedAttribute {
+                    name: "user.comment".to_string(),
+                    size: 8,
+                    value_base64: "cmV2aWV3ZWQ=".to_string(),
+                    value_text: Some("reviewed".to_string()),
+                },
+            ]
+        );
+
+        remove_extended_attribute(path.clone(), "user.comment".to_string()).unwrap();
+        let attributes = get_detailed_metadata(path.clone())
+            .unwrap()
+            .extended_attributes;
+        assert_eq!(attributes.len(), 1);
+
+        let missing = remove_extended_attribute(path.clone(), "user.comment".to_string());
+        assert_eq!(error_code(&missing.unwrap_err()), 405);
+        let invalid = set_extended_attribute(
+            path.clone(),
+            "user.x".to_string(),
+            "not base64!".to_string(),
+            Some(AttributeEncoding::Base64),
+        );
+        assert_eq!(error_code(&invalid.unwrap_err()), 408);
+        assert!(set_extended_attribute(path, String::new(), "x".to_string(), None).is_err());
+    }
+
+    fn acl_bytes(entries: &[(u16, u16, u32)]) -> Vec<u8> {
+        let mut data = 2u32.to_le_bytes().to_vec();
+        for (tag, permissions, id) in entries {
+            data.extend_from_slice(&tag.to_le_bytes());
+            data.extend_from_slice(&permissions.to_le_bytes());
+            data.extend_from_slice(&id.to_le_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn test_parse_posix_acl() {
+        let data = acl_bytes(&[
+            (0x01, 6, u32::MAX),
+            (0x02, 5, 0),
+            (0x04, 4, u32::MAX),
+            (0x10, 7, u32::MAX),
+            (0x20, 0, u32::MAX),
+        ]);
+        let acl = parse_posix_acl(&data).unwrap();
+        assert_eq!(acl.len(), 5);
+        assert_eq!(acl[0].tag, AclTag::UserObj);
+        assert_eq!(acl[0].permissions, "rw-");
+        assert_eq!(acl[0].qualifier, None);
+        assert_eq!(acl[1].tag, AclTag::User);
+        assert_eq!(acl[1].qualifier, Some(0));
+        assert_eq!(acl[1].permissions, "r-x");
+        #[cfg(unix)]
+        assert_eq!(acl[1].qualifier_name.as_deref(), Some("root"));
+        assert_eq!(acl[3].permissions, "rwx");
+        assert_eq!(acl[4].permissions, "---");
+
+        assert!(parse_posix_acl(&[]).is_none());
+        assert!(parse_posix_acl(&data[..data.len() - 1]).is_none());
+        assert!(parse_posix_acl(&acl_bytes(&[(0x40, 0, 0)])).is_none());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_detailed_metadata_reads_acl() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file = temp_dir.path().join("shared.txt");
+        fs::write(&file, b"content").unwrap();
+        let data = acl_bytes(&[
+            (0x01, 6, u32::MAX),
+            (0x02, 4, 0),
+            (0x04, 4, u32::MAX),
+            (0x10, 4, u32::MAX),
+            (0x20, 0, u32::MAX),
+        ]);
+        // Needs a file system with ACL support and the permission to set them
+        if xattr::set(&file, ACL_ACCESS_ATTRIBUTE, &data).is_err() {
+            return;
+        }
+
+        let metadata = get_detailed_metadata(file.to_string_lossy().to_string()).unwrap();
+        let acl = metadata.acl.unwrap();
+        assert!(acl
+            .iter()
+            .any(|entry| entry.tag == AclTag::User && entry.qualifier == Some(0)));
+        assert!(metadata
+            .extended_attributes
+            .iter()
+            .all(|attribute| !attribute.name.starts_with("system.posix_acl")));
+        assert_eq!(metadata.default_acl, None);
+    }
+}
@@ -1,15 +1,20 @@
+use crate::archive::browse::ArchivePath;
+use crate::archive::{self, safety, ArchiveCreationOptions, ArchiveError, ArchiveFormat};
+use crate::commands::archive_commands::extraction_limits;
 use crate::error_handling::{Error, ErrorCode};
 use crate::models::{
     count_subdirectories, count_subfiles, format_system_time, get_access_permission_number,
     get_access_permission_string, Entries,
 };
+use crate::state::archive_listing_data::ArchiveListingState;
+use crate::state::jobs_data::JobsState;
+use crate::state::SettingsState;
 use crate::{log_error, models};
 use std::fs;
 use std::fs::read_dir;
-use std::io::Write;
-use std::path::Path;
-use zip::write::FileOptions;
-use zip::ZipWriter;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::State;
 
 /// Opens a file at the given path and returns its contents as a string.
 /// Should only be used for text files.
@@ -90,9 +95,12 @@ pub async fn open_in_default_app(path: &str) -> Result<(), String> {
 }
 
 /// Opens a directory at the given path and returns its contents as a json string.
+/// Directories inside archives can be opened with paths like `/home/me/x.zip!/docs`,
+/// the returned entries then carry such virtual paths as well.
 ///
 /// # Arguments
 /// - `path` - A string slice that holds the path to the directory to be opened.
+/// - `archive_listing_state` - The cached listings of archives, used for paths into archives.
 ///
 /// # Returns
 /// - `Ok(Entries)` - If the directory was successfully opened and read.
@@ -100,7 +108,7 @@ pub async fn open_in_default_app(path: &str) -> Result<(), String> {
 ///
 /// # Example
 /// ```rust
-/// let result = open_directory("/path/to/directory").await;
+/// let result = open_directory("/path/to/directory", archive_listing_state).await;
 /// match result {
 ///    Ok(entries) => {
 ///       for dir in entries.directories {
@@ -114,7 +122,30 @@ pub async fn open_in_default_app(path: &str) -> Result<(), String> {
 /// }
 /// ```
 #[tauri::command]
-pub async fn open_directory(path: String) -> Result<String, String> {
+pub async fn open_directory(
+    path: String,
+    archive_listing_state: State<'_, Arc<Mutex<ArchiveListingState>>>,
+) -> Result<String, String> {
+    open_directory_impl(path, archive_listing_state.inner().clone()).await
+}
+
+pub async fn open_directory_impl(
+    path: String,
+    archive_listing_state: Arc<Mutex<ArchiveListingState>>,
+) -> Result<String, String> {
+    // Paths like `/home/me/x.zip!/docs` are listed from the archive
+    if let Some(archive_path) = ArchivePath::parse(&path) {
+        let entries = archive::browse::list_archive_directory(&archive_path, &archive_listing_state)?;
+        return serde_json::to_string(&entries).map_err(|err| {
+            log_error!("Failed to serialize entries: {}", err);
+            Error::new(
+                ErrorCode::InternalError,
+                format!("Failed to serialize entries: {}", err),
+            )
+            .to_json()
+        });
+    }
+
     let path_obj = Path::new(&path);
 
     // Check if path exists
@@ -201,6 +232,9 @@ pub async fn open_directory(path: String) -> Result<String, String> {
                     .map_or("1970-01-01 00:00:00".to_string(), |time| {
                         format_system_time(time)
                     }),
+                owner_id: None,
+                group_id: None,
+                symlink_target: None,
             });
         } else if file_type.is_file() {
             files.push(models::File {
@@ -231,6 +265,9 @@ pub async fn open_directory(path: String) -> Result<String, String> {
                     .map_or("1970-01-01 00:00:00".to_string(), |time| {
                         format_system_time(time)
                     }),
+                owner_id: None,
+                group_id: None,
+                symlink_target: None,
             });
         }
     }
@@ -468,7 +505,7 @@ pub async fn move_to_trash(path: &str) -> Result<(), String> {
 /// Generates a unique destination path by appending a number if the path already exists.
 /// For example: "file.txt" -> "file (1).txt" -> "file (2).txt"
 /// For directories: "folder" -> "folder (1)" -> "folder (2)"
-fn generate_unique_path(original_path: &str) -> String {
+pub(crate) fn generate_unique_path(original_path: &str) -> String {
     let path = Path::new(original_path);
     
     if !path.exists() {
@@ -617,34 +654,13 @@ pub async fn copy_file_or_dir(source_path: &str, destination_path: &str) -> Resu
         Ok(size)
     }
 }
-/// Zips files and directories to a destination zip file.
-/// If only one source path is provided and no destination is specified, creates a zip file with the same name.
-/// For multiple source paths, the destination path must be specified.
-///
-/// # Arguments
-/// * `source_paths` - Vector of paths to files/directories to be zipped
-/// * `destination_path` - Optional destination path for the zip file
-///
-/// # Returns
-/// * `Ok(())` - If the zip file was successfully created
-/// * `Err(String)` - If there was an error during the zipping process
-///
-/// # Example
-/// ```rust
-/// // Single file/directory with auto destination
-/// let result = zip(vec!["/path/to/file.txt"], None).await;
-///
-/// // Multiple files to specific destination
-/// let result = zip(
-///     vec!["/path/to/file1.txt", "/path/to/dir1"],
-///     Some("/path/to/archive.zip")
-/// ).await;
-/// ```
-#[tauri::command]
-pub async fn zip(
-    source_paths: Vec<String>,
+/// Validates the zip arguments and returns the path and format of the archive to create.
+/// The format is taken from the options, then from the destination file name and falls back to zip.
+fn resolve_zip_destination(
+    source_paths: &[String],
     destination_path: Option<String>,
-) -> Result<(), String> {
+    options: &ArchiveCreationOptions,
+) -> Result<(PathBuf, ArchiveFormat), String> {
     if source_paths.is_empty() {
         log_error!("No source paths provided");
         return Err(Error::new(
@@ -654,11 +670,18 @@ pub async fn zip(
         .to_json());
     }
 
-    // If single source and no destination, use source name with .zip
-    let zip_path = if source_paths.len() == 1 && destination_path.is_none() {
-        Path::new(&source_paths[0]).with_extension("zip")
+    // If single source and no destination, use source name with the archive extension
+    let (zip_path, format) = if source_paths.len() == 1 && destination_path.is_none() {
+        let format = options.format.unwrap_or(ArchiveFormat::Zip);
+        let path = Path::new(&source_paths[0]).with_extension(&format.extension()[1..]);
+        (path, format)
     } else if let Some(dest) = destination_path {
-        Path::new(&dest).to_path_buf()
+        let path = PathBuf::from(dest);
+        let format = options
+            .format
+            .or_else(|| ArchiveFormat::from_path(&path))
+            .unwrap_or(ArchiveFormat::Zip);
+        (path, format)
     } else {
         log_error!("Destination path required for multiple sources");
         return Err(Error::new(
@@ -668,25 +691,8 @@ pub async fn zip(
         .to_json());
     };
 
-    // Create zip file
-    let zip_file = fs::File::create(&zip_path).map_err(|e| {
-        log_error!("Failed to create zip file: {}", e);
-        Error::new(
-            ErrorCode::InternalError,
-            format!("Failed to create zip file: {}", e),
-        )
-        .to_json()
-    })?;
-
-    let mut zip = ZipWriter::new(zip_file);
-    let options: FileOptions<()> = FileOptions::default()
-        .compression_method(zip::CompressionMethod::Deflated)
-        .unix_permissions(0o755);
-
-    // Process each source path
     for source_path in source_paths {
-        let source = Path::new(&source_path);
-        if !source.exists() {
+        if fs::symlink_metadata(source_path).is_err() {
             log_error!("Source path does not exist: {}", source_path);
             return Err(Error::new(
                 ErrorCode::ResourceNotFound,
@@ -694,121 +700,183 @@ pub async fn zip(
             )
             .to_json());
         }
+        if Path::new(source_path).file_name().is_none() {
+            log_error!("Invalid source name: {}", source_path);
+            return Err(Error::new(
+                ErrorCode::InvalidInput,
+                format!("Invalid source name: {}", source_path),
+            )
+            .to_json());
+        }
+    }
+    archive::validate_creation_options(format, options)?;
+    Ok((zip_path, format))
+}
 
-        let base_name = source
-            .file_name()
-            .ok_or_else(|| "Invalid source name".to_string())?
-            .to_str()
-            .ok_or_else(|| "Invalid characters in source name".to_string())?;
-
-        if source.is_file() {
-            zip.start_file(base_name, options).map_err(|e| {
-                let err_msg = format!("Error adding file to zip: {}", e);
-                log_error!(&err_msg);
-                err_msg
-            })?;
-            let content = fs::read(source).map_err(|e| {
-                let err_msg = format!("Error reading file: {}", e);
-                log_error!(&err_msg);
-                err_msg
-            })?;
-            zip.write_all(&content).map_err(|e| {
-                let err_msg = format!("Error writing to zip: {}", e);
-                log_error!(&err_msg);
-                err_msg
-            })?;
-        } else if source.is_dir() {
-            for entry in walkdir::WalkDir::new(source) {
-                let entry = entry.map_err(|e| {
-                    let err_msg = format!("Error reading directory: {}", e);
-                    log_error!(&err_msg);
-                    err_msg
-                })?;
-                let path = entry.path();
-
-                if path.is_file() {
-                    let relative = path.strip_prefix(source).map_err(|e| {
-                        log_error!("Failed to strip prefix: {}", e);
-                        Error::new(
-                            ErrorCode::InternalError,
-                            format!("Error creating relative path: {}", e),
-                        )
-                        .to_json()
-                    })?;
-                    let name = format!(
-                        "{}/{}",
-                        base_name,
-                        relative
-                            .to_str()
-                            .ok_or_else(|| "Invalid characters in path".to_string())?
-                            .replace('\\', "/")
-                    );
-
-                    zip.start_file(&name, options).map_err(|e| {
-                        log_error!("Error adding file to zip: {}", e);
-                        Error::new(
-                            ErrorCode::InternalError,
-                            format!("Error adding file to zip: {}", e),
-                        )
-                        .to_json()
-                    })?;
-                    let content = fs::read(path).map_err(|e| {
-                        log_error!("Error reading file: {}", e);
-                        Error::new(
-                            ErrorCode::InternalError,
-                            format!("Error reading file: {}", e),
-                        )
-                        .to_json()
-                    })?;
-                    zip.write_all(&content).map_err(|e| {
-                        log_error!("Error writing to zip: {}", e);
-                        Error::new(
-                            ErrorCode::InternalError,
-                            format!("Error writing to zip: {}", e),
-                        )
-                        .to_json()
-                    })?;
-                }
-            }
+/// Creates an archive from files and directories. Despite the name, every writable archive
+/// format is supported: zip, tar, tar.gz, tar.bz2, tar.xz and tar.zst.
+/// If only one source path is provided and no destination is specified, creates an archive with the same name.
+/// For multiple source paths, the destination path must be specified.
+/// Files are streamed into the archive, permissions and modification times are kept and
+/// symlinks are stored as symlinks unless `follow_symlinks` is set.
+///
+/// # Arguments
+/// * `source_paths` - Vector of paths to files/directories to be zipped
+/// * `destination_path` - Optional destination path for the archive
+/// * `options` - Optional format, compression method, level, password and symlink handling
+///
+/// # Returns
+/// * `Ok(())` - If the archive was successfully created
+/// * `Err(String)` - If there was an error during the zipping process
+///
+/// # Example
+/// ```rust
+/// // Single file/directory with auto destination
+/// let result = zip(vec!["/path/to/file.txt"], None, None).await;
+///
+/// // Multiple files to specific destination, the format is taken from the extension
+/// let result = zip(
+///     vec!["/path/to/file1.txt", "/path/to/dir1"],
+///     Some("/path/to/archive.tar.zst"),
+///     Some(ArchiveCreationOptions { compression_level: Some(19), ..Default::default() })
+/// ).await;
+/// ```
+#[tauri::command]
+pub async fn zip(
+    source_paths: Vec<String>,
+    destination_path: Option<String>,
+    options: Option<ArchiveCreationOptions>,
+) -> Result<(), String> {
+    let options = options.unwrap_or_default();
+    let (zip_path, format) = resolve_zip_destination(&source_paths, destination_path, &options)?;
+
+    match archive::write_archive(&source_paths, &zip_path, format, &options, None) {
+        Ok(()) => Ok(()),
+        Err(ArchiveError::Failed(err)) => {
+            let _ = fs::remove_file(&zip_path);
+            Err(err)
         }
+        // Without a job there is nothing which could cancel the zipping
+        Err(ArchiveError::Cancelled) => Ok(()),
     }
+}
 
-    zip.finish().map_err(|e| {
-        log_error!("Error finalizing zip file: {}", e);
-        Error::new(
-            ErrorCode::InternalError,
-            format!("Error finalizing zip file: {}", e),
-        )
-        .to_json()
-    })?;
-    Ok(())
+/// Same as `zip`, but runs as a background job which reports the written items and bytes.
+/// The progress can be polled with `get_job_status` and the job can be cancelled,
+/// in which case the incomplete archive is removed.
+///
+/// # Arguments
+/// * `source_paths` - Vector of paths to files/directories to be zipped
+/// * `destination_path` - Optional destination path for the archive
+/// * `options` - Optional format, compression method, level, password and symlink handling
+/// * `jobs_state` - The jobs state the job is registered in
+///
+/// # Returns
+/// * `Ok(u64)` - The id of the job. Its result is the path of the archive.
+/// * `Err(String)` - If the arguments are invalid
+///
+/// # Example
+/// ```rust
+/// let job_id = zip_with_progress(vec!["/videos/holiday.mp4".to_string()], None, None, jobs_state)?;
+/// ```
+#[tauri::command]
+pub fn zip_with_progress(
+    source_paths: Vec<String>,
+    destination_path: Option<String>,
+    options: Option<ArchiveCreationOptions>,
+    jobs_state: State<Arc<Mutex<JobsState>>>,
+) -> Result<u64, String> {
+    zip_with_progress_impl(
+        source_paths,
+        destination_path,
+        options,
+        jobs_state.inner().clone(),
+    )
 }
 
-/// Extracts zip files to specified destinations.
-/// If extracting a single zip file without a specified destination,
-/// extracts to a directory with the same name as the zip file.
+pub fn zip_with_progress_impl(
+    source_paths: Vec<String>,
+    destination_path: Option<String>,
+    options: Option<ArchiveCreationOptions>,
+    jobs_state: Arc<Mutex<JobsState>>,
+) -> Result<u64, String> {
+    let options = options.unwrap_or_default();
+    let (zip_path, format) = resolve_zip_destination(&source_paths, destination_path, &options)?;
+
+    let job = JobsState::start_job(&jobs_state, "zip");
+    let job_id = job.id();
+
+    std::thread::Builder::new()
+        .name("zip-thread".to_string())
+        .spawn(move || {
+            match archive::write_archive(&source_paths, &zip_path, format, &options, Some(&job)) {
+                Ok(()) => job.complete(Some(serde_json::Value::String(
+                    zip_path.to_string_lossy().to_string(),
+                ))),
+                Err(ArchiveError::Cancelled) => {
+                    let _ = fs::remove_file(&zip_path);
+                    job.mark_cancelled();
+                }
+                Err(ArchiveError::Failed(err)) => {
+                    let _ = fs::remove_file(&zip_path);
+                    job.fail(err);
+                }
+            }
+        })
+        .map_err(|e| {
+            log_error!("Failed to spawn zip thread: {}", e);
+            Error::new(
+                ErrorCode::InternalError,
+                format!("Failed to spawn zip thread: {}", e),
+            )
+            .to_json()
+        })?;
+
+    Ok(job_id)
+}
+
+/// Extracts archives to specified destinations. Despite the name, every readable archive
+/// format is supported: zip, 7z, tar, tar.gz, tar.bz2, tar.xz and tar.zst.
+/// If extracting a single archive without a specified destination,
+/// extracts to a directory with the same name as the archive.
+/// Archives with entries pointing outside of the destination, or which exceed the limits in
+/// `backend_settings.archive_extraction_config`, are rejected before anything is written.
 ///
 /// # Arguments
-/// * `zip_paths` - Vector of paths to zip files
+/// * `zip_paths` - Vector of paths to archives
 /// * `destination_path` - Optional destination directory for extraction
+/// * `settings_state` - The settings holding the extraction limits
 ///
 /// # Returns
-/// * `Ok(())` - If all zip files were successfully extracted
-/// * `Err(String)` - If there was an error during extraction
+/// * `Ok(())` - If all archives were successfully extracted
+/// * `Err(String)` - If there was an error during extraction. Rejected archives return an
+///   `InvalidInput` error whose `details` list the rejected entries.
 ///
 /// # Example
 /// ```rust
 /// // Single zip with auto destination
 /// let result = unzip(vec!["/path/to/archive.zip"], None).await;
 ///
-/// // Multiple zips to specific destination
+/// // Multiple archives to specific destination
 /// let result = unzip(
-///     vec!["/path/to/zip1.zip", "/path/to/zip2.zip"],
+///     vec!["/path/to/zip1.zip", "/path/to/backup.tar.gz"],
 ///     Some("/path/to/extracted")
 /// ).await;
 /// ```
 #[tauri::command]
-pub async fn unzip(zip_paths: Vec<String>, destination_path: Option<String>) -> Result<(), String> {
+pub async fn unzip(
+    zip_paths: Vec<String>,
+    destination_path: Option<String>,
+    settings_state: State<'_, Arc<Mutex<SettingsState>>>,
+) -> Result<(), String> {
+    unzip_impl(zip_paths, destination_path, settings_state.inner().clone()).await
+}
+
+pub async fn unzip_impl(
+    zip_paths: Vec<String>,
+    destination_path: Option<String>,
+    settings_state: Arc<Mutex<SettingsState>>,
+) -> Result<(), String> {
     if zip_paths.is_empty() {
         log_error!("No zip files provided");
         return Err(
@@ -864,6 +932,8 @@ pub async fn unzip(zip_paths: Vec<String>, destination_path: Option<String>) ->
         .to_json());
     }
 
+    let limits = extraction_limits(&settings_state)?;
+
     for zip_path in zip_paths.clone() {
         let zip_path = Path::new(&zip_path);
         if !zip_path.exists() {
@@ -875,10 +945,10 @@ pub async fn unzip(zip_paths: Vec<String>, destination_path: Option<String>) ->
             .to_json());
         }
 
-        // Determine extraction path for this zip
-        let zip_name = match zip_path.file_stem() {
-            Some(name) => name,
-            None => {
+        // Determine extraction path for this archive
+        let zip_name = match (ArchiveFormat::from_path(zip_path), zip_path.file_name()) {
+            (Some(format), Some(name)) => format.strip_extension(&name.to_string_lossy()),
+            _ => {
                 log_error!("Invalid zip filename");
                 return Err(Error::new(
                     ErrorCode::InvalidInput,
@@ -888,39 +958,17 @@ pub async fn unzip(zip_paths: Vec<String>, destination_path: Option<String>) ->
             }
         };
 
-        // Open and read zip file first to analyze contents
-        let file = fs::File::open(zip_path).map_err(|e| {
-            log_error!("Failed to open zip file: {}", e);
-            Error::new(
-                ErrorCode::InternalError,
-                format!("Failed to open zip file: {}", e),
-            )
-            .to_json()
-        })?;
-
-        let mut archive = zip::ZipArchive::new(file).map_err(|e| {
-            log_error!("Failed to read zip archive: {}", e);
-            Error::new(
-                ErrorCode::InternalError,
-                format!("Failed to read zip archive: {}", e),
-            )
-            .to_json()
-        })?;
-
-        // Check if zip contains only a single file (not directory)
-        let file_entries: Vec<_> = (0..archive.len())
-            .filter_map(|i| {
-                archive.by_index(i).ok().and_then(|file| {
-                    if !file.name().ends_with('/') {
-                        Some(file.name().to_string())
-                    } else {
-                        None
-                    }
-                })
-            })
-            .collect();
+        // Open and read the archive first to analyze contents
+        let mut archive =
+            archive::open_archive(zip_path, None).map_err(ArchiveError::into_json)?;
+        let entries = archive.entries().map_err(ArchiveError::into_json)?;
+        let archive_size = fs::metadata(zip_path).map(|m| m.len()).unwrap_or(0);
+        safety::check_entries(&entries, archive_size, &limits)
+            .map_err(ArchiveError::into_json)?;
 
-        let is_single_file = file_entries.len() == 1 && archive.len() == 1;
+        // Check if the archive contains only a single file (not directory)
+        let is_single_file =
+            entries.len() == 1 && entries[0].kind != archive::ArchiveEntryKind::Directory;
 
         // Determine extraction path based on content
         let extract_path = if is_single_file {
@@ -928,96 +976,36 @@ pub async fn unzip(zip_paths: Vec<String>, destination_path: Option<String>) ->
             dest_path.to_path_buf()
         } else {
             // For multiple files or directories, create subdirectory
-            let extract_path_initial = dest_path.join(zip_name);
+            let extract_path_initial = dest_path.join(&zip_name);
             let unique_extract_path_string = generate_unique_path(&extract_path_initial.to_string_lossy());
             Path::new(&unique_extract_path_string).to_path_buf()
         };
 
-        // Create extraction directory only if needed (not for single file to current dir)
-        if !is_single_file {
-            if let Err(e) = fs::create_dir_all(&extract_path) {
-                log_error!("Failed to create extraction directory: {}", e);
-                return Err(Error::new(
-                    ErrorCode::InternalError,
-                    format!("Failed to create extraction directory: {}", e),
-                )
-                .to_json());
-            }
+        if let Err(e) = fs::create_dir_all(&extract_path) {
+            log_error!("Failed to create extraction directory: {}", e);
+            return Err(Error::new(
+                ErrorCode::InternalError,
+                format!("Failed to create extraction directory: {}", e),
+            )
+            .to_json());
         }
 
-        for i in 0..archive.len() {
-            let mut file = archive.by_index(i).map_err(|e| {
-                log_error!("Failed to read zip entry: {}", e);
-                Error::new(
-                    ErrorCode::InternalError,
-                    format!("Failed to read zip entry: {}", e),
-                )
-                .to_json()
-            })?;
-
-            if file.name().ends_with('/') {
-                // For directories, create them if they don't exist
-                let outpath = extract_path.join(file.mangled_name());
-                fs::create_dir_all(&outpath).map_err(|e| {
-                    log_error!("Failed to create directory: {}", e);
-                    Error::new(
-                        ErrorCode::InternalError,
-                        format!("Failed to create directory '{}': {}", outpath.display(), e),
-                    )
-                    .to_json()
-                })?;
-            } else {
-                // For files, determine the output path based on extraction type
-                let outpath = if is_single_file {
-                    // For single file, extract directly to destination with original filename
-                    let original_filename = Path::new(file.name()).file_name()
-                        .unwrap_or_else(|| std::ffi::OsStr::new("extracted_file"));
-                    extract_path.join(original_filename)
-                } else {
-                    // For multiple files, use the full path structure
-                    extract_path.join(file.mangled_name())
-                };
-
-                // Generate a unique path if the file already exists
-                let unique_outpath_string = generate_unique_path(&outpath.to_string_lossy());
-                let unique_outpath = Path::new(&unique_outpath_string);
-                
-                if let Some(parent) = unique_outpath.parent() {
-                    if !parent.exists() {
-                        fs::create_dir_all(parent).map_err(|e| {
-                            log_error!("Failed to create parent directory: {}", e);
-                            Error::new(
-                                ErrorCode::InternalError,
-                                format!(
-                                    "Failed to create parent directory '{}': {}",
-                                    parent.display(),
-                                    e
-                                ),
-                            )
-                            .to_json()
-                        })?;
-                    }
-                }
-                let mut outfile = fs::File::create(&unique_outpath).map_err(|e| {
-                    log_error!("Failed to create file: {}", e);
-                    Error::new(
-                        ErrorCode::InternalError,
-                        format!("Failed to create file {}': {}", unique_outpath.display(), e),
-                    )
-                    .to_json()
-                })?;
-                std::io::copy(&mut file, &mut outfile).map_err(|e| {
-                    log_error!("Failed to write file: {}", e);
-                    Error::new(
-                        ErrorCode::InternalError,
-                        format!("Failed to write file '{}': {}", unique_outpath.display(), e),
-                    )
-                    .to_json()
-                })?;
+        // A single file is extracted by its file name, everything else keeps its structure
+        let target_of = |entry_path: &Path| match entry_path.file_name() {
+            Some(file_name) if is_single_file => Some(PathBuf::from(file_name)),
+            _ => Some(entry_path.to_path_buf()),
+        };
+        if let Err(err) =
+            archive::extract_entries(archive.as_mut(), &extract_path, &target_of, &limits, None)
+        {
+            // Don't leave a half extracted directory behind
+            if !is_single_file {
+                let _ = fs::remove_dir_all(&extract_path);
             }
+            return Err(err.into_json());
         }
 
-        // Remove the zip file after successful extraction
+        // Remove the archive after successful extraction
         if let Err(e) = fs::remove_file(zip_path) {
             log_error!("Failed to remove zip file after extraction: {}", e);
             // Note: We don't return an error here since extraction was successful
@@ -1031,8 +1019,17 @@ pub async fn unzip(zip_paths: Vec<String>, destination_path: Option<String>) ->
 #[cfg(test)]
 mod tests_file_system_operation_commands {
     use super::*;
+    use crate::state::jobs_data::create_test_settings_state;
+    use crate::archive::ZipCompressionMethod;
+    use std::io::Write;
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
     use tempfile::tempdir;
 
+    fn test_archive_listing_state() -> Arc<Mutex<ArchiveListingState>> {
+        Arc::new(Mutex::new(ArchiveListingState::new()))
+    }
+
     #[tokio::test]
     async fn open_file_test() {
         use std::io::Write;
@@ -1450,7 +1447,7 @@ mod tests_file_system_operation_commands {
         println!("Sub File 2 created: {:?}", sub_file2_path);
 
         // Call the open_directory function
-        let result = open_directory(temp_dir.path().to_str().unwrap().to_string()).await;
+        let result = open_directory_impl(temp_dir.path().to_str().unwrap().to_string(), test_archive_listing_state()).await;
 
         // Verify that the operation was successful
         assert!(result.is_ok(), "Failed to open directory: {:?}", result);
@@ -1482,7 +1479,7 @@ mod tests_file_system_operation_commands {
         );
 
         // Verify subdirectory contents
-        let subdir_result = open_directory(sub_dir_path.to_str().unwrap().to_string()).await;
+        let subdir_result = open_directory_impl(sub_dir_path.to_str().unwrap().to_string(), test_archive_listing_state()).await;
         assert!(
             subdir_result.is_ok(),
             "Failed to open subdirectory: {:?}",
@@ -1524,7 +1521,7 @@ mod tests_file_system_operation_commands {
         test_path.push("open_directory_test.txt");
 
         // Open the file and read its contents
-        let result = open_directory(test_path.to_str().unwrap().to_string()).await;
+        let result = open_directory_impl(test_path.to_str().unwrap().to_string(), test_archive_listing_state()).await;
 
         // Verify that the operation was successful
         assert!(
@@ -1570,7 +1567,7 @@ mod tests_file_system_operation_commands {
         assert!(test_path.exists(), "Test file should exist before reading");
 
         // Open the file and read its contents
-        let result = open_directory(test_path.to_str().unwrap().to_string()).await;
+        let result = open_directory_impl(test_path.to_str().unwrap().to_string(), test_archive_listing_state()).await;
 
         // Verify that the operation was successful
         assert!(
@@ -1940,7 +1937,7 @@ mod tests_file_system_operation_commands {
         );
 
         // Zip the file
-        let result = zip(vec![test_file_path.to_str().unwrap().to_string()], None).await;
+        let result = zip(vec![test_file_path.to_str().unwrap().to_string()], None, None).await;
         assert!(result.is_ok(), "Failed to zip file: {:?}", result);
 
         // Check if zip file was created
@@ -1958,7 +1955,7 @@ mod tests_file_system_operation_commands {
 
     #[tokio::test]
     async fn failed_to_zip_because_no_source_paths_provided_test() {
-        let result = zip(vec![], None).await;
+        let result = zip(vec![], None, None).await;
 
         assert!(
             result.is_err(),
@@ -2002,6 +1999,7 @@ mod tests_file_system_operation_commands {
                 file2_path.to_str().unwrap().to_string(),
             ],
             None,
+            None,
         )
         .await;
 
@@ -2054,6 +2052,7 @@ mod tests_file_system_operation_commands {
                 non_existing_file_path.to_str().unwrap().to_string(),
             ],
             result_zip,
+            None,
         )
         .await;
 
@@ -2082,6 +2081,135 @@ mod tests_file_system_operation_commands {
         );
     }
 
+    #[tokio::test]
+    async fn zip_directory_with_options_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let source = temp_dir.path().join("source");
+        fs::create_dir_all(source.join("empty")).unwrap();
+        fs::write(source.join("data.txt"), "a".repeat(10_000)).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(source.join("data.txt"), fs::Permissions::from_mode(0o640))
+                .unwrap();
+            std::os::unix::fs::symlink("data.txt", source.join("link")).unwrap();
+        }
+
+        let zip_path = temp_dir.path().join("result.zip");
+        let options = ArchiveCreationOptions {
+            compression_method: ZipCompressionMethod::Zstd,
+            compression_level: Some(10),
+            ..Default::default()
+        };
+        let result = zip(
+            vec![source.to_string_lossy().to_string()],
+            Some(zip_path.to_string_lossy().to_string()),
+            Some(options),
+        )
+        .await;
+        assert!(result.is_ok(), "Failed to zip directory: {:?}", result);
+
+        let mut archive = zip::ZipArchive::new(fs::File::open(&zip_path).unwrap()).unwrap();
+        assert!(archive.by_name("source/empty/").is_ok(), "Empty directories are kept");
+
+        let file = archive.by_name("source/data.txt").unwrap();
+        assert_eq!(file.compression(), zip::CompressionMethod::Zstd);
+        assert_eq!(file.size(), 10_000);
+        assert!(file.compressed_size() < 10_000);
+        #[cfg(unix)]
+        assert_eq!(file.unix_mode().unwrap() & 0o777, 0o640);
+        assert!(file.last_modified().is_some_and(|time| time.year() >= 2024));
+        drop(file);
+
+        #[cfg(unix)]
+        {
+            let mut link = archive.by_name("source/link").unwrap();
+            assert!(link.is_symlink());
+            let mut target = String::new();
+            std::io::Read::read_to_string(&mut link, &mut target).unwrap();
+            assert_eq!(target, "data.txt");
+        }
+    }
+
+    #[tokio::test]
+    async fn zip_with_password_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let test_file_path = temp_dir.path().join("secret.txt");
+        fs::write(&test_file_path, "secret content").unwrap();
+
+        let options = ArchiveCreationOptions {
+            password: Some("hunter2".to_string()),
+            ..Default::default()
+        };
+        let result = zip(
+            vec![test_file_path.to_string_lossy().to_string()],
+            None,
+            Some(options),
+        )
+        .await;
+        assert!(result.is_ok(), "Failed to zip file: {:?}", result);
+
+        let zip_path = test_file_path.with_extension("zip");
+        let mut archive = zip::ZipArchive::new(fs::File::open(&zip_path).unwrap()).unwrap();
+        assert!(archive.by_index(0).is_err(), "Reading without password must fail");
+        assert!(archive.by_index_decrypt(0, b"wrong").is_err());
+
+        let mut file = archive.by_index_decrypt(0, b"hunter2").unwrap();
+        let mut content = String::new();
+        std::io::Read::read_to_string(&mut file, &mut content).unwrap();
+        assert_eq!(content, "secret content");
+    }
+
+    #[tokio::test]
+    async fn failed_to_zip_because_invalid_compression_level_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let test_file_path = temp_dir.path().join("test_file.txt");
+        fs::write(&test_file_path, "Test content").unwrap();
+
+        let options = ArchiveCreationOptions {
+            compression_method: ZipCompressionMethod::Stored,
+            compression_level: Some(5),
+            ..Default::default()
+        };
+        let result = zip(
+            vec![test_file_path.to_string_lossy().to_string()],
+            None,
+            Some(options),
+        )
+        .await;
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("InvalidInput"));
+        assert!(!test_file_path.with_extension("zip").exists());
+    }
+
+    #[test]
+    fn zip_with_progress_test() {
+        use crate::state::jobs_data::{wait_for_job, JobStatus};
+
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let source = temp_dir.path().join("source");
+        fs::create_dir(&source).unwrap();
+        fs::write(source.join("a.bin"), vec![7u8; 3 * 1024 * 1024]).unwrap();
+        fs::write(source.join("b.bin"), vec![8u8; 1024]).unwrap();
+
+        let jobs_state = Arc::new(Mutex::new(JobsState::new()));
+        let job_id = zip_with_progress_impl(
+            vec![source.to_string_lossy().to_string()],
+            None,
+            None,
+            jobs_state.clone(),
+        )
+        .expect("Failed to start zip job");
+
+        let info = wait_for_job(&jobs_state, job_id);
+
+        assert_eq!(info.status, JobStatus::Completed, "{:?}", info.error);
+        assert_eq!(info.progress.items_total, 3);
+        assert_eq!(info.progress.items_processed, 3);
+        assert_eq!(info.progress.bytes_processed, 3 * 1024 * 1024 + 1024);
+        assert!(source.with_extension("zip").exists());
+    }
+
     #[tokio::test]
     async fn unzip_single_file_test() {
         let temp_dir = tempdir().expect("Failed to create temporary directory");
@@ -2096,7 +2224,12 @@ mod tests_file_system_operation_commands {
         zip.finish().unwrap();
 
         // Test extraction without specifying destination
-        let result = unzip(vec![zip_path.to_str().unwrap().to_string()], None).await;
+        let result = unzip_impl(
+            vec![zip_path.to_str().unwrap().to_string()],
+            None,
+            create_test_settings_state(),
+        )
+        .await;
 
         assert!(result.is_ok(), "Failed to extract zip: {:?}", result);
 
@@ -2114,7 +2247,12 @@ mod tests_file_system_operation_commands {
 
     #[tokio::test]
     async fn failed_to_unzip_because_no_zip_files_provided_test() {
-        let result = unzip(vec![], None).await;
+        let result = unzip_impl(
+            vec![],
+            None,
+            create_test_settings_state(),
+        )
+        .await;
 
         assert!(
             result.is_err(),
@@ -2149,7 +2287,12 @@ mod tests_file_system_operation_commands {
         let zip_path = temp_dir.path().join("non_existing.zip");
 
         // Test extraction of a non-existing zip file
-        let result = unzip(vec![zip_path.to_str().unwrap().to_string()], None).await;
+        let result = unzip_impl(
+            vec![zip_path.to_str().unwrap().to_string()],
+            None,
+            create_test_settings_state(),
+        )
+        .await;
 
         assert!(
             result.is_err(),
@@ -2199,12 +2342,13 @@ mod tests_file_system_operation_commands {
         zip2.finish().unwrap();
 
         // Test extraction of multiple zips without specifying destination
-        let result = unzip(
+        let result = unzip_impl(
             vec![
                 zip1_path.to_str().unwrap().to_string(),
                 zip2_path.to_str().unwrap().to_string(),
             ],
             None,
+            create_test_settings_state(),
         )
         .await;
 
@@ -2255,12 +2399,13 @@ mod tests_file_system_operation_commands {
 
         // Attempt to unzip to an invalid destination path
         let invalid_dest = temp_dir.path().join("invalid");
-        let result = unzip(
+        let result = unzip_impl(
             vec![
                 zip_path.to_str().unwrap().to_string(),
                 zip2_path.to_str().unwrap().to_string(),
             ], // needs to be more than one path
             Some(invalid_dest.to_str().unwrap().to_string()),
+            create_test_settings_state(),
         )
         .await;
 
@@ -2288,4 +2433,336 @@ mod tests_file_system_operation_commands {
             "Error message does not match expected value"
         );
     }
+
+    #[tokio::test]
+    async fn zip_and_unzip_tar_formats_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let source = temp_dir.path().join("photos");
+        fs::create_dir_all(source.join("2024")).unwrap();
+        fs::write(source.join("2024").join("beach.jpg"), vec![1u8; 4096]).unwrap();
+        fs::write(source.join("list.txt"), "beach").unwrap();
+
+        for extension in ["tar", "tar.gz", "tar.bz2", "tar.xz", "tar.zst"] {
+            let archive_path = temp_dir.path().join(format!("photos.{}", extension));
+            let result = zip(
+                vec![source.to_string_lossy().to_string()],
+                Some(archive_path.to_string_lossy().to_string()),
+                None,
+            )
+            .await;
+            assert!(result.is_ok(), "Failed to create {}: {:?}", extension, result);
+
+            let out_dir = temp_dir.path().join(format!("out-{}", extension));
+            fs::create_dir(&out_dir).unwrap();
+            let result = unzip_impl(
+                vec![archive_path.to_string_lossy().to_string()],
+                Some(out_dir.to_string_lossy().to_string()),
+                create_test_settings_state(),
+            )
+            .await;
+            assert!(result.is_ok(), "Failed to extract {}: {:?}", extension, result);
+
+            let extracted = out_dir.join("photos").join("photos");
+            assert_eq!(
+                fs::read(extracted.join("2024").join("beach.jpg")).unwrap(),
+                vec![1u8; 4096]
+            );
+            assert_eq!(fs::read_to_string(extracted.join("list.txt")).unwrap(), "beach");
+            assert!(!archive_path.exists(), "Archive should be removed after extraction");
+        }
+    }
+
+    #[tokio::test]
+    async fn zip_with_format_option_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let test_file_path = temp_dir.path().join("notes.txt");
+        fs::write(&test_file_path, "notes").unwrap();
+
+        let options = ArchiveCreationOptions {
+            format: Some(ArchiveFormat::TarZst),
+            compression_level: Some(19),
+            ..Default::default()
+        };
+        let result = zip(
+            vec![test_file_path.to_string_lossy().to_string()],
+            None,
+            Some(options),
+        )
+        .await;
+        assert!(result.is_ok(), "Failed to create archive: {:?}", result);
+        assert!(temp_dir.path().join("notes.tar.zst").exists());
+
+        let options = ArchiveCreationOptions {
+            format: Some(ArchiveFormat::SevenZ),
+            ..Default::default()
+        };
+        let result = zip(
+            vec![test_file_path.to_string_lossy().to_string()],
+            None,
+            Some(options),
+        )
+        .await;
+        assert!(result.unwrap_err().contains("NotImplemented"));
+    }
+
+    #[tokio::test]
+    async fn unzip_7z_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let source = temp_dir.path().join("docs");
+        fs::create_dir(&source).unwrap();
+        fs::write(source.join("a.txt"), "a").unwrap();
+        fs::write(source.join("b.txt"), "b").unwrap();
+        let archive_path = temp_dir.path().join("docs.7z");
+        sevenz_rust::compress_to_path(&source, &archive_path).unwrap();
+
+        let result = unzip_impl(
+            vec![archive_path.to_string_lossy().to_string()],
+            None,
+            create_test_settings_state(),
+        )
+        .await;
+        assert!(result.is_ok(), "Failed to extract 7z: {:?}", result);
+
+        // The source directory still exists, so the extraction gets a unique name
+        let extracted = temp_dir.path().join("docs (1)");
+        assert_eq!(fs::read_to_string(extracted.join("a.txt")).unwrap(), "a");
+        assert_eq!(fs::read_to_string(extracted.join("b.txt")).unwrap(), "b");
+    }
+
+    #[tokio::test]
+    async fn open_directory_inside_archive_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let source = temp_dir.path().join("project");
+        fs::create_dir_all(source.join("src")).unwrap();
+        fs::write(source.join("src").join("lib.rs"), "pub fn f() {}").unwrap();
+        let archive_path = temp_dir.path().join("project.tar.gz");
+        zip(
+            vec![source.to_string_lossy().to_string()],
+            Some(archive_path.to_string_lossy().to_string()),
+            None,
+        )
+        .await
+        .unwrap();
+
+        let result = open_directory_impl(
+            format!("{}!/project/src/", archive_path.display()),
+            test_archive_listing_state(),
+        )
+        .await;
+        let entries: Entries = serde_json::from_str(&result.unwrap()).unwrap();
+        assert!(entries.directories.is_empty());
+        assert_eq!(entries.files.len(), 1);
+        assert_eq!(entries.files[0].name, "lib.rs");
+        assert_eq!(entries.files[0].size_in_bytes, 13);
+        assert_eq!(
+            entries.files[0].path,
+            format!("{}!/project/src/lib.rs", archive_path.display())
+        );
+    }
+
+    fn create_limited_settings_state(limits: &[(&str, u64)]) -> Arc<Mutex<SettingsState>> {
+        let state = create_test_settings_state();
+        for (field, value) in limits {
+            state
+                .lock()
+                .unwrap()
+                .update_setting_field(
+                    &format!("backend_settings.archive_extraction_config.{}", field),
+                    serde_json::json!(value),
+                )
+                .unwrap();
+        }
+        state
+    }
+
+    fn rejected_entries_of(error: &str) -> Vec<(Option<String>, String)> {
+        let error: serde_json::Value = serde_json::from_str(error).unwrap();
+        assert_eq!(error["message_from_code"], "InvalidInput");
+        error["details"]["rejected_entries"]
+            .as_array()
+            .expect("The error must list the rejected entries")
+            .iter()
+            .map(|entry| {
+                (
+                    entry["name"].as_str().map(str::to_string),
+                    entry["reason"].as_str().unwrap().to_string(),
+                )
+            })
+            .collect()
+    }
+
+    fn create_zip_with_entries(path: &Path, entries: &[(&str, &[u8])]) {
+        let mut zip = ZipWriter::new(fs::File::create(path).unwrap());
+        for (name, content) in entries {
+            zip.start_file::<_, ()>(*name, FileOptions::default())
+                .unwrap();
+            zip.write_all(content).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[tokio::test]
+    async fn unzip_rejects_path_traversal_entries_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let out_dir = temp_dir.path().join("out").join("nested");
+        fs::create_dir_all(&out_dir).unwrap();
+        let zip_path = temp_dir.path().join("slip.zip");
+        create_zip_with_entries(
+            &zip_path,
+            &[
+                ("ok.txt", b"fine"),
+                ("../../evil.txt", b"evil"),
+                ("/tmp/absolute-evil.txt", b"evil"),
+                ("docs/..\\..\\..\\windows-evil.txt", b"evil"),
+            ],
+        );
+
+        let result = unzip_impl(
+            vec![zip_path.to_string_lossy().to_string()],
+            Some(out_dir.to_string_lossy().to_string()),
+            create_test_settings_state(),
+        )
+        .await;
+
+        let rejected = rejected_entries_of(&result.unwrap_err());
+        let names: Vec<_> = rejected.iter().filter_map(|(name, _)| name.clone()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "../../evil.txt",
+                "/tmp/absolute-evil.txt",
+                "docs/..\\..\\..\\windows-evil.txt",
+            ]
+        );
+        assert!(rejected.iter().all(|(_, reason)| reason == "PathTraversal"));
+        assert!(!temp_dir.path().join("evil.txt").exists());
+        assert!(!temp_dir.path().join("windows-evil.txt").exists());
+        assert_eq!(
+            fs::read_dir(&out_dir).unwrap().count(),
+            0,
+            "Nothing may be extracted from a rejected archive"
+        );
+        assert!(zip_path.exists(), "A rejected archive must be kept");
+    }
+
+    #[tokio::test]
+    async fn unzip_rejects_symlinks_pointing_outside_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let tar_path = temp_dir.path().join("links.tar");
+        let mut builder = tar::Builder::new(fs::File::create(&tar_path).unwrap());
+
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_size(0);
+        header.set_mode(0o777);
+        header.set_path("docs/passwd").unwrap();
+        header.set_link_name("../../../../etc/passwd").unwrap();
+        header.set_cksum();
+        builder.append(&header, std::io::empty()).unwrap();
+
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_size(0);
+        header.set_mode(0o777);
+        header.set_path("docs/readme").unwrap();
+        header.set_link_name("../readme.md").unwrap();
+        header.set_cksum();
+        builder.append(&header, std::io::empty()).unwrap();
+
+        // The tar crate refuses `..` in paths, so the name is written into the header directly
+        let mut header = tar::Header::new_gnu();
+        header.as_old_mut().name[..13].copy_from_slice(b"../escape.txt");
+        header.set_entry_type(tar::EntryType::Regular);
+        header.set_size(4);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append(&header, &b"evil"[..]).unwrap();
+        builder.finish().unwrap();
+        drop(builder);
+
+        let result = unzip_impl(
+            vec![tar_path.to_string_lossy().to_string()],
+            None,
+            create_test_settings_state(),
+        )
+        .await;
+
+        assert_eq!(
+            rejected_entries_of(&result.unwrap_err()),
+            vec![
+                (
+                    Some("docs/passwd".to_string()),
+                    "SymlinkOutsideDestination".to_string()
+                ),
+                (Some("../escape.txt".to_string()), "PathTraversal".to_string()),
+            ]
+        );
+        assert!(!temp_dir.path().join("escape.txt").exists());
+        assert!(!temp_dir.path().join("links").exists());
+    }
+
+    #[tokio::test]
+    async fn unzip_rejects_zip_bomb_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let zip_path = temp_dir.path().join("bomb.zip");
+        let zeros = vec![0u8; 4 * 1024 * 1024];
+        create_zip_with_entries(&zip_path, &[("bomb.bin", &zeros), ("small.txt", b"small")]);
+
+        let result = unzip_impl(
+            vec![zip_path.to_string_lossy().to_string()],
+            None,
+            create_limited_settings_state(&[("compression_ratio_min_size", 1024 * 1024)]),
+        )
+        .await;
+
+        let error = result.unwrap_err();
+        let rejected = rejected_entries_of(&error);
+        assert!(rejected.contains(&(
+            Some("bomb.bin".to_string()),
+            "CompressionRatio".to_string()
+        )));
+        assert!(!rejected.iter().any(|(name, _)| name.as_deref() == Some("small.txt")));
+        let details: serde_json::Value = serde_json::from_str(&error).unwrap();
+        assert_eq!(details["details"]["limits"]["max_compression_ratio"], 250);
+        assert!(!temp_dir.path().join("bomb").exists());
+        assert!(zip_path.exists());
+    }
+
+    #[tokio::test]
+    async fn unzip_enforces_entry_count_and_total_size_limits_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let zip_path = temp_dir.path().join("many.zip");
+        create_zip_with_entries(
+            &zip_path,
+            &[("a.txt", b"aaaa"), ("b.txt", b"bbbb"), ("c.txt", b"cccc")],
+        );
+
+        let result = unzip_impl(
+            vec![zip_path.to_string_lossy().to_string()],
+            None,
+            create_limited_settings_state(&[("max_entry_count", 2), ("max_total_size", 10)]),
+        )
+        .await;
+        assert_eq!(
+            rejected_entries_of(&result.unwrap_err()),
+            vec![
+                (None, "TooManyEntries".to_string()),
+                (None, "TotalSizeExceeded".to_string()),
+            ]
+        );
+        assert!(!temp_dir.path().join("many").exists());
+
+        // Within the limits the same archive is extracted
+        let result = unzip_impl(
+            vec![zip_path.to_string_lossy().to_string()],
+            None,
+            create_limited_settings_state(&[("max_entry_count", 3), ("max_total_size", 12)]),
+        )
+        .await;
+        assert!(result.is_ok(), "Failed to extract zip: {:?}", result);
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("many").join("c.txt")).unwrap(),
+            "cccc"
+        );
+    }
 }
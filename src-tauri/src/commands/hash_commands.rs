@@ -1,15 +1,22 @@
 use std::fmt::Display;
+use crate::error_handling::{Error, ErrorCode};
+use crate::state::jobs_data::{JobHandle, JobsState};
 use crate::state::SettingsState;
+use crate::{log_error, log_info};
 use crc32fast::Hasher;
+use digest::Digest;
 use md5::{Digest as Md5Digest, Md5 as Md5Hasher};
 use serde::{Deserialize, Serialize};
-use sha2::{Digest as Sha2Digest, Sha256, Sha384, Sha512};
-use std::path::Path;
+use sha1::Sha1;
+use sha2::{Sha256, Sha384, Sha512};
+use std::io::Read;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use tauri::State;
-use tokio::fs::File;
-use tokio::io::AsyncReadExt;
+use xxhash_rust::xxh3::Xxh3;
+use xxhash_rust::xxh64::Xxh64;
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub enum HashError {
@@ -17,6 +24,8 @@ pub enum HashError {
     InvalidChecksumMethod,
     FileOperationError,
     ClipboardError,
+    InvalidManifest,
+    Cancelled,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
@@ -26,6 +35,10 @@ pub enum ChecksumMethod {
     SHA384,
     SHA512,
     CRC32,
+    SHA1,
+    BLAKE3,
+    XXH64,
+    XXH128,
 }
 
 impl FromStr for ChecksumMethod {
@@ -38,6 +51,10 @@ impl FromStr for ChecksumMethod {
             "SHA384" => Ok(ChecksumMethod::SHA384),
             "SHA512" => Ok(ChecksumMethod::SHA512),
             "CRC32" => Ok(ChecksumMethod::CRC32),
+            "SHA1" => Ok(ChecksumMethod::SHA1),
+            "BLAKE3" => Ok(ChecksumMethod::BLAKE3),
+            "XXH64" => Ok(ChecksumMethod::XXH64),
+            "XXH128" => Ok(ChecksumMethod::XXH128),
             _ => Err(HashError::InvalidChecksumMethod),
         }
     }
@@ -50,6 +67,8 @@ impl Display for HashError {
             HashError::InvalidChecksumMethod => "Invalid checksum method".to_string(),
             HashError::FileOperationError => "File operation failed".to_string(),
             HashError::ClipboardError => "Failed to copy to clipboard".to_string(),
+            HashError::InvalidManifest => "No checksums found in the file".to_string(),
+            HashError::Cancelled => "Hashing was cancelled".to_string(),
         };
         write!(f, "{}", str)
     }
@@ -66,68 +85,222 @@ async fn get_checksum_method(
     Ok(inner_settings.backend_settings.default_checksum_hash.clone())
 }
 
-fn calculate_md5(data: &[u8]) -> String {
-    let mut hasher = Md5Hasher::new();
-    hasher.update(data);
-    let result = hasher.finalize();
-    hex::encode(result)
+/// Incremental version of the checksum methods, for content which is hashed in chunks
+/// instead of being loaded into memory as a whole.
+pub(crate) enum StreamingHasher {
+    Md5(Md5Hasher),
+    Sha256(Sha256),
+    Sha384(Sha384),
+    Sha512(Sha512),
+    Crc32(Hasher),
+    Sha1(Sha1),
+    Blake3(Box<blake3::Hasher>),
+    Xxh64(Box<Xxh64>),
+    Xxh128(Box<Xxh3>),
 }
 
-fn calculate_sha256(data: &[u8]) -> String {
-    let mut hasher = Sha256::new();
-    hasher.update(data);
-    format!("{:x}", hasher.finalize())
+impl StreamingHasher {
+    pub(crate) fn new(method: &ChecksumMethod) -> Self {
+        match method {
+            ChecksumMethod::MD5 => StreamingHasher::Md5(Md5Hasher::new()),
+            ChecksumMethod::SHA256 => StreamingHasher::Sha256(Sha256::new()),
+            ChecksumMethod::SHA384 => StreamingHasher::Sha384(Sha384::new()),
+            ChecksumMethod::SHA512 => StreamingHasher::Sha512(Sha512::new()),
+            ChecksumMethod::CRC32 => StreamingHasher::Crc32(Hasher::new()),
+            ChecksumMethod::SHA1 => StreamingHasher::Sha1(Sha1::new()),
+            ChecksumMethod::BLAKE3 => StreamingHasher::Blake3(Box::new(blake3::Hasher::new())),
+            ChecksumMethod::XXH64 => StreamingHasher::Xxh64(Box::new(Xxh64::new(0))),
+            ChecksumMethod::XXH128 => StreamingHasher::Xxh128(Box::new(Xxh3::new())),
+        }
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        match self {
+            StreamingHasher::Md5(hasher) => Md5Digest::update(hasher, data),
+            StreamingHasher::Sha256(hasher) => Digest::update(hasher, data),
+            StreamingHasher::Sha384(hasher) => Digest::update(hasher, data),
+            StreamingHasher::Sha512(hasher) => Digest::update(hasher, data),
+            StreamingHasher::Crc32(hasher) => hasher.update(data),
+            StreamingHasher::Sha1(hasher) => Digest::update(hasher, data),
+            StreamingHasher::Blake3(hasher) => {
+                hasher.update(data);
+            }
+            StreamingHasher::Xxh64(hasher) => hasher.update(data),
+            StreamingHasher::Xxh128(hasher) => hasher.update(data),
+        }
+    }
+
+    /// Returns the hash as lowercase hex. The xxHash values are written big-endian, like `xxhsum` does.
+    pub(crate) fn finalize(self) -> String {
+        match self {
+            StreamingHasher::Md5(hasher) => hex::encode(hasher.finalize()),
+            StreamingHasher::Sha256(hasher) => format!("{:x}", hasher.finalize()),
+            StreamingHasher::Sha384(hasher) => format!("{:x}", hasher.finalize()),
+            StreamingHasher::Sha512(hasher) => format!("{:x}", hasher.finalize()),
+            StreamingHasher::Crc32(hasher) => format!("{:08x}", hasher.finalize()),
+            StreamingHasher::Sha1(hasher) => format!("{:x}", hasher.finalize()),
+            StreamingHasher::Blake3(hasher) => hasher.finalize().to_hex().to_string(),
+            StreamingHasher::Xxh64(hasher) => format!("{:016x}", hasher.digest()),
+            StreamingHasher::Xxh128(hasher) => format!("{:032x}", hasher.digest128()),
+        }
+    }
 }
 
-fn calculate_sha384(data: &[u8]) -> String {
-    let mut hasher = Sha384::new();
-    hasher.update(data);
-    format!("{:x}", hasher.finalize())
+/// Hashes everything a reader returns, in chunks of 64 KiB.
+pub(crate) fn hash_reader(
+    method: &ChecksumMethod,
+    reader: &mut dyn Read,
+) -> std::io::Result<String> {
+    let mut hasher = StreamingHasher::new(method);
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.finalize())
 }
 
-fn calculate_sha512(data: &[u8]) -> String {
-    let mut hasher = Sha512::new();
-    hasher.update(data);
-    format!("{:x}", hasher.finalize())
+/// Counts the hashed bytes as progress of a job and stops reading once the job is cancelled.
+struct JobReader<'a, R: Read> {
+    inner: R,
+    job: Option<&'a JobHandle>,
 }
 
-fn calculate_crc32(data: &[u8]) -> String {
-    let mut hasher = Hasher::new();
-    hasher.update(data);
-    let checksum = hasher.finalize();
-    format!("{:08x}", checksum)
+impl<R: Read> Read for JobReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.job.is_some_and(|job| job.is_cancelled()) {
+            return Err(std::io::Error::other("Hashing was cancelled"));
+        }
+        let read = self.inner.read(buf)?;
+        if let Some(job) = self.job {
+            job.update_progress(|p| p.bytes_processed += read as u64);
+        }
+        Ok(read)
+    }
 }
 
-async fn calculate_hash(method: ChecksumMethod, data: &[u8]) -> Result<String, HashError> {
-    let result = match method {
-        ChecksumMethod::MD5 => calculate_md5(data),
-        ChecksumMethod::SHA256 => calculate_sha256(data),
-        ChecksumMethod::SHA384 => calculate_sha384(data),
-        ChecksumMethod::SHA512 => calculate_sha512(data),
-        ChecksumMethod::CRC32 => calculate_crc32(data),
-    };
-    Ok(result)
+/// Hashes a file without loading it into memory.
+fn hash_file(
+    method: &ChecksumMethod,
+    path: &Path,
+    job: Option<&JobHandle>,
+) -> Result<String, HashError> {
+    let file = std::fs::File::open(path).map_err(|_| HashError::FileOperationError)?;
+    let mut reader = JobReader { inner: file, job };
+    hash_reader(method, &mut reader).map_err(|_| {
+        if job.is_some_and(|job| job.is_cancelled()) {
+            HashError::Cancelled
+        } else {
+            HashError::FileOperationError
+        }
+    })
 }
 
-async fn read_file(path: &Path) -> Result<Vec<u8>, HashError> {
-    if !path.exists() && path.is_dir() {
-        return Err(HashError::FileOperationError);
+/// Escapes a path like `sha256sum` does. Lines with escaped paths start with a backslash.
+fn escape_manifest_path(path: &str) -> (bool, String) {
+    let escaped = path
+        .replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r");
+    (escaped != path, escaped)
+}
+
+fn unescape_manifest_path(path: &str) -> String {
+    let mut unescaped = String::with_capacity(path.len());
+    let mut chars = path.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some(other) => unescaped.push(other),
+            None => unescaped.push('\\'),
+        }
     }
-    let mut file = File::open(path)
-        .await
-        .map_err(|_| HashError::FileOperationError)?;
-    let mut buffer = Vec::new();
-    file.read_to_end(&mut buffer)
+    unescaped
+}
+
+/// Lists the regular files below a directory by their relative path with `/` separators,
+/// sorted by that path. Symlinks are not followed and empty directories are not listed,
+/// like a manifest created with `find . -type f | sort | xargs sha256sum`.
+fn collect_manifest_files(dir: &Path) -> Result<Vec<(String, PathBuf)>, HashError> {
+    let mut files = Vec::new();
+    for entry in walkdir::WalkDir::new(dir).follow_links(false) {
+        let entry = entry.map_err(|_| HashError::FileOperationError)?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let relative = entry
+            .path()
+            .strip_prefix(dir)
+            .map_err(|_| HashError::FileOperationError)?
+            .to_string_lossy()
+            .replace('\\', "/");
+        files.push((relative, entry.into_path()));
+    }
+    files.sort_by(|a, b| a.0.as_bytes().cmp(b.0.as_bytes()));
+    Ok(files)
+}
+
+/// Hashes every file below a directory and returns a `sha256sum` compatible manifest with
+/// one `<hash>  <relative path>` line per file.
+fn build_manifest(
+    method: &ChecksumMethod,
+    dir: &Path,
+    job: Option<&JobHandle>,
+) -> Result<String, HashError> {
+    let mut manifest = String::new();
+    for (relative, path) in collect_manifest_files(dir)? {
+        let hash = hash_file(method, &path, job)?;
+        let (escaped, relative) = escape_manifest_path(&relative);
+        if escaped {
+            manifest.push('\\');
+        }
+        manifest.push_str(&format!("{}  {}\n", hash, relative));
+    }
+    Ok(manifest)
+}
+
+/// The aggregate hash of a directory is the hash of its manifest, so it only depends on the
+/// relative paths and contents of the files, not on timestamps or the order on disk.
+fn tree_hash(method: &ChecksumMethod, manifest: &str) -> String {
+    let mut hasher = StreamingHasher::new(method);
+    hasher.update(manifest.as_bytes());
+    hasher.finalize()
+}
+
+/// Hashes a file, or returns the aggregate tree hash of a directory.
+fn hash_path_blocking(
+    method: &ChecksumMethod,
+    path: &Path,
+    job: Option<&JobHandle>,
+) -> Result<String, HashError> {
+    if path.is_dir() {
+        Ok(tree_hash(method, &build_manifest(method, path, job)?))
+    } else {
+        hash_file(method, path, job)
+    }
+}
+
+async fn hash_path(method: ChecksumMethod, path: &Path) -> Result<String, HashError> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || hash_path_blocking(&method, &path, None))
         .await
-        .map_err(|_| HashError::FileOperationError)?;
-    Ok(buffer)
+        .map_err(|_| HashError::FileOperationError)?
 }
 
 /// Generates a hash for the given file and returns it as a string.
-/// The hash algorithm used is determined by the application settings (MD5, SHA256, SHA384, SHA512, or CRC32).
+/// For a directory the aggregate tree hash is returned, the hash of its manifest (see `gen_hash_and_save_to_file`).
+/// The hash algorithm used is determined by the application settings (MD5, SHA1, SHA256, SHA384, SHA512, CRC32, BLAKE3, XXH64 or XXH128).
 ///
 /// # Arguments
-/// * `path` - A string representing the absolute path to the file to generate a hash for.
+/// * `path` - A string representing the absolute path to the file or directory to generate a hash for.
 /// * `state` - The application's settings state containing the default hash algorithm.
 ///
 /// # Returns
@@ -157,10 +330,7 @@ pub async fn gen_hash_and_return_string_impl(
     let checksum_method = get_checksum_method(state)
         .await
         .map_err(|e| e.to_string())?;
-    let data = read_file(Path::new(&path))
-        .await
-        .map_err(|e| e.to_string())?;
-    let hash = calculate_hash(checksum_method, &data)
+    let hash = hash_path(checksum_method, Path::new(&path))
         .await
         .map_err(|e| e.to_string())?;
 
@@ -168,10 +338,12 @@ pub async fn gen_hash_and_return_string_impl(
 }
 
 /// Generates a hash for the given file and saves it to a specified output file.
+/// For a directory a `sha256sum` compatible manifest with one `<hash>  <relative path>` line per
+/// file is saved instead, and the aggregate tree hash is returned.
 /// The hash algorithm used is determined by the application settings.
 ///
 /// # Arguments
-/// * `source_path` - A string representing the absolute path to the file to generate a hash for.
+/// * `source_path` - A string representing the absolute path to the file or directory to generate a hash for.
 /// * `output_path` - A string representing the absolute path where the hash will be saved.
 /// * `state` - The application's settings state containing the default hash algorithm.
 ///
@@ -204,25 +376,32 @@ pub async fn gen_hash_and_save_to_file_impl(
     let checksum_method = get_checksum_method(state)
         .await
         .map_err(|e| e.to_string())?;
-    let data = read_file(Path::new(&source_path))
-        .await
-        .map_err(|e| e.to_string())?;
-    let hash = calculate_hash(checksum_method, &data)
-        .await
-        .map_err(|e| e.to_string())?;
+    let source = PathBuf::from(&source_path);
+    let (hash, content) = tokio::task::spawn_blocking(move || {
+        if source.is_dir() {
+            let manifest = build_manifest(&checksum_method, &source, None)?;
+            Ok((tree_hash(&checksum_method, &manifest), manifest))
+        } else {
+            let hash = hash_file(&checksum_method, &source, None)?;
+            Ok((hash.clone(), hash))
+        }
+    })
+    .await
+    .map_err(|_| HashError::FileOperationError.to_string())?
+    .map_err(|e: HashError| e.to_string())?;
 
-    tokio::fs::write(output_path, hash.as_bytes())
+    tokio::fs::write(output_path, content.as_bytes())
         .await
         .map_err(|_| "Failed to write hash to file".to_string())?;
 
     Ok(hash)
 }
 
-/// Compares a file's generated hash with a provided hash value.
+/// Compares a file's generated hash, or a directory's tree hash, with a provided hash value.
 /// The hash algorithm used is determined by the application settings.
 ///
 /// # Arguments
-/// * `path` - A string representing the absolute path to the file to check.
+/// * `path` - A string representing the absolute path to the file or directory to check.
 /// * `hash_to_compare` - A string representing the expected hash value to compare against.
 /// * `state` - The application's settings state containing the default hash algorithm.
 ///
@@ -255,34 +434,417 @@ pub async fn compare_file_or_dir_with_hash_impl(
     let checksum_method = get_checksum_method(state)
         .await
         .map_err(|e| e.to_string())?;
-    let data = read_file(Path::new(&path))
-        .await
-        .map_err(|e| e.to_string())?;
-    let calculated_hash = calculate_hash(checksum_method, &data)
+    let calculated_hash = hash_path(checksum_method, Path::new(&path))
         .await
         .map_err(|e| e.to_string())?;
 
     Ok(calculated_hash.eq_ignore_ascii_case(&hash_to_compare))
 }
 
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub enum ManifestEntryStatus {
+    Passed,
+    Failed,
+    Missing,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ManifestEntryResult {
+    pub path: String,
+    pub expected: String,
+    /// `None` if the file is missing or could not be read.
+    pub actual: Option<String>,
+    pub status: ManifestEntryStatus,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ManifestVerification {
+    pub method: ChecksumMethod,
+    pub entries: Vec<ManifestEntryResult>,
+    pub passed: usize,
+    pub failed: usize,
+    pub missing: usize,
+    /// Line numbers, starting at 1, of lines which are no checksum lines.
+    pub invalid_lines: Vec<usize>,
+}
+
+/// A line of a checksum file, either GNU style `<hash>  <path>` (`<hash> *<path>` in binary
+/// mode) or BSD style `SHA256 (<path>) = <hash>`.
+struct ManifestLine {
+    hash: String,
+    path: String,
+    algorithm: Option<String>,
+}
+
+fn parse_manifest_line(line: &str) -> Option<ManifestLine> {
+    let (escaped, line) = match line.strip_prefix('\\') {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+
+    let is_hex = |hash: &str| !hash.is_empty() && hash.chars().all(|c| c.is_ascii_hexdigit());
+    let gnu = line.split_once(' ').and_then(|(hash, rest)| {
+        let path = rest.strip_prefix(' ').or_else(|| rest.strip_prefix('*'))?;
+        is_hex(hash).then_some((hash, path, None))
+    });
+    let (hash, path, algorithm) = match gnu {
+        Some(parsed) => parsed,
+        None => {
+            let (algorithm, rest) = line.split_once(" (")?;
+            let (path, hash) = rest.rsplit_once(") = ")?;
+            (hash, path, Some(algorithm.to_string()))
+        }
+    };
+
+    if path.is_empty() || !is_hex(hash) {
+        return None;
+    }
+    Some(ManifestLine {
+        hash: hash.to_lowercase(),
+        path: if escaped {
+            unescape_manifest_path(path)
+        } else {
+            path.to_string()
+        },
+        algorithm,
+    })
+}
+
+/// Detects the checksum method from the file name, e.g. `SHA256SUMS` or `archive.md5`,
+/// the BSD algorithm name or the length of the hash.
+fn detect_manifest_method(manifest_path: &Path, first: &ManifestLine) -> Option<ChecksumMethod> {
+    let name = manifest_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_uppercase())
+        .unwrap_or_default();
+    for (method, names) in [
+        (ChecksumMethod::MD5, ["MD5SUMS", ".MD5"]),
+        (ChecksumMethod::SHA256, ["SHA256SUMS", ".SHA256"]),
+        (ChecksumMethod::SHA384, ["SHA384SUMS", ".SHA384"]),
+        (ChecksumMethod::SHA512, ["SHA512SUMS", ".SHA512"]),
+        (ChecksumMethod::SHA1, ["SHA1SUMS", ".SHA1"]),
+        (ChecksumMethod::BLAKE3, ["B3SUMS", ".B3"]),
+        (ChecksumMethod::XXH64, ["XXH64SUMS", ".XXH64"]),
+        (ChecksumMethod::XXH128, ["XXH128SUMS", ".XXH128"]),
+    ] {
+        if name == names[0] || name.ends_with(names[1]) {
+            return Some(method);
+        }
+    }
+
+    if let Some(method) = first
+        .algorithm
+        .as_deref()
+        .and_then(|algorithm| ChecksumMethod::from_str(algorithm).ok())
+    {
+        return Some(method);
+    }
+
+    match first.hash.len() {
+        8 => Some(ChecksumMethod::CRC32),
+        16 => Some(ChecksumMethod::XXH64),
+        40 => Some(ChecksumMethod::SHA1),
+        32 => Some(ChecksumMethod::MD5),
+        64 => Some(ChecksumMethod::SHA256),
+        96 => Some(ChecksumMethod::SHA384),
+        128 => Some(ChecksumMethod::SHA512),
+        _ => None,
+    }
+}
+
+fn verify_manifest(
+    manifest_path: &Path,
+    method: Option<ChecksumMethod>,
+) -> Result<ManifestVerification, HashError> {
+    let content =
+        std::fs::read_to_string(manifest_path).map_err(|_| HashError::FileOperationError)?;
+    let base_dir = manifest_path.parent().unwrap_or(Path::new(""));
+
+    let mut lines = Vec::new();
+    let mut invalid_lines = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match parse_manifest_line(line) {
+            Some(parsed) => lines.push(parsed),
+            None => invalid_lines.push(index + 1),
+        }
+    }
+
+    let first = lines.first().ok_or(HashError::InvalidManifest)?;
+    let method = match method {
+        Some(method) => method,
+        None => {
+            detect_manifest_method(manifest_path, first).ok_or(HashError::InvalidChecksumMethod)?
+        }
+    };
+
+    let mut verification = ManifestVerification {
+        method: method.clone(),
+        entries: Vec::with_capacity(lines.len()),
+        passed: 0,
+        failed: 0,
+        missing: 0,
+        invalid_lines,
+    };
+    for line in lines {
+        let path = base_dir.join(&line.path);
+        let (actual, status) = if !path.is_file() {
+            verification.missing += 1;
+            (None, ManifestEntryStatus::Missing)
+        } else {
+            match hash_file(&method, &path, None) {
+                Ok(actual) if actual == line.hash => {
+                    verification.passed += 1;
+                    (Some(actual), ManifestEntryStatus::Passed)
+                }
+                Ok(actual) => {
+                    verification.failed += 1;
+                    (Some(actual), ManifestEntryStatus::Failed)
+                }
+                Err(_) => {
+                    verification.failed += 1;
+                    (None, ManifestEntryStatus::Failed)
+                }
+            }
+        };
+        verification.entries.push(ManifestEntryResult {
+            path: line.path,
+            expected: line.hash,
+            actual,
+            status,
+        });
+    }
+    Ok(verification)
+}
+
+/// Verifies the files listed in a checksum file, e.g. a `.sha256`, `.md5` or `SHA256SUMS` file
+/// created by `sha256sum` or `gen_hash_and_save_to_file`. Relative paths are resolved against the
+/// directory of the checksum file.
+///
+/// # Arguments
+/// * `manifest_path` - A string representing the absolute path to the checksum file.
+/// * `method` - The checksum method of the file. If `None` it is detected from the file name,
+///   or from the length of the hashes.
+///
+/// # Returns
+/// * `Ok(ManifestVerification)` - The result for every listed file and the number of passed, failed and missing files.
+/// * `Err(String)` - An error message if the checksum file cannot be read or contains no checksums.
+///
+/// # Example
+/// ```rust
+/// let result = verify_checksum_manifest("/path/to/SHA256SUMS", None).await;
+/// match result {
+///     Ok(verification) => println!("{} of {} files passed", verification.passed, verification.entries.len()),
+///     Err(err) => println!("Error verifying checksums: {}", err),
+/// }
+/// ```
+#[tauri::command]
+pub async fn verify_checksum_manifest(
+    manifest_path: String,
+    method: Option<ChecksumMethod>,
+) -> Result<ManifestVerification, String> {
+    tokio::task::spawn_blocking(move || verify_manifest(Path::new(&manifest_path), method))
+        .await
+        .map_err(|_| HashError::FileOperationError.to_string())?
+        .map_err(|e| e.to_string())
+}
+
+/// Number of files hashed at the same time. More threads rarely help, the disk is the limit then.
+const MAX_PARALLEL_HASHES: usize = 4;
+
+#[derive(Debug, Serialize, Clone)]
+pub struct FileHash {
+    pub path: String,
+    pub hash: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct HashFilesResult {
+    pub method: ChecksumMethod,
+    pub files: Vec<FileHash>,
+}
+
+/// The number of bytes which will be hashed, used as total for the progress.
+fn total_hash_size(paths: &[String]) -> u64 {
+    paths
+        .iter()
+        .flat_map(|path| walkdir::WalkDir::new(path).follow_links(false))
+        .flatten()
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| entry.metadata().map(|m| m.len()).unwrap_or(0))
+        .sum()
+}
+
+/// Hashes the paths on up to `MAX_PARALLEL_HASHES` threads. Returns the results in the order
+/// of the paths, after a cancellation only those of the paths which were finished.
+fn hash_paths_parallel(
+    method: &ChecksumMethod,
+    paths: &[String],
+    job: &JobHandle,
+) -> Vec<FileHash> {
+    job.update_progress(|p| {
+        p.items_total = paths.len() as u64;
+        p.bytes_total = total_hash_size(paths);
+    });
+
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<FileHash>>> = Mutex::new(vec![None; paths.len()]);
+    let workers = std::thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(MAX_PARALLEL_HASHES)
+        .min(paths.len())
+        .max(1);
+
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                if job.is_cancelled() {
+                    break;
+                }
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                let Some(path) = paths.get(index) else {
+                    break;
+                };
+                job.update_progress(|p| p.current_path = Some(path.clone()));
+
+                let (hash, error) = match hash_path_blocking(method, Path::new(path), Some(job)) {
+                    Ok(hash) => (Some(hash), None),
+                    Err(HashError::Cancelled) => break,
+                    Err(e) => {
+                        log_error!("Failed to hash {}: {}", path, e);
+                        (None, Some(e.to_string()))
+                    }
+                };
+                results
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner())[index] = Some(FileHash {
+                    path: path.clone(),
+                    hash,
+                    error,
+                });
+                job.update_progress(|p| p.items_processed += 1);
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+/// Hashes several files or directories in a job, in parallel and with progress in bytes.
+/// Files are read in chunks, so even files larger than the memory can be hashed.
+/// Directories get their aggregate tree hash.
+///
+/// # Arguments
+/// * `paths` - The absolute paths of the files or directories to hash.
+/// * `method` - The checksum method to use. If `None`, the default method of the settings is used.
+/// * `jobs_state` - The state which tracks the running jobs.
+/// * `settings_state` - The application's settings state containing the default hash algorithm.
+///
+/// # Returns
+/// * `Ok(u64)` - The id of the job. Its result is a `HashFilesResult`.
+/// * `Err(String)` - If no path is given or one of the paths does not exist.
+///
+/// # Example
+/// ```rust
+/// let job_id = hash_files(vec!["/path/to/image.iso".to_string()], Some(ChecksumMethod::BLAKE3), jobs_state, settings_state).await?;
+/// ```
+#[tauri::command]
+pub async fn hash_files(
+    paths: Vec<String>,
+    method: Option<ChecksumMethod>,
+    jobs_state: State<'_, Arc<Mutex<JobsState>>>,
+    settings_state: State<'_, Arc<Mutex<SettingsState>>>,
+) -> Result<u64, String> {
+    hash_files_impl(
+        paths,
+        method,
+        jobs_state.inner().clone(),
+        settings_state.inner().clone(),
+    )
+    .await
+}
+
+pub async fn hash_files_impl(
+    paths: Vec<String>,
+    method: Option<ChecksumMethod>,
+    jobs_state: Arc<Mutex<JobsState>>,
+    settings_state: Arc<Mutex<SettingsState>>,
+) -> Result<u64, String> {
+    if paths.is_empty() {
+        log_error!("No paths provided for hashing");
+        return Err(Error::new(
+            ErrorCode::InvalidInput,
+            "No paths provided for hashing".to_string(),
+        )
+        .to_json());
+    }
+    for path in &paths {
+        if !Path::new(path).exists() {
+            log_error!("Path does not exist: {}", path);
+            return Err(Error::new(
+                ErrorCode::ResourceNotFound,
+                format!("Path does not exist: {}", path),
+            )
+            .to_json());
+        }
+    }
+
+    let method = match method {
+        Some(method) => method,
+        None => get_checksum_method(settings_state)
+            .await
+            .map_err(|e| Error::new(ErrorCode::InternalError, e.to_string()).to_json())?,
+    };
+
+    let job = JobsState::start_job(&jobs_state, "hash");
+    let job_id = job.id();
+
+    std::thread::Builder::new()
+        .name("hash-thread".to_string())
+        .spawn(move || {
+            let files = hash_paths_parallel(&method, &paths, &job);
+            let result = serde_json::to_value(HashFilesResult { method, files }).ok();
+            if job.is_cancelled() {
+                log_info!("Hashing was cancelled");
+                if let Some(result) = result {
+                    job.set_partial_result(result);
+                }
+                job.mark_cancelled();
+            } else {
+                job.complete(result);
+            }
+        })
+        .map_err(|e| {
+            log_error!("Failed to spawn hash thread: {}", e);
+            Error::new(
+                ErrorCode::InternalError,
+                format!("Failed to spawn hash thread: {}", e),
+            )
+            .to_json()
+        })?;
+
+    Ok(job_id)
+}
+
 #[cfg(test)]
 mod tests_hash_commands {
     use super::*;
+    use crate::state::jobs_data::{create_test_settings_state, wait_for_job, JobStatus};
     use crate::state::SettingsState;
     use serde_json::json;
     use std::io::Write;
     use std::sync::Arc;
     use tempfile::tempdir;
 
-    // Testing: Helper function to create a test SettingsState
-    fn create_test_settings_state() -> Arc<Mutex<SettingsState>> {
-        let temp_file = tempfile::NamedTempFile::new().unwrap();
-        let path = temp_file.path().to_path_buf();
-
-        // Create a settings state with a temporary file path
-        Arc::new(Mutex::new(SettingsState::new_with_path(path)))
-    }
-
     fn create_test_state(method: ChecksumMethod) -> Arc<Mutex<SettingsState>> {
         let state = create_test_settings_state();
         let state_guard = state.lock().unwrap();
@@ -361,6 +923,324 @@ mod tests_hash_commands {
         assert!(!result_wrong.unwrap(), "Hash should not match");
     }
 
+    #[test]
+    fn test_hash_reader_known_vectors() {
+        let expected_hashes = [
+            (ChecksumMethod::MD5, "900150983cd24fb0d6963f7d28e17f72"),
+            (
+                ChecksumMethod::SHA256,
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            ),
+            (ChecksumMethod::CRC32, "352441c2"),
+            (
+                ChecksumMethod::SHA1,
+                "a9993e364706816aba3e25717850c26c9cd0d89d",
+            ),
+            (
+                ChecksumMethod::BLAKE3,
+                "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85",
+            ),
+            (ChecksumMethod::XXH64, "44bc2cf5ad770999"),
+            (ChecksumMethod::XXH128, "06b05ab6733a618578af5f94892f3950"),
+        ];
+        for (method, expected_hash) in expected_hashes {
+            let hash = hash_reader(&method, &mut b"abc".as_slice()).unwrap();
+            assert_eq!(hash, expected_hash, "Hash mismatch for {:?}", method);
+        }
+
+        // Content larger than one chunk hashes like a single update
+        let data = b"Hello, world!".repeat(10_000);
+        let mut hasher = StreamingHasher::new(&ChecksumMethod::SHA512);
+        hasher.update(&data);
+        assert_eq!(
+            hash_reader(&ChecksumMethod::SHA512, &mut data.as_slice()).unwrap(),
+            hasher.finalize()
+        );
+    }
+
+    fn create_test_tree() -> tempfile::TempDir {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        std::fs::create_dir_all(temp_dir.path().join("sub/empty")).unwrap();
+        std::fs::write(temp_dir.path().join("b.txt"), b"abc").unwrap();
+        std::fs::write(temp_dir.path().join("a.txt"), b"").unwrap();
+        std::fs::write(temp_dir.path().join("sub/c.txt"), b"Hello, world!").unwrap();
+        temp_dir
+    }
+
+    #[tokio::test]
+    async fn test_directory_manifest() {
+        let tree = create_test_tree();
+        let output_dir = tempdir().unwrap();
+        let manifest_path = output_dir.path().join("tree.sha256");
+
+        let result = gen_hash_and_save_to_file_impl(
+            tree.path().to_str().unwrap().to_string(),
+            manifest_path.to_str().unwrap().to_string(),
+            create_test_state(ChecksumMethod::SHA256),
+        )
+        .await
+        .expect("Hashing the directory failed");
+
+        let manifest = std::fs::read_to_string(&manifest_path).unwrap();
+        assert_eq!(
+            manifest,
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855  a.txt\n\
+             ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad  b.txt\n\
+             315f5bdb76d078c43b8ac0064e4a0164612b1fce77c869345bfc94c75894edd3  sub/c.txt\n"
+        );
+        assert_eq!(result, tree_hash(&ChecksumMethod::SHA256, &manifest));
+
+        let returned = gen_hash_and_return_string_impl(
+            tree.path().to_str().unwrap().to_string(),
+            create_test_state(ChecksumMethod::SHA256),
+        )
+        .await
+        .unwrap();
+        assert_eq!(returned, result);
+    }
+
+    #[tokio::test]
+    async fn test_tree_hash_is_deterministic() {
+        let first = create_test_tree();
+        let second = create_test_tree();
+        std::fs::File::options()
+            .write(true)
+            .open(second.path().join("b.txt"))
+            .unwrap()
+            .set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000))
+            .unwrap();
+        let state = create_test_state(ChecksumMethod::SHA256);
+
+        let first_hash = gen_hash_and_return_string_impl(
+            first.path().to_str().unwrap().to_string(),
+            state.clone(),
+        )
+        .await
+        .unwrap();
+        let second_hash = gen_hash_and_return_string_impl(
+            second.path().to_str().unwrap().to_string(),
+            state.clone(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(first_hash, second_hash);
+
+        let matches = compare_file_or_dir_with_hash_impl(
+            second.path().to_str().unwrap().to_string(),
+            first_hash.to_uppercase(),
+            state.clone(),
+        )
+        .await
+        .unwrap();
+        assert!(matches);
+
+        std::fs::write(second.path().join("sub/c.txt"), b"Hello, World!").unwrap();
+        let matches = compare_file_or_dir_with_hash_impl(
+            second.path().to_str().unwrap().to_string(),
+            first_hash,
+            state,
+        )
+        .await
+        .unwrap();
+        assert!(!matches);
+    }
+
+    #[test]
+    fn test_manifest_path_escaping() {
+        let name = "line\nbreak\\name";
+        let (escaped, path) = escape_manifest_path(name);
+        assert!(escaped);
+        assert_eq!(path, "line\\nbreak\\\\name");
+        assert_eq!(unescape_manifest_path(&path), name);
+        assert_eq!(
+            escape_manifest_path("plain.txt"),
+            (false, "plain.txt".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_verify_checksum_manifest() {
+        let tree = create_test_tree();
+        std::fs::write(
+            tree.path().join("SHA256SUMS"),
+            "# generated by sha256sum\n\
+             e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855  a.txt\n\
+             0000000000000000000000000000000000000000000000000000000000000000 *b.txt\n\
+             315F5BDB76D078C43B8AC0064E4A0164612B1FCE77C869345BFC94C75894EDD3  sub/c.txt\n\
+             not a checksum line\n\
+             \n\
+             e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855  gone.txt\n",
+        )
+        .unwrap();
+
+        let verification = verify_checksum_manifest(
+            tree.path().join("SHA256SUMS").to_str().unwrap().to_string(),
+            None,
+        )
+        .await
+        .expect("Verification failed");
+
+        assert_eq!(verification.method, ChecksumMethod::SHA256);
+        assert_eq!(
+            (
+                verification.passed,
+                verification.failed,
+                verification.missing
+            ),
+            (2, 1, 1)
+        );
+        assert_eq!(verification.invalid_lines, vec![5]);
+        let statuses: Vec<_> = verification
+            .entries
+            .iter()
+            .map(|entry| (entry.path.as_str(), entry.status.clone()))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ("a.txt", ManifestEntryStatus::Passed),
+                ("b.txt", ManifestEntryStatus::Failed),
+                ("sub/c.txt", ManifestEntryStatus::Passed),
+                ("gone.txt", ManifestEntryStatus::Missing),
+            ]
+        );
+        assert_eq!(
+            verification.entries[1].actual.as_deref(),
+            Some("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_verify_bsd_style_and_detection() {
+        let tree = create_test_tree();
+        let manifest_path = tree.path().join("checksums.txt");
+        std::fs::write(
+            &manifest_path,
+            "MD5 (b.txt) = 900150983cd24fb0d6963f7d28e17f72\n",
+        )
+        .unwrap();
+        let verification =
+            verify_checksum_manifest(manifest_path.to_str().unwrap().to_string(), None)
+                .await
+                .unwrap();
+        assert_eq!(verification.method, ChecksumMethod::MD5);
+        assert_eq!(verification.passed, 1);
+
+        // Detected from the hash length
+        std::fs::write(&manifest_path, "352441c2  b.txt\n").unwrap();
+        let verification =
+            verify_checksum_manifest(manifest_path.to_str().unwrap().to_string(), None)
+                .await
+                .unwrap();
+        assert_eq!(verification.method, ChecksumMethod::CRC32);
+        assert_eq!(verification.passed, 1);
+
+        std::fs::write(&manifest_path, "no checksums here\n").unwrap();
+        let result =
+            verify_checksum_manifest(manifest_path.to_str().unwrap().to_string(), None).await;
+        assert_eq!(result.unwrap_err(), HashError::InvalidManifest.to_string());
+    }
+
+
+    #[tokio::test]
+    async fn test_hash_files_job() {
+        let tree = create_test_tree();
+        let large_file = tree.path().join("large.bin");
+        std::fs::write(&large_file, vec![42u8; 300 * 1024]).unwrap();
+        let paths: Vec<String> = ["b.txt", "large.bin", "sub", "missing_later.txt"]
+            .iter()
+            .map(|name| tree.path().join(name).to_str().unwrap().to_string())
+            .collect();
+        std::fs::write(&paths[3], b"").unwrap();
+
+        let jobs_state = Arc::new(Mutex::new(JobsState::new()));
+        let job_id = hash_files_impl(
+            paths.clone(),
+            Some(ChecksumMethod::BLAKE3),
+            jobs_state.clone(),
+            create_test_settings_state(),
+        )
+        .await
+        .unwrap();
+        std::fs::remove_file(&paths[3]).unwrap();
+
+        let info = wait_for_job(&jobs_state, job_id);
+        assert_eq!(info.status, JobStatus::Completed, "{:?}", info.error);
+        let result = info.result.unwrap();
+        assert_eq!(result["method"], "BLAKE3");
+        let files = result["files"].as_array().unwrap();
+        let returned_paths: Vec<_> = files.iter().map(|f| f["path"].as_str().unwrap()).collect();
+        assert_eq!(
+            returned_paths,
+            paths.iter().map(String::as_str).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            files[0]["hash"],
+            "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+        );
+        assert_eq!(
+            files[1]["hash"].as_str().unwrap(),
+            blake3::hash(&[42u8; 300 * 1024]).to_hex().as_str()
+        );
+        assert_eq!(files[2]["hash"].as_str().unwrap().len(), 64);
+        assert!(files[3]["hash"].is_null());
+        assert_eq!(files[3]["error"], HashError::FileOperationError.to_string());
+
+        assert_eq!(info.progress.items_processed, 4);
+        assert_eq!(info.progress.bytes_processed, info.progress.bytes_total);
+        assert!(info.progress.bytes_processed >= 300 * 1024);
+    }
+
+    #[tokio::test]
+    async fn test_hash_files_uses_default_method() {
+        let tree = create_test_tree();
+        let jobs_state = Arc::new(Mutex::new(JobsState::new()));
+        let job_id = hash_files_impl(
+            vec![tree.path().join("b.txt").to_str().unwrap().to_string()],
+            None,
+            jobs_state.clone(),
+            create_test_state(ChecksumMethod::XXH64),
+        )
+        .await
+        .unwrap();
+
+        let result = wait_for_job(&jobs_state, job_id).result.unwrap();
+        assert_eq!(result["files"][0]["hash"], "44bc2cf5ad770999");
+
+        let missing = hash_files_impl(
+            vec![tree.path().join("nope.txt").to_str().unwrap().to_string()],
+            None,
+            jobs_state.clone(),
+            create_test_settings_state(),
+        )
+        .await;
+        assert!(missing.is_err());
+        assert!(
+            hash_files_impl(vec![], None, jobs_state, create_test_settings_state())
+                .await
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_hash_paths_parallel_stops_when_cancelled() {
+        let tree = create_test_tree();
+        let jobs_state = Arc::new(Mutex::new(JobsState::new()));
+        let job = JobsState::start_job(&jobs_state, "hash");
+        jobs_state.lock().unwrap().cancel_job(job.id()).unwrap();
+
+        let paths = vec![tree.path().join("b.txt").to_str().unwrap().to_string()];
+        assert!(hash_paths_parallel(&ChecksumMethod::SHA256, &paths, &job).is_empty());
+        assert!(matches!(
+            hash_file(
+                &ChecksumMethod::SHA256,
+                &tree.path().join("b.txt"),
+                Some(&job)
+            ),
+            Err(HashError::Cancelled)
+        ));
+    }
+
     #[tokio::test]
     async fn test_all_hash_methods() {
         let temp_dir = tempdir().expect("Failed to create temporary directory");
@@ -501,11 +1381,11 @@ mod tests_hash_commands {
         ];
 
         for method in methods {
-            let state_guard = state.lock().unwrap();
-            state_guard
+            state
+                .lock()
+                .unwrap()
                 .update_setting_field("backend_settings.default_checksum_hash", json!(method.clone()))
                 .unwrap();
-            drop(state_guard);
 
             let result = gen_hash_and_return_string_impl(file_path.clone(), state.clone()).await;
 
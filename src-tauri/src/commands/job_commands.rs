@@ -0,0 +1,122 @@
+use crate::error_handling::{Error, ErrorCode};
+use crate::state::jobs_data::{JobInfo, JobsState};
+use std::sync::{Arc, Mutex};
+use tauri::State;
+
+fn lock_error() -> String {
+    Error::new(
+        ErrorCode::InternalError,
+        "Failed to acquire lock on jobs state".to_string(),
+    )
+    .to_json()
+}
+
+/// Returns the status, progress and (partial) result of a background job.
+///
+/// # Arguments
+/// * `job_id` - The id returned by the command which started the job.
+/// * `state` - The jobs state.
+///
+/// # Returns
+/// * `Ok(JobInfo)` - The current snapshot of the job.
+/// * `Err(String)` - If the job does not exist.
+///
+/// # Example
+/// ```rust
+/// let result = get_job_status(job_id, state);
+/// match result {
+///     Ok(job) => println!("Processed {} of {}", job.progress.items_processed, job.progress.items_total),
+///     Err(err) => println!("Error fetching job: {}", err),
+/// }
+/// ```
+#[tauri::command]
+pub fn get_job_status(job_id: u64, state: State<Arc<Mutex<JobsState>>>) -> Result<JobInfo, String> {
+    get_job_status_impl(job_id, state.inner().clone())
+}
+
+pub fn get_job_status_impl(job_id: u64, state: Arc<Mutex<JobsState>>) -> Result<JobInfo, String> {
+    let jobs = state.lock().map_err(|_| lock_error())?;
+    jobs.get_job(job_id).ok_or_else(|| {
+        Error::new(
+            ErrorCode::ResourceNotFound,
+            format!("Job not found: {}", job_id),
+        )
+        .to_json()
+    })
+}
+
+/// Lists all known jobs, running and finished, ordered by id.
+#[tauri::command]
+pub fn list_jobs(state: State<Arc<Mutex<JobsState>>>) -> Result<Vec<JobInfo>, String> {
+    list_jobs_impl(state.inner().clone())
+}
+
+pub fn list_jobs_impl(state: Arc<Mutex<JobsState>>) -> Result<Vec<JobInfo>, String> {
+    let jobs = state.lock().map_err(|_| lock_error())?;
+    Ok(jobs.list_jobs())
+}
+
+/// Requests cancellation of a running job. The job stops at its next checkpoint
+/// and its status changes to `Cancelled`.
+///
+/// # Arguments
+/// * `job_id` - The id of the job to cancel.
+/// * `state` - The jobs state.
+///
+/// # Returns
+/// * `Ok(())` - If cancellation was requested.
+/// * `Err(String)` - If the job does not exist or is not running anymore.
+#[tauri::command]
+pub fn cancel_job(job_id: u64, state: State<Arc<Mutex<JobsState>>>) -> Result<(), String> {
+    cancel_job_impl(job_id, state.inner().clone())
+}
+
+pub fn cancel_job_impl(job_id: u64, state: Arc<Mutex<JobsState>>) -> Result<(), String> {
+    let jobs = state.lock().map_err(|_| lock_error())?;
+    jobs.cancel_job(job_id)
+        .map_err(|e| Error::new(ErrorCode::InvalidInput, e).to_json())
+}
+
+/// Removes all completed, failed and cancelled jobs.
+///
+/// # Returns
+/// * `Ok(usize)` - The number of removed jobs.
+#[tauri::command]
+pub fn clear_finished_jobs(state: State<Arc<Mutex<JobsState>>>) -> Result<usize, String> {
+    clear_finished_jobs_impl(state.inner().clone())
+}
+
+pub fn clear_finished_jobs_impl(state: Arc<Mutex<JobsState>>) -> Result<usize, String> {
+    let mut jobs = state.lock().map_err(|_| lock_error())?;
+    Ok(jobs.remove_finished_jobs())
+}
+
+#[cfg(test)]
+mod tests_job_commands {
+    use super::*;
+    use crate::state::jobs_data::JobStatus;
+
+    #[test]
+    fn get_unknown_job_test() {
+        let state = Arc::new(Mutex::new(JobsState::new()));
+        let result = get_job_status_impl(42, state);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("ResourceNotFound"));
+    }
+
+    #[test]
+    fn cancel_and_clear_jobs_test() {
+        let state = Arc::new(Mutex::new(JobsState::new()));
+        let job = JobsState::start_job(&state, "test");
+
+        assert!(cancel_job_impl(job.id(), state.clone()).is_ok());
+        assert!(job.is_cancelled());
+        job.mark_cancelled();
+
+        let info = get_job_status_impl(job.id(), state.clone()).unwrap();
+        assert_eq!(info.status, JobStatus::Cancelled);
+
+        assert_eq!(clear_finished_jobs_impl(state.clone()).unwrap(), 1);
+        assert!(list_jobs_impl(state).unwrap().is_empty());
+    }
+}
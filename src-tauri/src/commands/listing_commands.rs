@@ -0,0 +1,589 @@
+use crate::error_handling::{Error, ErrorCode};
+use crate::models::{
+    count_subfiles_and_subdirectories, format_system_time, get_access_permission_number,
+    get_access_permission_string, Entries,
+};
+use crate::state::listing_data::{DirectoryListingState, ListingEntry};
+use crate::state::{SettingsState, SortBy, SortDirection};
+use crate::{log_error, models};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tauri::State;
+
+const DEFAULT_PAGE_SIZE: usize = 500;
+const MAX_PAGE_SIZE: usize = 10_000;
+
+/// One page of a directory listing.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct DirectoryPage {
+    pub path: String,
+    pub listing_id: u64,
+    /// Position of the first entry of this page in the whole listing.
+    pub offset: usize,
+    /// Number of entries in the whole listing after filtering.
+    pub total_count: usize,
+    pub directory_count: usize,
+    pub entries: Entries,
+    /// Cursor for the next page, `None` on the last page.
+    pub next_cursor: Option<String>,
+}
+
+/// Number of direct children of a directory.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ChildCount {
+    pub path: String,
+    pub sub_file_count: usize,
+    pub sub_dir_count: usize,
+}
+
+fn invalid_cursor(cursor: &str) -> String {
+    log_error!("Invalid or expired cursor: {}", cursor);
+    Error::new(
+        ErrorCode::InvalidInput,
+        format!("Invalid or expired cursor: {}", cursor),
+    )
+    .to_json()
+}
+
+fn lock_error() -> String {
+    Error::new(
+        ErrorCode::InternalError,
+        "Failed to acquire lock on directory listing state".to_string(),
+    )
+    .to_json()
+}
+
+/// Cursors have the form `<listing_id>:<offset>`.
+fn parse_cursor(cursor: &str) -> Result<(u64, usize), String> {
+    let (id, offset) = cursor.split_once(':').ok_or_else(|| invalid_cursor(cursor))?;
+    let id = id.parse().map_err(|_| invalid_cursor(cursor))?;
+    let offset = offset.parse().map_err(|_| invalid_cursor(cursor))?;
+    Ok((id, offset))
+}
+
+#[cfg(windows)]
+fn is_hidden(name: &str, metadata: &fs::Metadata) -> bool {
+    use std::os::windows::fs::MetadataExt;
+    const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+    name.starts_with('.') || metadata.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0
+}
+
+#[cfg(not(windows))]
+fn is_hidden(name: &str, _metadata: &fs::Metadata) -> bool {
+    name.starts_with('.')
+}
+
+/// Reads a directory with a single metadata call per entry. Symlinks are listed as the
+/// type of their target, broken symlinks as files.
+fn read_listing_entries(path: &Path, show_hidden: bool) -> Result<Vec<ListingEntry>, String> {
+    let read_dir = fs::read_dir(path).map_err(|err| {
+        log_error!("Failed to read directory: {}", err);
+        Error::new(
+            ErrorCode::InternalError,
+            format!("Failed to read directory: {}", err),
+        )
+        .to_json()
+    })?;
+
+    let mut entries = Vec::new();
+    for entry in read_dir.flatten() {
+        let entry_path = entry.path();
+        let Ok(link_metadata) = entry.metadata() else {
+            continue;
+        };
+        let metadata = if link_metadata.file_type().is_symlink() {
+            fs::metadata(&entry_path).unwrap_or(link_metadata)
+        } else {
+            link_metadata
+        };
+
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !show_hidden && is_hidden(&name, &metadata) {
+            continue;
+        }
+
+        let is_dir = metadata.is_dir();
+        entries.push(ListingEntry {
+            name,
+            path: entry_path,
+            is_dir,
+            size: if is_dir { 0 } else { metadata.len() },
+            modified: metadata.modified().ok(),
+        });
+    }
+    Ok(entries)
+}
+
+fn extension_of(name: &str) -> &str {
+    Path::new(name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("")
+}
+
+/// Sorts directories before files. Within each group entries are ordered by `sort_by`
+/// and then by name, so entries with the same size or date keep a stable order.
+fn sort_listing_entries(
+    entries: Vec<ListingEntry>,
+    sort_by: &SortBy,
+    sort_direction: &SortDirection,
+) -> Vec<ListingEntry> {
+    let mut keyed: Vec<(String, ListingEntry)> = entries
+        .into_iter()
+        .map(|entry| (entry.name.to_lowercase(), entry))
+        .collect();
+
+    keyed.sort_by(|(a_key, a), (b_key, b)| {
+        let by_name = || a_key.cmp(b_key).then_with(|| a.name.cmp(&b.name));
+        let ordering = match sort_by {
+            SortBy::Name => by_name(),
+            SortBy::Size => a.size.cmp(&b.size).then_with(by_name),
+            SortBy::Date => a.modified.cmp(&b.modified).then_with(by_name),
+            SortBy::Type => extension_of(a_key)
+                .cmp(extension_of(b_key))
+                .then_with(by_name),
+        };
+        let ordering = match sort_direction {
+            SortDirection::Acscending => ordering,
+            SortDirection::Descending => ordering.reverse(),
+        };
+        match (a.is_dir, b.is_dir) {
+            (true, false) => Ordering::Less,
+            (false, true) => Ordering::Greater,
+            _ => ordering,
+        }
+    });
+
+    keyed.into_iter().map(|(_, entry)| entry).collect()
+}
+
+fn time_or_default(time: std::io::Result<std::time::SystemTime>) -> String {
+    time.map_or("1970-01-01 00:00:00".to_string(), format_system_time)
+}
+
+/// Builds the models for one page. Child counts of directories are left at 0,
+/// they can be loaded for the visible rows with `get_child_counts`.
+fn build_page_entries(entries: &[ListingEntry]) -> Entries {
+    let mut directories = Vec::new();
+    let mut files = Vec::new();
+
+    for entry in entries {
+        let is_symlink = entry.path.is_symlink();
+        // The entry may have been removed since the listing was created
+        let Ok(metadata) = fs::metadata(&entry.path).or_else(|_| fs::symlink_metadata(&entry.path))
+        else {
+            continue;
+        };
+        let path = entry.path.to_str().unwrap_or("[invalid path]").to_string();
+
+        if entry.is_dir {
+            directories.push(models::Directory {
+                name: entry.name.clone(),
+                path,
+                is_symlink,
+                access_rights_as_string: get_access_permission_string(metadata.permissions(), true),
+                access_rights_as_number: get_access_permission_number(metadata.permissions(), true),
+                size_in_bytes: 0,
+                sub_file_count: 0,
+                sub_dir_count: 0,
+                created: time_or_default(metadata.created()),
+                last_modified: time_or_default(metadata.modified()),
+                accessed: time_or_default(metadata.accessed()),
+                owner_id: None,
+                group_id: None,
+                symlink_target: None,
+            });
+        } else {
+            files.push(models::File {
+                name: entry.name.clone(),
+                path,
+                is_symlink,
+                access_rights_as_string: get_access_permission_string(
+                    metadata.permissions(),
+                    false,
+                ),
+                access_rights_as_number: get_access_permission_number(
+                    metadata.permissions(),
+                    false,
+                ),
+                size_in_bytes: metadata.len(),
+                created: time_or_default(metadata.created()),
+                last_modified: time_or_default(metadata.modified()),
+                accessed: time_or_default(metadata.accessed()),
+                owner_id: None,
+                group_id: None,
+                symlink_target: None,
+            });
+        }
+    }
+
+    Entries { directories, files }
+}
+
+/// Lists a directory page by page. The first call reads and sorts the whole directory once
+/// and returns the first page together with a cursor, the following pages are requested with
+/// that cursor and are served from the stored listing.
+///
+/// # Arguments
+/// * `path` - The directory to list. Ignored when a cursor is given.
+/// * `cursor` - The `next_cursor` of the previous page, `None` for the first page.
+/// * `page_size` - Number of entries per page (default 500, maximum 10000).
+/// * `sort_by` - Overrides the `sort_by` setting.
+/// * `sort_direction` - Overrides the `sort_direction` setting.
+/// * `show_hidden` - Overrides the `show_hidden_files_and_folders` setting.
+/// * `settings_state` - The settings state.
+/// * `listing_state` - The state holding the sorted listings.
+///
+/// # Returns
+/// * `Ok(DirectoryPage)` - The entries of the page, directories first.
+/// * `Err(String)` - If the path is not a directory or the cursor is invalid or expired.
+///
+/// # Example
+/// ```rust
+/// let first = list_directory_page("/home/user".to_string(), None, Some(200), None, None, None, settings_state, listing_state).await?;
+/// if let Some(cursor) = first.next_cursor {
+///     let second = list_directory_page(first.path, Some(cursor), Some(200), None, None, None, settings_state, listing_state).await?;
+/// }
+/// ```
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn list_directory_page(
+    path: String,
+    cursor: Option<String>,
+    page_size: Option<usize>,
+    sort_by: Option<SortBy>,
+    sort_direction: Option<SortDirection>,
+    show_hidden: Option<bool>,
+    settings_state: State<'_, Arc<Mutex<SettingsState>>>,
+    listing_state: State<'_, Arc<Mutex<DirectoryListingState>>>,
+) -> Result<DirectoryPage, String> {
+    let settings_state = settings_state.inner().clone();
+    let listing_state = listing_state.inner().clone();
+    tokio::task::spawn_blocking(move || {
+        list_directory_page_impl(
+            path,
+            cursor,
+            page_size,
+            sort_by,
+            sort_direction,
+            show_hidden,
+            settings_state,
+            listing_state,
+        )
+    })
+    .await
+    .map_err(|e| Error::new(ErrorCode::InternalError, e.to_string()).to_json())?
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn list_directory_page_impl(
+    path: String,
+    cursor: Option<String>,
+    page_size: Option<usize>,
+    sort_by: Option<SortBy>,
+    sort_direction: Option<SortDirection>,
+    show_hidden: Option<bool>,
+    settings_state: Arc<Mutex<SettingsState>>,
+    listing_state: Arc<Mutex<DirectoryListingState>>,
+) -> Result<DirectoryPage, String> {
+    let page_size = page_size.unwrap_or(DEFAULT_PAGE_SIZE);
+    if page_size == 0 || page_size > MAX_PAGE_SIZE {
+        log_error!("Invalid page size: {}", page_size);
+        return Err(Error::new(
+            ErrorCode::InvalidInput,
+            format!("Page size must be between 1 and {}", MAX_PAGE_SIZE),
+        )
+        .to_json());
+    }
+
+    let (listing_id, offset) = match cursor {
+        Some(cursor) => parse_cursor(&cursor)?,
+        None => {
+            let path_obj = Path::new(&path);
+            if !path_obj.exists() {
+                log_error!("Directory does not exist: {}", path);
+                return Err(Error::new(
+                    ErrorCode::ResourceNotFound,
+                    format!("Directory does not exist: {}", path),
+                )
+                .to_json());
+            }
+            if !path_obj.is_dir() {
+                log_error!("Path is not a directory: {}", path);
+                return Err(Error::new(
+                    ErrorCode::InvalidInput,
+                    format!("Path is not a directory: {}", path),
+                )
+                .to_json());
+            }
+
+            let (default_sort_by, default_sort_direction, default_show_hidden) = {
+                let settings_state = settings_state.lock().map_err(|_| lock_error())?;
+                let settings = settings_state.0.lock().map_err(|_| lock_error())?;
+                (
+                    settings.sort_by.clone(),
+                    settings.sort_direction.clone(),
+                    settings.show_hidden_files_and_folders,
+                )
+            };
+
+            let entries =
+                read_listing_entries(path_obj, show_hidden.unwrap_or(default_show_hidden))?;
+            let entries = sort_listing_entries(
+                entries,
+                &sort_by.unwrap_or(default_sort_by),
+                &sort_direction.unwrap_or(default_sort_direction),
+            );
+
+            let id = listing_state
+                .lock()
+                .map_err(|_| lock_error())?
+                .insert(path.clone(), entries);
+            (id, 0)
+        }
+    };
+
+    // Only the page is copied out of the listing, the entries are read from disk after the
+    // lock is released so slow file systems don't block the other listing commands
+    let (listing_path, directory_count, total_count, start, page_entries) = {
+        let listing_state = listing_state.lock().map_err(|_| lock_error())?;
+        let snapshot = listing_state
+            .get(listing_id)
+            .ok_or_else(|| invalid_cursor(&format!("{}:{}", listing_id, offset)))?;
+
+        let total_count = snapshot.entries.len();
+        let start = offset.min(total_count);
+        let end = (start + page_size).min(total_count);
+        (
+            snapshot.path.clone(),
+            snapshot.directory_count,
+            total_count,
+            start,
+            snapshot.entries[start..end].to_vec(),
+        )
+    };
+    let end = start + page_entries.len();
+
+    Ok(DirectoryPage {
+        path: listing_path,
+        listing_id,
+        offset: start,
+        total_count,
+        directory_count,
+        entries: build_page_entries(&page_entries),
+        next_cursor: (end < total_count).then(|| format!("{}:{}", listing_id, end)),
+    })
+}
+
+/// Drops a stored listing once the frontend navigated away from the directory.
+/// Listings also expire on their own after five minutes.
+///
+/// # Returns
+/// * `Ok(bool)` - Whether a listing with the id existed.
+#[tauri::command]
+pub async fn release_directory_listing(
+    listing_id: u64,
+    listing_state: State<'_, Arc<Mutex<DirectoryListingState>>>,
+) -> Result<bool, String> {
+    let listing_state = listing_state.inner().clone();
+    tokio::task::spawn_blocking(move || release_directory_listing_impl(listing_id, listing_state))
+    .await
+    .map_err(|e| Error::new(ErrorCode::InternalError, e.to_string()).to_json())?
+}
+
+pub fn release_directory_listing_impl(
+    listing_id: u64,
+    listing_state: Arc<Mutex<DirectoryListingState>>,
+) -> Result<bool, String> {
+    let mut listing_state = listing_state.lock().map_err(|_| lock_error())?;
+    Ok(listing_state.remove(listing_id))
+}
+
+/// Counts the direct files and subdirectories of the given directories.
+/// Meant to be called for the rows which are currently visible.
+///
+/// # Arguments
+/// * `paths` - The directories to count the children of.
+///
+/// # Returns
+/// * `Ok(Vec<ChildCount>)` - One entry per path, unreadable directories have counts of 0.
+///
+/// # Example
+/// ```rust
+/// let counts = get_child_counts(vec!["/home/user/Documents".to_string()]).await?;
+/// ```
+#[tauri::command]
+pub async fn get_child_counts(paths: Vec<String>) -> Result<Vec<ChildCount>, String> {
+    tokio::task::spawn_blocking(move || get_child_counts_impl(paths))
+    .await
+    .map_err(|e| Error::new(ErrorCode::InternalError, e.to_string()).to_json())?
+}
+
+pub fn get_child_counts_impl(paths: Vec<String>) -> Result<Vec<ChildCount>, String> {
+    Ok(paths
+        .into_iter()
+        .map(|path| {
+            let (sub_file_count, sub_dir_count) = count_subfiles_and_subdirectories(&path);
+            ChildCount {
+                path,
+                sub_file_count,
+                sub_dir_count,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests_listing_commands {
+    use super::*;
+    use crate::state::jobs_data::create_test_settings_state;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    fn create_test_directory(root: &Path) {
+        fs::create_dir(root.join("b_dir")).unwrap();
+        fs::create_dir(root.join("A_dir")).unwrap();
+        fs::write(root.join("b_dir").join("child.txt"), "x").unwrap();
+        fs::write(root.join("c.txt"), vec![0u8; 30]).unwrap();
+        fs::write(root.join("a.rs"), vec![0u8; 10]).unwrap();
+        fs::write(root.join("B.md"), vec![0u8; 20]).unwrap();
+        fs::write(root.join(".hidden"), "").unwrap();
+    }
+
+    fn names(page: &DirectoryPage) -> Vec<String> {
+        page.entries
+            .directories
+            .iter()
+            .map(|d| d.name.clone())
+            .chain(page.entries.files.iter().map(|f| f.name.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn paginate_sorted_listing_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        create_test_directory(temp_dir.path());
+        let settings_state = create_test_settings_state();
+        let listing_state = Arc::new(Mutex::new(DirectoryListingState::new()));
+        let path = temp_dir.path().to_string_lossy().to_string();
+
+        let first = list_directory_page_impl(
+            path.clone(),
+            None,
+            Some(3),
+            Some(SortBy::Name),
+            Some(SortDirection::Acscending),
+            Some(false),
+            settings_state.clone(),
+            listing_state.clone(),
+        )
+        .expect("Failed to list first page");
+        assert_eq!(first.total_count, 5);
+        assert_eq!(first.directory_count, 2);
+        assert_eq!(names(&first), vec!["A_dir", "b_dir", "a.rs"]);
+        assert_eq!(first.entries.directories[0].sub_file_count, 0);
+
+        let second = list_directory_page_impl(
+            path,
+            first.next_cursor.clone(),
+            Some(3),
+            None,
+            None,
+            None,
+            settings_state,
+            listing_state,
+        )
+        .expect("Failed to list second page");
+        assert_eq!(second.offset, 3);
+        assert_eq!(names(&second), vec!["B.md", "c.txt"]);
+        assert!(second.next_cursor.is_none());
+    }
+
+    #[test]
+    fn sort_by_size_descending_and_hidden_from_settings_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        create_test_directory(temp_dir.path());
+        let settings_state = create_test_settings_state();
+        settings_state
+            .lock()
+            .unwrap()
+            .update_setting_field("show_hidden_files_and_folders", json!(true))
+            .unwrap();
+
+        let page = list_directory_page_impl(
+            temp_dir.path().to_string_lossy().to_string(),
+            None,
+            None,
+            Some(SortBy::Size),
+            Some(SortDirection::Descending),
+            None,
+            settings_state,
+            Arc::new(Mutex::new(DirectoryListingState::new())),
+        )
+        .expect("Failed to list directory");
+
+        assert_eq!(page.total_count, 6);
+        assert_eq!(
+            names(&page),
+            vec!["b_dir", "A_dir", "c.txt", "B.md", "a.rs", ".hidden"]
+        );
+    }
+
+    #[test]
+    fn invalid_cursor_test() {
+        let result = list_directory_page_impl(
+            String::new(),
+            Some("999:0".to_string()),
+            None,
+            None,
+            None,
+            None,
+            create_test_settings_state(),
+            Arc::new(Mutex::new(DirectoryListingState::new())),
+        );
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("InvalidInput"));
+
+        assert!(parse_cursor("not-a-cursor").is_err());
+    }
+
+    #[test]
+    fn release_listing_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        create_test_directory(temp_dir.path());
+        let listing_state = Arc::new(Mutex::new(DirectoryListingState::new()));
+
+        let page = list_directory_page_impl(
+            temp_dir.path().to_string_lossy().to_string(),
+            None,
+            Some(1),
+            None,
+            None,
+            None,
+            create_test_settings_state(),
+            listing_state.clone(),
+        )
+        .unwrap();
+        assert!(release_directory_listing_impl(page.listing_id, listing_state.clone()).unwrap());
+        assert!(!release_directory_listing_impl(page.listing_id, listing_state).unwrap());
+    }
+
+    #[tokio::test]
+    async fn get_child_counts_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        create_test_directory(temp_dir.path());
+
+        let counts = get_child_counts(vec![
+            temp_dir.path().to_string_lossy().to_string(),
+            temp_dir.path().join("b_dir").to_string_lossy().to_string(),
+        ])
+        .await
+        .unwrap();
+        assert_eq!(counts[0].sub_file_count, 4);
+        assert_eq!(counts[0].sub_dir_count, 2);
+        assert_eq!(counts[1].sub_file_count, 1);
+    }
+}
@@ -9,3 +9,18 @@ pub mod volume_operations_commands;
 pub mod sftp_file_system_operation_commands;
 pub mod preview_commands;
 pub mod permission_commands;
+pub mod batch_rename_commands;
+pub mod job_commands;
+pub mod delete_commands;
+pub mod trash_commands;
+pub mod dir_size_commands;
+pub mod listing_commands;
+pub mod archive_commands;
+pub mod duplicate_commands;
+pub mod sync_commands;
+pub mod file_properties_commands;
+pub mod connection_commands;
+pub mod sftp_transfer_commands;
+pub mod remote_edit_commands;
+pub mod remote_file_system_commands;
+pub mod remote_transfer_commands;
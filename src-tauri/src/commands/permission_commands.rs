@@ -1,4 +1,10 @@
-use std::process::Command;
+use crate::error_handling::{Error, ErrorCode};
+use crate::log_error;
+use filetime::FileTime;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
 
 #[tauri::command]
 pub fn request_full_disk_access() -> Result<(), String> {
@@ -32,6 +38,471 @@ pub fn check_directory_access(path: String) -> Result<bool, String> {
     }
 }
 
+/// The outcome of a property change for a single path.
+#[derive(Debug, Serialize, Clone)]
+pub struct PropertyChangeResult {
+    pub path: String,
+    /// `None` if the change was applied, otherwise the error in the usual error structure.
+    /// The code is `Unauthorized` if the OS refused the change.
+    pub error: Option<serde_json::Value>,
+}
+
+/// A change of the permission bits, either absolute like `755` or symbolic like `u+x,go-w`.
+#[derive(Debug, Clone, PartialEq)]
+enum ModeChange {
+    Absolute(u32),
+    Symbolic(Vec<SymbolicClause>),
+}
+
+/// One operation of a symbolic mode, e.g. `go-w` is `who: 0o077, op: '-', perms: "w"`.
+#[derive(Debug, Clone, PartialEq)]
+struct SymbolicClause {
+    who: u32,
+    op: char,
+    perms: String,
+}
+
+fn invalid_mode(mode: &str) -> String {
+    log_error!("Invalid permission mode: {}", mode);
+    Error::new(
+        ErrorCode::InvalidInput,
+        format!("Invalid permission mode: {}", mode),
+    )
+    .to_json()
+}
+
+/// Parses a mode like `chmod` accepts it. A symbolic mode without `u`, `g`, `o` or `a`
+/// applies to all three classes, the umask is not taken into account.
+fn parse_mode(mode: &str) -> Result<ModeChange, String> {
+    let mode = mode.trim();
+    if !mode.is_empty() && mode.chars().all(|c| c.is_ascii_digit()) {
+        return match u32::from_str_radix(mode, 8) {
+            Ok(bits) if mode.len() <= 4 && bits <= 0o7777 => Ok(ModeChange::Absolute(bits)),
+            _ => Err(invalid_mode(mode)),
+        };
+    }
+
+    let mut clauses = Vec::new();
+    for clause in mode.split(',') {
+        let mut chars = clause.chars().peekable();
+        let mut who = 0;
+        while let Some(&c) = chars.peek() {
+            who |= match c {
+                'u' => 0o4700,
+                'g' => 0o2070,
+                'o' => 0o1007,
+                'a' => 0o7777,
+                _ => break,
+            };
+            chars.next();
+        }
+        if who == 0 {
+            who = 0o7777;
+        }
+
+        let mut has_operation = false;
+        while let Some(op) = chars.next() {
+            if !matches!(op, '+' | '-' | '=') {
+                return Err(invalid_mode(mode));
+            }
+            let mut perms = String::new();
+            while let Some(&c) = chars.peek() {
+                if !"rwxXst".contains(c) {
+                    break;
+                }
+                perms.push(c);
+                chars.next();
+            }
+            clauses.push(SymbolicClause { who, op, perms });
+            has_operation = true;
+        }
+        if !has_operation {
+            return Err(invalid_mode(mode));
+        }
+    }
+    Ok(ModeChange::Symbolic(clauses))
+}
+
+/// Applies a mode change to the current permission bits of an entry.
+fn apply_mode_change(change: &ModeChange, current: u32, is_directory: bool) -> u32 {
+    let clauses = match change {
+        ModeChange::Absolute(bits) => return *bits,
+        ModeChange::Symbolic(clauses) => clauses,
+    };
+
+    let mut mode = current & 0o7777;
+    for clause in clauses {
+        let mut bits = 0;
+        for perm in clause.perms.chars() {
+            bits |= match perm {
+                'r' => 0o444,
+                'w' => 0o222,
+                'x' => 0o111,
+                // Execute only for directories and files which are already executable by someone
+                'X' if is_directory || current & 0o111 != 0 => 0o111,
+                's' => 0o6000,
+                't' => 0o1000,
+                _ => 0,
+            };
+        }
+        bits &= clause.who;
+        mode = match clause.op {
+            '+' => mode | bits,
+            '-' => mode & !bits,
+            _ => (mode & !(clause.who & 0o777)) | bits,
+        };
+    }
+    mode
+}
+
+/// Maps an IO error of a property change to the error structure, a refusal of the OS is
+/// reported as `Unauthorized`.
+fn change_error(path: &Path, action: &str, error: std::io::Error) -> serde_json::Value {
+    let code = match error.kind() {
+        std::io::ErrorKind::PermissionDenied => ErrorCode::Unauthorized,
+        std::io::ErrorKind::NotFound => ErrorCode::ResourceNotFound,
+        _ => ErrorCode::InternalError,
+    };
+    log_error!("Failed to {} '{}': {}", action, path.display(), error);
+    serde_json::to_value(Error::new(
+        code,
+        format!("Failed to {} '{}': {}", action, path.display(), error),
+    ))
+    .unwrap_or_default()
+}
+
+fn validate_paths(paths: &[String]) -> Result<(), String> {
+    if paths.is_empty() {
+        log_error!("No paths provided");
+        return Err(Error::new(ErrorCode::InvalidInput, "No paths provided".to_string()).to_json());
+    }
+    Ok(())
+}
+
+/// Calls `change` for every path, and for everything below a directory if `recursive` is set.
+/// Entries below a directory are changed without following symlinks, `change` gets `true`
+/// as second argument for them.
+fn change_paths<F>(
+    paths: &[String],
+    recursive: bool,
+    action: &str,
+    change: F,
+) -> Vec<PropertyChangeResult>
+where
+    F: Fn(&Path, bool) -> std::io::Result<()>,
+{
+    let mut results = Vec::new();
+    for path in paths {
+        let mut entries = vec![(PathBuf::from(path), false)];
+        if recursive && Path::new(path).is_dir() {
+            entries.extend(
+                WalkDir::new(path)
+                    .follow_links(false)
+                    .min_depth(1)
+                    .into_iter()
+                    .flatten()
+                    .map(|entry| (entry.into_path(), true)),
+            );
+        }
+        for (entry, nested) in entries {
+            let error = change(&entry, nested)
+                .err()
+                .map(|e| change_error(&entry, action, e));
+            results.push(PropertyChangeResult {
+                path: entry.to_string_lossy().to_string(),
+                error,
+            });
+        }
+    }
+    results
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, nested: bool, change: &ModeChange) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let metadata = fs::symlink_metadata(path)?;
+    // chmod on a symlink changes its target, which may be outside of the directory
+    if nested && metadata.file_type().is_symlink() {
+        return Ok(());
+    }
+    let metadata = fs::metadata(path)?;
+    let mode = apply_mode_change(change, metadata.permissions().mode(), metadata.is_dir());
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
+
+/// Windows only knows the read-only flag, it is set if the owner loses the write permission.
+#[cfg(not(unix))]
+fn set_mode(path: &Path, nested: bool, change: &ModeChange) -> std::io::Result<()> {
+    let metadata = fs::symlink_metadata(path)?;
+    if nested && metadata.file_type().is_symlink() {
+        return Ok(());
+    }
+    let metadata = fs::metadata(path)?;
+    let mut permissions = metadata.permissions();
+    let current = if permissions.readonly() { 0o555 } else { 0o777 };
+    let mode = apply_mode_change(change, current, metadata.is_dir());
+    permissions.set_readonly(mode & 0o200 == 0);
+    fs::set_permissions(path, permissions)
+}
+
+/// Changes the permissions of files and directories, like `chmod`.
+///
+/// # Arguments
+/// * `paths` - The paths to change.
+/// * `mode` - Either an octal mode like `"755"` or a symbolic mode like `"u+x,go-w"` or `"a=rX"`.
+/// * `recursive` - If true, everything below the given directories is changed too. Symlinks are skipped then.
+///
+/// # Returns
+/// * `Ok(Vec<PropertyChangeResult>)` - One result for every changed path.
+/// * `Err(String)` - An error if no path is given or the mode is invalid.
+///
+/// # Example
+/// ```rust
+/// let results = change_permissions(vec!["/home/user/script.sh".to_string()], "u+x".to_string(), None);
+/// ```
+#[tauri::command]
+pub fn change_permissions(
+    paths: Vec<String>,
+    mode: String,
+    recursive: Option<bool>,
+) -> Result<Vec<PropertyChangeResult>, String> {
+    validate_paths(&paths)?;
+    let change = parse_mode(&mode)?;
+    Ok(change_paths(
+        &paths,
+        recursive.unwrap_or(false),
+        "change permissions of",
+        |path, nested| set_mode(path, nested, &change),
+    ))
+}
+
+/// Resolves a user or group name, numeric ids are used as they are.
+#[cfg(unix)]
+fn resolve_id(name: &str, is_group: bool) -> Result<u32, String> {
+    if let Ok(id) = name.parse::<u32>() {
+        return Ok(id);
+    }
+    let unknown = || {
+        let kind = if is_group { "group" } else { "user" };
+        log_error!("Unknown {}: {}", kind, name);
+        Error::new(
+            ErrorCode::InvalidInput,
+            format!("Unknown {}: {}", kind, name),
+        )
+        .to_json()
+    };
+    let c_name = std::ffi::CString::new(name).map_err(|_| unknown())?;
+    let mut buffer = vec![0 as libc::c_char; 16 * 1024];
+
+    // SAFETY: the structs are only read after the call succeeded and filled them, and the
+    // strings they point to live in `buffer`, which outlives them.
+    unsafe {
+        if is_group {
+            let mut group: libc::group = std::mem::zeroed();
+            let mut result: *mut libc::group = std::ptr::null_mut();
+            let code = libc::getgrnam_r(
+                c_name.as_ptr(),
+                &mut group,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+                &mut result,
+            );
+            if code != 0 || result.is_null() {
+                return Err(unknown());
+            }
+            Ok(group.gr_gid)
+        } else {
+            let mut passwd: libc::passwd = std::mem::zeroed();
+            let mut result: *mut libc::passwd = std::ptr::null_mut();
+            let code = libc::getpwnam_r(
+                c_name.as_ptr(),
+                &mut passwd,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+                &mut result,
+            );
+            if code != 0 || result.is_null() {
+                return Err(unknown());
+            }
+            Ok(passwd.pw_uid)
+        }
+    }
+}
+
+/// Looks up the name of a user or group id, `None` if the id has no name.
+#[cfg(unix)]
+pub(crate) fn id_name(id: u32, is_group: bool) -> Option<String> {
+    let mut buffer = vec![0 as libc::c_char; 16 * 1024];
+
+    // SAFETY: see `resolve_id`, the name is copied out of `buffer` before it is dropped.
+    unsafe {
+        let name = if is_group {
+            let mut group: libc::group = std::mem::zeroed();
+            let mut result: *mut libc::group = std::ptr::null_mut();
+            let code = libc::getgrgid_r(
+                id,
+                &mut group,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+                &mut result,
+            );
+            if code != 0 || result.is_null() {
+                return None;
+            }
+            group.gr_name
+        } else {
+            let mut passwd: libc::passwd = std::mem::zeroed();
+            let mut result: *mut libc::passwd = std::ptr::null_mut();
+            let code = libc::getpwuid_r(
+                id,
+                &mut passwd,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+                &mut result,
+            );
+            if code != 0 || result.is_null() {
+                return None;
+            }
+            passwd.pw_name
+        };
+        Some(std::ffi::CStr::from_ptr(name).to_string_lossy().to_string())
+    }
+}
+
+/// Changes the owner and/or group of files and directories, like `chown` and `chgrp`.
+/// Changing the owner usually requires root, the group can be changed by the owner to
+/// any group they are a member of.
+///
+/// # Arguments
+/// * `paths` - The paths to change.
+/// * `owner` - Optional user name or numeric user id.
+/// * `group` - Optional group name or numeric group id.
+/// * `recursive` - If true, everything below the given directories is changed too. Symlinks
+///   below them are changed themselves, not their targets.
+///
+/// # Returns
+/// * `Ok(Vec<PropertyChangeResult>)` - One result for every changed path.
+/// * `Err(String)` - An error if no path, neither owner nor group, or an unknown name is given,
+///   or `NotImplementedForOS` on Windows.
+///
+/// # Example
+/// ```rust
+/// let results = change_owner(vec!["/srv/www".to_string()], None, Some("www-data".to_string()), Some(true));
+/// ```
+#[tauri::command]
+pub fn change_owner(
+    paths: Vec<String>,
+    owner: Option<String>,
+    group: Option<String>,
+    recursive: Option<bool>,
+) -> Result<Vec<PropertyChangeResult>, String> {
+    validate_paths(&paths)?;
+    if owner.is_none() && group.is_none() {
+        log_error!("Neither an owner nor a group was provided");
+        return Err(Error::new(
+            ErrorCode::InvalidInput,
+            "Neither an owner nor a group was provided".to_string(),
+        )
+        .to_json());
+    }
+
+    #[cfg(unix)]
+    {
+        let uid = owner.as_deref().map(|o| resolve_id(o, false)).transpose()?;
+        let gid = group.as_deref().map(|g| resolve_id(g, true)).transpose()?;
+        Ok(change_paths(
+            &paths,
+            recursive.unwrap_or(false),
+            "change owner of",
+            |path, nested| {
+                if nested {
+                    std::os::unix::fs::lchown(path, uid, gid)
+                } else {
+                    std::os::unix::fs::chown(path, uid, gid)
+                }
+            },
+        ))
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = recursive;
+        log_error!("Changing the owner is not supported on this OS");
+        Err(Error::new(
+            ErrorCode::NotImplementedForOS,
+            "Changing the owner is not supported on this OS".to_string(),
+        )
+        .to_json())
+    }
+}
+
+fn file_time_from_millis(millis: i64) -> FileTime {
+    FileTime::from_unix_time(
+        millis.div_euclid(1000),
+        (millis.rem_euclid(1000) * 1_000_000) as u32,
+    )
+}
+
+/// Sets the modification and access time of files and directories, like `touch` on existing paths.
+/// If neither time is given both are set to now, if only one is given the other one is kept.
+///
+/// # Arguments
+/// * `paths` - The paths to change.
+/// * `modified` - Optional modification time in milliseconds since the epoch.
+/// * `accessed` - Optional access time in milliseconds since the epoch.
+/// * `recursive` - If true, everything below the given directories is changed too. Symlinks
+///   below them are changed themselves, not their targets.
+///
+/// # Returns
+/// * `Ok(Vec<PropertyChangeResult>)` - One result for every changed path.
+/// * `Err(String)` - An error if no path is given.
+///
+/// # Example
+/// ```rust
+/// let results = set_timestamps(vec!["/home/user/notes.txt".to_string()], Some(1718000000000), None, None);
+/// ```
+#[tauri::command]
+pub fn set_timestamps(
+    paths: Vec<String>,
+    modified: Option<i64>,
+    accessed: Option<i64>,
+    recursive: Option<bool>,
+) -> Result<Vec<PropertyChangeResult>, String> {
+    validate_paths(&paths)?;
+    let (modified, accessed) = match (modified, accessed) {
+        (None, None) => {
+            let now = FileTime::now();
+            (Some(now), Some(now))
+        }
+        (modified, accessed) => (
+            modified.map(file_time_from_millis),
+            accessed.map(file_time_from_millis),
+        ),
+    };
+
+    Ok(change_paths(
+        &paths,
+        recursive.unwrap_or(false),
+        "set the timestamps of",
+        |path, nested| {
+            let metadata = if nested {
+                fs::symlink_metadata(path)?
+            } else {
+                fs::metadata(path)?
+            };
+            let modified =
+                modified.unwrap_or_else(|| FileTime::from_last_modification_time(&metadata));
+            let accessed = accessed.unwrap_or_else(|| FileTime::from_last_access_time(&metadata));
+            if nested {
+                filetime::set_symlink_file_times(path, accessed, modified)
+            } else {
+                filetime::set_file_times(path, accessed, modified)
+            }
+        },
+    ))
+}
+
 #[cfg(test)]
 mod permission_commands_tests {
     use super::*;
@@ -52,6 +523,186 @@ mod permission_commands_tests {
         assert_eq!(result, Ok(false));
     }
 
+    #[test]
+    fn test_parse_mode() {
+        assert_eq!(parse_mode("755"), Ok(ModeChange::Absolute(0o755)));
+        assert_eq!(parse_mode("4750"), Ok(ModeChange::Absolute(0o4750)));
+        assert!(parse_mode("").is_err());
+        assert!(parse_mode("79").is_err());
+        assert!(parse_mode("17777").is_err());
+        assert!(parse_mode("u").is_err());
+        assert!(parse_mode("u+x,").is_err());
+        assert!(parse_mode("z+x").is_err());
+
+        let error: serde_json::Value =
+            serde_json::from_str(&parse_mode("u*x").unwrap_err()).unwrap();
+        assert_eq!(error["code"], 408);
+    }
+
+    #[test]
+    fn test_apply_symbolic_mode() {
+        let apply = |mode: &str, current: u32, is_directory: bool| {
+            apply_mode_change(&parse_mode(mode).unwrap(), current, is_directory)
+        };
+        assert_eq!(apply("u+x", 0o644, false), 0o744);
+        assert_eq!(apply("go-w", 0o666, false), 0o644);
+        assert_eq!(apply("+x", 0o644, false), 0o755);
+        assert_eq!(apply("a=r", 0o777, false), 0o444);
+        assert_eq!(apply("u=rwx,g=rx,o=", 0o000, false), 0o750);
+        assert_eq!(apply("u+rw-x", 0o100, false), 0o600);
+        assert_eq!(apply("a+X", 0o644, false), 0o644);
+        assert_eq!(apply("a+X", 0o744, false), 0o755);
+        assert_eq!(apply("a+X", 0o644, true), 0o755);
+        assert_eq!(apply("u+s,o+t", 0o755, true), 0o5755);
+        assert_eq!(apply("600", 0o4755, false), 0o600);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_change_permissions_recursive() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path().join("project");
+        fs::create_dir_all(root.join("bin")).unwrap();
+        fs::write(root.join("bin/run.sh"), b"#!/bin/sh").unwrap();
+        fs::write(root.join("readme.md"), b"hi").unwrap();
+        fs::set_permissions(root.join("bin/run.sh"), fs::Permissions::from_mode(0o700)).unwrap();
+        fs::set_permissions(root.join("readme.md"), fs::Permissions::from_mode(0o600)).unwrap();
+        std::os::unix::fs::symlink("/etc/passwd", root.join("link")).unwrap();
+        let passwd_mode = fs::metadata("/etc/passwd").unwrap().permissions().mode();
+
+        let results = change_permissions(
+            vec![root.to_string_lossy().to_string()],
+            "go+rX".to_string(),
+            Some(true),
+        )
+        .unwrap();
+
+        assert_eq!(results.len(), 5);
+        assert!(results.iter().all(|r| r.error.is_none()), "{:?}", results);
+        let mode = |p: &str| fs::metadata(root.join(p)).unwrap().permissions().mode() & 0o7777;
+        assert_eq!(mode("bin/run.sh"), 0o755);
+        assert_eq!(mode("readme.md"), 0o644);
+        assert_eq!(mode("bin") & 0o055, 0o055);
+        // The target of the symlink was not touched
+        assert_eq!(
+            fs::metadata("/etc/passwd").unwrap().permissions().mode(),
+            passwd_mode
+        );
+
+        let results = change_permissions(
+            vec![
+                root.join("readme.md").to_string_lossy().to_string(),
+                root.join("missing.txt").to_string_lossy().to_string(),
+            ],
+            "400".to_string(),
+            None,
+        )
+        .unwrap();
+        assert!(results[0].error.is_none());
+        assert_eq!(mode("readme.md"), 0o400);
+        assert_eq!(results[1].error.as_ref().unwrap()["code"], 405);
+
+        assert!(change_permissions(vec![], "755".to_string(), None).is_err());
+    }
+
+    #[test]
+    fn test_change_error_maps_permission_denied_to_unauthorized() {
+        let error = change_error(
+            Path::new("/etc/shadow"),
+            "change permissions of",
+            std::io::Error::from(std::io::ErrorKind::PermissionDenied),
+        );
+        assert_eq!(error["code"], 401);
+        assert_eq!(error["message_from_code"], "Unauthorized");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_change_owner() {
+        use std::os::unix::fs::MetadataExt;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file = temp_dir.path().join("owned.txt");
+        fs::write(&file, b"data").unwrap();
+        let metadata = fs::metadata(&file).unwrap();
+
+        // Setting the current owner and group is always permitted
+        let results = change_owner(
+            vec![file.to_string_lossy().to_string()],
+            Some(metadata.uid().to_string()),
+            Some(metadata.gid().to_string()),
+            None,
+        )
+        .unwrap();
+        assert!(results[0].error.is_none(), "{:?}", results);
+
+        assert_eq!(resolve_id("root", false), Ok(0));
+        assert_eq!(id_name(0, false).as_deref(), Some("root"));
+        assert_eq!(id_name(u32::MAX - 1, false), None);
+        assert!(resolve_id("no-such-user-for-tests", false).is_err());
+        assert!(resolve_id("no-such-group-for-tests", true).is_err());
+        assert!(change_owner(vec![file.to_string_lossy().to_string()], None, None, None).is_err());
+    }
+
+    #[test]
+    fn test_set_timestamps() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path().join("dir");
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("a.txt"), b"a").unwrap();
+
+        let results = set_timestamps(
+            vec![dir.to_string_lossy().to_string()],
+            Some(1_600_000_000_500),
+            Some(1_500_000_000_000),
+            Some(true),
+        )
+        .unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|r| r.error.is_none()), "{:?}", results);
+
+        let metadata = fs::metadata(dir.join("a.txt")).unwrap();
+        assert_eq!(
+            FileTime::from_last_modification_time(&metadata),
+            FileTime::from_unix_time(1_600_000_000, 500_000_000)
+        );
+        assert_eq!(
+            FileTime::from_last_access_time(&metadata),
+            FileTime::from_unix_time(1_500_000_000, 0)
+        );
+
+        // Only the modification time, the access time is kept
+        set_timestamps(
+            vec![dir.join("a.txt").to_string_lossy().to_string()],
+            Some(1_700_000_000_000),
+            None,
+            None,
+        )
+        .unwrap();
+        let metadata = fs::metadata(dir.join("a.txt")).unwrap();
+        assert_eq!(
+            FileTime::from_last_modification_time(&metadata).unix_seconds(),
+            1_700_000_000
+        );
+        assert_eq!(
+            FileTime::from_last_access_time(&metadata).unix_seconds(),
+            1_500_000_000
+        );
+
+        // Without times both are set to now
+        set_timestamps(
+            vec![dir.join("a.txt").to_string_lossy().to_string()],
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        let metadata = fs::metadata(dir.join("a.txt")).unwrap();
+        assert!(FileTime::from_last_modification_time(&metadata).unix_seconds() > 1_700_000_000);
+    }
+
     #[cfg(not(target_os = "macos"))]
     #[test]
     fn test_request_full_disk_access_noop_non_macos() {
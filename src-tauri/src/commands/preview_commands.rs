@@ -1,7 +1,12 @@
+use crate::archive::browse::{self, ArchivePath};
+use crate::error_handling::{Error, ErrorCode};
+use crate::state::archive_listing_data::ArchiveListingState;
 use base64::Engine;
 use anyhow::Result;
 use serde::Serialize;
 use std::{fs, io::Read, path::{Path, PathBuf}};
+use std::sync::{Arc, Mutex};
+use tauri::State;
 
 #[derive(Serialize, Debug)]
 #[serde(tag = "kind")]
@@ -57,7 +62,22 @@ fn detect_mime(path: &Path, head: &[u8]) -> Option<&'static str> {
 }
 
 #[tauri::command]
-pub fn build_preview(path: String) -> Result<PreviewPayload, String> {
+pub fn build_preview(
+    path: String,
+    archive_listing_state: State<Arc<Mutex<ArchiveListingState>>>,
+) -> Result<PreviewPayload, String> {
+    build_preview_impl(path, archive_listing_state.inner().clone())
+}
+
+pub fn build_preview_impl(
+    path: String,
+    archive_listing_state: Arc<Mutex<ArchiveListingState>>,
+) -> Result<PreviewPayload, String> {
+    // Paths like `/home/me/x.zip!/docs/readme.md` point into an archive
+    if let Some(archive_path) = ArchivePath::parse(&path) {
+        return build_archive_preview(&archive_path, &archive_listing_state);
+    }
+
     let p = PathBuf::from(&path);
     let name = filename(&p);
 
@@ -99,15 +119,35 @@ pub fn build_preview(path: String) -> Result<PreviewPayload, String> {
     // Read a small head for detection + maybe text
     let head = read_prefix(&p, 256 * 1024).map_err(|e| e.to_string())?;
     let mime = detect_mime(&p, &head).unwrap_or("application/octet-stream");
+    build_content_preview(
+        name,
+        mime,
+        head,
+        meta.len(),
+        || fs::read(&p).map_err(|e| e.to_string()),
+        || Ok(path.clone()),
+    )
+}
 
+/// Builds the preview of a file from the start of its content.
+/// `read_all` is only called for images and pdfs which are small enough to be shown completely,
+/// `media_path` only for videos and audio files, which the frontend plays from a path.
+fn build_content_preview(
+    name: String,
+    mime: &str,
+    head: Vec<u8>,
+    size: u64,
+    read_all: impl FnOnce() -> Result<Vec<u8>, String>,
+    media_path: impl FnOnce() -> Result<String, String>,
+) -> Result<PreviewPayload, String> {
     // Branch by mime top-level type
     if mime.starts_with("image/") {
         // Encode entire file only if small; else just the head (fast path)
         // You can raise this cap depending on your perf goals
         let cap = 6 * 1024 * 1024;
-        let bytes = meta.len() as usize;
+        let bytes = size as usize;
         let data = if bytes <= cap {
-            fs::read(&p).map_err(|e| e.to_string())?
+            read_all()?
         } else {
             head.clone()
         };
@@ -117,9 +157,9 @@ pub fn build_preview(path: String) -> Result<PreviewPayload, String> {
     if mime == "application/pdf" {
         // Encode entire file only if small; else just the head (fast path)
         let cap = 12 * 1024 * 1024; // Allow larger PDFs than images
-        let bytes = meta.len() as usize;
+        let bytes = size as usize;
         let data = if bytes <= cap {
-            fs::read(&p).map_err(|e| e.to_string())?
+            read_all()?
         } else {
             head.clone()
         };
@@ -128,16 +168,16 @@ pub fn build_preview(path: String) -> Result<PreviewPayload, String> {
     }
 
     if mime.starts_with("video/") {
-        return Ok(PreviewPayload::Video { name, path });
+        return Ok(PreviewPayload::Video { name, path: media_path()? });
     }
 
     if mime.starts_with("audio/") {
-        return Ok(PreviewPayload::Audio { name, path });
+        return Ok(PreviewPayload::Audio { name, path: media_path()? });
     }
 
     // Heuristic: treat smallish or text‑ish files as text
-    let looks_texty = mime.starts_with("text/") || head.iter().all(|&b| b == 9 || b == 10 || b == 13 || (b >= 32 && b < 0xF5));
-    if looks_texty || meta.len() <= 2 * 1024 * 1024 {
+    let looks_texty = mime.starts_with("text/") || head.iter().all(|&b| b == 9 || b == 10 || b == 13 || (32..0xF5).contains(&b));
+    if looks_texty || size <= 2 * 1024 * 1024 {
         let mut det = chardetng::EncodingDetector::new();
         det.feed(&head, true);
         let enc = det.guess(None, true);
@@ -155,6 +195,82 @@ pub fn build_preview(path: String) -> Result<PreviewPayload, String> {
     Ok(PreviewPayload::Unknown { name })
 }
 
+/// Previews a file or folder inside an archive, see `archive::browse` for the path format.
+/// Folders are summarized from the archive listing, files are read up to the size which is
+/// shown completely. Videos and audio files are extracted to the temp directory, so the
+/// frontend can play them from a path.
+fn build_archive_preview(
+    archive_path: &ArchivePath,
+    archive_listing_state: &Arc<Mutex<ArchiveListingState>>,
+) -> Result<PreviewPayload, String> {
+    let name = archive_path.name();
+    let entries = browse::cached_entries(Path::new(&archive_path.archive), archive_listing_state)?;
+    let tree = browse::ArchiveTree::new(&entries);
+
+    if let Some(children) = tree.children_of(&archive_path.inner) {
+        let mut size = 0;
+        let mut latest_modified: Option<std::time::SystemTime> = None;
+        for entry in children.values().filter_map(|node| node.entry.as_ref()) {
+            size += entry.size;
+            if let Some(modified) = entry.modified {
+                latest_modified = latest_modified.max(Some(modified));
+            }
+        }
+        return Ok(PreviewPayload::Folder {
+            name,
+            size,
+            item_count: children.len(),
+            modified: latest_modified.map(|t| chrono::DateTime::<chrono::Local>::from(t).to_rfc3339()),
+        });
+    }
+
+    let (entry, content) = browse::read_archive_file(archive_path, ARCHIVE_PREVIEW_READ_LIMIT)?;
+    let head = content[..content.len().min(256 * 1024)].to_vec();
+    let mime = detect_mime(Path::new(&archive_path.inner), &head).unwrap_or("application/octet-stream");
+    build_content_preview(
+        name,
+        mime,
+        head,
+        entry.size,
+        || Ok(content.clone()),
+        || extract_for_playback(archive_path, archive_listing_state),
+    )
+}
+
+/// Images and pdfs up to this size are shown completely, so no more than this is read from archives.
+const ARCHIVE_PREVIEW_READ_LIMIT: u64 = 12 * 1024 * 1024;
+
+/// Videos and audio files up to this size are extracted from archives to be played.
+const ARCHIVE_PLAYBACK_EXTRACT_LIMIT: u64 = 1024 * 1024 * 1024;
+
+/// Extracts a file from an archive to the preview directory of the archive and returns the
+/// path of the copy. The copy is named after a hash of the path inside the archive, so
+/// previewing it again overwrites it. The previews are removed with the archive listing.
+fn extract_for_playback(
+    archive_path: &ArchivePath,
+    archive_listing_state: &Arc<Mutex<ArchiveListingState>>,
+) -> Result<String, String> {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    archive_path.inner.hash(&mut hasher);
+    let directory = archive_listing_state
+        .lock()
+        .map_err(|_| {
+            Error::new(
+                ErrorCode::InternalError,
+                "Failed to acquire lock on archive listings".to_string(),
+            )
+            .to_json()
+        })?
+        .preview_directory(Path::new(&archive_path.archive))?
+        .join(format!("{:016x}", hasher.finish()));
+    fs::create_dir_all(&directory).map_err(|e| e.to_string())?;
+    let target = directory.join(archive_path.name());
+
+    browse::copy_archive_file(archive_path, &target, ARCHIVE_PLAYBACK_EXTRACT_LIMIT)?;
+    Ok(target.to_string_lossy().to_string())
+}
+
 #[cfg(test)]
 mod preview_tests {
     use super::*;
@@ -162,6 +278,10 @@ mod preview_tests {
     use tempfile::TempDir;
     use crate::{log_info, log_error};
 
+    fn test_listing_state() -> Arc<Mutex<ArchiveListingState>> {
+        Arc::new(Mutex::new(ArchiveListingState::new()))
+    }
+
     #[test]
     fn test_filename() {
         log_info!("Starting test_filename");
@@ -247,7 +367,7 @@ mod preview_tests {
         
         log_info!("Test folder structure created successfully");
         
-        let result = build_preview(test_dir.to_string_lossy().to_string());
+        let result = build_preview_impl(test_dir.to_string_lossy().to_string(), test_listing_state());
         
         match result {
             Ok(PreviewPayload::Folder { name, size, item_count, modified, .. }) => {
@@ -284,7 +404,7 @@ mod preview_tests {
         
         log_info!("Test text file created successfully");
         
-        let result = build_preview(test_file.to_string_lossy().to_string());
+        let result = build_preview_impl(test_file.to_string_lossy().to_string(), test_listing_state());
         
         match result {
             Ok(PreviewPayload::Text { name, text, truncated }) => {
@@ -323,7 +443,7 @@ mod preview_tests {
         
         log_info!("Test PNG file created successfully");
         
-        let result = build_preview(test_file.to_string_lossy().to_string());
+        let result = build_preview_impl(test_file.to_string_lossy().to_string(), test_listing_state());
         
         match result {
             Ok(PreviewPayload::Image { name, data_uri, bytes }) => {
@@ -360,7 +480,7 @@ mod preview_tests {
         
         log_info!("Test PDF file created successfully");
         
-        let result = build_preview(test_file.to_string_lossy().to_string());
+        let result = build_preview_impl(test_file.to_string_lossy().to_string(), test_listing_state());
         
         match result {
             Ok(PreviewPayload::Pdf { name, data_uri: _, bytes, .. }) => {
@@ -386,7 +506,7 @@ mod preview_tests {
     fn test_build_preview_nonexistent_file() {
         log_info!("Starting test_build_preview_nonexistent_file");
         
-        let result = build_preview("/nonexistent/path".to_string());
+        let result = build_preview_impl("/nonexistent/path".to_string(), test_listing_state());
         
         match result {
             Err(e) => {
@@ -415,7 +535,7 @@ mod preview_tests {
         
         log_info!("Large test file created successfully (300,000 characters)");
         
-        let result = build_preview(test_file.to_string_lossy().to_string());
+        let result = build_preview_impl(test_file.to_string_lossy().to_string(), test_listing_state());
         
         match result {
             Ok(PreviewPayload::Text { name, text, truncated }) => {
@@ -461,7 +581,7 @@ mod preview_tests {
         
         log_info!("Large folder created successfully (250 files)");
         
-        let result = build_preview(test_dir.to_string_lossy().to_string());
+        let result = build_preview_impl(test_dir.to_string_lossy().to_string(), test_listing_state());
         
         match result {
             Ok(PreviewPayload::Folder { name, size, item_count, modified, .. }) => {
@@ -482,4 +602,65 @@ mod preview_tests {
         
         log_info!("test_build_preview_folder_truncation completed successfully");
     }
+
+    #[test]
+    fn test_build_preview_archive_entries() {
+        log_info!("Starting test_build_preview_archive_entries");
+
+        let temp_dir = TempDir::new().unwrap();
+        let zip_path = temp_dir.path().join("bundle.zip");
+        {
+            use std::io::Write;
+            let mut zip = zip::ZipWriter::new(fs::File::create(&zip_path).unwrap());
+            zip.start_file::<_, ()>("docs/notes.txt", zip::write::FileOptions::default())
+                .unwrap();
+            zip.write_all(b"Notes inside an archive").unwrap();
+            zip.start_file::<_, ()>("docs/clip.mp4", zip::write::FileOptions::default())
+                .unwrap();
+            zip.write_all(b"not really a video").unwrap();
+            zip.finish().unwrap();
+        }
+        let zip = zip_path.to_string_lossy().to_string();
+        let listing_state = Arc::new(Mutex::new(ArchiveListingState::new()));
+
+        match build_preview_impl(format!("{}!/docs/notes.txt", zip), listing_state.clone()) {
+            Ok(PreviewPayload::Text { name, text, truncated }) => {
+                assert_eq!(name, "notes.txt");
+                assert_eq!(text, "Notes inside an archive");
+                assert!(!truncated);
+            }
+            other => panic!("Expected text preview, got: {:?}", other),
+        }
+
+        match build_preview_impl(format!("{}!/docs", zip), listing_state.clone()) {
+            Ok(PreviewPayload::Folder { name, size, item_count, .. }) => {
+                assert_eq!(name, "docs");
+                assert_eq!(item_count, 2);
+                assert_eq!(size, 23 + 18);
+            }
+            other => panic!("Expected folder preview, got: {:?}", other),
+        }
+
+        // Media is extracted, so the frontend can play it from a path
+        match build_preview_impl(format!("{}!/docs/clip.mp4", zip), listing_state.clone()) {
+            Ok(PreviewPayload::Video { name, path }) => {
+                assert_eq!(name, "clip.mp4");
+                assert_eq!(fs::read(&path).unwrap(), b"not really a video");
+                let preview_directory = listing_state
+                    .lock()
+                    .unwrap()
+                    .preview_directory(&zip_path)
+                    .unwrap();
+                assert!(Path::new(&path).starts_with(preview_directory));
+
+                // Extracted previews don't outlive the app
+                listing_state.lock().unwrap().close();
+                assert!(!Path::new(&path).exists());
+            }
+            other => panic!("Expected video preview, got: {:?}", other),
+        }
+
+        assert!(build_preview_impl(format!("{}!/docs/missing.txt", zip), listing_state.clone()).is_err());
+        log_info!("test_build_preview_archive_entries completed successfully");
+    }
 }
@@ -0,0 +1,529 @@
+use crate::commands::sftp_file_system_operation_commands::{
+    filename_from_path, get_session, session_temp_dir,
+};
+use crate::error_handling::{Error, ErrorCode};
+use crate::remote::transfer::{download_to_path, upload_from_path};
+use crate::state::remote_edit_data::{FileVersion, RemoteEdit, RemoteEditState, RemoteEditStatus};
+use crate::state::sftp_session_data::{SftpSession, SftpSessionState};
+use crate::{log_error, log_info};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tauri::State;
+
+/// How a conflict between the local copy and a remote file changed by someone else is resolved.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[allow(clippy::enum_variant_names)]
+pub enum RemoteEditResolution {
+    /// Uploads the local copy over the changed remote file.
+    KeepLocal,
+    /// Replaces the local copy with the remote file, the local changes are lost.
+    KeepRemote,
+    /// Uploads the local copy next to the remote file as `<name> (conflict <time>)` and
+    /// replaces the local copy with the remote file.
+    KeepBoth,
+}
+
+fn lock_error() -> String {
+    Error::new(
+        ErrorCode::InternalError,
+        "Failed to acquire lock on remote edit state".to_string(),
+    )
+    .to_json()
+}
+
+fn edit_not_found(edit_id: u64) -> String {
+    log_error!("Remote edit {} does not exist", edit_id);
+    Error::new(
+        ErrorCode::ResourceNotFound,
+        format!("Remote edit {} does not exist", edit_id),
+    )
+    .to_json()
+}
+
+fn remote_version(session: &SftpSession, remote_path: &str) -> Result<Option<FileVersion>, String> {
+    session.with_sftp(|sftp| {
+        Ok(sftp
+            .stat(Path::new(remote_path))
+            .ok()
+            .map(|stat| FileVersion::of_remote(stat.mtime, stat.size)))
+    })
+}
+
+fn local_version(path: &Path) -> Result<FileVersion, String> {
+    FileVersion::of_local(path)
+        .ok_or_else(|| format!("The local copy {} does not exist", path.display()))
+}
+
+/// Replaces the local copy with the remote file and returns both versions.
+fn download_edit(
+    session: &SftpSession,
+    edit: &RemoteEdit,
+) -> Result<(FileVersion, FileVersion), String> {
+    let stat = download_to_path(session, &edit.remote_path, &edit.local_path)?;
+    Ok((
+        FileVersion::of_remote(stat.mtime, stat.size),
+        local_version(&edit.local_path)?,
+    ))
+}
+
+/// Uploads the local copy over the remote file and returns both versions.
+fn upload_edit(
+    session: &SftpSession,
+    edit: &RemoteEdit,
+    local: FileVersion,
+) -> Result<(FileVersion, FileVersion), String> {
+    upload_from_path(session, &edit.local_path, &edit.remote_path)?;
+    let remote = remote_version(session, &edit.remote_path)?
+        .ok_or_else(|| format!("{} is missing after the upload", edit.remote_path))?;
+    Ok((remote, local))
+}
+
+/// `app.conf` becomes `app (conflict 2024-06-01 142233).conf`.
+fn conflict_copy_path(remote_path: &str) -> String {
+    let (directory, name) = match remote_path.rsplit_once('/') {
+        Some((directory, name)) => (format!("{}/", directory), name),
+        None => (String::new(), remote_path),
+    };
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{}", extension)),
+        _ => (name, String::new()),
+    };
+    format!(
+        "{}{} (conflict {}){}",
+        directory,
+        stem,
+        chrono::Local::now().format("%Y-%m-%d %H%M%S"),
+        extension
+    )
+}
+
+/// Uploads one saved local copy, unless the remote file was changed since it was downloaded.
+fn sync_edit(
+    edit: &RemoteEdit,
+    sftp_state: &Arc<Mutex<SftpSessionState>>,
+) -> Result<Option<(FileVersion, FileVersion)>, String> {
+    let session = get_session(edit.session_id, sftp_state)?;
+    let local = local_version(&edit.local_path)?;
+    if remote_version(&session, &edit.remote_path)? != Some(edit.remote_version) {
+        return Ok(None);
+    }
+    upload_edit(&session, edit, local).map(Some)
+}
+
+/// Checks the local copies of all edits once and uploads the saved ones. Called by the watcher
+/// thread of the `RemoteEditState`, the state is only locked while the edits are checked.
+pub fn sync_remote_edits(
+    state: &Arc<Mutex<RemoteEditState>>,
+    sftp_state: &Arc<Mutex<SftpSessionState>>,
+) {
+    let open_sessions: Vec<u64> = match sftp_state.lock() {
+        Ok(sftp_state) => sftp_state
+            .sessions()
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect(),
+        Err(_) => return,
+    };
+    let ready = match state.lock() {
+        Ok(mut state) => {
+            state.retain_sessions(&open_sessions);
+            state.take_ready_uploads()
+        }
+        Err(_) => return,
+    };
+
+    for edit in ready {
+        let result = sync_edit(&edit, sftp_state);
+        let Ok(mut state) = state.lock() else {
+            return;
+        };
+        match result {
+            Ok(Some((remote, local))) => {
+                log_info!("Uploaded the local copy of {}", edit.remote_path);
+                state.mark_synced(edit.id, remote, local, true);
+            }
+            Ok(None) => {
+                log_info!(
+                    "{} was changed on the server, not uploading",
+                    edit.remote_path
+                );
+                state.mark_conflict(edit.id);
+            }
+            Err(err) => {
+                log_error!(
+                    "Failed to upload the local copy of {}: {}",
+                    edit.remote_path,
+                    err
+                );
+                state.mark_failed(edit.id, err);
+            }
+        }
+    }
+}
+
+/// Downloads a remote file into a temporary local copy and opens it in the default app. Every
+/// time the copy is saved it is uploaded to the server again, unless someone else changed the
+/// remote file meanwhile, then the edit is in `Conflict` until it is resolved with
+/// `resolve_remote_edit_conflict`. Editing a file which is already edited opens the existing copy.
+///
+/// # Arguments
+/// * `session_id` - The id of a session opened with `open_sftp_session`.
+/// * `file_path` - The path of the file on the server.
+/// * `open_file` - Whether to open the copy in the default app, true if not given.
+/// * `sftp_state` - The open SFTP sessions.
+/// * `state` - The edited remote files.
+///
+/// # Returns
+/// * `Ok(RemoteEdit)` - The edit with the path of the local copy and its status.
+/// * `Err(String)` - If the session does not exist or the file can't be downloaded or opened.
+///
+/// # Example
+/// ```rust
+/// let edit = edit_sftp_file(session_id, "/etc/nginx/nginx.conf".to_string(), None, sftp_state, state).await?;
+/// ```
+#[tauri::command]
+pub async fn edit_sftp_file(
+    session_id: u64,
+    file_path: String,
+    open_file: Option<bool>,
+    sftp_state: State<'_, Arc<Mutex<SftpSessionState>>>,
+    state: State<'_, Arc<Mutex<RemoteEditState>>>,
+) -> Result<RemoteEdit, String> {
+    let sftp_state = sftp_state.inner().clone();
+    let state = state.inner().clone();
+    tokio::task::spawn_blocking(move || {
+        edit_sftp_file_impl(session_id, file_path, open_file, sftp_state, state)
+    })
+    .await
+    .map_err(|e| Error::new(ErrorCode::InternalError, e.to_string()).to_json())?
+}
+
+pub fn edit_sftp_file_impl(
+    session_id: u64,
+    file_path: String,
+    open_file: Option<bool>,
+    sftp_state: Arc<Mutex<SftpSessionState>>,
+    state: Arc<Mutex<RemoteEditState>>,
+) -> Result<RemoteEdit, String> {
+    let session = get_session(session_id, &sftp_state)?;
+    let existing = state
+        .lock()
+        .map_err(|_| lock_error())?
+        .find(session_id, &file_path);
+
+    let edit = match existing {
+        Some(edit) => edit,
+        None => {
+            let id = state.lock().map_err(|_| lock_error())?.next_id();
+            let local_path = session_temp_dir(session_id)
+                .join(format!("edit-{}", id))
+                .join(filename_from_path(&file_path));
+            if let Some(parent) = local_path.parent() {
+                fs::create_dir_all(parent).map_err(|e| {
+                    Error::new(
+                        ErrorCode::InternalError,
+                        format!("Failed to create temp directory: {}", e),
+                    )
+                    .to_json()
+                })?;
+            }
+            let stat =
+                download_to_path(session.as_ref(), &file_path, &local_path).map_err(|err| {
+                    log_error!("Failed to download {} for editing: {}", file_path, err);
+                    Error::new(ErrorCode::ResourceNotFound, err).to_json()
+                })?;
+            let local = local_version(&local_path)
+                .map_err(|err| Error::new(ErrorCode::InternalError, err).to_json())?;
+            let edit = RemoteEdit::new(
+                id,
+                session_id,
+                file_path,
+                local_path,
+                FileVersion::of_remote(stat.mtime, stat.size),
+                local,
+            );
+            state.lock().map_err(|_| lock_error())?.insert(edit.clone());
+            edit
+        }
+    };
+
+    if open_file.unwrap_or(true) {
+        open::that(&edit.local_path).map_err(|err| {
+            log_error!("Failed to open {}: {}", edit.local_path.display(), err);
+            Error::new(
+                ErrorCode::InternalError,
+                format!("Failed to open file in default app: {}", err),
+            )
+            .to_json()
+        })?;
+    }
+    Ok(edit)
+}
+
+/// Lists the edited remote files with their status, the frontend polls it to show pending
+/// uploads and conflicts.
+#[tauri::command]
+pub fn list_remote_edits(
+    state: State<Arc<Mutex<RemoteEditState>>>,
+) -> Result<Vec<RemoteEdit>, String> {
+    list_remote_edits_impl(state.inner().clone())
+}
+
+pub fn list_remote_edits_impl(
+    state: Arc<Mutex<RemoteEditState>>,
+) -> Result<Vec<RemoteEdit>, String> {
+    Ok(state.lock().map_err(|_| lock_error())?.list())
+}
+
+/// Resolves the conflict of an edit whose remote file was changed by someone else.
+///
+/// # Arguments
+/// * `edit_id` - The id of the edit in `Conflict`.
+/// * `resolution` - `KeepLocal`, `KeepRemote` or `KeepBoth`.
+/// * `sftp_state` - The open SFTP sessions.
+/// * `state` - The edited remote files.
+///
+/// # Returns
+/// * `Ok(RemoteEdit)` - The edit, `Synced` again.
+/// * `Err(String)` - If the edit does not exist or is not in conflict, or the transfer failed.
+///   A failed resolution leaves the edit in `Conflict`.
+///
+/// # Example
+/// ```rust
+/// let edit = resolve_remote_edit_conflict(edit.id, RemoteEditResolution::KeepBoth, sftp_state, state).await?;
+/// ```
+#[tauri::command]
+pub async fn resolve_remote_edit_conflict(
+    edit_id: u64,
+    resolution: RemoteEditResolution,
+    sftp_state: State<'_, Arc<Mutex<SftpSessionState>>>,
+    state: State<'_, Arc<Mutex<RemoteEditState>>>,
+) -> Result<RemoteEdit, String> {
+    let sftp_state = sftp_state.inner().clone();
+    let state = state.inner().clone();
+    tokio::task::spawn_blocking(move || {
+        resolve_remote_edit_conflict_impl(edit_id, resolution, sftp_state, state)
+    })
+    .await
+    .map_err(|e| Error::new(ErrorCode::InternalError, e.to_string()).to_json())?
+}
+
+pub fn resolve_remote_edit_conflict_impl(
+    edit_id: u64,
+    resolution: RemoteEditResolution,
+    sftp_state: Arc<Mutex<SftpSessionState>>,
+    state: Arc<Mutex<RemoteEditState>>,
+) -> Result<RemoteEdit, String> {
+    let edit = {
+        let mut state = state.lock().map_err(|_| lock_error())?;
+        let edit = state.get(edit_id).ok_or_else(|| edit_not_found(edit_id))?;
+        if edit.status != RemoteEditStatus::Conflict {
+            return Err(Error::new(
+                ErrorCode::InvalidInput,
+                format!("Remote edit {} is not in conflict", edit_id),
+            )
+            .to_json());
+        }
+        // Keeps the watcher away while the files are transferred
+        state.begin_sync(edit_id);
+        edit
+    };
+
+    let resolve = || -> Result<(FileVersion, FileVersion), String> {
+        let session = get_session(edit.session_id, &sftp_state)?;
+        match resolution {
+            RemoteEditResolution::KeepLocal => {
+                upload_edit(&session, &edit, local_version(&edit.local_path)?)
+            }
+            RemoteEditResolution::KeepRemote => download_edit(&session, &edit),
+            RemoteEditResolution::KeepBoth => {
+                upload_from_path(
+                    session.as_ref(),
+                    &edit.local_path,
+                    &conflict_copy_path(&edit.remote_path),
+                )?;
+                download_edit(&session, &edit)
+            }
+        }
+    };
+
+    let result = resolve();
+    let mut state = state.lock().map_err(|_| lock_error())?;
+    match result {
+        Ok((remote, local)) => {
+            state.mark_synced(
+                edit_id,
+                remote,
+                local,
+                resolution != RemoteEditResolution::KeepRemote,
+            );
+            state.get(edit_id).ok_or_else(|| edit_not_found(edit_id))
+        }
+        Err(err) => {
+            log_error!(
+                "Failed to resolve the conflict of {}: {}",
+                edit.remote_path,
+                err
+            );
+            state.mark_conflict(edit_id);
+            Err(Error::new(ErrorCode::InternalError, err).to_json())
+        }
+    }
+}
+
+/// Stops tracking an edit and removes its local copy.
+///
+/// # Arguments
+/// * `edit_id` - The id of the edit.
+/// * `discard_changes` - Must be true to stop an edit with changes which are not uploaded.
+/// * `state` - The edited remote files.
+///
+/// # Returns
+/// * `Ok(())` - The edit was stopped.
+/// * `Err(String)` - `ResourceNotFound` if the edit does not exist, `InvalidInput` if it has
+///   changes which are not uploaded and `discard_changes` is not set.
+#[tauri::command]
+pub fn stop_remote_edit(
+    edit_id: u64,
+    discard_changes: Option<bool>,
+    state: State<Arc<Mutex<RemoteEditState>>>,
+) -> Result<(), String> {
+    stop_remote_edit_impl(edit_id, discard_changes, state.inner().clone())
+}
+
+pub fn stop_remote_edit_impl(
+    edit_id: u64,
+    discard_changes: Option<bool>,
+    state: Arc<Mutex<RemoteEditState>>,
+) -> Result<(), String> {
+    let mut state = state.lock().map_err(|_| lock_error())?;
+    let edit = state.get(edit_id).ok_or_else(|| edit_not_found(edit_id))?;
+    if edit.has_pending_changes() && !discard_changes.unwrap_or(false) {
+        return Err(Error::new(
+            ErrorCode::InvalidInput,
+            format!(
+                "The local copy of {} has changes which were not uploaded",
+                edit.remote_path
+            ),
+        )
+        .to_json());
+    }
+    state.remove(edit_id);
+    drop(state);
+
+    // The copy is alone in a directory named after the edit
+    if let Some(directory) = edit.local_path.parent() {
+        let _ = fs::remove_dir_all(directory);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests_remote_edit_commands {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn state_with_edit(local_path: &Path) -> Arc<Mutex<RemoteEditState>> {
+        let mut state = RemoteEditState::new();
+        let id = state.next_id();
+        state.insert(RemoteEdit::new(
+            id,
+            1,
+            "/srv/app.conf".to_string(),
+            local_path.to_path_buf(),
+            FileVersion::of_remote(Some(1_700_000_000), Some(9)),
+            FileVersion::of_local(local_path).unwrap(),
+        ));
+        Arc::new(Mutex::new(state))
+    }
+
+    #[test]
+    fn conflict_copy_path_test() {
+        let path = conflict_copy_path("/srv/app.conf");
+        assert!(path.starts_with("/srv/app (conflict "), "{}", path);
+        assert!(path.ends_with(").conf"), "{}", path);
+        assert!(conflict_copy_path("Makefile").starts_with("Makefile (conflict "));
+        assert!(conflict_copy_path("/home/.bashrc").starts_with("/home/.bashrc (conflict "));
+    }
+
+    #[test]
+    fn stop_edit_keeps_unsaved_changes_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let edit_dir = temp_dir.path().join("edit-1");
+        fs::create_dir_all(&edit_dir).unwrap();
+        let local_path = edit_dir.join("app.conf");
+        fs::write(&local_path, "port = 80").unwrap();
+        let state = state_with_edit(&local_path);
+
+        state.lock().unwrap().mark_conflict(1);
+        let result = stop_remote_edit_impl(1, None, state.clone());
+        assert!(result.unwrap_err().contains("InvalidInput"));
+        assert!(local_path.exists());
+
+        assert!(stop_remote_edit_impl(1, Some(true), state.clone()).is_ok());
+        assert!(!edit_dir.exists());
+        assert!(list_remote_edits_impl(state.clone()).unwrap().is_empty());
+        assert!(stop_remote_edit_impl(1, None, state)
+            .unwrap_err()
+            .contains("ResourceNotFound"));
+    }
+
+    #[test]
+    fn resolve_needs_a_conflict_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let local_path = temp_dir.path().join("app.conf");
+        fs::write(&local_path, "port = 80").unwrap();
+        let state = state_with_edit(&local_path);
+        let sftp_state = Arc::new(Mutex::new(SftpSessionState::new()));
+
+        let result = resolve_remote_edit_conflict_impl(
+            1,
+            RemoteEditResolution::KeepLocal,
+            sftp_state.clone(),
+            state.clone(),
+        );
+        assert!(result.unwrap_err().contains("InvalidInput"));
+
+        // Without the session the conflict stays
+        state.lock().unwrap().mark_conflict(1);
+        let result = resolve_remote_edit_conflict_impl(
+            1,
+            RemoteEditResolution::KeepRemote,
+            sftp_state,
+            state.clone(),
+        );
+        assert!(result.is_err());
+        assert_eq!(
+            state.lock().unwrap().get(1).unwrap().status,
+            RemoteEditStatus::Conflict
+        );
+    }
+
+    #[test]
+    fn edits_of_closed_sessions_are_dropped_by_sync_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let local_path = temp_dir.path().join("app.conf");
+        fs::write(&local_path, "port = 80").unwrap();
+        let state = state_with_edit(&local_path);
+        let sftp_state = Arc::new(Mutex::new(SftpSessionState::new()));
+
+        sync_remote_edits(&state, &sftp_state);
+
+        assert!(list_remote_edits_impl(state).unwrap().is_empty());
+        assert!(local_path.exists());
+    }
+
+    #[test]
+    fn edit_needs_an_open_session_test() {
+        let result = edit_sftp_file_impl(
+            5,
+            "/srv/app.conf".to_string(),
+            Some(false),
+            Arc::new(Mutex::new(SftpSessionState::new())),
+            Arc::new(Mutex::new(RemoteEditState::new())),
+        );
+        assert!(result.unwrap_err().contains("ResourceNotFound"));
+    }
+}
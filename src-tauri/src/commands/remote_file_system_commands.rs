@@ -0,0 +1,853 @@
+use crate::commands::preview_commands::PreviewPayload;
+use crate::error_handling::{Error, ErrorCode};
+use crate::log_error;
+use crate::remote::{
+    build_preview, connect, remote_entries, remove_recursive, RemoteFileSystem, RemoteProtocol,
+    RemoteSessionConfig,
+};
+use crate::state::remote_session_data::RemoteSessionState;
+use crate::state::sftp_session_data::SftpSessionState;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::State;
+
+fn lock_error() -> String {
+    Error::new(
+        ErrorCode::InternalError,
+        "Failed to acquire lock on remote session state".to_string(),
+    )
+    .to_json()
+}
+
+fn unknown_session(session_id: u64) -> String {
+    log_error!("Remote session {} does not exist", session_id);
+    Error::new(
+        ErrorCode::ResourceNotFound,
+        format!("Remote session {} does not exist or was closed", session_id),
+    )
+    .to_json()
+}
+
+/// Looks up an FTP, WebDAV, S3 or SFTP session, the locks on the states are released before the
+/// caller uses it.
+pub(crate) fn remote_session(
+    session_id: u64,
+    sftp_state: &Arc<Mutex<SftpSessionState>>,
+    remote_state: &Arc<Mutex<RemoteSessionState>>,
+) -> Result<Arc<dyn RemoteFileSystem>, String> {
+    if let Some(session) = remote_state
+        .lock()
+        .map_err(|_| lock_error())?
+        .get(session_id)
+    {
+        return Ok(session);
+    }
+    let session = sftp_state
+        .lock()
+        .map_err(|_| lock_error())?
+        .get(session_id)
+        .ok_or_else(|| unknown_session(session_id))?;
+    Ok(session)
+}
+
+/// A session as it is shown to the frontend, without its credentials.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RemoteSessionInfo {
+    pub id: u64,
+    pub protocol: RemoteProtocol,
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub connected: bool,
+}
+
+fn session_info(id: u64, session: &dyn RemoteFileSystem) -> RemoteSessionInfo {
+    let endpoint = session.endpoint();
+    RemoteSessionInfo {
+        id,
+        protocol: endpoint.protocol,
+        host: endpoint.host,
+        port: endpoint.port,
+        username: endpoint.username,
+        connected: session.is_connected(),
+    }
+}
+
+/// Connects and logs in to an SFTP, FTP, FTPS, WebDAV, WebDAVS or S3 server and keeps the
+/// session open. The other remote commands take the returned session id, they accept the ids of
+/// SFTP sessions opened with `open_sftp_session` as well. SFTP and FTP sessions reconnect
+/// transparently if the connection drops, WebDAV and S3 sessions open a connection per request.
+///
+/// # Arguments
+/// * `config` - The protocol, server and credentials. SFTP sessions log in with the password
+///   and check the host key like `open_sftp_session`. An empty username logs in anonymously
+///   to FTP servers, WebDAV servers additionally take the path of their root as `base_path`.
+///   S3 sessions log in with the access key id and secret access key as username and password
+///   and sign their requests for `region`.
+///
+/// # Returns
+/// * `Ok(u64)` - The id of the new session.
+/// * `Err(String)` - `Unauthorized` if the login is refused or the SFTP host key is not trusted,
+///   or an error if the server can't be reached.
+///
+/// # Example
+/// ```rust
+/// let config = RemoteSessionConfig { protocol: RemoteProtocol::Ftps, host: "ftp.example.com".to_string(), port: None, username: "alice".to_string(), password: "secret".to_string(), base_path: None, region: None, accept_invalid_certificates: false };
+/// let session_id = open_remote_session(config, sftp_state, remote_state).await?;
+/// ```
+#[tauri::command]
+pub async fn open_remote_session(
+    config: RemoteSessionConfig,
+    sftp_state: State<'_, Arc<Mutex<SftpSessionState>>>,
+    remote_state: State<'_, Arc<Mutex<RemoteSessionState>>>,
+) -> Result<u64, String> {
+    let sftp_state = sftp_state.inner().clone();
+    let remote_state = remote_state.inner().clone();
+    // Logging in takes a few round trips, so don't block the command thread
+    tokio::task::spawn_blocking(move || open_remote_session_impl(config, sftp_state, remote_state))
+        .await
+        .map_err(|e| Error::new(ErrorCode::InternalError, e.to_string()).to_json())?
+}
+
+pub fn open_remote_session_impl(
+    config: RemoteSessionConfig,
+    sftp_state: Arc<Mutex<SftpSessionState>>,
+    remote_state: Arc<Mutex<RemoteSessionState>>,
+) -> Result<u64, String> {
+    let sftp_auth = sftp_state.lock().map_err(|_| lock_error())?.auth();
+    let session = connect(config, sftp_auth)?;
+    let mut state = remote_state.lock().map_err(|_| lock_error())?;
+    Ok(state.insert(session))
+}
+
+/// Closes a remote session of any protocol. Transfers still running on it fail.
+///
+/// # Arguments
+/// * `session_id` - The id returned by `open_remote_session` or `open_sftp_session`.
+///
+/// # Returns
+/// * `Ok(())` - The session was closed.
+/// * `Err(String)` - `ResourceNotFound` if the session does not exist.
+///
+/// # Example
+/// ```rust
+/// close_remote_session(session_id, sftp_state, remote_state)?;
+/// ```
+#[tauri::command]
+pub fn close_remote_session(
+    session_id: u64,
+    sftp_state: State<Arc<Mutex<SftpSessionState>>>,
+    remote_state: State<Arc<Mutex<RemoteSessionState>>>,
+) -> Result<(), String> {
+    close_remote_session_impl(
+        session_id,
+        sftp_state.inner().clone(),
+        remote_state.inner().clone(),
+    )
+}
+
+pub fn close_remote_session_impl(
+    session_id: u64,
+    sftp_state: Arc<Mutex<SftpSessionState>>,
+    remote_state: Arc<Mutex<RemoteSessionState>>,
+) -> Result<(), String> {
+    let removed = remote_state
+        .lock()
+        .map_err(|_| lock_error())?
+        .remove(session_id);
+    let session: Arc<dyn RemoteFileSystem> = match removed {
+        Some(session) => session,
+        None => sftp_state
+            .lock()
+            .map_err(|_| lock_error())?
+            .remove(session_id)
+            .ok_or_else(|| unknown_session(session_id))?,
+    };
+    session.disconnect();
+    Ok(())
+}
+
+/// Returns the open sessions of all protocols, SFTP sessions included.
+///
+/// # Returns
+/// * `Ok(Vec<RemoteSessionInfo>)` - The sessions ordered by id.
+/// * `Err(String)` - An error if a state can't be locked.
+///
+/// # Example
+/// ```rust
+/// let sessions = list_remote_sessions(sftp_state, remote_state)?;
+/// ```
+#[tauri::command]
+pub fn list_remote_sessions(
+    sftp_state: State<Arc<Mutex<SftpSessionState>>>,
+    remote_state: State<Arc<Mutex<RemoteSessionState>>>,
+) -> Result<Vec<RemoteSessionInfo>, String> {
+    list_remote_sessions_impl(sftp_state.inner().clone(), remote_state.inner().clone())
+}
+
+pub fn list_remote_sessions_impl(
+    sftp_state: Arc<Mutex<SftpSessionState>>,
+    remote_state: Arc<Mutex<RemoteSessionState>>,
+) -> Result<Vec<RemoteSessionInfo>, String> {
+    let sftp_sessions = sftp_state.lock().map_err(|_| lock_error())?.sessions();
+    let remote_sessions = remote_state.lock().map_err(|_| lock_error())?.sessions();
+    let mut sessions: Vec<RemoteSessionInfo> = sftp_sessions
+        .iter()
+        .map(|(id, session)| session_info(*id, session.as_ref()))
+        .chain(
+            remote_sessions
+                .iter()
+                .map(|(id, session)| session_info(*id, session.as_ref())),
+        )
+        .collect();
+    sessions.sort_by_key(|session| session.id);
+    Ok(sessions)
+}
+
+/// Lists a remote directory with the same model `load_dir` returns for SFTP. Protocols
+/// without permissions, like WebDAV, list the modes of their entries as file type only.
+///
+/// # Arguments
+/// * `session_id` - The id of a session of any protocol.
+/// * `directory` - The remote directory. Relative paths start at the login directory, for
+///   WebDAV at its root.
+///
+/// # Returns
+/// * `Ok(String)` - The `Entries` as JSON, like `open_directory`.
+/// * `Err(String)` - An error if the directory can't be listed.
+///
+/// # Example
+/// ```rust
+/// let entries: Entries = serde_json::from_str(&load_remote_dir(session_id, "/pub".to_string(), sftp_state, remote_state)?)?;
+/// ```
+#[tauri::command]
+pub fn load_remote_dir(
+    session_id: u64,
+    directory: String,
+    sftp_state: State<Arc<Mutex<SftpSessionState>>>,
+    remote_state: State<Arc<Mutex<RemoteSessionState>>>,
+) -> Result<String, String> {
+    load_remote_dir_impl(
+        session_id,
+        directory,
+        sftp_state.inner().clone(),
+        remote_state.inner().clone(),
+    )
+}
+
+pub fn load_remote_dir_impl(
+    session_id: u64,
+    directory: String,
+    sftp_state: Arc<Mutex<SftpSessionState>>,
+    remote_state: Arc<Mutex<RemoteSessionState>>,
+) -> Result<String, String> {
+    let listing = remote_session(session_id, &sftp_state, &remote_state)?.list_dir(&directory)?;
+    serde_json::to_string(&remote_entries(listing)).map_err(|e| {
+        log_error!("Failed to serialize entries: {}", e);
+        Error::new(
+            ErrorCode::InternalError,
+            format!("Failed to serialize entries: {}", e),
+        )
+        .to_json()
+    })
+}
+
+/// Builds the preview of a remote file or directory, like `build_preview_sftp`.
+///
+/// # Arguments
+/// * `session_id` - The id of a session of any protocol.
+/// * `file_path` - The remote file or directory.
+///
+/// # Returns
+/// * `Ok(PreviewPayload)` - The preview, images and PDFs are sent as data URIs.
+/// * `Err(String)` - An error if the path can't be read.
+///
+/// # Example
+/// ```rust
+/// let preview = build_remote_preview(session_id, "/pub/readme.md".to_string(), sftp_state, remote_state)?;
+/// ```
+#[tauri::command]
+pub fn build_remote_preview(
+    session_id: u64,
+    file_path: String,
+    sftp_state: State<Arc<Mutex<SftpSessionState>>>,
+    remote_state: State<Arc<Mutex<RemoteSessionState>>>,
+) -> Result<PreviewPayload, String> {
+    build_remote_preview_impl(
+        session_id,
+        file_path,
+        sftp_state.inner().clone(),
+        remote_state.inner().clone(),
+    )
+}
+
+pub fn build_remote_preview_impl(
+    session_id: u64,
+    file_path: String,
+    sftp_state: Arc<Mutex<SftpSessionState>>,
+    remote_state: Arc<Mutex<RemoteSessionState>>,
+) -> Result<PreviewPayload, String> {
+    let session = remote_session(session_id, &sftp_state, &remote_state)?;
+    build_preview(session.as_ref(), &file_path)
+}
+
+/// Renames or moves a remote file or directory on the same server.
+///
+/// # Arguments
+/// * `session_id` - The id of a session of any protocol.
+/// * `old_path` - The current path.
+/// * `new_path` - The new path. Whether an existing file there is replaced depends on the
+///   server, WebDAV never replaces it.
+///
+/// # Returns
+/// * `Ok(String)` - A success message.
+/// * `Err(String)` - An error if the path can't be renamed.
+///
+/// # Example
+/// ```rust
+/// rename_remote_path(session_id, "/pub/a.txt".to_string(), "/pub/b.txt".to_string(), sftp_state, remote_state)?;
+/// ```
+#[tauri::command]
+pub fn rename_remote_path(
+    session_id: u64,
+    old_path: String,
+    new_path: String,
+    sftp_state: State<Arc<Mutex<SftpSessionState>>>,
+    remote_state: State<Arc<Mutex<RemoteSessionState>>>,
+) -> Result<String, String> {
+    rename_remote_path_impl(
+        session_id,
+        old_path,
+        new_path,
+        sftp_state.inner().clone(),
+        remote_state.inner().clone(),
+    )
+}
+
+pub fn rename_remote_path_impl(
+    session_id: u64,
+    old_path: String,
+    new_path: String,
+    sftp_state: Arc<Mutex<SftpSessionState>>,
+    remote_state: Arc<Mutex<RemoteSessionState>>,
+) -> Result<String, String> {
+    remote_session(session_id, &sftp_state, &remote_state)?.rename(&old_path, &new_path)?;
+    Ok(format!("Renamed {} to {}", old_path, new_path))
+}
+
+/// Deletes a remote file, or a directory with all its content.
+///
+/// # Arguments
+/// * `session_id` - The id of a session of any protocol.
+/// * `path` - The file or directory to delete. Symlinks are deleted themselves, not their
+///   target.
+///
+/// # Returns
+/// * `Ok(String)` - A success message.
+/// * `Err(String)` - An error if something could not be deleted, the entries deleted before
+///   stay deleted.
+///
+/// # Example
+/// ```rust
+/// delete_remote_path(session_id, "/pub/old".to_string(), sftp_state, remote_state)?;
+/// ```
+#[tauri::command]
+pub fn delete_remote_path(
+    session_id: u64,
+    path: String,
+    sftp_state: State<Arc<Mutex<SftpSessionState>>>,
+    remote_state: State<Arc<Mutex<RemoteSessionState>>>,
+) -> Result<String, String> {
+    delete_remote_path_impl(
+        session_id,
+        path,
+        sftp_state.inner().clone(),
+        remote_state.inner().clone(),
+    )
+}
+
+pub fn delete_remote_path_impl(
+    session_id: u64,
+    path: String,
+    sftp_state: Arc<Mutex<SftpSessionState>>,
+    remote_state: Arc<Mutex<RemoteSessionState>>,
+) -> Result<String, String> {
+    let session = remote_session(session_id, &sftp_state, &remote_state)?;
+    remove_recursive(session.as_ref(), &path)?;
+    Ok(format!("Deleted {}", path))
+}
+
+/// Creates a remote directory, its parent has to exist.
+///
+/// # Arguments
+/// * `session_id` - The id of a session of any protocol.
+/// * `path` - The directory to create.
+///
+/// # Returns
+/// * `Ok(String)` - A success message.
+/// * `Err(String)` - An error if the directory exists or can't be created.
+///
+/// # Example
+/// ```rust
+/// create_remote_directory(session_id, "/pub/new".to_string(), sftp_state, remote_state)?;
+/// ```
+#[tauri::command]
+pub fn create_remote_directory(
+    session_id: u64,
+    path: String,
+    sftp_state: State<Arc<Mutex<SftpSessionState>>>,
+    remote_state: State<Arc<Mutex<RemoteSessionState>>>,
+) -> Result<String, String> {
+    create_remote_directory_impl(
+        session_id,
+        path,
+        sftp_state.inner().clone(),
+        remote_state.inner().clone(),
+    )
+}
+
+pub fn create_remote_directory_impl(
+    session_id: u64,
+    path: String,
+    sftp_state: Arc<Mutex<SftpSessionState>>,
+    remote_state: Arc<Mutex<RemoteSessionState>>,
+) -> Result<String, String> {
+    remote_session(session_id, &sftp_state, &remote_state)?.create_dir(&path)?;
+    Ok(format!("Created directory {}", path))
+}
+
+/// Copies a remote file or directory on the server, without downloading it. Only S3 sessions
+/// copy on the server, large objects are copied in parts.
+///
+/// # Arguments
+/// * `session_id` - The id of an S3 session.
+/// * `source_path` - The file or directory to copy.
+/// * `destination_path` - The path of the copy, an existing object there is replaced.
+///
+/// # Returns
+/// * `Ok(String)` - A success message.
+/// * `Err(String)` - `NotImplemented` for other protocols, otherwise an error if something
+///   could not be copied.
+///
+/// # Example
+/// ```rust
+/// copy_remote_path(session_id, "/artifacts/v1".to_string(), "/artifacts/latest".to_string(), sftp_state, remote_state)?;
+/// ```
+#[tauri::command]
+pub fn copy_remote_path(
+    session_id: u64,
+    source_path: String,
+    destination_path: String,
+    sftp_state: State<Arc<Mutex<SftpSessionState>>>,
+    remote_state: State<Arc<Mutex<RemoteSessionState>>>,
+) -> Result<String, String> {
+    copy_remote_path_impl(
+        session_id,
+        source_path,
+        destination_path,
+        sftp_state.inner().clone(),
+        remote_state.inner().clone(),
+    )
+}
+
+pub fn copy_remote_path_impl(
+    session_id: u64,
+    source_path: String,
+    destination_path: String,
+    sftp_state: Arc<Mutex<SftpSessionState>>,
+    remote_state: Arc<Mutex<RemoteSessionState>>,
+) -> Result<String, String> {
+    remote_session(session_id, &sftp_state, &remote_state)?
+        .copy(&source_path, &destination_path)?;
+    Ok(format!("Copied {} to {}", source_path, destination_path))
+}
+
+/// Creates a presigned URL of a remote file, which can be shared or opened in a browser
+/// without credentials until it expires. Only S3 sessions presign URLs.
+///
+/// # Arguments
+/// * `session_id` - The id of an S3 session.
+/// * `path` - The object.
+/// * `method` - The HTTP method the URL allows, `GET` if not set. `PUT` lets someone upload the
+///   object.
+/// * `expires_in_seconds` - How long the URL is valid, at most seven days. One hour if not set.
+///
+/// # Returns
+/// * `Ok(String)` - The URL.
+/// * `Err(String)` - `InvalidInput` for buckets, other methods or expiries, `NotImplemented`
+///   for other protocols.
+///
+/// # Example
+/// ```rust
+/// let url = presign_remote_url(session_id, "/artifacts/app.zip".to_string(), None, Some(600), sftp_state, remote_state)?;
+/// ```
+#[tauri::command]
+pub fn presign_remote_url(
+    session_id: u64,
+    path: String,
+    method: Option<String>,
+    expires_in_seconds: Option<u64>,
+    sftp_state: State<Arc<Mutex<SftpSessionState>>>,
+    remote_state: State<Arc<Mutex<RemoteSessionState>>>,
+) -> Result<String, String> {
+    presign_remote_url_impl(
+        session_id,
+        path,
+        method,
+        expires_in_seconds,
+        sftp_state.inner().clone(),
+        remote_state.inner().clone(),
+    )
+}
+
+pub fn presign_remote_url_impl(
+    session_id: u64,
+    path: String,
+    method: Option<String>,
+    expires_in_seconds: Option<u64>,
+    sftp_state: Arc<Mutex<SftpSessionState>>,
+    remote_state: Arc<Mutex<RemoteSessionState>>,
+) -> Result<String, String> {
+    let method = method.unwrap_or("GET".to_string()).to_ascii_uppercase();
+    let expires_in = Duration::from_secs(expires_in_seconds.unwrap_or(60 * 60));
+    remote_session(session_id, &sftp_state, &remote_state)?
+        .presigned_url(&path, &method, expires_in)
+}
+
+#[cfg(test)]
+mod tests_remote_file_system_commands {
+    use super::*;
+    use crate::models::Entries;
+    use crate::remote::test_servers::{
+        FtpTestServer, S3TestServer, WebDavTestServer, TEST_PASSWORD, TEST_USER,
+    };
+    use std::fs;
+    use std::net::TcpListener;
+
+    fn states() -> (Arc<Mutex<SftpSessionState>>, Arc<Mutex<RemoteSessionState>>) {
+        (
+            Arc::new(Mutex::new(SftpSessionState::new())),
+            Arc::new(Mutex::new(RemoteSessionState::new())),
+        )
+    }
+
+    fn config(protocol: RemoteProtocol, port: u16, base_path: Option<&str>) -> RemoteSessionConfig {
+        RemoteSessionConfig {
+            protocol,
+            host: "127.0.0.1".to_string(),
+            port: Some(port),
+            username: TEST_USER.to_string(),
+            password: TEST_PASSWORD.to_string(),
+            base_path: base_path.map(str::to_string),
+            region: None,
+            accept_invalid_certificates: true,
+        }
+    }
+
+    /// Runs the same commands on a session of any protocol, the server serves `root`.
+    fn check_commands(
+        session_id: u64,
+        root: &std::path::Path,
+        sftp_state: &Arc<Mutex<SftpSessionState>>,
+        remote_state: &Arc<Mutex<RemoteSessionState>>,
+    ) {
+        fs::create_dir_all(root.join("project/src")).unwrap();
+        fs::write(root.join("project/src/main.rs"), "fn main() {}\n").unwrap();
+        fs::write(root.join("project/readme.md"), "# Project\n").unwrap();
+
+        let listing = load_remote_dir_impl(
+            session_id,
+            "/project".to_string(),
+            sftp_state.clone(),
+            remote_state.clone(),
+        )
+        .unwrap();
+        let entries: Entries = serde_json::from_str(&listing).unwrap();
+        assert_eq!(entries.directories.len(), 1);
+        assert_eq!(entries.directories[0].name, "src");
+        assert_eq!(entries.directories[0].path, "/project/src");
+        assert_eq!(entries.files.len(), 1);
+        assert_eq!(entries.files[0].name, "readme.md");
+        assert_eq!(entries.files[0].size_in_bytes, 10);
+
+        match build_remote_preview_impl(
+            session_id,
+            "/project/readme.md".to_string(),
+            sftp_state.clone(),
+            remote_state.clone(),
+        )
+        .unwrap()
+        {
+            PreviewPayload::Text { name, text, .. } => {
+                assert_eq!(name, "readme.md");
+                assert_eq!(text, "# Project\n");
+            }
+            other => panic!("Expected a text preview, got {:?}", other),
+        }
+        match build_remote_preview_impl(
+            session_id,
+            "/project".to_string(),
+            sftp_state.clone(),
+            remote_state.clone(),
+        )
+        .unwrap()
+        {
+            PreviewPayload::Folder { item_count, .. } => assert_eq!(item_count, 2),
+            other => panic!("Expected a folder preview, got {:?}", other),
+        }
+
+        create_remote_directory_impl(
+            session_id,
+            "/project/docs".to_string(),
+            sftp_state.clone(),
+            remote_state.clone(),
+        )
+        .unwrap();
+        assert!(root.join("project/docs").is_dir());
+        rename_remote_path_impl(
+            session_id,
+            "/project/readme.md".to_string(),
+            "/project/docs/readme.md".to_string(),
+            sftp_state.clone(),
+            remote_state.clone(),
+        )
+        .unwrap();
+        assert!(root.join("project/docs/readme.md").is_file());
+
+        delete_remote_path_impl(
+            session_id,
+            "/project".to_string(),
+            sftp_state.clone(),
+            remote_state.clone(),
+        )
+        .unwrap();
+        assert!(!root.join("project").exists());
+    }
+
+    #[test]
+    fn ftp_commands_test() {
+        let server = FtpTestServer::start(false);
+        let (sftp_state, remote_state) = states();
+        let session_id = open_remote_session_impl(
+            config(RemoteProtocol::Ftp, server.port(), None),
+            sftp_state.clone(),
+            remote_state.clone(),
+        )
+        .unwrap();
+        check_commands(session_id, server.root(), &sftp_state, &remote_state);
+    }
+
+    #[test]
+    fn webdav_commands_test() {
+        let server = WebDavTestServer::start(false);
+        let (sftp_state, remote_state) = states();
+        let session_id = open_remote_session_impl(
+            config(
+                RemoteProtocol::WebDav,
+                server.port(),
+                Some(server.base_path()),
+            ),
+            sftp_state.clone(),
+            remote_state.clone(),
+        )
+        .unwrap();
+        check_commands(session_id, server.root(), &sftp_state, &remote_state);
+    }
+
+    #[test]
+    fn sessions_are_listed_and_closed_test() {
+        let ftp_server = FtpTestServer::start(true);
+        let webdav_server = WebDavTestServer::start(true);
+        let (sftp_state, remote_state) = states();
+        let ftp_id = open_remote_session_impl(
+            config(RemoteProtocol::Ftps, ftp_server.port(), None),
+            sftp_state.clone(),
+            remote_state.clone(),
+        )
+        .unwrap();
+        let webdav_id = open_remote_session_impl(
+            config(
+                RemoteProtocol::WebDavs,
+                webdav_server.port(),
+                Some(webdav_server.base_path()),
+            ),
+            sftp_state.clone(),
+            remote_state.clone(),
+        )
+        .unwrap();
+        assert_ne!(ftp_id, webdav_id);
+
+        let sessions = list_remote_sessions_impl(sftp_state.clone(), remote_state.clone()).unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].id, ftp_id.min(webdav_id));
+        let ftp = sessions
+            .iter()
+            .find(|session| session.id == ftp_id)
+            .unwrap();
+        assert_eq!(ftp.protocol, RemoteProtocol::Ftps);
+        assert_eq!(ftp.port, ftp_server.port());
+        assert_eq!(ftp.username, TEST_USER);
+        assert!(ftp.connected);
+
+        close_remote_session_impl(ftp_id, sftp_state.clone(), remote_state.clone()).unwrap();
+        close_remote_session_impl(webdav_id, sftp_state.clone(), remote_state.clone()).unwrap();
+        assert!(
+            list_remote_sessions_impl(sftp_state.clone(), remote_state.clone())
+                .unwrap()
+                .is_empty()
+        );
+
+        let error = load_remote_dir_impl(
+            ftp_id,
+            "/".to_string(),
+            sftp_state.clone(),
+            remote_state.clone(),
+        )
+        .unwrap_err();
+        assert!(error.contains("405"), "{}", error);
+        assert!(close_remote_session_impl(ftp_id, sftp_state, remote_state).is_err());
+    }
+
+    #[test]
+    fn s3_commands_test() {
+        let server = S3TestServer::start(false);
+        server.put_object("artifacts", "app/readme.md", b"# App\n");
+        server.put_object("artifacts", "app/bin/app", b"binary");
+        let (sftp_state, remote_state) = states();
+        let session_id = open_remote_session_impl(
+            config(RemoteProtocol::S3Http, server.port(), None),
+            sftp_state.clone(),
+            remote_state.clone(),
+        )
+        .unwrap();
+
+        let listing = load_remote_dir_impl(
+            session_id,
+            "/".to_string(),
+            sftp_state.clone(),
+            remote_state.clone(),
+        )
+        .unwrap();
+        let entries: Entries = serde_json::from_str(&listing).unwrap();
+        assert_eq!(entries.directories.len(), 1);
+        assert_eq!(entries.directories[0].path, "/artifacts");
+
+        let listing = load_remote_dir_impl(
+            session_id,
+            "/artifacts/app".to_string(),
+            sftp_state.clone(),
+            remote_state.clone(),
+        )
+        .unwrap();
+        let entries: Entries = serde_json::from_str(&listing).unwrap();
+        assert_eq!(entries.directories[0].name, "bin");
+        assert_eq!(entries.files[0].name, "readme.md");
+        assert_eq!(entries.files[0].size_in_bytes, 6);
+        assert_ne!(entries.files[0].last_modified, "1970-01-01 00:00:00");
+
+        match build_remote_preview_impl(
+            session_id,
+            "/artifacts/app/readme.md".to_string(),
+            sftp_state.clone(),
+            remote_state.clone(),
+        )
+        .unwrap()
+        {
+            PreviewPayload::Text { text, .. } => assert_eq!(text, "# App\n"),
+            other => panic!("Expected a text preview, got {:?}", other),
+        }
+
+        copy_remote_path_impl(
+            session_id,
+            "/artifacts/app".to_string(),
+            "/artifacts/app-copy".to_string(),
+            sftp_state.clone(),
+            remote_state.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            server.object("artifacts", "app-copy/bin/app").unwrap(),
+            b"binary"
+        );
+
+        let url = presign_remote_url_impl(
+            session_id,
+            "/artifacts/app-copy/readme.md".to_string(),
+            None,
+            Some(600),
+            sftp_state.clone(),
+            remote_state.clone(),
+        )
+        .unwrap();
+        assert!(url.contains("/artifacts/app-copy/readme.md?"), "{}", url);
+        assert!(url.contains("X-Amz-Expires=600"), "{}", url);
+
+        delete_remote_path_impl(
+            session_id,
+            "/artifacts/app".to_string(),
+            sftp_state.clone(),
+            remote_state.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            server.keys("artifacts"),
+            vec!["app-copy/bin/app", "app-copy/readme.md"]
+        );
+    }
+
+    #[test]
+    fn copy_and_presign_need_s3_test() {
+        let server = FtpTestServer::start(false);
+        let (sftp_state, remote_state) = states();
+        let session_id = open_remote_session_impl(
+            config(RemoteProtocol::Ftp, server.port(), None),
+            sftp_state.clone(),
+            remote_state.clone(),
+        )
+        .unwrap();
+        fs::write(server.root().join("a.txt"), "a").unwrap();
+
+        let error = copy_remote_path_impl(
+            session_id,
+            "/a.txt".to_string(),
+            "/b.txt".to_string(),
+            sftp_state.clone(),
+            remote_state.clone(),
+        )
+        .unwrap_err();
+        assert!(error.contains("407"), "{}", error);
+        let error = presign_remote_url_impl(
+            session_id,
+            "/a.txt".to_string(),
+            None,
+            None,
+            sftp_state,
+            remote_state,
+        )
+        .unwrap_err();
+        assert!(error.contains("407"), "{}", error);
+    }
+
+    #[test]
+    fn unreachable_sftp_session_fails_test() {
+        // Bind and drop a listener to get a port nothing listens on
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let (sftp_state, remote_state) = states();
+        let error = open_remote_session_impl(
+            config(RemoteProtocol::Sftp, port, None),
+            sftp_state.clone(),
+            remote_state.clone(),
+        )
+        .unwrap_err();
+        let error: serde_json::Value = serde_json::from_str(&error).unwrap();
+        assert_eq!(error["code"], 500, "{}", error);
+        assert!(list_remote_sessions_impl(sftp_state, remote_state)
+            .unwrap()
+            .is_empty());
+    }
+}
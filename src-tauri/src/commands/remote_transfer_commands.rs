@@ -0,0 +1,418 @@
+use crate::commands::remote_file_system_commands::remote_session;
+use crate::remote::transfer::{
+    download_directory, plan_download, plan_upload_sources, start_transfer, TransferDirection,
+    TransferOptions,
+};
+use crate::state::jobs_data::JobsState;
+use crate::state::remote_session_data::RemoteSessionState;
+use crate::state::sftp_session_data::SftpSessionState;
+use std::sync::{Arc, Mutex};
+use tauri::State;
+
+/// Uploads local files and directories into a directory on the server of any remote session
+/// as a background job. All protocols upload the same way as `upload_to_sftp` into `.part`
+/// files, servers which can't hash their files are only checked by size.
+///
+/// # Arguments
+/// * `session_id` - The id of a session opened with `open_remote_session` or `open_sftp_session`.
+/// * `local_paths` - The local files and directories to upload.
+/// * `remote_directory` - The directory on the server the paths are uploaded into.
+/// * `options` - Optional resume, verification and overwrite settings.
+/// * `sftp_state` - The open SFTP sessions.
+/// * `remote_state` - The open FTP and WebDAV sessions.
+/// * `jobs_state` - The jobs state the upload is registered in.
+///
+/// # Returns
+/// * `Ok(u64)` - The id of the job, its result is a `TransferReport`.
+/// * `Err(String)` - If the session does not exist or a local path can't be read.
+///
+/// # Example
+/// ```rust
+/// let job_id = upload_to_remote(session_id, vec!["/home/alice/site".to_string()], "/htdocs".to_string(), None, sftp_state, remote_state, jobs_state)?;
+/// ```
+#[tauri::command]
+pub fn upload_to_remote(
+    session_id: u64,
+    local_paths: Vec<String>,
+    remote_directory: String,
+    options: Option<TransferOptions>,
+    sftp_state: State<Arc<Mutex<SftpSessionState>>>,
+    remote_state: State<Arc<Mutex<RemoteSessionState>>>,
+    jobs_state: State<Arc<Mutex<JobsState>>>,
+) -> Result<u64, String> {
+    upload_to_remote_impl(
+        session_id,
+        local_paths,
+        remote_directory,
+        options,
+        sftp_state.inner().clone(),
+        remote_state.inner().clone(),
+        jobs_state.inner().clone(),
+    )
+}
+
+pub fn upload_to_remote_impl(
+    session_id: u64,
+    local_paths: Vec<String>,
+    remote_directory: String,
+    options: Option<TransferOptions>,
+    sftp_state: Arc<Mutex<SftpSessionState>>,
+    remote_state: Arc<Mutex<RemoteSessionState>>,
+    jobs_state: Arc<Mutex<JobsState>>,
+) -> Result<u64, String> {
+    let session = remote_session(session_id, &sftp_state, &remote_state)?;
+    let plan = plan_upload_sources(&local_paths, &remote_directory)?;
+    start_transfer(
+        session,
+        TransferDirection::Upload,
+        move |_| Ok(plan),
+        options.unwrap_or_default(),
+        jobs_state,
+    )
+}
+
+/// Downloads files and directories from the server of any remote session into a local
+/// directory as a background job, like `download_from_sftp`.
+///
+/// # Arguments
+/// * `session_id` - The id of a session opened with `open_remote_session` or `open_sftp_session`.
+/// * `remote_paths` - The files and directories on the server to download.
+/// * `local_directory` - The existing local directory the paths are downloaded into.
+/// * `options` - Optional resume, verification and overwrite settings.
+/// * `sftp_state` - The open SFTP sessions.
+/// * `remote_state` - The open FTP and WebDAV sessions.
+/// * `jobs_state` - The jobs state the download is registered in.
+///
+/// # Returns
+/// * `Ok(u64)` - The id of the job. Its result is a `TransferReport`, the job fails if a remote
+///   path does not exist.
+/// * `Err(String)` - If the session or the local directory does not exist.
+///
+/// # Example
+/// ```rust
+/// let job_id = download_from_remote(session_id, vec!["/pub/releases".to_string()], "/home/alice/Downloads".to_string(), None, sftp_state, remote_state, jobs_state)?;
+/// ```
+#[tauri::command]
+pub fn download_from_remote(
+    session_id: u64,
+    remote_paths: Vec<String>,
+    local_directory: String,
+    options: Option<TransferOptions>,
+    sftp_state: State<Arc<Mutex<SftpSessionState>>>,
+    remote_state: State<Arc<Mutex<RemoteSessionState>>>,
+    jobs_state: State<Arc<Mutex<JobsState>>>,
+) -> Result<u64, String> {
+    download_from_remote_impl(
+        session_id,
+        remote_paths,
+        local_directory,
+        options,
+        sftp_state.inner().clone(),
+        remote_state.inner().clone(),
+        jobs_state.inner().clone(),
+    )
+}
+
+pub fn download_from_remote_impl(
+    session_id: u64,
+    remote_paths: Vec<String>,
+    local_directory: String,
+    options: Option<TransferOptions>,
+    sftp_state: Arc<Mutex<SftpSessionState>>,
+    remote_state: Arc<Mutex<RemoteSessionState>>,
+    jobs_state: Arc<Mutex<JobsState>>,
+) -> Result<u64, String> {
+    let session = remote_session(session_id, &sftp_state, &remote_state)?;
+    let local_directory = download_directory(local_directory)?;
+    start_transfer(
+        session,
+        TransferDirection::Download,
+        move |session| plan_download(session, &remote_paths, &local_directory),
+        options.unwrap_or_default(),
+        jobs_state,
+    )
+}
+
+#[cfg(test)]
+mod tests_remote_transfer_commands {
+    use super::*;
+    use crate::commands::remote_file_system_commands::open_remote_session_impl;
+    use crate::remote::test_servers::{
+        FtpTestServer, S3TestServer, WebDavTestServer, TEST_PASSWORD, TEST_USER,
+    };
+    use crate::remote::transfer::{system_time, TransferReport, TransferStatus};
+    use crate::remote::{RemoteProtocol, RemoteSessionConfig};
+    use crate::state::jobs_data::{wait_for_job, JobInfo, JobStatus};
+    use std::fs;
+    use std::path::Path;
+    use std::time::{Duration, SystemTime};
+    use tempfile::tempdir;
+
+    struct Setup {
+        sftp_state: Arc<Mutex<SftpSessionState>>,
+        remote_state: Arc<Mutex<RemoteSessionState>>,
+        jobs_state: Arc<Mutex<JobsState>>,
+        session_id: u64,
+    }
+
+    fn setup(protocol: RemoteProtocol, port: u16, base_path: Option<&str>) -> Setup {
+        let remote_state = Arc::new(Mutex::new(RemoteSessionState::new()));
+        let config = RemoteSessionConfig {
+            protocol,
+            host: "127.0.0.1".to_string(),
+            port: Some(port),
+            username: TEST_USER.to_string(),
+            password: TEST_PASSWORD.to_string(),
+            base_path: base_path.map(str::to_string),
+            region: None,
+            accept_invalid_certificates: true,
+        };
+        let sftp_state = Arc::new(Mutex::new(SftpSessionState::new()));
+        let session_id =
+            open_remote_session_impl(config, sftp_state.clone(), remote_state.clone()).unwrap();
+        Setup {
+            sftp_state,
+            remote_state,
+            jobs_state: Arc::new(Mutex::new(JobsState::new())),
+            session_id,
+        }
+    }
+
+    fn report(info: &JobInfo) -> TransferReport {
+        assert_eq!(info.status, JobStatus::Completed, "{:?}", info.error);
+        serde_json::from_value(info.result.clone().unwrap()).unwrap()
+    }
+
+    fn upload(
+        setup: &Setup,
+        local_paths: Vec<String>,
+        options: Option<TransferOptions>,
+    ) -> TransferReport {
+        let job_id = upload_to_remote_impl(
+            setup.session_id,
+            local_paths,
+            "/".to_string(),
+            options,
+            setup.sftp_state.clone(),
+            setup.remote_state.clone(),
+            setup.jobs_state.clone(),
+        )
+        .unwrap();
+        report(&wait_for_job(&setup.jobs_state, job_id))
+    }
+
+    fn download(
+        setup: &Setup,
+        remote_paths: Vec<String>,
+        local_directory: &Path,
+    ) -> TransferReport {
+        let job_id = download_from_remote_impl(
+            setup.session_id,
+            remote_paths,
+            local_directory.to_string_lossy().to_string(),
+            None,
+            setup.sftp_state.clone(),
+            setup.remote_state.clone(),
+            setup.jobs_state.clone(),
+        )
+        .unwrap();
+        report(&wait_for_job(&setup.jobs_state, job_id))
+    }
+
+    /// Uploads a directory, downloads it again and resumes a partial download.
+    fn check_round_trip(setup: &Setup, server_root: &Path) {
+        let local = tempdir().unwrap();
+        let site = local.path().join("site");
+        fs::create_dir_all(site.join("assets")).unwrap();
+        let page = "<html>hello</html>\n".repeat(100);
+        fs::write(site.join("index.html"), &page).unwrap();
+        fs::write(site.join("assets").join("logo.svg"), "<svg/>").unwrap();
+
+        let uploaded = upload(setup, vec![site.to_string_lossy().to_string()], None);
+        assert_eq!(uploaded.files.len(), 2);
+        assert!(uploaded
+            .files
+            .iter()
+            .all(|file| file.status == TransferStatus::Done));
+        assert_eq!(uploaded.bytes_transferred, page.len() as u64 + 6);
+        assert_eq!(
+            fs::read_to_string(server_root.join("site/index.html")).unwrap(),
+            page
+        );
+        assert!(!server_root.join("site/index.html.part").exists());
+
+        // Existing files are skipped unless they are overwritten
+        let skipped = upload(setup, vec![site.to_string_lossy().to_string()], None);
+        assert!(skipped
+            .files
+            .iter()
+            .all(|file| file.status == TransferStatus::Skipped));
+        fs::write(site.join("index.html"), "changed").unwrap();
+        let options = TransferOptions {
+            overwrite: true,
+            ..TransferOptions::default()
+        };
+        upload(
+            setup,
+            vec![site.join("index.html").to_string_lossy().to_string()],
+            Some(options),
+        );
+        assert_eq!(
+            fs::read_to_string(server_root.join("index.html")).unwrap(),
+            "changed"
+        );
+
+        let target = tempdir().unwrap();
+        let downloaded = download(setup, vec!["/site".to_string()], target.path());
+        assert_eq!(downloaded.files.len(), 2);
+        assert_eq!(
+            fs::read_to_string(target.path().join("site/index.html")).unwrap(),
+            page
+        );
+        assert_eq!(
+            fs::read_to_string(target.path().join("site/assets/logo.svg")).unwrap(),
+            "<svg/>"
+        );
+
+        // A partial file with the modification time of the source is continued
+        let resume_target = tempdir().unwrap();
+        let partial = resume_target.path().join("index.html.part");
+        fs::write(&partial, &page[..500]).unwrap();
+        let modified = fs::metadata(server_root.join("site/index.html"))
+            .unwrap()
+            .modified()
+            .unwrap()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        fs::File::options()
+            .write(true)
+            .open(&partial)
+            .unwrap()
+            .set_modified(system_time(modified))
+            .unwrap();
+        let resumed = download(
+            setup,
+            vec!["/site/index.html".to_string()],
+            resume_target.path(),
+        );
+        assert_eq!(resumed.files[0].resumed_from, 500);
+        assert_eq!(resumed.bytes_transferred, page.len() as u64 - 500);
+        assert_eq!(
+            fs::read_to_string(resume_target.path().join("index.html")).unwrap(),
+            page
+        );
+    }
+
+    #[test]
+    fn ftp_transfers_test() {
+        let server = FtpTestServer::start(false);
+        let setup = setup(RemoteProtocol::Ftp, server.port(), None);
+        check_round_trip(&setup, server.root());
+    }
+
+    #[test]
+    fn webdav_transfers_test() {
+        let server = WebDavTestServer::start(true);
+        let setup = setup(
+            RemoteProtocol::WebDavs,
+            server.port(),
+            Some(server.base_path()),
+        );
+        check_round_trip(&setup, server.root());
+    }
+
+    #[test]
+    fn s3_transfers_test() {
+        let server = S3TestServer::start(false);
+        server.put_object("artifacts", "readme.md", b"# Artifacts");
+        let setup = setup(RemoteProtocol::S3Http, server.port(), None);
+        let local = tempdir().unwrap();
+        let site = local.path().join("site");
+        fs::create_dir_all(site.join("assets")).unwrap();
+        let page = "<html>hello</html>\n".repeat(100);
+        fs::write(site.join("index.html"), &page).unwrap();
+        fs::write(site.join("assets").join("logo.svg"), "<svg/>").unwrap();
+
+        let job_id = upload_to_remote_impl(
+            setup.session_id,
+            vec![site.to_string_lossy().to_string()],
+            "/artifacts".to_string(),
+            None,
+            setup.sftp_state.clone(),
+            setup.remote_state.clone(),
+            setup.jobs_state.clone(),
+        )
+        .unwrap();
+        let uploaded = report(&wait_for_job(&setup.jobs_state, job_id));
+        assert_eq!(uploaded.files.len(), 2);
+        assert_eq!(
+            server.object("artifacts", "site/index.html").unwrap(),
+            page.as_bytes()
+        );
+        assert!(server
+            .keys("artifacts")
+            .iter()
+            .all(|key| !key.ends_with(".part")));
+
+        let target = tempdir().unwrap();
+        let downloaded = download(&setup, vec!["/artifacts/site".to_string()], target.path());
+        assert_eq!(downloaded.files.len(), 2);
+        assert_eq!(
+            fs::read_to_string(target.path().join("site/index.html")).unwrap(),
+            page
+        );
+        assert_eq!(
+            fs::read_to_string(target.path().join("site/assets/logo.svg")).unwrap(),
+            "<svg/>"
+        );
+    }
+
+    #[test]
+    fn ftp_upload_resumes_partial_file_test() {
+        let server = FtpTestServer::start(false);
+        let setup = setup(RemoteProtocol::Ftp, server.port(), None);
+        let local = tempdir().unwrap();
+        let source = local.path().join("backup.tar");
+        let content = "0123456789".repeat(1000);
+        fs::write(&source, &content).unwrap();
+        // Written on the server after the source was modified
+        std::thread::sleep(Duration::from_millis(1100));
+        fs::write(server.root().join("backup.tar.part"), &content[..4000]).unwrap();
+
+        let uploaded = upload(&setup, vec![source.to_string_lossy().to_string()], None);
+        assert_eq!(uploaded.files[0].resumed_from, 4000);
+        assert_eq!(
+            fs::read_to_string(server.root().join("backup.tar")).unwrap(),
+            content
+        );
+    }
+
+    #[test]
+    fn transfer_commands_need_an_open_session_test() {
+        let sftp_state = Arc::new(Mutex::new(SftpSessionState::new()));
+        let remote_state = Arc::new(Mutex::new(RemoteSessionState::new()));
+        let jobs_state = Arc::new(Mutex::new(JobsState::new()));
+        let upload = upload_to_remote_impl(
+            7,
+            vec![],
+            "/".to_string(),
+            None,
+            sftp_state.clone(),
+            remote_state.clone(),
+            jobs_state.clone(),
+        );
+        assert!(upload.unwrap_err().contains("ResourceNotFound"));
+        let download = download_from_remote_impl(
+            7,
+            vec!["/a.txt".to_string()],
+            ".".to_string(),
+            None,
+            sftp_state,
+            remote_state,
+            jobs_state.clone(),
+        );
+        assert!(download.unwrap_err().contains("ResourceNotFound"));
+        assert!(jobs_state.lock().unwrap().list_jobs().is_empty());
+    }
+}
@@ -782,7 +782,7 @@ pub fn get_suggestions_impl(
             for (path, _score) in search_results.into_iter().take(limit * 3) { // Get more results to filter from
                 
                 // Extract filename suggestions
-                if let Some(filename) = path.split('/').last() {
+                if let Some(filename) = path.split('/').next_back() {
                     // Only suggest if filename starts with prefix (case-insensitive)
                     if filename.to_lowercase().starts_with(&prefix.to_lowercase()) && 
                        !seen_suggestions.contains(filename) &&
@@ -145,7 +145,7 @@ pub fn update_settings_field_impl(
     settings_state
         .update_setting_field(&key, value)
         .and_then(|updated| {
-            to_string(&updated).map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+            to_string(&updated).map_err(io::Error::other)
         })
         .map_err(|e| {
             Error::new(
@@ -204,7 +204,7 @@ pub fn update_multiple_settings_impl(
     settings_state
         .update_multiple_settings(&updates)
         .and_then(|updated| {
-            to_string(&updated).map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+            to_string(&updated).map_err(io::Error::other)
         })
         .map_err(|e| {
             Error::new(
@@ -252,7 +252,7 @@ pub fn reset_settings_impl(state: Arc<Mutex<SettingsState>>) -> Result<String, S
     settings_state
         .reset_settings()
         .and_then(|updated| {
-            to_string(&updated).map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+            to_string(&updated).map_err(io::Error::other)
         })
         .map_err(|e| {
             Error::new(
@@ -266,18 +266,10 @@ pub fn reset_settings_impl(state: Arc<Mutex<SettingsState>>) -> Result<String, S
 #[cfg(test)]
 mod tests_settings_commands {
     use super::*;
+    use crate::state::jobs_data::create_test_settings_state;
     use serde_json::json;
     use std::path::PathBuf;
 
-    // Testing: Helper function to create a test SettingsState
-    fn create_test_settings_state() -> Arc<Mutex<SettingsState>> {
-        let temp_file = tempfile::NamedTempFile::new().unwrap();
-        let path = temp_file.path().to_path_buf();
-
-        // Create a settings state with a temporary file path
-        Arc::new(Mutex::new(SettingsState::new_with_path(path)))
-    }
-
     fn create_test_settings_state_with_temp_file(temp_file: PathBuf) -> Arc<Mutex<SettingsState>> {
         // Create a settings state with a temporary file path
         Arc::new(Mutex::new(SettingsState::new_with_path(temp_file)))
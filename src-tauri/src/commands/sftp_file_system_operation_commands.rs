@@ -1,500 +1,876 @@
-use std::io::{Read, Write};
-use ssh2::{Session, Sftp};
-use std::net::TcpStream;
-use std::path::Path;
+use ssh2::Sftp;
+use std::path::{Path, PathBuf};
 use std::fs;
-use crate::models::SFTPDirectory;
+use std::sync::{Arc, Mutex};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+use crate::error_handling::{Error, ErrorCode};
+use crate::log_error;
 use crate::commands::preview_commands::PreviewPayload;
-use base64::Engine;
+pub(crate) use crate::remote::filename_from_path;
+use crate::remote::transfer::download_to_path;
+use crate::remote::{build_preview, remote_entries, sftp_error, sftp_io_error, shell_quote, RemoteFileSystem};
+use crate::models::ssh_config::{SshConfig, SshConfigHost, SshJumpHost};
+use crate::state::sftp_auth_data::{HostKeyInfo, SftpAuthPrompt, TrustError};
+use crate::state::sftp_session_data::{
+    SftpConnection, SftpConnectionConfig, SftpCopyDataChannel, SftpCredentials, SftpSession,
+    SftpSessionState,
+};
 
-fn connect_to_sftp_via_password(
+fn lock_error() -> String {
+    Error::new(
+        ErrorCode::InternalError,
+        "Failed to acquire lock on SFTP session state".to_string(),
+    )
+    .to_json()
+}
+
+/// Looks up an open session, the lock on the state is released before the caller uses it.
+pub(crate) fn get_session(
+    session_id: u64,
+    sftp_state: &Arc<Mutex<SftpSessionState>>,
+) -> Result<Arc<SftpSession>, String> {
+    let state = sftp_state.lock().map_err(|_| lock_error())?;
+    state.get(session_id).ok_or_else(|| {
+        log_error!("SFTP session {} does not exist", session_id);
+        Error::new(
+            ErrorCode::ResourceNotFound,
+            format!("SFTP session {} does not exist or was closed", session_id),
+        )
+        .to_json()
+    })
+}
+
+/// A session as it is shown to the frontend, without its credentials.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SftpSessionInfo {
+    pub id: u64,
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub connected: bool,
+}
+
+/// Connects and authenticates to an SFTP server and keeps the connection open. All other SFTP
+/// commands take the returned session id, so the credentials only cross the IPC boundary once.
+/// The session sends keepalives while idle and reconnects transparently if the connection drops.
+///
+/// The host key is checked against `~/.ssh/known_hosts`. An unknown key fails with the details
+/// `UnknownHostKey`, after the user accepted its fingerprint `trust_sftp_host_key` adds it and
+/// the session can be opened again. Keyboard-interactive challenges wait for the answers given
+/// with `answer_sftp_auth_prompt` while this command is running.
+///
+/// # Arguments
+/// * `host` - The hostname or IP address of the server.
+/// * `port` - The port of the server, usually 22.
+/// * `username` - The user to log in as.
+/// * `auth` - The authentication method and its secrets.
+/// * `proxy_jump` - The jump hosts to tunnel through in the order they are connected, e.g. from
+///   `resolve_ssh_config_host`. They authenticate with the SSH agent or their identity file.
+///
+/// # Returns
+/// * `Ok(u64)` - The id of the new session.
+/// * `Err(String)` - An error if the server can't be reached, the host key is not trusted or the
+///   authentication fails.
+///
+/// # Example
+/// ```rust
+/// let auth = SftpCredentials::PublicKey { private_key_path: "/home/alice/.ssh/id_ed25519".into(), passphrase: None };
+/// let session_id = open_sftp_session("example.com".to_string(), 22, "alice".to_string(), auth, None, state).await?;
+/// let listing = load_dir(session_id, ".".to_string(), state)?;
+/// ```
+#[tauri::command]
+pub async fn open_sftp_session(
     host: String,
     port: u16,
     username: String,
-    password: String,
-) -> Result<Sftp, String> {
-    // Create the TCP connection string
-    let connection_string = format!("{}:{}", host, port);
-    // Connect to the SSH server
-    let tcp = TcpStream::connect(connection_string).map_err(|e| e.to_string())?;
-    let mut session = Session::new().map_err(|_| "Could not initialize session".to_string())?;
-    session.set_tcp_stream(tcp);
-    session.handshake().map_err(|e| e.to_string())?;
-
-    // Generates a unique SFTP destination path by appending a number if the path already exists on the remote server.
-    // For example: "file.txt" -> "file (1).txt" -> "file (2).txt"
-    // For directories: "folder" -> "folder (1)" -> "folder (2)"
-    // Authenticate
-    session.userauth_password(&username, &password).map_err(|e| e.to_string())?;
-    
-    // Check if authentication was successful
-    if !session.authenticated() {
-        return Err("Authentication failed".to_string());
-    }
-    
-    // Open an SFTP session
-    session.sftp().map_err(|e| e.to_string()).map_err(|e| e.to_string())
+    auth: SftpCredentials,
+    proxy_jump: Option<Vec<SshJumpHost>>,
+    sftp_state: State<'_, Arc<Mutex<SftpSessionState>>>,
+) -> Result<u64, String> {
+    let sftp_state = sftp_state.inner().clone();
+    let config = SftpConnectionConfig {
+        host,
+        port,
+        username,
+        credentials: auth,
+        proxy_jump: proxy_jump.unwrap_or_default(),
+    };
+    // Keyboard-interactive logins block until the user answered, so don't block the command thread
+    tokio::task::spawn_blocking(move || open_sftp_session_impl(config, sftp_state))
+        .await
+        .map_err(|e| Error::new(ErrorCode::InternalError, e.to_string()).to_json())?
+}
+
+pub fn open_sftp_session_impl(
+    config: SftpConnectionConfig,
+    sftp_state: Arc<Mutex<SftpSessionState>>,
+) -> Result<u64, String> {
+    let auth = sftp_state.lock().map_err(|_| lock_error())?.auth();
+    let connection = SftpConnection::open(&config, &auth).map_err(|e| {
+        log_error!("Failed to connect to {}:{}: {:?}", config.host, config.port, e);
+        e.to_error().to_json()
+    })?;
+
+    let mut state = sftp_state.lock().map_err(|_| lock_error())?;
+    Ok(state.insert(SftpSession::new(config, auth, connection)))
+}
+
+fn load_ssh_config(config_path: Option<PathBuf>) -> Result<SshConfig, String> {
+    let Some(config_path) = config_path else {
+        return Ok(SshConfig::default());
+    };
+    SshConfig::load(&config_path).map_err(|e| {
+        log_error!("Failed to read {}: {}", config_path.display(), e);
+        Error::new(
+            ErrorCode::InternalError,
+            format!("Failed to read {}: {}", config_path.display(), e),
+        )
+        .to_json()
+    })
+}
+
+/// Returns the hosts configured in `~/.ssh/config` as connection suggestions, every alias is
+/// resolved into its connection parameters. Wildcard patterns like `Host *` are not listed.
+///
+/// # Returns
+/// * `Ok(Vec<SshConfigHost>)` - The hosts in the order of the file, empty if there is no config.
+/// * `Err(String)` - An error if the config can't be read.
+///
+/// # Example
+/// ```rust
+/// let hosts = list_ssh_config_hosts()?;
+/// ```
+#[tauri::command]
+pub fn list_ssh_config_hosts() -> Result<Vec<SshConfigHost>, String> {
+    list_ssh_config_hosts_impl(SshConfig::default_path())
+}
+
+pub fn list_ssh_config_hosts_impl(config_path: Option<PathBuf>) -> Result<Vec<SshConfigHost>, String> {
+    Ok(load_ssh_config(config_path)?.hosts())
+}
+
+/// Resolves a host alias of `~/.ssh/config` into the full connection parameters, applying every
+/// matching `Host` block like OpenSSH. `ProxyJump` is resolved into the chain of jump hosts to
+/// pass to `open_sftp_session`. A name which is not in the config resolves to itself.
+///
+/// # Arguments
+/// * `alias` - The host alias, e.g. `db` for `Host db`.
+///
+/// # Returns
+/// * `Ok(SshConfigHost)` - The host name, port, user, identity files and jump hosts.
+/// * `Err(String)` - An error if the config can't be read.
+///
+/// # Example
+/// ```rust
+/// let host = resolve_ssh_config_host("db".to_string())?;
+/// ```
+#[tauri::command]
+pub fn resolve_ssh_config_host(alias: String) -> Result<SshConfigHost, String> {
+    resolve_ssh_config_host_impl(alias, SshConfig::default_path())
+}
+
+pub fn resolve_ssh_config_host_impl(
+    alias: String,
+    config_path: Option<PathBuf>,
+) -> Result<SshConfigHost, String> {
+    Ok(load_ssh_config(config_path)?.resolve(&alias))
 }
 
-#[allow(dead_code)]
+/// Adds the host key of a server to `~/.ssh/known_hosts` after the user accepted it, see the
+/// `UnknownHostKey` error of `open_sftp_session`.
+///
+/// # Arguments
+/// * `host` - The host as it was passed to `open_sftp_session`.
+/// * `port` - The port as it was passed to `open_sftp_session`.
+/// * `fingerprint` - The fingerprint shown to the user, only the key with this fingerprint is trusted.
+///
+/// # Returns
+/// * `Ok(HostKeyInfo)` - The key which was added.
+/// * `Err(String)` - `ResourceNotFound` if no unknown key of this host was seen, `InvalidInput`
+///   if the fingerprint differs.
+///
+/// # Example
+/// ```rust
+/// trust_sftp_host_key("example.com".to_string(), 22, "SHA256:uNiVztksCsDhcc0u9e8BujQXVUpKZIDTMczCvj3tD2s".to_string(), state)?;
+/// ```
 #[tauri::command]
-pub fn connect_to_sftp(
+pub fn trust_sftp_host_key(
     host: String,
     port: u16,
-    username: String,
-    password: String,
-) -> Result<Sftp, String> {
-    connect_to_sftp_via_password(host, port, username, password)
+    fingerprint: String,
+    sftp_state: State<Arc<Mutex<SftpSessionState>>>,
+) -> Result<HostKeyInfo, String> {
+    trust_sftp_host_key_impl(host, port, fingerprint, sftp_state.inner().clone())
 }
 
-#[tauri::command]
-pub fn load_dir(
+pub fn trust_sftp_host_key_impl(
     host: String,
     port: u16,
-    username: String,
-    password: String,
+    fingerprint: String,
+    sftp_state: Arc<Mutex<SftpSessionState>>,
+) -> Result<HostKeyInfo, String> {
+    let auth = sftp_state.lock().map_err(|_| lock_error())?.auth();
+    auth.trust_host_key(&host, port, &fingerprint).map_err(|e| {
+        let (code, message) = match e {
+            TrustError::NotPending => (
+                ErrorCode::ResourceNotFound,
+                format!("No unknown host key of {}:{} is waiting to be trusted", host, port),
+            ),
+            TrustError::FingerprintMismatch => (
+                ErrorCode::InvalidInput,
+                format!("The host key of {}:{} has a different fingerprint", host, port),
+            ),
+            TrustError::NoKnownHostsFile => (
+                ErrorCode::InternalError,
+                "The location of the known_hosts file is unknown".to_string(),
+            ),
+            TrustError::Io(message) => (ErrorCode::InternalError, message),
+        };
+        log_error!("Failed to trust host key: {}", message);
+        Error::new(code, message).to_json()
+    })
+}
+
+/// Returns the keyboard-interactive challenges waiting for the user, e.g. a one-time code. The
+/// frontend polls this while `open_sftp_session` is running or a session reconnects.
+///
+/// # Returns
+/// * `Ok(Vec<SftpAuthPrompt>)` - The pending challenges ordered by id.
+/// * `Err(String)` - An error if the state can't be locked.
+///
+/// # Example
+/// ```rust
+/// let prompts = get_sftp_auth_prompts(state)?;
+/// ```
+#[tauri::command]
+pub fn get_sftp_auth_prompts(
+    sftp_state: State<Arc<Mutex<SftpSessionState>>>,
+) -> Result<Vec<SftpAuthPrompt>, String> {
+    get_sftp_auth_prompts_impl(sftp_state.inner().clone())
+}
+
+pub fn get_sftp_auth_prompts_impl(
+    sftp_state: Arc<Mutex<SftpSessionState>>,
+) -> Result<Vec<SftpAuthPrompt>, String> {
+    let auth = sftp_state.lock().map_err(|_| lock_error())?.auth();
+    Ok(auth.pending_prompts())
+}
+
+/// Answers a keyboard-interactive challenge.
+///
+/// # Arguments
+/// * `prompt_id` - The id of the challenge.
+/// * `responses` - One answer per field of the challenge, `None` cancels the login.
+///
+/// # Returns
+/// * `Ok(())` - If the answers were handed to the login.
+/// * `Err(String)` - `ResourceNotFound` if the challenge does not exist or timed out.
+///
+/// # Example
+/// ```rust
+/// answer_sftp_auth_prompt(prompt.id, Some(vec!["123456".to_string()]), state)?;
+/// ```
+#[tauri::command]
+pub fn answer_sftp_auth_prompt(
+    prompt_id: u64,
+    responses: Option<Vec<String>>,
+    sftp_state: State<Arc<Mutex<SftpSessionState>>>,
+) -> Result<(), String> {
+    answer_sftp_auth_prompt_impl(prompt_id, responses, sftp_state.inner().clone())
+}
+
+pub fn answer_sftp_auth_prompt_impl(
+    prompt_id: u64,
+    responses: Option<Vec<String>>,
+    sftp_state: Arc<Mutex<SftpSessionState>>,
+) -> Result<(), String> {
+    let auth = sftp_state.lock().map_err(|_| lock_error())?.auth();
+    if auth.answer(prompt_id, responses) {
+        Ok(())
+    } else {
+        log_error!("Authentication prompt {} does not exist", prompt_id);
+        Err(Error::new(
+            ErrorCode::ResourceNotFound,
+            format!("Authentication prompt {} does not exist or timed out", prompt_id),
+        )
+        .to_json())
+    }
+}
+
+/// Closes an SFTP session and its connection.
+///
+/// # Arguments
+/// * `session_id` - The id returned by `open_sftp_session`.
+///
+/// # Returns
+/// * `Ok(())` - If the session was closed.
+/// * `Err(String)` - `ResourceNotFound` if there is no session with this id.
+///
+/// # Example
+/// ```rust
+/// close_sftp_session(session_id, state)?;
+/// ```
+#[tauri::command]
+pub fn close_sftp_session(
+    session_id: u64,
+    sftp_state: State<Arc<Mutex<SftpSessionState>>>,
+) -> Result<(), String> {
+    close_sftp_session_impl(session_id, sftp_state.inner().clone())
+}
+
+pub fn close_sftp_session_impl(
+    session_id: u64,
+    sftp_state: Arc<Mutex<SftpSessionState>>,
+) -> Result<(), String> {
+    let session = sftp_state
+        .lock()
+        .map_err(|_| lock_error())?
+        .remove(session_id)
+        .ok_or_else(|| {
+            log_error!("SFTP session {} does not exist", session_id);
+            Error::new(
+                ErrorCode::ResourceNotFound,
+                format!("SFTP session {} does not exist or was closed", session_id),
+            )
+            .to_json()
+        })?;
+    session.disconnect();
+    Ok(())
+}
+
+/// Returns the open SFTP sessions, e.g. to reuse one after the frontend reloaded.
+///
+/// # Returns
+/// * `Ok(Vec<SftpSessionInfo>)` - The sessions ordered by id.
+/// * `Err(String)` - An error if the state can't be locked.
+///
+/// # Example
+/// ```rust
+/// let sessions = list_sftp_sessions(state)?;
+/// ```
+#[tauri::command]
+pub fn list_sftp_sessions(
+    sftp_state: State<Arc<Mutex<SftpSessionState>>>,
+) -> Result<Vec<SftpSessionInfo>, String> {
+    list_sftp_sessions_impl(sftp_state.inner().clone())
+}
+
+pub fn list_sftp_sessions_impl(
+    sftp_state: Arc<Mutex<SftpSessionState>>,
+) -> Result<Vec<SftpSessionInfo>, String> {
+    let sessions = sftp_state.lock().map_err(|_| lock_error())?.sessions();
+    Ok(sessions
+        .into_iter()
+        .map(|(id, session)| SftpSessionInfo {
+            id,
+            host: session.config().host.clone(),
+            port: session.config().port,
+            username: session.config().username.clone(),
+            connected: session.is_connected(),
+        })
+        .collect())
+}
+
+/// Lists a remote directory with the same model `open_directory` returns for local ones, so
+/// remote panes can show and sort by size, dates and permissions. Symlinks are listed as the
+/// kind of their target with `is_symlink` and `symlink_target` set, broken links as files.
+/// Remote entries additionally have `owner_id` and `group_id`. The sub directory and file counts
+/// of directories are 0, counting them would list every sub directory.
+///
+/// # Arguments
+/// * `session_id` - The id returned by `open_sftp_session`.
+/// * `directory` - The remote directory, relative paths start at the login directory.
+///
+/// # Returns
+/// * `Ok(String)` - The `Entries` as JSON, like `open_directory`.
+/// * `Err(String)` - An error if the directory can't be listed.
+///
+/// # Example
+/// ```rust
+/// let entries: Entries = serde_json::from_str(&load_dir(session_id, "/var/www".to_string(), state)?)?;
+/// ```
+#[tauri::command]
+pub fn load_dir(
+    session_id: u64,
     directory: String,
+    sftp_state: State<Arc<Mutex<SftpSessionState>>>,
 ) -> Result<String, String> {
-    let sftp = connect_to_sftp_via_password(host, port, username, password)?;
-    
-    // Read the directory entries
-    let entries = sftp.readdir(&directory).map_err(|e| e.to_string())?;
-    
-    // Convert entries to SFTPDirectory format
-    let files: Vec<String> = entries.iter()
-        .filter_map(|(path, stat)| {
-            if stat.is_file() {
-                Some(path.to_str().unwrap_or("").to_string())
-            } else {
-                None
-            }
-        })
-        .collect();
-    
-    let directories: Vec<String> = entries.iter()
-        .filter_map(|(path, stat)| {
-            if stat.is_dir() {
-                Some(path.to_str().unwrap_or("").to_string())
-            } else {
-                None
-            }
-        })
-        .collect();
-    
-    let sftp_directory = SFTPDirectory {
-        sftp_directory: directory,
-        files,
-        directories,
-    };
-    
-    // Serialize the SFTPDirectory to JSON
-    serde_json::to_string(&sftp_directory).map_err(|e| e.to_string())
+    load_dir_impl(session_id, directory, sftp_state.inner().clone())
+}
+
+pub fn load_dir_impl(
+    session_id: u64,
+    directory: String,
+    sftp_state: Arc<Mutex<SftpSessionState>>,
+) -> Result<String, String> {
+    let listing = get_session(session_id, &sftp_state)?.list_dir(&directory)?;
+
+    serde_json::to_string(&remote_entries(listing)).map_err(|e| {
+        log_error!("Failed to serialize entries: {}", e);
+        Error::new(ErrorCode::InternalError, format!("Failed to serialize entries: {}", e)).to_json()
+    })
 }
 
 #[tauri::command]
 pub fn open_file_sftp(
-    host: String,
-    port: u16,
-    username: String,
-    password: String,
+    session_id: u64,
     file_path: String,
+    sftp_state: State<Arc<Mutex<SftpSessionState>>>,
 ) -> Result<String, String> {
-    let sftp = connect_to_sftp_via_password(host, port, username, password)?;
-    
-    // Open the file
-    let mut file = sftp.open(&file_path).map_err(|e| e.to_string())?;
-    
-    // Read the file content
-    let mut contents = String::new();
-    file.read_to_string(&mut contents).map_err(|e| e.to_string())?;
-    
-    Ok(contents)
+    open_file_sftp_impl(session_id, file_path, sftp_state.inner().clone())
+}
+
+pub fn open_file_sftp_impl(
+    session_id: u64,
+    file_path: String,
+    sftp_state: Arc<Mutex<SftpSessionState>>,
+) -> Result<String, String> {
+    let mut contents = Vec::new();
+    get_session(session_id, &sftp_state)?.read_file(&file_path, 0, None, &mut contents)?;
+
+    String::from_utf8(contents).map_err(|_| {
+        log_error!("File is not valid UTF-8: {}", file_path);
+        Error::new(ErrorCode::InvalidInput, format!("File is not valid UTF-8: {}", file_path)).to_json()
+    })
 }
 
 #[tauri::command]
 pub fn create_file_sftp(
-    host: String,
-    port: u16,
-    username: String,
-    password: String,
+    session_id: u64,
     file_path: String,
+    sftp_state: State<Arc<Mutex<SftpSessionState>>>,
+) -> Result<String, String> {
+    create_file_sftp_impl(session_id, file_path, sftp_state.inner().clone())
+}
+
+pub fn create_file_sftp_impl(
+    session_id: u64,
+    file_path: String,
+    sftp_state: Arc<Mutex<SftpSessionState>>,
 ) -> Result<String, String> {
-    let sftp = connect_to_sftp_via_password(host, port, username, password)?;
-    
     // Create the file
-    sftp.create(file_path.as_ref()).map_err(|e| e.to_string())?;
-    
+    get_session(session_id, &sftp_state)?.write_file(&file_path, 0, &mut std::io::empty(), 0)?;
+
     Ok(format!("File created at: {}", file_path))
 }
 
 #[tauri::command]
 pub fn delete_file_sftp(
-    host: String,
-    port: u16,
-    username: String,
-    password: String,
+    session_id: u64,
     file_path: String,
+    sftp_state: State<Arc<Mutex<SftpSessionState>>>,
+) -> Result<String, String> {
+    delete_file_sftp_impl(session_id, file_path, sftp_state.inner().clone())
+}
+
+pub fn delete_file_sftp_impl(
+    session_id: u64,
+    file_path: String,
+    sftp_state: Arc<Mutex<SftpSessionState>>,
 ) -> Result<String, String> {
-    let sftp = connect_to_sftp_via_password(host, port, username, password)?;
-    
     // Delete the file
-    sftp.unlink(file_path.as_ref()).map_err(|e| e.to_string())?;
-    
+    get_session(session_id, &sftp_state)?.remove_file(&file_path)?;
+
     Ok(format!("File deleted at: {}", file_path))
 }
 
 #[tauri::command]
 pub fn rename_file_sftp(
-    host: String,
-    port: u16,
-    username: String,
-    password: String,
+    session_id: u64,
     old_path: String,
     new_path: String,
+    sftp_state: State<Arc<Mutex<SftpSessionState>>>,
+) -> Result<String, String> {
+    rename_file_sftp_impl(session_id, old_path, new_path, sftp_state.inner().clone())
+}
+
+pub fn rename_file_sftp_impl(
+    session_id: u64,
+    old_path: String,
+    new_path: String,
+    sftp_state: Arc<Mutex<SftpSessionState>>,
 ) -> Result<String, String> {
-    let sftp = connect_to_sftp_via_password(host, port, username, password)?;
-    
     // Rename the file
-    sftp.rename(old_path.as_ref(), new_path.as_ref(), None).map_err(|e| e.to_string())?;
-    
+    get_session(session_id, &sftp_state)?.rename(&old_path, &new_path)?;
+
     Ok(format!("File renamed from {} to {}", old_path, new_path))
 }
 
+/// How a copy on the server was done.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum SftpCopyStrategy {
+    /// The server copied the data itself with the `copy-data` extension of SFTP.
+    CopyData,
+    /// The server copied with `cp` run over an exec channel.
+    RemoteCommand,
+    /// The data was downloaded and uploaded again through the client.
+    Streaming,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SftpCopyResult {
+    pub message: String,
+    pub strategy: SftpCopyStrategy,
+}
+
+/// Streams a file through the client, the fallback if the server can't copy by itself.
+fn copy_file(sftp: &Sftp, source_path: &Path, destination_path: &Path) -> Result<(), String> {
+    let context = format!("Failed to copy {} to {}", source_path.display(), destination_path.display());
+    let mut source_file = sftp.open(source_path).map_err(|e| sftp_error(&context, e))?;
+    let mut destination_file = sftp.create(destination_path).map_err(|e| sftp_error(&context, e))?;
+
+    std::io::copy(&mut source_file, &mut destination_file).map_err(|e| sftp_io_error(&context, e))?;
+    Ok(())
+}
+
+/// The errors of the `copy-data` channel are plain messages of the SFTP responses.
+fn copy_data_error(source_path: &str, destination_path: &str, message: String) -> String {
+    log_error!("Failed to copy {} to {}: {}", source_path, destination_path, message);
+    Error::new(
+        ErrorCode::InternalError,
+        format!("Failed to copy {} to {}: {}", source_path, destination_path, message),
+    )
+    .to_json()
+}
+
+/// Copies a file on the server with `copy-data`, `cp` or by streaming it, the first strategy
+/// the server supports is used.
+fn copy_remote_file(
+    session: &SftpSession,
+    source_path: &str,
+    destination_path: &str,
+) -> Result<SftpCopyStrategy, String> {
+    let copied = session.with_ssh(|ssh, sftp| {
+        let copy_error = |message| copy_data_error(source_path, destination_path, message);
+        let Some(mut copy_data) = SftpCopyDataChannel::open(ssh).map_err(copy_error)? else {
+            return Ok(false);
+        };
+        let permissions = sftp.stat(Path::new(source_path)).ok().and_then(|stat| stat.perm);
+        copy_data.copy_file(source_path, destination_path, permissions).map_err(copy_error)?;
+        Ok(true)
+    })?;
+    if copied {
+        return Ok(SftpCopyStrategy::CopyData);
+    }
+
+    let command = format!("cp -- {} {}", shell_quote(source_path), shell_quote(destination_path));
+    if session.exec(&command)?.is_some() {
+        return Ok(SftpCopyStrategy::RemoteCommand);
+    }
+
+    // Also reached if cp failed, streaming then reports why the file can't be copied
+    session.with_sftp(|sftp| copy_file(sftp, source_path.as_ref(), destination_path.as_ref()))?;
+    Ok(SftpCopyStrategy::Streaming)
+}
+
+/// Copies a file on the server. The server copies the data itself with the SFTP `copy-data`
+/// extension or with `cp` if it supports one of them, otherwise the file is streamed through
+/// the client.
+///
+/// # Arguments
+/// * `session_id` - The id of a session opened with `open_sftp_session`.
+/// * `source_path` - The file to copy.
+/// * `destination_path` - The path of the copy, an existing file is replaced.
+/// * `sftp_state` - The open SFTP sessions.
+///
+/// # Returns
+/// * `Ok(SftpCopyResult)` - A message and the strategy which was used.
+/// * `Err(String)` - If the session does not exist or the file can't be copied.
+///
+/// # Example
+/// ```rust
+/// let result = copy_file_sftp(session_id, "backup.tar".to_string(), "backup-copy.tar".to_string(), sftp_state)?;
+/// println!("Copied with {:?}", result.strategy);
+/// ```
 #[tauri::command]
 pub fn copy_file_sftp(
-    host: String,
-    port: u16,
-    username: String,
-    password: String,
+    session_id: u64,
     source_path: String,
     destination_path: String,
-) -> Result<String, String> {
-    let sftp = connect_to_sftp_via_password(host, port, username, password)?;
-    
-    // Copy the file
-    let mut source_file = sftp.open(&source_path).map_err(|e| e.to_string())?;
-    let mut destination_file = sftp.create(destination_path.as_ref()).map_err(|e| e.to_string())?;
-    
-    let mut buffer = Vec::new();
-    source_file.read_to_end(&mut buffer).map_err(|e| e.to_string())?;
-    destination_file.write_all(&buffer).map_err(|e| e.to_string())?;
-    
-    Ok(format!("File copied from {} to {}", source_path, destination_path))
+    sftp_state: State<Arc<Mutex<SftpSessionState>>>,
+) -> Result<SftpCopyResult, String> {
+    copy_file_sftp_impl(session_id, source_path, destination_path, sftp_state.inner().clone())
+}
+
+pub fn copy_file_sftp_impl(
+    session_id: u64,
+    source_path: String,
+    destination_path: String,
+    sftp_state: Arc<Mutex<SftpSessionState>>,
+) -> Result<SftpCopyResult, String> {
+    let session = get_session(session_id, &sftp_state)?;
+    let strategy = copy_remote_file(&session, &source_path, &destination_path)?;
+
+    Ok(SftpCopyResult {
+        message: format!("File copied from {} to {}", source_path, destination_path),
+        strategy,
+    })
 }
 
 #[tauri::command]
 pub fn move_file_sftp(
-    host: String,
-    port: u16,
-    username: String,
-    password: String,
+    session_id: u64,
     source_path: String,
     destination_path: String,
+    sftp_state: State<Arc<Mutex<SftpSessionState>>>,
+) -> Result<String, String> {
+    move_file_sftp_impl(session_id, source_path, destination_path, sftp_state.inner().clone())
+}
+
+pub fn move_file_sftp_impl(
+    session_id: u64,
+    source_path: String,
+    destination_path: String,
+    sftp_state: Arc<Mutex<SftpSessionState>>,
 ) -> Result<String, String> {
-    let sftp = connect_to_sftp_via_password(host, port, username, password)?;
-    
     // Move the file
-    sftp.rename(source_path.as_ref(), destination_path.as_ref(), None).map_err(|e| e.to_string())?;
-    
+    get_session(session_id, &sftp_state)?.rename(&source_path, &destination_path)?;
+
     Ok(format!("File moved from {} to {}", source_path, destination_path))
 }
 
 #[tauri::command]
 pub fn create_directory_sftp(
-    host: String,
-    port: u16,
-    username: String,
-    password: String,
+    session_id: u64,
     directory_path: String,
+    sftp_state: State<Arc<Mutex<SftpSessionState>>>,
+) -> Result<String, String> {
+    create_directory_sftp_impl(session_id, directory_path, sftp_state.inner().clone())
+}
+
+pub fn create_directory_sftp_impl(
+    session_id: u64,
+    directory_path: String,
+    sftp_state: Arc<Mutex<SftpSessionState>>,
 ) -> Result<String, String> {
-    let sftp = connect_to_sftp_via_password(host, port, username, password)?;
-    
     // Create the directory
-    sftp.mkdir(directory_path.as_ref(), 0o755).map_err(|e| e.to_string())?;
-    
+    get_session(session_id, &sftp_state)?.create_dir(&directory_path)?;
+
     Ok(format!("Directory created at: {}", directory_path))
 }
 
 #[tauri::command]
 pub fn delete_directory_sftp(
-    host: String,
-    port: u16,
-    username: String,
-    password: String,
+    session_id: u64,
     directory_path: String,
+    sftp_state: State<Arc<Mutex<SftpSessionState>>>,
+) -> Result<String, String> {
+    delete_directory_sftp_impl(session_id, directory_path, sftp_state.inner().clone())
+}
+
+pub fn delete_directory_sftp_impl(
+    session_id: u64,
+    directory_path: String,
+    sftp_state: Arc<Mutex<SftpSessionState>>,
 ) -> Result<String, String> {
-    let sftp = connect_to_sftp_via_password(host, port, username, password)?;
-    
     // Delete the directory
-    sftp.rmdir(directory_path.as_ref()).map_err(|e| e.to_string())?;
-    
+    get_session(session_id, &sftp_state)?.remove_dir(&directory_path)?;
+
     Ok(format!("Directory deleted at: {}", directory_path))
 }
 
 #[tauri::command]
 pub fn rename_directory_sftp(
-    host: String,
-    port: u16,
-    username: String,
-    password: String,
+    session_id: u64,
     old_path: String,
     new_path: String,
+    sftp_state: State<Arc<Mutex<SftpSessionState>>>,
+) -> Result<String, String> {
+    rename_directory_sftp_impl(session_id, old_path, new_path, sftp_state.inner().clone())
+}
+
+pub fn rename_directory_sftp_impl(
+    session_id: u64,
+    old_path: String,
+    new_path: String,
+    sftp_state: Arc<Mutex<SftpSessionState>>,
 ) -> Result<String, String> {
-    let sftp = connect_to_sftp_via_password(host, port, username, password)?;
-    
     // Rename the directory
-    sftp.rename(old_path.as_ref(), new_path.as_ref(), None).map_err(|e| e.to_string())?;
-    
+    get_session(session_id, &sftp_state)?.rename(&old_path, &new_path)?;
+
     Ok(format!("Directory renamed from {} to {}", old_path, new_path))
 }
 
-#[tauri::command]
-pub fn copy_directory_sftp(
-    host: String,
-    port: u16,
-    username: String,
-    password: String,
-    source_path: String,
-    destination_path: String,
-) -> Result<String, String> {
-    let sftp = connect_to_sftp_via_password(host.clone(), port, username.clone(), password.clone())?;
-    
+/// Copies a directory file by file, with `copy-data` if the channel is given.
+fn copy_directory(
+    sftp: &Sftp,
+    copy_data: &mut Option<SftpCopyDataChannel>,
+    source_path: &str,
+    destination_path: &str,
+) -> Result<(), String> {
     // Create the destination directory
-    sftp.mkdir(destination_path.as_ref(), 0o755).map_err(|e| e.to_string())?;
-    
+    sftp.mkdir(destination_path.as_ref(), 0o755)
+        .map_err(|e| sftp_error(&format!("Failed to create {}", destination_path), e))?;
+
     // Read the source directory entries
-    let entries = sftp.readdir(&source_path).map_err(|e| e.to_string())?;
-    
+    let entries = sftp.readdir(Path::new(source_path))
+        .map_err(|e| sftp_error(&format!("Failed to list {}", source_path), e))?;
+
     for (path, stat) in entries {
         let file_name = path.file_name()
             .and_then(|name| name.to_str())
             .unwrap_or("[invalid_filename]");
         let new_path = format!("{}/{}", destination_path, file_name);
-        
+
         if stat.is_file() {
             // Copy file
-            let mut source_file = sftp.open(&path).map_err(|e| e.to_string())?;
-            let mut destination_file = sftp.create(new_path.as_ref()).map_err(|e| e.to_string())?;
-            
-            let mut buffer = Vec::new();
-            source_file.read_to_end(&mut buffer).map_err(|e| e.to_string())?;
-            destination_file.write_all(&buffer).map_err(|e| e.to_string())?;
+            let path_str = path.to_str().unwrap_or("[invalid_path]");
+            match copy_data {
+                Some(channel) => channel
+                    .copy_file(path_str, &new_path, stat.perm)
+                    .map_err(|message| copy_data_error(path_str, &new_path, message))?,
+                None => copy_file(sftp, &path, new_path.as_ref())?,
+            }
         } else if stat.is_dir() {
             // Recursively copy directory
             let path_str = path.to_str().unwrap_or("[invalid_path]").to_string();
-            copy_directory_sftp(host.clone(), port, username.clone(), password.clone(), path_str, new_path)?;
+            copy_directory(sftp, copy_data, &path_str, &new_path)?;
         }
     }
-    
-    Ok(format!("Directory copied from {} to {}", source_path, destination_path))
+    Ok(())
 }
 
+/// Copies a directory with its content on the server. The server copies the whole tree with
+/// `cp -R` if it allows running commands, otherwise the files are copied one by one with the
+/// SFTP `copy-data` extension or streamed through the client.
+///
+/// # Arguments
+/// * `session_id` - The id of a session opened with `open_sftp_session`.
+/// * `source_path` - The directory to copy.
+/// * `destination_path` - The path of the copy, it must not exist yet.
+/// * `sftp_state` - The open SFTP sessions.
+///
+/// # Returns
+/// * `Ok(SftpCopyResult)` - A message and the strategy which was used.
+/// * `Err(String)` - If the session does not exist, the destination exists or the directory
+///   can't be copied.
+///
+/// # Example
+/// ```rust
+/// let result = copy_directory_sftp(session_id, "site".to_string(), "site-backup".to_string(), sftp_state)?;
+/// ```
 #[tauri::command]
-pub fn move_directory_sftp(
-    host: String,
-    port: u16,
-    username: String,
-    password: String,
+pub fn copy_directory_sftp(
+    session_id: u64,
     source_path: String,
     destination_path: String,
-) -> Result<String, String> {
-    let sftp = connect_to_sftp_via_password(host, port, username, password)?;
-    
-    // Move the directory
-    sftp.rename(source_path.as_ref(), destination_path.as_ref(), None).map_err(|e| e.to_string())?;
-    
-    Ok(format!("Directory moved from {} to {}", source_path, destination_path))
+    sftp_state: State<Arc<Mutex<SftpSessionState>>>,
+) -> Result<SftpCopyResult, String> {
+    copy_directory_sftp_impl(session_id, source_path, destination_path, sftp_state.inner().clone())
 }
 
-fn filename_from_path(path: &str) -> String {
-    if let Some(name) = path.split('/').last() {
-        if !name.is_empty() {
-            return name.to_string();
+pub fn copy_directory_sftp_impl(
+    session_id: u64,
+    source_path: String,
+    destination_path: String,
+    sftp_state: Arc<Mutex<SftpSessionState>>,
+) -> Result<SftpCopyResult, String> {
+    let session = get_session(session_id, &sftp_state)?;
+    let destination_exists = |session: &SftpSession| {
+        session.with_sftp(|sftp| Ok(sftp.stat(Path::new(&destination_path)).is_ok()))
+    };
+
+    // cp would copy into an existing destination instead of failing like mkdir does
+    if !destination_exists(&session)? {
+        let command = format!(
+            "cp -R -- {} {}",
+            shell_quote(&source_path),
+            shell_quote(&destination_path)
+        );
+        if session.exec(&command)?.is_some() {
+            return Ok(SftpCopyResult {
+                message: format!("Directory copied from {} to {}", source_path, destination_path),
+                strategy: SftpCopyStrategy::RemoteCommand,
+            });
+        }
+        if destination_exists(&session)? {
+            let message = format!(
+                "cp failed while copying {} to {}, the copy may be incomplete",
+                source_path, destination_path
+            );
+            log_error!("{}", message);
+            return Err(Error::new(ErrorCode::InternalError, message).to_json());
         }
     }
-    "file".to_string()
+
+    let strategy = session.with_ssh(|ssh, sftp| {
+        let mut copy_data = SftpCopyDataChannel::open(ssh)
+            .map_err(|message| copy_data_error(&source_path, &destination_path, message))?;
+        copy_directory(sftp, &mut copy_data, &source_path, &destination_path)?;
+        Ok(match copy_data {
+            Some(_) => SftpCopyStrategy::CopyData,
+            None => SftpCopyStrategy::Streaming,
+        })
+    })?;
+
+    Ok(SftpCopyResult {
+        message: format!("Directory copied from {} to {}", source_path, destination_path),
+        strategy,
+    })
 }
 
-fn detect_mime_sftp(path: &str, head: &[u8]) -> Option<&'static str> {
-    if let Some(kind) = infer::get(head) {
-        return Some(kind.mime_type());
-    }
-    
-    if let Some(ext) = path.split('.').last().map(|s| s.to_lowercase()) {
-        return Some(match ext.as_str() {
-            "md" | "rs" | "ts" | "tsx" | "js" | "jsx" | "json" | "txt" | "log" | "toml" | "yaml" | "yml" | "xml" | "ini" | "csv" => "text/plain",
-            "pdf" => "application/pdf",
-            "png" => "image/png",
-            "jpg" | "jpeg" => "image/jpeg",
-            "gif" => "image/gif",
-            "webp" => "image/webp",
-            "mp4" => "video/mp4",
-            "mov" => "video/quicktime",
-            "mp3" => "audio/mpeg",
-            "wav" => "audio/wav",
-            _ => "application/octet-stream",
-        });
-    }
-    None
+#[tauri::command]
+pub fn move_directory_sftp(
+    session_id: u64,
+    source_path: String,
+    destination_path: String,
+    sftp_state: State<Arc<Mutex<SftpSessionState>>>,
+) -> Result<String, String> {
+    move_directory_sftp_impl(session_id, source_path, destination_path, sftp_state.inner().clone())
 }
 
-fn read_sftp_prefix(sftp: &Sftp, path: &str, max_bytes: usize) -> Result<Vec<u8>, String> {
-    let mut file = sftp.open(Path::new(path)).map_err(|e| e.to_string())?;
-    let mut buf = Vec::with_capacity(max_bytes.min(1024 * 1024));
-    let mut temp_buf = vec![0u8; max_bytes.min(8192)];
-    let mut total_read = 0;
-    
-    while total_read < max_bytes {
-        let chunk_size = std::cmp::min(temp_buf.len(), max_bytes - total_read);
-        match file.read(&mut temp_buf[..chunk_size]) {
-            Ok(0) => break, // EOF
-            Ok(n) => {
-                buf.extend_from_slice(&temp_buf[..n]);
-                total_read += n;
-            }
-            Err(e) => return Err(e.to_string()),
-        }
-    }
-    Ok(buf)
+pub fn move_directory_sftp_impl(
+    session_id: u64,
+    source_path: String,
+    destination_path: String,
+    sftp_state: Arc<Mutex<SftpSessionState>>,
+) -> Result<String, String> {
+    // Move the directory
+    get_session(session_id, &sftp_state)?.rename(&source_path, &destination_path)?;
+
+    Ok(format!("Directory moved from {} to {}", source_path, destination_path))
 }
 
 #[tauri::command]
 pub fn build_preview_sftp(
-    host: String,
-    port: u16,
-    username: String,
-    password: String,
+    session_id: u64,
     file_path: String,
+    sftp_state: State<Arc<Mutex<SftpSessionState>>>,
 ) -> Result<PreviewPayload, String> {
-    let sftp = connect_to_sftp_via_password(host, port, username, password)?;
-    let name = filename_from_path(&file_path);
-    
-    // Get file stats to check if it's a directory or file
-    let stat = sftp.stat(Path::new(&file_path)).map_err(|e| e.to_string())?;
-    
-    // Handle directories
-    if stat.is_dir() {
-        // Count items (files + dirs, not recursive)
-        let mut item_count = 0;
-        let mut size: u64 = 0;
-        let mut latest_modified: Option<u64> = None;
-        
-        if let Ok(entries) = sftp.readdir(Path::new(&file_path)) {
-            for (_, entry_stat) in entries {
-                item_count += 1;
-                if let Some(entry_size) = entry_stat.size {
-                    size += entry_size;
-                }
-                if let Some(mtime) = entry_stat.mtime {
-                    let mtime_u64 = mtime as u64;
-                    latest_modified = match latest_modified {
-                        Some(current) if current > mtime_u64 => Some(current),
-                        _ => Some(mtime_u64),
-                    };
-                }
-            }
-        }
-        
-        // Use folder's own modified time if no children
-        let folder_modified = stat.mtime;
-        let modified_time = latest_modified.or(folder_modified);
-        let modified_str = modified_time.map(|t| {
-            chrono::DateTime::from_timestamp(t as i64, 0)
-                .map(|dt| dt.to_rfc3339())
-                .unwrap_or_else(|| "unknown".to_string())
-        });
-        
-        return Ok(PreviewPayload::Folder {
-            name,
-            size,
-            item_count,
-            modified: modified_str,
-        });
-    }
-    
-    // Files
-    let bytes = stat.size.unwrap_or(0) as usize;
-    // Read a small head for detection + maybe text
-    let head = read_sftp_prefix(&sftp, &file_path, 256 * 1024).map_err(|e| e.to_string())?;
-    let mime = detect_mime_sftp(&file_path, &head).unwrap_or("application/octet-stream");
-    
-    // Branch by mime top-level type - exactly like original
-    if mime.starts_with("image/") {
-        // Encode entire file only if small; else just the head (fast path)
-        let cap = 6 * 1024 * 1024;
-        let data = if bytes <= cap {
-            let mut full_file = sftp.open(Path::new(&file_path)).map_err(|e| e.to_string())?;
-            let mut full_data = Vec::new();
-            full_file.read_to_end(&mut full_data).map_err(|e| e.to_string())?;
-            full_data
-        } else {
-            head.clone()
-        };
-        let data_uri = format!("data:{};base64,{}", mime, base64::engine::general_purpose::STANDARD.encode(data));
-        return Ok(PreviewPayload::Image { name, data_uri, bytes });
-    }
-    
-    if mime == "application/pdf" {
-        // Encode entire file only if small; else just the head (fast path)
-        let cap = 12 * 1024 * 1024; // Allow larger PDFs than images
-        let data = if bytes <= cap {
-            let mut full_file = sftp.open(Path::new(&file_path)).map_err(|e| e.to_string())?;
-            let mut full_data = Vec::new();
-            full_file.read_to_end(&mut full_data).map_err(|e| e.to_string())?;
-            full_data
-        } else {
-            head.clone()
-        };
-        let data_uri = format!("data:{};base64,{}", mime, base64::engine::general_purpose::STANDARD.encode(data));
-        return Ok(PreviewPayload::Pdf { name, data_uri, bytes });
-    }
-
-    if mime.starts_with("video/") {
-        // For SFTP videos, treat as unknown since we can't stream remote files
-        return Ok(PreviewPayload::Unknown { name });
-    }
-
-    if mime.starts_with("audio/") {
-        // For SFTP audio, treat as unknown since we can't stream remote files
-        return Ok(PreviewPayload::Unknown { name });
-    }
-
-    // Heuristic: treat smallish or text‑ish files as text
-    let looks_texty = mime.starts_with("text/") || head.iter().all(|&b| b == 9 || b == 10 || b == 13 || (b >= 32 && b < 0xF5));
-    if looks_texty || bytes <= 2 * 1024 * 1024 {
-        let mut det = chardetng::EncodingDetector::new();
-        det.feed(&head, true);
-        let enc = det.guess(None, true);
-        let (cow, _, _) = enc.decode(&head);
-        let mut text = cow.to_string();
-        let mut truncated = false;
-        if text.len() > 200_000 {
-            text.truncate(200_000);
-            text.push_str("\n…(truncated)");
-            truncated = true;
-        }
-        return Ok(PreviewPayload::Text { name, text, truncated });
-    }
+    build_preview_sftp_impl(session_id, file_path, sftp_state.inner().clone())
+}
 
-    Ok(PreviewPayload::Unknown { name })
+pub fn build_preview_sftp_impl(
+    session_id: u64,
+    file_path: String,
+    sftp_state: Arc<Mutex<SftpSessionState>>,
+) -> Result<PreviewPayload, String> {
+    let session = get_session(session_id, &sftp_state)?;
+    build_preview(session.as_ref(), &file_path)
 }
 
 #[tauri::command]
 pub fn download_and_open_sftp_file(
-    host: String,
-    port: u16,
-    username: String,
-    password: String,
+    session_id: u64,
     file_path: String,
     open_file: Option<bool>,
+    sftp_state: State<Arc<Mutex<SftpSessionState>>>,
+) -> Result<String, String> {
+    download_and_open_sftp_file_impl(session_id, file_path, open_file, sftp_state.inner().clone())
+}
+
+pub fn download_and_open_sftp_file_impl(
+    session_id: u64,
+    file_path: String,
+    open_file: Option<bool>,
+    sftp_state: Arc<Mutex<SftpSessionState>>,
 ) -> Result<String, String> {
-    let sftp = connect_to_sftp_via_password(host, port, username, password)?;
-    
     // Get the filename from the path
     let filename = filename_from_path(&file_path);
     
     // Create a temporary directory if it doesn't exist
-    let temp_dir = std::env::temp_dir().join("file_explorer_sftp");
+    let temp_dir = session_temp_dir(session_id);
     if !temp_dir.exists() {
         fs::create_dir_all(&temp_dir).map_err(|e| format!("Failed to create temp directory: {}", e))?;
     }
@@ -502,12 +878,9 @@ pub fn download_and_open_sftp_file(
     // Create a unique temporary file path
     let temp_file_path = temp_dir.join(&filename);
     
-    // Download the file from SFTP
-    let mut remote_file = sftp.open(Path::new(&file_path)).map_err(|e| e.to_string())?;
-    let mut local_file = fs::File::create(&temp_file_path).map_err(|e| e.to_string())?;
-    
-    // Copy the file content
-    std::io::copy(&mut remote_file, &mut local_file).map_err(|e| e.to_string())?;
+    // Download the file from SFTP, a partial download of an earlier attempt is resumed
+    let session = get_session(session_id, &sftp_state)?;
+    download_to_path(session.as_ref(), &file_path, &temp_file_path)?;
     
     // Only open the file if explicitly requested (default is true for backward compatibility)
     let should_open = open_file.unwrap_or(true);
@@ -545,45 +918,90 @@ pub fn download_and_open_sftp_file(
     }
 }
 
+/// The directory SFTP files are downloaded to for opening them locally.
+pub(crate) fn sftp_temp_dir() -> PathBuf {
+    std::env::temp_dir().join("file_explorer_sftp")
+}
+
+/// The temporary files of a session are kept in their own directory, so they can be removed
+/// once the session is closed.
+pub(crate) fn session_temp_dir(session_id: u64) -> PathBuf {
+    sftp_temp_dir().join(format!("session-{}", session_id))
+}
+
+/// Removes the temporary files of closed sessions, including the local copies of files edited
+/// with `edit_sftp_file`. Files of open sessions are kept, they may still be open in an editor.
+///
+/// # Returns
+/// * `Ok(String)` - A message with the number of removed files and directories.
+/// * `Err(String)` - If the temporary directory can't be read.
+///
+/// # Example
+/// ```rust
+/// let message = cleanup_sftp_temp_files(sftp_state)?;
+/// ```
 #[tauri::command]
-pub fn cleanup_sftp_temp_files() -> Result<String, String> {
-    let temp_dir = std::env::temp_dir().join("file_explorer_sftp");
-    
+pub fn cleanup_sftp_temp_files(
+    sftp_state: State<Arc<Mutex<SftpSessionState>>>,
+) -> Result<String, String> {
+    cleanup_sftp_temp_files_impl(sftp_state.inner().clone(), &sftp_temp_dir())
+}
+
+pub fn cleanup_sftp_temp_files_impl(
+    sftp_state: Arc<Mutex<SftpSessionState>>,
+    temp_dir: &Path,
+) -> Result<String, String> {
     if !temp_dir.exists() {
         return Ok("No temporary directory to clean".to_string());
     }
-    
+
+    let open_sessions: Vec<String> = {
+        let state = sftp_state.lock().map_err(|_| lock_error())?;
+        state
+            .sessions()
+            .into_iter()
+            .map(|(id, _)| format!("session-{}", id))
+            .collect()
+    };
+
+    let entries = fs::read_dir(temp_dir).map_err(|e| {
+        log_error!("Failed to read temp directory: {}", e);
+        Error::new(
+            ErrorCode::InternalError,
+            format!("Failed to read temp directory: {}", e),
+        )
+        .to_json()
+    })?;
+
     let mut cleaned_count = 0;
-    
-    match fs::read_dir(&temp_dir) {
-        Ok(entries) => {
-            for entry in entries {
-                if let Ok(entry) = entry {
-                    if let Ok(metadata) = entry.metadata() {
-                        if let Ok(modified) = metadata.modified() {
-                            // Delete files older than 24 hours
-                            if let Ok(elapsed) = modified.elapsed() {
-                                if elapsed.as_secs() > 24 * 60 * 60 {
-                                    if fs::remove_file(entry.path()).is_ok() {
-                                        cleaned_count += 1;
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if open_sessions.contains(&name) {
+            continue;
+        }
+        // Files directly in the temporary directory were written by older versions
+        let removed = if entry.path().is_dir() {
+            fs::remove_dir_all(entry.path())
+        } else {
+            fs::remove_file(entry.path())
+        };
+        if removed.is_ok() {
+            cleaned_count += 1;
         }
-        Err(e) => return Err(format!("Failed to read temp directory: {}", e)),
     }
-    
-    Ok(format!("Cleaned {} old temporary files", cleaned_count))
+
+    Ok(format!(
+        "Cleaned {} temporary files of closed sessions",
+        cleaned_count
+    ))
 }
 
 #[cfg(test)]
 #[cfg(feature = "sftp-tests")]
 mod sftp_file_system_operation_commands_tests {
     use super::*;
+    use crate::models::Entries;
+    use crate::state::sftp_auth_data::SftpAuthContext;
 
     // Test data
     const TEST_HOST: &str = "localhost";
@@ -593,6 +1011,43 @@ mod sftp_file_system_operation_commands_tests {
     const TEST_WRONG_PASSWORD: &str = "wrong_password";
     const TEST_WRONG_HOST: &str = "nonexistent.host";
 
+    fn test_config(password: &str) -> SftpConnectionConfig {
+        SftpConnectionConfig {
+            host: TEST_HOST.to_string(),
+            port: TEST_PORT,
+            username: TEST_USERNAME.to_string(),
+            credentials: SftpCredentials::Password {
+                password: password.to_string(),
+            },
+            proxy_jump: Vec::new(),
+        }
+    }
+
+    /// A state with its own known_hosts file, so the tests don't touch the one of the user.
+    fn test_state() -> Arc<Mutex<SftpSessionState>> {
+        let known_hosts = std::env::temp_dir().join("file_explorer_sftp_tests_known_hosts");
+        Arc::new(Mutex::new(SftpSessionState::with_auth(SftpAuthContext::new(Some(known_hosts)))))
+    }
+
+    /// Opens a session with the test credentials in a new state, the host key of the test
+    /// server is trusted on first use.
+    fn open_test_session() -> (Arc<Mutex<SftpSessionState>>, u64) {
+        let sftp_state = test_state();
+        let session_id = match open_sftp_session_impl(test_config(TEST_PASSWORD), sftp_state.clone()) {
+            Ok(session_id) => session_id,
+            Err(error) => {
+                let error: serde_json::Value = serde_json::from_str(&error).unwrap();
+                assert_eq!(error["details"]["reason"], "UnknownHostKey", "{}", error);
+                let fingerprint = error["details"]["host_key"]["fingerprint"].as_str().unwrap();
+                trust_sftp_host_key_impl(TEST_HOST.to_string(), TEST_PORT, fingerprint.to_string(), sftp_state.clone())
+                    .expect("Should trust the host key of the test server");
+                open_sftp_session_impl(test_config(TEST_PASSWORD), sftp_state.clone())
+                    .expect("Should successfully connect to SFTP server")
+            }
+        };
+        (sftp_state, session_id)
+    }
+
     // Helper function to create test file content
     #[allow(dead_code)]
     fn get_test_file_content() -> &'static str {
@@ -600,73 +1055,55 @@ mod sftp_file_system_operation_commands_tests {
     }
 
     #[test]
-    fn test_connect_to_sftp_via_password_success() {
-        let result = connect_to_sftp_via_password(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
-        );
-        
-        assert!(result.is_ok(), "Should successfully connect to SFTP server");
+    fn test_open_sftp_session_success() {
+        let (sftp_state, session_id) = open_test_session();
+
+        let sessions = list_sftp_sessions_impl(sftp_state.clone()).unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].id, session_id);
+        assert!(sessions[0].connected);
     }
 
     #[test]
-    fn test_connect_to_sftp_via_password_failure_wrong_password() {
-        let result = connect_to_sftp_via_password(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_WRONG_PASSWORD.to_string(),
-        );
-        
-        assert!(result.is_err(), "Should fail with wrong password");
+    fn test_open_sftp_session_failure_wrong_password() {
+        let (sftp_state, _) = open_test_session();
+        let result = open_sftp_session_impl(test_config(TEST_WRONG_PASSWORD), sftp_state.clone());
+
+        let error: serde_json::Value = serde_json::from_str(&result.expect_err("Should fail with wrong password")).unwrap();
+        assert_eq!(error["details"]["reason"], "AuthenticationFailed");
+        assert_eq!(list_sftp_sessions_impl(sftp_state).unwrap().len(), 1);
     }
 
     #[test]
-    fn test_connect_to_sftp_via_password_failure_wrong_host() {
-        let result = connect_to_sftp_via_password(
-            TEST_WRONG_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
-        );
-        
+    fn test_open_sftp_session_failure_wrong_host() {
+        let mut config = test_config(TEST_PASSWORD);
+        config.host = TEST_WRONG_HOST.to_string();
+        let result = open_sftp_session_impl(config, test_state());
+
         assert!(result.is_err(), "Should fail with wrong host");
     }
 
     #[test]
-    fn test_connect_to_sftp_success() {
-        let result = connect_to_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
-        );
-        
-        assert!(result.is_ok(), "Should successfully connect to SFTP server");
-    }
+    fn test_session_is_reused_and_closed() {
+        let (sftp_state, session_id) = open_test_session();
 
-    #[test]
-    fn test_connect_to_sftp_failure() {
-        let result = connect_to_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_WRONG_PASSWORD.to_string(),
-        );
-        
-        assert!(result.is_err(), "Should fail with wrong credentials");
+        for _ in 0..5 {
+            assert!(load_dir_impl(session_id, ".".to_string(), sftp_state.clone()).is_ok());
+        }
+        assert!(close_sftp_session_impl(session_id, sftp_state.clone()).is_ok());
+
+        let result = load_dir_impl(session_id, ".".to_string(), sftp_state.clone());
+        assert!(result.is_err(), "Should fail with a closed session");
+        assert!(close_sftp_session_impl(session_id, sftp_state).is_err());
     }
 
     #[test]
     fn test_load_dir_success() {
-        let result = load_dir(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let (sftp_state, session_id) = open_test_session();
+        let result = load_dir_impl(
+            session_id,
             ".".to_string(),
+            sftp_state.clone(),
         );
         
         match result {
@@ -674,8 +1111,10 @@ mod sftp_file_system_operation_commands_tests {
                 println!("SFTP Directory JSON: {}", json);
                 assert!(!json.is_empty(), "JSON should not be empty");
                 // Try to parse the JSON to ensure it's valid
-                let parsed: Result<SFTPDirectory, _> = serde_json::from_str(&json);
+                let parsed: Result<Entries, _> = serde_json::from_str(&json);
                 assert!(parsed.is_ok(), "Should be valid JSON");
+                let entries = parsed.unwrap();
+                assert!(entries.files.iter().all(|file| file.owner_id.is_some()));
             },
             Err(e) => {
                 panic!("Should successfully load directory: {}", e);
@@ -684,26 +1123,23 @@ mod sftp_file_system_operation_commands_tests {
     }
 
     #[test]
-    fn test_load_dir_failure_wrong_credentials() {
-        let result = load_dir(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_WRONG_PASSWORD.to_string(),
+    fn test_load_dir_failure_unknown_session() {
+        let result = load_dir_impl(
+            u64::MAX,
             ".".to_string(),
+            Arc::new(Mutex::new(SftpSessionState::new())),
         );
         
-        assert!(result.is_err(), "Should fail with wrong credentials");
+        assert!(result.is_err(), "Should fail with an unknown session");
     }
 
     #[test]
     fn test_load_dir_failure_nonexistent_directory() {
-        let result = load_dir(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let (sftp_state, session_id) = open_test_session();
+        let result = load_dir_impl(
+            session_id,
             "/nonexistent/directory".to_string(),
+            sftp_state.clone(),
         );
         
         assert!(result.is_err(), "Should fail with nonexistent directory");
@@ -711,62 +1147,54 @@ mod sftp_file_system_operation_commands_tests {
 
     #[test]
     fn test_create_file_sftp_success() {
+        let (sftp_state, session_id) = open_test_session();
         let test_file = "test_create_file.txt";
         
-        let result = create_file_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let result = create_file_sftp_impl(
+            session_id,
             test_file.to_string(),
+            sftp_state.clone(),
         );
         
         assert!(result.is_ok(), "Should successfully create file");
         
         // Clean up - delete the test file
-        let _ = delete_file_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let _ = delete_file_sftp_impl(
+            session_id,
             test_file.to_string(),
+            sftp_state.clone(),
         );
     }
 
     #[test]
-    fn test_create_file_sftp_failure() {
-        let result = create_file_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_WRONG_PASSWORD.to_string(),
+    fn test_create_file_sftp_failure_unknown_session() {
+        let result = create_file_sftp_impl(
+            u64::MAX,
             "test_file.txt".to_string(),
+            Arc::new(Mutex::new(SftpSessionState::new())),
         );
         
-        assert!(result.is_err(), "Should fail with wrong credentials");
+        assert!(result.is_err(), "Should fail with an unknown session");
     }
 
     #[test]
     fn test_delete_file_sftp_success() {
+        let (sftp_state, session_id) = open_test_session();
         let test_file = "test_delete_file.txt";
         
         // First create a file
-        let create_result = create_file_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let create_result = create_file_sftp_impl(
+            session_id,
             test_file.to_string(),
+            sftp_state.clone(),
         );
         assert!(create_result.is_ok(), "Should create test file first");
         
         // Then delete it
-        let result = delete_file_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let result = delete_file_sftp_impl(
+            session_id,
             test_file.to_string(),
+            sftp_state.clone(),
         );
         
         assert!(result.is_ok(), "Should successfully delete file");
@@ -774,12 +1202,11 @@ mod sftp_file_system_operation_commands_tests {
 
     #[test]
     fn test_delete_file_sftp_failure_nonexistent_file() {
-        let result = delete_file_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let (sftp_state, session_id) = open_test_session();
+        let result = delete_file_sftp_impl(
+            session_id,
             "nonexistent_file.txt".to_string(),
+            sftp_state.clone(),
         );
         
         assert!(result.is_err(), "Should fail with nonexistent file");
@@ -787,50 +1214,44 @@ mod sftp_file_system_operation_commands_tests {
 
     #[test]
     fn test_rename_file_sftp_success() {
+        let (sftp_state, session_id) = open_test_session();
         let original_file = "test_rename_original.txt";
         let renamed_file = "test_rename_new.txt";
         
         // First create a file
-        let create_result = create_file_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let create_result = create_file_sftp_impl(
+            session_id,
             original_file.to_string(),
+            sftp_state.clone(),
         );
         assert!(create_result.is_ok(), "Should create test file first");
         
         // Then rename it
-        let result = rename_file_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let result = rename_file_sftp_impl(
+            session_id,
             original_file.to_string(),
             renamed_file.to_string(),
+            sftp_state.clone(),
         );
         
         assert!(result.is_ok(), "Should successfully rename file");
         
         // Clean up
-        let _ = delete_file_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let _ = delete_file_sftp_impl(
+            session_id,
             renamed_file.to_string(),
+            sftp_state.clone(),
         );
     }
 
     #[test]
     fn test_rename_file_sftp_failure() {
-        let result = rename_file_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let (sftp_state, session_id) = open_test_session();
+        let result = rename_file_sftp_impl(
+            session_id,
             "nonexistent_file.txt".to_string(),
             "new_name.txt".to_string(),
+            sftp_state.clone(),
         );
         
         assert!(result.is_err(), "Should fail with nonexistent file");
@@ -838,57 +1259,49 @@ mod sftp_file_system_operation_commands_tests {
 
     #[test]
     fn test_copy_file_sftp_success() {
+        let (sftp_state, session_id) = open_test_session();
         let source_file = "test_copy_source.txt";
         let dest_file = "test_copy_dest.txt";
         
         // First create a source file
-        let create_result = create_file_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let create_result = create_file_sftp_impl(
+            session_id,
             source_file.to_string(),
+            sftp_state.clone(),
         );
         assert!(create_result.is_ok(), "Should create source file first");
         
         // Then copy it
-        let result = copy_file_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let result = copy_file_sftp_impl(
+            session_id,
             source_file.to_string(),
             dest_file.to_string(),
+            sftp_state.clone(),
         );
         
         assert!(result.is_ok(), "Should successfully copy file");
         
         // Clean up
-        let _ = delete_file_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let _ = delete_file_sftp_impl(
+            session_id,
             source_file.to_string(),
+            sftp_state.clone(),
         );
-        let _ = delete_file_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let _ = delete_file_sftp_impl(
+            session_id,
             dest_file.to_string(),
+            sftp_state.clone(),
         );
     }
 
     #[test]
     fn test_copy_file_sftp_failure() {
-        let result = copy_file_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let (sftp_state, session_id) = open_test_session();
+        let result = copy_file_sftp_impl(
+            session_id,
             "nonexistent_source.txt".to_string(),
             "dest.txt".to_string(),
+            sftp_state.clone(),
         );
         
         assert!(result.is_err(), "Should fail with nonexistent source file");
@@ -896,50 +1309,44 @@ mod sftp_file_system_operation_commands_tests {
 
     #[test]
     fn test_move_file_sftp_success() {
+        let (sftp_state, session_id) = open_test_session();
         let source_file = "test_move_source.txt";
         let dest_file = "test_move_dest.txt";
         
         // First create a source file
-        let create_result = create_file_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let create_result = create_file_sftp_impl(
+            session_id,
             source_file.to_string(),
+            sftp_state.clone(),
         );
         assert!(create_result.is_ok(), "Should create source file first");
         
         // Then move it
-        let result = move_file_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let result = move_file_sftp_impl(
+            session_id,
             source_file.to_string(),
             dest_file.to_string(),
+            sftp_state.clone(),
         );
         
         assert!(result.is_ok(), "Should successfully move file");
         
         // Clean up
-        let _ = delete_file_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let _ = delete_file_sftp_impl(
+            session_id,
             dest_file.to_string(),
+            sftp_state.clone(),
         );
     }
 
     #[test]
     fn test_move_file_sftp_failure() {
-        let result = move_file_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let (sftp_state, session_id) = open_test_session();
+        let result = move_file_sftp_impl(
+            session_id,
             "nonexistent_file.txt".to_string(),
             "dest.txt".to_string(),
+            sftp_state.clone(),
         );
         
         assert!(result.is_err(), "Should fail with nonexistent file");
@@ -947,62 +1354,54 @@ mod sftp_file_system_operation_commands_tests {
 
     #[test]
     fn test_create_directory_sftp_success() {
+        let (sftp_state, session_id) = open_test_session();
         let test_dir = "test_create_directory";
         
-        let result = create_directory_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let result = create_directory_sftp_impl(
+            session_id,
             test_dir.to_string(),
+            sftp_state.clone(),
         );
         
         assert!(result.is_ok(), "Should successfully create directory");
         
         // Clean up
-        let _ = delete_directory_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let _ = delete_directory_sftp_impl(
+            session_id,
             test_dir.to_string(),
+            sftp_state.clone(),
         );
     }
 
     #[test]
-    fn test_create_directory_sftp_failure() {
-        let result = create_directory_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_WRONG_PASSWORD.to_string(),
+    fn test_create_directory_sftp_failure_unknown_session() {
+        let result = create_directory_sftp_impl(
+            u64::MAX,
             "test_dir".to_string(),
+            Arc::new(Mutex::new(SftpSessionState::new())),
         );
         
-        assert!(result.is_err(), "Should fail with wrong credentials");
+        assert!(result.is_err(), "Should fail with an unknown session");
     }
 
     #[test]
     fn test_delete_directory_sftp_success() {
+        let (sftp_state, session_id) = open_test_session();
         let test_dir = "test_delete_directory";
         
         // First create a directory
-        let create_result = create_directory_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let create_result = create_directory_sftp_impl(
+            session_id,
             test_dir.to_string(),
+            sftp_state.clone(),
         );
         assert!(create_result.is_ok(), "Should create test directory first");
         
         // Then delete it
-        let result = delete_directory_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let result = delete_directory_sftp_impl(
+            session_id,
             test_dir.to_string(),
+            sftp_state.clone(),
         );
         
         assert!(result.is_ok(), "Should successfully delete directory");
@@ -1010,12 +1409,11 @@ mod sftp_file_system_operation_commands_tests {
 
     #[test]
     fn test_delete_directory_sftp_failure() {
-        let result = delete_directory_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let (sftp_state, session_id) = open_test_session();
+        let result = delete_directory_sftp_impl(
+            session_id,
             "nonexistent_directory".to_string(),
+            sftp_state.clone(),
         );
         
         assert!(result.is_err(), "Should fail with nonexistent directory");
@@ -1023,50 +1421,44 @@ mod sftp_file_system_operation_commands_tests {
 
     #[test]
     fn test_rename_directory_sftp_success() {
+        let (sftp_state, session_id) = open_test_session();
         let original_dir = "test_rename_dir_original";
         let renamed_dir = "test_rename_dir_new";
         
         // First create a directory
-        let create_result = create_directory_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let create_result = create_directory_sftp_impl(
+            session_id,
             original_dir.to_string(),
+            sftp_state.clone(),
         );
         assert!(create_result.is_ok(), "Should create test directory first");
         
         // Then rename it
-        let result = rename_directory_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let result = rename_directory_sftp_impl(
+            session_id,
             original_dir.to_string(),
             renamed_dir.to_string(),
+            sftp_state.clone(),
         );
         
         assert!(result.is_ok(), "Should successfully rename directory");
         
         // Clean up
-        let _ = delete_directory_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let _ = delete_directory_sftp_impl(
+            session_id,
             renamed_dir.to_string(),
+            sftp_state.clone(),
         );
     }
 
     #[test]
     fn test_rename_directory_sftp_failure() {
-        let result = rename_directory_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let (sftp_state, session_id) = open_test_session();
+        let result = rename_directory_sftp_impl(
+            session_id,
             "nonexistent_directory".to_string(),
             "new_name".to_string(),
+            sftp_state.clone(),
         );
         
         assert!(result.is_err(), "Should fail with nonexistent directory");
@@ -1074,50 +1466,44 @@ mod sftp_file_system_operation_commands_tests {
 
     #[test]
     fn test_move_directory_sftp_success() {
+        let (sftp_state, session_id) = open_test_session();
         let source_dir = "test_move_dir_source";
         let dest_dir = "test_move_dir_dest";
         
         // First create a source directory
-        let create_result = create_directory_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let create_result = create_directory_sftp_impl(
+            session_id,
             source_dir.to_string(),
+            sftp_state.clone(),
         );
         assert!(create_result.is_ok(), "Should create source directory first");
         
         // Then move it
-        let result = move_directory_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let result = move_directory_sftp_impl(
+            session_id,
             source_dir.to_string(),
             dest_dir.to_string(),
+            sftp_state.clone(),
         );
         
         assert!(result.is_ok(), "Should successfully move directory");
         
         // Clean up
-        let _ = delete_directory_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let _ = delete_directory_sftp_impl(
+            session_id,
             dest_dir.to_string(),
+            sftp_state.clone(),
         );
     }
 
     #[test]
     fn test_move_directory_sftp_failure() {
-        let result = move_directory_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let (sftp_state, session_id) = open_test_session();
+        let result = move_directory_sftp_impl(
+            session_id,
             "nonexistent_directory".to_string(),
             "dest_dir".to_string(),
+            sftp_state.clone(),
         );
         
         assert!(result.is_err(), "Should fail with nonexistent directory");
@@ -1125,57 +1511,49 @@ mod sftp_file_system_operation_commands_tests {
 
     #[test]
     fn test_copy_directory_sftp_success() {
+        let (sftp_state, session_id) = open_test_session();
         let source_dir = "test_copy_dir_source";
         let dest_dir = "test_copy_dir_dest";
         
         // First create a source directory
-        let create_result = create_directory_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let create_result = create_directory_sftp_impl(
+            session_id,
             source_dir.to_string(),
+            sftp_state.clone(),
         );
         assert!(create_result.is_ok(), "Should create source directory first");
         
         // Then copy it
-        let result = copy_directory_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let result = copy_directory_sftp_impl(
+            session_id,
             source_dir.to_string(),
             dest_dir.to_string(),
+            sftp_state.clone(),
         );
         
         assert!(result.is_ok(), "Should successfully copy directory");
         
         // Clean up
-        let _ = delete_directory_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let _ = delete_directory_sftp_impl(
+            session_id,
             source_dir.to_string(),
+            sftp_state.clone(),
         );
-        let _ = delete_directory_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let _ = delete_directory_sftp_impl(
+            session_id,
             dest_dir.to_string(),
+            sftp_state.clone(),
         );
     }
 
     #[test]
     fn test_copy_directory_sftp_failure() {
-        let result = copy_directory_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let (sftp_state, session_id) = open_test_session();
+        let result = copy_directory_sftp_impl(
+            session_id,
             "nonexistent_directory".to_string(),
             "dest_dir".to_string(),
+            sftp_state.clone(),
         );
         
         assert!(result.is_err(), "Should fail with nonexistent directory");
@@ -1183,51 +1561,158 @@ mod sftp_file_system_operation_commands_tests {
 
     #[test]
     fn test_open_file_sftp_success() {
+        let (sftp_state, session_id) = open_test_session();
         // Test with an existing file - let's assume there's at least one file in the test directory
         // We'll create a file first, then read it
         let test_file = "test_read_file.txt";
         
         // First create a file
-        let create_result = create_file_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let create_result = create_file_sftp_impl(
+            session_id,
             test_file.to_string(),
+            sftp_state.clone(),
         );
         assert!(create_result.is_ok(), "Should create test file first");
         
         // Then try to read it
-        let result = open_file_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let result = open_file_sftp_impl(
+            session_id,
             test_file.to_string(),
+            sftp_state.clone(),
         );
         
         assert!(result.is_ok(), "Should successfully read file");
         
         // Clean up
-        let _ = delete_file_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let _ = delete_file_sftp_impl(
+            session_id,
             test_file.to_string(),
+            sftp_state.clone(),
         );
     }
 
     #[test]
     fn test_open_file_sftp_failure() {
-        let result = open_file_sftp(
-            TEST_HOST.to_string(),
-            TEST_PORT,
-            TEST_USERNAME.to_string(),
-            TEST_PASSWORD.to_string(),
+        let (sftp_state, session_id) = open_test_session();
+        let result = open_file_sftp_impl(
+            session_id,
             "nonexistent_file.txt".to_string(),
+            sftp_state.clone(),
         );
         
         assert!(result.is_err(), "Should fail with nonexistent file");
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests_sftp_listing {
+    use super::*;
+    use crate::remote::RemoteEntry;
+    use ssh2::FileStat;
+
+    fn stat(perm: u32, size: u64) -> FileStat {
+        FileStat {
+            size: Some(size),
+            uid: Some(1000),
+            gid: Some(100),
+            perm: Some(perm),
+            atime: Some(1_700_000_100),
+            mtime: Some(1_700_000_000),
+        }
+    }
+
+    fn entry(path: &str, stat: FileStat) -> RemoteEntry {
+        RemoteEntry {
+            path: PathBuf::from(path),
+            stat,
+            symlink_target: None,
+            target_stat: None,
+        }
+    }
+
+    #[test]
+    fn remote_entries_have_metadata_test() {
+        let entries = remote_entries(vec![
+            entry("/srv/www", stat(0o40755, 4096)),
+            entry("/srv/report.pdf", stat(0o100640, 48213)),
+        ]);
+
+        let directory = &entries.directories[0];
+        assert_eq!(directory.name, "www");
+        assert_eq!(directory.path, "/srv/www");
+        assert_eq!(directory.access_rights_as_string, "rwxr-xr-x");
+        assert_eq!(directory.size_in_bytes, 0);
+
+        let file = &entries.files[0];
+        assert_eq!(file.name, "report.pdf");
+        assert_eq!(file.size_in_bytes, 48213);
+        assert_eq!(file.access_rights_as_string, "rw-r-----");
+        assert_eq!(file.access_rights_as_number, 0o100640);
+        assert_eq!(file.last_modified, "2023-11-14 22:13:20");
+        assert_eq!(file.created, file.last_modified);
+        assert_eq!(file.accessed, "2023-11-14 22:15:00");
+        assert_eq!((file.owner_id, file.group_id), (Some(1000), Some(100)));
+        assert!(!file.is_symlink);
+    }
+
+    #[test]
+    fn symlinks_are_listed_as_their_target_test() {
+        let mut to_directory = entry("/srv/current", stat(0o120777, 8));
+        to_directory.symlink_target = Some("releases/42".to_string());
+        to_directory.target_stat = Some(stat(0o40755, 4096));
+        let mut broken = entry("/srv/broken", stat(0o120777, 7));
+        broken.symlink_target = Some("missing".to_string());
+
+        let entries = remote_entries(vec![to_directory, broken]);
+        assert_eq!(entries.directories.len(), 1);
+        assert!(entries.directories[0].is_symlink);
+        assert_eq!(
+            entries.directories[0].symlink_target.as_deref(),
+            Some("releases/42")
+        );
+        assert_eq!(entries.files.len(), 1);
+        assert!(entries.files[0].is_symlink);
+        assert_eq!(entries.files[0].size_in_bytes, 7);
+
+        // Local entries don't have the remote fields in their JSON
+        let json = serde_json::to_value(&entries.files[0]).unwrap();
+        assert_eq!(json["symlink_target"], "missing");
+        let mut local = entries.files[0].clone();
+        local.owner_id = None;
+        local.group_id = None;
+        local.symlink_target = None;
+        let json = serde_json::to_value(&local).unwrap();
+        assert!(json.get("owner_id").is_none());
+    }
+}
+
+#[cfg(test)]
+mod tests_sftp_temp_files {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn cleanup_removes_files_of_closed_sessions_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let edit_dir = temp_dir.path().join("session-4").join("edit-1");
+        fs::create_dir_all(&edit_dir).unwrap();
+        fs::write(edit_dir.join("app.conf"), "port = 80").unwrap();
+        fs::write(temp_dir.path().join("report.pdf"), "old layout").unwrap();
+        let sftp_state = Arc::new(Mutex::new(SftpSessionState::new()));
+
+        let message = cleanup_sftp_temp_files_impl(sftp_state.clone(), temp_dir.path()).unwrap();
+
+        assert_eq!(message, "Cleaned 2 temporary files of closed sessions");
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+
+        let missing = temp_dir.path().join("missing");
+        assert_eq!(
+            cleanup_sftp_temp_files_impl(sftp_state, &missing).unwrap(),
+            "No temporary directory to clean"
+        );
+    }
+
+    #[test]
+    fn session_temp_dir_test() {
+        assert_eq!(session_temp_dir(7), sftp_temp_dir().join("session-7"));
+    }
+}
@@ -0,0 +1,160 @@
+use crate::commands::sftp_file_system_operation_commands::get_session;
+use crate::remote::transfer::{
+    download_directory, plan_download, plan_upload_sources, start_transfer, TransferDirection,
+    TransferOptions,
+};
+use crate::state::jobs_data::JobsState;
+use crate::state::sftp_session_data::SftpSessionState;
+use std::sync::{Arc, Mutex};
+use tauri::State;
+
+/// Uploads local files and directories into a directory on the server as a background job.
+/// Files are sent in batches into a `.part` file which is renamed once it is complete and
+/// verified. If the connection drops the job reconnects and continues where it stopped, a
+/// cancelled or failed upload continues from the `.part` file the next time it is started.
+///
+/// # Arguments
+/// * `session_id` - The id of a session opened with `open_sftp_session`.
+/// * `local_paths` - The local files and directories to upload.
+/// * `remote_directory` - The directory on the server the paths are uploaded into.
+/// * `options` - Optional resume, verification and overwrite settings.
+/// * `sftp_state` - The open SFTP sessions.
+/// * `jobs_state` - The jobs state the upload is registered in.
+///
+/// # Returns
+/// * `Ok(u64)` - The id of the job. Its progress counts files and bytes, its result is a
+///   `TransferReport`.
+/// * `Err(String)` - If the session does not exist or a local path can't be read.
+///
+/// # Example
+/// ```rust
+/// let job_id = upload_to_sftp(session_id, vec!["/home/alice/photos".to_string()], "/srv/backup".to_string(), None, sftp_state, jobs_state)?;
+/// ```
+#[tauri::command]
+pub fn upload_to_sftp(
+    session_id: u64,
+    local_paths: Vec<String>,
+    remote_directory: String,
+    options: Option<TransferOptions>,
+    sftp_state: State<Arc<Mutex<SftpSessionState>>>,
+    jobs_state: State<Arc<Mutex<JobsState>>>,
+) -> Result<u64, String> {
+    upload_to_sftp_impl(
+        session_id,
+        local_paths,
+        remote_directory,
+        options,
+        sftp_state.inner().clone(),
+        jobs_state.inner().clone(),
+    )
+}
+
+pub fn upload_to_sftp_impl(
+    session_id: u64,
+    local_paths: Vec<String>,
+    remote_directory: String,
+    options: Option<TransferOptions>,
+    sftp_state: Arc<Mutex<SftpSessionState>>,
+    jobs_state: Arc<Mutex<JobsState>>,
+) -> Result<u64, String> {
+    let session = get_session(session_id, &sftp_state)?;
+    let plan = plan_upload_sources(&local_paths, &remote_directory)?;
+    start_transfer(
+        session,
+        TransferDirection::Upload,
+        move |_| Ok(plan),
+        options.unwrap_or_default(),
+        jobs_state,
+    )
+}
+
+/// Downloads files and directories from the server into a local directory as a background
+/// job. Like `upload_to_sftp`, files are written to a `.part` file first and a transfer
+/// continues from it after a dropped connection or when it is started again.
+///
+/// # Arguments
+/// * `session_id` - The id of a session opened with `open_sftp_session`.
+/// * `remote_paths` - The files and directories on the server to download.
+/// * `local_directory` - The existing local directory the paths are downloaded into.
+/// * `options` - Optional resume, verification and overwrite settings.
+/// * `sftp_state` - The open SFTP sessions.
+/// * `jobs_state` - The jobs state the download is registered in.
+///
+/// # Returns
+/// * `Ok(u64)` - The id of the job. Its result is a `TransferReport`, the job fails if a remote
+///   path does not exist.
+/// * `Err(String)` - If the session or the local directory does not exist.
+///
+/// # Example
+/// ```rust
+/// let job_id = download_from_sftp(session_id, vec!["/var/log/nginx".to_string()], "/home/alice/logs".to_string(), None, sftp_state, jobs_state)?;
+/// ```
+#[tauri::command]
+pub fn download_from_sftp(
+    session_id: u64,
+    remote_paths: Vec<String>,
+    local_directory: String,
+    options: Option<TransferOptions>,
+    sftp_state: State<Arc<Mutex<SftpSessionState>>>,
+    jobs_state: State<Arc<Mutex<JobsState>>>,
+) -> Result<u64, String> {
+    download_from_sftp_impl(
+        session_id,
+        remote_paths,
+        local_directory,
+        options,
+        sftp_state.inner().clone(),
+        jobs_state.inner().clone(),
+    )
+}
+
+pub fn download_from_sftp_impl(
+    session_id: u64,
+    remote_paths: Vec<String>,
+    local_directory: String,
+    options: Option<TransferOptions>,
+    sftp_state: Arc<Mutex<SftpSessionState>>,
+    jobs_state: Arc<Mutex<JobsState>>,
+) -> Result<u64, String> {
+    let session = get_session(session_id, &sftp_state)?;
+    let local_directory = download_directory(local_directory)?;
+    start_transfer(
+        session,
+        TransferDirection::Download,
+        move |session| plan_download(session, &remote_paths, &local_directory),
+        options.unwrap_or_default(),
+        jobs_state,
+    )
+}
+
+#[cfg(test)]
+mod tests_sftp_transfer_commands {
+    use super::*;
+
+    #[test]
+    fn transfer_commands_need_an_open_session_test() {
+        let sftp_state = Arc::new(Mutex::new(SftpSessionState::new()));
+        let jobs_state = Arc::new(Mutex::new(JobsState::new()));
+
+        let upload = upload_to_sftp_impl(
+            7,
+            vec![],
+            "/srv".to_string(),
+            None,
+            sftp_state.clone(),
+            jobs_state.clone(),
+        );
+        assert!(upload.unwrap_err().contains("ResourceNotFound"));
+
+        let download = download_from_sftp_impl(
+            7,
+            vec!["/srv/a.txt".to_string()],
+            ".".to_string(),
+            None,
+            sftp_state,
+            jobs_state.clone(),
+        );
+        assert!(download.unwrap_err().contains("ResourceNotFound"));
+        assert!(jobs_state.lock().unwrap().list_jobs().is_empty());
+    }
+}
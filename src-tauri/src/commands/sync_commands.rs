@@ -0,0 +1,1266 @@
+use crate::commands::hash_commands::{hash_reader, ChecksumMethod};
+use crate::commands::trash_commands::{SystemTrash, TrashBackend};
+use crate::error_handling::{Error, ErrorCode};
+use crate::filesystem::fs_utils::{is_excluded, modified_millis};
+use crate::state::jobs_data::{JobHandle, JobsState};
+use crate::state::sync_snapshots_data::{SnapshotEntry, SyncSnapshot, SyncSnapshotsState};
+use crate::{log_error, log_info};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::State;
+
+/// Default tolerance for modification times. FAT and exFAT drives store them with a
+/// resolution of two seconds, so an exact comparison would report every copied file.
+const DEFAULT_MODIFIED_TOLERANCE_MS: u64 = 2000;
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub enum CompareMethod {
+    /// Files with the same size and modification time are considered equal.
+    SizeAndModified,
+    /// Files are equal if their SHA256 is equal, independent of the modification time.
+    Hash,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct CompareOptions {
+    pub method: CompareMethod,
+    /// Relative paths containing one of these patterns are neither compared nor synchronized.
+    pub excluded_patterns: Vec<String>,
+    /// Modification times which differ by at most this many milliseconds count as equal.
+    pub modified_tolerance_ms: u64,
+}
+
+impl Default for CompareOptions {
+    fn default() -> Self {
+        Self {
+            method: CompareMethod::SizeAndModified,
+            excluded_patterns: Vec::new(),
+            modified_tolerance_ms: DEFAULT_MODIFIED_TOLERANCE_MS,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub enum DifferenceKind {
+    OnlyLeft,
+    OnlyRight,
+    LeftNewer,
+    RightNewer,
+    /// The content differs, but the modification times are equal.
+    ContentDiffers,
+    /// A file on one side and a directory on the other.
+    TypeMismatch,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct EntryInfo {
+    pub is_directory: bool,
+    pub size: u64,
+    pub modified: u64, // as milliseconds since epoch
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct DirectoryDifference {
+    /// Path relative to both roots, with `/` separators.
+    pub path: String,
+    pub kind: DifferenceKind,
+    pub left: Option<EntryInfo>,
+    pub right: Option<EntryInfo>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct DirectoryComparison {
+    pub left: String,
+    pub right: String,
+    /// Differences sorted by path. A directory which only exists on one side is reported
+    /// once, without its content.
+    pub differences: Vec<DirectoryDifference>,
+    pub identical_files: u64,
+    /// Relative paths of directories which could not be read on one of the sides.
+    pub unreadable: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub enum SyncMode {
+    /// Makes the right side an exact copy of the left side, extra entries on the right are
+    /// moved to the trash.
+    Mirror,
+    /// Copies new and newer entries from left to right, nothing is deleted.
+    Update,
+    /// Copies new and changed entries in both directions. A snapshot of the last run tells
+    /// which side changed an entry, entries changed on both sides are reported as conflicts.
+    /// Without a snapshot, on the first run, the newer side wins and only entries which differ
+    /// with the same modification time or in their type are conflicts.
+    TwoWay,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub enum SyncAction {
+    CopyToRight,
+    CopyToLeft,
+    DeleteFromRight,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub enum SyncOperationStatus {
+    /// Planned in a dry run.
+    Planned,
+    Done,
+    Failed,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct SyncOperation {
+    pub path: String,
+    pub action: SyncAction,
+    pub is_directory: bool,
+    pub status: SyncOperationStatus,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct SyncReport {
+    pub mode: SyncMode,
+    pub dry_run: bool,
+    pub operations: Vec<SyncOperation>,
+    /// Differences which the mode can't resolve, e.g. files changed on both sides.
+    pub conflicts: Vec<DirectoryDifference>,
+    pub bytes_copied: u64,
+}
+
+enum SyncError {
+    Cancelled,
+}
+
+fn is_cancelled(job: Option<&JobHandle>) -> bool {
+    job.is_some_and(|job| job.is_cancelled())
+}
+
+fn entry_info(metadata: &fs::Metadata) -> EntryInfo {
+    EntryInfo {
+        is_directory: metadata.is_dir(),
+        size: if metadata.is_dir() { 0 } else { metadata.len() },
+        modified: modified_millis(metadata),
+    }
+}
+
+fn join_relative(parent: &str, name: &OsString) -> String {
+    let name = name.to_string_lossy();
+    if parent.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", parent, name)
+    }
+}
+
+/// Reads the files and directories of a directory by name. Symlinks and special files
+/// are neither compared nor synchronized.
+fn read_entries(path: &Path) -> std::io::Result<BTreeMap<OsString, fs::Metadata>> {
+    let mut entries = BTreeMap::new();
+    for entry in fs::read_dir(path)?.flatten() {
+        let Ok(metadata) = entry.path().symlink_metadata() else {
+            continue;
+        };
+        if metadata.is_dir() || metadata.is_file() {
+            entries.insert(entry.file_name(), metadata);
+        }
+    }
+    Ok(entries)
+}
+
+fn hash_of(path: &Path) -> std::io::Result<String> {
+    hash_reader(&ChecksumMethod::SHA256, &mut fs::File::open(path)?)
+}
+
+/// Compares two files which exist on both sides. Returns `None` if they are equal.
+fn compare_files(
+    left_path: &Path,
+    right_path: &Path,
+    left: &EntryInfo,
+    right: &EntryInfo,
+    options: &CompareOptions,
+) -> Option<DifferenceKind> {
+    let modified_diff = left.modified.abs_diff(right.modified);
+    let same_modified = modified_diff <= options.modified_tolerance_ms;
+    let same_content = match options.method {
+        CompareMethod::SizeAndModified => left.size == right.size && same_modified,
+        CompareMethod::Hash => {
+            left.size == right.size
+                && match (hash_of(left_path), hash_of(right_path)) {
+                    (Ok(left_hash), Ok(right_hash)) => left_hash == right_hash,
+                    _ => false,
+                }
+        }
+    };
+    if same_content {
+        None
+    } else if same_modified {
+        Some(DifferenceKind::ContentDiffers)
+    } else if left.modified > right.modified {
+        Some(DifferenceKind::LeftNewer)
+    } else {
+        Some(DifferenceKind::RightNewer)
+    }
+}
+
+fn compare_level(
+    left_dir: &Path,
+    right_dir: &Path,
+    relative: &str,
+    options: &CompareOptions,
+    comparison: &mut DirectoryComparison,
+    job: Option<&JobHandle>,
+) -> Result<(), SyncError> {
+    if is_cancelled(job) {
+        return Err(SyncError::Cancelled);
+    }
+    let (left_entries, right_entries) = match (read_entries(left_dir), read_entries(right_dir)) {
+        (Ok(left), Ok(right)) => (left, right),
+        (Err(err), _) | (_, Err(err)) => {
+            log_error!(
+                "Failed to read '{}' or '{}' for comparison: {}",
+                left_dir.display(),
+                right_dir.display(),
+                err
+            );
+            comparison.unreadable.push(relative.to_string());
+            return Ok(());
+        }
+    };
+    if let Some(job) = job {
+        job.update_progress(|p| {
+            p.items_processed += 1;
+            p.current_path = Some(left_dir.to_string_lossy().to_string());
+        });
+    }
+
+    let mut names: Vec<&OsString> = left_entries.keys().chain(right_entries.keys()).collect();
+    names.sort();
+    names.dedup();
+
+    for name in names {
+        let path = join_relative(relative, name);
+        if is_excluded(&path, &options.excluded_patterns) {
+            continue;
+        }
+        let left = left_entries.get(name).map(entry_info);
+        let right = right_entries.get(name).map(entry_info);
+        let kind = match (&left, &right) {
+            (Some(_), None) => Some(DifferenceKind::OnlyLeft),
+            (None, Some(_)) => Some(DifferenceKind::OnlyRight),
+            (Some(l), Some(r)) if l.is_directory != r.is_directory => {
+                Some(DifferenceKind::TypeMismatch)
+            }
+            (Some(l), Some(_)) if l.is_directory => {
+                compare_level(
+                    &left_dir.join(name),
+                    &right_dir.join(name),
+                    &path,
+                    options,
+                    comparison,
+                    job,
+                )?;
+                continue;
+            }
+            (Some(l), Some(r)) => {
+                let kind =
+                    compare_files(&left_dir.join(name), &right_dir.join(name), l, r, options);
+                if kind.is_none() {
+                    comparison.identical_files += 1;
+                }
+                kind
+            }
+            (None, None) => None,
+        };
+        if let Some(kind) = kind {
+            comparison.differences.push(DirectoryDifference {
+                path,
+                kind,
+                left,
+                right,
+            });
+        }
+    }
+    Ok(())
+}
+
+fn compare_trees(
+    left: &str,
+    right: &str,
+    options: &CompareOptions,
+    job: Option<&JobHandle>,
+) -> Result<DirectoryComparison, SyncError> {
+    let mut comparison = DirectoryComparison {
+        left: left.to_string(),
+        right: right.to_string(),
+        differences: Vec::new(),
+        identical_files: 0,
+        unreadable: Vec::new(),
+    };
+    compare_level(
+        Path::new(left),
+        Path::new(right),
+        "",
+        options,
+        &mut comparison,
+        job,
+    )?;
+    Ok(comparison)
+}
+
+fn validate_directories(left: &str, right: &str) -> Result<(), String> {
+    for path in [left, right] {
+        if !Path::new(path).is_dir() {
+            log_error!("Directory does not exist: {}", path);
+            return Err(Error::new(
+                ErrorCode::ResourceNotFound,
+                format!("Directory does not exist: {}", path),
+            )
+            .to_json());
+        }
+    }
+    let canonical = |path: &str| fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+    let (left_canonical, right_canonical) = (canonical(left), canonical(right));
+    if left_canonical.starts_with(&right_canonical) || right_canonical.starts_with(&left_canonical)
+    {
+        log_error!(
+            "Directories must not contain each other: {} {}",
+            left,
+            right
+        );
+        return Err(Error::new(
+            ErrorCode::InvalidInput,
+            "Directories must not contain each other".to_string(),
+        )
+        .to_json());
+    }
+    Ok(())
+}
+
+/// Compares two directory trees, e.g. a local folder with its copy on a USB drive.
+///
+/// # Arguments
+/// * `left` - The first directory.
+/// * `right` - The second directory.
+/// * `options` - Optional comparison method, excluded patterns and tolerance for modification
+///   times (2 seconds by default, the resolution of FAT drives).
+///
+/// # Returns
+/// * `Ok(DirectoryComparison)` - All entries which are only on one side, newer on one side or
+///   have a different content.
+/// * `Err(String)` - If one of the directories does not exist or they contain each other.
+///
+/// # Example
+/// ```rust
+/// let comparison = compare_directories("/home/user/Photos".to_string(), "/media/usb/Photos".to_string(), None).await?;
+/// ```
+#[tauri::command]
+pub async fn compare_directories(
+    left: String,
+    right: String,
+    options: Option<CompareOptions>,
+) -> Result<DirectoryComparison, String> {
+    validate_directories(&left, &right)?;
+    let options = options.unwrap_or_default();
+    tokio::task::spawn_blocking(move || compare_trees(&left, &right, &options, None))
+        .await
+        .map_err(|e| {
+            log_error!("Directory comparison failed: {}", e);
+            Error::new(
+                ErrorCode::InternalError,
+                format!("Directory comparison failed: {}", e),
+            )
+            .to_json()
+        })?
+        .map_err(|SyncError::Cancelled| {
+            Error::new(
+                ErrorCode::InternalError,
+                "Directory comparison was cancelled".to_string(),
+            )
+            .to_json()
+        })
+}
+
+/// Whether an entry differs from its state after the last run. An entry which did not exist
+/// back then is new, so it counts as changed.
+fn changed_since(
+    base: Option<&SnapshotEntry>,
+    current: &EntryInfo,
+    path: &Path,
+    options: &CompareOptions,
+) -> bool {
+    let Some(base) = base else {
+        return true;
+    };
+    if base.is_directory != current.is_directory {
+        return true;
+    }
+    if current.is_directory {
+        return false;
+    }
+    match (&base.hash, options.method) {
+        (Some(hash), CompareMethod::Hash) => {
+            base.size != current.size || hash_of(path).map_or(true, |current| &current != hash)
+        }
+        _ => {
+            base.size != current.size
+                || base.modified.abs_diff(current.modified) > options.modified_tolerance_ms
+        }
+    }
+}
+
+/// Decides a difference of a two-way synchronization by the sides which changed since the
+/// last run. Returns `None` for a conflict.
+fn two_way_action(
+    difference: &DirectoryDifference,
+    snapshot: &SyncSnapshot,
+    comparison: &DirectoryComparison,
+    options: &CompareOptions,
+) -> Option<SyncAction> {
+    let (Some(left), Some(right)) = (&difference.left, &difference.right) else {
+        // An entry on one side only is copied to the other one
+        return match difference.left {
+            Some(_) => Some(SyncAction::CopyToRight),
+            None => Some(SyncAction::CopyToLeft),
+        };
+    };
+    let base = snapshot.get(&difference.path);
+    let left_path = Path::new(&comparison.left).join(&difference.path);
+    let right_path = Path::new(&comparison.right).join(&difference.path);
+    match (
+        changed_since(base, left, &left_path, options),
+        changed_since(base, right, &right_path, options),
+    ) {
+        (true, false) => Some(SyncAction::CopyToRight),
+        (false, true) => Some(SyncAction::CopyToLeft),
+        _ => None,
+    }
+}
+
+/// Decides what to do with every difference. Differences without an action in the mode are
+/// returned as conflicts. `snapshot` is the state after the last two-way run of the pair.
+fn plan_operations(
+    comparison: &DirectoryComparison,
+    mode: SyncMode,
+    snapshot: Option<&SyncSnapshot>,
+    options: &CompareOptions,
+) -> (Vec<SyncOperation>, Vec<DirectoryDifference>) {
+    let mut operations = Vec::new();
+    let mut conflicts = Vec::new();
+    for difference in &comparison.differences {
+        let action = match (mode, difference.kind) {
+            (SyncMode::TwoWay, _) if snapshot.is_some() => {
+                let action = snapshot
+                    .and_then(|snapshot| two_way_action(difference, snapshot, comparison, options));
+                if action.is_none() {
+                    conflicts.push(difference.clone());
+                }
+                action
+            }
+            (_, DifferenceKind::OnlyLeft) | (_, DifferenceKind::LeftNewer) => {
+                Some(SyncAction::CopyToRight)
+            }
+            (SyncMode::Mirror, DifferenceKind::OnlyRight) => Some(SyncAction::DeleteFromRight),
+            (SyncMode::Mirror, _) => Some(SyncAction::CopyToRight),
+            (SyncMode::Update, DifferenceKind::OnlyRight) => None,
+            (SyncMode::TwoWay, DifferenceKind::OnlyRight)
+            | (SyncMode::TwoWay, DifferenceKind::RightNewer) => Some(SyncAction::CopyToLeft),
+            _ => {
+                conflicts.push(difference.clone());
+                None
+            }
+        };
+        if let Some(action) = action {
+            let source = match action {
+                SyncAction::CopyToLeft | SyncAction::DeleteFromRight => &difference.right,
+                SyncAction::CopyToRight => &difference.left,
+            };
+            operations.push(SyncOperation {
+                path: difference.path.clone(),
+                action,
+                is_directory: source.as_ref().is_some_and(|info| info.is_directory),
+                status: SyncOperationStatus::Planned,
+                error: None,
+            });
+        }
+    }
+    (operations, conflicts)
+}
+
+/// Reports copied bytes to the job and stops the copy once the job is cancelled.
+struct ProgressReader<'a, R: Read> {
+    inner: R,
+    job: &'a JobHandle,
+}
+
+impl<R: Read> Read for ProgressReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.job.is_cancelled() {
+            return Err(std::io::Error::other("Synchronization was cancelled"));
+        }
+        let read = self.inner.read(buf)?;
+        self.job
+            .update_progress(|p| p.bytes_processed += read as u64);
+        Ok(read)
+    }
+}
+
+/// Copies a file over a temporary file next to the target, so an interrupted copy never
+/// leaves a truncated target behind. The modification time is kept, otherwise the next
+/// comparison would report the copy as newer.
+fn copy_file(source: &Path, target: &Path, job: &JobHandle) -> Result<u64, String> {
+    let file_name = target
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let temp_path = target.with_file_name(format!(".{}.sync-tmp", file_name));
+    let copy = || -> std::io::Result<u64> {
+        let source_file = fs::File::open(source)?;
+        let metadata = source_file.metadata()?;
+        let mut temp_file = fs::File::create(&temp_path)?;
+        let copied = std::io::copy(
+            &mut ProgressReader {
+                inner: source_file,
+                job,
+            },
+            &mut temp_file,
+        )?;
+        temp_file.set_permissions(metadata.permissions())?;
+        temp_file.set_modified(metadata.modified()?)?;
+        drop(temp_file);
+        fs::rename(&temp_path, target)?;
+        Ok(copied)
+    };
+    copy().map_err(|err| {
+        let _ = fs::remove_file(&temp_path);
+        err.to_string()
+    })
+}
+
+/// Copies a file or a whole directory, skipping excluded entries.
+fn copy_entry(
+    source_root: &Path,
+    target_root: &Path,
+    relative: &str,
+    options: &CompareOptions,
+    trash: &dyn TrashBackend,
+    job: &JobHandle,
+) -> Result<u64, String> {
+    let source = source_root.join(relative);
+    let target = target_root.join(relative);
+
+    // Resolves a type mismatch, the entry of the source side wins and the replaced entry is
+    // moved to the trash, so it can be recovered
+    if let Ok(metadata) = target.symlink_metadata() {
+        if metadata.is_dir() != source.is_dir() {
+            trash.delete(&target).map_err(|e| e.to_string())?;
+        }
+    }
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    if !source.is_dir() {
+        return copy_file(&source, &target, job);
+    }
+
+    let mut bytes = 0;
+    let walker = walkdir::WalkDir::new(&source)
+        .follow_links(false)
+        .into_iter()
+        .filter_entry(|entry| {
+            let path = entry
+                .path()
+                .strip_prefix(source_root)
+                .map(|p| p.to_string_lossy().replace('\\', "/"))
+                .unwrap_or_default();
+            entry.depth() == 0 || !is_excluded(&path, &options.excluded_patterns)
+        });
+    for entry in walker {
+        if job.is_cancelled() {
+            return Err("Synchronization was cancelled".to_string());
+        }
+        let entry = entry.map_err(|e| e.to_string())?;
+        let entry_target = target.join(
+            entry
+                .path()
+                .strip_prefix(&source)
+                .map_err(|e| e.to_string())?,
+        );
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&entry_target).map_err(|e| e.to_string())?;
+        } else if entry.file_type().is_file() {
+            bytes += copy_file(entry.path(), &entry_target, job)?;
+        }
+    }
+    Ok(bytes)
+}
+
+fn execute_operation(
+    operation: &SyncOperation,
+    left: &Path,
+    right: &Path,
+    options: &CompareOptions,
+    trash: &dyn TrashBackend,
+    job: &JobHandle,
+) -> Result<u64, String> {
+    match operation.action {
+        SyncAction::CopyToRight => copy_entry(left, right, &operation.path, options, trash, job),
+        SyncAction::CopyToLeft => copy_entry(right, left, &operation.path, options, trash, job),
+        SyncAction::DeleteFromRight => trash
+            .delete(&right.join(&operation.path))
+            .map(|_| 0)
+            .map_err(|e| e.to_string()),
+    }
+}
+
+fn is_within(path: &str, parent: &str) -> bool {
+    parent.is_empty()
+        || path == parent
+        || path
+            .strip_prefix(parent)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// Records the entries which are equal on both sides after a two-way run. Entries which still
+/// differ, because of a conflict or a failed operation, keep their state of the previous run,
+/// so they are decided the same way next time.
+fn build_snapshot(
+    left: &str,
+    right: &str,
+    options: &CompareOptions,
+    previous: Option<&SyncSnapshot>,
+) -> SyncSnapshot {
+    // Without a job the comparison is never cancelled
+    let Ok(comparison) = compare_trees(left, right, options, None) else {
+        return previous.cloned().unwrap_or_default();
+    };
+    let left_root = Path::new(left);
+    let walker = walkdir::WalkDir::new(left_root)
+        .min_depth(1)
+        .follow_links(false)
+        .into_iter()
+        .filter_entry(|entry| {
+            let path = entry
+                .path()
+                .strip_prefix(left_root)
+                .map(|p| p.to_string_lossy().replace('\\', "/"))
+                .unwrap_or_default();
+            !is_excluded(&path, &options.excluded_patterns)
+        });
+
+    let mut snapshot = SyncSnapshot::new();
+    for entry in walker.flatten() {
+        if !entry.file_type().is_dir() && !entry.file_type().is_file() {
+            continue;
+        }
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        let info = entry_info(&metadata);
+        let hash = match options.method {
+            CompareMethod::Hash if !info.is_directory => hash_of(entry.path()).ok(),
+            _ => None,
+        };
+        let path = entry
+            .path()
+            .strip_prefix(left_root)
+            .map(|p| p.to_string_lossy().replace('\\', "/"))
+            .unwrap_or_default();
+        snapshot.insert(
+            path,
+            SnapshotEntry {
+                is_directory: info.is_directory,
+                size: info.size,
+                modified: info.modified,
+                hash,
+            },
+        );
+    }
+
+    let unsynchronized = comparison
+        .differences
+        .iter()
+        .map(|difference| &difference.path)
+        .chain(&comparison.unreadable);
+    for parent in unsynchronized {
+        snapshot.retain(|path, _| !is_within(path, parent));
+        if let Some(previous) = previous {
+            snapshot.extend(
+                previous
+                    .iter()
+                    .filter(|(path, _)| is_within(path, parent))
+                    .map(|(path, entry)| (path.clone(), entry.clone())),
+            );
+        }
+    }
+    snapshot
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_sync(
+    left: &str,
+    right: &str,
+    mode: SyncMode,
+    options: &CompareOptions,
+    dry_run: bool,
+    trash: &dyn TrashBackend,
+    snapshots: &Mutex<SyncSnapshotsState>,
+    job: &JobHandle,
+) -> Result<SyncReport, (SyncError, Option<SyncReport>)> {
+    let comparison = compare_trees(left, right, options, Some(job)).map_err(|e| (e, None))?;
+    let snapshot = match mode {
+        SyncMode::TwoWay => snapshots.lock().unwrap().load(left, right),
+        _ => None,
+    };
+    let (operations, conflicts) = plan_operations(&comparison, mode, snapshot.as_ref(), options);
+    let mut report = SyncReport {
+        mode,
+        dry_run,
+        operations,
+        conflicts,
+        bytes_copied: 0,
+    };
+    if dry_run {
+        return Ok(report);
+    }
+
+    job.update_progress(|p| {
+        p.items_total = report.operations.len() as u64;
+        p.items_processed = 0;
+    });
+    for index in 0..report.operations.len() {
+        if job.is_cancelled() {
+            return Err((SyncError::Cancelled, Some(report)));
+        }
+        let operation = &report.operations[index];
+        job.update_progress(|p| p.current_path = Some(operation.path.clone()));
+        let result = execute_operation(
+            operation,
+            Path::new(left),
+            Path::new(right),
+            options,
+            trash,
+            job,
+        );
+        if job.is_cancelled() {
+            return Err((SyncError::Cancelled, Some(report)));
+        }
+        let operation = &mut report.operations[index];
+        match result {
+            Ok(bytes) => {
+                operation.status = SyncOperationStatus::Done;
+                report.bytes_copied += bytes;
+            }
+            Err(err) => {
+                log_error!("Failed to synchronize '{}': {}", operation.path, err);
+                operation.status = SyncOperationStatus::Failed;
+                operation.error = Some(err);
+            }
+        }
+        job.update_progress(|p| p.items_processed += 1);
+    }
+
+    if mode == SyncMode::TwoWay {
+        let new_snapshot = build_snapshot(left, right, options, snapshot.as_ref());
+        if let Err(err) = snapshots.lock().unwrap().save(left, right, &new_snapshot) {
+            log_error!(
+                "Failed to save the snapshot of '{}' and '{}': {}",
+                left,
+                right,
+                err
+            );
+        }
+    }
+    Ok(report)
+}
+
+/// Starts a background job which synchronizes two directories. The job compares both trees
+/// first, plans the operations for the mode and executes them. In a dry run the job only
+/// returns the plan.
+///
+/// # Arguments
+/// * `left` - The source directory, for `TwoWay` just the first directory.
+/// * `right` - The target directory.
+/// * `mode` - `Mirror`, `Update` or `TwoWay`.
+/// * `options` - Optional comparison options, see `compare_directories`.
+/// * `dry_run` - If true, nothing is changed and the operations are returned as `Planned`.
+///   Deleted entries and entries replaced by one of another type are moved to the trash.
+/// * `sync_snapshots_state` - The snapshots of earlier `TwoWay` runs, updated after each run.
+/// * `jobs_state` - The jobs state the synchronization is registered in.
+///
+/// # Returns
+/// * `Ok(u64)` - The id of the job. Its result is a `SyncReport`, which contains the finished
+///   operations if the job is cancelled.
+/// * `Err(String)` - If one of the directories does not exist or they contain each other.
+///
+/// # Example
+/// ```rust
+/// let job_id = sync_directories(left, right, SyncMode::Update, None, Some(true), sync_snapshots_state, jobs_state)?;
+/// ```
+#[tauri::command]
+pub fn sync_directories(
+    left: String,
+    right: String,
+    mode: SyncMode,
+    options: Option<CompareOptions>,
+    dry_run: Option<bool>,
+    sync_snapshots_state: State<Arc<Mutex<SyncSnapshotsState>>>,
+    jobs_state: State<Arc<Mutex<JobsState>>>,
+) -> Result<u64, String> {
+    sync_directories_impl(
+        left,
+        right,
+        mode,
+        options,
+        dry_run,
+        Arc::new(SystemTrash),
+        sync_snapshots_state.inner().clone(),
+        jobs_state.inner().clone(),
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn sync_directories_impl(
+    left: String,
+    right: String,
+    mode: SyncMode,
+    options: Option<CompareOptions>,
+    dry_run: Option<bool>,
+    trash: Arc<dyn TrashBackend>,
+    sync_snapshots_state: Arc<Mutex<SyncSnapshotsState>>,
+    jobs_state: Arc<Mutex<JobsState>>,
+) -> Result<u64, String> {
+    validate_directories(&left, &right)?;
+    let options = options.unwrap_or_default();
+    let dry_run = dry_run.unwrap_or(false);
+
+    let job = JobsState::start_job(&jobs_state, "directory_sync");
+    let job_id = job.id();
+
+    std::thread::Builder::new()
+        .name("directory-sync-thread".to_string())
+        .spawn(move || {
+            match run_sync(
+                &left,
+                &right,
+                mode,
+                &options,
+                dry_run,
+                trash.as_ref(),
+                &sync_snapshots_state,
+                &job,
+            ) {
+                Ok(report) => job.complete(serde_json::to_value(report).ok()),
+                Err((SyncError::Cancelled, report)) => {
+                    log_info!("Directory synchronization was cancelled");
+                    if let Some(report) = report {
+                        job.set_partial_result(serde_json::to_value(report).unwrap_or_default());
+                    }
+                    job.mark_cancelled();
+                }
+            }
+        })
+        .map_err(|e| {
+            log_error!("Failed to spawn directory sync thread: {}", e);
+            Error::new(
+                ErrorCode::InternalError,
+                format!("Failed to spawn directory sync thread: {}", e),
+            )
+            .to_json()
+        })?;
+
+    Ok(job_id)
+}
+
+#[cfg(test)]
+mod tests_sync_commands {
+    use super::*;
+    use crate::commands::trash_commands::DirectoryTrash;
+    use crate::state::jobs_data::{wait_for_job, JobStatus};
+    use std::time::{Duration, SystemTime};
+    use tempfile::tempdir;
+
+    fn write_file(path: &Path, content: &str, age_secs: u64) {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(path, content).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(age_secs))
+            .unwrap();
+    }
+
+    /// Left and right share `same.txt`, the other files differ in every possible way.
+    fn create_trees(root: &Path) -> (PathBuf, PathBuf) {
+        let left = root.join("left");
+        let right = root.join("right");
+        write_file(&left.join("same.txt"), "same", 1000);
+        write_file(&right.join("same.txt"), "same", 1000);
+        write_file(&left.join("only_left.txt"), "left", 1000);
+        write_file(&left.join("new_dir").join("a.txt"), "a", 1000);
+        write_file(&right.join("only_right.txt"), "right", 1000);
+        write_file(&left.join("docs").join("newer.txt"), "new", 10);
+        write_file(&right.join("docs").join("newer.txt"), "old", 1000);
+        write_file(&left.join("docs").join("older.txt"), "old", 1000);
+        write_file(&right.join("docs").join("older.txt"), "newer", 10);
+        write_file(&left.join("conflict.txt"), "abcd", 500);
+        write_file(&right.join("conflict.txt"), "xyz", 500);
+        (left, right)
+    }
+
+    fn kinds_of(comparison: &DirectoryComparison) -> Vec<(String, DifferenceKind)> {
+        comparison
+            .differences
+            .iter()
+            .map(|d| (d.path.clone(), d.kind))
+            .collect()
+    }
+
+    /// Runs a synchronization job with a trash next to `left`.
+    fn run_sync_job(left: &Path, right: &Path, mode: SyncMode, dry_run: bool) -> SyncReport {
+        let trash = DirectoryTrash::new(&left.parent().unwrap().join(".trash"));
+        run_sync_job_with_trash(left, right, mode, dry_run, Arc::new(trash))
+    }
+
+    /// Runs a synchronization job. The snapshots are kept next to `left`, so later runs of the
+    /// same test see the earlier ones.
+    fn run_sync_job_with_trash(
+        left: &Path,
+        right: &Path,
+        mode: SyncMode,
+        dry_run: bool,
+        trash: Arc<DirectoryTrash>,
+    ) -> SyncReport {
+        let jobs_state = Arc::new(Mutex::new(JobsState::new()));
+        let snapshots_path = left.parent().unwrap().join(".sync_snapshots");
+        let job_id = sync_directories_impl(
+            left.to_string_lossy().to_string(),
+            right.to_string_lossy().to_string(),
+            mode,
+            None,
+            Some(dry_run),
+            trash,
+            Arc::new(Mutex::new(SyncSnapshotsState::new_with_path(
+                snapshots_path,
+            ))),
+            jobs_state.clone(),
+        )
+        .unwrap();
+        let info = wait_for_job(&jobs_state, job_id);
+        assert_eq!(info.status, JobStatus::Completed, "{:?}", info.error);
+        serde_json::from_value(info.result.unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn compare_directories_by_size_and_modified_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let (left, right) = create_trees(temp_dir.path());
+
+        let comparison = compare_directories(
+            left.to_string_lossy().to_string(),
+            right.to_string_lossy().to_string(),
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            kinds_of(&comparison),
+            vec![
+                ("conflict.txt".to_string(), DifferenceKind::ContentDiffers),
+                ("docs/newer.txt".to_string(), DifferenceKind::LeftNewer),
+                ("docs/older.txt".to_string(), DifferenceKind::RightNewer),
+                ("new_dir".to_string(), DifferenceKind::OnlyLeft),
+                ("only_left.txt".to_string(), DifferenceKind::OnlyLeft),
+                ("only_right.txt".to_string(), DifferenceKind::OnlyRight),
+            ]
+        );
+        assert_eq!(comparison.identical_files, 1);
+        assert!(
+            comparison.differences[3]
+                .left
+                .as_ref()
+                .unwrap()
+                .is_directory
+        );
+    }
+
+    #[tokio::test]
+    async fn compare_directories_by_hash_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let left = temp_dir.path().join("left");
+        let right = temp_dir.path().join("right");
+        // Same content with different times is equal, same size and time with other content not
+        write_file(&left.join("touched.txt"), "content", 10);
+        write_file(&right.join("touched.txt"), "content", 1000);
+        write_file(&left.join("edited.txt"), "abc", 1000);
+        write_file(&right.join("edited.txt"), "xyz", 1000);
+        write_file(&left.join("skip").join("x.txt"), "x", 10);
+
+        let comparison = compare_directories(
+            left.to_string_lossy().to_string(),
+            right.to_string_lossy().to_string(),
+            Some(CompareOptions {
+                method: CompareMethod::Hash,
+                excluded_patterns: vec!["skip".to_string()],
+                ..Default::default()
+            }),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            kinds_of(&comparison),
+            vec![("edited.txt".to_string(), DifferenceKind::ContentDiffers)]
+        );
+        assert_eq!(comparison.identical_files, 1);
+    }
+
+    #[tokio::test]
+    async fn compare_directories_rejects_nested_directories_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let inner = temp_dir.path().join("inner");
+        fs::create_dir(&inner).unwrap();
+
+        let result = compare_directories(
+            temp_dir.path().to_string_lossy().to_string(),
+            inner.to_string_lossy().to_string(),
+            None,
+        )
+        .await;
+        assert!(result.unwrap_err().contains("must not contain each other"));
+    }
+
+    #[test]
+    fn sync_dry_run_changes_nothing_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let (left, right) = create_trees(temp_dir.path());
+
+        let report = run_sync_job(&left, &right, SyncMode::Mirror, true);
+
+        assert!(report.dry_run);
+        assert!(report
+            .operations
+            .iter()
+            .all(|op| op.status == SyncOperationStatus::Planned));
+        let actions: Vec<_> = report
+            .operations
+            .iter()
+            .map(|op| (op.path.as_str(), op.action))
+            .collect();
+        assert_eq!(
+            actions,
+            vec![
+                ("conflict.txt", SyncAction::CopyToRight),
+                ("docs/newer.txt", SyncAction::CopyToRight),
+                ("docs/older.txt", SyncAction::CopyToRight),
+                ("new_dir", SyncAction::CopyToRight),
+                ("only_left.txt", SyncAction::CopyToRight),
+                ("only_right.txt", SyncAction::DeleteFromRight),
+            ]
+        );
+        assert!(right.join("only_right.txt").exists());
+        assert!(!right.join("only_left.txt").exists());
+    }
+
+    #[test]
+    fn sync_update_copies_new_and_newer_files_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let (left, right) = create_trees(temp_dir.path());
+
+        let report = run_sync_job(&left, &right, SyncMode::Update, false);
+
+        assert!(report
+            .operations
+            .iter()
+            .all(|op| op.status == SyncOperationStatus::Done));
+        assert_eq!(
+            fs::read_to_string(right.join("docs/newer.txt")).unwrap(),
+            "new"
+        );
+        assert_eq!(
+            fs::read_to_string(right.join("docs/older.txt")).unwrap(),
+            "newer"
+        );
+        assert_eq!(
+            fs::read_to_string(right.join("new_dir/a.txt")).unwrap(),
+            "a"
+        );
+        assert!(right.join("only_right.txt").exists());
+        assert_eq!(
+            fs::read_to_string(right.join("conflict.txt")).unwrap(),
+            "xyz"
+        );
+        let conflicts: Vec<_> = report.conflicts.iter().map(|c| c.path.as_str()).collect();
+        assert_eq!(conflicts, vec!["conflict.txt", "docs/older.txt"]);
+
+        // The modification times are kept, so the copies are equal afterwards
+        let report = run_sync_job(&left, &right, SyncMode::Update, true);
+        assert!(report.operations.is_empty());
+    }
+
+    #[test]
+    fn sync_two_way_reports_conflicts_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let (left, right) = create_trees(temp_dir.path());
+
+        let report = run_sync_job(&left, &right, SyncMode::TwoWay, false);
+
+        assert_eq!(
+            fs::read_to_string(right.join("docs/newer.txt")).unwrap(),
+            "new"
+        );
+        assert_eq!(
+            fs::read_to_string(left.join("docs/older.txt")).unwrap(),
+            "newer"
+        );
+        assert_eq!(
+            fs::read_to_string(left.join("only_right.txt")).unwrap(),
+            "right"
+        );
+        assert_eq!(
+            fs::read_to_string(right.join("only_left.txt")).unwrap(),
+            "left"
+        );
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(report.conflicts[0].path, "conflict.txt");
+        assert_eq!(report.conflicts[0].kind, DifferenceKind::ContentDiffers);
+        assert_eq!(
+            fs::read_to_string(left.join("conflict.txt")).unwrap(),
+            "abcd"
+        );
+        assert_eq!(
+            fs::read_to_string(right.join("conflict.txt")).unwrap(),
+            "xyz"
+        );
+    }
+
+    #[test]
+    fn sync_two_way_newer_side_wins_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let left = temp_dir.path().join("left");
+        let right = temp_dir.path().join("right");
+        // Without an earlier run nothing tells which side edited the file, the newer edit wins
+        write_file(&left.join("notes.txt"), "left edit", 100);
+        write_file(&right.join("notes.txt"), "right edit", 10);
+
+        let report = run_sync_job(&left, &right, SyncMode::TwoWay, false);
+
+        assert!(report.conflicts.is_empty());
+        assert_eq!(
+            fs::read_to_string(left.join("notes.txt")).unwrap(),
+            "right edit"
+        );
+    }
+
+    #[test]
+    fn sync_two_way_reports_files_edited_on_both_sides_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let left = temp_dir.path().join("left");
+        let right = temp_dir.path().join("right");
+        write_file(&left.join("notes.txt"), "first", 1000);
+        write_file(&left.join("todo.txt"), "first", 1000);
+        fs::create_dir(&right).unwrap();
+        let report = run_sync_job(&left, &right, SyncMode::TwoWay, false);
+        assert!(report.conflicts.is_empty());
+
+        // Both sides edit the notes, only the right side edits the todo list. Its edit is older
+        // than the copy on the left, still the right side wins because only it changed.
+        write_file(&left.join("notes.txt"), "left edit", 100);
+        write_file(&right.join("notes.txt"), "right edit", 10);
+        write_file(&right.join("todo.txt"), "right edit", 1100);
+
+        let report = run_sync_job(&left, &right, SyncMode::TwoWay, false);
+
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(report.conflicts[0].path, "notes.txt");
+        assert_eq!(
+            fs::read_to_string(left.join("notes.txt")).unwrap(),
+            "left edit"
+        );
+        assert_eq!(
+            fs::read_to_string(right.join("notes.txt")).unwrap(),
+            "right edit"
+        );
+        assert_eq!(
+            fs::read_to_string(left.join("todo.txt")).unwrap(),
+            "right edit"
+        );
+
+        // The conflict is kept until it is resolved
+        let report = run_sync_job(&left, &right, SyncMode::TwoWay, true);
+        assert_eq!(report.conflicts.len(), 1);
+        assert!(report.operations.is_empty());
+    }
+
+    #[test]
+    fn sync_mirror_makes_right_identical_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let (left, right) = create_trees(temp_dir.path());
+        // A directory on the right where the left has a file
+        fs::create_dir_all(right.join("kind").join("sub")).unwrap();
+        write_file(&left.join("kind"), "file now", 10);
+
+        let report = run_sync_job(&left, &right, SyncMode::Mirror, false);
+
+        assert!(report.conflicts.is_empty());
+        assert!(report
+            .operations
+            .iter()
+            .all(|op| op.status == SyncOperationStatus::Done));
+        assert!(!right.join("only_right.txt").exists());
+        assert_eq!(fs::read_to_string(right.join("kind")).unwrap(), "file now");
+        assert_eq!(
+            fs::read_to_string(right.join("conflict.txt")).unwrap(),
+            "abcd"
+        );
+
+        let comparison = compare_trees(
+            &left.to_string_lossy(),
+            &right.to_string_lossy(),
+            &CompareOptions::default(),
+            None,
+        )
+        .ok()
+        .unwrap();
+        assert!(comparison.differences.is_empty());
+    }
+
+    #[test]
+    fn sync_moves_entries_replaced_by_another_type_to_the_trash_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let left = temp_dir.path().join("left");
+        let right = temp_dir.path().join("right");
+        write_file(&left.join("photos"), "a file now", 10);
+        write_file(&right.join("photos").join("beach.jpg"), "beach", 1000);
+        let trash = Arc::new(DirectoryTrash::new(&temp_dir.path().join(".trash")));
+
+        let report = run_sync_job_with_trash(&left, &right, SyncMode::Mirror, false, trash.clone());
+
+        assert_eq!(report.operations[0].status, SyncOperationStatus::Done);
+        assert_eq!(
+            fs::read_to_string(right.join("photos")).unwrap(),
+            "a file now"
+        );
+
+        // The replaced directory can be recovered from the trash
+        let items = trash.list().unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].original_path(), right.join("photos"));
+        fs::remove_file(right.join("photos")).unwrap();
+        trash.restore(items[0].clone()).unwrap();
+        assert_eq!(
+            fs::read_to_string(right.join("photos").join("beach.jpg")).unwrap(),
+            "beach"
+        );
+    }
+}
@@ -790,11 +790,11 @@ mod tests_template_commands {
             .expect("Failed to create test file in template");
 
         // Update template paths in state
-        let meta_state = state.lock().unwrap();
-        meta_state
+        state
+            .lock()
+            .unwrap()
             .update_template_paths()
             .expect("Failed to update template paths");
-        drop(meta_state);
 
         // Verify the template exists
         assert!(
@@ -0,0 +1,660 @@
+use crate::commands::file_system_operation_commands::generate_unique_path;
+use crate::error_handling::{Error, ErrorCode};
+use crate::{log_error, log_info};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use trash::{TrashItem, TrashItemMetadata};
+
+/// What to do when the original location of a trashed item is occupied.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub enum RestoreConflictStrategy {
+    /// Leave the item in the trash.
+    Skip,
+    /// Restore the item next to the existing one as "name (1).ext".
+    Rename,
+    /// Move the existing item to the trash and restore the trashed one in its place.
+    Overwrite,
+}
+
+/// An item in the trash as shown in the frontend.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TrashEntry {
+    /// Platform specific identifier, on Linux the path of the `.trashinfo` file.
+    pub id: String,
+    pub name: String,
+    pub original_path: String,
+    pub deleted_at: i64, // as seconds since epoch
+    pub is_directory: bool,
+    pub size_in_bytes: Option<u64>,
+    pub entry_count: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub enum RestoreStatus {
+    Restored,
+    Skipped,
+    Failed,
+}
+
+/// Outcome of restoring a single trash item.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RestoreResult {
+    pub id: String,
+    pub original_path: String,
+    pub restored_path: Option<String>,
+    pub status: RestoreStatus,
+    pub error: Option<String>,
+}
+
+#[cfg(not(target_os = "macos"))]
+mod platform {
+    use trash::os_limited;
+    use trash::{TrashItem, TrashItemMetadata};
+
+    pub fn list() -> Result<Vec<TrashItem>, trash::Error> {
+        os_limited::list()
+    }
+
+    pub fn metadata(item: &TrashItem) -> Result<TrashItemMetadata, trash::Error> {
+        os_limited::metadata(item)
+    }
+
+    pub fn restore(item: TrashItem) -> Result<(), trash::Error> {
+        os_limited::restore_all(vec![item])
+    }
+
+    pub fn purge(items: Vec<TrashItem>) -> Result<(), trash::Error> {
+        os_limited::purge_all(items)
+    }
+}
+
+/// The macOS trash can not be read by third party apps, so only `move_to_trash` is supported there.
+#[cfg(target_os = "macos")]
+mod platform {
+    use trash::{TrashItem, TrashItemMetadata};
+
+    fn unsupported() -> trash::Error {
+        trash::Error::Unknown {
+            description: "Browsing the trash is not supported on macOS".to_string(),
+        }
+    }
+
+    pub fn list() -> Result<Vec<TrashItem>, trash::Error> {
+        Err(unsupported())
+    }
+
+    pub fn metadata(_item: &TrashItem) -> Result<TrashItemMetadata, trash::Error> {
+        Err(unsupported())
+    }
+
+    pub fn restore(_item: TrashItem) -> Result<(), trash::Error> {
+        Err(unsupported())
+    }
+
+    pub fn purge(_items: Vec<TrashItem>) -> Result<(), trash::Error> {
+        Err(unsupported())
+    }
+}
+
+/// Where items are moved to, listed, restored and purged. The commands use the trash of the
+/// system, tests use a [`DirectoryTrash`] so they don't touch the trash of the user.
+pub(crate) trait TrashBackend: Send + Sync {
+    fn list(&self) -> Result<Vec<TrashItem>, trash::Error>;
+
+    fn metadata(&self, item: &TrashItem) -> Result<TrashItemMetadata, trash::Error>;
+
+    /// Moves a file or directory to the trash.
+    fn delete(&self, path: &Path) -> Result<(), trash::Error>;
+
+    /// Moves an item back to its original path.
+    fn restore(&self, item: TrashItem) -> Result<(), trash::Error>;
+
+    fn purge(&self, items: Vec<TrashItem>) -> Result<(), trash::Error>;
+}
+
+/// The trash of the operating system.
+pub(crate) struct SystemTrash;
+
+impl TrashBackend for SystemTrash {
+    fn list(&self) -> Result<Vec<TrashItem>, trash::Error> {
+        platform::list()
+    }
+
+    fn metadata(&self, item: &TrashItem) -> Result<TrashItemMetadata, trash::Error> {
+        platform::metadata(item)
+    }
+
+    fn delete(&self, path: &Path) -> Result<(), trash::Error> {
+        trash::delete(path)
+    }
+
+    fn restore(&self, item: TrashItem) -> Result<(), trash::Error> {
+        platform::restore(item)
+    }
+
+    fn purge(&self, items: Vec<TrashItem>) -> Result<(), trash::Error> {
+        platform::purge(items)
+    }
+}
+
+/// A trash in a directory for tests. Items are moved into `root` under a number as their id.
+#[cfg(test)]
+pub(crate) struct DirectoryTrash {
+    root: std::path::PathBuf,
+    items: std::sync::Mutex<Vec<TrashItem>>,
+    next_id: std::sync::atomic::AtomicUsize,
+}
+
+#[cfg(test)]
+impl DirectoryTrash {
+    /// Creates the trash in `root`, which has to be on the same file system as the trashed items.
+    pub(crate) fn new(root: &Path) -> Self {
+        fs::create_dir_all(root).expect("Failed to create the trash directory");
+        Self {
+            root: root.to_path_buf(),
+            items: std::sync::Mutex::new(Vec::new()),
+            next_id: std::sync::atomic::AtomicUsize::new(0),
+        }
+    }
+
+    fn error(error: std::io::Error) -> trash::Error {
+        trash::Error::Unknown {
+            description: error.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+impl TrashBackend for DirectoryTrash {
+    fn list(&self) -> Result<Vec<TrashItem>, trash::Error> {
+        Ok(self.items.lock().unwrap().clone())
+    }
+
+    fn metadata(&self, item: &TrashItem) -> Result<TrashItemMetadata, trash::Error> {
+        let path = Path::new(&item.id);
+        let size = match path.is_dir() {
+            true => trash::TrashItemSize::Entries(fs::read_dir(path).map_err(Self::error)?.count()),
+            false => {
+                trash::TrashItemSize::Bytes(fs::symlink_metadata(path).map_err(Self::error)?.len())
+            }
+        };
+        Ok(TrashItemMetadata { size })
+    }
+
+    fn delete(&self, path: &Path) -> Result<(), trash::Error> {
+        let id = self
+            .next_id
+            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let trashed = self.root.join(id.to_string());
+        fs::rename(path, &trashed).map_err(Self::error)?;
+        self.items.lock().unwrap().push(TrashItem {
+            id: trashed.into_os_string(),
+            name: path.file_name().unwrap_or_default().to_os_string(),
+            original_parent: path.parent().map(Path::to_path_buf).unwrap_or_default(),
+            time_deleted: chrono::Utc::now().timestamp(),
+        });
+        Ok(())
+    }
+
+    fn restore(&self, item: TrashItem) -> Result<(), trash::Error> {
+        let original_path = item.original_path();
+        if fs::symlink_metadata(&original_path).is_ok() {
+            return Err(trash::Error::Unknown {
+                description: format!("{} already exists", original_path.display()),
+            });
+        }
+        fs::rename(&item.id, &original_path).map_err(Self::error)?;
+        self.items
+            .lock()
+            .unwrap()
+            .retain(|trashed| trashed.id != item.id);
+        Ok(())
+    }
+
+    fn purge(&self, items: Vec<TrashItem>) -> Result<(), trash::Error> {
+        for item in items {
+            let path = Path::new(&item.id);
+            match path.is_dir() {
+                true => fs::remove_dir_all(path),
+                false => fs::remove_file(path),
+            }
+            .map_err(Self::error)?;
+            self.items
+                .lock()
+                .unwrap()
+                .retain(|trashed| trashed.id != item.id);
+        }
+        Ok(())
+    }
+}
+
+fn ensure_supported() -> Result<(), String> {
+    if cfg!(target_os = "macos") {
+        log_error!("Browsing the trash is not supported on macOS");
+        return Err(Error::new(
+            ErrorCode::NotImplementedForOS,
+            "Browsing the trash is not supported on macOS".to_string(),
+        )
+        .to_json());
+    }
+    Ok(())
+}
+
+fn list_items(trash: &dyn TrashBackend) -> Result<Vec<TrashItem>, String> {
+    trash.list().map_err(|err| {
+        log_error!("Failed to read the trash: {}", err);
+        Error::new(
+            ErrorCode::InternalError,
+            format!("Failed to read the trash: {}", err),
+        )
+        .to_json()
+    })
+}
+
+/// Looks up the trash items for the given ids. Fails if any of the ids is unknown.
+fn find_items(ids: &[String], trash: &dyn TrashBackend) -> Result<Vec<TrashItem>, String> {
+    let items = list_items(trash)?;
+    ids.iter()
+        .map(|id| {
+            items
+                .iter()
+                .find(|item| item.id.to_string_lossy() == id.as_str())
+                .cloned()
+                .ok_or_else(|| {
+                    log_error!("Trash item not found: {}", id);
+                    Error::new(
+                        ErrorCode::ResourceNotFound,
+                        format!("Trash item not found: {}", id),
+                    )
+                    .to_json()
+                })
+        })
+        .collect()
+}
+
+fn to_trash_entry(item: &TrashItem, trash: &dyn TrashBackend) -> TrashEntry {
+    let size = trash.metadata(item).ok().map(|m| m.size);
+    TrashEntry {
+        id: item.id.to_string_lossy().to_string(),
+        name: item.name.to_string_lossy().to_string(),
+        original_path: item.original_path().to_string_lossy().to_string(),
+        deleted_at: item.time_deleted,
+        is_directory: size.map(|s| s.entries().is_some()).unwrap_or(false),
+        size_in_bytes: size.and_then(|s| s.size()),
+        entry_count: size.and_then(|s| s.entries()),
+    }
+}
+
+/// Restores a single item. Returns the path it was restored to, or `None` if it was skipped.
+fn restore_item(
+    item: TrashItem,
+    strategy: RestoreConflictStrategy,
+    trash: &dyn TrashBackend,
+) -> Result<Option<String>, String> {
+    let original_path = item.original_path();
+    let original = original_path.to_string_lossy().to_string();
+
+    if fs::symlink_metadata(&original_path).is_err() {
+        trash
+            .restore(item)
+            .map_err(|e| format!("Failed to restore '{}': {}", original, e))?;
+        return Ok(Some(original));
+    }
+
+    match strategy {
+        RestoreConflictStrategy::Skip => Ok(None),
+        RestoreConflictStrategy::Overwrite => {
+            trash.delete(&original_path).map_err(|e| {
+                format!("Failed to move existing '{}' to the trash: {}", original, e)
+            })?;
+            trash
+                .restore(item)
+                .map_err(|e| format!("Failed to restore '{}': {}", original, e))?;
+            Ok(Some(original))
+        }
+        RestoreConflictStrategy::Rename => {
+            // The trash can only restore to the original path, so the existing item is parked
+            // under a temporary name, the trashed item is restored and renamed, and the
+            // existing item is moved back.
+            let target = generate_unique_path(&original);
+            let parked = generate_unique_path(&format!("{}.restoring", original));
+
+            fs::rename(&original_path, &parked)
+                .map_err(|e| format!("Failed to move existing '{}' aside: {}", original, e))?;
+
+            if let Err(e) = trash.restore(item) {
+                let _ = fs::rename(&parked, &original_path);
+                return Err(format!("Failed to restore '{}': {}", original, e));
+            }
+            // If this fails the existing item stays parked, moving it back would replace the restored one
+            fs::rename(&original_path, &target).map_err(|e| {
+                format!(
+                    "Failed to rename restored item to '{}', the existing item was kept at '{}': {}",
+                    target, parked, e
+                )
+            })?;
+            fs::rename(&parked, &original_path).map_err(|e| {
+                format!(
+                    "Restored item but failed to move '{}' back to '{}': {}",
+                    parked, original, e
+                )
+            })?;
+            Ok(Some(target))
+        }
+    }
+}
+
+/// Lists all items in the trash, most recently deleted first.
+/// On Linux all freedesktop trash directories are read, including the ones on other mount points.
+///
+/// # Returns
+/// * `Ok(Vec<TrashEntry>)` - The trashed items with their original paths and deletion times.
+/// * `Err(String)` - If the trash could not be read or the OS is not supported (macOS).
+///
+/// # Example
+/// ```rust
+/// let entries = list_trash().await?;
+/// for entry in entries {
+///     println!("{} was deleted at {}", entry.original_path, entry.deleted_at);
+/// }
+/// ```
+#[tauri::command]
+pub async fn list_trash() -> Result<Vec<TrashEntry>, String> {
+    ensure_supported()?;
+    list_trash_impl(&SystemTrash)
+}
+
+pub fn list_trash_impl(trash: &dyn TrashBackend) -> Result<Vec<TrashEntry>, String> {
+    let mut entries: Vec<TrashEntry> = list_items(trash)?
+        .iter()
+        .map(|item| to_trash_entry(item, trash))
+        .collect();
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.deleted_at));
+    Ok(entries)
+}
+
+/// Restores the given trash items to their original location.
+///
+/// # Arguments
+/// * `ids` - The ids of the items as returned by `list_trash`.
+/// * `conflict_strategy` - What to do if the original location is occupied, defaults to `Skip`.
+///
+/// # Returns
+/// * `Ok(Vec<RestoreResult>)` - One result per id, failures of single items do not stop the others.
+/// * `Err(String)` - If an id is unknown or the OS is not supported (macOS).
+///
+/// # Example
+/// ```rust
+/// let results = restore_from_trash(vec![entry.id], Some(RestoreConflictStrategy::Rename)).await?;
+/// ```
+#[tauri::command]
+pub async fn restore_from_trash(
+    ids: Vec<String>,
+    conflict_strategy: Option<RestoreConflictStrategy>,
+) -> Result<Vec<RestoreResult>, String> {
+    ensure_supported()?;
+    restore_from_trash_impl(ids, conflict_strategy, &SystemTrash)
+}
+
+pub fn restore_from_trash_impl(
+    ids: Vec<String>,
+    conflict_strategy: Option<RestoreConflictStrategy>,
+    trash: &dyn TrashBackend,
+) -> Result<Vec<RestoreResult>, String> {
+    let strategy = conflict_strategy.unwrap_or(RestoreConflictStrategy::Skip);
+    let items = find_items(&ids, trash)?;
+
+    let mut results = Vec::with_capacity(items.len());
+    for item in items {
+        let id = item.id.to_string_lossy().to_string();
+        let original_path = item.original_path().to_string_lossy().to_string();
+
+        let result = match restore_item(item, strategy, trash) {
+            Ok(Some(restored_path)) => {
+                log_info!("Restored '{}' to '{}'", original_path, restored_path);
+                RestoreResult {
+                    id,
+                    original_path,
+                    restored_path: Some(restored_path),
+                    status: RestoreStatus::Restored,
+                    error: None,
+                }
+            }
+            Ok(None) => RestoreResult {
+                id,
+                original_path,
+                restored_path: None,
+                status: RestoreStatus::Skipped,
+                error: None,
+            },
+            Err(err) => {
+                log_error!("{}", err);
+                RestoreResult {
+                    id,
+                    original_path,
+                    restored_path: None,
+                    status: RestoreStatus::Failed,
+                    error: Some(err),
+                }
+            }
+        };
+        results.push(result);
+    }
+    Ok(results)
+}
+
+/// Permanently deletes the given items from the trash.
+///
+/// # Arguments
+/// * `ids` - The ids of the items as returned by `list_trash`.
+///
+/// # Returns
+/// * `Ok(usize)` - The number of purged items.
+/// * `Err(String)` - If an id is unknown, purging failed or the OS is not supported (macOS).
+///
+/// # Example
+/// ```rust
+/// let purged = purge_from_trash(vec![entry.id]).await?;
+/// ```
+#[tauri::command]
+pub async fn purge_from_trash(ids: Vec<String>) -> Result<usize, String> {
+    ensure_supported()?;
+    purge_from_trash_impl(ids, &SystemTrash)
+}
+
+pub fn purge_from_trash_impl(ids: Vec<String>, trash: &dyn TrashBackend) -> Result<usize, String> {
+    let items = find_items(&ids, trash)?;
+    purge_items(items, trash)
+}
+
+/// Permanently deletes everything in the trash.
+///
+/// # Returns
+/// * `Ok(usize)` - The number of purged items.
+/// * `Err(String)` - If purging failed or the OS is not supported (macOS).
+///
+/// # Example
+/// ```rust
+/// let purged = empty_trash().await?;
+/// println!("Removed {} items", purged);
+/// ```
+#[tauri::command]
+pub async fn empty_trash() -> Result<usize, String> {
+    ensure_supported()?;
+    empty_trash_impl(&SystemTrash)
+}
+
+pub fn empty_trash_impl(trash: &dyn TrashBackend) -> Result<usize, String> {
+    let items = list_items(trash)?;
+    purge_items(items, trash)
+}
+
+fn purge_items(items: Vec<TrashItem>, trash: &dyn TrashBackend) -> Result<usize, String> {
+    let count = items.len();
+    if count == 0 {
+        return Ok(0);
+    }
+    trash.purge(items).map_err(|err| {
+        log_error!("Failed to purge trash items: {}", err);
+        Error::new(
+            ErrorCode::InternalError,
+            format!("Failed to purge trash items: {}", err),
+        )
+        .to_json()
+    })?;
+    log_info!("Purged {} items from the trash", count);
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests_trash_commands {
+    use super::*;
+    use tempfile::{tempdir, TempDir};
+
+    /// A temporary directory with a trash in its `.trash` subdirectory.
+    fn test_trash() -> (TempDir, DirectoryTrash) {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let trash = DirectoryTrash::new(&temp_dir.path().join(".trash"));
+        (temp_dir, trash)
+    }
+
+    fn trash_file(path: &Path, content: &str, trash: &dyn TrashBackend) -> TrashEntry {
+        fs::write(path, content).unwrap();
+        trash.delete(path).expect("Failed to move file to trash");
+
+        let original = path.to_string_lossy().to_string();
+        list_trash_impl(trash)
+            .expect("Failed to list trash")
+            .into_iter()
+            .find(|entry| entry.original_path == original)
+            .expect("Trashed file is not listed")
+    }
+
+    #[test]
+    fn list_and_restore_trash_item_test() {
+        let (temp_dir, trash) = test_trash();
+        let path = temp_dir.path().join("restore_from_trash_test.txt");
+
+        let entry = trash_file(&path, "restore me", &trash);
+        assert_eq!(entry.name, "restore_from_trash_test.txt");
+        assert!(!entry.is_directory);
+        assert_eq!(entry.size_in_bytes, Some(10));
+        assert!(!path.exists());
+
+        let results = restore_from_trash_impl(vec![entry.id], None, &trash).unwrap();
+        assert_eq!(results[0].status, RestoreStatus::Restored);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "restore me");
+        assert!(list_trash_impl(&trash).unwrap().is_empty());
+    }
+
+    #[test]
+    fn restore_with_conflict_strategies_test() {
+        let (temp_dir, trash) = test_trash();
+        let path = temp_dir.path().join("conflict_test.txt");
+
+        let entry = trash_file(&path, "trashed", &trash);
+        fs::write(&path, "new file").unwrap();
+
+        let results = restore_from_trash_impl(
+            vec![entry.id.clone()],
+            Some(RestoreConflictStrategy::Skip),
+            &trash,
+        )
+        .unwrap();
+        assert_eq!(results[0].status, RestoreStatus::Skipped);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new file");
+
+        let results = restore_from_trash_impl(
+            vec![entry.id],
+            Some(RestoreConflictStrategy::Rename),
+            &trash,
+        )
+        .unwrap();
+        assert_eq!(results[0].status, RestoreStatus::Restored);
+        let renamed = temp_dir.path().join("conflict_test (1).txt");
+        assert_eq!(
+            results[0].restored_path.as_deref(),
+            Some(renamed.to_string_lossy().as_ref())
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new file");
+        assert_eq!(fs::read_to_string(&renamed).unwrap(), "trashed");
+    }
+
+    #[test]
+    fn restore_with_overwrite_trashes_the_existing_item_test() {
+        let (temp_dir, trash) = test_trash();
+        let path = temp_dir.path().join("overwrite_test.txt");
+
+        let entry = trash_file(&path, "trashed", &trash);
+        fs::write(&path, "new file").unwrap();
+
+        let results = restore_from_trash_impl(
+            vec![entry.id],
+            Some(RestoreConflictStrategy::Overwrite),
+            &trash,
+        )
+        .unwrap();
+        assert_eq!(results[0].status, RestoreStatus::Restored);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "trashed");
+
+        // The replaced file is in the trash now
+        let entries = list_trash_impl(&trash).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].size_in_bytes, Some(8));
+    }
+
+    #[test]
+    fn purge_trash_item_test() {
+        let (temp_dir, trash) = test_trash();
+        let path = temp_dir.path().join("purge_from_trash_test.txt");
+
+        let entry = trash_file(&path, "purge me", &trash);
+        assert_eq!(
+            purge_from_trash_impl(vec![entry.id.clone()], &trash).unwrap(),
+            1
+        );
+
+        let still_listed = list_trash_impl(&trash)
+            .unwrap()
+            .into_iter()
+            .any(|e| e.id == entry.id);
+        assert!(!still_listed);
+        assert!(!Path::new(&entry.id).exists());
+    }
+
+    #[test]
+    fn empty_trash_test() {
+        let (temp_dir, trash) = test_trash();
+        trash_file(&temp_dir.path().join("a.txt"), "a", &trash);
+        trash_file(&temp_dir.path().join("b.txt"), "b", &trash);
+
+        assert_eq!(empty_trash_impl(&trash).unwrap(), 2);
+        assert_eq!(empty_trash_impl(&trash).unwrap(), 0);
+    }
+
+    #[test]
+    fn unknown_trash_item_test() {
+        let (_temp_dir, trash) = test_trash();
+        let result = restore_from_trash_impl(
+            vec!["/not/a/trash/item.trashinfo".to_string()],
+            None,
+            &trash,
+        );
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("ResourceNotFound"));
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    #[tokio::test]
+    #[ignore = "moves a file to the trash of the user"]
+    async fn system_trash_round_trip_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let path = temp_dir.path().join("system_trash_round_trip_test.txt");
+        let entry = trash_file(&path, "restore me", &SystemTrash);
+
+        let results = restore_from_trash(vec![entry.id], None).await.unwrap();
+        assert_eq!(results[0].status, RestoreStatus::Restored);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "restore me");
+    }
+}
@@ -45,3 +45,15 @@ pub static SETTINGS_CONFIG_FILE_NAME: &str = "settings.json";
 pub static TEMPLATES_ABS_PATH_FOLDER: LazyLock<PathBuf> =
     LazyLock::new(|| CONFIG_PATH.join(TEMPLATES_FOLDER));
 pub static TEMPLATES_FOLDER: &str = "templates";
+
+pub static CONNECTIONS_CONFIG_ABS_PATH: LazyLock<PathBuf> =
+    LazyLock::new(|| CONFIG_PATH.join(CONNECTIONS_CONFIG_FILE_NAME));
+pub static CONNECTIONS_CONFIG_FILE_NAME: &str = "connections.json";
+
+pub static SECRET_STORE_ABS_PATH: LazyLock<PathBuf> =
+    LazyLock::new(|| CONFIG_PATH.join(SECRET_STORE_FILE_NAME));
+pub static SECRET_STORE_FILE_NAME: &str = "secrets.enc.json";
+
+pub static SYNC_SNAPSHOTS_ABS_PATH: LazyLock<PathBuf> =
+    LazyLock::new(|| CONFIG_PATH.join(SYNC_SNAPSHOTS_FOLDER));
+pub static SYNC_SNAPSHOTS_FOLDER: &str = "sync_snapshots";
@@ -47,6 +47,9 @@ pub struct Error {
     code: u16,
     message_from_code: ErrorCode,
     custom_message: String,
+    /// Optional structured information about the error, e.g. the entries which were rejected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    details: Option<serde_json::Value>,
 }
 impl Error {
     pub fn new(code: ErrorCode, message: String) -> Self {
@@ -54,8 +57,13 @@ impl Error {
             code: code.get_code_as_u16(),
             message_from_code: code,
             custom_message: message,
+            details: None,
         }
     }
+    pub fn with_details(mut self, details: serde_json::Value) -> Self {
+        self.details = Some(details);
+        self
+    }
     pub fn to_json(&self) -> String {
         serde_json::to_string(self).unwrap_or_else(|_| {
             r#"{"code":500,"message_from_code":"InternalError","custom_message":"Failed to serialize error"}"#.to_string()
@@ -1,4 +1,6 @@
+use std::fs;
 use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
 
 pub fn _get_mount_point(path: String) -> Option<String> {
     let path = Path::new(&path);
@@ -10,3 +12,37 @@ pub fn _get_mount_point(path: String) -> Option<String> {
     mount_point_path.push("\\");
     Some(mount_point_path.to_string_lossy().into_owned())
 }
+
+/// Returns the modification time as milliseconds since epoch, 0 if it is not available.
+pub fn modified_millis(metadata: &fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Returns whether a path contains one of the excluded patterns. Both are compared with `/`
+/// separators, so patterns written for Windows work everywhere.
+pub fn is_excluded(path: &str, excluded_patterns: &[String]) -> bool {
+    let normalized_path = path.replace('\\', "/");
+    excluded_patterns
+        .iter()
+        .any(|pattern| normalized_path.contains(&pattern.replace('\\', "/")))
+}
+
+#[cfg(test)]
+mod tests_fs_utils {
+    use super::*;
+
+    #[test]
+    fn is_excluded_ignores_separator_style_test() {
+        let patterns = vec!["node_modules".to_string(), "build\\cache".to_string()];
+
+        assert!(is_excluded("app/node_modules/x.js", &patterns));
+        assert!(is_excluded("C:\\app\\build\\cache\\a.bin", &patterns));
+        assert!(is_excluded("app/build/cache", &patterns));
+        assert!(!is_excluded("app/build/out.bin", &patterns));
+    }
+}
@@ -1,17 +1,26 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+mod archive;
 mod commands;
 pub mod constants;
 mod error_handling;
 mod filesystem;
 pub mod models;
+mod remote;
 mod search_engine;
 mod state;
 
 use crate::commands::{
     command_exec_commands, file_system_operation_commands, hash_commands, meta_data_commands,
-    search_engine_commands, settings_commands, template_commands, volume_operations_commands, sftp_file_system_operation_commands, preview_commands, permission_commands
+    search_engine_commands, settings_commands, template_commands, volume_operations_commands, sftp_file_system_operation_commands, preview_commands, permission_commands, batch_rename_commands,
+    delete_commands, job_commands, trash_commands,
+    dir_size_commands, listing_commands, archive_commands, duplicate_commands,
+    sync_commands, file_properties_commands, connection_commands, sftp_transfer_commands,
+    remote_edit_commands, remote_file_system_commands, remote_transfer_commands
 };
+use crate::state::archive_listing_data::ArchiveListingState;
+use crate::state::sftp_session_data::SftpSessionState;
+use std::sync::{Arc, Mutex};
 use tauri::ipc::Invoke;
 use tauri::Manager;
 
@@ -27,7 +36,39 @@ fn all_commands() -> fn(Invoke) -> bool {
         file_system_operation_commands::move_to_trash,
         file_system_operation_commands::copy_file_or_dir,
         file_system_operation_commands::zip,
+        file_system_operation_commands::zip_with_progress,
         file_system_operation_commands::unzip,
+        // Archive commands
+        archive_commands::extract_archive_entries,
+        // Directory listing commands
+        listing_commands::list_directory_page,
+        listing_commands::release_directory_listing,
+        listing_commands::get_child_counts,
+        // Batch rename commands
+        batch_rename_commands::batch_rename,
+        // Delete commands
+        delete_commands::request_delete_confirmation,
+        delete_commands::delete_permanently,
+        // Trash commands
+        trash_commands::list_trash,
+        trash_commands::restore_from_trash,
+        trash_commands::purge_from_trash,
+        trash_commands::empty_trash,
+        // Directory size commands
+        dir_size_commands::calculate_directory_sizes,
+        dir_size_commands::get_disk_usage,
+        dir_size_commands::clear_directory_size_cache,
+        // Duplicate commands
+        duplicate_commands::find_duplicates,
+        duplicate_commands::resolve_duplicates,
+        // Directory comparison commands
+        sync_commands::compare_directories,
+        sync_commands::sync_directories,
+        // Job commands
+        job_commands::get_job_status,
+        job_commands::list_jobs,
+        job_commands::cancel_job,
+        job_commands::clear_finished_jobs,
         // Command execution commands
         command_exec_commands::execute_command,
         command_exec_commands::execute_command_improved,
@@ -48,6 +89,8 @@ fn all_commands() -> fn(Invoke) -> bool {
         hash_commands::gen_hash_and_return_string,
         hash_commands::gen_hash_and_save_to_file,
         hash_commands::compare_file_or_dir_with_hash,
+        hash_commands::verify_checksum_manifest,
+        hash_commands::hash_files,
         // Template commands
         template_commands::get_template_paths_as_json,
         template_commands::add_template,
@@ -72,6 +115,14 @@ fn all_commands() -> fn(Invoke) -> bool {
         preview_commands::build_preview,
 
         //sftp commands
+        sftp_file_system_operation_commands::open_sftp_session,
+        sftp_file_system_operation_commands::close_sftp_session,
+        sftp_file_system_operation_commands::list_sftp_sessions,
+        sftp_file_system_operation_commands::trust_sftp_host_key,
+        sftp_file_system_operation_commands::get_sftp_auth_prompts,
+        sftp_file_system_operation_commands::answer_sftp_auth_prompt,
+        sftp_file_system_operation_commands::list_ssh_config_hosts,
+        sftp_file_system_operation_commands::resolve_ssh_config_host,
         sftp_file_system_operation_commands::load_dir,
         sftp_file_system_operation_commands::open_file_sftp,
         sftp_file_system_operation_commands::create_file_sftp,
@@ -87,10 +138,50 @@ fn all_commands() -> fn(Invoke) -> bool {
         sftp_file_system_operation_commands::build_preview_sftp,
         sftp_file_system_operation_commands::download_and_open_sftp_file,
         sftp_file_system_operation_commands::cleanup_sftp_temp_files,
+        sftp_transfer_commands::upload_to_sftp,
+        sftp_transfer_commands::download_from_sftp,
+        remote_edit_commands::edit_sftp_file,
+        remote_edit_commands::list_remote_edits,
+        remote_edit_commands::resolve_remote_edit_conflict,
+        remote_edit_commands::stop_remote_edit,
+
+        // Remote session commands
+        remote_file_system_commands::open_remote_session,
+        remote_file_system_commands::close_remote_session,
+        remote_file_system_commands::list_remote_sessions,
+        remote_file_system_commands::load_remote_dir,
+        remote_file_system_commands::build_remote_preview,
+        remote_file_system_commands::rename_remote_path,
+        remote_file_system_commands::delete_remote_path,
+        remote_file_system_commands::create_remote_directory,
+        remote_file_system_commands::copy_remote_path,
+        remote_file_system_commands::presign_remote_url,
+        remote_transfer_commands::upload_to_remote,
+        remote_transfer_commands::download_from_remote,
+
+        // Saved connection commands
+        connection_commands::get_secret_store_status,
+        connection_commands::unlock_secret_store,
+        connection_commands::lock_secret_store,
+        connection_commands::change_secret_store_passphrase,
+        connection_commands::list_remote_connections,
+        connection_commands::add_remote_connection,
+        connection_commands::update_remote_connection,
+        connection_commands::remove_remote_connection,
+        connection_commands::test_remote_connection,
+        connection_commands::open_remote_connection,
 
         // Permission commands
         permission_commands::request_full_disk_access,
         permission_commands::check_directory_access,
+        permission_commands::change_permissions,
+        permission_commands::change_owner,
+        permission_commands::set_timestamps,
+
+        // File properties commands
+        file_properties_commands::get_detailed_metadata,
+        file_properties_commands::set_extended_attribute,
+        file_properties_commands::remove_extended_attribute,
     ]
 }
 
@@ -107,9 +198,11 @@ async fn main() {
                 let _ = window.set_focus();
             }
             
-            // Clean up old SFTP temporary files on startup
-            tokio::spawn(async {
-                if let Err(e) = commands::sftp_file_system_operation_commands::cleanup_sftp_temp_files() {
+            // Clean up SFTP temporary files of the last run, no session is open yet
+            let sftp_state = app.state::<Arc<Mutex<SftpSessionState>>>().inner().clone();
+            tokio::spawn(async move {
+                let temp_dir = commands::sftp_file_system_operation_commands::sftp_temp_dir();
+                if let Err(e) = commands::sftp_file_system_operation_commands::cleanup_sftp_temp_files_impl(sftp_state, &temp_dir) {
                     eprintln!("Failed to cleanup SFTP temp files: {}", e);
                 }
             });
@@ -121,9 +214,19 @@ async fn main() {
 
     log_info!("Starting Tauri application...");
 
-    app.run(tauri::generate_context!()).expect({
+    let app = app.build(tauri::generate_context!()).unwrap_or_else(|e| {
         let error_msg = "error while running tauri application";
         log_critical!(error_msg);
-        &error_msg.to_string()
+        panic!("{}: {}", error_msg, e)
+    });
+
+    app.run(|app_handle, event| {
+        if let tauri::RunEvent::Exit = event {
+            // Media extracted from archives for previews is only needed while the app runs
+            let archive_listing_state = app_handle.state::<Arc<Mutex<ArchiveListingState>>>();
+            if let Ok(mut archive_listings) = archive_listing_state.lock() {
+                archive_listings.close();
+            };
+        }
     });
 }
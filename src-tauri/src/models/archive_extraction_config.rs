@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+
+/// Limits which protect the extraction of archives against archive bombs.
+/// Archives exceeding a limit are rejected before anything is written.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct ArchiveExtractionConfig {
+    /// Maximum total size of all extracted files in bytes.
+    pub max_total_size: u64,
+    /// Maximum number of entries in an archive.
+    pub max_entry_count: usize,
+    /// Maximum ratio between the uncompressed and the compressed size, for single entries and
+    /// for the whole archive.
+    pub max_compression_ratio: u64,
+    /// Entries and archives smaller than this are never rejected because of their ratio,
+    /// small files of zeros easily reach ratios of several hundred.
+    pub compression_ratio_min_size: u64,
+}
+
+impl Default for ArchiveExtractionConfig {
+    fn default() -> Self {
+        Self {
+            max_total_size: 32 * 1024 * 1024 * 1024, // 32 GiB
+            max_entry_count: 200_000,
+            max_compression_ratio: 250,
+            compression_ratio_min_size: 16 * 1024 * 1024, // 16 MiB
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use crate::models::search_engine_config::SearchEngineConfig;
 use crate::models::logging_config::LoggingConfig;
+use crate::models::archive_extraction_config::ArchiveExtractionConfig;
 
 use serde::{Deserialize, Serialize};
 use crate::commands::hash_commands::ChecksumMethod;
@@ -12,6 +13,9 @@ pub struct BackendSettings {
     pub logging_config: LoggingConfig,
     /// Default hash algorithm for file checksums
     pub default_checksum_hash: ChecksumMethod,
+    /// Limits for extracting archives, missing in settings files of older versions
+    #[serde(default)]
+    pub archive_extraction_config: ArchiveExtractionConfig,
 }
 
 impl Default for BackendSettings {
@@ -20,6 +24,7 @@ impl Default for BackendSettings {
             search_engine_config: SearchEngineConfig::default(),
             logging_config: LoggingConfig::default(),
             default_checksum_hash: ChecksumMethod::SHA256,
+            archive_extraction_config: ArchiveExtractionConfig::default(),
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,65 @@
+use serde::{Deserialize, Serialize};
+
+/// Everything known about a single file or directory. Reading it is much slower than the
+/// `File` and `Directory` models of a listing, so it is only loaded for one path on demand.
+///
+/// Fields the platform does not provide are `None`, lists it does not support are empty.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DetailedMetadata {
+    pub name: String,
+    pub path: String,
+    pub is_directory: bool,
+    pub is_symlink: bool,
+    pub symlink_target: Option<String>,
+    pub size_in_bytes: u64,
+    pub access_rights_as_string: String,
+    pub access_rights_as_number: u32,
+    pub created: String,
+    pub last_modified: String,
+    pub accessed: String,
+    pub inode: Option<u64>,
+    pub hard_link_count: Option<u64>,
+    pub device_id: Option<u64>,
+    pub owner_id: Option<u32>,
+    pub owner_name: Option<String>,
+    pub group_id: Option<u32>,
+    pub group_name: Option<String>,
+    /// Flags like `immutable` or `hidden`, from `chattr` on Linux, `chflags` on macOS and the
+    /// file attributes on Windows.
+    pub flags: Vec<String>,
+    pub extended_attributes: Vec<ExtendedAttribute>,
+    /// The POSIX access ACL, `None` if the entry only has the permissions of the mode.
+    pub acl: Option<Vec<AclEntry>>,
+    /// The POSIX default ACL of a directory, inherited by new entries inside of it.
+    pub default_acl: Option<Vec<AclEntry>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ExtendedAttribute {
+    pub name: String,
+    pub size: usize,
+    pub value_base64: String,
+    /// The value as text, `None` if it is not valid UTF-8.
+    pub value_text: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum AclTag {
+    UserObj,
+    User,
+    GroupObj,
+    Group,
+    Mask,
+    Other,
+}
+
+/// One entry of a POSIX ACL, like `user:alice:rw-` in the output of `getfacl`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AclEntry {
+    pub tag: AclTag,
+    /// The user or group id of `User` and `Group` entries.
+    pub qualifier: Option<u32>,
+    pub qualifier_name: Option<String>,
+    /// The permissions as `rwx` string, e.g. `r-x`.
+    pub permissions: String,
+}
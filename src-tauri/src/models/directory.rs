@@ -13,4 +13,12 @@ pub struct Directory {
     pub created: String,
     pub last_modified: String,
     pub accessed: String,
+    /// The owner and group ids, only set for remote entries where they come with the listing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner_id: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_id: Option<u32>,
+    /// Where a symlink points to, only set for remote entries.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symlink_target: Option<String>,
 }
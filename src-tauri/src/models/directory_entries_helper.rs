@@ -51,8 +51,7 @@ pub fn get_access_permission_number(permissions: Permissions, _is_directory: boo
     }
     #[cfg(unix)]
     {
-        let mode = permissions.mode();
-        mode
+        permissions.mode()
     }
 }
 
@@ -178,6 +177,25 @@ pub fn access_rights_to_string_unix(permissions: Permissions) -> String {
     result
 }
 
+/// Formats the permission bits of a Unix mode like `access_rights_to_string_unix` does, for
+/// entries which are not on the local file system, e.g. inside archives or on SFTP servers.
+///
+/// # Parameters
+/// - `mode`: The mode, file type bits are ignored.
+///
+/// # Returns
+/// The permissions like `rwxr-xr-x`.
+pub fn permission_string_from_mode(mode: u32) -> String {
+    let mut result = String::with_capacity(9);
+    for shift in [6, 3, 0] {
+        let bits = (mode >> shift) & 0o7;
+        result.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        result.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        result.push(if bits & 0o1 != 0 { 'x' } else { '-' });
+    }
+    result
+}
+
 /// This function formats a SystemTime object into a human-readable string.
 /// It converts the SystemTime into a DateTime<Utc> object and then formats it into a string.
 ///
@@ -15,6 +15,14 @@ pub struct File {
     pub created: String,
     pub last_modified: String,
     pub accessed: String,
+    /// The owner and group ids, only set for remote entries where they come with the listing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner_id: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_id: Option<u32>,
+    /// Where a symlink points to, only set for remote entries.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symlink_target: Option<String>,
 }
 
 impl File {
@@ -39,6 +47,9 @@ impl File {
             created: format_system_time(metadata.created()?),
             last_modified: format_system_time(metadata.modified()?),
             accessed: format_system_time(metadata.accessed()?),
+            owner_id: None,
+            group_id: None,
+            symlink_target: None,
         })
     }
 }
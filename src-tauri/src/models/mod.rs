@@ -10,16 +10,18 @@ pub use volume::VolumeInformation;
 mod directory_entries_helper;
 pub use directory_entries_helper::Entries;
 pub use directory_entries_helper::{
-    count_subdirectories, count_subfiles, format_system_time,
-    get_access_permission_number, get_access_permission_string,
+    count_subdirectories, count_subfiles, count_subfiles_and_subdirectories, format_system_time,
+    get_access_permission_number, get_access_permission_string, permission_string_from_mode,
 };
 
 pub mod logging_level;
 pub mod ranking_config;
 pub mod backend_settings;
 pub mod search_engine_config;
+pub mod archive_extraction_config;
+pub mod detailed_metadata;
+pub mod ssh_config;
+pub mod remote_connection;
 mod logging_config;
-mod sftp_directory;
-pub use sftp_directory::SFTPDirectory;
 
 pub use logging_level::LoggingLevel;
@@ -0,0 +1,119 @@
+use crate::models::ssh_config::SshJumpHost;
+use crate::remote::RemoteProtocol;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// How a saved connection logs in. Password, key passphrase and secret access key are kept in
+/// the secret store.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum RemoteAuthMethod {
+    Password,
+    PublicKey,
+    Agent,
+    KeyboardInteractive,
+    /// S3 only, the access key id is the `username` and the secret access key the secret.
+    AccessKey,
+}
+
+impl RemoteAuthMethod {
+    /// Whether the method can have a secret, a password, the passphrase of a key or a secret
+    /// access key.
+    pub fn uses_secret(&self) -> bool {
+        matches!(
+            self,
+            RemoteAuthMethod::Password | RemoteAuthMethod::PublicKey | RemoteAuthMethod::AccessKey
+        )
+    }
+
+    /// Whether a connection of the protocol can log in with the method.
+    pub fn supports(&self, protocol: RemoteProtocol) -> bool {
+        match protocol {
+            RemoteProtocol::Sftp => *self != RemoteAuthMethod::AccessKey,
+            RemoteProtocol::S3 | RemoteProtocol::S3Http => *self == RemoteAuthMethod::AccessKey,
+            _ => *self == RemoteAuthMethod::Password,
+        }
+    }
+}
+
+fn default_protocol() -> RemoteProtocol {
+    RemoteProtocol::Sftp
+}
+
+/// A saved connection as it is stored in `connections.json` and shown to the frontend. It never
+/// contains the secret, `has_secret` only tells whether the secret store holds one.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RemoteConnection {
+    pub id: String,
+    pub name: String,
+    /// Connections saved before other protocols were supported are SFTP connections.
+    #[serde(default = "default_protocol")]
+    pub protocol: RemoteProtocol,
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub auth_method: RemoteAuthMethod,
+    #[serde(default)]
+    pub private_key_path: Option<PathBuf>,
+    /// The directory opened after connecting, the login directory of the user if not set.
+    #[serde(default)]
+    pub default_path: Option<String>,
+    /// SFTP only.
+    #[serde(default)]
+    pub proxy_jump: Vec<SshJumpHost>,
+    /// WebDAV only, the path of the WebDAV root on the server.
+    #[serde(default)]
+    pub base_path: Option<String>,
+    /// S3 only, the region requests are signed for.
+    #[serde(default)]
+    pub region: Option<String>,
+    /// Accepts self-signed and otherwise invalid certificates for FTPS, WebDAVS and S3.
+    #[serde(default)]
+    pub accept_invalid_certificates: bool,
+    #[serde(default)]
+    pub has_secret: bool,
+}
+
+/// The fields of a connection the frontend sends when adding or editing one.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RemoteConnectionInput {
+    pub name: String,
+    #[serde(default = "default_protocol")]
+    pub protocol: RemoteProtocol,
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub auth_method: RemoteAuthMethod,
+    #[serde(default)]
+    pub private_key_path: Option<PathBuf>,
+    #[serde(default)]
+    pub default_path: Option<String>,
+    #[serde(default)]
+    pub proxy_jump: Vec<SshJumpHost>,
+    #[serde(default)]
+    pub base_path: Option<String>,
+    #[serde(default)]
+    pub region: Option<String>,
+    #[serde(default)]
+    pub accept_invalid_certificates: bool,
+}
+
+impl RemoteConnection {
+    pub fn from_input(id: String, input: RemoteConnectionInput) -> Self {
+        Self {
+            id,
+            name: input.name,
+            protocol: input.protocol,
+            host: input.host,
+            port: input.port,
+            username: input.username,
+            auth_method: input.auth_method,
+            private_key_path: input.private_key_path,
+            default_path: input.default_path,
+            proxy_jump: input.proxy_jump,
+            base_path: input.base_path,
+            region: input.region,
+            accept_invalid_certificates: input.accept_invalid_certificates,
+            has_secret: false,
+        }
+    }
+}
@@ -0,0 +1,538 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// How deep `Include` directives may nest, like OpenSSH this also stops include loops.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// How many jump hosts a chain may have, stops `ProxyJump` loops between aliases.
+const MAX_JUMP_HOSTS: usize = 16;
+
+/// A server reached on the way to the target, parsed from a `ProxyJump` entry and resolved
+/// against the config like OpenSSH does, so a jump host can be an alias itself.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SshJumpHost {
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    /// The first `IdentityFile` of the jump host, the SSH agent is used without one.
+    pub identity_file: Option<PathBuf>,
+}
+
+/// The connection parameters of a host alias after applying every matching `Host` block.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SshConfigHost {
+    pub alias: String,
+    pub host_name: String,
+    pub port: u16,
+    pub username: String,
+    pub identity_files: Vec<PathBuf>,
+    /// The jump hosts in the order they are connected, the last one connects to the target.
+    pub proxy_jump: Vec<SshJumpHost>,
+}
+
+/// A `Host` or `Match` block with the options in the order they appear.
+#[derive(Debug, Clone)]
+struct Block {
+    patterns: Vec<String>,
+    /// `Match` blocks are never applied, their criteria need a connection to evaluate.
+    is_match: bool,
+    options: Vec<(String, String)>,
+}
+
+/// A parsed `~/.ssh/config` with its `Include`d files.
+#[derive(Debug, Clone, Default)]
+pub struct SshConfig {
+    blocks: Vec<Block>,
+}
+
+impl SshConfig {
+    /// The config file of the current user, `~/.ssh/config`.
+    pub fn default_path() -> Option<PathBuf> {
+        home::home_dir().map(|home| home.join(".ssh").join("config"))
+    }
+
+    /// Reads a config file, a missing file is an empty config.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e),
+        };
+        let base = path.parent().unwrap_or(Path::new("."));
+        Ok(Self::parse(&content, base))
+    }
+
+    /// Parses the content of a config file. Relative `Include` paths are looked up in `base`,
+    /// which is `~/.ssh` for the user config.
+    pub fn parse(content: &str, base: &Path) -> Self {
+        let mut config = Self {
+            // Options before the first Host line apply to every host
+            blocks: vec![Block {
+                patterns: vec!["*".to_string()],
+                is_match: false,
+                options: Vec::new(),
+            }],
+        };
+        config.parse_into(content, base, 0);
+        config
+    }
+
+    fn parse_into(&mut self, content: &str, base: &Path, depth: usize) {
+        for line in content.lines() {
+            let Some((keyword, args)) = split_line(line) else {
+                continue;
+            };
+            match keyword.as_str() {
+                "host" => self.blocks.push(Block {
+                    patterns: args,
+                    is_match: false,
+                    options: Vec::new(),
+                }),
+                "match" => self.blocks.push(Block {
+                    patterns: Vec::new(),
+                    is_match: true,
+                    options: Vec::new(),
+                }),
+                "include" if depth < MAX_INCLUDE_DEPTH => {
+                    // The options of included files belong to the block the Include is in
+                    let current = self.blocks.len() - 1;
+                    for pattern in &args {
+                        for file in expand_include(base, pattern) {
+                            if let Ok(included) = fs::read_to_string(&file) {
+                                let mut nested = Self {
+                                    blocks: vec![Block {
+                                        patterns: Vec::new(),
+                                        is_match: false,
+                                        options: Vec::new(),
+                                    }],
+                                };
+                                nested.parse_into(&included, base, depth + 1);
+                                let mut blocks = nested.blocks.into_iter();
+                                if let Some(first) = blocks.next() {
+                                    self.blocks[current].options.extend(first.options);
+                                }
+                                self.blocks.extend(blocks);
+                            }
+                        }
+                    }
+                    // The lines after the Include still belong to the enclosing block
+                    if self.blocks.len() - 1 != current {
+                        let enclosing = Block {
+                            options: Vec::new(),
+                            ..self.blocks[current].clone()
+                        };
+                        self.blocks.push(enclosing);
+                    }
+                }
+                _ => {
+                    if let Some(block) = self.blocks.last_mut() {
+                        block.options.push((keyword, args.join(" ")));
+                    }
+                }
+            }
+        }
+    }
+
+    /// The aliases of the `Host` lines without wildcards, in the order of the file.
+    pub fn aliases(&self) -> Vec<String> {
+        let mut aliases: Vec<String> = Vec::new();
+        for block in self.blocks.iter().filter(|block| !block.is_match) {
+            for pattern in &block.patterns {
+                let is_alias = !pattern.starts_with('!')
+                    && !pattern.contains(['*', '?'])
+                    && !aliases.contains(pattern);
+                if is_alias {
+                    aliases.push(pattern.clone());
+                }
+            }
+        }
+        aliases
+    }
+
+    /// Every alias resolved into its connection parameters, used as connection suggestions.
+    pub fn hosts(&self) -> Vec<SshConfigHost> {
+        self.aliases()
+            .iter()
+            .map(|alias| self.resolve(alias))
+            .collect()
+    }
+
+    /// Applies every block matching `alias`, the first value of an option wins like in OpenSSH.
+    /// A name which is not in the config resolves to itself with the defaults.
+    pub fn resolve(&self, alias: &str) -> SshConfigHost {
+        self.resolve_chain(alias, 0)
+    }
+
+    fn resolve_chain(&self, alias: &str, depth: usize) -> SshConfigHost {
+        let options = self.options_for(alias);
+        let first = |name: &str| {
+            options
+                .iter()
+                .find(|(keyword, _)| keyword == name)
+                .map(|(_, value)| value.as_str())
+        };
+
+        let host_name = first("hostname")
+            .map(|value| expand_tokens(value, alias, alias, ""))
+            .unwrap_or_else(|| alias.to_string());
+        let port = first("port")
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(22);
+        let username = first("user")
+            .map(str::to_string)
+            .unwrap_or_else(local_username);
+        let identity_files = options
+            .iter()
+            .filter(|(keyword, _)| keyword == "identityfile")
+            .filter(|(_, value)| !value.eq_ignore_ascii_case("none"))
+            .map(|(_, value)| expand_path(&expand_tokens(value, alias, &host_name, &username)))
+            .collect();
+
+        let mut proxy_jump = Vec::new();
+        if let Some(value) = first("proxyjump").filter(|value| !value.eq_ignore_ascii_case("none"))
+        {
+            for jump in value
+                .split(',')
+                .map(str::trim)
+                .filter(|jump| !jump.is_empty())
+            {
+                if depth + proxy_jump.len() >= MAX_JUMP_HOSTS {
+                    break;
+                }
+                let (user, jump_alias, jump_port) = parse_jump(jump);
+                let resolved = self.resolve_chain(&jump_alias, depth + proxy_jump.len() + 1);
+                // A jump host is reached through its own jump hosts first
+                proxy_jump.extend(resolved.proxy_jump.iter().cloned());
+                proxy_jump.push(SshJumpHost {
+                    host: resolved.host_name,
+                    port: jump_port.unwrap_or(resolved.port),
+                    username: user.unwrap_or(resolved.username),
+                    identity_file: resolved.identity_files.into_iter().next(),
+                });
+            }
+        }
+
+        SshConfigHost {
+            alias: alias.to_string(),
+            host_name,
+            port,
+            username,
+            identity_files,
+            proxy_jump,
+        }
+    }
+
+    fn options_for(&self, alias: &str) -> Vec<(String, String)> {
+        self.blocks
+            .iter()
+            .filter(|block| !block.is_match && host_matches(&block.patterns, alias))
+            .flat_map(|block| block.options.iter().cloned())
+            .collect()
+    }
+}
+
+/// Splits a line into its lowercase keyword and arguments, `Key value`, `Key=value` and
+/// quoted arguments are allowed. Returns `None` for empty lines and comments.
+fn split_line(line: &str) -> Option<(String, Vec<String>)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let end = line.find(|c: char| c.is_whitespace() || c == '=')?;
+    let keyword = line[..end].to_ascii_lowercase();
+    let rest = line[end..].trim_start();
+    let rest = rest.strip_prefix('=').unwrap_or(rest);
+
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in rest.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    args.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        args.push(current);
+    }
+    Some((keyword, args))
+}
+
+/// Whether a host matches a `Host` line, a negated pattern excludes the host even if another
+/// pattern matches.
+fn host_matches(patterns: &[String], host: &str) -> bool {
+    let mut matched = false;
+    for pattern in patterns {
+        if let Some(negated) = pattern.strip_prefix('!') {
+            if wildcard_match(negated, host) {
+                return false;
+            }
+        } else if wildcard_match(pattern, host) {
+            matched = true;
+        }
+    }
+    matched
+}
+
+/// Matches `*` and `?` wildcards, hosts are compared case-insensitively.
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// The files of an `Include`, wildcards are allowed in the file name.
+fn expand_include(base: &Path, pattern: &str) -> Vec<PathBuf> {
+    let path = expand_path(pattern);
+    let path = if path.is_absolute() {
+        path
+    } else {
+        base.join(path)
+    };
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    if !file_name.contains(['*', '?']) {
+        return vec![path];
+    }
+    let Some(directory) = path.parent() else {
+        return Vec::new();
+    };
+    let mut files: Vec<PathBuf> = fs::read_dir(directory)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter(|entry| wildcard_match(&file_name, &entry.file_name().to_string_lossy()))
+                .map(|entry| entry.path())
+                .filter(|path| path.is_file())
+                .collect()
+        })
+        .unwrap_or_default();
+    files.sort();
+    files
+}
+
+/// Parses a `ProxyJump` entry, `[user@]host[:port]` or `ssh://[user@]host[:port]`.
+fn parse_jump(jump: &str) -> (Option<String>, String, Option<u16>) {
+    let jump = jump.strip_prefix("ssh://").unwrap_or(jump);
+    let (user, host) = match jump.rsplit_once('@') {
+        Some((user, host)) => (Some(user.to_string()), host),
+        None => (None, jump),
+    };
+    // IPv6 addresses are written as [::1]:22
+    if let Some(bracketed) = host.strip_prefix('[') {
+        if let Some((address, rest)) = bracketed.split_once(']') {
+            let port = rest.strip_prefix(':').and_then(|port| port.parse().ok());
+            return (user, address.to_string(), port);
+        }
+    }
+    match host.rsplit_once(':') {
+        Some((name, port)) if !name.contains(':') => (user, name.to_string(), port.parse().ok()),
+        _ => (user, host.to_string(), None),
+    }
+}
+
+/// Replaces the tokens OpenSSH allows in `HostName` and `IdentityFile`.
+fn expand_tokens(value: &str, alias: &str, host_name: &str, username: &str) -> String {
+    let mut result = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('%') => result.push('%'),
+            Some('h') => result.push_str(host_name),
+            Some('n') => result.push_str(alias),
+            Some('r') => result.push_str(username),
+            Some('u') => result.push_str(&local_username()),
+            Some('d') => {
+                if let Some(home) = home::home_dir() {
+                    result.push_str(&home.to_string_lossy());
+                }
+            }
+            Some(other) => {
+                result.push('%');
+                result.push(other);
+            }
+            None => result.push('%'),
+        }
+    }
+    result
+}
+
+fn expand_path(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), home::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// The user ssh logs in as when the config has no `User`.
+fn local_username() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests_ssh_config {
+    use super::*;
+
+    const CONFIG: &str = r#"
+# Defaults before the first Host line
+ServerAliveInterval 30
+
+Host bastion
+    HostName bastion.example.com
+    User jump
+    IdentityFile ~/.ssh/bastion_key
+
+Host db web-*
+    ProxyJump bastion
+    User deploy
+
+Host db
+    HostName 10.0.0.5
+    Port=2222
+    User ignored
+    IdentityFile "/keys/db key"
+
+Host inner
+    HostName %h.internal
+    ProxyJump admin@db:2200,[::1]:2022
+
+Host * !bastion
+    IdentityFile ~/.ssh/id_ed25519
+"#;
+
+    fn config() -> SshConfig {
+        SshConfig::parse(CONFIG, Path::new("/nonexistent"))
+    }
+
+    #[test]
+    fn aliases_skip_wildcards_test() {
+        assert_eq!(config().aliases(), vec!["bastion", "db", "inner"]);
+    }
+
+    #[test]
+    fn first_value_wins_test() {
+        let home = home::home_dir().unwrap();
+        let db = config().resolve("db");
+        assert_eq!(db.host_name, "10.0.0.5");
+        assert_eq!(db.port, 2222);
+        assert_eq!(db.username, "deploy");
+        assert_eq!(
+            db.identity_files,
+            vec![PathBuf::from("/keys/db key"), home.join(".ssh/id_ed25519")]
+        );
+        assert_eq!(
+            db.proxy_jump,
+            vec![SshJumpHost {
+                host: "bastion.example.com".to_string(),
+                port: 22,
+                username: "jump".to_string(),
+                identity_file: Some(home.join(".ssh/bastion_key")),
+            }]
+        );
+    }
+
+    #[test]
+    fn jump_hosts_are_chained_test() {
+        let inner = config().resolve("inner");
+        assert_eq!(inner.host_name, "inner.internal");
+        let hops: Vec<(&str, u16, &str)> = inner
+            .proxy_jump
+            .iter()
+            .map(|jump| (jump.host.as_str(), jump.port, jump.username.as_str()))
+            .collect();
+        assert_eq!(hops[0], ("bastion.example.com", 22, "jump"));
+        assert_eq!(hops[1], ("10.0.0.5", 2200, "admin"));
+        assert_eq!(hops[2].0, "::1");
+        assert_eq!(hops[2].1, 2022);
+    }
+
+    #[test]
+    fn unknown_alias_resolves_to_itself_test() {
+        let host = config().resolve("web-1");
+        assert_eq!(host.host_name, "web-1");
+        assert_eq!(host.port, 22);
+        assert_eq!(host.username, "deploy");
+        assert_eq!(host.proxy_jump.len(), 1);
+    }
+
+    #[test]
+    fn jump_loops_are_stopped_test() {
+        let config = SshConfig::parse(
+            "Host a\n  ProxyJump b\nHost b\n  ProxyJump a\n",
+            Path::new("/"),
+        );
+        assert!(config.resolve("a").proxy_jump.len() <= MAX_JUMP_HOSTS + 1);
+    }
+
+    #[test]
+    fn include_test() {
+        let dir = std::env::temp_dir().join(format!("ssh_config_include_{}", std::process::id()));
+        fs::create_dir_all(dir.join("config.d")).unwrap();
+        fs::write(
+            dir.join("config.d/work.conf"),
+            "Host work\n  HostName work.example.com\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("config"),
+            "Include config.d/*.conf\nHost home\n  HostName home.example.com\n",
+        )
+        .unwrap();
+
+        let config = SshConfig::load(&dir.join("config")).unwrap();
+        assert_eq!(config.aliases(), vec!["work", "home"]);
+        assert_eq!(config.resolve("work").host_name, "work.example.com");
+        assert!(SshConfig::load(&dir.join("missing"))
+            .unwrap()
+            .aliases()
+            .is_empty());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn wildcard_match_test() {
+        assert!(wildcard_match("*.example.com", "db.EXAMPLE.com"));
+        assert!(wildcard_match("web-?", "web-1"));
+        assert!(!wildcard_match("web-?", "web-10"));
+        assert!(host_matches(
+            &["*".to_string(), "!bastion".to_string()],
+            "db"
+        ));
+        assert!(!host_matches(
+            &["*".to_string(), "!bastion".to_string()],
+            "bastion"
+        ));
+    }
+}
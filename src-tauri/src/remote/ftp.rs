@@ -0,0 +1,920 @@
+//! FTP client (RFC 959) with the extensions servers commonly support: MLSD, MLST and REST for
+//! listings and resuming (RFC 3659), EPSV (RFC 2428) and explicit TLS (RFC 4217). Transfers
+//! are binary and always use passive mode.
+
+use super::net::{connect_tcp, start_tls, tls_connector, Stream};
+use super::{
+    file_stat, join_path, remote_error, RemoteEndpoint, RemoteEntry, RemoteFileSystem,
+    RemoteSessionConfig, S_IFDIR, S_IFLNK, S_IFREG,
+};
+use crate::error_handling::ErrorCode;
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Utc};
+use openssl::ssl::SslConnector;
+use ssh2::FileStat;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Servers close idle connections after a few minutes. A connection idle for longer is checked
+/// with `NOOP` first, so transfers, which can't be repeated, don't start on a dead connection.
+const IDLE_CHECK: Duration = Duration::from_secs(30);
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug)]
+enum FtpError {
+    /// The connection failed, the operation can be tried again on a new connection.
+    Connection(String),
+    /// The server refused the command with this reply.
+    Reply(u32, String),
+    /// Reading or writing the local side of a transfer failed, e.g. because it was cancelled.
+    Local(String),
+}
+
+impl From<io::Error> for FtpError {
+    fn from(error: io::Error) -> Self {
+        FtpError::Connection(error.to_string())
+    }
+}
+
+impl FtpError {
+    fn into_json(self, context: &str) -> String {
+        match self {
+            FtpError::Connection(message) => remote_error(
+                ErrorCode::InternalError,
+                format!("{}: connection failed: {}", context, message),
+            ),
+            FtpError::Reply(code, message) => {
+                let error_code = match code {
+                    530 | 532 => ErrorCode::Unauthorized,
+                    550 => ErrorCode::ResourceNotFound,
+                    553 => ErrorCode::InvalidInput,
+                    _ => ErrorCode::InternalError,
+                };
+                remote_error(error_code, format!("{}: {} {}", context, code, message))
+            }
+            FtpError::Local(message) => remote_error(
+                ErrorCode::InternalError,
+                format!("{}: {}", context, message),
+            ),
+        }
+    }
+}
+
+/// A reply of the server, multi-line replies have all their lines.
+struct Reply {
+    code: u32,
+    lines: Vec<String>,
+}
+
+impl Reply {
+    /// The text of the last line without the code.
+    fn text(&self) -> &str {
+        self.lines
+            .last()
+            .and_then(|line| line.get(4..))
+            .unwrap_or("")
+    }
+}
+
+/// Copies from `reader` to `writer`, at most `limit` bytes. Errors of the server side are
+/// `Connection` errors, errors of the local side `Local` errors.
+fn copy(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    limit: Option<u64>,
+    from_server: bool,
+) -> Result<u64, FtpError> {
+    let server_error: fn(io::Error) -> FtpError = |e| FtpError::Connection(e.to_string());
+    let local_error: fn(io::Error) -> FtpError = |e| FtpError::Local(e.to_string());
+    let (read_error, write_error) = if from_server {
+        (server_error, local_error)
+    } else {
+        (local_error, server_error)
+    };
+
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    let mut copied = 0u64;
+    while limit.is_none_or(|limit| copied < limit) {
+        let wanted = limit.map_or(CHUNK_SIZE as u64, |limit| {
+            (limit - copied).min(CHUNK_SIZE as u64)
+        });
+        let read = reader
+            .read(&mut buffer[..wanted as usize])
+            .map_err(read_error)?;
+        if read == 0 {
+            break;
+        }
+        writer.write_all(&buffer[..read]).map_err(write_error)?;
+        copied += read as u64;
+    }
+    writer.flush().map_err(write_error)?;
+    Ok(copied)
+}
+
+fn parse_mlsd_time(value: &str) -> Option<u64> {
+    let time = NaiveDateTime::parse_from_str(value.get(..14)?, "%Y%m%d%H%M%S").ok()?;
+    u64::try_from(time.and_utc().timestamp()).ok()
+}
+
+/// Parses a line of an `MLSD` listing or the facts of an `MLST` reply, e.g.
+/// `type=file;size=1024;modify=20240101120000;UNIX.mode=0644; notes.txt`. The entries for the
+/// directory itself and its parent are only returned if `keep_current` is set.
+fn parse_mlsd_line(line: &str, keep_current: bool) -> Option<(String, FileStat, Option<String>)> {
+    let (facts, name) = line.split_once(' ')?;
+    let mut stat = file_stat(S_IFREG, None, None);
+    let mut file_type = S_IFREG;
+    let mut symlink_target = None;
+    for fact in facts.split(';').filter(|fact| !fact.is_empty()) {
+        let Some((key, value)) = fact.split_once('=') else {
+            continue;
+        };
+        match key.to_ascii_lowercase().as_str() {
+            "type" => {
+                let lower = value.to_ascii_lowercase();
+                file_type = match lower.as_str() {
+                    "dir" => S_IFDIR,
+                    "cdir" | "pdir" if keep_current => S_IFDIR,
+                    "cdir" | "pdir" => return None,
+                    _ if lower.starts_with("os.unix=slink")
+                        || lower.starts_with("os.unix=symlink") =>
+                    {
+                        symlink_target = value
+                            .split_once(':')
+                            .map(|(_, target)| target.to_string())
+                            .filter(|target| !target.is_empty());
+                        S_IFLNK
+                    }
+                    _ => S_IFREG,
+                };
+            }
+            "size" => stat.size = value.parse().ok(),
+            "modify" => stat.mtime = parse_mlsd_time(value),
+            "unix.mode" => stat.perm = u32::from_str_radix(value, 8).ok().map(|mode| mode & 0o7777),
+            "unix.uid" | "unix.owner" => stat.uid = value.parse().ok(),
+            "unix.gid" | "unix.group" => stat.gid = value.parse().ok(),
+            _ => {}
+        }
+    }
+    stat.perm = Some(file_type | stat.perm.unwrap_or(0));
+    Some((name.to_string(), stat, symlink_target))
+}
+
+fn mode_from_permissions(permissions: &str) -> Option<u32> {
+    let bytes = permissions.as_bytes();
+    if bytes.len() < 10 {
+        return None;
+    }
+    let file_type = match bytes[0] {
+        b'd' => S_IFDIR,
+        b'l' => S_IFLNK,
+        _ => S_IFREG,
+    };
+    let mut mode = 0;
+    for (index, &flag) in bytes[1..10].iter().enumerate() {
+        let bit = 1 << (8 - index);
+        match flag {
+            b'-' => {}
+            b'S' | b'T' => {}
+            _ => mode |= bit,
+        }
+        // setuid, setgid and sticky bits replace the execute flag
+        match (index, flag) {
+            (2, b's' | b'S') => mode |= 0o4000,
+            (5, b's' | b'S') => mode |= 0o2000,
+            (8, b't' | b'T') => mode |= 0o1000,
+            _ => {}
+        }
+    }
+    Some(file_type | mode)
+}
+
+/// The modification time of a `LIST` line, `Jan 31 12:00` in the last year or `Jan 31 2023`.
+fn parse_list_time(month: &str, day: &str, time_or_year: &str, now: NaiveDateTime) -> Option<u64> {
+    const MONTHS: [&str; 12] = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
+    let month = MONTHS
+        .iter()
+        .position(|name| month.eq_ignore_ascii_case(name))? as u32
+        + 1;
+    let day: u32 = day.parse().ok()?;
+    let time = match time_or_year.split_once(':') {
+        Some((hour, minute)) => {
+            let (hour, minute) = (hour.parse().ok()?, minute.parse().ok()?);
+            let this_year =
+                NaiveDate::from_ymd_opt(now.year(), month, day)?.and_hms_opt(hour, minute, 0)?;
+            // Without a year the date is within the last year, a date after today is from the
+            // year before
+            if this_year > now + chrono::Duration::days(1) {
+                NaiveDate::from_ymd_opt(now.year() - 1, month, day)?.and_hms_opt(hour, minute, 0)?
+            } else {
+                this_year
+            }
+        }
+        None => {
+            NaiveDate::from_ymd_opt(time_or_year.parse().ok()?, month, day)?.and_hms_opt(0, 0, 0)?
+        }
+    };
+    u64::try_from(time.and_utc().timestamp()).ok()
+}
+
+/// Parses a line of a Unix style `LIST` listing, the fallback for servers without `MLSD`, e.g.
+/// `-rw-r--r--   1 alice  staff  1024 Jan 31 12:00 notes.txt`.
+fn parse_list_line(line: &str, now: NaiveDateTime) -> Option<(String, FileStat, Option<String>)> {
+    let mut fields = Vec::with_capacity(8);
+    let mut rest = line;
+    for _ in 0..8 {
+        rest = rest.trim_start();
+        let end = rest.find(' ')?;
+        fields.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+    let name = rest.trim_start();
+    let mode = mode_from_permissions(fields[0])?;
+    let stat = file_stat(
+        mode,
+        fields[4].parse().ok(),
+        parse_list_time(fields[5], fields[6], fields[7], now),
+    );
+    match name.split_once(" -> ") {
+        Some((name, target)) if mode & super::S_IFMT == S_IFLNK => {
+            Some((name.to_string(), stat, Some(target.to_string())))
+        }
+        _ => Some((name.to_string(), stat, None)),
+    }
+}
+
+/// An open control connection.
+struct FtpConnection {
+    control: BufReader<Stream>,
+    host: String,
+    /// Set for FTPS, the data connections are protected as well.
+    tls: Option<SslConnector>,
+    /// The features the server announced with `FEAT`, in upper case.
+    features: Vec<String>,
+    epsv_unsupported: bool,
+    last_used: Instant,
+}
+
+impl FtpConnection {
+    fn open(config: &RemoteSessionConfig) -> Result<Self, FtpError> {
+        let stream = connect_tcp(&config.host, config.port())?;
+        let mut connection = FtpConnection {
+            control: BufReader::new(Stream::Plain(stream)),
+            host: config.host.clone(),
+            tls: None,
+            features: Vec::new(),
+            epsv_unsupported: false,
+            last_used: Instant::now(),
+        };
+        let mut greeting = connection.read_reply()?;
+        // 120 means the server is busy and sends 220 once it is ready
+        while greeting.code == 120 {
+            greeting = connection.read_reply()?;
+        }
+        connection.check(greeting, &[220])?;
+
+        if config.protocol.uses_tls() {
+            let connector = tls_connector(config.accept_invalid_certificates)?;
+            connection.command("AUTH TLS", &[234])?;
+            let Stream::Plain(stream) = connection.control.into_inner() else {
+                return Err(FtpError::Connection(
+                    "Connection is already encrypted".to_string(),
+                ));
+            };
+            connection.control = BufReader::new(start_tls(&connector, &config.host, stream, None)?);
+            connection.tls = Some(connector);
+        }
+
+        let username = match config.username.as_str() {
+            "" => "anonymous",
+            username => username,
+        };
+        let reply = connection.command(&format!("USER {}", username), &[230, 331])?;
+        if reply.code == 331 {
+            connection.command(&format!("PASS {}", config.password), &[230, 202])?;
+        }
+        if connection.tls.is_some() {
+            connection.command("PBSZ 0", &[200])?;
+            connection.command("PROT P", &[200])?;
+        }
+
+        if let Ok(reply) = connection.command("FEAT", &[211]) {
+            let count = reply.lines.len();
+            connection.features = reply
+                .lines
+                .into_iter()
+                .take(count.saturating_sub(1))
+                .skip(1)
+                .map(|line| line.trim().to_uppercase())
+                .collect();
+        }
+        if connection.has_feature("UTF8") {
+            let _ = connection.command("OPTS UTF8 ON", &[200, 202]);
+        }
+        connection.command("TYPE I", &[200])?;
+        Ok(connection)
+    }
+
+    fn has_feature(&self, name: &str) -> bool {
+        self.features
+            .iter()
+            .any(|feature| feature.split(' ').next() == Some(name))
+    }
+
+    fn read_line(&mut self) -> Result<String, FtpError> {
+        let mut line = Vec::new();
+        if self.control.read_until(b'\n', &mut line)? == 0 {
+            return Err(FtpError::Connection(
+                "Connection closed by the server".to_string(),
+            ));
+        }
+        Ok(String::from_utf8_lossy(&line)
+            .trim_end_matches(['\r', '\n'])
+            .to_string())
+    }
+
+    fn read_reply(&mut self) -> Result<Reply, FtpError> {
+        let first = self.read_line()?;
+        let code = first
+            .get(..3)
+            .and_then(|code| code.parse().ok())
+            .ok_or_else(|| FtpError::Connection(format!("Invalid reply: {}", first)))?;
+        let mut lines = vec![first];
+        if lines[0].as_bytes().get(3) == Some(&b'-') {
+            let end = format!("{} ", code);
+            loop {
+                let line = self.read_line()?;
+                let done = line.starts_with(&end);
+                lines.push(line);
+                if done {
+                    break;
+                }
+            }
+        }
+        Ok(Reply { code, lines })
+    }
+
+    fn check(&self, reply: Reply, accepted: &[u32]) -> Result<Reply, FtpError> {
+        if accepted.contains(&reply.code) {
+            Ok(reply)
+        } else if reply.code == 421 {
+            // The server is shutting the connection down, e.g. after an idle timeout
+            Err(FtpError::Connection(reply.text().to_string()))
+        } else {
+            Err(FtpError::Reply(reply.code, reply.text().to_string()))
+        }
+    }
+
+    /// Sends a command and fails unless the server replies with one of the `accepted` codes.
+    fn command(&mut self, command: &str, accepted: &[u32]) -> Result<Reply, FtpError> {
+        let stream = self.control.get_mut();
+        stream.write_all(format!("{}\r\n", command).as_bytes())?;
+        stream.flush()?;
+        let reply = self.read_reply()?;
+        self.last_used = Instant::now();
+        self.check(reply, accepted)
+    }
+
+    /// Connects to the data port of the server. The address of the control connection is used
+    /// instead of the one in the `PASV` reply, servers behind NAT announce their private address.
+    fn passive(&mut self) -> Result<TcpStream, FtpError> {
+        let host = self.control.get_ref().tcp().peer_addr()?.ip().to_string();
+        let mut port = None;
+        if !self.epsv_unsupported {
+            match self.command("EPSV", &[229]) {
+                Ok(reply) => port = parse_epsv(reply.text()),
+                Err(FtpError::Reply(_, _)) => self.epsv_unsupported = true,
+                Err(err) => return Err(err),
+            }
+        }
+        let port = match port {
+            Some(port) => port,
+            None => {
+                let reply = self.command("PASV", &[227])?;
+                parse_pasv(reply.text())
+                    .ok_or_else(|| FtpError::Reply(reply.code, reply.text().to_string()))?
+            }
+        };
+        Ok(connect_tcp(&host, port)?)
+    }
+
+    /// Sends a command which transfers data and returns the data connection once the server
+    /// accepted the command. TLS starts afterwards, servers only answer the handshake then.
+    fn open_data(&mut self, command: &str) -> Result<Stream, FtpError> {
+        let stream = self.passive()?;
+        self.command(command, &[125, 150])?;
+        match &self.tls {
+            Some(connector) => {
+                // Servers like vsftpd require the data connection to resume the TLS session
+                let session = self.control.get_ref().tls_session();
+                Ok(start_tls(connector, &self.host, stream, session.as_ref())?)
+            }
+            None => Ok(Stream::Plain(stream)),
+        }
+    }
+
+    /// Closes the data connection and reads the reply ending the transfer. A transfer which
+    /// stopped early is aborted by the server, its error reply is expected then.
+    fn close_data(&mut self, data: Stream, complete: bool) -> Result<(), FtpError> {
+        data.close();
+        let reply = self.read_reply()?;
+        self.last_used = Instant::now();
+        if complete {
+            self.check(reply, &[226, 250]).map(|_| ())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn list(&mut self, path: &str) -> Result<Vec<RemoteEntry>, FtpError> {
+        let mlsd = self.has_feature("MLST");
+        let command = if mlsd {
+            format!("MLSD {}", path)
+        } else {
+            format!("LIST {}", path)
+        };
+        let mut data = self.open_data(&command)?;
+        let mut listing = Vec::new();
+        let read = data.read_to_end(&mut listing);
+        let closed = self.close_data(data, read.is_ok());
+        read?;
+        closed?;
+
+        let now = Utc::now().naive_utc();
+        let mut entries = Vec::new();
+        for line in String::from_utf8_lossy(&listing).lines() {
+            let parsed = if mlsd {
+                parse_mlsd_line(line, false)
+            } else {
+                parse_list_line(line, now)
+            };
+            let Some((name, stat, symlink_target)) = parsed else {
+                continue;
+            };
+            if name == "." || name == ".." {
+                continue;
+            }
+            let entry_path = join_path(path, &name);
+            // MLST follows symlinks, so their targets can be told apart from broken links
+            let target_stat = if stat.file_type().is_symlink() && mlsd {
+                self.stat(&entry_path).ok()
+            } else {
+                None
+            };
+            entries.push(RemoteEntry {
+                path: PathBuf::from(entry_path),
+                stat,
+                symlink_target,
+                target_stat,
+            });
+        }
+        Ok(entries)
+    }
+
+    fn stat(&mut self, path: &str) -> Result<FileStat, FtpError> {
+        if self.has_feature("MLST") {
+            let reply = self.command(&format!("MLST {}", path), &[250])?;
+            return reply
+                .lines
+                .iter()
+                .skip(1)
+                .find(|line| line.starts_with(' '))
+                .and_then(|line| parse_mlsd_line(line.trim_start(), true))
+                .map(|(_, stat, _)| stat)
+                .ok_or_else(|| FtpError::Reply(reply.code, reply.text().to_string()));
+        }
+
+        // Without MLST the entry is looked up in the listing of its parent
+        let trimmed = path.trim_end_matches('/');
+        if trimmed.is_empty() {
+            return Ok(file_stat(S_IFDIR, None, None));
+        }
+        let (parent, name) = match trimmed.rsplit_once('/') {
+            Some(("", name)) => ("/", name),
+            Some((parent, name)) => (parent, name),
+            None => (".", trimmed),
+        };
+        self.list(parent)?
+            .into_iter()
+            .find(|entry| {
+                entry
+                    .path
+                    .file_name()
+                    .is_some_and(|entry_name| entry_name == name)
+            })
+            .map(|entry| entry.target_stat.unwrap_or(entry.stat))
+            .ok_or_else(|| FtpError::Reply(550, format!("{}: No such file or directory", path)))
+    }
+
+    fn read_file(
+        &mut self,
+        path: &str,
+        offset: u64,
+        limit: Option<u64>,
+        writer: &mut dyn Write,
+    ) -> Result<u64, FtpError> {
+        if offset > 0 {
+            self.command(&format!("REST {}", offset), &[350])?;
+        }
+        let mut data = self.open_data(&format!("RETR {}", path))?;
+        let copied = copy(&mut data, writer, limit, true);
+        let complete = matches!(copied, Ok(copied) if limit.is_none_or(|limit| copied < limit));
+        let closed = self.close_data(data, complete);
+        let copied = copied?;
+        closed?;
+        Ok(copied)
+    }
+
+    fn write_file(
+        &mut self,
+        path: &str,
+        offset: u64,
+        reader: &mut dyn Read,
+        length: u64,
+    ) -> Result<u64, FtpError> {
+        let command = match offset {
+            0 => format!("STOR {}", path),
+            _ => format!("APPE {}", path),
+        };
+        let mut data = self.open_data(&command)?;
+        let copied = copy(reader, &mut data, Some(length), false);
+        let closed = self.close_data(data, copied.is_ok());
+        let copied = copied?;
+        closed?;
+        Ok(copied)
+    }
+}
+
+/// The port of an `EPSV` reply, e.g. `Entering Extended Passive Mode (|||6446|)`.
+fn parse_epsv(text: &str) -> Option<u16> {
+    let start = text.find("|||")? + 3;
+    let end = start + text[start..].find('|')?;
+    text[start..end].parse().ok()
+}
+
+/// The port of a `PASV` reply, e.g. `Entering Passive Mode (192,168,1,2,19,137)`.
+fn parse_pasv(text: &str) -> Option<u16> {
+    let start = text.find('(').map_or_else(
+        || text.find(|c: char| c.is_ascii_digit()),
+        |start| Some(start + 1),
+    )?;
+    let numbers: Vec<u16> = text[start..]
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|number| !number.is_empty())
+        .take(6)
+        .map(|number| number.parse().ok())
+        .collect::<Option<_>>()?;
+    match numbers.as_slice() {
+        [_, _, _, _, high, low] if *high < 256 && *low < 256 => Some(high * 256 + low),
+        _ => None,
+    }
+}
+
+/// An FTP or FTPS session. The control connection is opened again whenever it was lost.
+pub struct FtpSession {
+    config: RemoteSessionConfig,
+    connection: Mutex<Option<FtpConnection>>,
+}
+
+impl FtpSession {
+    /// Connects and logs in, fails if the server can't be reached or refuses the login.
+    pub fn connect(config: RemoteSessionConfig) -> Result<Self, String> {
+        let connection = FtpConnection::open(&config).map_err(|e| {
+            e.into_json(&format!(
+                "Failed to connect to {}:{}",
+                config.host,
+                config.port()
+            ))
+        })?;
+        Ok(Self {
+            config,
+            connection: Mutex::new(Some(connection)),
+        })
+    }
+
+    /// Runs an operation on the control connection. Operations of the same session run one
+    /// after another. If the connection dropped, it is opened again and operations which don't
+    /// transfer data are tried once more, transfers fail and are resumed by the caller.
+    fn with_connection<T>(
+        &self,
+        context: &str,
+        retry: bool,
+        mut operation: impl FnMut(&mut FtpConnection) -> Result<T, FtpError>,
+    ) -> Result<T, String> {
+        let mut connection = self.connection.lock().map_err(|_| {
+            remote_error(
+                ErrorCode::InternalError,
+                "Failed to acquire lock on FTP session".to_string(),
+            )
+        })?;
+        if let Some(open) = connection.as_mut() {
+            if open.last_used.elapsed() >= IDLE_CHECK && open.command("NOOP", &[200]).is_err() {
+                *connection = None;
+            }
+        }
+        if connection.is_none() {
+            *connection =
+                Some(FtpConnection::open(&self.config).map_err(|e| e.into_json(context))?);
+        }
+
+        let mut result = operation(connection.as_mut().unwrap());
+        if let Err(FtpError::Connection(_)) = result {
+            *connection = None;
+            if retry {
+                let reopened = connection
+                    .insert(FtpConnection::open(&self.config).map_err(|e| e.into_json(context))?);
+                result = operation(reopened);
+                if let Err(FtpError::Connection(_)) = result {
+                    *connection = None;
+                }
+            }
+        }
+        result.map_err(|e| e.into_json(context))
+    }
+}
+
+impl RemoteFileSystem for FtpSession {
+    fn endpoint(&self) -> RemoteEndpoint {
+        RemoteEndpoint {
+            protocol: self.config.protocol,
+            host: self.config.host.clone(),
+            port: self.config.port(),
+            username: self.config.username.clone(),
+        }
+    }
+
+    fn is_connected(&self) -> bool {
+        self.connection
+            .lock()
+            .map(|connection| connection.is_some())
+            .unwrap_or(false)
+    }
+
+    fn list_dir(&self, path: &str) -> Result<Vec<RemoteEntry>, String> {
+        self.with_connection(&format!("Failed to list {}", path), true, |connection| {
+            connection.list(path)
+        })
+    }
+
+    fn stat(&self, path: &str) -> Result<FileStat, String> {
+        self.with_connection(&format!("Failed to read {}", path), true, |connection| {
+            connection.stat(path)
+        })
+    }
+
+    fn read_file(
+        &self,
+        path: &str,
+        offset: u64,
+        limit: Option<u64>,
+        writer: &mut dyn Write,
+    ) -> Result<u64, String> {
+        self.with_connection(
+            &format!("Failed to download {}", path),
+            false,
+            |connection| connection.read_file(path, offset, limit, writer),
+        )
+    }
+
+    fn write_file(
+        &self,
+        path: &str,
+        offset: u64,
+        reader: &mut dyn Read,
+        length: u64,
+    ) -> Result<u64, String> {
+        self.with_connection(&format!("Failed to upload {}", path), false, |connection| {
+            connection.write_file(path, offset, reader, length)
+        })
+    }
+
+    fn supports_append(&self) -> bool {
+        true
+    }
+
+    fn create_dir(&self, path: &str) -> Result<(), String> {
+        self.with_connection(&format!("Failed to create {}", path), true, |connection| {
+            connection
+                .command(&format!("MKD {}", path), &[257])
+                .map(|_| ())
+        })
+    }
+
+    fn remove_file(&self, path: &str) -> Result<(), String> {
+        self.with_connection(&format!("Failed to delete {}", path), true, |connection| {
+            connection
+                .command(&format!("DELE {}", path), &[250])
+                .map(|_| ())
+        })
+    }
+
+    fn remove_dir(&self, path: &str) -> Result<(), String> {
+        self.with_connection(&format!("Failed to delete {}", path), true, |connection| {
+            connection
+                .command(&format!("RMD {}", path), &[250])
+                .map(|_| ())
+        })
+    }
+
+    fn rename(&self, from: &str, to: &str) -> Result<(), String> {
+        let context = format!("Failed to rename {} to {}", from, to);
+        self.with_connection(&context, true, |connection| {
+            connection.command(&format!("RNFR {}", from), &[350])?;
+            connection
+                .command(&format!("RNTO {}", to), &[250])
+                .map(|_| ())
+        })
+    }
+
+    fn disconnect(&self) {
+        if let Ok(mut connection) = self.connection.lock() {
+            if let Some(mut open) = connection.take() {
+                let _ = open.command("QUIT", &[221]);
+                open.control.into_inner().close();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_ftp {
+    use super::*;
+    use crate::remote::test_servers::{FtpTestServer, TEST_PASSWORD, TEST_USER};
+    use crate::remote::RemoteProtocol;
+    use std::fs;
+
+    fn config(server: &FtpTestServer, protocol: RemoteProtocol) -> RemoteSessionConfig {
+        RemoteSessionConfig {
+            protocol,
+            host: "127.0.0.1".to_string(),
+            port: Some(server.port()),
+            username: TEST_USER.to_string(),
+            password: TEST_PASSWORD.to_string(),
+            base_path: None,
+            region: None,
+            accept_invalid_certificates: true,
+        }
+    }
+
+    fn read_all(session: &FtpSession, path: &str, offset: u64, limit: Option<u64>) -> String {
+        let mut content = Vec::new();
+        session
+            .read_file(path, offset, limit, &mut content)
+            .unwrap();
+        String::from_utf8(content).unwrap()
+    }
+
+    fn check_operations(session: &FtpSession, server: &FtpTestServer) {
+        session.create_dir("/docs").unwrap();
+        let content = b"first line\nsecond line\n";
+        let written = session
+            .write_file(
+                "/docs/notes.txt",
+                0,
+                &mut &content[..],
+                content.len() as u64,
+            )
+            .unwrap();
+        assert_eq!(written, content.len() as u64);
+        assert_eq!(
+            fs::read(server.root().join("docs/notes.txt")).unwrap(),
+            content
+        );
+
+        let listing = session.list_dir("/docs").unwrap();
+        assert_eq!(listing.len(), 1);
+        assert_eq!(listing[0].path, PathBuf::from("/docs/notes.txt"));
+        assert_eq!(listing[0].stat.size, Some(content.len() as u64));
+        assert!(listing[0].stat.is_file());
+        assert!(session.stat("/docs").unwrap().is_dir());
+
+        assert_eq!(
+            read_all(session, "/docs/notes.txt", 0, None),
+            "first line\nsecond line\n"
+        );
+        assert_eq!(read_all(session, "/docs/notes.txt", 6, Some(4)), "line");
+        // The connection is still in sync after a transfer stopped early
+        assert_eq!(
+            read_all(session, "/docs/notes.txt", 11, None),
+            "second line\n"
+        );
+
+        session
+            .write_file(
+                "/docs/notes.txt",
+                content.len() as u64,
+                &mut &b"third\n"[..],
+                6,
+            )
+            .unwrap();
+        assert_eq!(session.stat("/docs/notes.txt").unwrap().size, Some(29));
+
+        session
+            .rename("/docs/notes.txt", "/docs/renamed.txt")
+            .unwrap();
+        assert!(server.root().join("docs/renamed.txt").exists());
+        session.remove_file("/docs/renamed.txt").unwrap();
+        session.remove_dir("/docs").unwrap();
+        assert!(!server.root().join("docs").exists());
+
+        let error = session.stat("/missing").unwrap_err();
+        assert!(error.contains("405"), "{}", error);
+    }
+
+    #[test]
+    fn ftp_session_operations_test() {
+        let server = FtpTestServer::start(false);
+        let session = FtpSession::connect(config(&server, RemoteProtocol::Ftp)).unwrap();
+        check_operations(&session, &server);
+        session.disconnect();
+        assert!(!session.is_connected());
+    }
+
+    #[test]
+    fn ftps_session_operations_test() {
+        let server = FtpTestServer::start(true);
+        let session = FtpSession::connect(config(&server, RemoteProtocol::Ftps)).unwrap();
+        check_operations(&session, &server);
+
+        // Certificates are checked unless invalid ones are accepted
+        let mut strict = config(&server, RemoteProtocol::Ftps);
+        strict.accept_invalid_certificates = false;
+        assert!(FtpSession::connect(strict).is_err());
+    }
+
+    #[test]
+    fn ftp_session_reconnects_test() {
+        let server = FtpTestServer::start(false);
+        let session = FtpSession::connect(config(&server, RemoteProtocol::Ftp)).unwrap();
+        fs::write(server.root().join("a.txt"), "a").unwrap();
+        server.drop_connections();
+        assert_eq!(session.list_dir("/").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn ftp_wrong_password_test() {
+        let server = FtpTestServer::start(false);
+        let mut wrong = config(&server, RemoteProtocol::Ftp);
+        wrong.password = "wrong".to_string();
+        let error = FtpSession::connect(wrong).err().unwrap();
+        assert!(error.contains("401"), "{}", error);
+    }
+
+    #[test]
+    fn parse_listing_lines_test() {
+        let (name, stat, _) = parse_mlsd_line(
+            "type=file;size=1024;modify=20231114221320;UNIX.mode=0640;UNIX.uid=1000; my notes.txt",
+            false,
+        )
+        .unwrap();
+        assert_eq!(name, "my notes.txt");
+        assert_eq!(stat.size, Some(1024));
+        assert_eq!(stat.mtime, Some(1_700_000_000));
+        assert_eq!(stat.perm, Some(0o100640));
+        assert_eq!(stat.uid, Some(1000));
+        assert!(parse_mlsd_line("type=cdir;modify=20231114221320; .", false).is_none());
+        let (_, stat, target) =
+            parse_mlsd_line("type=OS.unix=slink:/srv/releases/42; current", false).unwrap();
+        assert!(stat.file_type().is_symlink());
+        assert_eq!(target.as_deref(), Some("/srv/releases/42"));
+
+        let now = NaiveDate::from_ymd_opt(2024, 3, 1)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        let (name, stat, _) = parse_list_line(
+            "drwxr-sr-x    2 alice    staff        4096 Dec 24 18:30 my photos",
+            now,
+        )
+        .unwrap();
+        assert_eq!(name, "my photos");
+        assert_eq!(stat.perm, Some(0o42755));
+        // Dates without a year after today are from the year before
+        assert_eq!(stat.mtime, Some(1_703_442_600));
+        let (name, stat, target) = parse_list_line(
+            "lrwxrwxrwx 1 alice staff 11 Jan  2  2020 current -> releases/42",
+            now,
+        )
+        .unwrap();
+        assert_eq!(
+            (name.as_str(), target.as_deref()),
+            ("current", Some("releases/42"))
+        );
+        assert_eq!(stat.mtime, Some(1_577_923_200));
+        assert!(parse_list_line("total 12", now).is_none());
+
+        assert_eq!(
+            parse_epsv("Entering Extended Passive Mode (|||6446|)"),
+            Some(6446)
+        );
+        assert_eq!(
+            parse_pasv("Entering Passive Mode (192,168,1,2,19,137)"),
+            Some(5001)
+        );
+        assert_eq!(parse_pasv("Entering Passive Mode"), None);
+    }
+}
@@ -0,0 +1,221 @@
+//! The HTTP client of the WebDAV and S3 sessions, a thin layer over reqwest's blocking client.
+//! A session keeps one client, which reuses its connections between requests.
+
+use super::net::{CONNECT_TIMEOUT, IO_TIMEOUT};
+use reqwest::blocking::{Body as RequestBody, Client, RequestBuilder};
+use reqwest::Method;
+use std::io::{self, Read};
+use std::sync::mpsc::{sync_channel, Receiver};
+
+/// Size of the pieces a streamed body is handed to the client in.
+const CHUNK_SIZE: usize = 256 * 1024;
+
+pub(super) enum Body<'a> {
+    Empty,
+    Bytes(&'a [u8]),
+    /// A reader with the number of bytes to send from it.
+    Reader(&'a mut dyn Read, u64),
+}
+
+pub(super) struct Response {
+    pub(super) status: u16,
+    pub(super) reason: String,
+    pub(super) headers: Vec<(String, String)>,
+    pub(super) body: Box<dyn Read>,
+}
+
+impl Response {
+    pub(super) fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// The body of a streamed request. The chunks are read from the caller's reader on its own
+/// thread, because the client only accepts readers it can own.
+struct ChannelReader {
+    receiver: Receiver<io::Result<Vec<u8>>>,
+    chunk: Vec<u8>,
+    position: usize,
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.chunk.len() {
+            match self.receiver.recv() {
+                Ok(chunk) => {
+                    self.chunk = chunk?;
+                    self.position = 0;
+                }
+                Err(_) => return Ok(0),
+            }
+        }
+        let read = buf.len().min(self.chunk.len() - self.position);
+        buf[..read].copy_from_slice(&self.chunk[self.position..self.position + read]);
+        self.position += read;
+        Ok(read)
+    }
+}
+
+pub(super) fn parse_http_date(value: &str) -> Option<u64> {
+    let time = chrono::DateTime::parse_from_rfc2822(value.trim()).ok()?;
+    u64::try_from(time.timestamp()).ok()
+}
+
+/// The `Host` header of a server, without the port if it is the default one.
+pub(super) fn host_header(host: &str, port: u16, default_port: u16) -> String {
+    let host = match host.contains(':') {
+        true => format!("[{}]", host),
+        false => host.to_string(),
+    };
+    match port != default_port {
+        true => format!("{}:{}", host, port),
+        false => host,
+    }
+}
+
+/// Builds the client of a session. Redirects are not followed, because a redirected S3
+/// request would no longer match its signature.
+pub(super) fn client(accept_invalid_certificates: bool) -> Result<Client, String> {
+    Client::builder()
+        .user_agent("explr")
+        .redirect(reqwest::redirect::Policy::none())
+        .connect_timeout(CONNECT_TIMEOUT)
+        // Set per request, a streamed upload may take longer than any fixed timeout
+        .timeout(None)
+        .danger_accept_invalid_certs(accept_invalid_certificates)
+        .build()
+        .map_err(|e| format!("Failed to create the HTTP client: {}", error_message(&e)))
+}
+
+/// The message of a client error with all of its causes, the top level one alone rarely
+/// says what went wrong.
+fn error_message(error: &reqwest::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = std::error::Error::source(error);
+    while let Some(cause) = source {
+        message.push_str(&format!(": {}", cause));
+        source = cause.source();
+    }
+    message
+}
+
+fn into_response(response: reqwest::blocking::Response) -> Response {
+    Response {
+        status: response.status().as_u16(),
+        reason: response
+            .status()
+            .canonical_reason()
+            .unwrap_or("")
+            .to_string(),
+        headers: response
+            .headers()
+            .iter()
+            .map(|(name, value)| {
+                (
+                    name.to_string(),
+                    String::from_utf8_lossy(value.as_bytes()).to_string(),
+                )
+            })
+            .collect(),
+        body: Box::new(response),
+    }
+}
+
+/// Sends the request while the calling thread reads `length` bytes of the body from `reader`.
+fn send_streamed(
+    request: RequestBuilder,
+    reader: &mut dyn Read,
+    length: u64,
+) -> io::Result<Response> {
+    let (sender, receiver) = sync_channel(4);
+    let body = ChannelReader {
+        receiver,
+        chunk: Vec::new(),
+        position: 0,
+    };
+    let request = request.body(RequestBody::sized(body, length));
+
+    std::thread::scope(|scope| {
+        let sending = scope.spawn(move || request.send());
+        let mut buffer = vec![0u8; CHUNK_SIZE];
+        let mut remaining = length;
+        let mut read_error = None;
+        while remaining > 0 {
+            let wanted = (buffer.len() as u64).min(remaining) as usize;
+            match reader.read(&mut buffer[..wanted]) {
+                Ok(0) => {
+                    read_error = Some(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        format!(
+                            "Only {} of {} bytes could be read",
+                            length - remaining,
+                            length
+                        ),
+                    ));
+                    break;
+                }
+                Ok(read) => {
+                    // The request ended early, e.g. the server answered before the body
+                    if sender.send(Ok(buffer[..read].to_vec())).is_err() {
+                        break;
+                    }
+                    remaining -= read as u64;
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    read_error = Some(e);
+                    break;
+                }
+            }
+        }
+        if let Some(e) = &read_error {
+            // Aborts the request instead of sending a truncated body
+            let _ = sender.send(Err(io::Error::new(e.kind(), e.to_string())));
+        }
+        drop(sender);
+
+        let result = sending
+            .join()
+            .map_err(|_| io::Error::other("The request thread panicked"))?;
+        match read_error {
+            Some(e) => Err(e),
+            None => result
+                .map(into_response)
+                .map_err(|e| io::Error::other(error_message(&e))),
+        }
+    })
+}
+
+/// Sends a request and returns the response with its unread body. `url` is the full URL with
+/// the encoded path and query, `headers` are sent in addition to the ones the client adds
+/// (`Host`, `User-Agent` and `Content-Length`).
+pub(super) fn send_request(
+    client: &Client,
+    method: &str,
+    url: &str,
+    headers: &[(&str, String)],
+    body: Body,
+) -> io::Result<Response> {
+    let method = Method::from_bytes(method.as_bytes()).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Invalid method: {}", method),
+        )
+    })?;
+    let mut request = client.request(method, url);
+    for (name, value) in headers {
+        request = request.header(*name, value.as_str());
+    }
+    let request = match body {
+        Body::Empty => request.timeout(IO_TIMEOUT),
+        Body::Bytes(bytes) => request.timeout(IO_TIMEOUT).body(bytes.to_vec()),
+        Body::Reader(reader, length) => return send_streamed(request, reader, length),
+    };
+    request
+        .send()
+        .map(into_response)
+        .map_err(|e| io::Error::other(error_message(&e)))
+}
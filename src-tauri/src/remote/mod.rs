@@ -0,0 +1,561 @@
+//! # Remote file systems
+//!
+//! The protocols of remote sessions behind one interface, so listing, preview, transfers,
+//! renaming and deleting work the same way for every kind of server.
+//!
+//! | Protocol        | Listing     | Resume downloads | Resume uploads | Permissions |
+//! |-----------------|-------------|------------------|----------------|-------------|
+//! | SFTP            | readdir     | yes              | yes            | yes         |
+//! | FTP, FTPS       | MLSD, LIST  | REST             | APPE           | if listed   |
+//! | WebDAV, WebDAVS | PROPFIND    | Range            | no             | no          |
+//! | S3              | ListObjects | Range            | no             | no          |
+//!
+//! FTPS is explicit FTPS, the connection starts as plain FTP on port 21 and is upgraded with
+//! `AUTH TLS` before logging in. Data connections are protected as well (`PROT P`).
+//!
+//! S3 sessions show the buckets as the directories of the root and the `/` separated prefixes of
+//! the object keys as directories inside a bucket. Objects are uploaded, downloaded and copied
+//! in parts, so large objects don't need one long request.
+//!
+//! `open_remote_session` opens sessions of every protocol, SFTP sessions which log in with keys,
+//! the SSH agent or through jump hosts are opened with `open_sftp_session` and kept in their own
+//! state. Both take their ids from the same counter, so a session id names exactly one session
+//! of either kind.
+
+mod ftp;
+mod http;
+mod net;
+mod s3;
+mod sftp;
+#[cfg(test)]
+pub(crate) mod test_servers;
+pub mod transfer;
+mod webdav;
+
+pub use ftp::FtpSession;
+pub use s3::S3Session;
+pub(crate) use sftp::{sftp_error, sftp_io_error, shell_quote};
+pub use webdav::WebDavSession;
+
+use crate::commands::preview_commands::PreviewPayload;
+use crate::error_handling::{Error, ErrorCode};
+use crate::log_error;
+use crate::models::{format_system_time, permission_string_from_mode, Directory, Entries, File};
+use crate::state::sftp_auth_data::SftpAuthContext;
+use crate::state::sftp_session_data::SftpSession;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use ssh2::FileStat;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+
+/// File type bits of `FileStat::perm`, the attributes of all protocols are mapped onto them.
+pub(crate) const S_IFMT: u32 = 0o170000;
+pub(crate) const S_IFDIR: u32 = 0o040000;
+pub(crate) const S_IFREG: u32 = 0o100000;
+pub(crate) const S_IFLNK: u32 = 0o120000;
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum RemoteProtocol {
+    Sftp,
+    Ftp,
+    /// Explicit FTPS, plain FTP upgraded with `AUTH TLS`.
+    Ftps,
+    WebDav,
+    /// WebDAV over HTTPS.
+    WebDavs,
+    /// S3 compatible object storage over HTTPS.
+    S3,
+    /// S3 compatible object storage over plain HTTP, e.g. a local MinIO.
+    S3Http,
+}
+
+impl RemoteProtocol {
+    pub fn default_port(self) -> u16 {
+        match self {
+            RemoteProtocol::Sftp => 22,
+            RemoteProtocol::Ftp | RemoteProtocol::Ftps => 21,
+            RemoteProtocol::WebDav | RemoteProtocol::S3Http => 80,
+            RemoteProtocol::WebDavs | RemoteProtocol::S3 => 443,
+        }
+    }
+
+    pub fn uses_tls(self) -> bool {
+        matches!(
+            self,
+            RemoteProtocol::Ftps | RemoteProtocol::WebDavs | RemoteProtocol::S3
+        )
+    }
+}
+
+/// Where to connect a session to and how to log in. S3 sessions log in with the access key id
+/// as `username` and the secret access key as `password`.
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
+pub struct RemoteSessionConfig {
+    pub protocol: RemoteProtocol,
+    pub host: String,
+    /// The port of the server, the default port of the protocol if not set.
+    #[serde(default)]
+    pub port: Option<u16>,
+    /// Empty for anonymous FTP.
+    #[serde(default)]
+    pub username: String,
+    #[serde(default)]
+    pub password: String,
+    /// WebDAV only, the path of the WebDAV root on the server, e.g.
+    /// `/remote.php/dav/files/alice` for Nextcloud.
+    #[serde(default)]
+    pub base_path: Option<String>,
+    /// S3 only, the region requests are signed for, `us-east-1` if not set. MinIO accepts
+    /// `us-east-1` unless it was configured with another region.
+    #[serde(default)]
+    pub region: Option<String>,
+    /// Accepts self-signed and otherwise invalid certificates for FTPS, WebDAVS and S3.
+    #[serde(default)]
+    pub accept_invalid_certificates: bool,
+}
+
+impl RemoteSessionConfig {
+    pub fn port(&self) -> u16 {
+        self.port.unwrap_or(self.protocol.default_port())
+    }
+}
+
+impl std::fmt::Debug for RemoteSessionConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RemoteSessionConfig")
+            .field("protocol", &self.protocol)
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("username", &self.username)
+            .field("base_path", &self.base_path)
+            .field("region", &self.region)
+            .field(
+                "accept_invalid_certificates",
+                &self.accept_invalid_certificates,
+            )
+            .finish_non_exhaustive()
+    }
+}
+
+/// The server a session is connected to, without its credentials.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct RemoteEndpoint {
+    pub protocol: RemoteProtocol,
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+}
+
+/// An entry of a remote listing. The attributes of every protocol are mapped onto the SFTP
+/// attributes, protocols without permissions only set the file type bits of `perm`.
+#[derive(Debug, Clone)]
+pub struct RemoteEntry {
+    pub path: PathBuf,
+    /// The attributes of the entry itself, of the link for symlinks.
+    pub stat: FileStat,
+    pub symlink_target: Option<String>,
+    /// The attributes of the symlink target, `None` for other entries and broken links.
+    pub target_stat: Option<FileStat>,
+}
+
+/// The operations every protocol supports. Paths are absolute paths on the server with `/`
+/// separators. Errors are `error_handling::Error` as json.
+pub trait RemoteFileSystem: Send + Sync {
+    fn endpoint(&self) -> RemoteEndpoint;
+
+    /// Whether the session currently has an open connection. Sessions without a persistent
+    /// connection are always connected.
+    fn is_connected(&self) -> bool;
+
+    /// Lists a directory without `.` and `..`.
+    fn list_dir(&self, path: &str) -> Result<Vec<RemoteEntry>, String>;
+
+    /// The attributes of a path, following symlinks.
+    fn stat(&self, path: &str) -> Result<FileStat, String>;
+
+    /// Writes the content of a file starting at `offset` into `writer`, at most `limit` bytes.
+    /// Returns the number of written bytes.
+    fn read_file(
+        &self,
+        path: &str,
+        offset: u64,
+        limit: Option<u64>,
+        writer: &mut dyn Write,
+    ) -> Result<u64, String>;
+
+    /// Writes `length` bytes of `reader` into a file. With an `offset` they are appended to the
+    /// file, which must be `offset` bytes long, otherwise the file is created or replaced.
+    fn write_file(
+        &self,
+        path: &str,
+        offset: u64,
+        reader: &mut dyn Read,
+        length: u64,
+    ) -> Result<u64, String>;
+
+    /// Whether `write_file` can append to an existing file, so uploads can be resumed.
+    fn supports_append(&self) -> bool;
+
+    fn create_dir(&self, path: &str) -> Result<(), String>;
+
+    fn remove_file(&self, path: &str) -> Result<(), String>;
+
+    /// Removes an empty directory. WebDAV removes directories with their content.
+    fn remove_dir(&self, path: &str) -> Result<(), String>;
+
+    /// Renames a file or directory. Depending on the server an existing destination is
+    /// replaced or the rename fails.
+    fn rename(&self, from: &str, to: &str) -> Result<(), String>;
+
+    /// Closes the connection, the session can't be used afterwards.
+    fn disconnect(&self);
+
+    /// The SHA256 of a file, computed on the server. `None` if the server can't hash its files,
+    /// only SFTP servers which allow running `sha256sum` or `shasum` can.
+    fn sha256(&self, _path: &str) -> Result<Option<String>, String> {
+        Ok(None)
+    }
+
+    /// Sets the modification time and the permissions of a file where the protocol and the
+    /// server allow it. Only SFTP sessions set them, a refusal is ignored.
+    fn set_attributes(&self, _path: &str, _modified: Option<u64>, _permissions: Option<u32>) {}
+
+    /// Copies a file or directory on the server without transferring it. Only S3 sessions copy,
+    /// SFTP sessions copy with `copy_file_sftp` and `copy_directory_sftp`.
+    fn copy(&self, from: &str, _to: &str) -> Result<(), String> {
+        Err(remote_error(
+            ErrorCode::NotImplemented,
+            format!(
+                "{:?} sessions can't copy {} on the server",
+                self.endpoint().protocol,
+                from
+            ),
+        ))
+    }
+
+    /// A URL which allows `method` on a file without credentials until it expires. Only S3
+    /// sessions presign URLs.
+    fn presigned_url(
+        &self,
+        path: &str,
+        _method: &str,
+        _expires_in: Duration,
+    ) -> Result<String, String> {
+        Err(remote_error(
+            ErrorCode::NotImplemented,
+            format!(
+                "{:?} sessions can't presign a URL for {}",
+                self.endpoint().protocol,
+                path
+            ),
+        ))
+    }
+}
+
+static NEXT_SESSION_ID: AtomicU64 = AtomicU64::new(1);
+
+/// The id of a new SFTP, FTP, WebDAV or S3 session.
+pub fn next_session_id() -> u64 {
+    NEXT_SESSION_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+pub(crate) fn remote_error(code: ErrorCode, message: String) -> String {
+    log_error!("{}", message);
+    Error::new(code, message).to_json()
+}
+
+/// Connects and logs in to an SFTP, FTP, WebDAV or S3 server. SFTP sessions log in with the
+/// password and check the host key with `sftp_auth`.
+pub fn connect(
+    config: RemoteSessionConfig,
+    sftp_auth: Arc<SftpAuthContext>,
+) -> Result<Arc<dyn RemoteFileSystem>, String> {
+    match config.protocol {
+        RemoteProtocol::Sftp => {
+            Ok(Arc::new(SftpSession::connect(config, sftp_auth)?) as Arc<dyn RemoteFileSystem>)
+        }
+        RemoteProtocol::Ftp | RemoteProtocol::Ftps => {
+            Ok(Arc::new(FtpSession::connect(config)?) as Arc<dyn RemoteFileSystem>)
+        }
+        RemoteProtocol::WebDav | RemoteProtocol::WebDavs => {
+            Ok(Arc::new(WebDavSession::connect(config)?) as Arc<dyn RemoteFileSystem>)
+        }
+        RemoteProtocol::S3 | RemoteProtocol::S3Http => {
+            Ok(Arc::new(S3Session::connect(config)?) as Arc<dyn RemoteFileSystem>)
+        }
+    }
+}
+
+/// Joins remote paths with `/`, independent of the platform the explorer runs on.
+pub fn join_path(directory: &str, name: &str) -> String {
+    if directory.is_empty() {
+        name.to_string()
+    } else if directory.ends_with('/') {
+        format!("{}{}", directory, name)
+    } else {
+        format!("{}/{}", directory, name)
+    }
+}
+
+/// Attributes of an entry without permissions, only the file type and size are known.
+pub(crate) fn file_stat(file_type: u32, size: Option<u64>, mtime: Option<u64>) -> FileStat {
+    FileStat {
+        size,
+        uid: None,
+        gid: None,
+        perm: Some(file_type),
+        atime: None,
+        mtime,
+    }
+}
+
+fn format_remote_time(seconds: Option<u64>) -> String {
+    seconds
+        .and_then(|seconds| UNIX_EPOCH.checked_add(Duration::from_secs(seconds)))
+        .map_or("1970-01-01 00:00:00".to_string(), format_system_time)
+}
+
+/// Converts a remote listing into the `File` and `Directory` models. Remote servers have no
+/// creation time, `created` is the modification time like for entries inside archives.
+pub fn remote_entries(listing: Vec<RemoteEntry>) -> Entries {
+    let mut directories = Vec::new();
+    let mut files = Vec::new();
+    for entry in listing {
+        let is_symlink = entry.stat.file_type().is_symlink();
+        let stat = entry.target_stat.as_ref().unwrap_or(&entry.stat);
+        let mode = stat.perm.unwrap_or(0);
+        let name = entry
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let path = entry.path.to_string_lossy().to_string();
+        let last_modified = format_remote_time(stat.mtime);
+
+        if stat.is_dir() {
+            directories.push(Directory {
+                name,
+                path,
+                is_symlink,
+                access_rights_as_string: permission_string_from_mode(mode),
+                access_rights_as_number: mode,
+                size_in_bytes: 0,
+                sub_file_count: 0,
+                sub_dir_count: 0,
+                created: last_modified.clone(),
+                last_modified,
+                accessed: format_remote_time(stat.atime),
+                owner_id: stat.uid,
+                group_id: stat.gid,
+                symlink_target: entry.symlink_target,
+            });
+        } else {
+            files.push(File {
+                name,
+                path,
+                is_symlink,
+                access_rights_as_string: permission_string_from_mode(mode),
+                access_rights_as_number: mode,
+                size_in_bytes: stat.size.unwrap_or(0),
+                created: last_modified.clone(),
+                last_modified,
+                accessed: format_remote_time(stat.atime),
+                owner_id: stat.uid,
+                group_id: stat.gid,
+                symlink_target: entry.symlink_target,
+            });
+        }
+    }
+    Entries { directories, files }
+}
+
+/// Removes a file, or a directory with all its content.
+pub fn remove_recursive(fs: &dyn RemoteFileSystem, path: &str) -> Result<(), String> {
+    let stat = fs.stat(path)?;
+    if !stat.is_dir() {
+        return fs.remove_file(path);
+    }
+    for entry in fs.list_dir(path)? {
+        let entry_path = entry.path.to_string_lossy().to_string();
+        // Symlinks are removed themselves, never the directory they point to
+        if entry.stat.is_dir() {
+            remove_recursive(fs, &entry_path)?;
+        } else {
+            fs.remove_file(&entry_path)?;
+        }
+    }
+    fs.remove_dir(path)
+}
+
+pub(crate) fn filename_from_path(path: &str) -> String {
+    if let Some(name) = path.split('/').next_back() {
+        if !name.is_empty() {
+            return name.to_string();
+        }
+    }
+    "file".to_string()
+}
+
+fn detect_mime(path: &str, head: &[u8]) -> Option<&'static str> {
+    if let Some(kind) = infer::get(head) {
+        return Some(kind.mime_type());
+    }
+
+    if let Some(ext) = path.split('.').next_back().map(|s| s.to_lowercase()) {
+        return Some(match ext.as_str() {
+            "md" | "rs" | "ts" | "tsx" | "js" | "jsx" | "json" | "txt" | "log" | "toml"
+            | "yaml" | "yml" | "xml" | "ini" | "csv" => "text/plain",
+            "pdf" => "application/pdf",
+            "png" => "image/png",
+            "jpg" | "jpeg" => "image/jpeg",
+            "gif" => "image/gif",
+            "webp" => "image/webp",
+            "mp4" => "video/mp4",
+            "mov" => "video/quicktime",
+            "mp3" => "audio/mpeg",
+            "wav" => "audio/wav",
+            _ => "application/octet-stream",
+        });
+    }
+    None
+}
+
+fn read_prefix(fs: &dyn RemoteFileSystem, path: &str, max_bytes: u64) -> Result<Vec<u8>, String> {
+    let mut buf = Vec::new();
+    fs.read_file(path, 0, Some(max_bytes), &mut buf)?;
+    Ok(buf)
+}
+
+/// Builds the preview of a remote file or directory, like `build_preview` does for local ones.
+pub fn build_preview(fs: &dyn RemoteFileSystem, file_path: &str) -> Result<PreviewPayload, String> {
+    let name = filename_from_path(file_path);
+
+    // Get file stats to check if it's a directory or file
+    let stat = fs.stat(file_path)?;
+
+    // Handle directories
+    if stat.is_dir() {
+        // Count items (files + dirs, not recursive)
+        let mut item_count = 0;
+        let mut size: u64 = 0;
+        let mut latest_modified: Option<u64> = None;
+
+        if let Ok(entries) = fs.list_dir(file_path) {
+            for entry in entries {
+                item_count += 1;
+                if let Some(entry_size) = entry.stat.size {
+                    size += entry_size;
+                }
+                if let Some(mtime) = entry.stat.mtime {
+                    latest_modified = match latest_modified {
+                        Some(current) if current > mtime => Some(current),
+                        _ => Some(mtime),
+                    };
+                }
+            }
+        }
+
+        // Use folder's own modified time if no children
+        let folder_modified = stat.mtime;
+        let modified_time = latest_modified.or(folder_modified);
+        let modified_str = modified_time.map(|t| {
+            chrono::DateTime::from_timestamp(t as i64, 0)
+                .map(|dt| dt.to_rfc3339())
+                .unwrap_or_else(|| "unknown".to_string())
+        });
+
+        return Ok(PreviewPayload::Folder {
+            name,
+            size,
+            item_count,
+            modified: modified_str,
+        });
+    }
+
+    // Files
+    let bytes = stat.size.unwrap_or(0) as usize;
+    // Read a small head for detection + maybe text
+    let head = read_prefix(fs, file_path, 256 * 1024)?;
+    let mime = detect_mime(file_path, &head).unwrap_or("application/octet-stream");
+
+    // Branch by mime top-level type - exactly like original
+    if mime.starts_with("image/") {
+        // Encode entire file only if small; else just the head (fast path)
+        let cap = 6 * 1024 * 1024;
+        let data = if bytes <= cap {
+            read_prefix(fs, file_path, cap as u64)?
+        } else {
+            head.clone()
+        };
+        let data_uri = format!(
+            "data:{};base64,{}",
+            mime,
+            base64::engine::general_purpose::STANDARD.encode(data)
+        );
+        return Ok(PreviewPayload::Image {
+            name,
+            data_uri,
+            bytes,
+        });
+    }
+
+    if mime == "application/pdf" {
+        // Encode entire file only if small; else just the head (fast path)
+        let cap = 12 * 1024 * 1024; // Allow larger PDFs than images
+        let data = if bytes <= cap {
+            read_prefix(fs, file_path, cap as u64)?
+        } else {
+            head.clone()
+        };
+        let data_uri = format!(
+            "data:{};base64,{}",
+            mime,
+            base64::engine::general_purpose::STANDARD.encode(data)
+        );
+        return Ok(PreviewPayload::Pdf {
+            name,
+            data_uri,
+            bytes,
+        });
+    }
+
+    if mime.starts_with("video/") {
+        // Remote videos are treated as unknown since we can't stream remote files
+        return Ok(PreviewPayload::Unknown { name });
+    }
+
+    if mime.starts_with("audio/") {
+        // Remote audio is treated as unknown since we can't stream remote files
+        return Ok(PreviewPayload::Unknown { name });
+    }
+
+    // Heuristic: treat smallish or text‑ish files as text
+    let looks_texty = mime.starts_with("text/")
+        || head
+            .iter()
+            .all(|&b| b == 9 || b == 10 || b == 13 || (32..0xF5).contains(&b));
+    if looks_texty || bytes <= 2 * 1024 * 1024 {
+        let mut det = chardetng::EncodingDetector::new();
+        det.feed(&head, true);
+        let enc = det.guess(None, true);
+        let (cow, _, _) = enc.decode(&head);
+        let mut text = cow.to_string();
+        let mut truncated = false;
+        if text.len() > 200_000 {
+            text.truncate(200_000);
+            text.push_str("\n…(truncated)");
+            truncated = true;
+        }
+        return Ok(PreviewPayload::Text {
+            name,
+            text,
+            truncated,
+        });
+    }
+
+    Ok(PreviewPayload::Unknown { name })
+}
@@ -0,0 +1,114 @@
+//! TCP and TLS connections shared by the FTP and WebDAV clients.
+
+use openssl::ssl::{SslConnector, SslMethod, SslSession, SslStream, SslVerifyMode};
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+pub(super) const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// How long a read or write may block before the connection counts as lost.
+pub(super) const IO_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A connection which may be upgraded to TLS.
+pub(super) enum Stream {
+    Plain(TcpStream),
+    Tls(Box<SslStream<TcpStream>>),
+}
+
+impl Stream {
+    pub(super) fn tcp(&self) -> &TcpStream {
+        match self {
+            Stream::Plain(stream) => stream,
+            Stream::Tls(stream) => stream.get_ref(),
+        }
+    }
+
+    /// The TLS session, for resuming it on the data connections of FTPS.
+    pub(super) fn tls_session(&self) -> Option<SslSession> {
+        match self {
+            Stream::Plain(_) => None,
+            Stream::Tls(stream) => stream.ssl().session().map(|session| session.to_owned()),
+        }
+    }
+
+    /// Closes the connection in both directions, TLS connections send their close notify first.
+    pub(super) fn close(self) {
+        match self {
+            Stream::Plain(stream) => {
+                let _ = stream.shutdown(Shutdown::Both);
+            }
+            Stream::Tls(mut stream) => {
+                let _ = stream.shutdown();
+                let _ = stream.get_ref().shutdown(Shutdown::Both);
+            }
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Plain(stream) => stream.read(buf),
+            Stream::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Plain(stream) => stream.write(buf),
+            Stream::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Plain(stream) => stream.flush(),
+            Stream::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+/// Connects to the first reachable address of a host.
+pub(super) fn connect_tcp(host: &str, port: u16) -> io::Result<TcpStream> {
+    let mut last_error = io::Error::new(io::ErrorKind::NotFound, "Host has no address");
+    for address in (host, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&address, CONNECT_TIMEOUT) {
+            Ok(stream) => {
+                stream.set_read_timeout(Some(IO_TIMEOUT))?;
+                stream.set_write_timeout(Some(IO_TIMEOUT))?;
+                return Ok(stream);
+            }
+            Err(err) => last_error = err,
+        }
+    }
+    Err(last_error)
+}
+
+pub(super) fn tls_connector(accept_invalid_certificates: bool) -> io::Result<SslConnector> {
+    let mut builder = SslConnector::builder(SslMethod::tls_client()).map_err(io::Error::other)?;
+    if accept_invalid_certificates {
+        builder.set_verify(SslVerifyMode::NONE);
+    }
+    Ok(builder.build())
+}
+
+/// Starts TLS on a connection, resuming `session` if given.
+pub(super) fn start_tls(
+    connector: &SslConnector,
+    host: &str,
+    stream: TcpStream,
+    session: Option<&SslSession>,
+) -> io::Result<Stream> {
+    let mut configuration = connector.configure().map_err(io::Error::other)?;
+    if let Some(session) = session {
+        // SAFETY: the session was created by a connection of the same connector
+        unsafe { configuration.set_session(session) }.map_err(io::Error::other)?;
+    }
+    configuration
+        .connect(host, stream)
+        .map(|stream| Stream::Tls(Box::new(stream)))
+        .map_err(|e| io::Error::new(io::ErrorKind::ConnectionAborted, e.to_string()))
+}
@@ -0,0 +1,293 @@
+//! SFTP sessions behind the common interface. `connect` logs in with a password, sessions
+//! with keys, the SSH agent or jump hosts are opened with `open_sftp_session`.
+
+use super::{
+    remote_error, RemoteEndpoint, RemoteEntry, RemoteFileSystem, RemoteProtocol,
+    RemoteSessionConfig,
+};
+use crate::error_handling::ErrorCode;
+use crate::log_error;
+use crate::state::sftp_auth_data::SftpAuthContext;
+use crate::state::sftp_session_data::{
+    SftpConnection, SftpConnectionConfig, SftpCredentials, SftpSession,
+};
+use ssh2::{FileStat, OpenFlags, OpenType};
+use std::cell::{Cell, RefCell};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Status codes of SFTP responses, see draft-ietf-secsh-filexfer-02.
+const SSH_FX_NO_SUCH_FILE: i32 = 2;
+const SSH_FX_PERMISSION_DENIED: i32 = 3;
+const SSH_FX_NO_SUCH_PATH: i32 = 10;
+const SSH_FX_FILE_ALREADY_EXISTS: i32 = 11;
+
+/// Maps an error of the SFTP channel onto the error codes of the other protocols.
+pub(crate) fn sftp_error(context: &str, error: ssh2::Error) -> String {
+    let code = match error.code() {
+        ssh2::ErrorCode::SFTP(SSH_FX_NO_SUCH_FILE | SSH_FX_NO_SUCH_PATH) => {
+            ErrorCode::ResourceNotFound
+        }
+        ssh2::ErrorCode::SFTP(SSH_FX_PERMISSION_DENIED) => ErrorCode::Unauthorized,
+        ssh2::ErrorCode::SFTP(SSH_FX_FILE_ALREADY_EXISTS) => ErrorCode::ResourceAlreadyExists,
+        _ => ErrorCode::InternalError,
+    };
+    remote_error(code, format!("{}: {}", context, error.message()))
+}
+
+/// An error while reading or writing an open remote file.
+pub(crate) fn sftp_io_error(context: &str, error: io::Error) -> String {
+    remote_error(ErrorCode::InternalError, format!("{}: {}", context, error))
+}
+
+/// An operation which moved data must not run again on a new connection, it would write the
+/// data a second time. Fails if `started` is already set, otherwise sets it.
+fn start_once(started: &Cell<bool>, path: &str) -> Result<(), String> {
+    if started.replace(true) {
+        return Err(remote_error(
+            ErrorCode::InternalError,
+            format!("The connection dropped during the transfer of {}", path),
+        ));
+    }
+    Ok(())
+}
+
+/// Quotes an argument for the POSIX shell the server runs commands in.
+pub(crate) fn shell_quote(argument: &str) -> String {
+    format!("'{}'", argument.replace('\'', "'\\''"))
+}
+
+/// Reads the hash from the output of `sha256sum` or `shasum`. File names with special
+/// characters make `sha256sum` prefix the line with a backslash.
+fn parse_hash_output(output: &str) -> Option<String> {
+    let hash = output
+        .split_whitespace()
+        .next()?
+        .trim_start_matches('\\')
+        .to_lowercase();
+    (hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit())).then_some(hash)
+}
+
+/// Hashes a file on the server, `None` if the server does not allow running the tools.
+fn remote_sha256(session: &SftpSession, path: &str) -> Result<Option<String>, String> {
+    let quoted = shell_quote(path);
+    for command in [
+        format!("sha256sum -- {}", quoted),
+        format!("shasum -a 256 -- {}", quoted),
+    ] {
+        if let Some(hash) = session
+            .exec(&command)?
+            .as_deref()
+            .and_then(parse_hash_output)
+        {
+            return Ok(Some(hash));
+        }
+    }
+    Ok(None)
+}
+
+impl SftpSession {
+    /// Connects and logs in with the password of `config`. The host key is checked against
+    /// the known_hosts file of `auth` like for sessions opened with `open_sftp_session`.
+    pub fn connect(
+        config: RemoteSessionConfig,
+        auth: Arc<SftpAuthContext>,
+    ) -> Result<Self, String> {
+        let config = SftpConnectionConfig {
+            port: config.port(),
+            host: config.host,
+            username: config.username,
+            credentials: SftpCredentials::Password {
+                password: config.password,
+            },
+            proxy_jump: Vec::new(),
+        };
+        let connection = SftpConnection::open(&config, &auth).map_err(|e| {
+            log_error!(
+                "Failed to connect to {}:{}: {:?}",
+                config.host,
+                config.port,
+                e
+            );
+            e.to_error().to_json()
+        })?;
+        Ok(SftpSession::new(config, auth, connection))
+    }
+}
+
+impl RemoteFileSystem for SftpSession {
+    fn endpoint(&self) -> RemoteEndpoint {
+        let config = self.config();
+        RemoteEndpoint {
+            protocol: RemoteProtocol::Sftp,
+            host: config.host.clone(),
+            port: config.port,
+            username: config.username.clone(),
+        }
+    }
+
+    fn is_connected(&self) -> bool {
+        SftpSession::is_connected(self)
+    }
+
+    fn list_dir(&self, path: &str) -> Result<Vec<RemoteEntry>, String> {
+        self.with_sftp(|sftp| {
+            let entries = sftp
+                .readdir(Path::new(path))
+                .map_err(|e| sftp_error(&format!("Failed to list {}", path), e))?;
+            // The listing doesn't follow symlinks, their target decides how they are shown
+            Ok(entries
+                .into_iter()
+                .map(|(path, stat)| {
+                    let is_symlink = stat.file_type().is_symlink();
+                    RemoteEntry {
+                        symlink_target: is_symlink
+                            .then(|| sftp.readlink(&path).ok())
+                            .flatten()
+                            .map(|target| target.to_string_lossy().to_string()),
+                        target_stat: is_symlink.then(|| sftp.stat(&path).ok()).flatten(),
+                        path,
+                        stat,
+                    }
+                })
+                .collect())
+        })
+    }
+
+    fn stat(&self, path: &str) -> Result<FileStat, String> {
+        self.with_sftp(|sftp| {
+            sftp.stat(Path::new(path))
+                .map_err(|e| sftp_error(&format!("Failed to read {}", path), e))
+        })
+    }
+
+    fn read_file(
+        &self,
+        path: &str,
+        offset: u64,
+        limit: Option<u64>,
+        writer: &mut dyn Write,
+    ) -> Result<u64, String> {
+        let writer = RefCell::new(writer);
+        let started = Cell::new(false);
+        let context = format!("Failed to download {}", path);
+        self.with_sftp(|sftp| {
+            let mut file = sftp
+                .open(Path::new(path))
+                .map_err(|e| sftp_error(&context, e))?;
+            file.seek(SeekFrom::Start(offset))
+                .map_err(|e| sftp_io_error(&context, e))?;
+            start_once(&started, path)?;
+            let mut writer = writer.borrow_mut();
+            io::copy(&mut file.take(limit.unwrap_or(u64::MAX)), &mut **writer)
+                .map_err(|e| sftp_io_error(&context, e))
+        })
+    }
+
+    fn write_file(
+        &self,
+        path: &str,
+        offset: u64,
+        reader: &mut dyn Read,
+        length: u64,
+    ) -> Result<u64, String> {
+        let reader = RefCell::new(reader);
+        let started = Cell::new(false);
+        let context = format!("Failed to upload {}", path);
+        self.with_sftp(|sftp| {
+            let flags = match offset {
+                0 => OpenFlags::WRITE | OpenFlags::CREATE | OpenFlags::TRUNCATE,
+                _ => OpenFlags::WRITE,
+            };
+            let mut file = sftp
+                .open_mode(Path::new(path), flags, 0o644, OpenType::File)
+                .map_err(|e| sftp_error(&context, e))?;
+            file.seek(SeekFrom::Start(offset))
+                .map_err(|e| sftp_io_error(&context, e))?;
+            start_once(&started, path)?;
+            let mut reader = reader.borrow_mut();
+            io::copy(&mut (&mut **reader).take(length), &mut file)
+                .map_err(|e| sftp_io_error(&context, e))
+        })
+    }
+
+    fn supports_append(&self) -> bool {
+        true
+    }
+
+    fn create_dir(&self, path: &str) -> Result<(), String> {
+        self.with_sftp(|sftp| {
+            sftp.mkdir(Path::new(path), 0o755)
+                .map_err(|e| sftp_error(&format!("Failed to create {}", path), e))
+        })
+    }
+
+    fn remove_file(&self, path: &str) -> Result<(), String> {
+        self.with_sftp(|sftp| {
+            sftp.unlink(Path::new(path))
+                .map_err(|e| sftp_error(&format!("Failed to delete {}", path), e))
+        })
+    }
+
+    fn remove_dir(&self, path: &str) -> Result<(), String> {
+        self.with_sftp(|sftp| {
+            sftp.rmdir(Path::new(path))
+                .map_err(|e| sftp_error(&format!("Failed to delete {}", path), e))
+        })
+    }
+
+    fn rename(&self, from: &str, to: &str) -> Result<(), String> {
+        self.with_sftp(|sftp| {
+            sftp.rename(Path::new(from), Path::new(to), None)
+                .map_err(|e| sftp_error(&format!("Failed to rename {} to {}", from, to), e))
+        })
+    }
+
+    fn disconnect(&self) {
+        SftpSession::disconnect(self)
+    }
+
+    fn sha256(&self, path: &str) -> Result<Option<String>, String> {
+        remote_sha256(self, path)
+    }
+
+    fn set_attributes(&self, path: &str, modified: Option<u64>, permissions: Option<u32>) {
+        let _ = self.with_sftp(|sftp| {
+            sftp.setstat(
+                Path::new(path),
+                FileStat {
+                    size: None,
+                    uid: None,
+                    gid: None,
+                    perm: permissions,
+                    atime: modified,
+                    mtime: modified,
+                },
+            )
+            .map_err(|e| e.to_string())
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests_sftp {
+    use super::*;
+
+    #[test]
+    fn shell_quote_and_hash_output_test() {
+        assert_eq!(shell_quote("it's here"), "'it'\\''s here'");
+
+        let hash = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+        assert_eq!(
+            parse_hash_output(&format!("{}  /srv/empty.txt\n", hash)).as_deref(),
+            Some(hash)
+        );
+        assert_eq!(
+            parse_hash_output(&format!("\\{}  /srv/new\\nline\n", hash.to_uppercase())).as_deref(),
+            Some(hash)
+        );
+        assert_eq!(parse_hash_output("sha256sum: not found"), None);
+        assert_eq!(parse_hash_output(""), None);
+    }
+}
@@ -0,0 +1,1280 @@
+//! Minimal FTP, WebDAV and S3 servers, so the clients are tested against real connections
+//! without a server installed on the machine running the tests.
+
+use super::net::Stream;
+use super::s3::{canonical_query, parse_xml, sha256_hex, Credentials, UNSIGNED_PAYLOAD};
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use openssl::asn1::Asn1Time;
+use openssl::bn::BigNum;
+use openssl::ec::{EcGroup, EcKey};
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkey::PKey;
+use openssl::ssl::{SslAcceptor, SslMethod};
+use openssl::x509::{X509NameBuilder, X509};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tempfile::TempDir;
+
+pub(crate) const TEST_USER: &str = "explorer";
+pub(crate) const TEST_PASSWORD: &str = "secret";
+
+const HREF_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC.remove(b'.').remove(b'-').remove(b'_');
+
+/// A TLS acceptor with a self-signed certificate for `localhost`.
+fn test_acceptor() -> Arc<SslAcceptor> {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+    let mut name = X509NameBuilder::new().unwrap();
+    name.append_entry_by_text("CN", "localhost").unwrap();
+    let name = name.build();
+
+    let mut certificate = X509::builder().unwrap();
+    certificate.set_version(2).unwrap();
+    certificate.set_subject_name(&name).unwrap();
+    certificate.set_issuer_name(&name).unwrap();
+    certificate.set_pubkey(&key).unwrap();
+    let serial = BigNum::from_u32(1).unwrap().to_asn1_integer().unwrap();
+    certificate.set_serial_number(&serial).unwrap();
+    certificate
+        .set_not_before(&Asn1Time::days_from_now(0).unwrap())
+        .unwrap();
+    certificate
+        .set_not_after(&Asn1Time::days_from_now(1).unwrap())
+        .unwrap();
+    certificate.sign(&key, MessageDigest::sha256()).unwrap();
+
+    let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+    acceptor.set_private_key(&key).unwrap();
+    acceptor.set_certificate(&certificate.build()).unwrap();
+    // Session tickets sent after the handshake would be left unread on upload connections,
+    // closing them with unread data resets the connection before the server read everything
+    acceptor.set_num_tickets(0).unwrap();
+    Arc::new(acceptor.build())
+}
+
+fn accept_tls(acceptor: &SslAcceptor, stream: TcpStream) -> io::Result<Stream> {
+    acceptor
+        .accept(stream)
+        .map(|stream| Stream::Tls(Box::new(stream)))
+        .map_err(|e| io::Error::other(e.to_string()))
+}
+
+fn modified_time(metadata: &fs::Metadata) -> DateTime<Utc> {
+    metadata
+        .modified()
+        .map(DateTime::<Utc>::from)
+        .unwrap_or_default()
+}
+
+/// Maps an absolute path of a request onto the root directory.
+fn local_path(root: &Path, path: &str) -> PathBuf {
+    let relative: PathBuf = path
+        .split('/')
+        .filter(|segment| !segment.is_empty() && *segment != "." && *segment != "..")
+        .collect();
+    root.join(relative)
+}
+
+/// An FTP server, with explicit TLS if started with `tls`. It announces MLST and EPSV, so the
+/// `LIST` and `PASV` fallbacks of the client are only covered by the parser tests.
+pub(crate) struct FtpTestServer {
+    port: u16,
+    root: TempDir,
+    connections: Arc<Mutex<Vec<TcpStream>>>,
+}
+
+impl FtpTestServer {
+    pub(crate) fn start(tls: bool) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let root = tempfile::tempdir().unwrap();
+        let connections = Arc::new(Mutex::new(Vec::new()));
+        let acceptor = tls.then(test_acceptor);
+
+        let root_path = root.path().to_path_buf();
+        let accepted = connections.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if let Ok(clone) = stream.try_clone() {
+                    accepted.lock().unwrap().push(clone);
+                }
+                let mut client = FtpClient {
+                    root: root_path.clone(),
+                    acceptor: acceptor.clone(),
+                    user: None,
+                    logged_in: false,
+                    protect: false,
+                    rest: 0,
+                    rename_from: None,
+                    passive: None,
+                };
+                thread::spawn(move || {
+                    let _ = client.serve(stream);
+                });
+            }
+        });
+        Self {
+            port,
+            root,
+            connections,
+        }
+    }
+
+    pub(crate) fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub(crate) fn root(&self) -> &Path {
+        self.root.path()
+    }
+
+    /// Closes all control connections, like a server restart or an idle timeout.
+    pub(crate) fn drop_connections(&self) {
+        for connection in self.connections.lock().unwrap().drain(..) {
+            let _ = connection.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+struct FtpClient {
+    root: PathBuf,
+    acceptor: Option<Arc<SslAcceptor>>,
+    user: Option<String>,
+    logged_in: bool,
+    protect: bool,
+    rest: u64,
+    rename_from: Option<PathBuf>,
+    passive: Option<TcpListener>,
+}
+
+fn reply(control: &mut BufReader<Stream>, text: &str) -> io::Result<()> {
+    let stream = control.get_mut();
+    stream.write_all(format!("{}\r\n", text).as_bytes())?;
+    stream.flush()
+}
+
+/// The MLSD facts of a file, followed by its name.
+fn mlsd_facts(path: &Path, name: &str, follow_links: bool) -> io::Result<String> {
+    let metadata = match follow_links {
+        true => fs::metadata(path)?,
+        false => fs::symlink_metadata(path)?,
+    };
+    let (kind, mode) = if metadata.is_symlink() {
+        let target = fs::read_link(path)?;
+        (format!("OS.unix=slink:{}", target.display()), "0777")
+    } else if metadata.is_dir() {
+        ("dir".to_string(), "0755")
+    } else {
+        ("file".to_string(), "0644")
+    };
+    Ok(format!(
+        "type={};size={};modify={};UNIX.mode={}; {}",
+        kind,
+        metadata.len(),
+        modified_time(&metadata).format("%Y%m%d%H%M%S"),
+        mode,
+        name
+    ))
+}
+
+impl FtpClient {
+    fn serve(&mut self, stream: TcpStream) -> io::Result<()> {
+        let mut control = BufReader::new(Stream::Plain(stream));
+        reply(&mut control, "220 Test server ready")?;
+        loop {
+            let mut line = String::new();
+            if control.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            let line = line.trim_end_matches(['\r', '\n']);
+            let (command, argument) = line.split_once(' ').unwrap_or((line, ""));
+            let command = command.to_uppercase();
+
+            if command == "AUTH" {
+                let Some(acceptor) = self.acceptor.clone() else {
+                    reply(&mut control, "502 TLS is not enabled")?;
+                    continue;
+                };
+                reply(&mut control, "234 Starting TLS")?;
+                let Stream::Plain(stream) = control.into_inner() else {
+                    return Ok(());
+                };
+                control = BufReader::new(accept_tls(&acceptor, stream)?);
+                continue;
+            }
+            if command == "QUIT" {
+                return reply(&mut control, "221 Bye");
+            }
+            let response = self.handle(&command, argument, &mut control)?;
+            reply(&mut control, &response)?;
+        }
+    }
+
+    /// Handles a command and returns the final reply. Transfers send their preliminary reply
+    /// on `control` themselves.
+    fn handle(
+        &mut self,
+        command: &str,
+        argument: &str,
+        control: &mut BufReader<Stream>,
+    ) -> io::Result<String> {
+        match command {
+            "USER" => {
+                self.user = Some(argument.to_string());
+                self.logged_in = false;
+                return Ok("331 Password required".to_string());
+            }
+            "PASS" => {
+                self.logged_in =
+                    self.user.as_deref() == Some(TEST_USER) && argument == TEST_PASSWORD;
+                return Ok(match self.logged_in {
+                    true => "230 Logged in".to_string(),
+                    false => "530 Login incorrect".to_string(),
+                });
+            }
+            "FEAT" => {
+                return Ok(
+                    "211-Features:\r\n MLST type*;size*;modify*;UNIX.mode*;\r\n EPSV\r\n REST STREAM\r\n UTF8\r\n211 End"
+                        .to_string(),
+                )
+            }
+            _ if !self.logged_in => return Ok("530 Please log in".to_string()),
+            _ => {}
+        }
+
+        let path = local_path(&self.root, argument);
+        let response = match command {
+            "PBSZ" | "TYPE" | "OPTS" | "NOOP" => "200 OK".to_string(),
+            "PROT" => {
+                self.protect = argument.eq_ignore_ascii_case("P");
+                "200 OK".to_string()
+            }
+            "EPSV" | "PASV" => {
+                let listener = TcpListener::bind("127.0.0.1:0")?;
+                let port = listener.local_addr()?.port();
+                self.passive = Some(listener);
+                match command {
+                    "EPSV" => format!("229 Entering Extended Passive Mode (|||{}|)", port),
+                    _ => format!(
+                        "227 Entering Passive Mode (127,0,0,1,{},{})",
+                        port / 256,
+                        port % 256
+                    ),
+                }
+            }
+            "REST" => match argument.parse() {
+                Ok(offset) => {
+                    self.rest = offset;
+                    format!("350 Restarting at {}", offset)
+                }
+                Err(_) => "501 Invalid offset".to_string(),
+            },
+            "MLST" => match mlsd_facts(&path, argument, true) {
+                Ok(facts) => format!("250-Listing {}\r\n {}\r\n250 End", argument, facts),
+                Err(_) => format!("550 {}: No such file or directory", argument),
+            },
+            "MLSD" => {
+                let mut names: Vec<String> = match fs::read_dir(&path) {
+                    Ok(entries) => entries
+                        .flatten()
+                        .map(|entry| entry.file_name().to_string_lossy().to_string())
+                        .collect(),
+                    Err(_) => return Ok(format!("550 {}: No such directory", argument)),
+                };
+                names.sort();
+                let listing: String = names
+                    .iter()
+                    .filter_map(|name| mlsd_facts(&path.join(name), name, false).ok())
+                    .map(|facts| format!("{}\r\n", facts))
+                    .collect();
+                self.transfer(control, |data| data.write_all(listing.as_bytes()))?
+            }
+            "RETR" => {
+                let Ok(mut file) = fs::File::open(&path) else {
+                    return Ok(format!("550 {}: No such file", argument));
+                };
+                file.seek(SeekFrom::Start(std::mem::take(&mut self.rest)))?;
+                self.transfer(control, |data| io::copy(&mut file, data).map(|_| ()))?
+            }
+            "STOR" | "APPE" => {
+                let file = fs::OpenOptions::new()
+                    .create(true)
+                    .write(true)
+                    .append(command == "APPE")
+                    .truncate(command == "STOR")
+                    .open(&path);
+                let Ok(mut file) = file else {
+                    return Ok(format!("553 {}: Can't create file", argument));
+                };
+                self.transfer(control, |data| io::copy(data, &mut file).map(|_| ()))?
+            }
+            "MKD" => match fs::create_dir(&path) {
+                Ok(_) => format!("257 \"{}\" created", argument),
+                Err(_) => format!("550 {}: Can't create directory", argument),
+            },
+            "RMD" => match fs::remove_dir(&path) {
+                Ok(_) => "250 Directory removed".to_string(),
+                Err(_) => format!("550 {}: Can't remove directory", argument),
+            },
+            "DELE" => match fs::remove_file(&path) {
+                Ok(_) => "250 File removed".to_string(),
+                Err(_) => format!("550 {}: Can't remove file", argument),
+            },
+            "RNFR" if path.exists() => {
+                self.rename_from = Some(path);
+                "350 Ready for RNTO".to_string()
+            }
+            "RNFR" => format!("550 {}: No such file", argument),
+            "RNTO" => match self.rename_from.take().map(|from| fs::rename(from, &path)) {
+                Some(Ok(_)) => "250 Renamed".to_string(),
+                Some(Err(_)) => format!("553 {}: Can't rename", argument),
+                None => "503 RNFR first".to_string(),
+            },
+            _ => format!("502 {} not implemented", command),
+        };
+        Ok(response)
+    }
+
+    /// Accepts the data connection announced by the last `EPSV` or `PASV` and runs a transfer
+    /// on it. Returns the reply ending the transfer.
+    fn transfer(
+        &mut self,
+        control: &mut BufReader<Stream>,
+        run: impl FnOnce(&mut Stream) -> io::Result<()>,
+    ) -> io::Result<String> {
+        let Some(listener) = self.passive.take() else {
+            return Ok("425 Use EPSV or PASV first".to_string());
+        };
+        reply(control, "150 Opening data connection")?;
+        let (stream, _) = listener.accept()?;
+        let mut data = match (&self.acceptor, self.protect) {
+            (Some(acceptor), true) => match accept_tls(acceptor, stream) {
+                Ok(data) => data,
+                Err(_) => return Ok("425 TLS handshake failed".to_string()),
+            },
+            _ => Stream::Plain(stream),
+        };
+        let result = run(&mut data).and_then(|_| data.flush());
+        data.close();
+        Ok(match result {
+            Ok(_) => "226 Transfer complete".to_string(),
+            Err(_) => "426 Transfer aborted".to_string(),
+        })
+    }
+}
+
+/// A WebDAV server with Basic authentication, serving its root directory under `/dav`.
+pub(crate) struct WebDavTestServer {
+    port: u16,
+    root: TempDir,
+    tls: bool,
+}
+
+impl WebDavTestServer {
+    pub(crate) fn start(tls: bool) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let root = tempfile::tempdir().unwrap();
+        let acceptor = tls.then(test_acceptor);
+
+        let root_path = root.path().to_path_buf();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let root = root_path.clone();
+                let acceptor = acceptor.clone();
+                thread::spawn(move || {
+                    let stream = match &acceptor {
+                        Some(acceptor) => accept_tls(acceptor, stream),
+                        None => Ok(Stream::Plain(stream)),
+                    };
+                    if let Ok(stream) = stream {
+                        let _ = serve_http(&root, stream);
+                    }
+                });
+            }
+        });
+        Self { port, root, tls }
+    }
+
+    pub(crate) fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub(crate) fn root(&self) -> &Path {
+        self.root.path()
+    }
+
+    pub(crate) fn base_path(&self) -> &str {
+        "/dav"
+    }
+
+    pub(crate) fn is_tls(&self) -> bool {
+        self.tls
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+fn read_request(reader: &mut BufReader<Stream>) -> io::Result<Request> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    let mut request = Request {
+        method,
+        path,
+        headers,
+        body: Vec::new(),
+    };
+    let length: u64 = request
+        .header("Content-Length")
+        .and_then(|length| length.parse().ok())
+        .unwrap_or(0);
+    reader.take(length).read_to_end(&mut request.body)?;
+    Ok(request)
+}
+
+fn respond(
+    stream: &mut Stream,
+    status: u16,
+    headers: &[(&str, String)],
+    body: &[u8],
+) -> io::Result<()> {
+    let mut head = format!(
+        "HTTP/1.1 {} Test\r\nContent-Length: {}\r\nConnection: close\r\n",
+        status,
+        body.len()
+    );
+    for (name, value) in headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(body)?;
+    stream.flush()
+}
+
+/// The request path below `/dav`, decoded, `None` for paths outside of it.
+fn dav_path(path: &str) -> Option<String> {
+    let path = match path.split_once("://") {
+        Some((_, rest)) => &rest[rest.find('/')?..],
+        None => path,
+    };
+    let relative = path.strip_prefix("/dav")?;
+    (relative.is_empty() || relative.starts_with('/'))
+        .then(|| percent_decode_str(relative).decode_utf8_lossy().to_string())
+}
+
+fn propfind_response(root: &Path, path: &str) -> String {
+    let local = local_path(root, path);
+    let Ok(metadata) = fs::metadata(&local) else {
+        return String::new();
+    };
+    let segments: Vec<String> = path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| utf8_percent_encode(segment, HREF_SEGMENT).to_string())
+        .collect();
+    let mut href = format!("/dav/{}", segments.join("/"));
+    let modified = modified_time(&metadata).format("%a, %d %b %Y %H:%M:%S GMT");
+    let properties = if metadata.is_dir() {
+        if !href.ends_with('/') {
+            href.push('/');
+        }
+        format!(
+            "<d:resourcetype><d:collection/></d:resourcetype><d:getlastmodified>{}</d:getlastmodified>",
+            modified
+        )
+    } else {
+        format!(
+            "<d:resourcetype/><d:getcontentlength>{}</d:getcontentlength><d:getlastmodified>{}</d:getlastmodified>",
+            metadata.len(),
+            modified
+        )
+    };
+    format!(
+        concat!(
+            "<d:response><d:href>{}</d:href>",
+            "<d:propstat><d:prop>{}</d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat>",
+            "<d:propstat><d:prop><d:quota-used-bytes/></d:prop>",
+            "<d:status>HTTP/1.1 404 Not Found</d:status></d:propstat>",
+            "</d:response>"
+        ),
+        href, properties
+    )
+}
+
+/// The start and end of a `Range` header of the form `bytes=start-` or `bytes=start-end`.
+fn parse_range(range: &str, length: u64) -> Option<(u64, u64)> {
+    let (start, end) = range.strip_prefix("bytes=")?.split_once('-')?;
+    let start: u64 = start.parse().ok()?;
+    let end = match end {
+        "" => length.saturating_sub(1),
+        end => end.parse::<u64>().ok()?.min(length.saturating_sub(1)),
+    };
+    Some((start, end))
+}
+
+fn serve_http(root: &Path, stream: Stream) -> io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    let request = read_request(&mut reader)?;
+    let mut stream = reader.into_inner();
+    let result = handle_http(root, &request, &mut stream);
+    stream.close();
+    result
+}
+
+fn handle_http(root: &Path, request: &Request, stream: &mut Stream) -> io::Result<()> {
+    let credentials = base64::engine::general_purpose::STANDARD
+        .encode(format!("{}:{}", TEST_USER, TEST_PASSWORD));
+    if request.header("Authorization") != Some(&format!("Basic {}", credentials)) {
+        let challenge = [("WWW-Authenticate", "Basic realm=\"test\"".to_string())];
+        return respond(stream, 401, &challenge, b"");
+    }
+    let Some(path) = dav_path(&request.path) else {
+        return respond(stream, 404, &[], b"");
+    };
+    let local = local_path(root, &path);
+    let parent_exists = local.parent().is_some_and(Path::is_dir);
+
+    match request.method.as_str() {
+        "PROPFIND" => {
+            if !local.exists() {
+                return respond(stream, 404, &[], b"");
+            }
+            let mut responses = propfind_response(root, &path);
+            if request.header("Depth") == Some("1") && local.is_dir() {
+                let mut names: Vec<String> = fs::read_dir(&local)?
+                    .flatten()
+                    .map(|entry| entry.file_name().to_string_lossy().to_string())
+                    .collect();
+                names.sort();
+                for name in names {
+                    let child = format!("{}/{}", path.trim_end_matches('/'), name);
+                    responses.push_str(&propfind_response(root, &child));
+                }
+            }
+            let body = format!(
+                r#"<?xml version="1.0" encoding="utf-8"?><d:multistatus xmlns:d="DAV:">{}</d:multistatus>"#,
+                responses
+            );
+            let content_type = [("Content-Type", "application/xml; charset=utf-8".to_string())];
+            respond(stream, 207, &content_type, body.as_bytes())
+        }
+        "GET" => {
+            let Ok(content) = fs::read(&local) else {
+                return respond(stream, 404, &[], b"");
+            };
+            let length = content.len() as u64;
+            match request
+                .header("Range")
+                .map(|range| parse_range(range, length))
+            {
+                Some(Some((start, _))) if start >= length => respond(stream, 416, &[], b""),
+                Some(Some((start, end))) => {
+                    let range = [(
+                        "Content-Range",
+                        format!("bytes {}-{}/{}", start, end, length),
+                    )];
+                    respond(stream, 206, &range, &content[start as usize..=end as usize])
+                }
+                Some(None) => respond(stream, 400, &[], b""),
+                None => {
+                    // Whole files are sent chunked, like servers do for generated content
+                    stream.write_all(
+                        b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n",
+                    )?;
+                    for chunk in content.chunks(1000) {
+                        stream.write_all(format!("{:x}\r\n", chunk.len()).as_bytes())?;
+                        stream.write_all(chunk)?;
+                        stream.write_all(b"\r\n")?;
+                    }
+                    stream.write_all(b"0\r\n\r\n")?;
+                    stream.flush()
+                }
+            }
+        }
+        "PUT" if !parent_exists => respond(stream, 409, &[], b""),
+        "PUT" => {
+            let existed = local.exists();
+            fs::write(&local, &request.body)?;
+            respond(stream, if existed { 204 } else { 201 }, &[], b"")
+        }
+        "MKCOL" if local.exists() => respond(stream, 405, &[], b""),
+        "MKCOL" if !parent_exists => respond(stream, 409, &[], b""),
+        "MKCOL" => {
+            fs::create_dir(&local)?;
+            respond(stream, 201, &[], b"")
+        }
+        "DELETE" if local.is_dir() => {
+            fs::remove_dir_all(&local)?;
+            respond(stream, 204, &[], b"")
+        }
+        "DELETE" => match fs::remove_file(&local) {
+            Ok(_) => respond(stream, 204, &[], b""),
+            Err(_) => respond(stream, 404, &[], b""),
+        },
+        "MOVE" => {
+            let Some(destination) = request.header("Destination").and_then(dav_path) else {
+                return respond(stream, 400, &[], b"");
+            };
+            let destination = local_path(root, &destination);
+            if !local.exists() {
+                respond(stream, 404, &[], b"")
+            } else if destination.exists() && request.header("Overwrite") == Some("F") {
+                respond(stream, 412, &[], b"")
+            } else {
+                fs::rename(&local, &destination)?;
+                respond(stream, 201, &[], b"")
+            }
+        }
+        _ => respond(stream, 405, &[], b""),
+    }
+}
+
+/// Objects listed per page by the S3 server, small so the client has to follow continuations.
+const S3_PAGE_SIZE: usize = 3;
+
+struct S3Object {
+    content: Vec<u8>,
+    modified: DateTime<Utc>,
+}
+
+struct S3Bucket {
+    created: DateTime<Utc>,
+    objects: BTreeMap<String, S3Object>,
+}
+
+struct S3Upload {
+    bucket: String,
+    key: String,
+    parts: BTreeMap<u64, Vec<u8>>,
+}
+
+#[derive(Default)]
+struct S3Store {
+    buckets: BTreeMap<String, S3Bucket>,
+    uploads: HashMap<String, S3Upload>,
+    next_upload: u64,
+    requests: Vec<String>,
+}
+
+/// An S3 server with path-style URLs, keeping its buckets in memory. Requests have to be
+/// signed with `TEST_USER` as access key and `TEST_PASSWORD` as secret key, in any region.
+pub(crate) struct S3TestServer {
+    port: u16,
+    tls: bool,
+    store: Arc<Mutex<S3Store>>,
+}
+
+impl S3TestServer {
+    pub(crate) fn start(tls: bool) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let store = Arc::new(Mutex::new(S3Store::default()));
+        let acceptor = tls.then(test_acceptor);
+
+        let served = store.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let store = served.clone();
+                let acceptor = acceptor.clone();
+                thread::spawn(move || {
+                    let stream = match &acceptor {
+                        Some(acceptor) => accept_tls(acceptor, stream),
+                        None => Ok(Stream::Plain(stream)),
+                    };
+                    if let Ok(stream) = stream {
+                        let _ = serve_s3(&store, stream);
+                    }
+                });
+            }
+        });
+        Self { port, tls, store }
+    }
+
+    pub(crate) fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub(crate) fn is_tls(&self) -> bool {
+        self.tls
+    }
+
+    /// Stores an object, the bucket is created if it does not exist.
+    pub(crate) fn put_object(&self, bucket: &str, key: &str, content: &[u8]) {
+        let mut store = self.store.lock().unwrap();
+        store
+            .buckets
+            .entry(bucket.to_string())
+            .or_insert_with(|| S3Bucket {
+                created: Utc::now(),
+                objects: BTreeMap::new(),
+            })
+            .objects
+            .insert(
+                key.to_string(),
+                S3Object {
+                    content: content.to_vec(),
+                    modified: Utc::now(),
+                },
+            );
+    }
+
+    pub(crate) fn object(&self, bucket: &str, key: &str) -> Option<Vec<u8>> {
+        let store = self.store.lock().unwrap();
+        let object = store.buckets.get(bucket)?.objects.get(key)?;
+        Some(object.content.clone())
+    }
+
+    pub(crate) fn keys(&self, bucket: &str) -> Vec<String> {
+        let store = self.store.lock().unwrap();
+        store
+            .buckets
+            .get(bucket)
+            .map(|bucket| bucket.objects.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// The method and target of every request received so far.
+    pub(crate) fn requests(&self) -> Vec<String> {
+        self.store.lock().unwrap().requests.clone()
+    }
+
+    /// The number of multipart uploads which were neither completed nor aborted.
+    pub(crate) fn open_uploads(&self) -> usize {
+        self.store.lock().unwrap().uploads.len()
+    }
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn s3_time(time: &DateTime<Utc>) -> String {
+    time.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string()
+}
+
+fn s3_error(stream: &mut Stream, status: u16, code: &str) -> io::Result<()> {
+    let body = format!(
+        "<Error><Code>{}</Code><Message>{} by the test server</Message></Error>",
+        code, code
+    );
+    respond(stream, status, &[], body.as_bytes())
+}
+
+fn s3_xml(stream: &mut Stream, body: &str) -> io::Result<()> {
+    let body = format!(r#"<?xml version="1.0" encoding="UTF-8"?>{}"#, body);
+    respond(
+        stream,
+        200,
+        &[("Content-Type", "application/xml".to_string())],
+        body.as_bytes(),
+    )
+}
+
+fn decode(value: &str) -> String {
+    percent_decode_str(value).decode_utf8_lossy().to_string()
+}
+
+/// Checks the signature of the `Authorization` header or of a presigned URL.
+fn s3_signature_valid(request: &Request, path: &str, query: &[(String, String)]) -> bool {
+    let parameter = |name: &str| {
+        query
+            .iter()
+            .find(|(parameter, _)| parameter == name)
+            .map(|(_, value)| value.as_str())
+    };
+    let (credential, signed_headers, signature, amz_date, payload_hash, signed_query) =
+        match parameter("X-Amz-Signature") {
+            Some(signature) => {
+                let (Some(credential), Some(signed_headers), Some(amz_date), Some(expires)) = (
+                    parameter("X-Amz-Credential"),
+                    parameter("X-Amz-SignedHeaders"),
+                    parameter("X-Amz-Date"),
+                    parameter("X-Amz-Expires").and_then(|expires| expires.parse::<i64>().ok()),
+                ) else {
+                    return false;
+                };
+                let expired = chrono::NaiveDateTime::parse_from_str(amz_date, "%Y%m%dT%H%M%SZ")
+                    .map_or(true, |signed| {
+                        signed.and_utc() + chrono::Duration::seconds(expires) < Utc::now()
+                    });
+                if expired {
+                    return false;
+                }
+                let signed_query: Vec<(&str, String)> = query
+                    .iter()
+                    .filter(|(name, _)| name != "X-Amz-Signature")
+                    .map(|(name, value)| (name.as_str(), value.clone()))
+                    .collect();
+                (
+                    credential.to_string(),
+                    signed_headers.to_string(),
+                    signature.to_string(),
+                    amz_date.to_string(),
+                    UNSIGNED_PAYLOAD.to_string(),
+                    signed_query,
+                )
+            }
+            None => {
+                let Some(authorization) = request
+                    .header("Authorization")
+                    .and_then(|value| value.strip_prefix("AWS4-HMAC-SHA256 "))
+                else {
+                    return false;
+                };
+                let field = |name: &str| {
+                    authorization
+                        .split(',')
+                        .filter_map(|field| field.trim().split_once('='))
+                        .find(|(field, _)| *field == name)
+                        .map(|(_, value)| value.to_string())
+                };
+                let (Some(credential), Some(signed_headers), Some(signature)) = (
+                    field("Credential"),
+                    field("SignedHeaders"),
+                    field("Signature"),
+                ) else {
+                    return false;
+                };
+                let payload_hash = request.header("x-amz-content-sha256").unwrap_or("");
+                if payload_hash != sha256_hex(&request.body) {
+                    return false;
+                }
+                (
+                    credential,
+                    signed_headers,
+                    signature,
+                    request.header("x-amz-date").unwrap_or("").to_string(),
+                    payload_hash.to_string(),
+                    query
+                        .iter()
+                        .map(|(name, value)| (name.as_str(), value.clone()))
+                        .collect(),
+                )
+            }
+        };
+
+    let scope: Vec<&str> = credential.split('/').collect();
+    if scope.len() != 5 || scope[0] != TEST_USER || !amz_date.starts_with(scope[1]) {
+        return false;
+    }
+    let credentials = Credentials {
+        access_key: TEST_USER.to_string(),
+        secret_key: TEST_PASSWORD.to_string(),
+        region: scope[2].to_string(),
+    };
+    let headers: Vec<(&str, String)> = signed_headers
+        .split(';')
+        .map(|name| (name, request.header(name).unwrap_or("").to_string()))
+        .collect();
+    credentials
+        .sign(
+            &amz_date,
+            &request.method,
+            path,
+            &canonical_query(&signed_query),
+            &headers,
+            &payload_hash,
+        )
+        .is_ok_and(|(_, expected)| expected == signature)
+}
+
+/// The objects of a `ListObjectsV2` page as `Contents` and `CommonPrefixes` elements.
+fn list_objects_xml(
+    bucket: &str,
+    objects: &BTreeMap<String, S3Object>,
+    query: &[(String, String)],
+) -> String {
+    let parameter = |name: &str| {
+        query
+            .iter()
+            .find(|(parameter, _)| parameter == name)
+            .map(|(_, value)| value.as_str())
+    };
+    let prefix = parameter("prefix").unwrap_or("");
+    let delimiter = parameter("delimiter").filter(|delimiter| !delimiter.is_empty());
+    let token = parameter("continuation-token");
+
+    let mut items = String::new();
+    let mut count = 0;
+    let mut last: Option<String> = None;
+    let mut truncated = false;
+    for (key, object) in objects.range(prefix.to_string()..) {
+        if !key.starts_with(prefix) {
+            break;
+        }
+        let common = delimiter.and_then(|delimiter| {
+            key[prefix.len()..]
+                .find(delimiter)
+                .map(|index| key[..prefix.len() + index + delimiter.len()].to_string())
+        });
+        let name = common.clone().unwrap_or(key.clone());
+        if token.is_some_and(|token| name.as_str() <= token) || last.as_ref() == Some(&name) {
+            continue;
+        }
+        if count == S3_PAGE_SIZE {
+            truncated = true;
+            break;
+        }
+        items.push_str(&match common {
+            Some(common) => format!(
+                "<CommonPrefixes><Prefix>{}</Prefix></CommonPrefixes>",
+                xml_escape(&common)
+            ),
+            None => format!(
+                "<Contents><Key>{}</Key><LastModified>{}</LastModified><ETag>\"{}\"</ETag><Size>{}</Size></Contents>",
+                xml_escape(key),
+                s3_time(&object.modified),
+                sha256_hex(&object.content),
+                object.content.len()
+            ),
+        });
+        count += 1;
+        last = Some(name);
+    }
+    let continuation = match (truncated, last) {
+        (true, Some(last)) => format!(
+            "<NextContinuationToken>{}</NextContinuationToken>",
+            xml_escape(&last)
+        ),
+        _ => String::new(),
+    };
+    format!(
+        concat!(
+            r#"<ListBucketResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">"#,
+            "<Name>{}</Name><Prefix>{}</Prefix><KeyCount>{}</KeyCount>",
+            "<IsTruncated>{}</IsTruncated>{}{}</ListBucketResult>"
+        ),
+        xml_escape(bucket),
+        xml_escape(prefix),
+        count,
+        truncated,
+        continuation,
+        items
+    )
+}
+
+fn serve_s3(store: &Mutex<S3Store>, stream: Stream) -> io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    let request = read_request(&mut reader)?;
+    let mut stream = reader.into_inner();
+    let result = handle_s3(store, &request, &mut stream);
+    stream.close();
+    result
+}
+
+fn handle_s3(store: &Mutex<S3Store>, request: &Request, stream: &mut Stream) -> io::Result<()> {
+    let (path, raw_query) = request
+        .path
+        .split_once('?')
+        .unwrap_or((request.path.as_str(), ""));
+    let query: Vec<(String, String)> = raw_query
+        .split('&')
+        .filter(|parameter| !parameter.is_empty())
+        .map(|parameter| {
+            let (name, value) = parameter.split_once('=').unwrap_or((parameter, ""));
+            (decode(name), decode(value))
+        })
+        .collect();
+    let parameter = |name: &str| {
+        query
+            .iter()
+            .find(|(parameter, _)| parameter == name)
+            .map(|(_, value)| value.clone())
+    };
+    let mut store = store.lock().unwrap();
+    store
+        .requests
+        .push(format!("{} {}", request.method, request.path));
+    if !s3_signature_valid(request, path, &query) {
+        return s3_error(stream, 403, "SignatureDoesNotMatch");
+    }
+
+    let (bucket, key) = match path.trim_start_matches('/').split_once('/') {
+        Some((bucket, key)) => (decode(bucket), decode(key)),
+        None => (decode(path.trim_start_matches('/')), String::new()),
+    };
+    let store = &mut *store;
+
+    if bucket.is_empty() {
+        let buckets: String = store
+            .buckets
+            .iter()
+            .map(|(name, bucket)| {
+                format!(
+                    "<Bucket><Name>{}</Name><CreationDate>{}</CreationDate></Bucket>",
+                    xml_escape(name),
+                    s3_time(&bucket.created)
+                )
+            })
+            .collect();
+        return s3_xml(
+            stream,
+            &format!(
+                "<ListAllMyBucketsResult><Owner><ID>test</ID></Owner><Buckets>{}</Buckets></ListAllMyBucketsResult>",
+                buckets
+            ),
+        );
+    }
+
+    if key.is_empty() {
+        return match request.method.as_str() {
+            "PUT" if store.buckets.contains_key(&bucket) => {
+                s3_error(stream, 409, "BucketAlreadyOwnedByYou")
+            }
+            "PUT" => {
+                store.buckets.insert(
+                    bucket,
+                    S3Bucket {
+                        created: Utc::now(),
+                        objects: BTreeMap::new(),
+                    },
+                );
+                respond(stream, 200, &[], b"")
+            }
+            _ => match store.buckets.get(&bucket) {
+                None => s3_error(stream, 404, "NoSuchBucket"),
+                Some(_) if request.method == "HEAD" => respond(stream, 200, &[], b""),
+                Some(existing) if request.method == "GET" => {
+                    let body = list_objects_xml(&bucket, &existing.objects, &query);
+                    s3_xml(stream, &body)
+                }
+                Some(existing) if request.method == "DELETE" => {
+                    if !existing.objects.is_empty() {
+                        return s3_error(stream, 409, "BucketNotEmpty");
+                    }
+                    store.buckets.remove(&bucket);
+                    respond(stream, 204, &[], b"")
+                }
+                Some(_) => s3_error(stream, 405, "MethodNotAllowed"),
+            },
+        };
+    }
+
+    let Some(objects) = store.buckets.get(&bucket).map(|bucket| &bucket.objects) else {
+        return s3_error(stream, 404, "NoSuchBucket");
+    };
+    // The source of a copy, `/bucket/key` encoded
+    let copy_source = request.header("x-amz-copy-source").map(|source| {
+        let source = source.trim_start_matches('/');
+        let (bucket, key) = source.split_once('/').unwrap_or((source, ""));
+        (decode(bucket), decode(key))
+    });
+    let source_content = match &copy_source {
+        Some((source_bucket, source_key)) => {
+            match store
+                .buckets
+                .get(source_bucket)
+                .and_then(|bucket| bucket.objects.get(source_key))
+            {
+                Some(object) => Some(object.content.clone()),
+                None => return s3_error(stream, 404, "NoSuchKey"),
+            }
+        }
+        None => None,
+    };
+
+    match (
+        request.method.as_str(),
+        parameter("uploadId"),
+        parameter("partNumber"),
+    ) {
+        ("POST", None, _) if parameter("uploads").is_some() => {
+            store.next_upload += 1;
+            let upload_id = format!("upload-{}", store.next_upload);
+            store.uploads.insert(
+                upload_id.clone(),
+                S3Upload {
+                    bucket: bucket.clone(),
+                    key: key.clone(),
+                    parts: BTreeMap::new(),
+                },
+            );
+            s3_xml(
+                stream,
+                &format!(
+                    "<InitiateMultipartUploadResult><Bucket>{}</Bucket><Key>{}</Key><UploadId>{}</UploadId></InitiateMultipartUploadResult>",
+                    xml_escape(&bucket),
+                    xml_escape(&key),
+                    upload_id
+                ),
+            )
+        }
+        ("PUT", Some(upload_id), Some(number)) => {
+            let Some(upload) = store.uploads.get_mut(&upload_id) else {
+                return s3_error(stream, 404, "NoSuchUpload");
+            };
+            let number: u64 = number.parse().unwrap_or(0);
+            let content = match source_content {
+                Some(source) => {
+                    let range = request
+                        .header("x-amz-copy-source-range")
+                        .and_then(|range| parse_range(range, source.len() as u64));
+                    let Some((start, end)) = range else {
+                        return s3_error(stream, 400, "InvalidArgument");
+                    };
+                    source[start as usize..=end as usize].to_vec()
+                }
+                None => request.body.clone(),
+            };
+            let etag = format!("\"{}\"", sha256_hex(&content));
+            upload.parts.insert(number, content);
+            match copy_source {
+                Some(_) => s3_xml(
+                    stream,
+                    &format!(
+                        "<CopyPartResult><ETag>{}</ETag></CopyPartResult>",
+                        xml_escape(&etag)
+                    ),
+                ),
+                None => respond(stream, 200, &[("ETag", etag)], b""),
+            }
+        }
+        ("POST", Some(upload_id), None) => {
+            let Some(upload) = store.uploads.remove(&upload_id) else {
+                return s3_error(stream, 404, "NoSuchUpload");
+            };
+            let completion = parse_xml(&String::from_utf8_lossy(&request.body));
+            let Ok(completion) = completion else {
+                return s3_error(stream, 400, "MalformedXML");
+            };
+            let mut content = Vec::new();
+            for part in completion.children("Part") {
+                let number: u64 = part
+                    .text_of("PartNumber")
+                    .and_then(|number| number.parse().ok())
+                    .unwrap_or(0);
+                match upload.parts.get(&number) {
+                    Some(part) => content.extend_from_slice(part),
+                    None => return s3_error(stream, 400, "InvalidPart"),
+                }
+            }
+            if let Some(bucket) = store.buckets.get_mut(&upload.bucket) {
+                bucket.objects.insert(
+                    upload.key.clone(),
+                    S3Object {
+                        content,
+                        modified: Utc::now(),
+                    },
+                );
+            }
+            s3_xml(
+                stream,
+                &format!(
+                    "<CompleteMultipartUploadResult><Key>{}</Key></CompleteMultipartUploadResult>",
+                    xml_escape(&upload.key)
+                ),
+            )
+        }
+        ("DELETE", Some(upload_id), None) => {
+            store.uploads.remove(&upload_id);
+            respond(stream, 204, &[], b"")
+        }
+        ("PUT", None, None) => {
+            let content = source_content.unwrap_or_else(|| request.body.clone());
+            let etag = format!("\"{}\"", sha256_hex(&content));
+            let modified = Utc::now();
+            if let Some(bucket) = store.buckets.get_mut(&bucket) {
+                bucket.objects.insert(key, S3Object { content, modified });
+            }
+            match copy_source {
+                Some(_) => s3_xml(
+                    stream,
+                    &format!(
+                        "<CopyObjectResult><ETag>{}</ETag><LastModified>{}</LastModified></CopyObjectResult>",
+                        xml_escape(&etag),
+                        s3_time(&modified)
+                    ),
+                ),
+                None => respond(stream, 200, &[("ETag", etag)], b""),
+            }
+        }
+        ("DELETE", None, None) => {
+            if let Some(bucket) = store.buckets.get_mut(&bucket) {
+                bucket.objects.remove(&key);
+            }
+            respond(stream, 204, &[], b"")
+        }
+        ("GET" | "HEAD", None, None) => {
+            let Some(object) = objects.get(&key) else {
+                return s3_error(stream, 404, "NoSuchKey");
+            };
+            let length = object.content.len() as u64;
+            let modified = (
+                "Last-Modified",
+                object
+                    .modified
+                    .format("%a, %d %b %Y %H:%M:%S GMT")
+                    .to_string(),
+            );
+            if request.method == "HEAD" {
+                stream.write_all(
+                    format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n{}: {}\r\nConnection: close\r\n\r\n",
+                        length, modified.0, modified.1
+                    )
+                    .as_bytes(),
+                )?;
+                return stream.flush();
+            }
+            match request
+                .header("Range")
+                .map(|range| parse_range(range, length))
+            {
+                Some(Some((start, _))) if start >= length => s3_error(stream, 416, "InvalidRange"),
+                Some(Some((start, end))) => {
+                    let range = (
+                        "Content-Range",
+                        format!("bytes {}-{}/{}", start, end, length),
+                    );
+                    let content = object.content[start as usize..=end as usize].to_vec();
+                    respond(stream, 206, &[range, modified], &content)
+                }
+                Some(None) => s3_error(stream, 400, "InvalidArgument"),
+                None => {
+                    let content = object.content.clone();
+                    respond(stream, 200, &[modified], &content)
+                }
+            }
+        }
+        _ => s3_error(stream, 405, "MethodNotAllowed"),
+    }
+}
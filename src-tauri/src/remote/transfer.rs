@@ -0,0 +1,889 @@
+//! The transfer engine of all protocols. Files are planned first, then sent in batches into
+//! `.part` files which are verified and renamed once they are complete, so a dropped connection
+//! or a cancelled job continues where it stopped the next time.
+
+use super::{join_path, RemoteFileSystem, RemoteProtocol};
+use crate::commands::hash_commands::{hash_reader, ChecksumMethod};
+use crate::error_handling::{Error, ErrorCode};
+use crate::state::jobs_data::{JobHandle, JobsState};
+use crate::{log_error, log_info};
+use serde::{Deserialize, Serialize};
+use ssh2::FileStat;
+use std::cell::Cell;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How much is transferred in one request on the session. Other commands of the same session,
+/// e.g. listing a directory, run between two batches instead of waiting for the whole file.
+const BATCH_SIZE: u64 = 8 * 1024 * 1024;
+
+/// How often a batch is tried again after the connection dropped and could not be reopened.
+pub(crate) const MAX_RETRIES: u32 = 3;
+
+/// Suffix of incomplete files. They are renamed to their real name once the transfer is
+/// complete and verified, and picked up again by the next transfer of the same file. Partial
+/// files get the modification time of their source, so a partial file of a source which
+/// changed since is started over instead of resumed.
+pub const PARTIAL_SUFFIX: &str = ".part";
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub enum TransferDirection {
+    Upload,
+    Download,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub enum TransferVerification {
+    /// The size of the transferred file has to match the source.
+    Size,
+    /// The SHA256 of both sides has to match. The server computes its hash with `sha256sum` or
+    /// `shasum`, if it does not allow running commands only the size is compared.
+    Hash,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct TransferOptions {
+    /// Continues partial files of an earlier transfer instead of starting them over.
+    pub resume: bool,
+    pub verification: TransferVerification,
+    /// Replaces existing files at the destination, otherwise they are skipped.
+    pub overwrite: bool,
+}
+
+impl Default for TransferOptions {
+    fn default() -> Self {
+        Self {
+            resume: true,
+            verification: TransferVerification::Hash,
+            overwrite: false,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub enum TransferStatus {
+    Done,
+    /// The destination already existed and `overwrite` was not set.
+    Skipped,
+    Failed,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct TransferredFile {
+    pub source: String,
+    pub destination: String,
+    pub size: u64,
+    /// Bytes which were already transferred by an earlier attempt.
+    pub resumed_from: u64,
+    /// How the file was checked, only set for files which are `Done`.
+    pub verified_by: Option<TransferVerification>,
+    pub status: TransferStatus,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct TransferReport {
+    pub direction: TransferDirection,
+    pub files: Vec<TransferredFile>,
+    /// Bytes sent over the connection, without the resumed parts.
+    pub bytes_transferred: u64,
+}
+
+pub(crate) enum TransferError {
+    Cancelled,
+    Failed(String),
+}
+
+impl From<String> for TransferError {
+    fn from(error: String) -> Self {
+        TransferError::Failed(error)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct PlannedFile {
+    pub(crate) source: String,
+    pub(crate) destination: String,
+    pub(crate) size: u64,
+    pub(crate) modified: Option<u64>, // as seconds since epoch
+    pub(crate) permissions: Option<u32>,
+}
+
+/// The directories to create and the files to transfer, parents come before their children.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct TransferPlan {
+    pub(crate) directories: Vec<String>,
+    pub(crate) files: Vec<PlannedFile>,
+}
+
+/// Counts the bytes written into the local file of a download and stops once the job is
+/// cancelled.
+struct JobWriter<'a> {
+    inner: fs::File,
+    job: Option<&'a JobHandle>,
+}
+
+impl Write for JobWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if is_cancelled(self.job) {
+            return Err(io::Error::other("Transfer was cancelled"));
+        }
+        let written = self.inner.write(buf)?;
+        if let Some(job) = self.job {
+            job.update_progress(|p| p.bytes_processed += written as u64);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Counts the bytes read from the local file of an upload and stops once the job is cancelled.
+struct JobReader<'a> {
+    inner: fs::File,
+    job: Option<&'a JobHandle>,
+}
+
+impl Read for JobReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if is_cancelled(self.job) {
+            return Err(io::Error::other("Transfer was cancelled"));
+        }
+        let read = self.inner.read(buf)?;
+        if let Some(job) = self.job {
+            job.update_progress(|p| p.bytes_processed += read as u64);
+        }
+        Ok(read)
+    }
+}
+
+pub(crate) fn is_cancelled(job: Option<&JobHandle>) -> bool {
+    job.is_some_and(|job| job.is_cancelled())
+}
+
+pub(crate) fn partial_path(path: &str) -> String {
+    format!("{}{}", path, PARTIAL_SUFFIX)
+}
+
+pub(crate) fn remote_file_name(path: &str) -> Result<&str, String> {
+    path.trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .filter(|name| !name.is_empty() && *name != "." && *name != "..")
+        .ok_or_else(|| format!("Path has no file name: {}", path))
+}
+
+fn local_sha256(path: &Path) -> Result<String, String> {
+    let mut file = fs::File::open(path).map_err(|e| e.to_string())?;
+    hash_reader(&ChecksumMethod::SHA256, &mut file).map_err(|e| e.to_string())
+}
+
+/// Checks the transferred file. A mismatch means the partial file is broken, it is removed by
+/// the caller so the next attempt starts over instead of resuming it. Servers which can't hash
+/// their files are only checked by size.
+fn verify(
+    session: &dyn RemoteFileSystem,
+    remote_path: &str,
+    local_path: &Path,
+    verification: TransferVerification,
+) -> Result<TransferVerification, String> {
+    let remote_size = session.stat(remote_path)?.size.unwrap_or(0);
+    let local_size = fs::metadata(local_path).map_err(|e| e.to_string())?.len();
+    if remote_size != local_size {
+        return Err(format!(
+            "Size mismatch after transfer: {} bytes on the server, {} bytes locally",
+            remote_size, local_size
+        ));
+    }
+    if verification == TransferVerification::Size {
+        return Ok(TransferVerification::Size);
+    }
+    match session.sha256(remote_path)? {
+        Some(remote_hash) if remote_hash == local_sha256(local_path)? => {
+            Ok(TransferVerification::Hash)
+        }
+        Some(_) => Err("Checksum mismatch after transfer".to_string()),
+        None => Ok(TransferVerification::Size),
+    }
+}
+
+/// Runs a batch and tries it again while the session can't reconnect, e.g. during a short
+/// network outage. Errors of the server itself are returned right away.
+pub(crate) fn with_retries<T>(
+    session: &dyn RemoteFileSystem,
+    job: Option<&JobHandle>,
+    batch: impl Fn() -> Result<T, String>,
+) -> Result<T, TransferError> {
+    let mut attempt = 0;
+    loop {
+        match batch() {
+            Ok(result) => return Ok(result),
+            Err(_) if is_cancelled(job) => return Err(TransferError::Cancelled),
+            Err(err) if attempt < MAX_RETRIES && !session.is_connected() => {
+                attempt += 1;
+                log_info!("Transfer lost its connection, retrying: {}", err);
+                std::thread::sleep(Duration::from_secs(1 << attempt));
+            }
+            Err(err) => return Err(TransferError::Failed(err)),
+        }
+    }
+}
+
+pub(crate) fn system_time(seconds: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(seconds)
+}
+
+pub(crate) fn modified_seconds(metadata: &fs::Metadata) -> Option<u64> {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs())
+}
+
+/// Downloads a file into a partial file next to the destination and renames it once it is
+/// complete and verified. Every protocol can read from an offset, so each batch continues where
+/// the partial file ends, also after a dropped connection. Returns the resumed bytes and the
+/// verification which was used.
+fn download_file(
+    session: &dyn RemoteFileSystem,
+    file: &PlannedFile,
+    options: &TransferOptions,
+    job: Option<&JobHandle>,
+) -> Result<(u64, TransferVerification), TransferError> {
+    let partial = PathBuf::from(partial_path(&file.destination));
+    let resumed_from = match fs::metadata(&partial) {
+        Ok(metadata)
+            if options.resume
+                && metadata.len() <= file.size
+                && modified_seconds(&metadata) == file.modified =>
+        {
+            metadata.len()
+        }
+        Ok(_) => {
+            fs::remove_file(&partial).map_err(|e| e.to_string())?;
+            0
+        }
+        Err(_) => 0,
+    };
+    if let Some(job) = job {
+        job.update_progress(|p| p.bytes_processed += resumed_from);
+    }
+
+    loop {
+        let finished = with_retries(session, job, || {
+            let local_file = fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&partial)
+                .map_err(|e| e.to_string())?;
+            let offset = local_file.metadata().map_err(|e| e.to_string())?.len();
+            let mut writer = JobWriter {
+                inner: local_file,
+                job,
+            };
+            let result = session.read_file(&file.source, offset, Some(BATCH_SIZE), &mut writer);
+            // The partial file keeps the modification time of its source, so it can be resumed
+            if let Some(modified) = file.modified {
+                let _ = writer.inner.set_modified(system_time(modified));
+            }
+            result.map(|read| read < BATCH_SIZE || offset + read >= file.size)
+        })?;
+        if is_cancelled(job) {
+            return Err(TransferError::Cancelled);
+        }
+        if finished {
+            break;
+        }
+    }
+
+    let verified_by =
+        verify(session, &file.source, &partial, options.verification).inspect_err(|_| {
+            let _ = fs::remove_file(&partial);
+        })?;
+    // Protocols without permissions, like WebDAV, only list the file type
+    #[cfg(unix)]
+    if let Some(permissions) = file.permissions.filter(|permissions| *permissions != 0) {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = fs::set_permissions(&partial, fs::Permissions::from_mode(permissions));
+    }
+    fs::rename(&partial, &file.destination).map_err(|e| e.to_string())?;
+    Ok((resumed_from, verified_by))
+}
+
+/// Renames the complete partial file on the server. Not every server renames over an existing
+/// file, e.g. servers speaking SFTP version 3 like OpenSSH, so the destination is removed and
+/// the rename tried again if it fails.
+fn finish_upload(
+    session: &dyn RemoteFileSystem,
+    partial: &str,
+    destination: &str,
+) -> Result<(), String> {
+    if session.rename(partial, destination).is_ok() {
+        return Ok(());
+    }
+    let _ = session.remove_file(destination);
+    session.rename(partial, destination)
+}
+
+/// Uploads a file into a partial file next to the destination on the server and renames it
+/// once it is complete and verified. Sessions which can append send the file in batches and
+/// continue the partial file after a dropped connection. Not every server can be told the
+/// modification time of an upload, so a partial file is only resumed if it is not older than
+/// the source. Protocols which can't append, like WebDAV, always upload the whole file.
+/// Returns the resumed bytes and the verification which was used.
+fn upload_file(
+    session: &dyn RemoteFileSystem,
+    file: &PlannedFile,
+    options: &TransferOptions,
+    job: Option<&JobHandle>,
+) -> Result<(u64, TransferVerification), TransferError> {
+    let partial = partial_path(&file.destination);
+    let supports_append = session.supports_append();
+    let resumed_from = match session.stat(&partial) {
+        Ok(stat)
+            if options.resume
+                && supports_append
+                && stat.size.unwrap_or(0) <= file.size
+                && stat
+                    .mtime
+                    .zip(file.modified)
+                    .is_some_and(|(partial, source)| partial >= source) =>
+        {
+            stat.size.unwrap_or(0)
+        }
+        Ok(_) => {
+            session.remove_file(&partial)?;
+            0
+        }
+        Err(_) => 0,
+    };
+    if let Some(job) = job {
+        job.update_progress(|p| p.bytes_processed += resumed_from);
+    }
+
+    let mut offset = resumed_from;
+    loop {
+        let attempt = Cell::new(0);
+        let result = with_retries(session, job, || {
+            attempt.set(attempt.get() + 1);
+            let start = match attempt.get() {
+                1 => offset,
+                // After a dropped connection the partial file has what arrived before
+                _ if supports_append => session
+                    .stat(&partial)
+                    .ok()
+                    .and_then(|stat| stat.size)
+                    .unwrap_or(0)
+                    .min(file.size),
+                _ => 0,
+            };
+            let length = match supports_append {
+                true => BATCH_SIZE.min(file.size - start),
+                false => file.size - start,
+            };
+            let mut local_file = fs::File::open(&file.source).map_err(|e| e.to_string())?;
+            local_file
+                .seek(SeekFrom::Start(start))
+                .map_err(|e| e.to_string())?;
+            let mut reader = JobReader {
+                inner: local_file,
+                job,
+            };
+            session
+                .write_file(&partial, start, &mut reader, length)
+                .map(|written| (start + written, written < length))
+        });
+        // The partial file gets the modification time of its source where the server allows it
+        session.set_attributes(&partial, file.modified, None);
+        let (written_until, source_ended) = result?;
+        offset = written_until;
+        if is_cancelled(job) {
+            return Err(TransferError::Cancelled);
+        }
+        // A source which got shorter meanwhile fails the verification
+        if offset >= file.size || source_ended {
+            break;
+        }
+    }
+
+    let verified_by = verify(
+        session,
+        &partial,
+        Path::new(&file.source),
+        options.verification,
+    )
+    .inspect_err(|_| {
+        let _ = session.remove_file(&partial);
+    })?;
+    session.set_attributes(&partial, file.modified, file.permissions);
+    finish_upload(session, &partial, &file.destination)?;
+    Ok((resumed_from, verified_by))
+}
+
+#[cfg(unix)]
+fn local_permissions(metadata: &fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(metadata.permissions().mode() & 0o777)
+}
+
+#[cfg(not(unix))]
+fn local_permissions(_metadata: &fs::Metadata) -> Option<u32> {
+    None
+}
+
+fn planned_local_file(path: &Path, destination: String, metadata: &fs::Metadata) -> PlannedFile {
+    PlannedFile {
+        source: path.to_string_lossy().to_string(),
+        destination,
+        size: metadata.len(),
+        modified: modified_seconds(metadata),
+        permissions: local_permissions(metadata),
+    }
+}
+
+/// Collects the local files and directories to upload into the remote directory. Symlinks
+/// inside directories are skipped, like the directory synchronization does.
+pub(crate) fn plan_upload(
+    local_paths: &[String],
+    remote_directory: &str,
+) -> Result<TransferPlan, String> {
+    let mut plan = TransferPlan::default();
+    for local_path in local_paths {
+        let path = Path::new(local_path);
+        let metadata = fs::metadata(path).map_err(|e| format!("{}: {}", local_path, e))?;
+        let name = path
+            .file_name()
+            .ok_or_else(|| format!("Path has no file name: {}", local_path))?
+            .to_string_lossy()
+            .to_string();
+        let destination = join_path(remote_directory, &name);
+        if !metadata.is_dir() {
+            plan.files
+                .push(planned_local_file(path, destination, &metadata));
+            continue;
+        }
+
+        for entry in walkdir::WalkDir::new(path)
+            .follow_links(false)
+            .sort_by_file_name()
+        {
+            let entry = entry.map_err(|e| e.to_string())?;
+            let relative = entry
+                .path()
+                .strip_prefix(path)
+                .map_err(|e| e.to_string())?
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy().to_string())
+                .collect::<Vec<_>>()
+                .join("/");
+            let entry_destination = join_path(&destination, &relative)
+                .trim_end_matches('/')
+                .to_string();
+            if entry.file_type().is_dir() {
+                plan.directories.push(entry_destination);
+            } else if entry.file_type().is_file() {
+                let metadata = entry.metadata().map_err(|e| e.to_string())?;
+                plan.files.push(planned_local_file(
+                    entry.path(),
+                    entry_destination,
+                    &metadata,
+                ));
+            }
+        }
+    }
+    Ok(plan)
+}
+
+pub(crate) fn planned_remote_file(
+    path: String,
+    destination: &Path,
+    stat: &FileStat,
+) -> PlannedFile {
+    PlannedFile {
+        source: path,
+        destination: destination.to_string_lossy().to_string(),
+        size: stat.size.unwrap_or(0),
+        modified: stat.mtime,
+        permissions: stat.perm.map(|perm| perm & 0o777),
+    }
+}
+
+fn plan_remote_directory(
+    session: &dyn RemoteFileSystem,
+    directory: &str,
+    destination: &Path,
+    plan: &mut TransferPlan,
+) -> Result<(), String> {
+    plan.directories
+        .push(destination.to_string_lossy().to_string());
+    let mut entries = session.list_dir(directory)?;
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    for entry in entries {
+        let Some(name) = entry
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+        else {
+            continue;
+        };
+        let remote_path = join_path(directory, &name);
+        // Symlinks are skipped, their own attributes are neither file nor directory
+        if entry.stat.is_dir() {
+            plan_remote_directory(session, &remote_path, &destination.join(&name), plan)?;
+        } else if entry.stat.is_file() {
+            plan.files.push(planned_remote_file(
+                remote_path,
+                &destination.join(&name),
+                &entry.stat,
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Collects the remote files and directories to download into the local directory.
+pub(crate) fn plan_download(
+    session: &dyn RemoteFileSystem,
+    remote_paths: &[String],
+    local_directory: &Path,
+) -> Result<TransferPlan, String> {
+    let mut plan = TransferPlan::default();
+    for remote_path in remote_paths {
+        let stat = session.stat(remote_path)?;
+        let destination = local_directory.join(remote_file_name(remote_path)?);
+        if stat.is_dir() {
+            plan_remote_directory(
+                session,
+                remote_path.trim_end_matches('/'),
+                &destination,
+                &mut plan,
+            )?;
+        } else {
+            plan.files.push(planned_remote_file(
+                remote_path.clone(),
+                &destination,
+                &stat,
+            ));
+        }
+    }
+    Ok(plan)
+}
+
+fn create_directories(
+    session: &dyn RemoteFileSystem,
+    direction: TransferDirection,
+    directories: &[String],
+) -> Result<(), String> {
+    for directory in directories {
+        match direction {
+            TransferDirection::Download => {
+                fs::create_dir_all(directory).map_err(|e| e.to_string())?
+            }
+            TransferDirection::Upload if session.stat(directory).is_err() => {
+                session.create_dir(directory)?
+            }
+            TransferDirection::Upload => {}
+        }
+    }
+    Ok(())
+}
+
+fn destination_exists(
+    session: &dyn RemoteFileSystem,
+    direction: TransferDirection,
+    path: &str,
+) -> bool {
+    match direction {
+        TransferDirection::Download => Path::new(path).exists(),
+        TransferDirection::Upload => session.stat(path).is_ok(),
+    }
+}
+
+fn run_transfer(
+    session: &dyn RemoteFileSystem,
+    direction: TransferDirection,
+    plan: TransferPlan,
+    options: &TransferOptions,
+    job: &JobHandle,
+) -> Result<TransferReport, (TransferError, TransferReport)> {
+    let mut report = TransferReport {
+        direction,
+        files: Vec::new(),
+        bytes_transferred: 0,
+    };
+    job.update_progress(|p| {
+        p.items_total = plan.files.len() as u64;
+        p.bytes_total = plan.files.iter().map(|file| file.size).sum();
+    });
+    if let Err(err) = create_directories(session, direction, &plan.directories) {
+        return Err((TransferError::Failed(err), report));
+    }
+
+    for file in plan.files {
+        if job.is_cancelled() {
+            return Err((TransferError::Cancelled, report));
+        }
+        job.update_progress(|p| p.current_path = Some(file.source.clone()));
+        let mut transferred = TransferredFile {
+            source: file.source.clone(),
+            destination: file.destination.clone(),
+            size: file.size,
+            resumed_from: 0,
+            verified_by: None,
+            status: TransferStatus::Done,
+            error: None,
+        };
+
+        if !options.overwrite && destination_exists(session, direction, &file.destination) {
+            transferred.status = TransferStatus::Skipped;
+            job.update_progress(|p| p.bytes_processed += file.size);
+        } else {
+            let result = match direction {
+                TransferDirection::Download => download_file(session, &file, options, Some(job)),
+                TransferDirection::Upload => upload_file(session, &file, options, Some(job)),
+            };
+            match result {
+                Ok((resumed_from, verified_by)) => {
+                    transferred.resumed_from = resumed_from;
+                    transferred.verified_by = Some(verified_by);
+                    report.bytes_transferred += file.size - resumed_from;
+                }
+                Err(TransferError::Cancelled) => {
+                    return Err((TransferError::Cancelled, report));
+                }
+                Err(TransferError::Failed(err)) => {
+                    log_error!("Failed to transfer '{}': {}", file.source, err);
+                    transferred.status = TransferStatus::Failed;
+                    transferred.error = Some(err);
+                }
+            }
+        }
+        report.files.push(transferred);
+        job.update_progress(|p| p.items_processed += 1);
+    }
+    Ok(report)
+}
+
+/// Starts the job running a planned transfer on its own thread. This is the only transfer path,
+/// the protocols differ only in their `RemoteFileSystem` implementation. SFTP jobs are of the
+/// kinds `sftp_upload` and `sftp_download`, the others `remote_upload` and `remote_download`.
+pub(crate) fn start_transfer(
+    session: Arc<dyn RemoteFileSystem>,
+    direction: TransferDirection,
+    plan: impl FnOnce(&dyn RemoteFileSystem) -> Result<TransferPlan, String> + Send + 'static,
+    options: TransferOptions,
+    jobs_state: Arc<Mutex<JobsState>>,
+) -> Result<u64, String> {
+    let protocol = match session.endpoint().protocol {
+        RemoteProtocol::Sftp => "sftp",
+        _ => "remote",
+    };
+    let kind = match direction {
+        TransferDirection::Upload => format!("{}_upload", protocol),
+        TransferDirection::Download => format!("{}_download", protocol),
+    };
+    let job = JobsState::start_job(&jobs_state, &kind);
+    let job_id = job.id();
+
+    std::thread::Builder::new()
+        .name("transfer-thread".to_string())
+        .spawn(move || {
+            let plan = match plan(session.as_ref()) {
+                Ok(plan) => plan,
+                Err(err) => {
+                    log_error!("Failed to plan transfer: {}", err);
+                    job.fail(err);
+                    return;
+                }
+            };
+            match run_transfer(session.as_ref(), direction, plan, &options, &job) {
+                Ok(report) => job.complete(serde_json::to_value(report).ok()),
+                Err((TransferError::Cancelled, report)) => {
+                    log_info!("Transfer was cancelled");
+                    job.set_partial_result(serde_json::to_value(report).unwrap_or_default());
+                    job.mark_cancelled();
+                }
+                Err((TransferError::Failed(err), report)) => {
+                    log_error!("Transfer failed: {}", err);
+                    job.set_partial_result(serde_json::to_value(report).unwrap_or_default());
+                    job.fail(err);
+                }
+            }
+        })
+        .map_err(|e| {
+            log_error!("Failed to spawn transfer thread: {}", e);
+            Error::new(
+                ErrorCode::InternalError,
+                format!("Failed to spawn transfer thread: {}", e),
+            )
+            .to_json()
+        })?;
+
+    Ok(job_id)
+}
+
+/// Checks that the directory downloads are written into exists.
+pub(crate) fn download_directory(local_directory: String) -> Result<PathBuf, String> {
+    let local_directory = PathBuf::from(local_directory);
+    if !local_directory.is_dir() {
+        log_error!(
+            "Download directory does not exist: {}",
+            local_directory.display()
+        );
+        return Err(Error::new(
+            ErrorCode::ResourceNotFound,
+            format!("Directory does not exist: {}", local_directory.display()),
+        )
+        .to_json());
+    }
+    Ok(local_directory)
+}
+
+/// Plans an upload, a local path which can't be read fails the command.
+pub(crate) fn plan_upload_sources(
+    local_paths: &[String],
+    remote_directory: &str,
+) -> Result<TransferPlan, String> {
+    plan_upload(local_paths, remote_directory).map_err(|err| {
+        log_error!("Failed to read upload sources: {}", err);
+        Error::new(ErrorCode::ResourceNotFound, err).to_json()
+    })
+}
+
+/// Options of the transfers done without a job, the destination is always replaced.
+fn single_file_options() -> TransferOptions {
+    TransferOptions {
+        verification: TransferVerification::Size,
+        overwrite: true,
+        ..TransferOptions::default()
+    }
+}
+
+fn without_job<T>(result: Result<T, TransferError>) -> Result<T, String> {
+    result.map_err(|err| match err {
+        TransferError::Failed(err) => err,
+        TransferError::Cancelled => "Transfer was cancelled".to_string(),
+    })
+}
+
+/// Downloads a single file without a job, resuming a partial file of an earlier attempt.
+/// Used to fetch files which are opened locally. Returns the attributes of the remote file
+/// the download started with.
+pub(crate) fn download_to_path(
+    session: &dyn RemoteFileSystem,
+    remote_path: &str,
+    local_path: &Path,
+) -> Result<FileStat, String> {
+    let stat = session
+        .stat(remote_path)
+        .map_err(|e| format!("{}: {}", remote_path, e))?;
+    let file = planned_remote_file(remote_path.to_string(), local_path, &stat);
+    without_job(download_file(session, &file, &single_file_options(), None))?;
+    Ok(stat)
+}
+
+/// Uploads a single file without a job and replaces the remote file, e.g. a locally edited
+/// copy of it.
+pub(crate) fn upload_from_path(
+    session: &dyn RemoteFileSystem,
+    local_path: &Path,
+    remote_path: &str,
+) -> Result<(), String> {
+    let metadata = fs::metadata(local_path).map_err(|e| e.to_string())?;
+    let file = planned_local_file(local_path, remote_path.to_string(), &metadata);
+    without_job(upload_file(session, &file, &single_file_options(), None)).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests_transfer {
+    use super::*;
+    use crate::remote::test_servers::{FtpTestServer, TEST_PASSWORD, TEST_USER};
+    use crate::remote::{FtpSession, RemoteSessionConfig};
+    use tempfile::tempdir;
+
+    #[test]
+    fn remote_file_name_test() {
+        assert_eq!(remote_file_name("/var/log/").unwrap(), "log");
+        assert!(remote_file_name("/").is_err());
+    }
+
+    #[test]
+    fn plan_upload_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let photos = temp_dir.path().join("photos");
+        fs::create_dir_all(photos.join("2024")).unwrap();
+        fs::write(photos.join("2024").join("a.jpg"), "abc").unwrap();
+        fs::write(photos.join("b.jpg"), "de").unwrap();
+        let notes = temp_dir.path().join("notes.txt");
+        fs::write(&notes, "hello").unwrap();
+
+        let plan = plan_upload(
+            &[
+                photos.to_string_lossy().to_string(),
+                notes.to_string_lossy().to_string(),
+            ],
+            "/srv/backup",
+        )
+        .unwrap();
+
+        assert_eq!(
+            plan.directories,
+            vec!["/srv/backup/photos", "/srv/backup/photos/2024"]
+        );
+        let files: Vec<(&str, u64)> = plan
+            .files
+            .iter()
+            .map(|file| (file.destination.as_str(), file.size))
+            .collect();
+        assert_eq!(
+            files,
+            vec![
+                ("/srv/backup/photos/2024/a.jpg", 3),
+                ("/srv/backup/photos/b.jpg", 2),
+                ("/srv/backup/notes.txt", 5),
+            ]
+        );
+        assert!(plan.files.iter().all(|file| file.modified.is_some()));
+
+        assert!(plan_upload(&["/does/not/exist".to_string()], "/srv").is_err());
+    }
+
+    #[test]
+    fn files_larger_than_a_batch_are_transferred_test() {
+        let server = FtpTestServer::start(false);
+        let session = FtpSession::connect(RemoteSessionConfig {
+            protocol: RemoteProtocol::Ftp,
+            host: "127.0.0.1".to_string(),
+            port: Some(server.port()),
+            username: TEST_USER.to_string(),
+            password: TEST_PASSWORD.to_string(),
+            base_path: None,
+            region: None,
+            accept_invalid_certificates: false,
+        })
+        .unwrap();
+        let local = tempdir().expect("Failed to create temporary directory");
+        let source = local.path().join("large.bin");
+        let content: Vec<u8> = (0..BATCH_SIZE + 10).map(|i| (i % 251) as u8).collect();
+        fs::write(&source, &content).unwrap();
+
+        let metadata = fs::metadata(&source).unwrap();
+        let file = planned_local_file(&source, "/large.bin".to_string(), &metadata);
+        without_job(upload_file(&session, &file, &single_file_options(), None)).unwrap();
+        assert_eq!(fs::read(server.root().join("large.bin")).unwrap(), content);
+
+        let target = local.path().join("copy.bin");
+        let stat = RemoteFileSystem::stat(&session, "/large.bin").unwrap();
+        let file = planned_remote_file("/large.bin".to_string(), &target, &stat);
+        without_job(download_file(&session, &file, &single_file_options(), None)).unwrap();
+        assert_eq!(fs::read(&target).unwrap(), content);
+        assert!(!local.path().join("copy.bin.part").exists());
+    }
+}
@@ -0,0 +1,623 @@
+//! WebDAV client (RFC 4918) on the HTTP client of [`super::http`].
+
+use super::http::{self, parse_http_date, send_request, Body, Response};
+use super::{
+    file_stat, remote_error, RemoteEndpoint, RemoteEntry, RemoteFileSystem, RemoteSessionConfig,
+    S_IFDIR, S_IFREG,
+};
+use crate::error_handling::ErrorCode;
+use base64::Engine;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use reqwest::blocking::Client;
+use ssh2::FileStat;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+
+/// Characters escaped in the path segments of request URLs.
+const SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'[')
+    .add(b'\\')
+    .add(b']')
+    .add(b'^')
+    .add(b'`')
+    .add(b'{')
+    .add(b'|')
+    .add(b'}');
+
+const PROPFIND_BODY: &str = concat!(
+    r#"<?xml version="1.0" encoding="utf-8"?>"#,
+    r#"<d:propfind xmlns:d="DAV:"><d:prop>"#,
+    r#"<d:resourcetype/><d:getcontentlength/><d:getlastmodified/>"#,
+    r#"</d:prop></d:propfind>"#
+);
+
+/// Reads the hrefs and their attributes from a `multistatus` response of `PROPFIND`.
+/// Properties of a `propstat` which does not have the status 200 are ignored.
+fn parse_multistatus(xml: &str) -> Result<Vec<(String, FileStat)>, String> {
+    #[derive(Default, Clone, Copy)]
+    struct Properties {
+        is_collection: bool,
+        size: Option<u64>,
+        modified: Option<u64>,
+    }
+
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+    let mut elements: Vec<String> = Vec::new();
+    let mut entries = Vec::new();
+    let mut href = None;
+    let mut properties = Properties::default();
+    let mut propstat = Properties::default();
+    let mut propstat_ok = false;
+
+    loop {
+        let event = reader
+            .read_event()
+            .map_err(|e| format!("Invalid PROPFIND response: {}", e))?;
+        match event {
+            Event::Start(element) => {
+                let name = String::from_utf8_lossy(element.local_name().as_ref()).to_string();
+                match name.as_str() {
+                    "response" => {
+                        href = None;
+                        properties = Properties::default();
+                    }
+                    "propstat" => {
+                        propstat = Properties::default();
+                        propstat_ok = false;
+                    }
+                    "collection" if elements.last().is_some_and(|e| e == "resourcetype") => {
+                        propstat.is_collection = true
+                    }
+                    _ => {}
+                }
+                elements.push(name);
+            }
+            Event::Empty(element)
+                if element.local_name().as_ref() == b"collection"
+                    && elements.last().is_some_and(|e| e == "resourcetype") =>
+            {
+                propstat.is_collection = true;
+            }
+            Event::Text(text) => {
+                let text = text
+                    .unescape()
+                    .map_err(|e| format!("Invalid PROPFIND response: {}", e))?;
+                let parent = elements
+                    .len()
+                    .checked_sub(2)
+                    .map(|index| elements[index].as_str());
+                match (elements.last().map(String::as_str), parent) {
+                    (Some("href"), Some("response")) => href = Some(text.to_string()),
+                    (Some("getcontentlength"), _) => propstat.size = text.trim().parse().ok(),
+                    (Some("getlastmodified"), _) => propstat.modified = parse_http_date(&text),
+                    (Some("status"), Some("propstat")) => {
+                        propstat_ok = text.split(' ').nth(1) == Some("200")
+                    }
+                    _ => {}
+                }
+            }
+            Event::End(_) => match elements.pop().as_deref() {
+                Some("propstat") if propstat_ok => {
+                    properties.is_collection |= propstat.is_collection;
+                    properties.size = properties.size.or(propstat.size);
+                    properties.modified = properties.modified.or(propstat.modified);
+                }
+                Some("response") => {
+                    if let Some(href) = href.take() {
+                        let stat = match properties.is_collection {
+                            true => file_stat(S_IFDIR, None, properties.modified),
+                            false => file_stat(S_IFREG, properties.size, properties.modified),
+                        };
+                        entries.push((href, stat));
+                    }
+                }
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(entries)
+}
+
+/// Makes a path absolute and removes `.` segments and trailing slashes. WebDAV has no login
+/// directory, relative paths start at the WebDAV root.
+fn normalize(path: &str) -> String {
+    let segments: Vec<&str> = path
+        .split('/')
+        .filter(|segment| !segment.is_empty() && *segment != ".")
+        .collect();
+    format!("/{}", segments.join("/"))
+}
+
+/// A WebDAV session, e.g. to Nextcloud, ownCloud or a web server with WebDAV enabled.
+pub struct WebDavSession {
+    config: RemoteSessionConfig,
+    /// The path of the WebDAV root on the server, without a trailing slash.
+    base_path: String,
+    authorization: Option<String>,
+    client: Client,
+}
+
+impl WebDavSession {
+    /// Checks that the WebDAV root can be listed with the credentials.
+    pub fn connect(config: RemoteSessionConfig) -> Result<Self, String> {
+        let client = http::client(config.accept_invalid_certificates)
+            .map_err(|e| remote_error(ErrorCode::InternalError, e))?;
+        let base_path = normalize(config.base_path.as_deref().unwrap_or(""))
+            .trim_end_matches('/')
+            .to_string();
+        let authorization = (!config.username.is_empty()).then(|| {
+            let credentials = format!("{}:{}", config.username, config.password);
+            format!(
+                "Basic {}",
+                base64::engine::general_purpose::STANDARD.encode(credentials)
+            )
+        });
+        let session = Self {
+            config,
+            base_path,
+            authorization,
+            client,
+        };
+
+        if !session.stat("/")?.is_dir() {
+            return Err(remote_error(
+                ErrorCode::InvalidInput,
+                format!("{} is not a WebDAV directory", session.url("/")),
+            ));
+        }
+        Ok(session)
+    }
+
+    fn host_header(&self) -> String {
+        http::host_header(
+            &self.config.host,
+            self.config.port(),
+            self.config.protocol.default_port(),
+        )
+    }
+
+    /// The encoded path of the request URL of a remote path.
+    fn url_path(&self, path: &str) -> String {
+        let encoded: Vec<String> = normalize(path)
+            .split('/')
+            .map(|segment| utf8_percent_encode(segment, SEGMENT).to_string())
+            .collect();
+        format!("{}{}", self.base_path, encoded.join("/"))
+    }
+
+    fn url(&self, path: &str) -> String {
+        let scheme = match self.config.protocol.uses_tls() {
+            true => "https",
+            false => "http",
+        };
+        format!("{}://{}{}", scheme, self.host_header(), self.url_path(path))
+    }
+
+    /// The remote path of an href of a `PROPFIND` response, `None` if it is outside the root.
+    fn remote_path(&self, href: &str) -> Option<String> {
+        // hrefs are absolute paths or full URLs
+        let path = match href.split_once("://") {
+            Some((_, rest)) => rest.find('/').map_or("/", |start| &rest[start..]),
+            None => href,
+        };
+        let decoded = percent_decode_str(path).decode_utf8_lossy();
+        let relative = decoded.strip_prefix(self.base_path.as_str())?;
+        if !relative.is_empty() && !relative.starts_with('/') {
+            return None;
+        }
+        Some(normalize(relative))
+    }
+
+    fn request(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &[(&str, String)],
+        body: Body,
+    ) -> Result<Response, String> {
+        let io_error = |e: io::Error| {
+            remote_error(
+                ErrorCode::InternalError,
+                format!("WebDAV {} {} failed: {}", method, path, e),
+            )
+        };
+        let mut request_headers: Vec<(&str, String)> = Vec::new();
+        if let Some(authorization) = &self.authorization {
+            request_headers.push(("Authorization", authorization.clone()));
+        }
+        request_headers.extend(headers.iter().cloned());
+        send_request(
+            &self.client,
+            method,
+            &self.url(path),
+            &request_headers,
+            body,
+        )
+        .map_err(io_error)
+    }
+
+    /// Fails with the status of the response unless it is one of the `accepted` ones.
+    fn check(
+        &self,
+        method: &str,
+        path: &str,
+        response: Response,
+        accepted: &[u16],
+    ) -> Result<Response, String> {
+        if accepted.contains(&response.status) {
+            return Ok(response);
+        }
+        let code = match response.status {
+            401 | 403 => ErrorCode::Unauthorized,
+            // A missing parent directory is a conflict
+            404 | 409 => ErrorCode::ResourceNotFound,
+            405 if method == "MKCOL" => ErrorCode::ResourceAlreadyExists,
+            412 => ErrorCode::ResourceAlreadyExists,
+            _ => ErrorCode::InternalError,
+        };
+        Err(remote_error(
+            code,
+            format!(
+                "WebDAV {} {} failed: {} {}",
+                method, path, response.status, response.reason
+            ),
+        ))
+    }
+
+    fn send(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &[(&str, String)],
+        body: Body,
+        accepted: &[u16],
+    ) -> Result<Response, String> {
+        let response = self.request(method, path, headers, body)?;
+        self.check(method, path, response, accepted)
+    }
+
+    fn propfind(&self, path: &str, depth: u32) -> Result<Vec<(String, FileStat)>, String> {
+        let headers = [
+            ("Depth", depth.to_string()),
+            ("Content-Type", "application/xml; charset=utf-8".to_string()),
+        ];
+        let mut response = self.send(
+            "PROPFIND",
+            path,
+            &headers,
+            Body::Bytes(PROPFIND_BODY.as_bytes()),
+            &[207],
+        )?;
+        let mut xml = String::new();
+        response.body.read_to_string(&mut xml).map_err(|e| {
+            remote_error(
+                ErrorCode::InternalError,
+                format!("Failed to read PROPFIND response of {}: {}", path, e),
+            )
+        })?;
+        let entries = parse_multistatus(&xml)
+            .map_err(|message| remote_error(ErrorCode::InternalError, message))?;
+        Ok(entries
+            .into_iter()
+            .filter_map(|(href, stat)| Some((self.remote_path(&href)?, stat)))
+            .collect())
+    }
+}
+
+impl RemoteFileSystem for WebDavSession {
+    fn endpoint(&self) -> RemoteEndpoint {
+        RemoteEndpoint {
+            protocol: self.config.protocol,
+            host: self.config.host.clone(),
+            port: self.config.port(),
+            username: self.config.username.clone(),
+        }
+    }
+
+    fn is_connected(&self) -> bool {
+        true
+    }
+
+    fn list_dir(&self, path: &str) -> Result<Vec<RemoteEntry>, String> {
+        let directory = normalize(path);
+        // Collections are requested with a trailing slash, some servers redirect otherwise
+        Ok(self
+            .propfind(&format!("{}/", directory), 1)?
+            .into_iter()
+            .filter(|(entry_path, _)| *entry_path != directory)
+            .map(|(entry_path, stat)| RemoteEntry {
+                path: PathBuf::from(entry_path),
+                stat,
+                symlink_target: None,
+                target_stat: None,
+            })
+            .collect())
+    }
+
+    fn stat(&self, path: &str) -> Result<FileStat, String> {
+        self.propfind(path, 0)?
+            .into_iter()
+            .next()
+            .map(|(_, stat)| stat)
+            .ok_or_else(|| {
+                remote_error(
+                    ErrorCode::ResourceNotFound,
+                    format!("WebDAV PROPFIND {} returned no entry", path),
+                )
+            })
+    }
+
+    fn read_file(
+        &self,
+        path: &str,
+        offset: u64,
+        limit: Option<u64>,
+        writer: &mut dyn Write,
+    ) -> Result<u64, String> {
+        let range = match limit {
+            Some(0) => return Ok(0),
+            Some(limit) => Some(format!("bytes={}-{}", offset, offset + limit - 1)),
+            None if offset > 0 => Some(format!("bytes={}-", offset)),
+            None => None,
+        };
+        let headers: Vec<(&str, String)> =
+            range.into_iter().map(|range| ("Range", range)).collect();
+        let mut response = self.send("GET", path, &headers, Body::Empty, &[200, 206, 416])?;
+        let copy_error = |e: io::Error| {
+            remote_error(
+                ErrorCode::InternalError,
+                format!("Failed to download {}: {}", path, e),
+            )
+        };
+        match response.status {
+            // The offset is the end of the file
+            416 => return Ok(0),
+            // The server ignored the range and sends the whole file
+            200 if offset > 0 => {
+                io::copy(&mut (&mut response.body).take(offset), &mut io::sink())
+                    .map_err(copy_error)?;
+            }
+            _ => {}
+        }
+        let mut body = response.body.take(limit.unwrap_or(u64::MAX));
+        io::copy(&mut body, writer).map_err(copy_error)
+    }
+
+    fn write_file(
+        &self,
+        path: &str,
+        offset: u64,
+        reader: &mut dyn Read,
+        length: u64,
+    ) -> Result<u64, String> {
+        if offset > 0 {
+            return Err(remote_error(
+                ErrorCode::NotImplemented,
+                format!("WebDAV can't append to {}", path),
+            ));
+        }
+        self.send(
+            "PUT",
+            path,
+            &[],
+            Body::Reader(reader, length),
+            &[200, 201, 204],
+        )?;
+        Ok(length)
+    }
+
+    fn supports_append(&self) -> bool {
+        false
+    }
+
+    fn create_dir(&self, path: &str) -> Result<(), String> {
+        self.send("MKCOL", path, &[], Body::Empty, &[201])
+            .map(|_| ())
+    }
+
+    fn remove_file(&self, path: &str) -> Result<(), String> {
+        self.send("DELETE", path, &[], Body::Empty, &[200, 204])
+            .map(|_| ())
+    }
+
+    fn remove_dir(&self, path: &str) -> Result<(), String> {
+        self.remove_file(path)
+    }
+
+    fn rename(&self, from: &str, to: &str) -> Result<(), String> {
+        let headers = [
+            ("Destination", self.url(to)),
+            ("Overwrite", "F".to_string()),
+        ];
+        self.send("MOVE", from, &headers, Body::Empty, &[201, 204])
+            .map(|_| ())
+    }
+
+    fn disconnect(&self) {}
+}
+
+#[cfg(test)]
+mod tests_webdav {
+    use super::*;
+    use crate::remote::test_servers::{WebDavTestServer, TEST_PASSWORD, TEST_USER};
+    use crate::remote::RemoteProtocol;
+    use std::fs;
+
+    fn config(server: &WebDavTestServer) -> RemoteSessionConfig {
+        RemoteSessionConfig {
+            protocol: match server.is_tls() {
+                true => RemoteProtocol::WebDavs,
+                false => RemoteProtocol::WebDav,
+            },
+            host: "127.0.0.1".to_string(),
+            port: Some(server.port()),
+            username: TEST_USER.to_string(),
+            password: TEST_PASSWORD.to_string(),
+            base_path: Some(server.base_path().to_string()),
+            region: None,
+            accept_invalid_certificates: true,
+        }
+    }
+
+    fn read_all(session: &WebDavSession, path: &str, offset: u64, limit: Option<u64>) -> String {
+        let mut content = Vec::new();
+        session
+            .read_file(path, offset, limit, &mut content)
+            .unwrap();
+        String::from_utf8(content).unwrap()
+    }
+
+    fn check_operations(session: &WebDavSession, server: &WebDavTestServer) {
+        session.create_dir("/my docs").unwrap();
+        let error = session.create_dir("/my docs").unwrap_err();
+        assert!(error.contains("409"), "{}", error);
+
+        let content = "first line\nsecond line\n".repeat(200);
+        session
+            .write_file(
+                "/my docs/notes #1.txt",
+                0,
+                &mut content.as_bytes(),
+                content.len() as u64,
+            )
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(server.root().join("my docs/notes #1.txt")).unwrap(),
+            content
+        );
+        assert!(session
+            .write_file("/my docs/notes #1.txt", 5, &mut &b"x"[..], 1)
+            .is_err());
+
+        let listing = session.list_dir("my docs").unwrap();
+        assert_eq!(listing.len(), 1);
+        assert_eq!(listing[0].path, PathBuf::from("/my docs/notes #1.txt"));
+        assert_eq!(listing[0].stat.size, Some(content.len() as u64));
+        assert!(listing[0].stat.mtime.is_some());
+        let root = session.list_dir("/").unwrap();
+        assert!(root.len() == 1 && root[0].stat.is_dir());
+
+        // The whole file is sent chunked, ranges with a length
+        assert_eq!(read_all(session, "/my docs/notes #1.txt", 0, None), content);
+        assert_eq!(
+            read_all(session, "/my docs/notes #1.txt", 6, Some(4)),
+            "line"
+        );
+        assert_eq!(
+            read_all(session, "/my docs/notes #1.txt", content.len() as u64, None),
+            ""
+        );
+
+        session
+            .rename("/my docs/notes #1.txt", "/my docs/renamed.txt")
+            .unwrap();
+        assert!(server.root().join("my docs/renamed.txt").exists());
+        session.remove_file("/my docs/renamed.txt").unwrap();
+        session.remove_dir("/my docs").unwrap();
+        assert!(!server.root().join("my docs").exists());
+
+        let error = session.stat("/missing").unwrap_err();
+        assert!(error.contains("405"), "{}", error);
+    }
+
+    #[test]
+    fn webdav_session_operations_test() {
+        let server = WebDavTestServer::start(false);
+        let session = WebDavSession::connect(config(&server)).unwrap();
+        check_operations(&session, &server);
+    }
+
+    #[test]
+    fn webdavs_session_operations_test() {
+        let server = WebDavTestServer::start(true);
+        let session = WebDavSession::connect(config(&server)).unwrap();
+        check_operations(&session, &server);
+    }
+
+    #[test]
+    fn webdav_wrong_password_test() {
+        let server = WebDavTestServer::start(false);
+        let mut wrong = config(&server);
+        wrong.password = "wrong".to_string();
+        let error = WebDavSession::connect(wrong).err().unwrap();
+        assert!(error.contains("401"), "{}", error);
+    }
+
+    #[test]
+    fn parse_multistatus_test() {
+        let xml = r#"<?xml version="1.0"?>
+            <d:multistatus xmlns:d="DAV:">
+              <d:response>
+                <d:href>/remote.php/dav/files/alice/Photos/</d:href>
+                <d:propstat>
+                  <d:prop>
+                    <d:resourcetype><d:collection/></d:resourcetype>
+                    <d:getlastmodified>Tue, 14 Nov 2023 22:13:20 GMT</d:getlastmodified>
+                  </d:prop>
+                  <d:status>HTTP/1.1 200 OK</d:status>
+                </d:propstat>
+                <d:propstat>
+                  <d:prop><d:getcontentlength/></d:prop>
+                  <d:status>HTTP/1.1 404 Not Found</d:status>
+                </d:propstat>
+              </d:response>
+              <d:response>
+                <d:href>https://cloud.example.com/remote.php/dav/files/alice/Photos/a%20%26%20b.jpg</d:href>
+                <d:propstat>
+                  <d:prop>
+                    <d:resourcetype/>
+                    <d:getcontentlength>48213</d:getcontentlength>
+                  </d:prop>
+                  <d:status>HTTP/1.1 200 OK</d:status>
+                </d:propstat>
+              </d:response>
+            </d:multistatus>"#;
+        let entries = parse_multistatus(xml).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].1.is_dir());
+        assert_eq!(entries[0].1.mtime, Some(1_700_000_000));
+        assert_eq!(entries[1].1.size, Some(48213));
+
+        let session = WebDavSession {
+            config: RemoteSessionConfig {
+                protocol: RemoteProtocol::WebDavs,
+                host: "cloud.example.com".to_string(),
+                port: None,
+                username: String::new(),
+                password: String::new(),
+                base_path: None,
+                region: None,
+                accept_invalid_certificates: false,
+            },
+            base_path: "/remote.php/dav/files/alice".to_string(),
+            authorization: None,
+            client: Client::new(),
+        };
+        assert_eq!(
+            session.remote_path(&entries[0].0).as_deref(),
+            Some("/Photos")
+        );
+        assert_eq!(
+            session.remote_path(&entries[1].0).as_deref(),
+            Some("/Photos/a & b.jpg")
+        );
+        assert_eq!(session.remote_path("/remote.php/dav/files/alicia/x"), None);
+        assert_eq!(
+            session.url_path("Photos/a & b?.jpg"),
+            "/remote.php/dav/files/alice/Photos/a%20&%20b%3F.jpg"
+        );
+    }
+}
@@ -5,6 +5,7 @@ use smallvec::SmallVec;
 use std::cmp;
 use std::mem;
 
+#[allow(clippy::upper_case_acronyms)]
 pub struct ART {
     root: Option<Box<ARTNode>>,
     path_count: usize,
@@ -20,6 +21,7 @@ type KeyType = u8;
 
 type Prefix = SmallVec<[KeyType; 8]>;
 
+#[allow(clippy::large_enum_variant)]
 enum ARTNode {
     Node4(Node4),
     Node16(Node16),
@@ -123,8 +125,7 @@ impl ARTNode {
                             return false;
                         }
                     }
-                    let added = self.add_child(key, child.take());
-                    added
+                    self.add_child(key, child.take())
                 } else {
                     n.add_child(key, child.take())
                 }
@@ -141,8 +142,7 @@ impl ARTNode {
                             return false;
                         }
                     }
-                    let added = self.add_child(key, child.take());
-                    added
+                    self.add_child(key, child.take())
                 } else {
                     n.add_child(key, child.take())
                 }
@@ -159,8 +159,7 @@ impl ARTNode {
                             return false;
                         }
                     }
-                    let added = self.add_child(key, child.take());
-                    added
+                    self.add_child(key, child.take())
                 } else {
                     n.add_child(key, child.take())
                 }
@@ -170,7 +169,7 @@ impl ARTNode {
         added || grown
     }
 
-    fn find_child(&self, key: KeyType) -> Option<&Box<ARTNode>> {
+    fn find_child(&self, key: KeyType) -> Option<&ARTNode> {
         match self {
             ARTNode::Node4(n) => n.find_child(key),
             ARTNode::Node16(n) => n.find_child(key),
@@ -229,7 +228,7 @@ impl ARTNode {
         removed
     }
 
-    fn iter_children(&self) -> Vec<(KeyType, &Box<ARTNode>)> {
+    fn iter_children(&self) -> Vec<(KeyType, &ARTNode)> {
         match self {
             ARTNode::Node4(n) => n.iter_children(),
             ARTNode::Node16(n) => n.iter_children(),
@@ -451,10 +450,10 @@ impl Node4 {
         true
     }
 
-    fn find_child(&self, key: KeyType) -> Option<&Box<ARTNode>> {
+    fn find_child(&self, key: KeyType) -> Option<&ARTNode> {
         for i in 0..self.keys.len() {
             if self.keys[i] == key {
-                return self.children[i].as_ref();
+                return self.children[i].as_deref();
             }
         }
         None
@@ -480,11 +479,11 @@ impl Node4 {
         None
     }
 
-    fn iter_children(&self) -> Vec<(KeyType, &Box<ARTNode>)> {
+    fn iter_children(&self) -> Vec<(KeyType, &ARTNode)> {
         let mut result = Vec::with_capacity(self.keys.len());
         for i in 0..self.keys.len() {
             if let Some(child) = &self.children[i] {
-                result.push((self.keys[i], child));
+                result.push((self.keys[i], child.as_ref()));
             }
         }
         result
@@ -524,10 +523,10 @@ impl Node16 {
         true
     }
 
-    fn find_child(&self, key: KeyType) -> Option<&Box<ARTNode>> {
+    fn find_child(&self, key: KeyType) -> Option<&ARTNode> {
         for i in 0..self.keys.len() {
             if self.keys[i] == key {
-                return self.children[i].as_ref();
+                return self.children[i].as_deref();
             }
         }
         None
@@ -553,11 +552,11 @@ impl Node16 {
         None
     }
 
-    fn iter_children(&self) -> Vec<(KeyType, &Box<ARTNode>)> {
+    fn iter_children(&self) -> Vec<(KeyType, &ARTNode)> {
         let mut result = Vec::with_capacity(self.keys.len());
         for i in 0..self.keys.len() {
             if let Some(child) = &self.children[i] {
-                result.push((self.keys[i], child));
+                result.push((self.keys[i], child.as_ref()));
             }
         }
         result
@@ -594,10 +593,10 @@ impl Node48 {
         true
     }
 
-    fn find_child(&self, key: KeyType) -> Option<&Box<ARTNode>> {
+    fn find_child(&self, key: KeyType) -> Option<&ARTNode> {
         let key_idx = key as usize;
         if let Some(idx) = self.child_index[key_idx] {
-            self.children[idx as usize].as_ref()
+            self.children[idx as usize].as_deref()
         } else {
             None
         }
@@ -617,7 +616,7 @@ impl Node48 {
 
         if let Some(idx) = self.child_index[key_idx] {
             let idx = idx as usize;
-            let removed = mem::replace(&mut self.children[idx], None);
+            let removed = self.children[idx].take();
 
             self.child_index[key_idx] = None;
 
@@ -640,12 +639,12 @@ impl Node48 {
         }
     }
 
-    fn iter_children(&self) -> Vec<(KeyType, &Box<ARTNode>)> {
+    fn iter_children(&self) -> Vec<(KeyType, &ARTNode)> {
         let mut result = Vec::with_capacity(self.size);
         for i in 0..256 {
             if let Some(idx) = self.child_index[i] {
                 if let Some(child) = &self.children[idx as usize] {
-                    result.push((i as KeyType, child));
+                    result.push((i as KeyType, child.as_ref()));
                 }
             }
         }
@@ -677,8 +676,8 @@ impl Node256 {
         true
     }
 
-    fn find_child(&self, key: KeyType) -> Option<&Box<ARTNode>> {
-        self.children[key as usize].as_ref()
+    fn find_child(&self, key: KeyType) -> Option<&ARTNode> {
+        self.children[key as usize].as_deref()
     }
 
     fn find_child_mut(&mut self, key: KeyType) -> Option<&mut Option<Box<ARTNode>>> {
@@ -689,7 +688,7 @@ impl Node256 {
         let key_idx = key as usize;
 
         if self.children[key_idx].is_some() {
-            let removed = mem::replace(&mut self.children[key_idx], None);
+            let removed = self.children[key_idx].take();
             self.size -= 1;
             removed
         } else {
@@ -697,11 +696,11 @@ impl Node256 {
         }
     }
 
-    fn iter_children(&self) -> Vec<(KeyType, &Box<ARTNode>)> {
+    fn iter_children(&self) -> Vec<(KeyType, &ARTNode)> {
         let mut result = Vec::with_capacity(self.size);
         for i in 0..256 {
             if let Some(child) = &self.children[i] {
-                result.push((i as KeyType, child));
+                result.push((i as KeyType, child.as_ref()));
             }
         }
         result
@@ -1575,7 +1574,7 @@ impl ART {
                 let rem = normalized_bytes.len() - depth;
                 // Case A: the search prefix ends inside this node's prefix
                 if rem < prefix_len {
-                    if &node_prefix[..rem] != &normalized_bytes[depth..] {
+                    if node_prefix[..rem] != normalized_bytes[depth..] {
                         return Vec::new();
                     }
                     // Build base string so far: path_acc + full node_prefix
@@ -1589,7 +1588,7 @@ impl ART {
                     return results;
                 }
                 // Case B: need to match the entire node_prefix
-                if &node_prefix[..] != &normalized_bytes[depth..depth + prefix_len] {
+                if node_prefix != &normalized_bytes[depth..depth + prefix_len] {
                     return Vec::new();
                 }
                 // Full match: append node_prefix to path_acc and advance depth
@@ -1742,7 +1741,7 @@ impl ART {
                 if children.len() == 1 {
                     let (key, child) = &children[0];
                     if child.get_prefix().is_empty() {
-                        let mut merged_child = (**child).clone();
+                        let mut merged_child = Box::new((*child).clone());
                         let mut new_prefix = node_box.get_prefix().to_vec();
                         new_prefix.push(*key);
                         new_prefix.extend_from_slice(merged_child.get_prefix());
@@ -1753,7 +1752,7 @@ impl ART {
             }
 
             // If this node should not be removed, consider shrinking its type based on child count
-            if !(!node_box.is_terminal() && node_box.num_children() == 0) {
+            if node_box.is_terminal() || node_box.num_children() != 0 {
                 let prefix = node_box.get_prefix().to_vec();
                 let is_term = node_box.is_terminal();
                 let score = node_box.get_score();
@@ -1851,7 +1850,7 @@ impl ART {
         results.sort_by(|a, b| {
             // Use partial_cmp with a fallback to ensure stable sorting
             b.1.partial_cmp(&a.1)
-                .unwrap_or_else(|| cmp::Ordering::Equal)
+                .unwrap_or(cmp::Ordering::Equal)
         });
 
         // Deduplicate results if needed
@@ -2023,7 +2022,7 @@ mod tests_art_v5 {
                 }
             }
 
-            if let Some(walker) = std::fs::read_dir(dir).ok() {
+            if let Ok(walker) = std::fs::read_dir(dir) {
                 for entry in walker.filter_map(|e| e.ok()) {
                     let path = entry.path();
                     if let Some(path_str) = path.to_str() {
@@ -2429,7 +2428,7 @@ mod tests_art_v5 {
             before_completions.len(),
             path1
         );
-        log_info!("is_in_trie: {}", trie.find_completions(path1).len() > 0);
+        log_info!("is_in_trie: {}", !trie.find_completions(path1).is_empty());
         assert_eq!(
             before_completions.len(),
             1,
@@ -2683,7 +2682,7 @@ mod tests_art_v5 {
                 elapsed
             );
 
-            if completions.len() > 0 {
+            if !completions.is_empty() {
                 log_info!(
                     "First completion: {} (score: {:.1})",
                     completions[0].0,
@@ -2772,7 +2771,7 @@ mod tests_art_v5 {
             if i == 3 || i == 4 || i == 5 || i == 6 || i == 7 || i == 8 || i == 9 || i == 10 {
                 trie.debug_print();
             }
-            assert!(trie.find_completions(&path).len() > 0);
+            assert!(!trie.find_completions(&path).is_empty());
         }
 
         log_info!("Inserted {} paths with common prefix", trie.len());
@@ -2962,7 +2961,7 @@ mod tests_art_v5 {
                 let components: Vec<&str> = path.split(MAIN_SEPARATOR).collect();
                 for (i, component) in components.iter().enumerate() {
                     if !component.is_empty() {
-                        let prefix_path = components[0..=i].join(&MAIN_SEPARATOR.to_string());
+                        let prefix_path = components[0..=i].join(std::path::MAIN_SEPARATOR_STR);
                         *common_dirs.entry(prefix_path).or_insert(0) += 1;
                     }
                 }
@@ -2970,7 +2969,7 @@ mod tests_art_v5 {
 
             // Use the most common prefixes
             let mut prefix_counts: Vec<(String, usize)> = common_dirs.into_iter().collect();
-            prefix_counts.sort_by(|a, b| b.1.cmp(&a.1));
+            prefix_counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
 
             for (prefix, _count) in prefix_counts.into_iter().take(5) {
                 prefixes.push(prefix);
@@ -3066,8 +3065,8 @@ mod tests_art_v5 {
         let to_remove = paths.len().min(50);
         let mut removed = 0;
 
-        for i in 0..to_remove {
-            if trie.remove(&paths[i]) {
+        for path in paths.iter().take(to_remove) {
+            if trie.remove(path) {
                 removed += 1;
             }
         }
@@ -3479,12 +3478,10 @@ mod tests_art_v5 {
         }
 
         // Test searches with escaped spaces
-        let searches = vec![
-            "./test-data-for-fuzzy-search\\ coconut",
+        let searches = ["./test-data-for-fuzzy-search\\ coconut",
             "./test-data-for-fuzzy-search\\ blueberry",
             "./test-data-for-fuzzy-search\\ truck\\banana\\ raspberry",
-            "./test-data-for-fuzzy-search\\ tangerine",
-        ];
+            "./test-data-for-fuzzy-search\\ tangerine"];
 
         for (i, search) in searches.iter().enumerate() {
             let results = trie.find_completions(search);
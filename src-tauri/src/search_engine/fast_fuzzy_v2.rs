@@ -105,6 +105,7 @@ impl PathMatcher {
     ///
     /// The mapping table is used for efficient lowercase conversion without
     /// having to use the more expensive Unicode-aware to_lowercase() function.
+    #[allow(clippy::needless_range_loop)]
     fn init_char_mapping() {
         CHAR_MAPPING_INIT.call_once(|| unsafe {
             for i in 0..256 {
@@ -331,10 +332,7 @@ impl PathMatcher {
 
         // Sigmoid function: 1 - MIN_FACTOR/(1 + e^(-STEEPNESS * (x - MIDPOINT)))
         let length_f32 = path_length as f32;
-        let sigmoid =
-            1.0 - (1.0 - MIN_FACTOR) / (1.0 + (-STEEPNESS * (length_f32 - MIDPOINT)).exp());
-
-        sigmoid
+        1.0 - (1.0 - MIN_FACTOR) / (1.0 + (-STEEPNESS * (length_f32 - MIDPOINT)).exp())
     }
 
     /// Searches for paths matching the given query string, supporting fuzzy matching.
@@ -387,7 +385,7 @@ impl PathMatcher {
         }
 
         // 32-bit words can track 32 paths each
-        let bitmap_size = (self.paths.len() + 31) / 32;
+        let bitmap_size = self.paths.len().div_ceil(32);
         let mut path_bitmap = vec![0u32; bitmap_size];
         let mut hit_counts = vec![0u16; self.paths.len()];
         let mut total_hits = 0;
@@ -427,7 +425,7 @@ impl PathMatcher {
             .collect();
 
         // Sort candidates by hit count descending (most trigrams in common first)
-        candidates.sort_unstable_by(|a, b| b.1.cmp(&a.1));
+        candidates.sort_unstable_by_key(|&(_, count)| std::cmp::Reverse(count));
 
         // Take only the top N candidates to score (significantly reduces work and speed up search)
         let candidates_to_score = candidates
@@ -572,7 +570,7 @@ impl PathMatcher {
         let variations = self.generate_efficient_variations(&query_lower);
 
         // === Step 1: Fast Variation-based Fallback ===
-        let mut path_bitmap = vec![0u32; (self.paths.len() + 31) / 32];
+        let mut path_bitmap = vec![0u32; self.paths.len().div_ceil(32)];
         let mut variation_hits =
             FxHashMap::with_capacity_and_hasher(variations.len(), Default::default());
         let mut seen_paths =
@@ -584,9 +582,7 @@ impl PathMatcher {
             if trigrams.is_empty() {
                 continue;
             }
-            for word in &mut path_bitmap {
-                *word = 0;
-            }
+            path_bitmap.fill(0);
             for &trigram in &trigrams {
                 if let Some(path_indices) = self.trigram_index.get(&trigram) {
                     for &path_idx in path_indices {
@@ -601,15 +597,14 @@ impl PathMatcher {
                     }
                 }
             }
-            for word_idx in 0..path_bitmap.len() {
-                let mut word = path_bitmap[word_idx];
+            for (word_idx, mut word) in path_bitmap.iter().copied().enumerate() {
                 while word != 0 {
                     let bit_pos = word.trailing_zeros() as usize;
                     let path_idx = word_idx * 32 + bit_pos;
                     if path_idx < self.paths.len() && !seen_paths.contains(&path_idx) {
                         seen_paths.insert(path_idx);
                         let path = &self.paths[path_idx];
-                        let filename = path.split('/').last().unwrap_or(path);
+                        let filename = path.split('/').next_back().unwrap_or(path);
                         let filename_lower = filename.to_lowercase();
                         let variation_index = variation_idx as f32 / variations.len() as f32;
                         let mut score = 0.9 - (variation_index * 0.2);
@@ -668,9 +663,9 @@ impl PathMatcher {
         if len > 1 {
             for i in 0..len {
                 let mut new_query = String::with_capacity(len - 1);
-                for j in 0..len {
+                for (j, &c) in chars.iter().enumerate() {
                     if j != i {
-                        new_query.push(chars[j]);
+                        new_query.push(c);
                     }
                 }
                 variations.push(new_query);
@@ -771,7 +766,7 @@ mod tests_fast_fuzzy_v2 {
                 }
             }
 
-            if let Some(walker) = std::fs::read_dir(dir).ok() {
+            if let Ok(walker) = std::fs::read_dir(dir) {
                 for entry in walker.filter_map(|e| e.ok()) {
                     let path = entry.path();
                     if let Some(path_str) = path.to_str() {
@@ -1321,7 +1316,7 @@ mod tests_fast_fuzzy_v2 {
         let realistic_terms: Vec<String> = if !test_paths.is_empty() {
             let mut terms = Vec::new();
             for path in test_paths.iter().take(5) {
-                if let Some(filename) = path.split('/').last().or_else(|| path.split('\\').last()) {
+                if let Some(filename) = path.split('/').next_back().or_else(|| path.split('\\').next_back()) {
                     if filename.len() >= 3 {
                         terms.push(filename[0..3].to_string());
                     }
@@ -1578,7 +1573,7 @@ mod tests_fast_fuzzy_v2 {
         let mut all_paths = Vec::new();
 
         // Walk the directory and collect paths
-        if let Some(walker) = std::fs::read_dir(&test_path).ok() {
+        if let Ok(walker) = std::fs::read_dir(&test_path) {
             for entry in walker.filter_map(|e| e.ok()) {
                 if let Some(path_str) = entry.path().to_str().map(|s| s.to_string()) {
                     matcher.add_path(&path_str);
@@ -7,7 +7,7 @@ pub mod search_core;
 #[cfg(test)]
 pub mod test_generate_test_data {
     use crate::log_info;
-    use std::path::PathBuf;
+    use std::path::{Path, PathBuf};
     use std::sync::{Arc, Mutex};
 
     /// Generates a test data directory structure with random folder and file names.
@@ -69,7 +69,7 @@ pub mod test_generate_test_data {
             let mut rng = thread_rng();
 
             let idx = rng.gen_range(0, charset.len());
-            return charset[idx].to_string();
+            charset[idx].to_string()
         };
 
         // Function to create file extensions
@@ -88,8 +88,9 @@ pub mod test_generate_test_data {
         let entry_count = Arc::new(Mutex::new(0usize));
 
         // Recursive function to create the folder structure
+        #[allow(clippy::too_many_arguments)]
         fn create_structure(
-            path: &PathBuf,
+            path: &Path,
             depth: usize,
             max_depth: usize,
             folders_per_level: usize,
@@ -24,6 +24,7 @@ pub struct PathData {
 impl PathCache {
     #[cfg(test)]
     #[inline]
+    #[allow(clippy::arc_with_non_send_sync)]
     pub fn new(capacity: usize) -> Self {
         Self {
             inner: Arc::new(RwLock::new(LruPathCache::new(capacity))),
@@ -31,6 +32,7 @@ impl PathCache {
     }
 
     #[inline]
+    #[allow(clippy::arc_with_non_send_sync)]
     pub fn with_ttl(capacity: usize, ttl: Duration) -> Self {
         Self {
             inner: Arc::new(RwLock::new(LruPathCache::with_ttl(capacity, ttl))),
@@ -321,7 +321,7 @@ impl SearchCore {
         
         // Check if path should be excluded
         if let Some(patterns) = excluded_patterns {
-            if self.should_exclude_path(path, &patterns) {
+            if self.should_exclude_path(path, patterns) {
                 #[cfg(feature = "index-progress-logging")]
                 log_info!("Path excluded by pattern: '{}'", path);
                 
@@ -612,6 +612,7 @@ impl SearchCore {
     ///
     /// # Performance
     /// O(n) where n is the number of files and directories under the path
+    #[cfg_attr(not(feature = "index-progress-logging"), allow(unused_assignments))]
     pub fn remove_paths_recursive(&mut self, path: &str) {
         #[cfg(feature = "index-progress-logging")]
         let start_time = Instant::now();
@@ -792,6 +793,7 @@ impl SearchCore {
     /// - Typical latency: ~1ms for datasets of up to 170,000 paths
     /// - Cache provides 3×-7× speedup for repeated queries
     #[inline]
+    #[cfg_attr(not(feature = "search-progress-logging"), allow(unused_assignments))]
     pub fn search(&mut self, query: &str) -> Vec<(String, f32)> {
         #[cfg(feature = "search-progress-logging")]
         let search_start = Instant::now();
@@ -842,7 +844,7 @@ impl SearchCore {
         // 3. ART prefix search
         //let current_dir_ref = self.current_directory.as_deref();
         let prefix_results = self.trie.search(
-            &normalized_query,
+            normalized_query,
             None, // should add current_dif_ref, but rn not very performant
             false,
         );
@@ -873,7 +875,7 @@ impl SearchCore {
 
             let fuzzy_results = self
                 .fuzzy_matcher
-                .search(&normalized_query, self.max_results - self.results_buffer.len());
+                .search(normalized_query, self.max_results - self.results_buffer.len());
             
             #[cfg(feature = "search-progress-logging")]
             {
@@ -920,7 +922,7 @@ impl SearchCore {
         
         // Clone buffer temporarily to avoid borrowing conflicts
         let mut temp_results = self.results_buffer.clone();
-        self.rank_results(&mut temp_results, &normalized_query);
+        self.rank_results(&mut temp_results, normalized_query);
         self.results_buffer = temp_results;
         
         #[cfg(feature = "search-progress-logging")]
@@ -997,7 +999,7 @@ impl SearchCore {
     ///
     /// # Performance
     /// O(k log k) where k is the number of results to rank
-    fn rank_results(&self, results: &mut Vec<(String, f32)>, query: &str) {
+    fn rank_results(&self, results: &mut [(String, f32)], query: &str) {
         #[cfg(feature = "search-progress-logging")]
         let ranking_detailed_start = Instant::now();
         
@@ -1579,7 +1581,7 @@ mod tests_search_core {
         let root_path = PathBuf::from(root);
         assert!(root_path.exists(), "Test data directory should exist");
 
-        engine.add_paths_recursive(&root, None).await;
+        engine.add_paths_recursive(root, None).await;
 
         let results = engine.search("train");
         assert!(!results.is_empty(), "Should find train files");
@@ -1798,7 +1800,7 @@ mod tests_search_core {
                 }
             }
 
-            if let Some(walker) = fs::read_dir(dir).ok() {
+            if let Ok(walker) = fs::read_dir(dir) {
                 for entry in walker.filter_map(|e| e.ok()) {
                     let path = entry.path();
                     if let Some(path_str) = path.to_str() {
@@ -1896,7 +1898,7 @@ mod tests_search_core {
         // Extract some filename terms to search for from the data
         let mut filename_terms = Vec::new();
         for path in paths.iter().take(20) {
-            if let Some(filename) = path.split('/').last().or_else(|| path.split('\\').last()) {
+            if let Some(filename) = path.split('/').next_back().or_else(|| path.split('\\').next_back()) {
                 if filename.len() >= 3 {
                     filename_terms.push(filename[..3].to_string());
                 }
@@ -1978,13 +1980,13 @@ mod tests_search_core {
         // 4. Test with usage frequency and recency tracking
         if !paths.is_empty() {
             // Record usage for some paths to affect ranking
-            for i in 0..paths.len().min(5) {
-                engine.record_path_usage(&paths[i]);
+            for (i, path) in paths.iter().take(5).enumerate() {
+                engine.record_path_usage(path);
 
                 // Record multiple usages for the first path
                 if i == 0 {
-                    engine.record_path_usage(&paths[i]);
-                    engine.record_path_usage(&paths[i]);
+                    engine.record_path_usage(path);
+                    engine.record_path_usage(path);
                 }
             }
 
@@ -2046,7 +2048,7 @@ mod tests_search_core {
         // 6. Test cache behavior by repeating a search
         if !paths.is_empty() {
             let repeat_term = if let Some(path) = paths.first() {
-                if let Some(filename) = path.split('/').last().or_else(|| path.split('\\').last()) {
+                if let Some(filename) = path.split('/').next_back().or_else(|| path.split('\\').next_back()) {
                     if filename.len() >= 3 {
                         &filename[..3]
                     } else {
@@ -0,0 +1,219 @@
+use crate::archive::ArchiveEntry;
+use crate::error_handling::{Error, ErrorCode};
+use crate::log_error;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tempfile::TempDir;
+
+/// How many archive listings are kept, so opening folders inside an archive does not read
+/// the whole archive every time.
+const MAX_CACHED_ARCHIVES: usize = 8;
+
+struct CachedListing {
+    len: u64,
+    modified: Option<SystemTime>,
+    entries: Vec<ArchiveEntry>,
+    /// The value of the use counter of the state when the listing was last read or stored.
+    last_used: u64,
+}
+
+/// Listings of the archives browsed as folders, keyed by archive path and only valid for the
+/// same size and mtime.
+///
+/// Media files of an archive are extracted below a temporary directory of this process to be
+/// played from a path. They are removed together with the listing of their archive and when
+/// the app exits.
+pub struct ArchiveListingState {
+    listings: HashMap<PathBuf, CachedListing>,
+    /// `None` if the directory could not be created or the state was closed.
+    preview_root: Option<TempDir>,
+    uses: u64,
+}
+
+impl ArchiveListingState {
+    pub fn new() -> Self {
+        let preview_root = tempfile::Builder::new()
+            .prefix("explr-archive-previews-")
+            .tempdir()
+            .map_err(|e| log_error!("Failed to create the archive preview directory: {}", e))
+            .ok();
+        Self {
+            listings: HashMap::new(),
+            preview_root,
+            uses: 0,
+        }
+    }
+
+    /// Returns the entries of an archive if they were stored for the given size and mtime.
+    pub fn get(
+        &mut self,
+        archive: &Path,
+        len: u64,
+        modified: Option<SystemTime>,
+    ) -> Option<Vec<ArchiveEntry>> {
+        self.uses += 1;
+        let uses = self.uses;
+        self.listings
+            .get_mut(archive)
+            .filter(|listing| listing.len == len && listing.modified == modified)
+            .map(|listing| {
+                listing.last_used = uses;
+                listing.entries.clone()
+            })
+    }
+
+    /// Stores the entries of an archive. If the cache is full, the least recently used listing
+    /// is evicted. Replaced and evicted listings take their extracted previews with them,
+    /// previews of a changed archive would be outdated anyway.
+    pub fn insert(
+        &mut self,
+        archive: PathBuf,
+        len: u64,
+        modified: Option<SystemTime>,
+        entries: Vec<ArchiveEntry>,
+    ) {
+        if self.listings.len() >= MAX_CACHED_ARCHIVES && !self.listings.contains_key(&archive) {
+            let least_recently_used = self
+                .listings
+                .iter()
+                .min_by_key(|(_, listing)| listing.last_used)
+                .map(|(path, _)| path.clone());
+            if let Some(evicted) = least_recently_used {
+                self.listings.remove(&evicted);
+                self.remove_previews(&evicted);
+            }
+        }
+        self.remove_previews(&archive);
+        self.uses += 1;
+        self.listings.insert(
+            archive,
+            CachedListing {
+                len,
+                modified,
+                entries,
+                last_used: self.uses,
+            },
+        );
+    }
+
+    /// The directory the previews of an archive are extracted to.
+    ///
+    /// # Returns
+    /// * `Ok(PathBuf)` - A directory below the preview directory of this process.
+    /// * `Err(String)` - If the preview directory could not be created or was removed on exit.
+    pub fn preview_directory(&self, archive: &Path) -> Result<PathBuf, String> {
+        let preview_root = self.preview_root.as_ref().ok_or_else(|| {
+            Error::new(
+                ErrorCode::InternalError,
+                "There is no directory for archive previews".to_string(),
+            )
+            .to_json()
+        })?;
+        let mut hasher = DefaultHasher::new();
+        archive.hash(&mut hasher);
+        Ok(preview_root.path().join(format!("{:016x}", hasher.finish())))
+    }
+
+    /// Removes all listings and the preview directory of this process. Called when the app
+    /// exits, because managed state is not dropped then.
+    pub fn close(&mut self) {
+        self.listings.clear();
+        if let Some(preview_root) = self.preview_root.take() {
+            let _ = preview_root.close();
+        }
+    }
+
+    fn remove_previews(&self, archive: &Path) {
+        if let Ok(directory) = self.preview_directory(archive) {
+            let _ = fs::remove_dir_all(directory);
+        }
+    }
+}
+
+impl Default for ArchiveListingState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests_archive_listing_data {
+    use super::*;
+    use crate::archive::ArchiveEntryKind;
+
+    fn entries() -> Vec<ArchiveEntry> {
+        vec![ArchiveEntry {
+            name: "a.txt".to_string(),
+            kind: ArchiveEntryKind::File,
+            size: 1,
+            compressed_size: None,
+            modified: None,
+            mode: None,
+            link_target: None,
+        }]
+    }
+
+    fn create_preview(state: &ArchiveListingState, archive: &str) -> PathBuf {
+        let preview = state.preview_directory(Path::new(archive)).unwrap();
+        fs::create_dir_all(&preview).unwrap();
+        fs::write(preview.join("video.mp4"), "video").unwrap();
+        preview
+    }
+
+    #[test]
+    fn changed_archives_remove_their_previews_test() {
+        let mut state = ArchiveListingState::new();
+
+        state.insert(PathBuf::from("/tmp/0.zip"), 1, None, entries());
+        assert!(state.get(Path::new("/tmp/0.zip"), 1, None).is_some());
+        assert!(state.get(Path::new("/tmp/0.zip"), 2, None).is_none());
+
+        // The archive changed, its previews are outdated
+        let preview = create_preview(&state, "/tmp/0.zip");
+        state.insert(PathBuf::from("/tmp/0.zip"), 2, None, entries());
+        assert!(!preview.exists());
+    }
+
+    #[test]
+    fn least_recently_used_listing_is_evicted_test() {
+        let mut state = ArchiveListingState::new();
+        for i in 0..MAX_CACHED_ARCHIVES {
+            state.insert(PathBuf::from(format!("/tmp/{}.zip", i)), 1, None, entries());
+        }
+        let first_preview = create_preview(&state, "/tmp/0.zip");
+        let second_preview = create_preview(&state, "/tmp/1.zip");
+        assert!(state.get(Path::new("/tmp/0.zip"), 1, None).is_some());
+
+        state.insert(PathBuf::from("/tmp/new.zip"), 1, None, entries());
+        assert!(state.get(Path::new("/tmp/0.zip"), 1, None).is_some());
+        assert!(first_preview.exists());
+        assert!(state.get(Path::new("/tmp/1.zip"), 1, None).is_none());
+        assert!(!second_preview.exists());
+        for i in 2..MAX_CACHED_ARCHIVES {
+            let archive = format!("/tmp/{}.zip", i);
+            assert!(state.get(Path::new(&archive), 1, None).is_some());
+        }
+    }
+
+    #[test]
+    fn every_state_has_its_own_preview_directory_test() {
+        let mut first = ArchiveListingState::new();
+        let second = ArchiveListingState::new();
+        let first_preview = create_preview(&first, "/tmp/0.zip");
+        let second_preview = create_preview(&second, "/tmp/0.zip");
+        assert_ne!(first_preview, second_preview);
+
+        first.close();
+        assert!(!first_preview.exists());
+        assert!(second_preview.exists());
+        assert!(first.preview_directory(Path::new("/tmp/0.zip")).is_err());
+
+        let second_root = second_preview.parent().unwrap().to_path_buf();
+        drop(second);
+        assert!(!second_root.exists());
+    }
+}
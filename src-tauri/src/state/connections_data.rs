@@ -0,0 +1,533 @@
+use crate::constants;
+use crate::error_handling::{Error, ErrorCode};
+use crate::log_error;
+use crate::models::remote_connection::{RemoteAuthMethod, RemoteConnection, RemoteConnectionInput};
+use crate::remote::{RemoteProtocol, RemoteSessionConfig};
+use crate::state::secret_store_data::{SecretStore, SecretStoreError, SecretStoreStatus};
+use crate::state::sftp_session_data::{SftpConnectionConfig, SftpCredentials};
+use serde_json::json;
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConnectionsError {
+    NotFound(String),
+    InvalidInput(String),
+    AlreadyExists(String),
+    Secrets(SecretStoreError),
+    Io(String),
+}
+
+impl ConnectionsError {
+    pub fn to_error(&self) -> Error {
+        match self {
+            ConnectionsError::NotFound(id) => Error::new(
+                ErrorCode::ResourceNotFound,
+                format!("Connection {} does not exist", id),
+            ),
+            ConnectionsError::InvalidInput(message) => {
+                Error::new(ErrorCode::InvalidInput, message.clone())
+            }
+            ConnectionsError::AlreadyExists(name) => Error::new(
+                ErrorCode::ResourceAlreadyExists,
+                format!("A connection named '{}' already exists", name),
+            ),
+            // The frontend asks for the master passphrase on these
+            ConnectionsError::Secrets(SecretStoreError::Locked) => Error::new(
+                ErrorCode::Unauthorized,
+                SecretStoreError::Locked.to_string(),
+            )
+            .with_details(json!({ "reason": "SecretStoreLocked" })),
+            ConnectionsError::Secrets(SecretStoreError::WrongPassphrase) => Error::new(
+                ErrorCode::Unauthorized,
+                SecretStoreError::WrongPassphrase.to_string(),
+            )
+            .with_details(json!({ "reason": "WrongPassphrase" })),
+            ConnectionsError::Secrets(e) => Error::new(ErrorCode::InternalError, e.to_string()),
+            ConnectionsError::Io(message) => Error::new(ErrorCode::InternalError, message.clone()),
+        }
+    }
+}
+
+impl From<SecretStoreError> for ConnectionsError {
+    fn from(error: SecretStoreError) -> Self {
+        ConnectionsError::Secrets(error)
+    }
+}
+
+/// The saved remote connections, persisted in `connections.json` of the config directory. The
+/// passwords and key passphrases are kept in the encrypted [`SecretStore`], never in the config
+/// files, so connections can be listed and edited while the store is locked.
+pub struct ConnectionsState {
+    path: PathBuf,
+    connections: Vec<RemoteConnection>,
+    secrets: SecretStore,
+}
+
+impl ConnectionsState {
+    pub fn new() -> Self {
+        Self::new_with_paths(
+            constants::CONNECTIONS_CONFIG_ABS_PATH.to_path_buf(),
+            constants::SECRET_STORE_ABS_PATH.to_path_buf(),
+        )
+    }
+
+    /// Loads the connections of a file, a missing file has no connections.
+    pub fn new_with_paths(path: PathBuf, secrets_path: PathBuf) -> Self {
+        let connections = match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                log_error!("Failed to parse {}: {}", path.display(), e);
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+        Self {
+            path,
+            connections,
+            secrets: SecretStore::new(secrets_path),
+        }
+    }
+
+    pub fn list(&self) -> Vec<RemoteConnection> {
+        self.connections.clone()
+    }
+
+    pub fn get(&self, id: &str) -> Result<RemoteConnection, ConnectionsError> {
+        self.connections
+            .iter()
+            .find(|connection| connection.id == id)
+            .cloned()
+            .ok_or_else(|| ConnectionsError::NotFound(id.to_string()))
+    }
+
+    /// Saves a new connection. The secret needs an unlocked store, it is ignored for methods
+    /// without one.
+    pub fn add(
+        &mut self,
+        input: RemoteConnectionInput,
+        secret: Option<String>,
+    ) -> Result<RemoteConnection, ConnectionsError> {
+        self.validate(&input, None)?;
+        let id = format!("{:016x}", rand::random::<u64>());
+        let mut connection = RemoteConnection::from_input(id, input);
+        self.store_secret(&mut connection, secret)?;
+        self.connections.push(connection.clone());
+        self.save()?;
+        Ok(connection)
+    }
+
+    /// Replaces the fields of a connection. `None` keeps the stored secret, an empty secret
+    /// removes it. Switching to a method without secret removes it as well.
+    pub fn update(
+        &mut self,
+        id: &str,
+        input: RemoteConnectionInput,
+        secret: Option<String>,
+    ) -> Result<RemoteConnection, ConnectionsError> {
+        let existing = self.get(id)?;
+        self.validate(&input, Some(id))?;
+        let mut connection = RemoteConnection::from_input(id.to_string(), input);
+        connection.has_secret = existing.has_secret && connection.auth_method.uses_secret();
+        self.store_secret(&mut connection, secret)?;
+        if existing.has_secret && !connection.has_secret && self.secrets.is_unlocked() {
+            self.secrets.set(id, None)?;
+        }
+
+        if let Some(slot) = self.connections.iter_mut().find(|c| c.id == id) {
+            *slot = connection.clone();
+        }
+        self.save()?;
+        Ok(connection)
+    }
+
+    /// Removes a connection and its secret. While the store is locked the secret is removed the
+    /// next time it is unlocked.
+    pub fn remove(&mut self, id: &str) -> Result<(), ConnectionsError> {
+        let existing = self.get(id)?;
+        if existing.has_secret && self.secrets.is_unlocked() {
+            self.secrets.set(id, None)?;
+        }
+        self.connections.retain(|connection| connection.id != id);
+        self.save()
+    }
+
+    fn secret_of(&self, connection: &RemoteConnection) -> Result<Option<String>, ConnectionsError> {
+        if connection.has_secret {
+            Ok(self.secrets.get(&connection.id)?)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Everything needed to open an SFTP session to a saved connection, including its secret.
+    pub fn connection_config(&self, id: &str) -> Result<SftpConnectionConfig, ConnectionsError> {
+        let connection = self.get(id)?;
+        if connection.protocol != RemoteProtocol::Sftp {
+            return Err(ConnectionsError::InvalidInput(format!(
+                "Connection {} is not an SFTP connection",
+                connection.name
+            )));
+        }
+        let secret = self.secret_of(&connection)?;
+        let credentials = match connection.auth_method {
+            RemoteAuthMethod::Password | RemoteAuthMethod::AccessKey => SftpCredentials::Password {
+                password: secret.unwrap_or_default(),
+            },
+            RemoteAuthMethod::PublicKey => SftpCredentials::PublicKey {
+                private_key_path: connection.private_key_path.unwrap_or_default(),
+                passphrase: secret,
+            },
+            RemoteAuthMethod::Agent => SftpCredentials::Agent,
+            RemoteAuthMethod::KeyboardInteractive => SftpCredentials::KeyboardInteractive,
+        };
+        Ok(SftpConnectionConfig {
+            host: connection.host,
+            port: connection.port,
+            username: connection.username,
+            credentials,
+            proxy_jump: connection.proxy_jump,
+        })
+    }
+
+    /// Everything needed to open an FTP, WebDAV or S3 session to a saved connection, including
+    /// its password or secret access key.
+    pub fn remote_session_config(&self, id: &str) -> Result<RemoteSessionConfig, ConnectionsError> {
+        let connection = self.get(id)?;
+        if connection.protocol == RemoteProtocol::Sftp {
+            return Err(ConnectionsError::InvalidInput(format!(
+                "Connection {} is an SFTP connection",
+                connection.name
+            )));
+        }
+        let password = self.secret_of(&connection)?.unwrap_or_default();
+        Ok(RemoteSessionConfig {
+            protocol: connection.protocol,
+            host: connection.host,
+            port: Some(connection.port),
+            username: connection.username,
+            password,
+            base_path: connection.base_path,
+            region: connection.region,
+            accept_invalid_certificates: connection.accept_invalid_certificates,
+        })
+    }
+
+    pub fn secret_store_status(&self) -> SecretStoreStatus {
+        self.secrets.status()
+    }
+
+    /// Unlocks the secret store, or creates it with this passphrase. Secrets of connections
+    /// removed while it was locked are dropped.
+    pub fn unlock_secrets(&mut self, passphrase: &str) -> Result<(), ConnectionsError> {
+        self.secrets.unlock(passphrase)?;
+        let connections = &self.connections;
+        self.secrets.retain(|id| {
+            connections
+                .iter()
+                .any(|connection| connection.id == id && connection.has_secret)
+        })?;
+        Ok(())
+    }
+
+    pub fn lock_secrets(&mut self) {
+        self.secrets.lock();
+    }
+
+    pub fn change_secrets_passphrase(&mut self, passphrase: &str) -> Result<(), ConnectionsError> {
+        Ok(self.secrets.change_passphrase(passphrase)?)
+    }
+
+    fn validate(
+        &self,
+        input: &RemoteConnectionInput,
+        id: Option<&str>,
+    ) -> Result<(), ConnectionsError> {
+        let invalid = |message: &str| Err(ConnectionsError::InvalidInput(message.to_string()));
+        if input.name.trim().is_empty() {
+            return invalid("The name of the connection is empty");
+        }
+        if input.host.trim().is_empty() {
+            return invalid("The host of the connection is empty");
+        }
+        if input.port == 0 {
+            return invalid("The port of the connection must be between 1 and 65535");
+        }
+        if input.auth_method == RemoteAuthMethod::PublicKey && input.private_key_path.is_none() {
+            return invalid("Key authentication needs the path of the private key");
+        }
+        if !input.auth_method.supports(input.protocol) {
+            return Err(ConnectionsError::InvalidInput(format!(
+                "{:?} connections can't log in with {:?}",
+                input.protocol, input.auth_method
+            )));
+        }
+        if input.protocol != RemoteProtocol::Sftp && !input.proxy_jump.is_empty() {
+            return invalid("Only SFTP connections can use jump hosts");
+        }
+        // Sessions are cached by connection name in the frontend
+        let taken = self
+            .connections
+            .iter()
+            .any(|connection| connection.name == input.name && Some(connection.id.as_str()) != id);
+        if taken {
+            return Err(ConnectionsError::AlreadyExists(input.name.clone()));
+        }
+        Ok(())
+    }
+
+    fn store_secret(
+        &mut self,
+        connection: &mut RemoteConnection,
+        secret: Option<String>,
+    ) -> Result<(), ConnectionsError> {
+        let Some(secret) = secret.filter(|_| connection.auth_method.uses_secret()) else {
+            return Ok(());
+        };
+        if secret.is_empty() {
+            if connection.has_secret && self.secrets.is_unlocked() {
+                self.secrets.set(&connection.id, None)?;
+            }
+            connection.has_secret = false;
+        } else {
+            self.secrets.set(&connection.id, Some(secret))?;
+            connection.has_secret = true;
+        }
+        Ok(())
+    }
+
+    fn save(&self) -> Result<(), ConnectionsError> {
+        let io_error = |e: std::io::Error| ConnectionsError::Io(e.to_string());
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).map_err(io_error)?;
+        }
+        let serialized = serde_json::to_string_pretty(&self.connections)
+            .map_err(|e| ConnectionsError::Io(e.to_string()))?;
+        fs::write(&self.path, serialized).map_err(io_error)
+    }
+}
+
+impl Default for ConnectionsState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests_connections_data {
+    use super::*;
+    use tempfile::{tempdir, TempDir};
+
+    fn state() -> (TempDir, ConnectionsState) {
+        let dir = tempdir().unwrap();
+        let state = ConnectionsState::new_with_paths(
+            dir.path().join("connections.json"),
+            dir.path().join("secrets.enc.json"),
+        );
+        (dir, state)
+    }
+
+    fn input(name: &str, auth_method: RemoteAuthMethod) -> RemoteConnectionInput {
+        RemoteConnectionInput {
+            name: name.to_string(),
+            protocol: RemoteProtocol::Sftp,
+            host: "example.com".to_string(),
+            port: 22,
+            username: "alice".to_string(),
+            auth_method,
+            private_key_path: None,
+            default_path: Some("/srv".to_string()),
+            proxy_jump: Vec::new(),
+            base_path: None,
+            region: None,
+            accept_invalid_certificates: false,
+        }
+    }
+
+    #[test]
+    fn secrets_never_reach_the_config_file_test() {
+        let (dir, mut state) = state();
+        state.unlock_secrets("master").unwrap();
+        let connection = state
+            .add(
+                input("prod", RemoteAuthMethod::Password),
+                Some("hunter2".to_string()),
+            )
+            .unwrap();
+        assert!(connection.has_secret);
+
+        let content = fs::read_to_string(dir.path().join("connections.json")).unwrap();
+        assert!(content.contains("prod"));
+        assert!(!content.contains("hunter2"));
+
+        let config = state.connection_config(&connection.id).unwrap();
+        assert!(matches!(
+            config.credentials,
+            SftpCredentials::Password { ref password } if password == "hunter2"
+        ));
+
+        // A new state reads the connections back, the secret needs the passphrase again
+        let mut reloaded = ConnectionsState::new_with_paths(
+            dir.path().join("connections.json"),
+            dir.path().join("secrets.enc.json"),
+        );
+        assert_eq!(reloaded.list(), vec![connection.clone()]);
+        assert_eq!(
+            reloaded.connection_config(&connection.id).unwrap_err(),
+            ConnectionsError::Secrets(SecretStoreError::Locked)
+        );
+        reloaded.unlock_secrets("master").unwrap();
+        assert!(reloaded.connection_config(&connection.id).is_ok());
+    }
+
+    #[test]
+    fn locked_store_rejects_secrets_test() {
+        let (_dir, mut state) = state();
+        let error = state
+            .add(
+                input("prod", RemoteAuthMethod::Password),
+                Some("hunter2".to_string()),
+            )
+            .unwrap_err();
+        assert_eq!(error, ConnectionsError::Secrets(SecretStoreError::Locked));
+        let error: serde_json::Value = serde_json::from_str(&error.to_error().to_json()).unwrap();
+        assert_eq!(error["details"]["reason"], "SecretStoreLocked");
+        assert!(state.list().is_empty());
+
+        // Methods without secret don't need the store
+        let agent = state
+            .add(
+                input("agent", RemoteAuthMethod::Agent),
+                Some("ignored".to_string()),
+            )
+            .unwrap();
+        assert!(!agent.has_secret);
+        assert!(matches!(
+            state.connection_config(&agent.id).unwrap().credentials,
+            SftpCredentials::Agent
+        ));
+    }
+
+    #[test]
+    fn update_and_remove_test() {
+        let (_dir, mut state) = state();
+        state.unlock_secrets("master").unwrap();
+        let connection = state
+            .add(
+                input("prod", RemoteAuthMethod::Password),
+                Some("hunter2".to_string()),
+            )
+            .unwrap();
+
+        // None keeps the secret
+        let mut changed = input("production", RemoteAuthMethod::Password);
+        changed.port = 2222;
+        let updated = state.update(&connection.id, changed, None).unwrap();
+        assert_eq!(updated.port, 2222);
+        assert!(updated.has_secret);
+
+        // Switching to the agent drops the password
+        let updated = state
+            .update(
+                &connection.id,
+                input("production", RemoteAuthMethod::Agent),
+                None,
+            )
+            .unwrap();
+        assert!(!updated.has_secret);
+        assert_eq!(state.secrets.get(&connection.id).unwrap(), None);
+
+        state.remove(&connection.id).unwrap();
+        assert!(state.list().is_empty());
+        assert!(matches!(
+            state.remove(&connection.id),
+            Err(ConnectionsError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn secrets_of_connections_removed_while_locked_are_dropped_test() {
+        let (_dir, mut state) = state();
+        state.unlock_secrets("master").unwrap();
+        let connection = state
+            .add(
+                input("prod", RemoteAuthMethod::Password),
+                Some("hunter2".to_string()),
+            )
+            .unwrap();
+        state.lock_secrets();
+        state.remove(&connection.id).unwrap();
+        state.unlock_secrets("master").unwrap();
+        assert_eq!(state.secrets.get(&connection.id).unwrap(), None);
+    }
+
+    #[test]
+    fn validation_test() {
+        let (_dir, mut state) = state();
+        state
+            .add(input("prod", RemoteAuthMethod::Agent), None)
+            .unwrap();
+        assert!(matches!(
+            state.add(input("prod", RemoteAuthMethod::Agent), None),
+            Err(ConnectionsError::AlreadyExists(_))
+        ));
+        assert!(matches!(
+            state.add(input(" ", RemoteAuthMethod::Agent), None),
+            Err(ConnectionsError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            state.add(input("key", RemoteAuthMethod::PublicKey), None),
+            Err(ConnectionsError::InvalidInput(_))
+        ));
+
+        // S3 logs in with an access key only, and only SFTP with the agent
+        let mut s3 = input("s3", RemoteAuthMethod::Password);
+        s3.protocol = RemoteProtocol::S3;
+        assert!(matches!(
+            state.add(s3, None),
+            Err(ConnectionsError::InvalidInput(_))
+        ));
+        let mut ftp = input("ftp", RemoteAuthMethod::Agent);
+        ftp.protocol = RemoteProtocol::Ftp;
+        assert!(matches!(
+            state.add(ftp, None),
+            Err(ConnectionsError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn s3_connection_keeps_its_secret_access_key_test() {
+        let (dir, mut state) = state();
+        state.unlock_secrets("master").unwrap();
+        let mut s3 = input("minio", RemoteAuthMethod::AccessKey);
+        s3.protocol = RemoteProtocol::S3Http;
+        s3.port = 9000;
+        s3.username = "AKIAEXAMPLE".to_string();
+        s3.region = Some("eu-central-1".to_string());
+        s3.accept_invalid_certificates = true;
+        let connection = state.add(s3, Some("wJalrXUtnFEMI".to_string())).unwrap();
+        assert!(connection.has_secret);
+
+        let content = fs::read_to_string(dir.path().join("connections.json")).unwrap();
+        assert!(!content.contains("wJalrXUtnFEMI"));
+
+        let config = state.remote_session_config(&connection.id).unwrap();
+        assert_eq!(config.protocol, RemoteProtocol::S3Http);
+        assert_eq!(config.port(), 9000);
+        assert_eq!(config.username, "AKIAEXAMPLE");
+        assert_eq!(config.password, "wJalrXUtnFEMI");
+        assert_eq!(config.region.as_deref(), Some("eu-central-1"));
+        assert!(config.accept_invalid_certificates);
+        assert!(matches!(
+            state.connection_config(&connection.id),
+            Err(ConnectionsError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn connections_without_protocol_are_sftp_connections_test() {
+        let saved = r#"[{"id":"1","name":"old","host":"example.com","port":22,"username":"alice","auth_method":"Agent"}]"#;
+        let connections: Vec<RemoteConnection> = serde_json::from_str(saved).unwrap();
+        assert_eq!(connections[0].protocol, RemoteProtocol::Sftp);
+        assert!(!connections[0].accept_invalid_certificates);
+    }
+}
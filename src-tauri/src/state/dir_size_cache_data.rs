@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Stop caching once this many directories are stored, the cache is cleared and refilled.
+const MAX_CACHED_DIRECTORIES: usize = 500_000;
+
+/// A file with more than one hard link, identified by device and inode.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HardLinkedFile {
+    pub device: u64,
+    pub inode: u64,
+    pub apparent_size: u64,
+    pub disk_usage: u64,
+}
+
+/// Sizes of the direct children of a single directory.
+///
+/// Only the files directly inside the directory are summed up, subdirectories are listed
+/// so the recursive total can be put together from the cached entries of each level.
+/// Files with several hard links are not part of the sums, they are listed in `hard_links`
+/// so a scan can count each of them once no matter how many links it finds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedDirectory {
+    pub modified: SystemTime,
+    pub apparent_size: u64,
+    pub disk_usage: u64,
+    pub file_count: u64,
+    pub subdirectories: Vec<PathBuf>,
+    pub hard_links: Vec<HardLinkedFile>,
+}
+
+/// Cache for directory size calculations, keyed by path and validated by the directory mtime.
+///
+/// The mtime of a directory changes when entries are added, removed or renamed, so a cached
+/// entry stays valid as long as the mtime matches. Files which change their size in place do
+/// not touch the directory mtime, for that case the cache can be cleared.
+pub struct DirSizeCacheState {
+    directories: HashMap<PathBuf, CachedDirectory>,
+}
+
+impl DirSizeCacheState {
+    pub fn new() -> Self {
+        Self {
+            directories: HashMap::new(),
+        }
+    }
+
+    /// Returns the cached entry if it was stored for the given mtime.
+    pub fn get(&self, path: &Path, modified: SystemTime) -> Option<CachedDirectory> {
+        self.directories
+            .get(path)
+            .filter(|entry| entry.modified == modified)
+            .cloned()
+    }
+
+    pub fn insert(&mut self, path: PathBuf, entry: CachedDirectory) {
+        if self.directories.len() >= MAX_CACHED_DIRECTORIES {
+            self.directories.clear();
+        }
+        self.directories.insert(path, entry);
+    }
+
+    /// Removes all cached entries and returns how many there were.
+    pub fn clear(&mut self) -> usize {
+        let count = self.directories.len();
+        self.directories.clear();
+        count
+    }
+
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        self.directories.is_empty()
+    }
+}
+
+impl Default for DirSizeCacheState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests_dir_size_cache_data {
+    use super::*;
+    use std::time::Duration;
+
+    fn entry(modified: SystemTime) -> CachedDirectory {
+        CachedDirectory {
+            modified,
+            apparent_size: 10,
+            disk_usage: 4096,
+            file_count: 1,
+            subdirectories: vec![],
+            hard_links: vec![],
+        }
+    }
+
+    #[test]
+    fn cache_entry_is_invalidated_by_mtime_test() {
+        let mut cache = DirSizeCacheState::new();
+        let now = SystemTime::now();
+        cache.insert(PathBuf::from("/tmp/a"), entry(now));
+
+        assert!(cache.get(Path::new("/tmp/a"), now).is_some());
+        assert!(cache
+            .get(Path::new("/tmp/a"), now + Duration::from_secs(1))
+            .is_none());
+        assert!(cache.get(Path::new("/tmp/b"), now).is_none());
+
+        assert_eq!(cache.clear(), 1);
+        assert!(cache.is_empty());
+    }
+}
@@ -0,0 +1,295 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Current status of a background job.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub enum JobStatus {
+    Running,
+    Completed,
+    Cancelled,
+    Failed,
+}
+
+/// Progress of a background job.
+///
+/// Jobs fill in the counters that make sense for them, e.g. a delete job counts items and
+/// bytes while a hashing job may only count bytes. Totals are `0` while they are unknown.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct JobProgress {
+    pub items_total: u64,
+    pub items_processed: u64,
+    pub bytes_total: u64,
+    pub bytes_processed: u64,
+    pub current_path: Option<String>,
+}
+
+/// Serializable snapshot of a job for the frontend.
+///
+/// `result` may already be set while the job is still running, so jobs can stream
+/// partial results which the UI picks up when polling.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct JobInfo {
+    pub id: u64,
+    pub kind: String,
+    pub status: JobStatus,
+    pub progress: JobProgress,
+    pub result: Option<Value>,
+    pub error: Option<String>,
+    pub started_at: u64,           // as milliseconds since epoch
+    pub finished_at: Option<u64>, // as milliseconds since epoch
+}
+
+struct JobEntry {
+    info: JobInfo,
+    cancel_flag: Arc<AtomicBool>,
+}
+
+/// Registry of long-running operations like deleting, hashing or transferring files.
+///
+/// Commands start a job, hand the returned `JobHandle` to a worker thread and return the
+/// job id immediately. The frontend polls the job through the job commands and can
+/// request cancellation, which workers check between units of work.
+pub struct JobsState {
+    jobs: HashMap<u64, JobEntry>,
+    next_id: u64,
+}
+
+impl JobsState {
+    pub fn new() -> Self {
+        Self {
+            jobs: HashMap::new(),
+            next_id: 1,
+        }
+    }
+
+    /// Registers a new running job and returns the handle used by the worker to report on it.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - The shared jobs state, the handle keeps a reference to it.
+    /// * `kind` - A short identifier of the operation, e.g. `"delete"`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let job = JobsState::start_job(&jobs_state, "delete");
+    /// std::thread::spawn(move || {
+    ///     job.update_progress(|p| p.items_processed += 1);
+    ///     job.complete(None);
+    /// });
+    /// ```
+    pub fn start_job(state: &Arc<Mutex<JobsState>>, kind: &str) -> JobHandle {
+        let cancel_flag = Arc::new(AtomicBool::new(false));
+        let mut jobs = state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        let id = jobs.next_id;
+        jobs.next_id += 1;
+        jobs.jobs.insert(
+            id,
+            JobEntry {
+                info: JobInfo {
+                    id,
+                    kind: kind.to_string(),
+                    status: JobStatus::Running,
+                    progress: JobProgress::default(),
+                    result: None,
+                    error: None,
+                    started_at: chrono::Utc::now().timestamp_millis() as u64,
+                    finished_at: None,
+                },
+                cancel_flag: cancel_flag.clone(),
+            },
+        );
+
+        JobHandle {
+            id,
+            state: state.clone(),
+            cancel_flag,
+        }
+    }
+
+    pub fn get_job(&self, id: u64) -> Option<JobInfo> {
+        self.jobs.get(&id).map(|entry| entry.info.clone())
+    }
+
+    pub fn list_jobs(&self) -> Vec<JobInfo> {
+        let mut jobs: Vec<JobInfo> = self.jobs.values().map(|entry| entry.info.clone()).collect();
+        jobs.sort_by_key(|job| job.id);
+        jobs
+    }
+
+    /// Requests cancellation of a running job. The worker stops at its next check.
+    pub fn cancel_job(&self, id: u64) -> Result<(), String> {
+        let entry = self
+            .jobs
+            .get(&id)
+            .ok_or_else(|| format!("Job not found: {}", id))?;
+        if entry.info.status != JobStatus::Running {
+            return Err(format!("Job is not running: {}", id));
+        }
+        entry.cancel_flag.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Removes all jobs that are no longer running and returns how many were removed.
+    pub fn remove_finished_jobs(&mut self) -> usize {
+        let before = self.jobs.len();
+        self.jobs
+            .retain(|_, entry| entry.info.status == JobStatus::Running);
+        before - self.jobs.len()
+    }
+
+    fn with_job<F: FnOnce(&mut JobInfo)>(&mut self, id: u64, f: F) {
+        if let Some(entry) = self.jobs.get_mut(&id) {
+            f(&mut entry.info);
+        }
+    }
+}
+
+impl Default for JobsState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Handle given to the worker of a job to report progress and the final outcome.
+#[derive(Clone)]
+pub struct JobHandle {
+    id: u64,
+    state: Arc<Mutex<JobsState>>,
+    cancel_flag: Arc<AtomicBool>,
+}
+
+impl JobHandle {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Returns true once cancellation was requested for this job.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel_flag.load(Ordering::SeqCst)
+    }
+
+    fn update<F: FnOnce(&mut JobInfo)>(&self, f: F) {
+        let mut jobs = self
+            .state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        jobs.with_job(self.id, f);
+    }
+
+    pub fn update_progress<F: FnOnce(&mut JobProgress)>(&self, f: F) {
+        self.update(|info| f(&mut info.progress));
+    }
+
+    /// Stores an intermediate result which the frontend can already read while the job runs.
+    pub fn set_partial_result(&self, result: Value) {
+        self.update(|info| info.result = Some(result));
+    }
+
+    pub fn complete(&self, result: Option<Value>) {
+        self.finish(JobStatus::Completed, result, None);
+    }
+
+    pub fn fail(&self, error: String) {
+        self.finish(JobStatus::Failed, None, Some(error));
+    }
+
+    pub fn mark_cancelled(&self) {
+        self.finish(JobStatus::Cancelled, None, None);
+    }
+
+    fn finish(&self, status: JobStatus, result: Option<Value>, error: Option<String>) {
+        self.update(|info| {
+            info.status = status;
+            if result.is_some() {
+                info.result = result;
+            }
+            info.error = error;
+            info.progress.current_path = None;
+            info.finished_at = Some(chrono::Utc::now().timestamp_millis() as u64);
+        });
+    }
+}
+
+/// Polls a job until it is no longer running, for the tests of commands which start jobs.
+#[cfg(test)]
+pub fn wait_for_job(jobs_state: &Arc<Mutex<JobsState>>, job_id: u64) -> JobInfo {
+    for _ in 0..1000 {
+        let info = jobs_state.lock().unwrap().get_job(job_id).unwrap();
+        if info.status != JobStatus::Running {
+            return info;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    panic!("Job {} did not finish in time", job_id);
+}
+
+/// Creates a settings state stored in a temporary file, for the tests of commands which read
+/// settings.
+#[cfg(test)]
+pub fn create_test_settings_state() -> Arc<Mutex<crate::state::SettingsState>> {
+    let temp_file = tempfile::NamedTempFile::new().unwrap();
+    Arc::new(Mutex::new(crate::state::SettingsState::new_with_path(
+        temp_file.path().to_path_buf(),
+    )))
+}
+
+#[cfg(test)]
+mod tests_jobs_data {
+    use super::*;
+
+    #[test]
+    fn job_lifecycle_test() {
+        let state = Arc::new(Mutex::new(JobsState::new()));
+        let job = JobsState::start_job(&state, "test");
+
+        job.update_progress(|p| {
+            p.items_total = 2;
+            p.items_processed = 1;
+        });
+
+        let info = state.lock().unwrap().get_job(job.id()).unwrap();
+        assert_eq!(info.status, JobStatus::Running);
+        assert_eq!(info.progress.items_processed, 1);
+
+        job.complete(Some(serde_json::json!({ "deleted": 2 })));
+        let info = state.lock().unwrap().get_job(job.id()).unwrap();
+        assert_eq!(info.status, JobStatus::Completed);
+        assert!(info.finished_at.is_some());
+        assert_eq!(info.result.unwrap()["deleted"], 2);
+    }
+
+    #[test]
+    fn cancel_job_test() {
+        let state = Arc::new(Mutex::new(JobsState::new()));
+        let job = JobsState::start_job(&state, "test");
+
+        assert!(!job.is_cancelled());
+        assert!(state.lock().unwrap().cancel_job(job.id()).is_ok());
+        assert!(job.is_cancelled());
+
+        job.mark_cancelled();
+        assert!(
+            state.lock().unwrap().cancel_job(job.id()).is_err(),
+            "Finished jobs cannot be cancelled"
+        );
+        assert!(state.lock().unwrap().cancel_job(999).is_err());
+    }
+
+    #[test]
+    fn remove_finished_jobs_test() {
+        let state = Arc::new(Mutex::new(JobsState::new()));
+        let running = JobsState::start_job(&state, "running");
+        let failed = JobsState::start_job(&state, "failed");
+        failed.fail("boom".to_string());
+
+        assert_eq!(state.lock().unwrap().remove_finished_jobs(), 1);
+        let jobs = state.lock().unwrap().list_jobs();
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].id, running.id());
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How many listings are kept at the same time, the oldest one is dropped first.
+const MAX_SNAPSHOTS: usize = 16;
+
+/// How long a listing can be paged through before the frontend has to start over.
+const SNAPSHOT_LIFETIME: Duration = Duration::from_secs(300);
+
+/// The minimal information needed to sort and filter a directory entry.
+/// Everything else is read when the entry is actually part of a page.
+#[derive(Debug, Clone)]
+pub struct ListingEntry {
+    pub name: String,
+    pub path: PathBuf,
+    pub is_dir: bool,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+}
+
+/// A sorted and filtered directory listing which is handed out page by page.
+#[derive(Debug, Clone)]
+pub struct ListingSnapshot {
+    pub path: String,
+    pub entries: Vec<ListingEntry>,
+    pub directory_count: usize,
+    created: Instant,
+}
+
+/// Keeps sorted directory listings alive between page requests, so paging through a huge
+/// directory reads and sorts it only once and pages stay consistent while files change.
+pub struct DirectoryListingState {
+    snapshots: HashMap<u64, ListingSnapshot>,
+    next_id: u64,
+}
+
+impl DirectoryListingState {
+    pub fn new() -> Self {
+        Self {
+            snapshots: HashMap::new(),
+            next_id: 1,
+        }
+    }
+
+    /// Stores a listing and returns its id, which is part of the pagination cursor.
+    pub fn insert(&mut self, path: String, entries: Vec<ListingEntry>) -> u64 {
+        self.remove_expired();
+        if self.snapshots.len() >= MAX_SNAPSHOTS {
+            // Ids are handed out in order, so the smallest one is the oldest snapshot
+            if let Some(oldest) = self.snapshots.keys().min().copied() {
+                self.snapshots.remove(&oldest);
+            }
+        }
+
+        let id = self.next_id;
+        self.next_id += 1;
+        let directory_count = entries.iter().filter(|entry| entry.is_dir).count();
+        self.snapshots.insert(
+            id,
+            ListingSnapshot {
+                path,
+                entries,
+                directory_count,
+                created: Instant::now(),
+            },
+        );
+        id
+    }
+
+    pub fn get(&self, id: u64) -> Option<&ListingSnapshot> {
+        self.snapshots
+            .get(&id)
+            .filter(|snapshot| snapshot.created.elapsed() < SNAPSHOT_LIFETIME)
+    }
+
+    pub fn remove(&mut self, id: u64) -> bool {
+        self.snapshots.remove(&id).is_some()
+    }
+
+    fn remove_expired(&mut self) {
+        self.snapshots
+            .retain(|_, snapshot| snapshot.created.elapsed() < SNAPSHOT_LIFETIME);
+    }
+}
+
+impl Default for DirectoryListingState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests_listing_data {
+    use super::*;
+
+    #[test]
+    fn oldest_snapshot_is_dropped_test() {
+        let mut state = DirectoryListingState::new();
+        let first = state.insert("/first".to_string(), vec![]);
+        for i in 0..MAX_SNAPSHOTS {
+            state.insert(format!("/dir{}", i), vec![]);
+        }
+
+        assert!(state.get(first).is_none());
+        assert!(state.get(first + 1).is_some());
+        assert!(state.remove(first + 1));
+        assert!(state.get(first + 1).is_none());
+    }
+}
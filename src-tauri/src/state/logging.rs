@@ -59,7 +59,7 @@ use std::fmt;
 use std::fs;
 use std::fs::OpenOptions;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use serde_json::json;
 
@@ -231,12 +231,12 @@ impl Logger {
     // Create empty log files if they don't exist
     fn ensure_log_files_exist() {
         // Create empty app.log if it doesn't exist
-        if let Err(e) = OpenOptions::new().write(true).create(true).open(&*LOG_FILE_ABS_PATH) {
+        if let Err(e) = OpenOptions::new().write(true).create(true).truncate(false).open(&*LOG_FILE_ABS_PATH) {
             eprintln!("Failed to create log file: {}", e);
         }
         
         // Create empty error.log if it doesn't exist
-        if let Err(e) = OpenOptions::new().write(true).create(true).open(&*ERROR_LOG_FILE_ABS_PATH) {
+        if let Err(e) = OpenOptions::new().write(true).create(true).truncate(false).open(&*ERROR_LOG_FILE_ABS_PATH) {
             eprintln!("Failed to create error log file: {}", e);
         }
     }
@@ -262,7 +262,7 @@ impl Logger {
         // Retrieve the logging state with proper error handling
         let (logging_state, json_log) = match self.state.lock() {
             Ok(state_guard) => match state_guard.0.lock() {
-                Ok(settings) => (settings.backend_settings.logging_config.logging_level.clone(), settings.backend_settings.logging_config.json_log.clone()),
+                Ok(settings) => (settings.backend_settings.logging_config.logging_level.clone(), settings.backend_settings.logging_config.json_log),
                 Err(e) => {
                     eprintln!("Failed to acquire inner settings lock: {}", e);
                     (LoggingLevel::Minimal, false)
@@ -324,7 +324,7 @@ impl Logger {
         self.enforce_log_file_limit(path);
     }
 
-    fn enforce_log_file_limit(&self, current_log_path: &PathBuf) {
+    fn enforce_log_file_limit(&self, current_log_path: &Path) {
         if let Some(parent) = current_log_path.parent() {
             if let Some(base_name) = current_log_path.file_stem() {
                 let base_name = base_name.to_string_lossy();
@@ -348,10 +348,10 @@ impl Logger {
                 archived_logs.sort_by(|a, b| {
                     a.metadata()
                         .and_then(|m| m.modified())
-                        .unwrap_or_else(|_| std::time::SystemTime::UNIX_EPOCH)
+                        .unwrap_or(std::time::SystemTime::UNIX_EPOCH)
                         .cmp(&b.metadata()
                             .and_then(|m| m.modified())
-                            .unwrap_or_else(|_| std::time::SystemTime::UNIX_EPOCH))
+                            .unwrap_or(std::time::SystemTime::UNIX_EPOCH))
                 });
 
                 // If we have more than 2 archived files (3 total including current), remove the oldest
@@ -443,7 +443,7 @@ impl Logger {
             Err(e) => {
                 eprintln!("Failed to open log file for writing: {}", e);
                 eprintln!("Path: {}", path.display());
-                eprintln!("Parent exists: {}", path.parent().map_or(false, |p| p.exists()));
+                eprintln!("Parent exists: {}", path.parent().is_some_and(|p| p.exists()));
                 // Create an error using our error handling module but just log it
                 let error = Error::new(
                     ErrorCode::ResourceNotFound,
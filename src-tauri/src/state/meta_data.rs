@@ -131,8 +131,10 @@ impl MetaDataState {
     /// ```
     #[cfg(test)]
     pub fn new_with_path(path: PathBuf) -> Self {
-        let mut defaults = MetaData::default();
-        defaults.abs_file_path_buf = path;
+        let defaults = MetaData {
+            abs_file_path_buf: path,
+            ..MetaData::default()
+        };
         Self(Arc::new(Mutex::new(
             Self::write_meta_data_to_file_and_save_in_state(defaults),
         )))
@@ -207,7 +209,7 @@ impl MetaDataState {
     pub fn write_meta_data_to_file(&self, meta_data: &MetaData) -> io::Result<()> {
         let user_config_file_path = &meta_data.abs_file_path_buf;
         let serialized = serde_json::to_string_pretty(&meta_data)
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+            .map_err(io::Error::other)?;
 
         // Makes sure the parent directory exists
         if let Some(parent) = user_config_file_path.parent() {
@@ -422,9 +424,11 @@ mod metadata_tests {
         let test_path = temp_dir.path().join("meta_data.json");
 
         // Create a custom metadata object
-        let mut meta_data = MetaData::default();
-        meta_data.abs_file_path_buf = test_path.clone();
-        meta_data.version = "test-version".to_string();
+        let mut meta_data = MetaData {
+            abs_file_path_buf: test_path.clone(),
+            version: "test-version".to_string(),
+            ..MetaData::default()
+        };
 
         // Customize the OS, CPU, and home directory fields for testing
         let test_os = "test-os".to_string();
@@ -25,12 +25,34 @@ pub mod meta_data;
 pub mod searchengine_data;
 pub mod settings_data;
 pub mod logging;
+pub mod jobs_data;
+pub mod dir_size_cache_data;
+pub mod listing_data;
+pub mod sftp_auth_data;
+pub mod secret_store_data;
+pub mod connections_data;
+pub mod sftp_session_data;
+pub mod remote_edit_data;
+pub mod remote_session_data;
+pub mod pending_deletions_data;
+pub mod archive_listing_data;
+pub mod sync_snapshots_data;
 
 pub use settings_data::*;
 
 use logging::Logger;
 use crate::state::searchengine_data::SearchEngineState;
 use meta_data::MetaDataState;
+use jobs_data::JobsState;
+use dir_size_cache_data::DirSizeCacheState;
+use listing_data::DirectoryListingState;
+use sftp_session_data::SftpSessionState;
+use connections_data::ConnectionsState;
+use remote_edit_data::RemoteEditState;
+use remote_session_data::RemoteSessionState;
+use pending_deletions_data::PendingDeletionsState;
+use archive_listing_data::ArchiveListingState;
+use sync_snapshots_data::SyncSnapshotsState;
 use std::sync::{Arc, Mutex};
 use tauri::{Builder, Wry};
 
@@ -39,6 +61,18 @@ pub fn setup_app_state(app: Builder<Wry>) -> Builder<Wry> {
     let meta_data_state = Arc::new(Mutex::new(MetaDataState::new()));
     let settings_state = Arc::new(Mutex::new(SettingsState::new()));
     let search_engine_state = Arc::new(Mutex::new(SearchEngineState::new(settings_state.clone())));
+    let jobs_state = Arc::new(Mutex::new(JobsState::new()));
+    let dir_size_cache_state = Arc::new(Mutex::new(DirSizeCacheState::new()));
+    let listing_state = Arc::new(Mutex::new(DirectoryListingState::new()));
+    let sftp_session_state = Arc::new(Mutex::new(SftpSessionState::new()));
+    SftpSessionState::start_keepalive(&sftp_session_state);
+    let connections_state = Arc::new(Mutex::new(ConnectionsState::new()));
+    let remote_edit_state = Arc::new(Mutex::new(RemoteEditState::new()));
+    RemoteEditState::start_watcher(&remote_edit_state, &sftp_session_state);
+    let remote_session_state = Arc::new(Mutex::new(RemoteSessionState::new()));
+    let pending_deletions_state = Arc::new(Mutex::new(PendingDeletionsState::new()));
+    let archive_listing_state = Arc::new(Mutex::new(ArchiveListingState::new()));
+    let sync_snapshots_state = Arc::new(Mutex::new(SyncSnapshotsState::new()));
     
     // Initialize the logger with the settings state
     Logger::init(settings_state.clone());
@@ -47,4 +81,14 @@ pub fn setup_app_state(app: Builder<Wry>) -> Builder<Wry> {
     app.manage(meta_data_state)
         .manage(settings_state)
         .manage(search_engine_state)
+        .manage(jobs_state)
+        .manage(dir_size_cache_state)
+        .manage(listing_state)
+        .manage(sftp_session_state)
+        .manage(connections_state)
+        .manage(remote_edit_state)
+        .manage(remote_session_state)
+        .manage(pending_deletions_state)
+        .manage(archive_listing_state)
+        .manage(sync_snapshots_state)
 }
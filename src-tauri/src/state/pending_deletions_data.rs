@@ -0,0 +1,68 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// How long a confirmation token stays valid.
+pub const CONFIRMATION_TOKEN_LIFETIME: Duration = Duration::from_secs(120);
+
+struct PendingDeletion {
+    paths: Vec<String>,
+    created: Instant,
+}
+
+/// Confirmation tokens handed out by `request_delete_confirmation`, each one can be used once.
+pub struct PendingDeletionsState {
+    deletions: HashMap<String, PendingDeletion>,
+}
+
+impl PendingDeletionsState {
+    pub fn new() -> Self {
+        Self {
+            deletions: HashMap::new(),
+        }
+    }
+
+    /// Stores a token for the given paths, expired tokens are dropped on the way.
+    pub fn insert(&mut self, token: String, paths: Vec<String>) {
+        self.remove_expired();
+        self.deletions.insert(
+            token,
+            PendingDeletion {
+                paths,
+                created: Instant::now(),
+            },
+        );
+    }
+
+    /// Removes a token and returns the paths it was issued for, `None` if the token is
+    /// unknown or expired.
+    pub fn take(&mut self, token: &str) -> Option<Vec<String>> {
+        self.remove_expired();
+        self.deletions.remove(token).map(|deletion| deletion.paths)
+    }
+
+    fn remove_expired(&mut self) {
+        self.deletions
+            .retain(|_, deletion| deletion.created.elapsed() < CONFIRMATION_TOKEN_LIFETIME);
+    }
+}
+
+impl Default for PendingDeletionsState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests_pending_deletions_data {
+    use super::*;
+
+    #[test]
+    fn tokens_can_be_taken_once_test() {
+        let mut state = PendingDeletionsState::new();
+        state.insert("token".to_string(), vec!["/tmp/a".to_string()]);
+
+        assert_eq!(state.take("token"), Some(vec!["/tmp/a".to_string()]));
+        assert_eq!(state.take("token"), None);
+        assert_eq!(state.take("unknown"), None);
+    }
+}
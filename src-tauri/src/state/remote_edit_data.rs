@@ -0,0 +1,358 @@
+use crate::commands::remote_edit_commands::sync_remote_edits;
+use crate::state::sftp_session_data::SftpSessionState;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::{Duration, UNIX_EPOCH};
+
+/// How often the local copies are checked for changes. A change is uploaded once the file
+/// did not change for one interval, so editors which write a file in several steps are done.
+pub const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub enum RemoteEditStatus {
+    /// The local copy is the version on the server.
+    Synced,
+    /// The local copy was saved and is uploaded once it stops changing.
+    Modified,
+    Uploading,
+    /// The remote file was changed by someone else since it was downloaded. Nothing is
+    /// uploaded until the conflict is resolved.
+    Conflict,
+    /// The last upload failed, it is tried again on the next save.
+    Failed,
+}
+
+/// Modification time and size of a file, enough to notice that it was changed.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+pub struct FileVersion {
+    pub modified: Option<u64>, // as milliseconds since epoch
+    pub size: u64,
+}
+
+impl FileVersion {
+    pub fn of_local(path: &Path) -> Option<FileVersion> {
+        let metadata = fs::metadata(path).ok()?;
+        Some(FileVersion {
+            modified: metadata
+                .modified()
+                .ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map(|duration| duration.as_millis() as u64),
+            size: metadata.len(),
+        })
+    }
+
+    /// SFTP only has modification times in seconds.
+    pub fn of_remote(modified_secs: Option<u64>, size: Option<u64>) -> FileVersion {
+        FileVersion {
+            modified: modified_secs.map(|secs| secs * 1000),
+            size: size.unwrap_or(0),
+        }
+    }
+}
+
+/// A remote file which is edited through a local copy.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct RemoteEdit {
+    pub id: u64,
+    pub session_id: u64,
+    pub remote_path: String,
+    pub local_path: PathBuf,
+    pub status: RemoteEditStatus,
+    pub error: Option<String>,
+    /// The remote file as it was last downloaded or uploaded.
+    pub remote_version: FileVersion,
+    /// The local copy as it was last downloaded or uploaded.
+    pub local_version: FileVersion,
+    pub last_uploaded: Option<u64>, // as milliseconds since epoch
+    /// The local copy as it was seen by the last check, to wait until it stops changing.
+    #[serde(skip)]
+    observed_version: FileVersion,
+}
+
+impl RemoteEdit {
+    pub fn new(
+        id: u64,
+        session_id: u64,
+        remote_path: String,
+        local_path: PathBuf,
+        remote_version: FileVersion,
+        local_version: FileVersion,
+    ) -> Self {
+        Self {
+            id,
+            session_id,
+            remote_path,
+            local_path,
+            status: RemoteEditStatus::Synced,
+            error: None,
+            remote_version,
+            local_version,
+            last_uploaded: None,
+            observed_version: local_version,
+        }
+    }
+
+    /// Whether the local copy has changes which are not on the server.
+    pub fn has_pending_changes(&self) -> bool {
+        self.status != RemoteEditStatus::Synced
+    }
+
+    /// Compares the current local copy with the last synced one. Returns true once a change
+    /// was seen unchanged by two checks in a row and should be uploaded.
+    fn observe(&mut self, current: Option<FileVersion>) -> bool {
+        // Editors which save by replacing the file remove it for a moment
+        let Some(current) = current else {
+            return false;
+        };
+        match self.status {
+            RemoteEditStatus::Conflict | RemoteEditStatus::Uploading => return false,
+            RemoteEditStatus::Failed if current == self.observed_version => return false,
+            _ => {}
+        }
+        if current == self.local_version {
+            // Saved back to the synced content, e.g. after an undo
+            self.status = RemoteEditStatus::Synced;
+            return false;
+        }
+        let stable = self.status == RemoteEditStatus::Modified && current == self.observed_version;
+        self.observed_version = current;
+        self.status = RemoteEditStatus::Modified;
+        stable
+    }
+}
+
+/// The remote files which are edited locally. A watcher thread checks the local copies and
+/// uploads them when they were saved, see `sync_remote_edits`.
+pub struct RemoteEditState {
+    edits: HashMap<u64, RemoteEdit>,
+    next_id: u64,
+}
+
+impl RemoteEditState {
+    pub fn new() -> Self {
+        Self {
+            edits: HashMap::new(),
+            next_id: 1,
+        }
+    }
+
+    /// Reserves the id of a new edit, its local copy is stored in a directory named after it.
+    pub fn next_id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    pub fn insert(&mut self, edit: RemoteEdit) {
+        self.edits.insert(edit.id, edit);
+    }
+
+    pub fn get(&self, id: u64) -> Option<RemoteEdit> {
+        self.edits.get(&id).cloned()
+    }
+
+    /// Finds the edit of a remote file, so opening it twice reuses the local copy.
+    pub fn find(&self, session_id: u64, remote_path: &str) -> Option<RemoteEdit> {
+        self.edits
+            .values()
+            .find(|edit| edit.session_id == session_id && edit.remote_path == remote_path)
+            .cloned()
+    }
+
+    pub fn list(&self) -> Vec<RemoteEdit> {
+        let mut edits: Vec<RemoteEdit> = self.edits.values().cloned().collect();
+        edits.sort_by_key(|edit| edit.id);
+        edits
+    }
+
+    pub fn remove(&mut self, id: u64) -> Option<RemoteEdit> {
+        self.edits.remove(&id)
+    }
+
+    /// Drops the edits of closed sessions, their changes can't be uploaded anymore. The local
+    /// copies stay until `cleanup_sftp_temp_files` removes them.
+    pub fn retain_sessions(&mut self, open_sessions: &[u64]) {
+        self.edits
+            .retain(|_, edit| open_sessions.contains(&edit.session_id));
+    }
+
+    /// Checks all local copies and marks the ones which are ready to upload as `Uploading`.
+    /// Returns them, so they can be uploaded without holding the lock on the state.
+    pub fn take_ready_uploads(&mut self) -> Vec<RemoteEdit> {
+        let mut ready = Vec::new();
+        for edit in self.edits.values_mut() {
+            if edit.observe(FileVersion::of_local(&edit.local_path)) {
+                edit.status = RemoteEditStatus::Uploading;
+                ready.push(edit.clone());
+            }
+        }
+        ready.sort_by_key(|edit| edit.id);
+        ready
+    }
+
+    /// Marks an edit as `Uploading` so the watcher leaves it alone, e.g. while a conflict is
+    /// resolved.
+    pub fn begin_sync(&mut self, id: u64) {
+        if let Some(edit) = self.edits.get_mut(&id) {
+            edit.status = RemoteEditStatus::Uploading;
+        }
+    }
+
+    /// Records that the local copy and the remote file are the same again.
+    pub fn mark_synced(
+        &mut self,
+        id: u64,
+        remote_version: FileVersion,
+        local_version: FileVersion,
+        uploaded: bool,
+    ) {
+        if let Some(edit) = self.edits.get_mut(&id) {
+            edit.status = RemoteEditStatus::Synced;
+            edit.error = None;
+            edit.remote_version = remote_version;
+            edit.local_version = local_version;
+            edit.observed_version = local_version;
+            if uploaded {
+                edit.last_uploaded = Some(chrono::Utc::now().timestamp_millis() as u64);
+            }
+        }
+    }
+
+    pub fn mark_conflict(&mut self, id: u64) {
+        if let Some(edit) = self.edits.get_mut(&id) {
+            edit.status = RemoteEditStatus::Conflict;
+            edit.error = None;
+        }
+    }
+
+    pub fn mark_failed(&mut self, id: u64, error: String) {
+        if let Some(edit) = self.edits.get_mut(&id) {
+            edit.status = RemoteEditStatus::Failed;
+            edit.error = Some(error);
+        }
+    }
+
+    /// Starts the thread which uploads saved local copies. It stops once the state is dropped.
+    pub fn start_watcher(state: &Arc<Mutex<Self>>, sftp_state: &Arc<Mutex<SftpSessionState>>) {
+        let state: Weak<Mutex<Self>> = Arc::downgrade(state);
+        let sftp_state: Weak<Mutex<SftpSessionState>> = Arc::downgrade(sftp_state);
+        let _ = thread::Builder::new()
+            .name("remote-edit-watcher-thread".to_string())
+            .spawn(move || loop {
+                thread::sleep(WATCH_INTERVAL);
+                let (Some(state), Some(sftp_state)) = (state.upgrade(), sftp_state.upgrade())
+                else {
+                    break;
+                };
+                sync_remote_edits(&state, &sftp_state);
+            });
+    }
+}
+
+impl Default for RemoteEditState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests_remote_edit_data {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn edit_of(path: &Path) -> RemoteEdit {
+        let version = FileVersion::of_local(path).unwrap();
+        RemoteEdit::new(
+            1,
+            3,
+            "/srv/app.conf".to_string(),
+            path.to_path_buf(),
+            FileVersion::of_remote(Some(1_700_000_000), Some(version.size)),
+            version,
+        )
+    }
+
+    #[test]
+    fn change_is_uploaded_once_it_is_stable_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let path = temp_dir.path().join("app.conf");
+        fs::write(&path, "a").unwrap();
+        let mut state = RemoteEditState::new();
+        state.insert(edit_of(&path));
+
+        assert!(state.take_ready_uploads().is_empty());
+        assert_eq!(state.get(1).unwrap().status, RemoteEditStatus::Synced);
+
+        fs::write(&path, "changed").unwrap();
+        assert!(state.take_ready_uploads().is_empty());
+        assert_eq!(state.get(1).unwrap().status, RemoteEditStatus::Modified);
+
+        let ready = state.take_ready_uploads();
+        assert_eq!(ready.len(), 1);
+        assert_eq!(state.get(1).unwrap().status, RemoteEditStatus::Uploading);
+
+        // Nothing is uploaded twice while the upload runs
+        assert!(state.take_ready_uploads().is_empty());
+
+        let local_version = FileVersion::of_local(&path).unwrap();
+        state.mark_synced(
+            1,
+            FileVersion::of_remote(Some(1_700_000_100), Some(7)),
+            local_version,
+            true,
+        );
+        let edit = state.get(1).unwrap();
+        assert_eq!(edit.status, RemoteEditStatus::Synced);
+        assert!(edit.last_uploaded.is_some());
+        assert!(!edit.has_pending_changes());
+        assert!(state.take_ready_uploads().is_empty());
+    }
+
+    #[test]
+    fn conflict_and_failure_wait_for_the_user_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let path = temp_dir.path().join("app.conf");
+        fs::write(&path, "a").unwrap();
+        let mut state = RemoteEditState::new();
+        state.insert(edit_of(&path));
+
+        state.mark_conflict(1);
+        fs::write(&path, "changed").unwrap();
+        assert!(state.take_ready_uploads().is_empty());
+        assert!(state.take_ready_uploads().is_empty());
+        assert_eq!(state.get(1).unwrap().status, RemoteEditStatus::Conflict);
+
+        // A failed upload is retried after the next save
+        let local_version = state.get(1).unwrap().local_version;
+        state.mark_synced(1, FileVersion::default(), local_version, false);
+        assert!(state.take_ready_uploads().is_empty());
+        assert_eq!(state.take_ready_uploads().len(), 1);
+        state.mark_failed(1, "Permission denied".to_string());
+        assert!(state.take_ready_uploads().is_empty());
+        assert_eq!(state.get(1).unwrap().status, RemoteEditStatus::Failed);
+        fs::write(&path, "changed again").unwrap();
+        assert!(state.take_ready_uploads().is_empty());
+        assert_eq!(state.take_ready_uploads().len(), 1);
+    }
+
+    #[test]
+    fn edits_of_closed_sessions_are_dropped_test() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let path = temp_dir.path().join("app.conf");
+        fs::write(&path, "a").unwrap();
+        let mut state = RemoteEditState::new();
+        state.insert(edit_of(&path));
+
+        assert!(state.find(3, "/srv/app.conf").is_some());
+        assert!(state.find(4, "/srv/app.conf").is_none());
+        state.retain_sessions(&[3]);
+        assert_eq!(state.list().len(), 1);
+        state.retain_sessions(&[4]);
+        assert!(state.list().is_empty());
+    }
+}
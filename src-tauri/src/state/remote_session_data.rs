@@ -0,0 +1,49 @@
+use crate::remote::{next_session_id, RemoteFileSystem};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// The open FTP and WebDAV sessions. Like [`crate::state::sftp_session_data::SftpSessionState`],
+/// commands look a session up and release the lock on this state before talking to the server.
+pub struct RemoteSessionState {
+    sessions: HashMap<u64, Arc<dyn RemoteFileSystem>>,
+}
+
+impl RemoteSessionState {
+    pub fn new() -> Self {
+        Self {
+            sessions: HashMap::new(),
+        }
+    }
+
+    /// Adds a connected session and returns its id.
+    pub fn insert(&mut self, session: Arc<dyn RemoteFileSystem>) -> u64 {
+        let id = next_session_id();
+        self.sessions.insert(id, session);
+        id
+    }
+
+    pub fn get(&self, id: u64) -> Option<Arc<dyn RemoteFileSystem>> {
+        self.sessions.get(&id).cloned()
+    }
+
+    pub fn remove(&mut self, id: u64) -> Option<Arc<dyn RemoteFileSystem>> {
+        self.sessions.remove(&id)
+    }
+
+    /// All sessions ordered by id.
+    pub fn sessions(&self) -> Vec<(u64, Arc<dyn RemoteFileSystem>)> {
+        let mut sessions: Vec<_> = self
+            .sessions
+            .iter()
+            .map(|(id, session)| (*id, session.clone()))
+            .collect();
+        sessions.sort_by_key(|(id, _)| *id);
+        sessions
+    }
+}
+
+impl Default for RemoteSessionState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
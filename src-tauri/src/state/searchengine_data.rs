@@ -4,7 +4,7 @@ use crate::state::SettingsState;
 #[allow(unused_imports)]
 use crate::{log_error, log_info, log_warn};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex, RwLock};
 use std::time::{Instant};
 use std::{fs};
@@ -57,6 +57,7 @@ impl Default for IndexingProgress {
 /// Collects statistics about search engine performance to help users
 /// understand system behavior and identify potential optimizations.
 #[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Default)]
 pub struct SearchEngineMetrics {
     pub last_indexing_duration_ms: Option<u64>,
     pub average_search_time_ms: Option<f32>,
@@ -65,36 +66,18 @@ pub struct SearchEngineMetrics {
     pub cache_hits: usize,
 }
 
-impl Default for SearchEngineMetrics {
-    fn default() -> Self {
-        Self {
-            last_indexing_duration_ms: None,
-            average_search_time_ms: None,
-            cache_hit_rate: None,
-            total_searches: 0,
-            cache_hits: 0,
-        }
-    }
-}
 
 /// User activity data related to search operations.
 ///
 /// Tracks recent user interactions with the search system to provide
 /// history features and improve result relevance through usage patterns.
 #[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Default)]
 pub struct RecentActivity {
     pub recent_searches: Vec<String>,
     pub most_accessed_paths: Vec<String>,
 }
 
-impl Default for RecentActivity {
-    fn default() -> Self {
-        Self {
-            recent_searches: Vec::new(),
-            most_accessed_paths: Vec::new(),
-        }
-    }
-}
 
 /// Serializable version of engine statistics.
 ///
@@ -232,9 +215,10 @@ impl SearchEngineState {
     ///
     /// A SearchEngine instance with default settings.
     fn save_default_search_engine_in_state(config: SearchEngineConfig) -> SearchEngine {
-        let mut defaults = SearchEngine::default();
-        defaults.config = config;
-        Self::save_search_engine_in_state(defaults)
+        Self::save_search_engine_in_state(SearchEngine {
+            config,
+            ..SearchEngine::default()
+        })
     }
 
     /// Saves a search engine configuration to state.
@@ -456,7 +440,7 @@ impl SearchEngineState {
     /// Optimized to prevent stack overflow using iterative traversal
     fn index_directory_streaming(
         &self,
-        dir: &PathBuf,
+        dir: &Path,
         excluded_patterns: &[String],
         chunk_size: usize,
     ) -> Result<(), String> {
@@ -515,7 +499,7 @@ impl SearchEngineState {
     /// Uses a queue-based approach instead of recursion for memory safety
     fn process_directory_iterative(
         &self,
-        root_dir: &PathBuf,
+        root_dir: &Path,
         excluded_patterns: &[String],
         discovered_files: &mut usize,
         indexed_files: &mut usize,
@@ -526,7 +510,7 @@ impl SearchEngineState {
         
         // Use a queue for iterative traversal instead of recursion
         let mut dir_queue = VecDeque::new();
-        dir_queue.push_back((root_dir.clone(), 0));
+        dir_queue.push_back((root_dir.to_path_buf(), 0));
         
         const MAX_DEPTH: usize = 25;
         const MAX_FILES: usize = 500000;
@@ -585,7 +569,7 @@ impl SearchEngineState {
                             *discovered_files += 1;
 
                             // Update progress more frequently for better UX
-                            if *discovered_files % 10 == 0 || *discovered_files == 1 {
+                            if (*discovered_files).is_multiple_of(10) || *discovered_files == 1 {
                                 self.update_progress_safely(*discovered_files, *indexed_files, Some(path_str.to_string()));
                             }
 
@@ -804,7 +788,7 @@ impl SearchEngineState {
         if data.metrics.total_searches > 0 {
             let hit_rate = (data.metrics.cache_hits as f32 / data.metrics.total_searches as f32) * 100.0;
             // Ensure cache hit rate is reasonable (between 0% and 100%)
-            let clamped_hit_rate = hit_rate.min(100.0).max(0.0);
+            let clamped_hit_rate = hit_rate.clamp(0.0, 100.0);
             
             #[cfg(debug_assertions)]
             if hit_rate > 100.0 {
@@ -958,7 +942,7 @@ impl SearchEngineState {
         if data.metrics.total_searches > 0 {
             let hit_rate = (data.metrics.cache_hits as f32 / data.metrics.total_searches as f32) * 100.0;
             // Ensure cache hit rate is reasonable (between 0% and 100%)
-            let clamped_hit_rate = hit_rate.min(100.0).max(0.0);
+            let clamped_hit_rate = hit_rate.clamp(0.0, 100.0);
             
             #[cfg(debug_assertions)]
             if hit_rate > 100.0 {
@@ -1348,7 +1332,7 @@ fn collect_test_paths(limit: Option<usize>) -> Vec<String> {
             }
         }
 
-        if let Some(walker) = fs::read_dir(dir).ok() {
+        if let Ok(walker) = fs::read_dir(dir) {
             for entry in walker.filter_map(|e| e.ok()) {
                 let path = entry.path();
                 if let Some(path_str) = path.to_str() {
@@ -2777,7 +2761,7 @@ mod tests_searchengine_state {
         let search_term = "apple";
 
         // Search using the term
-        let search_result = state.search(&search_term);
+        let search_result = state.search(search_term);
         assert!(search_result.is_ok());
 
         let _results = search_result.unwrap();
@@ -2989,7 +2973,7 @@ mod tests_searchengine_state {
         // Get stats after chunked indexing
         let after_stats = state.get_stats();
         assert!(
-            !(after_stats.trie_size == 0),
+            (after_stats.trie_size != 0),
             "Trie should contain indexed paths after chunked indexing"
         );
     }
@@ -3340,7 +3324,7 @@ mod bench_indexing_methods {
     use std::time::Duration;
 
     // Helper function to create a larger test dataset for benchmarking using real test data
-    fn create_benchmark_test_files(base_dir: &PathBuf, file_count: usize) -> Vec<PathBuf> {
+    fn create_benchmark_test_files(base_dir: &Path, file_count: usize) -> Vec<PathBuf> {
         let mut created_files = Vec::new();
 
         // First try to use existing test data
@@ -3461,7 +3445,7 @@ mod bench_indexing_methods {
     // Helper function to measure indexing performance
     fn measure_indexing_performance(
         state: &SearchEngineState,
-        test_dir: &PathBuf,
+        test_dir: &Path,
         method_name: &str,
         chunk_size: Option<usize>,
     ) -> (Duration, bool) {
@@ -3476,11 +3460,11 @@ mod bench_indexing_methods {
         let result = match chunk_size {
             Some(size) => {
                 log_info!("Starting chunked indexing with chunk size {}", size);
-                state.start_chunked_indexing(test_dir.clone(), size)
+                state.start_chunked_indexing(test_dir.to_path_buf(), size)
             }
             None => {
                 log_info!("Starting traditional indexing");
-                state.start_indexing(test_dir.clone())
+                state.start_indexing(test_dir.to_path_buf())
             }
         };
 
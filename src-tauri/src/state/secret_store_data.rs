@@ -0,0 +1,421 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use openssl::pkcs5::scrypt;
+use openssl::rand::rand_bytes;
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+const FORMAT_VERSION: u32 = 1;
+const KEY_LENGTH: usize = 32;
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 12;
+const TAG_LENGTH: usize = 16;
+
+/// The scrypt cost of new keys, about 100 ms and 32 MiB to derive the key so guessing the
+/// passphrase of a stolen file is slow.
+const SCRYPT_N: u64 = 1 << 15;
+const SCRYPT_R: u64 = 8;
+const SCRYPT_P: u64 = 1;
+const SCRYPT_MAX_MEMORY: u64 = 64 * 1024 * 1024;
+
+/// The encrypted file as it is stored on disk. Everything needed to derive the key again is
+/// kept next to the ciphertext, only the master passphrase is missing.
+#[derive(Debug, Serialize, Deserialize)]
+struct EncryptedFile {
+    version: u32,
+    kdf: String,
+    n: u64,
+    r: u64,
+    p: u64,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+    tag: String,
+}
+
+/// Whether the store exists and can be read, the frontend asks for the master passphrase when
+/// it is locked.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SecretStoreStatus {
+    pub initialized: bool,
+    pub unlocked: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SecretStoreError {
+    /// The store has to be unlocked with the master passphrase first.
+    Locked,
+    WrongPassphrase,
+    /// The file is damaged or was written by a newer version.
+    Corrupted(String),
+    Io(String),
+}
+
+impl fmt::Display for SecretStoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecretStoreError::Locked => f.write_str("The secret store is locked"),
+            SecretStoreError::WrongPassphrase => f.write_str("The master passphrase is wrong"),
+            SecretStoreError::Corrupted(message) => {
+                write!(f, "The secret store is damaged: {}", message)
+            }
+            SecretStoreError::Io(message) => f.write_str(message),
+        }
+    }
+}
+
+fn io_error(error: impl ToString) -> SecretStoreError {
+    SecretStoreError::Io(error.to_string())
+}
+
+fn corrupted(error: impl ToString) -> SecretStoreError {
+    SecretStoreError::Corrupted(error.to_string())
+}
+
+/// The scrypt parameters a key was derived with. They are written back unchanged, otherwise
+/// a file created with other parameters could not be decrypted after the next change.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct KdfParams {
+    n: u64,
+    r: u64,
+    p: u64,
+}
+
+impl KdfParams {
+    const DEFAULT: KdfParams = KdfParams {
+        n: SCRYPT_N,
+        r: SCRYPT_R,
+        p: SCRYPT_P,
+    };
+}
+
+struct Unlocked {
+    key: Vec<u8>,
+    salt: Vec<u8>,
+    kdf: KdfParams,
+    secrets: BTreeMap<String, String>,
+}
+
+/// Passwords and key passphrases of the saved connections, encrypted with AES-256-GCM under a
+/// key derived from a master passphrase with scrypt. The secrets are only held in memory while
+/// the store is unlocked and every change rewrites the whole file.
+pub struct SecretStore {
+    path: PathBuf,
+    unlocked: Option<Unlocked>,
+}
+
+impl SecretStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            unlocked: None,
+        }
+    }
+
+    pub fn status(&self) -> SecretStoreStatus {
+        SecretStoreStatus {
+            initialized: self.path.exists(),
+            unlocked: self.unlocked.is_some(),
+        }
+    }
+
+    pub fn is_unlocked(&self) -> bool {
+        self.unlocked.is_some()
+    }
+
+    /// Decrypts the store with the master passphrase. Without a store file a new empty store is
+    /// created, encrypted with this passphrase.
+    pub fn unlock(&mut self, passphrase: &str) -> Result<(), SecretStoreError> {
+        if !self.path.exists() {
+            let salt = random_bytes(SALT_LENGTH)?;
+            self.unlocked = Some(Unlocked {
+                key: derive_key(passphrase, &salt, KdfParams::DEFAULT)?,
+                salt,
+                kdf: KdfParams::DEFAULT,
+                secrets: BTreeMap::new(),
+            });
+            return self.save();
+        }
+
+        let content = fs::read_to_string(&self.path).map_err(io_error)?;
+        let file: EncryptedFile = serde_json::from_str(&content).map_err(corrupted)?;
+        if file.version != FORMAT_VERSION || file.kdf != "scrypt" {
+            return Err(corrupted(format!(
+                "unsupported format {} with {}",
+                file.version, file.kdf
+            )));
+        }
+        let decode = |value: &str| STANDARD.decode(value).map_err(corrupted);
+        let salt = decode(&file.salt)?;
+        let kdf = KdfParams {
+            n: file.n,
+            r: file.r,
+            p: file.p,
+        };
+        let key = derive_key(passphrase, &salt, kdf)?;
+        let plaintext = decrypt_aead(
+            Cipher::aes_256_gcm(),
+            &key,
+            Some(&decode(&file.nonce)?),
+            &[],
+            &decode(&file.ciphertext)?,
+            &decode(&file.tag)?,
+        )
+        // GCM can't tell a wrong key from a modified file, a wrong passphrase is far more likely
+        .map_err(|_| SecretStoreError::WrongPassphrase)?;
+        let secrets = serde_json::from_slice(&plaintext).map_err(corrupted)?;
+        self.unlocked = Some(Unlocked {
+            key,
+            salt,
+            kdf,
+            secrets,
+        });
+        Ok(())
+    }
+
+    /// Forgets the key and the decrypted secrets.
+    pub fn lock(&mut self) {
+        self.unlocked = None;
+    }
+
+    /// Encrypts the store with a new master passphrase, the store has to be unlocked. The new
+    /// key is derived with the current default cost.
+    pub fn change_passphrase(&mut self, passphrase: &str) -> Result<(), SecretStoreError> {
+        let salt = random_bytes(SALT_LENGTH)?;
+        let key = derive_key(passphrase, &salt, KdfParams::DEFAULT)?;
+        let unlocked = self.unlocked.as_mut().ok_or(SecretStoreError::Locked)?;
+        unlocked.key = key;
+        unlocked.salt = salt;
+        unlocked.kdf = KdfParams::DEFAULT;
+        self.save()
+    }
+
+    pub fn get(&self, id: &str) -> Result<Option<String>, SecretStoreError> {
+        let unlocked = self.unlocked.as_ref().ok_or(SecretStoreError::Locked)?;
+        Ok(unlocked.secrets.get(id).cloned())
+    }
+
+    /// Stores a secret, `None` removes it.
+    pub fn set(&mut self, id: &str, secret: Option<String>) -> Result<(), SecretStoreError> {
+        let unlocked = self.unlocked.as_mut().ok_or(SecretStoreError::Locked)?;
+        let changed = match secret {
+            Some(secret) => unlocked.secrets.insert(id.to_string(), secret.clone()) != Some(secret),
+            None => unlocked.secrets.remove(id).is_some(),
+        };
+        if changed {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// Removes the secrets of every id `keep` rejects, e.g. of connections removed while the
+    /// store was locked.
+    pub fn retain(&mut self, keep: impl Fn(&str) -> bool) -> Result<(), SecretStoreError> {
+        let unlocked = self.unlocked.as_mut().ok_or(SecretStoreError::Locked)?;
+        let before = unlocked.secrets.len();
+        unlocked.secrets.retain(|id, _| keep(id));
+        if unlocked.secrets.len() != before {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    fn save(&self) -> Result<(), SecretStoreError> {
+        let unlocked = self.unlocked.as_ref().ok_or(SecretStoreError::Locked)?;
+        let plaintext = serde_json::to_vec(&unlocked.secrets).map_err(io_error)?;
+        // A new nonce for every write, reusing one with the same key breaks GCM
+        let nonce = random_bytes(NONCE_LENGTH)?;
+        let mut tag = [0u8; TAG_LENGTH];
+        let ciphertext = encrypt_aead(
+            Cipher::aes_256_gcm(),
+            &unlocked.key,
+            Some(&nonce),
+            &[],
+            &plaintext,
+            &mut tag,
+        )
+        .map_err(io_error)?;
+
+        let file = EncryptedFile {
+            version: FORMAT_VERSION,
+            kdf: "scrypt".to_string(),
+            n: unlocked.kdf.n,
+            r: unlocked.kdf.r,
+            p: unlocked.kdf.p,
+            salt: STANDARD.encode(&unlocked.salt),
+            nonce: STANDARD.encode(&nonce),
+            ciphertext: STANDARD.encode(&ciphertext),
+            tag: STANDARD.encode(tag),
+        };
+        let content = serde_json::to_string_pretty(&file).map_err(io_error)?;
+        write_private_file(&self.path, content.as_bytes()).map_err(io_error)
+    }
+}
+
+fn random_bytes(length: usize) -> Result<Vec<u8>, SecretStoreError> {
+    let mut bytes = vec![0u8; length];
+    rand_bytes(&mut bytes).map_err(io_error)?;
+    Ok(bytes)
+}
+
+fn derive_key(
+    passphrase: &str,
+    salt: &[u8],
+    kdf: KdfParams,
+) -> Result<Vec<u8>, SecretStoreError> {
+    let mut key = vec![0u8; KEY_LENGTH];
+    scrypt(
+        passphrase.as_bytes(),
+        salt,
+        kdf.n,
+        kdf.r,
+        kdf.p,
+        SCRYPT_MAX_MEMORY,
+        &mut key,
+    )
+    .map_err(corrupted)?;
+    Ok(key)
+}
+
+/// Writes the file through a temporary file and a rename, so a crash never leaves a half
+/// written store behind. Only the owner can read it.
+fn write_private_file(path: &Path, content: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let temp_path = path.with_extension("tmp");
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(&temp_path)?;
+    file.write_all(content)?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(&temp_path, path)
+}
+
+#[cfg(test)]
+mod tests_secret_store_data {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn secrets_survive_lock_and_unlock_test() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("secrets.json");
+        let mut store = SecretStore::new(path.clone());
+        assert_eq!(
+            store.status(),
+            SecretStoreStatus {
+                initialized: false,
+                unlocked: false
+            }
+        );
+        assert_eq!(store.get("a"), Err(SecretStoreError::Locked));
+
+        store.unlock("correct horse").unwrap();
+        store.set("a", Some("hunter2".to_string())).unwrap();
+        store.set("b", Some("passphrase".to_string())).unwrap();
+        store.set("b", None).unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(!content.contains("hunter2"));
+
+        store.lock();
+        assert_eq!(
+            store.status(),
+            SecretStoreStatus {
+                initialized: true,
+                unlocked: false
+            }
+        );
+        assert_eq!(
+            store.unlock("wrong"),
+            Err(SecretStoreError::WrongPassphrase)
+        );
+
+        let mut reopened = SecretStore::new(path);
+        reopened.unlock("correct horse").unwrap();
+        assert_eq!(reopened.get("a").unwrap(), Some("hunter2".to_string()));
+        assert_eq!(reopened.get("b").unwrap(), None);
+    }
+
+    #[test]
+    fn change_passphrase_test() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("secrets.json");
+        let mut store = SecretStore::new(path.clone());
+        store.unlock("old").unwrap();
+        store.set("a", Some("hunter2".to_string())).unwrap();
+        store.change_passphrase("new").unwrap();
+
+        let mut reopened = SecretStore::new(path);
+        assert_eq!(
+            reopened.unlock("old"),
+            Err(SecretStoreError::WrongPassphrase)
+        );
+        reopened.unlock("new").unwrap();
+        assert_eq!(reopened.get("a").unwrap(), Some("hunter2".to_string()));
+        reopened.retain(|id| id != "a").unwrap();
+        assert_eq!(reopened.get("a").unwrap(), None);
+    }
+
+    #[test]
+    fn kdf_params_of_the_file_are_kept_test() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("secrets.json");
+        let kdf = KdfParams { n: 1 << 10, r: 4, p: 2 };
+        let salt = random_bytes(SALT_LENGTH).unwrap();
+        let mut store = SecretStore::new(path.clone());
+        store.unlocked = Some(Unlocked {
+            key: derive_key("pass", &salt, kdf).unwrap(),
+            salt,
+            kdf,
+            secrets: BTreeMap::new(),
+        });
+        store.save().unwrap();
+
+        let mut reopened = SecretStore::new(path.clone());
+        reopened.unlock("pass").unwrap();
+        reopened.set("a", Some("hunter2".to_string())).unwrap();
+        let file: EncryptedFile =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!((file.n, file.r, file.p), (1 << 10, 4, 2));
+
+        let mut reopened = SecretStore::new(path);
+        reopened.unlock("pass").unwrap();
+        assert_eq!(reopened.get("a").unwrap(), Some("hunter2".to_string()));
+    }
+
+    #[test]
+    fn damaged_file_test() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("secrets.json");
+        fs::write(&path, "not json").unwrap();
+        let mut store = SecretStore::new(path);
+        assert!(matches!(
+            store.unlock("any"),
+            Err(SecretStoreError::Corrupted(_))
+        ));
+        assert!(!store.is_unlocked());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn store_is_private_test() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("secrets.json");
+        SecretStore::new(path.clone()).unlock("pass").unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
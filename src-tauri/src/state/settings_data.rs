@@ -206,7 +206,7 @@ impl SettingsState {
         settings: &Settings,
     ) -> Result<serde_json::Map<String, Value>, Error> {
         let settings_value = serde_json::to_value(settings)
-            .map_err(|e| Error::new(io::ErrorKind::Other, e))?;
+            .map_err(Error::other)?;
 
         settings_value.as_object().cloned().ok_or_else(|| {
             Error::new(
@@ -268,7 +268,7 @@ impl SettingsState {
     /// println!("Updated settings: {:?}", result);
     /// ```
     pub fn update_setting_field(&self, key: &str, value: Value) -> Result<Settings, Error> {
-        let mut settings = self.0.lock().map_err(|_| io::Error::new(io::ErrorKind::Other, "Failed to acquire settings lock"))?;
+        let mut settings = self.0.lock().map_err(|_| io::Error::other("Failed to acquire settings lock"))?;
 
         let mut settings_map = Self::settings_to_json_map(&settings)?;
 
@@ -373,9 +373,9 @@ impl SettingsState {
     /// println!("Current theme: {}", theme);
     /// ```
     pub fn get_setting_field(&self, key: &str) -> Result<Value, Error> {
-        let settings = self.0.lock().map_err(|_| io::Error::new(io::ErrorKind::Other, "Failed to acquire settings lock"))?;
+        let settings = self.0.lock().map_err(|_| io::Error::other("Failed to acquire settings lock"))?;
         let settings_value =
-            serde_json::to_value(&*settings).map_err(|e| Error::new(io::ErrorKind::Other, e))?;
+            serde_json::to_value(&*settings).map_err(Error::other)?;
 
         if let Some(obj) = settings_value.as_object() {
             // Handle nested fields with dot notation
@@ -512,7 +512,7 @@ impl SettingsState {
     /// }
     /// ```
     pub fn reset_settings(&self) -> Result<Settings, Error> {
-        let mut settings = self.0.lock().map_err(|_| io::Error::new(io::ErrorKind::Other, "Failed to acquire settings lock"))?;
+        let mut settings = self.0.lock().map_err(|_| io::Error::other("Failed to acquire settings lock"))?;
 
         let default_settings = Settings::default();
         *settings = default_settings.clone();
@@ -540,8 +540,10 @@ impl SettingsState {
     // For testing - allows creating a SettingsState with a custom path
     #[cfg(test)]
     pub fn new_with_path(path: PathBuf) -> Self {
-        let mut defaults = Settings::default();
-        defaults.abs_file_path_buf = path;
+        let defaults = Settings {
+            abs_file_path_buf: path,
+            ..Settings::default()
+        };
         Self(Arc::new(Mutex::new(
             Self::write_settings_to_file_and_save_in_state(defaults),
         )))
@@ -570,7 +572,7 @@ impl SettingsState {
     fn write_settings_to_file(&self, settings: &Settings) -> io::Result<()> {
         let user_config_file_path = &settings.abs_file_path_buf;
         let serialized = serde_json::to_string_pretty(&settings)
-            .map_err(|e| Error::new(io::ErrorKind::Other, e))?;
+            .map_err(Error::other)?;
 
         // Makes sure the parent directory exists
         if let Some(parent) = user_config_file_path.parent() {
@@ -674,7 +676,7 @@ mod tests_settings {
     #[test]
     fn test_default_settings() {
         let settings = Settings::default();
-        assert_eq!(settings.darkmode, false);
+        assert!(!settings.darkmode);
         //assert_eq!(settings.custom_themes, vec![]);
         assert_eq!(settings.default_theme, "".to_string());
         //assert_eq!(settings.default_themes_path, Default::default());
@@ -714,7 +716,7 @@ mod tests_settings {
         assert!(read_result.is_ok(), "Should be able to read settings file");
 
         let settings = read_result.unwrap();
-        assert_eq!(settings.darkmode, false);
+        assert!(!settings.darkmode);
         assert_eq!(settings.default_theme, "".to_string());
         //assert_eq!(settings.default_themes_path, Default::default());
         //assert_eq!(settings.default_folder_path_on_opening, Default::default());
@@ -747,7 +749,7 @@ mod tests_settings {
         );
 
         let loaded_settings = loaded.unwrap();
-        assert_eq!(loaded_settings.darkmode, true);
+        assert!(loaded_settings.darkmode);
         assert_eq!(loaded_settings.default_theme, "solarized");
     }
 
@@ -764,8 +766,10 @@ mod tests_settings {
         let test_path = temp_dir.path().join("settings.json");
 
         // Create a custom metadata object
-        let mut settings = Settings::default();
-        settings.abs_file_path_buf = test_path.clone();
+        let mut settings = Settings {
+            abs_file_path_buf: test_path.clone(),
+            ..Settings::default()
+        };
         settings.backend_settings.logging_config.logging_level = LoggingLevel::Partial;
         settings.default_folder_path_on_opening = PathBuf::from("temp_dir");
 
@@ -799,7 +803,7 @@ mod tests_settings {
 
         let result = state.update_setting_field("darkmode", json!(true));
         assert!(result.is_ok());
-        assert_eq!(result.unwrap().darkmode, true);
+        assert!(result.unwrap().darkmode);
     }
 
     /// Tests updating the default_theme setting field.
@@ -1004,7 +1008,7 @@ mod tests_settings {
         assert!(result.is_ok());
 
         let updated = result.unwrap();
-        assert_eq!(updated.darkmode, true);
+        assert!(updated.darkmode);
         assert_eq!(updated.default_theme, "gruvbox");
     }
 
@@ -0,0 +1,445 @@
+use base64::Engine;
+use home::home_dir;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use ssh2::{CheckResult, KeyboardInteractivePrompt, KnownHostFileKind, Prompt, Session};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Mutex};
+use std::time::Duration;
+
+/// How long a keyboard-interactive prompt waits for the user before the login is given up.
+const AUTH_PROMPT_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// A host key which is not (or differently) listed in the known_hosts file.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HostKeyInfo {
+    pub host: String,
+    pub port: u16,
+    /// The algorithm as it is written to known_hosts, e.g. `ssh-ed25519`.
+    pub key_type: String,
+    /// The fingerprint like `ssh-keygen -l` shows it, e.g. `SHA256:uNiVztksCsDhcc0u9e8BujQXVUpKZIDTMczCvj3tD2s`.
+    pub fingerprint: String,
+    #[serde(skip)]
+    pub key: Vec<u8>,
+}
+
+impl HostKeyInfo {
+    pub fn new(host: &str, port: u16, key: &[u8]) -> Self {
+        Self {
+            host: host.to_string(),
+            port,
+            key_type: key_type_of(key).unwrap_or_else(|| "unknown".to_string()),
+            fingerprint: format!(
+                "SHA256:{}",
+                base64::engine::general_purpose::STANDARD_NO_PAD.encode(Sha256::digest(key))
+            ),
+            key: key.to_vec(),
+        }
+    }
+
+    /// The line for the known_hosts file, OpenSSH writes `[host]:port` for ports other than 22.
+    fn known_hosts_line(&self) -> String {
+        let host = if self.port == 22 {
+            self.host.clone()
+        } else {
+            format!("[{}]:{}", self.host, self.port)
+        };
+        format!(
+            "{} {} {}\n",
+            host,
+            self.key_type,
+            base64::engine::general_purpose::STANDARD.encode(&self.key)
+        )
+    }
+}
+
+/// The algorithm name at the start of an SSH public key blob.
+fn key_type_of(key: &[u8]) -> Option<String> {
+    let length = u32::from_be_bytes(key.get(..4)?.try_into().ok()?) as usize;
+    let name = key.get(4..4 + length)?;
+    String::from_utf8(name.to_vec()).ok()
+}
+
+/// The result of comparing a host key with the known_hosts file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HostKeyCheck {
+    Trusted,
+    Unknown(HostKeyInfo),
+    Changed(HostKeyInfo),
+}
+
+/// One question of a keyboard-interactive login, e.g. `Password:` or `Verification code:`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AuthPromptField {
+    pub text: String,
+    /// Whether the answer may be shown while typing, false for passwords and codes.
+    pub echo: bool,
+}
+
+/// A keyboard-interactive challenge of the server waiting for the answers of the user.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SftpAuthPrompt {
+    pub id: u64,
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub instructions: String,
+    pub fields: Vec<AuthPromptField>,
+}
+
+struct PendingAuthPrompt {
+    prompt: SftpAuthPrompt,
+    responder: mpsc::Sender<Option<Vec<String>>>,
+}
+
+#[derive(Default)]
+struct PendingQuestions {
+    next_prompt_id: u64,
+    prompts: HashMap<u64, PendingAuthPrompt>,
+    unknown_host_keys: HashMap<(String, u16), HostKeyInfo>,
+}
+
+/// Shared by all SFTP connections: the known_hosts file the host keys are checked against and
+/// the questions a connection attempt waits for the user to answer. Connecting threads block
+/// on a prompt while the frontend polls for it and answers it with another command.
+pub struct SftpAuthContext {
+    known_hosts_path: Option<PathBuf>,
+    pending: Mutex<PendingQuestions>,
+}
+
+impl SftpAuthContext {
+    pub fn new(known_hosts_path: Option<PathBuf>) -> Self {
+        Self {
+            known_hosts_path,
+            pending: Mutex::new(PendingQuestions {
+                next_prompt_id: 1,
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// `~/.ssh/known_hosts`, shared with OpenSSH.
+    pub fn default_known_hosts_path() -> Option<PathBuf> {
+        home_dir().map(|home| home.join(".ssh").join("known_hosts"))
+    }
+
+    /// Compares a host key with the known_hosts file. An unknown key is remembered until it
+    /// is trusted with [`SftpAuthContext::trust_host_key`].
+    pub fn check_host_key(
+        &self,
+        host: &str,
+        port: u16,
+        key: &[u8],
+    ) -> Result<HostKeyCheck, String> {
+        let session = Session::new().map_err(|_| "Could not initialize session".to_string())?;
+        let mut known_hosts = session.known_hosts().map_err(|e| e.to_string())?;
+        if let Some(path) = self
+            .known_hosts_path
+            .as_deref()
+            .filter(|path| path.exists())
+        {
+            known_hosts
+                .read_file(path, KnownHostFileKind::OpenSSH)
+                .map_err(|e| format!("Failed to read '{}': {}", path.display(), e))?;
+        }
+
+        let info = HostKeyInfo::new(host, port, key);
+        match known_hosts.check_port(host, port, key) {
+            CheckResult::Match => Ok(HostKeyCheck::Trusted),
+            CheckResult::Mismatch => Ok(HostKeyCheck::Changed(info)),
+            CheckResult::NotFound => {
+                if let Ok(mut pending) = self.pending.lock() {
+                    pending
+                        .unknown_host_keys
+                        .insert((host.to_string(), port), info.clone());
+                }
+                Ok(HostKeyCheck::Unknown(info))
+            }
+            CheckResult::Failure => Err("Failed to check the host key".to_string()),
+        }
+    }
+
+    /// Adds the unknown key of a host seen by the last connection attempt to the known_hosts
+    /// file. The fingerprint has to match, so only the key the user was shown is trusted.
+    pub fn trust_host_key(
+        &self,
+        host: &str,
+        port: u16,
+        fingerprint: &str,
+    ) -> Result<HostKeyInfo, TrustError> {
+        let path = self
+            .known_hosts_path
+            .as_deref()
+            .ok_or(TrustError::NoKnownHostsFile)?;
+        let mut pending = self
+            .pending
+            .lock()
+            .map_err(|_| TrustError::Io("Failed to acquire lock".to_string()))?;
+        let info = pending
+            .unknown_host_keys
+            .get(&(host.to_string(), port))
+            .ok_or(TrustError::NotPending)?;
+        if info.fingerprint != fingerprint {
+            return Err(TrustError::FingerprintMismatch);
+        }
+
+        append_known_host(path, &info.known_hosts_line()).map_err(TrustError::Io)?;
+        Ok(pending
+            .unknown_host_keys
+            .remove(&(host.to_string(), port))
+            .unwrap())
+    }
+
+    /// Shows a keyboard-interactive challenge to the user and waits for the answers.
+    /// Returns `None` if the user cancelled or did not answer in time.
+    pub fn ask(&self, mut prompt: SftpAuthPrompt) -> Option<Vec<String>> {
+        let (responder, answers) = mpsc::channel();
+        let id = {
+            let mut pending = self.pending.lock().ok()?;
+            let id = pending.next_prompt_id;
+            pending.next_prompt_id += 1;
+            prompt.id = id;
+            pending
+                .prompts
+                .insert(id, PendingAuthPrompt { prompt, responder });
+            id
+        };
+
+        let answer = answers.recv_timeout(AUTH_PROMPT_TIMEOUT).ok().flatten();
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.prompts.remove(&id);
+        }
+        answer
+    }
+
+    /// The challenges waiting for an answer, ordered by id.
+    pub fn pending_prompts(&self) -> Vec<SftpAuthPrompt> {
+        let Ok(pending) = self.pending.lock() else {
+            return Vec::new();
+        };
+        let mut prompts: Vec<_> = pending
+            .prompts
+            .values()
+            .map(|pending| pending.prompt.clone())
+            .collect();
+        prompts.sort_by_key(|prompt| prompt.id);
+        prompts
+    }
+
+    /// Hands the answers (or `None` to cancel) to the waiting connection attempt.
+    /// Returns false if there is no such prompt.
+    pub fn answer(&self, id: u64, responses: Option<Vec<String>>) -> bool {
+        let Ok(mut pending) = self.pending.lock() else {
+            return false;
+        };
+        match pending.prompts.remove(&id) {
+            Some(prompt) => prompt.responder.send(responses).is_ok(),
+            None => false,
+        }
+    }
+}
+
+/// Why a host key could not be trusted.
+#[derive(Debug, PartialEq)]
+pub enum TrustError {
+    NoKnownHostsFile,
+    NotPending,
+    FingerprintMismatch,
+    Io(String),
+}
+
+fn append_known_host(path: &Path, line: &str) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        if !parent.exists() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                let _ = fs::set_permissions(parent, fs::Permissions::from_mode(0o700));
+            }
+        }
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Failed to open '{}': {}", path.display(), e))?;
+    // Don't glue the entry to a last line without a line break
+    let needs_newline = fs::read(path)
+        .map(|content| content.last().is_some_and(|last| *last != b'\n'))
+        .unwrap_or(false);
+    if needs_newline {
+        file.write_all(b"\n").map_err(|e| e.to_string())?;
+    }
+    file.write_all(line.as_bytes())
+        .map_err(|e| format!("Failed to write '{}': {}", path.display(), e))
+}
+
+/// Relays the keyboard-interactive challenges of a login to the frontend.
+pub struct RelayedPrompter<'a> {
+    pub context: &'a SftpAuthContext,
+    pub host: String,
+    pub port: u16,
+    pub cancelled: bool,
+}
+
+impl KeyboardInteractivePrompt for RelayedPrompter<'_> {
+    fn prompt<'b>(
+        &mut self,
+        username: &str,
+        instructions: &str,
+        prompts: &[Prompt<'b>],
+    ) -> Vec<String> {
+        // Servers may send an empty round, e.g. to show a banner
+        if prompts.is_empty() || self.cancelled {
+            return vec![String::new(); prompts.len()];
+        }
+
+        let answers = self.context.ask(SftpAuthPrompt {
+            id: 0,
+            host: self.host.clone(),
+            port: self.port,
+            username: username.to_string(),
+            instructions: instructions.to_string(),
+            fields: prompts
+                .iter()
+                .map(|prompt| AuthPromptField {
+                    text: prompt.text.to_string(),
+                    echo: prompt.echo,
+                })
+                .collect(),
+        });
+        match answers {
+            Some(mut answers) => {
+                answers.resize(prompts.len(), String::new());
+                answers
+            }
+            None => {
+                self.cancelled = true;
+                vec![String::new(); prompts.len()]
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_sftp_auth_data {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    fn ed25519_key(seed: u8) -> Vec<u8> {
+        let mut key = Vec::new();
+        key.extend_from_slice(&11u32.to_be_bytes());
+        key.extend_from_slice(b"ssh-ed25519");
+        key.extend_from_slice(&32u32.to_be_bytes());
+        key.extend_from_slice(&[seed; 32]);
+        key
+    }
+
+    #[test]
+    fn host_key_trust_on_first_use_test() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let known_hosts = temp_dir.path().join(".ssh").join("known_hosts");
+        let context = SftpAuthContext::new(Some(known_hosts.clone()));
+        let key = ed25519_key(1);
+
+        let HostKeyCheck::Unknown(info) =
+            context.check_host_key("example.com", 2222, &key).unwrap()
+        else {
+            panic!("The key should be unknown");
+        };
+        assert_eq!(info.key_type, "ssh-ed25519");
+        assert!(info.fingerprint.starts_with("SHA256:"));
+        assert_eq!(
+            context.trust_host_key("example.com", 2222, "SHA256:wrong"),
+            Err(TrustError::FingerprintMismatch)
+        );
+        assert_eq!(
+            context.trust_host_key("example.com", 22, &info.fingerprint),
+            Err(TrustError::NotPending)
+        );
+
+        context
+            .trust_host_key("example.com", 2222, &info.fingerprint)
+            .unwrap();
+        let content = fs::read_to_string(&known_hosts).unwrap();
+        assert!(content.starts_with("[example.com]:2222 ssh-ed25519 "));
+        assert_eq!(
+            context.check_host_key("example.com", 2222, &key).unwrap(),
+            HostKeyCheck::Trusted
+        );
+        assert!(matches!(
+            context
+                .check_host_key("example.com", 2222, &ed25519_key(2))
+                .unwrap(),
+            HostKeyCheck::Changed(_)
+        ));
+        // Trusted once, the key is no longer pending
+        assert_eq!(
+            context.trust_host_key("example.com", 2222, &info.fingerprint),
+            Err(TrustError::NotPending)
+        );
+    }
+
+    #[test]
+    fn keyboard_interactive_prompts_are_relayed_test() {
+        let context = Arc::new(SftpAuthContext::new(None));
+        let asking = {
+            let context = context.clone();
+            thread::spawn(move || {
+                let mut prompter = RelayedPrompter {
+                    context: &context,
+                    host: "example.com".to_string(),
+                    port: 22,
+                    cancelled: false,
+                };
+                let prompts = [
+                    Prompt {
+                        text: "Password: ".into(),
+                        echo: false,
+                    },
+                    Prompt {
+                        text: "Verification code: ".into(),
+                        echo: false,
+                    },
+                ];
+                let first = prompter.prompt("alice", "", &prompts);
+                let second = prompter.prompt("alice", "", &prompts);
+                (first, second, prompter.cancelled)
+            })
+        };
+
+        let mut answered = 0;
+        for _ in 0..500 {
+            if let Some(prompt) = context.pending_prompts().first() {
+                assert_eq!(prompt.fields.len(), 2);
+                assert_eq!(prompt.fields[1].text, "Verification code: ");
+                if answered == 0 {
+                    assert!(context.answer(
+                        prompt.id,
+                        Some(vec!["secret".to_string(), "123456".to_string()])
+                    ));
+                } else {
+                    assert!(context.answer(prompt.id, None));
+                }
+                assert!(!context.answer(prompt.id, None));
+                answered += 1;
+                if answered == 2 {
+                    break;
+                }
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let (first, second, cancelled) = asking.join().unwrap();
+        assert_eq!(first, vec!["secret", "123456"]);
+        assert_eq!(second, vec!["", ""]);
+        assert!(cancelled);
+        assert!(context.pending_prompts().is_empty());
+    }
+}
@@ -0,0 +1,1044 @@
+use crate::error_handling::{Error, ErrorCode};
+use crate::models::ssh_config::SshJumpHost;
+use crate::state::sftp_auth_data::{HostKeyCheck, HostKeyInfo, RelayedPrompter, SftpAuthContext};
+use serde::Deserialize;
+use serde_json::json;
+use ssh2::{Channel, Session, Sftp};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often idle sessions send a keepalive, servers often drop connections idle for a few minutes.
+pub const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How long connecting and a single SFTP request may take before the connection is considered dead.
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// How long a command run with [`SftpSession::exec`] may take, e.g. hashing a large file.
+const EXEC_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// How a session authenticates. The secrets are kept in the backend to reconnect and are
+/// never sent back to the frontend.
+#[derive(Clone, Deserialize)]
+#[serde(tag = "method")]
+pub enum SftpCredentials {
+    Password {
+        password: String,
+    },
+    /// A private key file like `~/.ssh/id_ed25519`, the passphrase is needed for encrypted keys.
+    PublicKey {
+        private_key_path: PathBuf,
+        passphrase: Option<String>,
+    },
+    /// Every identity of the running ssh-agent (`SSH_AUTH_SOCK`, Pageant on Windows) is tried.
+    Agent,
+    /// The challenges of the server, e.g. a password followed by a 2FA code, are relayed to the
+    /// frontend, see [`SftpAuthContext::ask`].
+    KeyboardInteractive,
+}
+
+impl fmt::Debug for SftpCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SftpCredentials::Password { .. } => f.write_str("Password(***)"),
+            SftpCredentials::PublicKey {
+                private_key_path, ..
+            } => write!(f, "PublicKey({})", private_key_path.display()),
+            SftpCredentials::Agent => f.write_str("Agent"),
+            SftpCredentials::KeyboardInteractive => f.write_str("KeyboardInteractive"),
+        }
+    }
+}
+
+/// Everything needed to (re)open the connection of a session.
+#[derive(Debug, Clone)]
+pub struct SftpConnectionConfig {
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub credentials: SftpCredentials,
+    /// The jump hosts to tunnel through, like `ProxyJump` of `~/.ssh/config`. Empty connects
+    /// directly.
+    pub proxy_jump: Vec<SshJumpHost>,
+}
+
+/// Why a connection could not be opened.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SftpConnectError {
+    Connection(String),
+    /// The host is not in the known_hosts file yet, the user has to trust the key first.
+    UnknownHostKey(HostKeyInfo),
+    /// The host presented a different key than the known_hosts file lists.
+    HostKeyChanged(HostKeyInfo),
+    Authentication(String),
+}
+
+impl SftpConnectError {
+    pub fn to_error(&self) -> Error {
+        match self {
+            SftpConnectError::Connection(message) => {
+                Error::new(ErrorCode::InternalError, message.clone())
+            }
+            SftpConnectError::UnknownHostKey(host_key) => Error::new(
+                ErrorCode::Unauthorized,
+                format!(
+                    "The authenticity of host '{}' can't be established, {} key fingerprint is {}",
+                    host_key.host, host_key.key_type, host_key.fingerprint
+                ),
+            )
+            .with_details(json!({ "reason": "UnknownHostKey", "host_key": host_key })),
+            SftpConnectError::HostKeyChanged(host_key) => Error::new(
+                ErrorCode::Unauthorized,
+                format!(
+                    "The host key of '{}' has changed to {}, someone could be intercepting the connection",
+                    host_key.host, host_key.fingerprint
+                ),
+            )
+            .with_details(json!({ "reason": "HostKeyChanged", "host_key": host_key })),
+            SftpConnectError::Authentication(message) => {
+                Error::new(ErrorCode::Unauthorized, message.clone())
+                    .with_details(json!({ "reason": "AuthenticationFailed" }))
+            }
+        }
+    }
+}
+
+impl fmt::Display for SftpConnectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_error().to_json())
+    }
+}
+
+fn connection_error(error: impl ToString) -> SftpConnectError {
+    SftpConnectError::Connection(error.to_string())
+}
+
+/// An authenticated SSH connection with its SFTP channel.
+pub struct SftpConnection {
+    session: Session,
+    sftp: Sftp,
+}
+
+impl SftpConnection {
+    /// Connects, verifies the host key, authenticates and opens the SFTP channel. With jump
+    /// hosts every hop is verified and authenticated the same way and the next hop is reached
+    /// through a `direct-tcpip` channel of the previous one.
+    pub fn open(
+        config: &SftpConnectionConfig,
+        auth: &SftpAuthContext,
+    ) -> Result<Self, SftpConnectError> {
+        let (first_host, first_port) = config
+            .proxy_jump
+            .first()
+            .map(|jump| (jump.host.as_str(), jump.port))
+            .unwrap_or((config.host.as_str(), config.port));
+        let mut stream = connect_tcp(first_host, first_port)?;
+
+        for (index, jump) in config.proxy_jump.iter().enumerate() {
+            let session = login(
+                stream,
+                &jump.host,
+                jump.port,
+                &jump.username,
+                &jump_credentials(jump),
+                auth,
+            )?;
+            let (next_host, next_port) = config
+                .proxy_jump
+                .get(index + 1)
+                .map(|next| (next.host.as_str(), next.port))
+                .unwrap_or((config.host.as_str(), config.port));
+            stream = tunnel(session, next_host, next_port)?;
+        }
+
+        let session = login(
+            stream,
+            &config.host,
+            config.port,
+            &config.username,
+            std::slice::from_ref(&config.credentials),
+            auth,
+        )?;
+        session.set_keepalive(true, KEEPALIVE_INTERVAL.as_secs() as u32);
+        let sftp = session.sftp().map_err(connection_error)?;
+        Ok(Self { session, sftp })
+    }
+
+    /// Whether the server still answers, a cheap request on the SFTP channel.
+    fn is_alive(&self) -> bool {
+        self.sftp.realpath(".".as_ref()).is_ok()
+    }
+}
+
+/// Resolves a leading `~` like a shell does, key paths are usually given as `~/.ssh/id_ed25519`.
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), home::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+fn connect_tcp(host: &str, port: u16) -> Result<TcpStream, SftpConnectError> {
+    let address = (host, port)
+        .to_socket_addrs()
+        .map_err(connection_error)?
+        .next()
+        .ok_or_else(|| connection_error(format!("Could not resolve host '{}'", host)))?;
+    TcpStream::connect_timeout(&address, CONNECTION_TIMEOUT).map_err(connection_error)
+}
+
+/// Runs the SSH handshake on a stream, verifies the host key and tries the credentials in order
+/// until one is accepted.
+fn login(
+    stream: TcpStream,
+    host: &str,
+    port: u16,
+    username: &str,
+    credentials: &[SftpCredentials],
+    auth: &SftpAuthContext,
+) -> Result<Session, SftpConnectError> {
+    let mut session =
+        Session::new().map_err(|_| connection_error("Could not initialize session"))?;
+    session.set_tcp_stream(stream);
+    session.set_timeout(CONNECTION_TIMEOUT.as_millis() as u32);
+    session.handshake().map_err(connection_error)?;
+
+    let (key, _) = session
+        .host_key()
+        .ok_or_else(|| connection_error("The server sent no host key"))?;
+    match auth
+        .check_host_key(host, port, key)
+        .map_err(connection_error)?
+    {
+        HostKeyCheck::Trusted => {}
+        HostKeyCheck::Unknown(info) => return Err(SftpConnectError::UnknownHostKey(info)),
+        HostKeyCheck::Changed(info) => return Err(SftpConnectError::HostKeyChanged(info)),
+    }
+
+    let mut result = Err(SftpConnectError::Authentication(
+        "Authentication failed".to_string(),
+    ));
+    for credentials in credentials {
+        result = authenticate(&session, host, port, username, credentials, auth);
+        if result.is_ok() && session.authenticated() {
+            return Ok(session);
+        }
+    }
+    match result {
+        Err(SftpConnectError::Authentication(message)) => Err(SftpConnectError::Authentication(
+            format!("{}@{}: {}", username, host, message),
+        )),
+        Err(e) => Err(e),
+        Ok(()) => Err(SftpConnectError::Authentication(
+            "Authentication failed".to_string(),
+        )),
+    }
+}
+
+/// Jump hosts use the SSH agent like OpenSSH does and fall back to their `IdentityFile`.
+fn jump_credentials(jump: &SshJumpHost) -> Vec<SftpCredentials> {
+    let mut credentials = vec![SftpCredentials::Agent];
+    if let Some(identity_file) = &jump.identity_file {
+        credentials.push(SftpCredentials::PublicKey {
+            private_key_path: identity_file.clone(),
+            passphrase: None,
+        });
+    }
+    credentials
+}
+
+fn authenticate(
+    session: &Session,
+    host: &str,
+    port: u16,
+    username: &str,
+    credentials: &SftpCredentials,
+    auth: &SftpAuthContext,
+) -> Result<(), SftpConnectError> {
+    let failed = |e: ssh2::Error| SftpConnectError::Authentication(e.message().to_string());
+    match credentials {
+        SftpCredentials::Password { password } => session
+            .userauth_password(username, password)
+            .map_err(failed),
+        SftpCredentials::PublicKey {
+            private_key_path,
+            passphrase,
+        } => session
+            .userauth_pubkey_file(
+                username,
+                None,
+                &expand_home(private_key_path),
+                passphrase.as_deref(),
+            )
+            .map_err(failed),
+        SftpCredentials::Agent => {
+            let mut agent = session.agent().map_err(failed)?;
+            agent.connect().map_err(failed)?;
+            agent.list_identities().map_err(failed)?;
+            let identities = agent.identities().map_err(failed)?;
+            let accepted = identities
+                .iter()
+                .any(|identity| agent.userauth(username, identity).is_ok());
+            let _ = agent.disconnect();
+            if accepted {
+                Ok(())
+            } else {
+                Err(SftpConnectError::Authentication(
+                    "No identity of the SSH agent was accepted".to_string(),
+                ))
+            }
+        }
+        SftpCredentials::KeyboardInteractive => {
+            let mut prompter = RelayedPrompter {
+                context: auth,
+                host: host.to_string(),
+                port,
+                cancelled: false,
+            };
+            let result = session.userauth_keyboard_interactive(username, &mut prompter);
+            if prompter.cancelled {
+                return Err(SftpConnectError::Authentication(
+                    "The login was cancelled".to_string(),
+                ));
+            }
+            result.map_err(failed)
+        }
+    }
+}
+
+/// Opens a `direct-tcpip` channel from a jump host to the next hop and returns a local socket
+/// connected to it, so the next SSH session can run on it like on a plain TCP connection. A
+/// thread copies the data between the socket and the channel until either side is closed, it
+/// owns the jump host session and disconnects it at the end.
+fn tunnel(session: Session, host: &str, port: u16) -> Result<TcpStream, SftpConnectError> {
+    let channel = session
+        .channel_direct_tcpip(host, port, None)
+        .map_err(|e| {
+            connection_error(format!(
+                "The jump host could not connect to {}:{}: {}",
+                host, port, e
+            ))
+        })?;
+
+    let listener = TcpListener::bind(("127.0.0.1", 0)).map_err(connection_error)?;
+    let client = TcpStream::connect(listener.local_addr().map_err(connection_error)?)
+        .map_err(connection_error)?;
+    let client_address = client.local_addr().map_err(connection_error)?;
+    // Only accept our own connection, another local process could have connected in between
+    let forwarded = loop {
+        let (stream, peer) = listener.accept().map_err(connection_error)?;
+        if peer == client_address {
+            break stream;
+        }
+    };
+    forwarded.set_nonblocking(true).map_err(connection_error)?;
+
+    session.set_keepalive(true, KEEPALIVE_INTERVAL.as_secs() as u32);
+    session.set_blocking(false);
+    thread::Builder::new()
+        .name("sftp-jump-thread".to_string())
+        .spawn(move || forward(session, channel, forwarded))
+        .map_err(connection_error)?;
+    Ok(client)
+}
+
+/// Copies data between the local socket and the channel of a jump host, both are non-blocking.
+fn forward(session: Session, mut channel: Channel, mut socket: TcpStream) {
+    let mut to_channel: Vec<u8> = Vec::new();
+    let mut to_socket: Vec<u8> = Vec::new();
+    let mut buffer = vec![0u8; 32 * 1024];
+    let mut last_keepalive = Instant::now();
+    let would_block = |e: &io::Error| e.kind() == io::ErrorKind::WouldBlock;
+
+    loop {
+        let mut progress = false;
+        if to_channel.is_empty() {
+            match socket.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(read) => {
+                    to_channel.extend_from_slice(&buffer[..read]);
+                    progress = true;
+                }
+                Err(e) if would_block(&e) => {}
+                Err(_) => break,
+            }
+        }
+        if !to_channel.is_empty() {
+            match channel.write(&to_channel) {
+                Ok(written) => {
+                    to_channel.drain(..written);
+                    progress |= written > 0;
+                }
+                Err(e) if would_block(&e) => {}
+                Err(_) => break,
+            }
+        }
+        if to_socket.is_empty() {
+            match channel.read(&mut buffer) {
+                Ok(0) if channel.eof() => break,
+                Ok(read) => {
+                    to_socket.extend_from_slice(&buffer[..read]);
+                    progress |= read > 0;
+                }
+                Err(e) if would_block(&e) => {}
+                Err(_) => break,
+            }
+        }
+        if !to_socket.is_empty() {
+            match socket.write(&to_socket) {
+                Ok(written) => {
+                    to_socket.drain(..written);
+                    progress |= written > 0;
+                }
+                Err(e) if would_block(&e) => {}
+                Err(_) => break,
+            }
+        }
+
+        if !progress {
+            // The hop is idle while the session on top of it is idle, keep it alive as well
+            if last_keepalive.elapsed() >= KEEPALIVE_INTERVAL {
+                let _ = session.keepalive_send();
+                last_keepalive = Instant::now();
+            }
+            thread::sleep(Duration::from_millis(2));
+        }
+    }
+
+    let _ = socket.shutdown(Shutdown::Both);
+    let _ = channel.close();
+    let _ = session.disconnect(None, "Tunnel closed", None);
+}
+
+const SSH_FXP_INIT: u8 = 1;
+const SSH_FXP_VERSION: u8 = 2;
+const SSH_FXP_OPEN: u8 = 3;
+const SSH_FXP_CLOSE: u8 = 4;
+const SSH_FXP_STATUS: u8 = 101;
+const SSH_FXP_HANDLE: u8 = 102;
+const SSH_FXP_EXTENDED: u8 = 200;
+const SSH_FXF_READ: u32 = 0x01;
+const SSH_FXF_WRITE: u32 = 0x02;
+const SSH_FXF_CREAT: u32 = 0x08;
+const SSH_FXF_TRUNC: u32 = 0x10;
+const SSH_FILEXFER_ATTR_PERMISSIONS: u32 = 0x04;
+
+/// Responses to the few requests of [`SftpCopyDataChannel`] are small, anything larger is garbage.
+const MAX_PACKET_SIZE: usize = 256 * 1024;
+
+fn put_u32(buffer: &mut Vec<u8>, value: u32) {
+    buffer.extend_from_slice(&value.to_be_bytes());
+}
+
+fn put_u64(buffer: &mut Vec<u8>, value: u64) {
+    buffer.extend_from_slice(&value.to_be_bytes());
+}
+
+fn put_string(buffer: &mut Vec<u8>, value: &[u8]) {
+    put_u32(buffer, value.len() as u32);
+    buffer.extend_from_slice(value);
+}
+
+/// Reads the fields of an SFTP packet in order.
+struct PacketReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> PacketReader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], String> {
+        if self.data.len() < length {
+            return Err("Truncated SFTP packet".to_string());
+        }
+        let (field, rest) = self.data.split_at(length);
+        self.data = rest;
+        Ok(field)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn string(&mut self) -> Result<&'a [u8], String> {
+        let length = self.u32()? as usize;
+        self.take(length)
+    }
+}
+
+/// Reads the extension names from the payload of `SSH_FXP_VERSION`.
+fn version_extensions(payload: &[u8]) -> Result<Vec<String>, String> {
+    let mut reader = PacketReader { data: payload };
+    reader.u32()?;
+    let mut extensions = Vec::new();
+    while !reader.data.is_empty() {
+        extensions.push(String::from_utf8_lossy(reader.string()?).to_string());
+        reader.string()?;
+    }
+    Ok(extensions)
+}
+
+/// Turns an `SSH_FXP_STATUS` response into a result, status 0 is success.
+fn status_result(kind: u8, body: &[u8], path: &str) -> Result<(), String> {
+    if kind != SSH_FXP_STATUS {
+        return Err(format!("Unexpected SFTP response {} for {}", kind, path));
+    }
+    let mut reader = PacketReader { data: body };
+    let code = reader.u32()?;
+    if code == 0 {
+        return Ok(());
+    }
+    let message = reader
+        .string()
+        .map(|message| String::from_utf8_lossy(message).to_string())
+        .unwrap_or_default();
+    if message.is_empty() {
+        Err(format!("{}: SFTP error {}", path, code))
+    } else {
+        Err(format!("{}: {}", path, message))
+    }
+}
+
+/// A second SFTP channel of a connection, speaking just enough of the protocol to use the
+/// `copy-data` extension of OpenSSH 9.0 and later, which libssh2 has no API for. The server
+/// copies the file itself, the data never goes through the client.
+pub struct SftpCopyDataChannel {
+    session: Session,
+    channel: Channel,
+    next_id: u32,
+}
+
+impl SftpCopyDataChannel {
+    /// Opens the channel, `None` if the server does not support `copy-data`.
+    pub fn open(session: &Session) -> Result<Option<Self>, String> {
+        let Ok(mut channel) = session.channel_session() else {
+            return Ok(None);
+        };
+        if channel.subsystem("sftp").is_err() {
+            return Ok(None);
+        }
+        let mut client = Self {
+            session: session.clone(),
+            channel,
+            next_id: 1,
+        };
+        let mut version = Vec::new();
+        put_u32(&mut version, 3);
+        client.send(SSH_FXP_INIT, &version)?;
+        let (kind, payload) = client.receive()?;
+        if kind != SSH_FXP_VERSION {
+            return Err(format!("Unexpected SFTP response {} to the version", kind));
+        }
+        if !version_extensions(&payload)?.iter().any(|name| name == "copy-data") {
+            return Ok(None);
+        }
+        Ok(Some(client))
+    }
+
+    /// Copies a file on the server, the destination is created or truncated.
+    pub fn copy_file(
+        &mut self,
+        source: &str,
+        destination: &str,
+        permissions: Option<u32>,
+    ) -> Result<(), String> {
+        let read_handle = self.open_file(source, SSH_FXF_READ, None)?;
+        let write_handle = match self.open_file(
+            destination,
+            SSH_FXF_WRITE | SSH_FXF_CREAT | SSH_FXF_TRUNC,
+            permissions,
+        ) {
+            Ok(handle) => handle,
+            Err(err) => {
+                let _ = self.close_handle(&read_handle, source);
+                return Err(err);
+            }
+        };
+
+        // A length of 0 copies until the end of the source
+        let mut request = Vec::new();
+        put_string(&mut request, b"copy-data");
+        put_string(&mut request, &read_handle);
+        put_u64(&mut request, 0);
+        put_u64(&mut request, 0);
+        put_string(&mut request, &write_handle);
+        put_u64(&mut request, 0);
+        // The server answers once the whole file is copied
+        self.session.set_timeout(EXEC_TIMEOUT.as_millis() as u32);
+        let copied = self
+            .request(SSH_FXP_EXTENDED, &request)
+            .and_then(|(kind, body)| status_result(kind, &body, destination));
+        self.session
+            .set_timeout(CONNECTION_TIMEOUT.as_millis() as u32);
+
+        let closed = self.close_handle(&write_handle, destination);
+        let _ = self.close_handle(&read_handle, source);
+        copied.and(closed)
+    }
+
+    fn open_file(
+        &mut self,
+        path: &str,
+        flags: u32,
+        permissions: Option<u32>,
+    ) -> Result<Vec<u8>, String> {
+        let mut body = Vec::new();
+        put_string(&mut body, path.as_bytes());
+        put_u32(&mut body, flags);
+        match permissions {
+            Some(permissions) => {
+                put_u32(&mut body, SSH_FILEXFER_ATTR_PERMISSIONS);
+                put_u32(&mut body, permissions);
+            }
+            None => put_u32(&mut body, 0),
+        }
+        let (kind, body) = self.request(SSH_FXP_OPEN, &body)?;
+        if kind == SSH_FXP_HANDLE {
+            return PacketReader { data: &body }.string().map(|handle| handle.to_vec());
+        }
+        status_result(kind, &body, path)?;
+        Err(format!("The server returned no handle for {}", path))
+    }
+
+    fn close_handle(&mut self, handle: &[u8], path: &str) -> Result<(), String> {
+        let mut body = Vec::new();
+        put_string(&mut body, handle);
+        let (kind, body) = self.request(SSH_FXP_CLOSE, &body)?;
+        status_result(kind, &body, path)
+    }
+
+    /// Sends a request and waits for its response, requests are not pipelined.
+    fn request(&mut self, kind: u8, body: &[u8]) -> Result<(u8, Vec<u8>), String> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let mut payload = Vec::with_capacity(body.len() + 4);
+        put_u32(&mut payload, id);
+        payload.extend_from_slice(body);
+        self.send(kind, &payload)?;
+
+        let (kind, payload) = self.receive()?;
+        let mut reader = PacketReader { data: &payload };
+        if reader.u32()? != id {
+            return Err("Unexpected SFTP response id".to_string());
+        }
+        Ok((kind, reader.data.to_vec()))
+    }
+
+    fn send(&mut self, kind: u8, payload: &[u8]) -> Result<(), String> {
+        let mut packet = Vec::with_capacity(payload.len() + 5);
+        put_u32(&mut packet, payload.len() as u32 + 1);
+        packet.push(kind);
+        packet.extend_from_slice(payload);
+        self.channel
+            .write_all(&packet)
+            .and_then(|_| self.channel.flush())
+            .map_err(|e| e.to_string())
+    }
+
+    fn receive(&mut self) -> Result<(u8, Vec<u8>), String> {
+        let mut length = [0u8; 4];
+        self.channel
+            .read_exact(&mut length)
+            .map_err(|e| e.to_string())?;
+        let length = u32::from_be_bytes(length) as usize;
+        if length == 0 || length > MAX_PACKET_SIZE {
+            return Err(format!("Invalid SFTP packet length {}", length));
+        }
+        let mut packet = vec![0u8; length];
+        self.channel
+            .read_exact(&mut packet)
+            .map_err(|e| e.to_string())?;
+        Ok((packet[0], packet[1..].to_vec()))
+    }
+}
+
+impl Drop for SftpCopyDataChannel {
+    fn drop(&mut self) {
+        let _ = self.channel.close();
+    }
+}
+
+/// A pooled connection, identified by the id handed to the frontend. The connection is
+/// opened again whenever it dropped, so a session id stays valid until it is closed.
+pub struct SftpSession {
+    config: SftpConnectionConfig,
+    auth: Arc<SftpAuthContext>,
+    connection: Mutex<Option<SftpConnection>>,
+    last_used: Mutex<Instant>,
+}
+
+impl SftpSession {
+    pub fn new(
+        config: SftpConnectionConfig,
+        auth: Arc<SftpAuthContext>,
+        connection: SftpConnection,
+    ) -> Self {
+        Self {
+            config,
+            auth,
+            connection: Mutex::new(Some(connection)),
+            last_used: Mutex::new(Instant::now()),
+        }
+    }
+
+    pub fn config(&self) -> &SftpConnectionConfig {
+        &self.config
+    }
+
+    /// Whether the session currently has an open connection.
+    pub fn is_connected(&self) -> bool {
+        self.connection
+            .lock()
+            .map(|connection| connection.is_some())
+            .unwrap_or(false)
+    }
+
+    /// Runs an operation on the SFTP channel of this session. The connection is reopened if it
+    /// was lost, and an operation failing because the connection dropped is retried once on a
+    /// new connection. Operations of the same session run one after another.
+    pub fn with_sftp<T>(
+        &self,
+        operation: impl Fn(&Sftp) -> Result<T, String>,
+    ) -> Result<T, String> {
+        self.with_connection(|connection| operation(&connection.sftp))
+    }
+
+    /// Runs a command on the server and returns its standard output. Returns `None` if the
+    /// command fails or the server does not allow executing commands, e.g. SFTP-only accounts.
+    pub fn exec(&self, command: &str) -> Result<Option<String>, String> {
+        self.with_connection(|connection| {
+            let Ok(mut channel) = connection.session.channel_session() else {
+                return Ok(None);
+            };
+            if channel.exec(command).is_err() {
+                return Ok(None);
+            }
+            let mut output = String::new();
+            connection.session.set_timeout(EXEC_TIMEOUT.as_millis() as u32);
+            let read = channel.read_to_string(&mut output);
+            let _ = channel.wait_close();
+            connection.session.set_timeout(CONNECTION_TIMEOUT.as_millis() as u32);
+            match (read, channel.exit_status()) {
+                (Ok(_), Ok(0)) => Ok(Some(output)),
+                _ => Ok(None),
+            }
+        })
+    }
+
+    /// Runs an operation which needs the SSH session besides the SFTP channel, e.g. to open
+    /// further channels. Reconnects like [`SftpSession::with_sftp`].
+    pub fn with_ssh<T>(
+        &self,
+        operation: impl Fn(&Session, &Sftp) -> Result<T, String>,
+    ) -> Result<T, String> {
+        self.with_connection(|connection| operation(&connection.session, &connection.sftp))
+    }
+
+    fn with_connection<T>(
+        &self,
+        operation: impl Fn(&SftpConnection) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let mut connection = self
+            .connection
+            .lock()
+            .map_err(|_| {
+                Error::new(
+                    ErrorCode::InternalError,
+                    "Failed to acquire lock on SFTP session".to_string(),
+                )
+                .to_json()
+            })?;
+        if let Ok(mut last_used) = self.last_used.lock() {
+            *last_used = Instant::now();
+        }
+
+        if connection.is_none() {
+            *connection = Some(self.reconnect()?);
+        }
+        let result = operation(connection.as_ref().unwrap());
+        if result.is_ok() || connection.as_ref().is_some_and(|c| c.is_alive()) {
+            return result;
+        }
+
+        // The connection is gone, e.g. the network changed or the server restarted
+        *connection = None;
+        let reconnected = connection.insert(self.reconnect()?);
+        operation(reconnected)
+    }
+
+    fn reconnect(&self) -> Result<SftpConnection, String> {
+        SftpConnection::open(&self.config, &self.auth).map_err(|e| e.to_error().to_json())
+    }
+
+    /// Sends a keepalive if the session was idle and drops the connection if it is dead, so the
+    /// next operation reconnects right away instead of waiting for a timeout. Busy sessions are
+    /// skipped, their requests keep the connection alive.
+    pub fn keepalive(&self) {
+        let idle = self
+            .last_used
+            .lock()
+            .map(|last_used| last_used.elapsed() >= KEEPALIVE_INTERVAL)
+            .unwrap_or(false);
+        if !idle {
+            return;
+        }
+        let Ok(mut connection) = self.connection.try_lock() else {
+            return;
+        };
+        if let Some(open) = connection.as_ref() {
+            if open.session.keepalive_send().is_err() {
+                *connection = None;
+            }
+        }
+    }
+
+    /// Closes the connection, the session can't be used afterwards.
+    pub fn disconnect(&self) {
+        if let Ok(mut connection) = self.connection.lock() {
+            if let Some(open) = connection.take() {
+                let _ = open.session.disconnect(None, "Session closed", None);
+            }
+        }
+    }
+}
+
+/// The open SFTP sessions. Commands look a session up by its id and release the lock on this
+/// state before talking to the server, so a slow server doesn't block the other sessions.
+pub struct SftpSessionState {
+    sessions: HashMap<u64, Arc<SftpSession>>,
+    auth: Arc<SftpAuthContext>,
+}
+
+impl SftpSessionState {
+    pub fn new() -> Self {
+        Self::with_auth(SftpAuthContext::new(
+            SftpAuthContext::default_known_hosts_path(),
+        ))
+    }
+
+    pub fn with_auth(auth: SftpAuthContext) -> Self {
+        Self {
+            sessions: HashMap::new(),
+            auth: Arc::new(auth),
+        }
+    }
+
+    /// The known_hosts file and pending prompts shared by all connections.
+    pub fn auth(&self) -> Arc<SftpAuthContext> {
+        self.auth.clone()
+    }
+
+    /// Adds a connected session and returns its id, see [`crate::remote::next_session_id`].
+    pub fn insert(&mut self, session: SftpSession) -> u64 {
+        let id = crate::remote::next_session_id();
+        self.sessions.insert(id, Arc::new(session));
+        id
+    }
+
+    pub fn get(&self, id: u64) -> Option<Arc<SftpSession>> {
+        self.sessions.get(&id).cloned()
+    }
+
+    pub fn remove(&mut self, id: u64) -> Option<Arc<SftpSession>> {
+        self.sessions.remove(&id)
+    }
+
+    /// All sessions ordered by id.
+    pub fn sessions(&self) -> Vec<(u64, Arc<SftpSession>)> {
+        let mut sessions: Vec<_> = self
+            .sessions
+            .iter()
+            .map(|(id, session)| (*id, session.clone()))
+            .collect();
+        sessions.sort_by_key(|(id, _)| *id);
+        sessions
+    }
+
+    /// Starts a thread sending keepalives on idle sessions. It ends when the state is dropped.
+    pub fn start_keepalive(state: &Arc<Mutex<Self>>) {
+        let state: Weak<Mutex<Self>> = Arc::downgrade(state);
+        let _ = thread::Builder::new()
+            .name("sftp-keepalive-thread".to_string())
+            .spawn(move || loop {
+                thread::sleep(KEEPALIVE_INTERVAL);
+                let Some(state) = state.upgrade() else {
+                    break;
+                };
+                let sessions = match state.lock() {
+                    Ok(state) => state.sessions(),
+                    Err(_) => break,
+                };
+                drop(state);
+                for (_, session) in sessions {
+                    session.keepalive();
+                }
+            });
+    }
+}
+
+impl Default for SftpSessionState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests_sftp_session_data {
+    use super::*;
+    use std::net::TcpListener;
+
+    fn config(port: u16) -> SftpConnectionConfig {
+        SftpConnectionConfig {
+            host: "127.0.0.1".to_string(),
+            port,
+            username: "explorer".to_string(),
+            credentials: SftpCredentials::Password {
+                password: "secret".to_string(),
+            },
+            proxy_jump: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn copy_data_packets_test() {
+        let mut version = Vec::new();
+        put_u32(&mut version, 3);
+        put_string(&mut version, b"posix-rename@openssh.com");
+        put_string(&mut version, b"1");
+        put_string(&mut version, b"copy-data");
+        put_string(&mut version, b"1");
+        assert_eq!(
+            version_extensions(&version).unwrap(),
+            vec!["posix-rename@openssh.com", "copy-data"]
+        );
+        assert!(version_extensions(&version[..10]).is_err());
+
+        let mut ok = Vec::new();
+        put_u32(&mut ok, 0);
+        assert!(status_result(SSH_FXP_STATUS, &ok, "/srv/a").is_ok());
+
+        let mut denied = Vec::new();
+        put_u32(&mut denied, 3);
+        put_string(&mut denied, b"Permission denied");
+        put_string(&mut denied, b"");
+        assert_eq!(
+            status_result(SSH_FXP_STATUS, &denied, "/srv/a").unwrap_err(),
+            "/srv/a: Permission denied"
+        );
+        assert!(status_result(SSH_FXP_HANDLE, &ok, "/srv/a").is_err());
+    }
+
+    #[test]
+    fn credentials_are_not_logged_test() {
+        let debug = format!("{:?}", config(22));
+        assert!(!debug.contains("secret"));
+        assert!(debug.contains("Password(***)"));
+    }
+
+    #[test]
+    fn credentials_from_json_test() {
+        let credentials: SftpCredentials = serde_json::from_value(json!({
+            "method": "PublicKey",
+            "private_key_path": "/home/alice/.ssh/id_ed25519",
+            "passphrase": "hunter2"
+        }))
+        .unwrap();
+        assert_eq!(
+            format!("{:?}", credentials),
+            "PublicKey(/home/alice/.ssh/id_ed25519)"
+        );
+        let credentials: SftpCredentials =
+            serde_json::from_value(json!({ "method": "Agent" })).unwrap();
+        assert!(matches!(credentials, SftpCredentials::Agent));
+    }
+
+    #[test]
+    fn expand_home_test() {
+        let home = home::home_dir().unwrap();
+        assert_eq!(
+            expand_home(Path::new("~/.ssh/id_ed25519")),
+            home.join(".ssh/id_ed25519")
+        );
+        assert_eq!(
+            expand_home(Path::new("/etc/ssh/key")),
+            PathBuf::from("/etc/ssh/key")
+        );
+        assert_eq!(
+            expand_home(Path::new("~alice/key")),
+            PathBuf::from("~alice/key")
+        );
+    }
+
+    #[test]
+    fn jump_hosts_try_agent_first_test() {
+        let mut jump = SshJumpHost {
+            host: "bastion.example.com".to_string(),
+            port: 22,
+            username: "jump".to_string(),
+            identity_file: None,
+        };
+        assert_eq!(format!("{:?}", jump_credentials(&jump)), "[Agent]");
+        jump.identity_file = Some(PathBuf::from("/keys/bastion"));
+        assert_eq!(
+            format!("{:?}", jump_credentials(&jump)),
+            "[Agent, PublicKey(/keys/bastion)]"
+        );
+    }
+
+    #[test]
+    fn unknown_host_key_error_has_details_test() {
+        let host_key = HostKeyInfo {
+            host: "example.com".to_string(),
+            port: 22,
+            key_type: "ssh-ed25519".to_string(),
+            fingerprint: "SHA256:abc".to_string(),
+            key: vec![1, 2, 3],
+        };
+        let error: serde_json::Value = serde_json::from_str(
+            &SftpConnectError::UnknownHostKey(host_key)
+                .to_error()
+                .to_json(),
+        )
+        .unwrap();
+        assert_eq!(error["code"], 401);
+        assert_eq!(error["details"]["reason"], "UnknownHostKey");
+        assert_eq!(error["details"]["host_key"]["fingerprint"], "SHA256:abc");
+        assert!(error["details"]["host_key"].get("key").is_none());
+    }
+
+    #[test]
+    fn open_fails_without_server_test() {
+        // Bind and drop a listener to get a port nothing listens on
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let auth = SftpAuthContext::new(None);
+        assert!(matches!(
+            SftpConnection::open(&config(port), &auth),
+            Err(SftpConnectError::Connection(_))
+        ));
+
+        // The first jump host is connected instead of the target
+        let mut through_jump_host = config(22);
+        through_jump_host.proxy_jump = vec![SshJumpHost {
+            host: "127.0.0.1".to_string(),
+            port,
+            username: "jump".to_string(),
+            identity_file: None,
+        }];
+        assert!(matches!(
+            SftpConnection::open(&through_jump_host, &auth),
+            Err(SftpConnectError::Connection(_))
+        ));
+
+        let state = SftpSessionState::new();
+        assert!(state.get(1).is_none());
+        assert!(state.sessions().is_empty());
+    }
+}
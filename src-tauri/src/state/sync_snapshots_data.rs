@@ -0,0 +1,112 @@
+use crate::constants;
+use crate::log_error;
+use digest::Digest;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// An entry as it was on both sides after a synchronization.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct SnapshotEntry {
+    pub is_directory: bool,
+    pub size: u64,
+    pub modified: u64, // as milliseconds since epoch
+    /// SHA256 of the file, only kept when the pair is compared by hash.
+    pub hash: Option<String>,
+}
+
+/// The entries of a pair of directories after its last successful two-way synchronization,
+/// by relative path with `/` separators.
+pub type SyncSnapshot = BTreeMap<String, SnapshotEntry>;
+
+/// Snapshots of the two-way synchronizations, one JSON file per pair of directories in the
+/// `sync_snapshots` folder of the config directory. They tell which side changed a file since
+/// the last run.
+pub struct SyncSnapshotsState {
+    directory: PathBuf,
+}
+
+impl SyncSnapshotsState {
+    pub fn new() -> Self {
+        Self::new_with_path(constants::SYNC_SNAPSHOTS_ABS_PATH.to_path_buf())
+    }
+
+    pub fn new_with_path(directory: PathBuf) -> Self {
+        Self { directory }
+    }
+
+    /// The file of a pair, named after the SHA256 of both canonical paths.
+    fn file_of(&self, left: &str, right: &str) -> PathBuf {
+        let canonical = |path: &str| fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+        let mut hasher = Sha256::new();
+        hasher.update(canonical(left).to_string_lossy().as_bytes());
+        hasher.update([0]);
+        hasher.update(canonical(right).to_string_lossy().as_bytes());
+        self.directory.join(format!("{:x}.json", hasher.finalize()))
+    }
+
+    /// Returns the snapshot of the last run, `None` if the pair was never synchronized or its
+    /// file can't be read.
+    pub fn load(&self, left: &str, right: &str) -> Option<SyncSnapshot> {
+        let path = self.file_of(left, right);
+        let content = fs::read_to_string(&path).ok()?;
+        serde_json::from_str(&content)
+            .map_err(|e| log_error!("Failed to parse {}: {}", path.display(), e))
+            .ok()
+    }
+
+    /// Replaces the snapshot of a pair.
+    pub fn save(&self, left: &str, right: &str, snapshot: &SyncSnapshot) -> Result<(), String> {
+        fs::create_dir_all(&self.directory).map_err(|e| e.to_string())?;
+        let serialized = serde_json::to_string(snapshot).map_err(|e| e.to_string())?;
+        write_atomically(&self.file_of(left, right), serialized.as_bytes())
+    }
+}
+
+/// Writes over a temporary file, so a crash never leaves half a snapshot behind.
+fn write_atomically(path: &Path, content: &[u8]) -> Result<(), String> {
+    let temp_path = path.with_extension("json.tmp");
+    fs::write(&temp_path, content)
+        .and_then(|_| fs::rename(&temp_path, path))
+        .map_err(|e| {
+            let _ = fs::remove_file(&temp_path);
+            e.to_string()
+        })
+}
+
+impl Default for SyncSnapshotsState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests_sync_snapshots_data {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn snapshots_are_kept_per_pair_test() {
+        let dir = tempdir().unwrap();
+        let state = SyncSnapshotsState::new_with_path(dir.path().join("snapshots"));
+        assert_eq!(state.load("/a", "/b"), None);
+
+        let mut snapshot = SyncSnapshot::new();
+        snapshot.insert(
+            "notes.txt".to_string(),
+            SnapshotEntry {
+                is_directory: false,
+                size: 4,
+                modified: 1_700_000_000_000,
+                hash: None,
+            },
+        );
+        state.save("/a", "/b", &snapshot).unwrap();
+
+        assert_eq!(state.load("/a", "/b"), Some(snapshot));
+        assert_eq!(state.load("/b", "/a"), None);
+        assert_eq!(state.load("/a", "/c"), None);
+    }
+}
@@ -1,7 +1,8 @@
 
-import React, { useState } from 'react';
+import React, { useEffect, useState } from 'react';
 import Modal from '../common/Modal';
 import Button from '../common/Button';
+import { listSshConfigHosts, openSftpSession } from '../../utils/sftpSession';
 
 /**
  * AddSftpConnectionView - Modal for adding a new SFTP connection
@@ -16,24 +17,53 @@ const AddSftpConnectionView = ({ isOpen, onClose, onAdd }) => {
 	const [port, setPort] = useState('22');
 	const [username, setUsername] = useState('');
 	const [password, setPassword] = useState('');
+	const [authMethod, setAuthMethod] = useState('Password');
+	const [privateKeyPath, setPrivateKeyPath] = useState('');
+	const [proxyJump, setProxyJump] = useState([]);
+	const [sshHosts, setSshHosts] = useState([]);
+	const [sshAlias, setSshAlias] = useState('');
 	const [testing, setTesting] = useState(false);
 	const [testResult, setTestResult] = useState(null);
 	const [error, setError] = useState(null);
 
+	useEffect(() => {
+		if (isOpen) listSshConfigHosts().then(setSshHosts);
+	}, [isOpen]);
+
+	// Fill the form with a host of ~/.ssh/config, including its jump hosts
+	const handleSshHostSelect = (alias) => {
+		setSshAlias(alias);
+		const sshHost = sshHosts.find(h => h.alias === alias);
+		if (!sshHost) {
+			setProxyJump([]);
+			return;
+		}
+		if (!name.trim()) setName(sshHost.alias);
+		setHost(sshHost.host_name);
+		setPort(String(sshHost.port));
+		setUsername(sshHost.username);
+		setProxyJump(sshHost.proxy_jump);
+		if (sshHost.identity_files.length > 0) {
+			setAuthMethod('PublicKey');
+			setPrivateKeyPath(sshHost.identity_files[0]);
+		} else {
+			setAuthMethod('Agent');
+		}
+	};
+
 	const handleTestConnection = async () => {
 		setTesting(true);
 		setTestResult(null);
 		setError(null);
 		try {
-			// Use Tauri invoke to test SFTP connection by calling load_dir on "."
+			// Test the SFTP connection by opening a session and listing "."
 			const { invoke } = await import('@tauri-apps/api/core');
-			await invoke('load_dir', {
-				host,
-				port: parseInt(port, 10),
-				username,
-				password,
-				directory: "."
-			});
+			const sessionId = await openSftpSession({ host, port, username, password, authMethod, privateKeyPath, passphrase: password, proxyJump });
+			try {
+				await invoke('load_dir', { sessionId, directory: "." });
+			} finally {
+				await invoke('close_sftp_session', { sessionId });
+			}
 			setTestResult('Connection successful!');
 		} catch (e) {
 			setTestResult(null);
@@ -45,12 +75,16 @@ const AddSftpConnectionView = ({ isOpen, onClose, onAdd }) => {
 
 	const handleAdd = () => {
 		if (!name.trim() || !host.trim() || !port.trim() || !username.trim()) return;
-		onAdd({ name, host, port, username, password });
+		onAdd({ name, host, port, username, password, authMethod, privateKeyPath, passphrase: authMethod === 'PublicKey' ? password : undefined, proxyJump });
 		setName('');
 		setHost('localhost');
 		setPort('22');
 		setUsername('');
 		setPassword('');
+		setAuthMethod('Password');
+		setPrivateKeyPath('');
+		setProxyJump([]);
+		setSshAlias('');
 		setTestResult(null);
 		setError(null);
 	};
@@ -61,6 +95,10 @@ const AddSftpConnectionView = ({ isOpen, onClose, onAdd }) => {
 		setPort('22');
 		setUsername('');
 		setPassword('');
+		setAuthMethod('Password');
+		setPrivateKeyPath('');
+		setProxyJump([]);
+		setSshAlias('');
 		setTestResult(null);
 		setError(null);
 		onClose();
@@ -93,6 +131,27 @@ const AddSftpConnectionView = ({ isOpen, onClose, onAdd }) => {
 			}
 		>
 			<form onSubmit={e => { e.preventDefault(); handleAdd(); }}>
+				{sshHosts.length > 0 && (
+					<div className="form-group">
+						<label htmlFor="sftp-ssh-config-host">From ~/.ssh/config</label>
+						<select
+							id="sftp-ssh-config-host"
+							className="input"
+							value={sshAlias}
+							onChange={e => handleSshHostSelect(e.target.value)}
+						>
+							<option value="">Enter manually</option>
+							{sshHosts.map(h => (
+								<option key={h.alias} value={h.alias}>{h.alias}</option>
+							))}
+						</select>
+						{proxyJump.length > 0 && (
+							<div className="input-hint">
+								Via {proxyJump.map(j => `${j.username}@${j.host}:${j.port}`).join(' → ')}
+							</div>
+						)}
+					</div>
+				)}
 				<div className="form-group">
 					<label htmlFor="sftp-name">Name</label>
 					<input
@@ -140,16 +199,45 @@ const AddSftpConnectionView = ({ isOpen, onClose, onAdd }) => {
 					/>
 				</div>
 				<div className="form-group">
-					<label htmlFor="sftp-password">Password</label>
-					<input
-						type="password"
-						id="sftp-password"
+					<label htmlFor="sftp-auth-method">Authentication</label>
+					<select
+						id="sftp-auth-method"
 						className="input"
-						value={password}
-						onChange={e => setPassword(e.target.value)}
-						placeholder="Password"
-					/>
+						value={authMethod}
+						onChange={e => setAuthMethod(e.target.value)}
+					>
+						<option value="Password">Password</option>
+						<option value="PublicKey">Private key file</option>
+						<option value="Agent">SSH agent</option>
+						<option value="KeyboardInteractive">Keyboard-interactive / 2FA</option>
+					</select>
 				</div>
+				{authMethod === 'PublicKey' && (
+					<div className="form-group">
+						<label htmlFor="sftp-private-key">Private key</label>
+						<input
+							type="text"
+							id="sftp-private-key"
+							className="input"
+							value={privateKeyPath}
+							onChange={e => setPrivateKeyPath(e.target.value)}
+							placeholder="~/.ssh/id_ed25519"
+						/>
+					</div>
+				)}
+				{(authMethod === 'Password' || authMethod === 'PublicKey') && (
+					<div className="form-group">
+						<label htmlFor="sftp-password">{authMethod === 'PublicKey' ? 'Passphrase' : 'Password'}</label>
+						<input
+							type="password"
+							id="sftp-password"
+							className="input"
+							value={password}
+							onChange={e => setPassword(e.target.value)}
+							placeholder={authMethod === 'PublicKey' ? 'Passphrase (optional)' : 'Password'}
+						/>
+					</div>
+				)}
 				{testResult && <div className="input-hint" style={{ color: 'var(--success)' }}>{testResult}</div>}
 				{error && <div className="input-hint" style={{ color: 'var(--danger)' }}>{error}</div>}
 			</form>
@@ -15,7 +15,7 @@ export function usePreview(getFocusedItem, navigateUp = null, navigateDown = nul
   const [open, setOpen] = useState(false);
   const [payload, setPayload] = useState(null);
   const [isLoading, setIsLoading] = useState(false);
-  const { isSftpPath, parseSftpPath } = useSftp();
+  const { isSftpPath, parseSftpPath, getSftpSession } = useSftp();
 
   /**
    * Opens preview for the specified path
@@ -34,10 +34,7 @@ export function usePreview(getFocusedItem, navigateUp = null, navigateDown = nul
         if (parsed && parsed.connection) {
           // Use SFTP preview command
           previewPayload = await invoke('build_preview_sftp', {
-            host: parsed.connection.host,
-            port: parseInt(parsed.connection.port, 10),
-            username: parsed.connection.username,
-            password: parsed.connection.password,
+            sessionId: await getSftpSession(parsed.connection),
             filePath: parsed.remotePath
           });
         } else {
@@ -61,7 +58,7 @@ export function usePreview(getFocusedItem, navigateUp = null, navigateDown = nul
     } finally {
       setIsLoading(false);
     }
-  }, [isLoading, isSftpPath, parseSftpPath]);
+  }, [isLoading, isSftpPath, parseSftpPath, getSftpSession]);
 
   /**
    * Closes the preview modal
@@ -1,6 +1,7 @@
 import React, { createContext, useContext, useState, useCallback, useRef } from 'react';
 import { invoke } from '@tauri-apps/api/core';
 import { showError, showSuccess } from '../utils/NotificationSystem';
+import { openSftpSession } from '../utils/sftpSession';
 
 const SftpContext = createContext({
     sftpConnections: [],
@@ -14,6 +15,7 @@ const SftpContext = createContext({
     copySftpItem: () => {},
     moveSftpItem: () => {},
     openSftpFile: () => {},
+    getSftpSession: () => {},
     downloadAndOpenSftpFile: () => {},
     isSftpPath: () => false,
     parseSftpPath: () => null,
@@ -51,6 +53,18 @@ export default function SftpProvider({ children }) {
         return () => window.removeEventListener('sftp-connections-updated', handler);
     }, [loadSftpConnections]);
 
+    // Open a backend session once per connection, all SFTP commands take its id instead of the credentials
+    const getSftpSession = useCallback(async (connection) => {
+        const cached = connectionCache.current.get(connection.name);
+        if (cached !== undefined) {
+            return cached;
+        }
+
+        const sessionId = await openSftpSession(connection);
+        connectionCache.current.set(connection.name, sessionId);
+        return sessionId;
+    }, []);
+
     // Check if a path is an SFTP path
     const isSftpPath = useCallback((path) => {
         return typeof path === 'string' && (path.startsWith('sftp://') || path.startsWith('sftp:'));
@@ -162,10 +176,7 @@ export default function SftpProvider({ children }) {
             
             // Load directory using existing SFTP command
             const result = await invoke('load_dir', {
-                host: connection.host,
-                port: parseInt(connection.port, 10),
-                username: connection.username,
-                password: connection.password,
+                sessionId: await getSftpSession(connection),
                 directory: sftpPath
             });
             
@@ -173,31 +184,23 @@ export default function SftpProvider({ children }) {
             const dirData = JSON.parse(result);
             console.log('Parsed SFTP directory data:', dirData);
             
-            // Transform data to match FileSystemProvider format
+            // The entries have the same shape as local ones, only their paths become SFTP URLs
+            const toSftpUrl = (remoteEntryPath) => createSftpUrl(
+                connection,
+                remoteEntryPath.startsWith('./') ? remoteEntryPath.substring(2) : remoteEntryPath
+            );
             const transformedData = {
                 directory: createSftpUrl(connection, remotePath),
-                directories: (dirData.directories || []).map(dirPath => {
-                    const name = dirPath.split('/').pop() || dirPath.replace(/^\.\//, '');
-                    const fullPath = dirPath.startsWith('./') ? dirPath.substring(2) : dirPath;
-                    return {
-                        name: name || 'Directory',
-                        path: createSftpUrl(connection, fullPath),
-                        isDirectory: true,
-                        size: 0,
-                        modified: null
-                    };
-                }),
-                files: (dirData.files || []).map(filePath => {
-                    const name = filePath.split('/').pop() || filePath.replace(/^\.\//, '');
-                    const fullPath = filePath.startsWith('./') ? filePath.substring(2) : filePath;
-                    return {
-                        name: name || 'File',
-                        path: createSftpUrl(connection, fullPath),
-                        isDirectory: false,
-                        size: 0,
-                        modified: null
-                    };
-                })
+                directories: (dirData.directories || []).map(dir => ({
+                    ...dir,
+                    path: toSftpUrl(dir.path),
+                    isDirectory: true
+                })),
+                files: (dirData.files || []).map(file => ({
+                    ...file,
+                    path: toSftpUrl(file.path),
+                    isDirectory: false
+                }))
             };
             
             console.log('Transformed SFTP data:', transformedData);
@@ -207,7 +210,7 @@ export default function SftpProvider({ children }) {
             showError(`Failed to connect to ${connection.name}: ${error.message || error}`);
             return null;
         }
-    }, [createSftpUrl]);
+    }, [createSftpUrl, getSftpSession]);
 
     // Load SFTP directory
     const loadSftpDirectory = useCallback(async (sftpPath) => {
@@ -240,10 +243,7 @@ export default function SftpProvider({ children }) {
         try {
             const filePath = `${parsed.remotePath}/${fileName}`.replace(/\/+/g, '/');
             await invoke('create_file_sftp', {
-                host: parsed.connection.host,
-                port: parseInt(parsed.connection.port, 10),
-                username: parsed.connection.username,
-                password: parsed.connection.password,
+                sessionId: await getSftpSession(parsed.connection),
                 filePath: filePath
             });
             
@@ -254,7 +254,7 @@ export default function SftpProvider({ children }) {
             showError(`Failed to create file: ${error.message || error}`);
             return false;
         }
-    }, [parseSftpPath]);
+    }, [parseSftpPath, getSftpSession]);
 
     const createSftpDirectory = useCallback(async (sftpPath, dirName) => {
         const parsed = parseSftpPath(sftpPath);
@@ -263,10 +263,7 @@ export default function SftpProvider({ children }) {
         try {
             const dirPath = `${parsed.remotePath}/${dirName}`.replace(/\/+/g, '/');
             await invoke('create_directory_sftp', {
-                host: parsed.connection.host,
-                port: parseInt(parsed.connection.port, 10),
-                username: parsed.connection.username,
-                password: parsed.connection.password,
+                sessionId: await getSftpSession(parsed.connection),
                 directoryPath: dirPath
             });
             
@@ -277,7 +274,7 @@ export default function SftpProvider({ children }) {
             showError(`Failed to create directory: ${error.message || error}`);
             return false;
         }
-    }, [parseSftpPath]);
+    }, [parseSftpPath, getSftpSession]);
 
     const deleteSftpItem = useCallback(async (sftpPath) => {
         const parsed = parseSftpPath(sftpPath);
@@ -288,10 +285,7 @@ export default function SftpProvider({ children }) {
             // For now, try both and handle errors
             try {
                 await invoke('delete_file_sftp', {
-                    host: parsed.connection.host,
-                    port: parseInt(parsed.connection.port, 10),
-                    username: parsed.connection.username,
-                    password: parsed.connection.password,
+                    sessionId: await getSftpSession(parsed.connection),
                     filePath: parsed.remotePath
                 });
                 showSuccess(`File deleted successfully`);
@@ -299,10 +293,7 @@ export default function SftpProvider({ children }) {
             } catch (fileError) {
                 // Try as directory if file deletion failed
                 await invoke('delete_directory_sftp', {
-                    host: parsed.connection.host,
-                    port: parseInt(parsed.connection.port, 10),
-                    username: parsed.connection.username,
-                    password: parsed.connection.password,
+                    sessionId: await getSftpSession(parsed.connection),
                     directoryPath: parsed.remotePath
                 });
                 showSuccess(`Directory deleted successfully`);
@@ -313,7 +304,7 @@ export default function SftpProvider({ children }) {
             showError(`Failed to delete item: ${error.message || error}`);
             return false;
         }
-    }, [parseSftpPath]);
+    }, [parseSftpPath, getSftpSession]);
 
     const renameSftpItem = useCallback(async (sftpPath, newName) => {
         const parsed = parseSftpPath(sftpPath);
@@ -327,19 +318,13 @@ export default function SftpProvider({ children }) {
             // Try both file and directory rename
             try {
                 await invoke('rename_file_sftp', {
-                    host: parsed.connection.host,
-                    port: parseInt(parsed.connection.port, 10),
-                    username: parsed.connection.username,
-                    password: parsed.connection.password,
+                    sessionId: await getSftpSession(parsed.connection),
                     oldPath: parsed.remotePath,
                     newPath: newPath
                 });
             } catch (fileError) {
                 await invoke('rename_directory_sftp', {
-                    host: parsed.connection.host,
-                    port: parseInt(parsed.connection.port, 10),
-                    username: parsed.connection.username,
-                    password: parsed.connection.password,
+                    sessionId: await getSftpSession(parsed.connection),
                     oldPath: parsed.remotePath,
                     newPath: newPath
                 });
@@ -352,7 +337,7 @@ export default function SftpProvider({ children }) {
             showError(`Failed to rename item: ${error.message || error}`);
             return false;
         }
-    }, [parseSftpPath]);
+    }, [parseSftpPath, getSftpSession]);
 
     const copySftpItem = useCallback(async (sftpPath, targetPath) => {
         const sourceParsed = parseSftpPath(sftpPath);
@@ -373,19 +358,13 @@ export default function SftpProvider({ children }) {
             // Try both file and directory copy
             try {
                 await invoke('copy_file_sftp', {
-                    host: sourceParsed.connection.host,
-                    port: parseInt(sourceParsed.connection.port, 10),
-                    username: sourceParsed.connection.username,
-                    password: sourceParsed.connection.password,
+                    sessionId: await getSftpSession(sourceParsed.connection),
                     sourcePath: sourceParsed.remotePath,
                     destinationPath: targetParsed.remotePath
                 });
             } catch (fileError) {
                 await invoke('copy_directory_sftp', {
-                    host: sourceParsed.connection.host,
-                    port: parseInt(sourceParsed.connection.port, 10),
-                    username: sourceParsed.connection.username,
-                    password: sourceParsed.connection.password,
+                    sessionId: await getSftpSession(sourceParsed.connection),
                     sourcePath: sourceParsed.remotePath,
                     destinationPath: targetParsed.remotePath
                 });
@@ -398,7 +377,7 @@ export default function SftpProvider({ children }) {
             showError(`Failed to copy item: ${error.message || error}`);
             return false;
         }
-    }, [parseSftpPath]);
+    }, [parseSftpPath, getSftpSession]);
 
     const moveSftpItem = useCallback(async (sftpPath, targetPath) => {
         const sourceParsed = parseSftpPath(sftpPath);
@@ -419,19 +398,13 @@ export default function SftpProvider({ children }) {
             // Try both file and directory move
             try {
                 await invoke('move_file_sftp', {
-                    host: sourceParsed.connection.host,
-                    port: parseInt(sourceParsed.connection.port, 10),
-                    username: sourceParsed.connection.username,
-                    password: sourceParsed.connection.password,
+                    sessionId: await getSftpSession(sourceParsed.connection),
                     sourcePath: sourceParsed.remotePath,
                     destinationPath: targetParsed.remotePath
                 });
             } catch (fileError) {
                 await invoke('move_directory_sftp', {
-                    host: sourceParsed.connection.host,
-                    port: parseInt(sourceParsed.connection.port, 10),
-                    username: sourceParsed.connection.username,
-                    password: sourceParsed.connection.password,
+                    sessionId: await getSftpSession(sourceParsed.connection),
                     sourcePath: sourceParsed.remotePath,
                     destinationPath: targetParsed.remotePath
                 });
@@ -444,7 +417,7 @@ export default function SftpProvider({ children }) {
             showError(`Failed to move item: ${error.message || error}`);
             return false;
         }
-    }, [parseSftpPath]);
+    }, [parseSftpPath, getSftpSession]);
 
     const openSftpFile = useCallback(async (sftpPath) => {
         const parsed = parseSftpPath(sftpPath);
@@ -452,10 +425,7 @@ export default function SftpProvider({ children }) {
 
         try {
             const content = await invoke('open_file_sftp', {
-                host: parsed.connection.host,
-                port: parseInt(parsed.connection.port, 10),
-                username: parsed.connection.username,
-                password: parsed.connection.password,
+                sessionId: await getSftpSession(parsed.connection),
                 filePath: parsed.remotePath
             });
             
@@ -465,7 +435,7 @@ export default function SftpProvider({ children }) {
             showError(`Failed to open file: ${error.message || error}`);
             return null;
         }
-    }, [parseSftpPath]);
+    }, [parseSftpPath, getSftpSession]);
 
     const downloadAndOpenSftpFile = useCallback(async (sftpPath, openFile = true) => {
         const parsed = parseSftpPath(sftpPath);
@@ -476,10 +446,7 @@ export default function SftpProvider({ children }) {
 
         try {
             const result = await invoke('download_and_open_sftp_file', {
-                host: parsed.connection.host,
-                port: parseInt(parsed.connection.port, 10),
-                username: parsed.connection.username,
-                password: parsed.connection.password,
+                sessionId: await getSftpSession(parsed.connection),
                 filePath: parsed.remotePath,
                 openFile: openFile
             });
@@ -496,11 +463,14 @@ export default function SftpProvider({ children }) {
             showError(`Failed to download file: ${error.message || error}`);
             return null;
         }
-    }, [parseSftpPath]);
+    }, [parseSftpPath, getSftpSession]);
 
     const disconnectSftp = useCallback(() => {
         setCurrentSftpConnection(null);
         setCurrentSftpPath(null);
+        for (const sessionId of connectionCache.current.values()) {
+            invoke('close_sftp_session', { sessionId }).catch(err => console.error('Failed to close SFTP session:', err));
+        }
         connectionCache.current.clear();
     }, []);
 
@@ -516,6 +486,7 @@ export default function SftpProvider({ children }) {
         copySftpItem,
         moveSftpItem,
         openSftpFile,
+        getSftpSession,
         downloadAndOpenSftpFile,
         isSftpPath,
         parseSftpPath,
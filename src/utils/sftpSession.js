@@ -0,0 +1,126 @@
+import { invoke } from '@tauri-apps/api/core';
+
+/**
+ * Build the `auth` argument of `open_sftp_session` from a saved connection.
+ * Connections saved before other methods existed only have a password.
+ * @param {Object} connection - The saved SFTP connection.
+ * @returns {Object} The authentication method with its secrets.
+ */
+export const buildSftpAuth = (connection) => {
+    switch (connection.authMethod) {
+        case 'PublicKey':
+            return {
+                method: 'PublicKey',
+                private_key_path: connection.privateKeyPath,
+                passphrase: connection.passphrase || null
+            };
+        case 'Agent':
+            return { method: 'Agent' };
+        case 'KeyboardInteractive':
+            return { method: 'KeyboardInteractive' };
+        default:
+            return { method: 'Password', password: connection.password || '' };
+    }
+};
+
+const parseError = (error) => {
+    try {
+        return typeof error === 'string' ? JSON.parse(error) : error;
+    } catch {
+        return null;
+    }
+};
+
+/**
+ * Answer the keyboard-interactive challenges of the backend while a login is running.
+ * @returns {Function} Stops polling.
+ */
+const relayAuthPrompts = () => {
+    const answered = new Set();
+    const timer = setInterval(async () => {
+        let prompts;
+        try {
+            prompts = await invoke('get_sftp_auth_prompts');
+        } catch (err) {
+            console.error('Failed to load SFTP authentication prompts:', err);
+            return;
+        }
+        for (const prompt of prompts) {
+            if (answered.has(prompt.id)) continue;
+            answered.add(prompt.id);
+
+            const responses = [];
+            for (const field of prompt.fields) {
+                const header = prompt.instructions ? `${prompt.instructions}\n\n` : '';
+                const response = window.prompt(`${prompt.username}@${prompt.host}\n${header}${field.text}`);
+                if (response === null) break;
+                responses.push(response);
+            }
+            const cancelled = responses.length < prompt.fields.length;
+            invoke('answer_sftp_auth_prompt', {
+                promptId: prompt.id,
+                responses: cancelled ? null : responses
+            }).catch(err => console.error('Failed to answer SFTP authentication prompt:', err));
+        }
+    }, 500);
+    return () => clearInterval(timer);
+};
+
+/**
+ * Open a backend SFTP session for a connection. Unknown host keys are shown to the user and
+ * trusted on confirmation, keyboard-interactive challenges are asked while the login runs.
+ * With jump hosts every hop can have an unknown key, so this asks once per hop.
+ * @param {Object} connection - The SFTP connection with host, port, username and auth fields.
+ * @returns {Promise<number>} The session id.
+ */
+export const openSftpSession = async (connection) => {
+    const proxyJump = connection.proxyJump?.length ? connection.proxyJump : null;
+    const args = {
+        host: connection.host,
+        port: parseInt(connection.port, 10),
+        username: connection.username,
+        auth: buildSftpAuth(connection),
+        proxyJump
+    };
+
+    const stopRelay = relayAuthPrompts();
+    try {
+        for (let hop = 0; ; hop++) {
+            try {
+                return await invoke('open_sftp_session', args);
+            } catch (error) {
+                const details = parseError(error)?.details;
+                if (details?.reason !== 'UnknownHostKey' || hop > (proxyJump?.length || 0)) throw error;
+
+                const hostKey = details.host_key;
+                const trusted = window.confirm(
+                    `The authenticity of host '${hostKey.host}' can't be established.\n` +
+                    `${hostKey.key_type} key fingerprint is ${hostKey.fingerprint}.\n\n` +
+                    'Are you sure you want to continue connecting?'
+                );
+                if (!trusted) throw error;
+
+                await invoke('trust_sftp_host_key', {
+                    host: hostKey.host,
+                    port: hostKey.port,
+                    fingerprint: hostKey.fingerprint
+                });
+            }
+        }
+    } finally {
+        stopRelay();
+    }
+};
+
+/**
+ * Load the hosts of ~/.ssh/config as connection suggestions.
+ * @returns {Promise<Array>} The resolved hosts, empty if the config can't be read.
+ */
+export const listSshConfigHosts = async () => {
+    try {
+        return await invoke('list_ssh_config_hosts');
+    } catch (err) {
+        console.error('Failed to read ~/.ssh/config:', err);
+        return [];
+    }
+};